@@ -0,0 +1,101 @@
+//! Benchmarks for the mixing, CSPRNG, statistics and CPU RNG hot paths.
+//!
+//! ```text
+//! cargo bench                                    # run and report
+//! cargo bench -- --save-baseline main            # store results as "main"
+//! cargo bench -- --baseline main                 # compare against "main"
+//! cargo bench -- --baseline main mix_entropy
+//! ```
+//!
+//! Baselines are kept by criterion under `target/criterion/`.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use mixrand::entropy::cpurng;
+use mixrand::error::Error;
+use mixrand::{csprng, mixer, stats};
+
+fn bench_mix_entropy(c: &mut Criterion) {
+    let small = [0x5Au8; 32];
+    let large = vec![0xA5u8; 4096];
+    let mut group = c.benchmark_group("mix_entropy");
+    group.throughput(Throughput::Bytes(32));
+    group.bench_function("32B", |b| {
+        b.iter(|| mixer::mix_entropy(&[("bench", black_box(&small))]))
+    });
+    group.throughput(Throughput::Bytes(4096));
+    group.bench_function("4KiB", |b| {
+        b.iter(|| mixer::mix_entropy(&[("bench", black_box(&large))]))
+    });
+    group.throughput(Throughput::Bytes(320));
+    group.bench_function("10x32B", |b| {
+        b.iter(|| {
+            let inputs: Vec<(&str, &[u8])> = (0..10).map(|_| ("bench", &small[..])).collect();
+            mixer::mix_entropy(black_box(&inputs))
+        })
+    });
+    group.finish();
+}
+
+fn bench_csprng(c: &mut Criterion) {
+    let mut group = c.benchmark_group("csprng::generate");
+    for (name, len) in [("64B", 64), ("64KiB", 65536)] {
+        group.throughput(Throughput::Bytes(len as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| csprng::generate(black_box([7u8; 32]), len))
+        });
+    }
+    group.finish();
+}
+
+fn bench_fips_suite(c: &mut Criterion) {
+    let window: [u8; 2500] = csprng::generate([9u8; 32], 2500).try_into().unwrap();
+    let mut group = c.benchmark_group("stats::fips_suite");
+    group.throughput(Throughput::Bytes(2500));
+    group.bench_function("2500B", |b| {
+        b.iter(|| stats::fips_suite(black_box(&window)))
+    });
+    group.finish();
+}
+
+/// Benchmarks a collector, skipping it if the first call fails (the CPU
+/// lacks the instruction).
+fn bench_collector(
+    c: &mut Criterion,
+    group: &str,
+    bytes: usize,
+    f: impl Fn() -> Result<Vec<u8>, Error>,
+) {
+    let name = format!("{}/{}B", group, bytes);
+    if let Err(e) = f() {
+        println!("{:<40} skipped: {}", name, e);
+        return;
+    }
+    let mut g = c.benchmark_group(group);
+    g.throughput(Throughput::Bytes(bytes as u64));
+    g.bench_function(format!("{}B", bytes), |b| b.iter(|| black_box(f())));
+    g.finish();
+}
+
+fn bench_cpurng(c: &mut Criterion) {
+    bench_collector(c, "cpurng::collect_rdseed", 64, || {
+        cpurng::collect_rdseed(64, 10)
+    });
+    bench_collector(c, "cpurng::collect_rdrand", 64, || {
+        cpurng::collect_rdrand(64, 10)
+    });
+    bench_collector(c, "cpurng::collect_xstore", 64, || {
+        cpurng::collect_xstore(64, 3)
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_mix_entropy,
+    bench_csprng,
+    bench_fips_suite,
+    bench_cpurng
+);
+criterion_main!(benches);
@@ -0,0 +1,40 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    compile_grpc();
+}
+
+/// Generates the `mixrand.v1.Entropy` service stubs without requiring protoc.
+/// Message types are hand-derived in `src/grpc.rs`; `proto/mixrand.proto`
+/// documents the same schema for non-Rust clients.
+#[cfg(feature = "grpc")]
+fn compile_grpc() {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    let codec = "tonic::codec::ProstCodec";
+    let service = Service::builder()
+        .name("Entropy")
+        .package("mixrand.v1")
+        .method(
+            Method::builder()
+                .name("get_entropy")
+                .route_name("GetEntropy")
+                .input_type("super::GetEntropyRequest")
+                .output_type("super::EntropyResponse")
+                .codec_path(codec)
+                .build(),
+        )
+        .method(
+            Method::builder()
+                .name("stream_entropy")
+                .route_name("StreamEntropy")
+                .input_type("super::StreamEntropyRequest")
+                .output_type("super::EntropyResponse")
+                .codec_path(codec)
+                .server_streaming()
+                .build(),
+        )
+        .build();
+
+    Builder::new().build_client(false).compile(&[service]);
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,41 @@
+/*
+ * mixrand entropy source plugin interface.
+ *
+ * Build a shared object that exports mixrand_plugin_v1() and install it in
+ * the [plugins] dir (default /usr/lib/mixrand/plugins), owned by root and
+ * not writable by group or others. mixrand loads plugins in file name
+ * order, calls init() once, collect() whenever the source is used, and
+ * teardown() at exit. Calls into one plugin are never concurrent.
+ *
+ * Raw collect() output is health-tested against min_entropy and mixed
+ * through BLAKE2b before use, so it need not be conditioned.
+ */
+#ifndef MIXRAND_PLUGIN_H
+#define MIXRAND_PLUGIN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define MIXRAND_PLUGIN_ABI 1
+
+struct mixrand_plugin {
+    /* Must be MIXRAND_PLUGIN_ABI. */
+    uint32_t abi_version;
+    /* Source name for --sources/--source: [A-Za-z0-9_-]{1,32}. */
+    const char *name;
+    /* One-line description shown by probe and check. */
+    const char *description;
+    /* Assessed min-entropy of collect() output, bits per byte, (0, 8]. */
+    double min_entropy;
+    /* Called once after loading; non-zero rejects the plugin. May be NULL. */
+    int (*init)(void);
+    /* Write up to len raw bytes to buf; return the count, 0 if none are
+     * available right now, or a negative value on error. */
+    long (*collect)(uint8_t *buf, size_t len);
+    /* Called once at exit. May be NULL. */
+    void (*teardown)(void);
+};
+
+const struct mixrand_plugin *mixrand_plugin_v1(void);
+
+#endif
@@ -0,0 +1,335 @@
+//! `--encrypt-to`: output sealed to age X25519 recipients
+//! (age-encryption.org/v1), so a secret generated for another custodian
+//! never reaches disk or a terminal in the clear. Output is ASCII-armored
+//! and decrypts with `age -d`.
+
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine;
+use bech32::primitives::decode::CheckedHrpstring;
+use bech32::Bech32;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::ChaCha20Poly1305;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand_chacha::ChaCha20Rng;
+use rand_core::{RngCore, SeedableRng};
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::config::Config;
+use crate::entropy::{self, cpurng};
+use crate::error::Error;
+use crate::mixer;
+
+const VERSION_LINE: &str = "age-encryption.org/v1";
+const X25519_INFO: &[u8] = b"age-encryption.org/v1/X25519";
+const CHUNK_BYTES: usize = 64 * 1024;
+const ARMOR_BEGIN: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+const ARMOR_END: &str = "-----END AGE ENCRYPTED FILE-----";
+
+/// An age X25519 recipient (`age1...`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recipient([u8; 32]);
+
+impl std::str::FromStr for Recipient {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let invalid =
+            |why: &str| Error::InvalidArgs(format!("invalid age recipient '{}': {}", s, why));
+        let parsed = CheckedHrpstring::new::<Bech32>(s).map_err(|e| invalid(&e.to_string()))?;
+        if parsed.hrp().to_lowercase() != "age" {
+            return Err(invalid("not an X25519 recipient (expected age1...)"));
+        }
+        let key: Vec<u8> = parsed.byte_iter().collect();
+        let key: [u8; 32] = key.try_into().map_err(|_| invalid("wrong key length"))?;
+        Ok(Recipient(key))
+    }
+}
+
+/// Recipients from `--encrypt-to` values: each is an `age1...` recipient
+/// or a recipients file (one per line, `#` comments), as `age -R` reads.
+pub fn load_recipients(specs: &[String]) -> Result<Vec<Recipient>, Error> {
+    let mut recipients = Vec::new();
+    for spec in specs {
+        if spec.starts_with("age1") {
+            recipients.push(spec.parse()?);
+            continue;
+        }
+        let contents = std::fs::read_to_string(spec).map_err(|e| {
+            Error::InvalidArgs(format!("failed to read recipients file {}: {}", spec, e))
+        })?;
+        let before = recipients.len();
+        for line in contents.lines().map(str::trim) {
+            if !line.is_empty() && !line.starts_with('#') {
+                recipients.push(line.parse()?);
+            }
+        }
+        if recipients.len() == before {
+            return Err(Error::InvalidArgs(format!("no recipients in {}", spec)));
+        }
+    }
+    Ok(recipients)
+}
+
+/// Keys and nonces for [`encrypt`], from the source chain like any other
+/// output.
+pub fn key_rng(config: &Config) -> Result<ChaCha20Rng, Error> {
+    let mut result = entropy::generate(32, config)?;
+    let rng = ChaCha20Rng::from_seed(mixer::mix_entropy(&[("age-keys", &result.bytes)]));
+    cpurng::zeroize_vec(&mut result.bytes);
+    Ok(rng)
+}
+
+/// `body` in unpadded base64, 64 columns a line, the last line shorter.
+fn wrap_body(body: &[u8]) -> String {
+    let encoded = STANDARD_NO_PAD.encode(body);
+    let mut out = String::new();
+    for line in encoded.as_bytes().chunks(64) {
+        out.push_str(std::str::from_utf8(line).unwrap());
+        out.push('\n');
+    }
+    if encoded.len().is_multiple_of(64) {
+        out.push('\n');
+    }
+    out
+}
+
+/// HKDF-SHA256 with 32 bytes of output, as every age key derivation uses.
+fn hkdf(salt: &[u8], ikm: &[u8], info: &[u8]) -> [u8; 32] {
+    let mut okm = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(info, &mut okm)
+        .expect("32 bytes is a valid HKDF-SHA256 length");
+    okm
+}
+
+/// ChaCha20-Poly1305 under `key` and `nonce`, with no associated data.
+fn seal(key: &[u8; 32], nonce: &[u8; 12], plaintext: &[u8]) -> Vec<u8> {
+    ChaCha20Poly1305::new(key.into())
+        .encrypt(nonce.into(), plaintext)
+        .expect("age chunks are far below the ChaCha20-Poly1305 limit")
+}
+
+/// The X25519 stanza wrapping `file_key` for `recipient`.
+fn x25519_stanza(
+    recipient: &Recipient,
+    file_key: &[u8; 16],
+    rng: &mut dyn RngCore,
+) -> Result<String, Error> {
+    let mut seed = [0u8; 32];
+    rng.fill_bytes(&mut seed);
+    let ephemeral = StaticSecret::from(seed);
+    cpurng::zeroize_bytes(&mut seed);
+    let share = PublicKey::from(&ephemeral);
+    let shared = ephemeral.diffie_hellman(&PublicKey::from(recipient.0));
+    if !shared.was_contributory() {
+        return Err(Error::InvalidArgs(
+            "age recipient is a low-order point".into(),
+        ));
+    }
+
+    let salt = [share.as_bytes().as_slice(), &recipient.0].concat();
+    let mut wrap_key = hkdf(&salt, shared.as_bytes(), X25519_INFO);
+    let body = seal(&wrap_key, &[0u8; 12], file_key);
+    cpurng::zeroize_bytes(&mut wrap_key);
+    Ok(format!(
+        "-> X25519 {}\n{}",
+        STANDARD_NO_PAD.encode(share.as_bytes()),
+        wrap_body(&body)
+    ))
+}
+
+/// Encrypts `plaintext` to every recipient, in the binary age format.
+pub fn encrypt(
+    recipients: &[Recipient],
+    plaintext: &[u8],
+    rng: &mut dyn RngCore,
+) -> Result<Vec<u8>, Error> {
+    let mut file_key = [0u8; 16];
+    rng.fill_bytes(&mut file_key);
+    let result = seal_with(recipients, plaintext, &file_key, rng);
+    cpurng::zeroize_bytes(&mut file_key);
+    result
+}
+
+fn seal_with(
+    recipients: &[Recipient],
+    plaintext: &[u8],
+    file_key: &[u8; 16],
+    rng: &mut dyn RngCore,
+) -> Result<Vec<u8>, Error> {
+    let mut header = format!("{}\n", VERSION_LINE);
+    for recipient in recipients {
+        header.push_str(&x25519_stanza(recipient, file_key, rng)?);
+    }
+    header.push_str("---");
+    let mut mac_key = hkdf(&[], file_key, b"header");
+    let mut mac =
+        <Hmac<Sha256> as Mac>::new_from_slice(&mac_key).expect("HMAC accepts any key length");
+    cpurng::zeroize_bytes(&mut mac_key);
+    mac.update(header.as_bytes());
+    let tag = mac.finalize().into_bytes();
+    header.push_str(&format!(" {}\n", STANDARD_NO_PAD.encode(tag)));
+
+    let mut nonce = [0u8; 16];
+    rng.fill_bytes(&mut nonce);
+    let mut payload_key = hkdf(&nonce, file_key, b"payload");
+    let mut out = header.into_bytes();
+    out.extend_from_slice(&nonce);
+
+    // STREAM: a big-endian chunk counter, then 1 on the last chunk
+    let chunks: Vec<&[u8]> = if plaintext.is_empty() {
+        vec![&[]]
+    } else {
+        plaintext.chunks(CHUNK_BYTES).collect()
+    };
+    for (i, chunk) in chunks.iter().enumerate() {
+        let mut chunk_nonce = [0u8; 12];
+        chunk_nonce[3..11].copy_from_slice(&(i as u64).to_be_bytes());
+        chunk_nonce[11] = (i + 1 == chunks.len()) as u8;
+        out.extend_from_slice(&seal(&payload_key, &chunk_nonce, chunk));
+    }
+    cpurng::zeroize_bytes(&mut payload_key);
+    Ok(out)
+}
+
+/// The ASCII armor `age -a` writes: padded base64 in 64-column lines.
+pub fn armor(ciphertext: &[u8]) -> String {
+    let encoded = STANDARD.encode(ciphertext);
+    let mut out = format!("{}\n", ARMOR_BEGIN);
+    for line in encoded.as_bytes().chunks(64) {
+        out.push_str(std::str::from_utf8(line).unwrap());
+        out.push('\n');
+    }
+    out.push_str(ARMOR_END);
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // X25519 public key of the secret 0x42 * 32
+    const RECIPIENT: &str = "age1zvkyg2lqzraa2lnjvqej32nkuu0ues2s82hzrye869xeexvn73equnujwj";
+
+    #[test]
+    fn test_recipient_parsing() {
+        assert!(RECIPIENT.parse::<Recipient>().is_ok());
+        let mut typo = RECIPIENT.to_string();
+        typo.replace_range(10..11, "q");
+        assert!(typo.parse::<Recipient>().is_err());
+        assert!("age1".parse::<Recipient>().is_err());
+        assert!(RECIPIENT
+            .replacen("age1", "agf1", 1)
+            .parse::<Recipient>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_chunking_and_header() {
+        let recipients = [RECIPIENT.parse::<Recipient>().unwrap()];
+        let mut rng = ChaCha20Rng::from_seed([7; 32]);
+        for len in [0, 1, CHUNK_BYTES, CHUNK_BYTES + 1] {
+            let out = encrypt(&recipients, &vec![0xa5; len], &mut rng).unwrap();
+            let text = String::from_utf8_lossy(&out);
+            assert!(text.starts_with("age-encryption.org/v1\n-> X25519 "));
+            let header_end = text.find("\n---").unwrap() + 4 + 1 + 43 + 1;
+            let chunks = len.div_ceil(CHUNK_BYTES).max(1);
+            assert_eq!(out.len(), header_end + 16 + len + 16 * chunks);
+        }
+    }
+
+    /// A fresh identity from the age crate and its recipient, parsed here.
+    fn identity() -> (::age::x25519::Identity, Recipient) {
+        let identity = ::age::x25519::Identity::generate();
+        let recipient = identity.to_public().to_string().parse().unwrap();
+        (identity, recipient)
+    }
+
+    #[test]
+    fn test_roundtrip_through_age_crate() {
+        let (identity, recipient) = identity();
+        let mut rng = ChaCha20Rng::from_seed([3; 32]);
+        for len in [0, 1, 31, CHUNK_BYTES, CHUNK_BYTES + 1, 3 * CHUNK_BYTES] {
+            let plaintext: Vec<u8> = (0..len).map(|i| (i * 7) as u8).collect();
+            let sealed = encrypt(std::slice::from_ref(&recipient), &plaintext, &mut rng).unwrap();
+            assert_eq!(::age::decrypt(&identity, &sealed).unwrap(), plaintext);
+            let armored = armor(&sealed);
+            assert_eq!(
+                ::age::decrypt(&identity, armored.as_bytes()).unwrap(),
+                plaintext
+            );
+        }
+    }
+
+    #[test]
+    fn test_every_recipient_can_decrypt() {
+        let (first, r1) = identity();
+        let (second, r2) = identity();
+        let (outsider, _) = identity();
+        let mut rng = ChaCha20Rng::from_seed([4; 32]);
+        let sealed = encrypt(&[r1, r2], b"custodian secret", &mut rng).unwrap();
+        assert_eq!(
+            ::age::decrypt(&first, &sealed).unwrap(),
+            b"custodian secret"
+        );
+        assert_eq!(
+            ::age::decrypt(&second, &sealed).unwrap(),
+            b"custodian secret"
+        );
+        assert!(::age::decrypt(&outsider, &sealed).is_err());
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let (identity, recipient) = identity();
+        let mut rng = ChaCha20Rng::from_seed([5; 32]);
+        let sealed = encrypt(&[recipient], b"custodian secret", &mut rng).unwrap();
+        // Last byte of the payload tag, then a byte of the header MAC
+        let header_end = sealed.windows(4).position(|w| w == b"\n---").unwrap();
+        for at in [sealed.len() - 1, header_end + 6] {
+            let mut tampered = sealed.clone();
+            tampered[at] ^= 1;
+            assert!(::age::decrypt(&identity, &tampered).is_err());
+        }
+    }
+
+    #[test]
+    fn test_decrypts_with_age_cli() {
+        use ::age::secrecy::ExposeSecret;
+        use std::process::Command;
+
+        // Only where the age or rage binary is installed
+        let Some(bin) = ["age", "rage"].into_iter().find(|b| {
+            Command::new(b)
+                .arg("--version")
+                .output()
+                .is_ok_and(|o| o.status.success())
+        }) else {
+            return;
+        };
+        let (identity, recipient) = identity();
+        let dir = std::env::temp_dir();
+        let key = dir.join(format!("mixrand_test_age_{}.key", std::process::id()));
+        let sealed = dir.join(format!("mixrand_test_age_{}.age", std::process::id()));
+        std::fs::write(&key, identity.to_string().expose_secret()).unwrap();
+        let mut rng = ChaCha20Rng::from_seed([6; 32]);
+        let plaintext = vec![0x5a; CHUNK_BYTES + 100];
+        let out = encrypt(&[recipient], &plaintext, &mut rng).unwrap();
+        std::fs::write(&sealed, armor(&out)).unwrap();
+
+        let decrypted = Command::new(bin)
+            .arg("-d")
+            .arg("-i")
+            .arg(&key)
+            .arg(&sealed)
+            .output()
+            .unwrap();
+        let _ = std::fs::remove_file(&key);
+        let _ = std::fs::remove_file(&sealed);
+        assert!(decrypted.status.success(), "{} -d failed", bin);
+        assert_eq!(decrypted.stdout, plaintext);
+    }
+}
@@ -0,0 +1,411 @@
+//! `[alerts]`: daemon failure hooks for paging systems. A hook POSTs the
+//! event as JSON or runs a script with it in `MIXRAND_*` variables.
+//!
+//! The daemon's sandbox rules out exec, so scripts are run by
+//! `mixrand alert-runner`, a helper started before the sandbox. It is given
+//! the configured scripts up front and only takes the index of one of them,
+//! so the daemon cannot make it run anything else.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cli::AlertRunnerArgs;
+use crate::config::AlertsConfig;
+use crate::error::Error;
+use crate::http::Endpoint;
+
+/// Pause between checks on whether a script has exited.
+const WAIT_POLL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    SourceFailure,
+    HealthFailure,
+    InjectionError,
+}
+
+impl EventKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            EventKind::SourceFailure => "source_failure",
+            EventKind::HealthFailure => "health_failure",
+            EventKind::InjectionError => "injection_error",
+        }
+    }
+}
+
+/// What a hook receives, as the JSON body or as environment variables.
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub event: EventKind,
+    /// The failing source, feed or injection target.
+    pub subject: String,
+    pub message: String,
+    /// Events of this kind held back by `min_interval_secs` since the
+    /// previous one was sent.
+    pub suppressed: u64,
+    pub hostname: String,
+    pub unix: u64,
+}
+
+impl Event {
+    fn env(&self) -> Vec<(String, String)> {
+        [
+            ("MIXRAND_EVENT", self.event.name().to_string()),
+            ("MIXRAND_SUBJECT", self.subject.clone()),
+            ("MIXRAND_MESSAGE", self.message.clone()),
+            ("MIXRAND_SUPPRESSED", self.suppressed.to_string()),
+            ("MIXRAND_HOSTNAME", self.hostname.clone()),
+            ("MIXRAND_UNIX", self.unix.to_string()),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect()
+    }
+}
+
+#[derive(Debug)]
+enum Hook {
+    Post(Endpoint),
+    /// Index into the alert runner's scripts.
+    Script(usize),
+}
+
+/// Parses one `on_*` entry, appending scripts to `scripts`.
+fn parse_hook(value: &str, scripts: &mut Vec<PathBuf>) -> Result<Option<Hook>, Error> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    if value.contains("://") {
+        return Ok(Some(Hook::Post(Endpoint::resolve(value)?)));
+    }
+    let path = Path::new(value);
+    if !path.is_absolute() {
+        return Err(Error::InvalidArgs(format!(
+            "alert hook must be an http:// URL or an absolute script path: {}",
+            value
+        )));
+    }
+    scripts.push(path.to_path_buf());
+    Ok(Some(Hook::Script(scripts.len() - 1)))
+}
+
+/// Sends at most one event of each kind per `min_interval`, counting the
+/// rest.
+#[derive(Debug)]
+struct Limiter {
+    min_interval: Duration,
+    last_sent: HashMap<EventKind, Instant>,
+    suppressed: HashMap<EventKind, u64>,
+}
+
+impl Limiter {
+    fn new(min_interval: Duration) -> Self {
+        Self {
+            min_interval,
+            last_sent: HashMap::new(),
+            suppressed: HashMap::new(),
+        }
+    }
+
+    /// The number of events held back since the last one sent, if this one
+    /// should go out.
+    fn admit(&mut self, kind: EventKind, now: Instant) -> Option<u64> {
+        if let Some(&last) = self.last_sent.get(&kind) {
+            if now.duration_since(last) < self.min_interval {
+                *self.suppressed.entry(kind).or_default() += 1;
+                return None;
+            }
+        }
+        self.last_sent.insert(kind, now);
+        Some(self.suppressed.remove(&kind).unwrap_or(0))
+    }
+}
+
+/// One request to the alert runner.
+#[derive(Debug, Serialize, Deserialize)]
+struct RunRequest {
+    hook: usize,
+    env: Vec<(String, String)>,
+}
+
+/// The daemon's end of the alert runner.
+struct Runner {
+    child: Child,
+    requests: ChildStdin,
+    replies: BufReader<ChildStdout>,
+}
+
+impl Runner {
+    fn spawn(scripts: &[PathBuf], timeout_secs: u64) -> Result<Self, Error> {
+        let mut child = Command::new("/proc/self/exe")
+            .arg("alert-runner")
+            .arg("--timeout-secs")
+            .arg(timeout_secs.to_string())
+            .args(scripts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let requests = child.stdin.take().expect("stdin is piped");
+        let replies = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        Ok(Self {
+            child,
+            requests,
+            replies,
+        })
+    }
+
+    /// Runs script `hook` and returns the runner's one-line verdict.
+    fn run(&mut self, hook: usize, event: &Event) -> Result<String, Error> {
+        let request = RunRequest {
+            hook,
+            env: event.env(),
+        };
+        let line = serde_json::to_string(&request).map_err(io::Error::other)?;
+        writeln!(self.requests, "{}", line)?;
+        let mut reply = String::new();
+        if self.replies.read_line(&mut reply)? == 0 {
+            return Err(Error::Io(io::Error::other(format!(
+                "alert runner (pid {}) exited",
+                self.child.id()
+            ))));
+        }
+        Ok(reply.trim_end().to_string())
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Delivers events on a thread of its own, so a slow hook never holds up
+/// injection.
+fn dispatch(
+    hooks: HashMap<EventKind, Hook>,
+    mut runner: Option<Runner>,
+    timeout: Duration,
+) -> Result<Sender<Event>, Error> {
+    let (tx, rx) = mpsc::channel::<Event>();
+    thread::Builder::new()
+        .name("alerts".into())
+        .spawn(move || {
+            for event in rx {
+                let outcome = match (&hooks[&event.event], runner.as_mut()) {
+                    (Hook::Post(endpoint), _) => serde_json::to_string(&event)
+                        .map_err(|e| Error::Io(io::Error::other(e)))
+                        .and_then(|body| endpoint.post_json(&body, timeout))
+                        .map(|status| match status {
+                            200..=299 => "ok".to_string(),
+                            _ => format!("{} returned HTTP {}", endpoint.url(), status),
+                        }),
+                    (Hook::Script(i), Some(runner)) => runner.run(*i, &event),
+                    (Hook::Script(_), None) => unreachable!("scripts start the runner"),
+                };
+                match outcome {
+                    Ok(ref verdict) if verdict == "ok" => log::debug!(
+                        target: "mixrand::daemon",
+                        "{} alert sent", event.event.name(),
+                    ),
+                    Ok(verdict) => log::error!(
+                        target: "mixrand::daemon",
+                        "{} alert hook failed: {}", event.event.name(), verdict,
+                    ),
+                    Err(e) => log::error!(
+                        target: "mixrand::daemon",
+                        "{} alert hook failed: {}", event.event.name(), e,
+                    ),
+                }
+            }
+        })?;
+    Ok(tx)
+}
+
+/// The daemon's handle on its `[alerts]` hooks.
+pub struct Alerts {
+    events: Sender<Event>,
+    kinds: Vec<EventKind>,
+    network: bool,
+    limiter: Limiter,
+    hostname: String,
+}
+
+impl Alerts {
+    /// Resolves the hooks' URLs, starts the alert runner if any hook is a
+    /// script, and starts delivery. `None` if no hook is configured. Must
+    /// run before the sandbox is installed.
+    pub fn start(config: &AlertsConfig) -> Result<Option<Self>, Error> {
+        let mut scripts = Vec::new();
+        let mut hooks = HashMap::new();
+        for (kind, value) in [
+            (EventKind::SourceFailure, &config.on_source_failure),
+            (EventKind::HealthFailure, &config.on_health_failure),
+            (EventKind::InjectionError, &config.on_injection_error),
+        ] {
+            if let Some(hook) = parse_hook(value, &mut scripts)? {
+                hooks.insert(kind, hook);
+            }
+        }
+        if hooks.is_empty() {
+            return Ok(None);
+        }
+        let runner = if scripts.is_empty() {
+            None
+        } else {
+            Some(Runner::spawn(&scripts, config.timeout_secs)?)
+        };
+        let kinds = hooks.keys().copied().collect();
+        let network = hooks.values().any(|h| matches!(h, Hook::Post(_)));
+        let events = dispatch(hooks, runner, Duration::from_secs(config.timeout_secs))?;
+        let hostname = fs::read_to_string("/proc/sys/kernel/hostname")
+            .map(|h| h.trim().to_string())
+            .unwrap_or_default();
+        Ok(Some(Self {
+            events,
+            kinds,
+            network,
+            limiter: Limiter::new(Duration::from_secs(config.min_interval_secs)),
+            hostname,
+        }))
+    }
+
+    /// Whether a hook POSTs, so the sandbox has to allow client sockets.
+    pub fn needs_network(&self) -> bool {
+        self.network
+    }
+
+    /// Queues `kind` for its hook, if one is configured and the event isn't
+    /// held back by `min_interval_secs`.
+    pub fn fire(&mut self, kind: EventKind, subject: &str, message: impl fmt::Display) {
+        if !self.kinds.contains(&kind) {
+            return;
+        }
+        let Some(suppressed) = self.limiter.admit(kind, Instant::now()) else {
+            return;
+        };
+        let _ = self.events.send(Event {
+            event: kind,
+            subject: subject.to_string(),
+            message: message.to_string(),
+            suppressed,
+            hostname: self.hostname.clone(),
+            unix: unix_now(),
+        });
+    }
+}
+
+/// Runs `script` with `env`, killing it after `timeout`; the one-line
+/// verdict the daemon logs.
+fn run_script(script: &Path, env: Vec<(String, String)>, timeout: Duration) -> String {
+    let mut child = match Command::new(script)
+        .envs(env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => return format!("cannot run {}: {}", script.display(), e),
+    };
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return "ok".into(),
+            Ok(Some(status)) => return format!("{} exited with {}", script.display(), status),
+            Ok(None) if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return format!(
+                    "{} timed out after {}s",
+                    script.display(),
+                    timeout.as_secs()
+                );
+            }
+            Ok(None) => thread::sleep(WAIT_POLL),
+            Err(e) => return format!("cannot wait for {}: {}", script.display(), e),
+        }
+    }
+}
+
+/// `mixrand alert-runner`: runs the daemon's alert scripts on request, one
+/// JSON request per line on stdin and one verdict per line on stdout, until
+/// stdin closes.
+pub fn run_runner(args: &AlertRunnerArgs) -> Result<(), Error> {
+    let timeout = Duration::from_secs(args.timeout_secs);
+    let mut out = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let verdict = match serde_json::from_str::<RunRequest>(&line) {
+            Ok(request) => match args.scripts.get(request.hook) {
+                Some(script) => run_script(script, request.env, timeout),
+                None => format!("no alert script #{}", request.hook),
+            },
+            Err(e) => format!("malformed request: {}", e),
+        };
+        writeln!(out, "{}", verdict.replace('\n', " "))?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hook() {
+        let mut scripts = Vec::new();
+        assert!(parse_hook("", &mut scripts).unwrap().is_none());
+        assert!(matches!(
+            parse_hook("http://127.0.0.1:9000/alert", &mut scripts).unwrap(),
+            Some(Hook::Post(_))
+        ));
+        assert!(parse_hook("https://example.org/", &mut scripts).is_err());
+        assert!(parse_hook("page.sh", &mut scripts).is_err());
+        assert!(matches!(
+            parse_hook("/usr/local/bin/page", &mut scripts).unwrap(),
+            Some(Hook::Script(0))
+        ));
+        assert_eq!(scripts, [PathBuf::from("/usr/local/bin/page")]);
+    }
+
+    #[test]
+    fn test_limiter_counts_held_back_events() {
+        let mut limiter = Limiter::new(Duration::from_secs(60));
+        let t0 = Instant::now();
+        let kind = EventKind::InjectionError;
+        assert_eq!(limiter.admit(kind, t0), Some(0));
+        assert_eq!(limiter.admit(kind, t0 + Duration::from_secs(10)), None);
+        assert_eq!(limiter.admit(kind, t0 + Duration::from_secs(20)), None);
+        assert_eq!(
+            limiter.admit(EventKind::SourceFailure, t0 + Duration::from_secs(20)),
+            Some(0)
+        );
+        assert_eq!(limiter.admit(kind, t0 + Duration::from_secs(61)), Some(2));
+    }
+
+    #[test]
+    fn test_run_script() {
+        let env = vec![("MIXRAND_EVENT".to_string(), "source_failure".to_string())];
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            run_script(Path::new("/bin/true"), env.clone(), timeout),
+            "ok"
+        );
+        assert!(run_script(Path::new("/bin/false"), env, timeout).contains("exited with"));
+        assert!(
+            run_script(Path::new("/nonexistent"), Vec::new(), timeout).starts_with("cannot run")
+        );
+    }
+}
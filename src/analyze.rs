@@ -0,0 +1,238 @@
+//! `analyze`: FIPS 140-2 tests and entropy estimates over a file or stdin.
+//! Input is read in chunks through the streaming calculators in
+//! [`crate::stats`], so multi-GB files and live pipes are never buffered.
+
+use std::fs::File;
+use std::io::{self, Read};
+
+use serde::Serialize;
+
+use crate::check;
+use crate::cli::AnalyzeArgs;
+use crate::environment::Environment;
+use crate::error::Error;
+use crate::signals::{self, Signal};
+use crate::stats::{self, EntropyEstimates, EntropyStream, FipsTally, FipsWindows};
+use crate::table::{self, Units};
+
+const CHUNK: usize = 64 * 1024;
+
+/// Feeds chunks of a stream to the FIPS tests and entropy estimates.
+pub struct Analyzer {
+    windows: FipsWindows,
+    fips: FipsTally,
+    entropy: EntropyStream,
+    max_lag: usize,
+}
+
+/// Results for everything fed to an [`Analyzer`].
+pub struct Analysis {
+    pub bytes: u64,
+    pub fips: FipsTally,
+    /// Trailing bytes too few for a FIPS window.
+    pub untested: usize,
+    pub estimates: EntropyEstimates,
+    /// Lag with the largest |r| over 1..=`max_lag`.
+    pub max_autocorrelation: Option<(usize, f64)>,
+    pub max_lag: usize,
+}
+
+impl Analyzer {
+    pub fn new(max_lag: usize) -> Self {
+        let max_lag = max_lag.max(1);
+        Self {
+            windows: FipsWindows::new(),
+            fips: FipsTally::default(),
+            entropy: EntropyStream::new(max_lag),
+            max_lag,
+        }
+    }
+
+    pub fn update(&mut self, chunk: &[u8]) {
+        let fips = &mut self.fips;
+        self.windows
+            .push(chunk, |window| fips.record(&stats::fips_suite(window)));
+        self.entropy.update(chunk);
+    }
+
+    pub fn finish(&self) -> Analysis {
+        let spectrum = self.entropy.autocorrelation_spectrum(1..=self.max_lag);
+        Analysis {
+            bytes: self.entropy.len(),
+            fips: self.fips,
+            untested: self.windows.pending(),
+            estimates: self.entropy.estimates(),
+            max_autocorrelation: stats::max_abs_autocorrelation(&spectrum, 1),
+            max_lag: self.max_lag,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonFips {
+    windows: u64,
+    monobit: u64,
+    poker: u64,
+    runs: u64,
+    long_runs: u64,
+    all: u64,
+    untested_bytes: usize,
+}
+
+#[derive(Serialize)]
+struct JsonAnalysis {
+    bytes: u64,
+    fips: JsonFips,
+    shannon: f64,
+    min_entropy: f64,
+    chi_square: f64,
+    chi_square_p: f64,
+    mean: f64,
+    serial_correlation: f64,
+    compression_ratio: f64,
+    max_autocorrelation_lag: Option<usize>,
+    max_autocorrelation: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    environment: Option<Environment>,
+}
+
+fn print_json(a: &Analysis, environment: Option<Environment>) -> Result<(), Error> {
+    let e = &a.estimates;
+    let report = JsonAnalysis {
+        bytes: a.bytes,
+        fips: JsonFips {
+            windows: a.fips.windows,
+            monobit: a.fips.monobit,
+            poker: a.fips.poker,
+            runs: a.fips.runs,
+            long_runs: a.fips.long_runs,
+            all: a.fips.all,
+            untested_bytes: a.untested,
+        },
+        shannon: e.shannon,
+        min_entropy: e.min_entropy,
+        chi_square: e.chi_square,
+        chi_square_p: stats::chi_square_p_value(e.chi_square, 255.0),
+        mean: e.mean,
+        serial_correlation: e.serial_correlation,
+        compression_ratio: e.compression_ratio,
+        max_autocorrelation_lag: a.max_autocorrelation.map(|(lag, _)| lag),
+        max_autocorrelation: a.max_autocorrelation.map(|(_, r)| r),
+        environment,
+    };
+    let json = serde_json::to_string_pretty(&report).map_err(|e| Error::Io(io::Error::other(e)))?;
+    println!("{}", json);
+    Ok(())
+}
+
+fn print_report(a: &Analysis, name: &str, units: Units) {
+    println!("--- {} ({}) ---", name, table::bytes(a.bytes, units));
+    if a.fips.windows > 0 {
+        println!(
+            "  FIPS 140-2:  Monobit {}  Poker {}  Runs {}  Long Runs {}",
+            check::fips_pass_ci(&a.fips, a.fips.monobit),
+            check::fips_pass_ci(&a.fips, a.fips.poker),
+            check::fips_pass_ci(&a.fips, a.fips.runs),
+            check::fips_pass_ci(&a.fips, a.fips.long_runs)
+        );
+        println!(
+            "               All {} over {} windows, 95% CI",
+            check::fips_pass_ci(&a.fips, a.fips.all),
+            a.fips.windows
+        );
+    }
+    if a.untested > 0 {
+        println!(
+            "               last {} bytes too few for a FIPS window",
+            a.untested
+        );
+    }
+    let e = &a.estimates;
+    println!(
+        "  Entropy:     Shannon {}  Min-ent {}  Chi-sq {} (p={})",
+        table::decimal(e.shannon, 4),
+        table::decimal(e.min_entropy, 4),
+        table::decimal(e.chi_square, 1),
+        table::decimal(stats::chi_square_p_value(e.chi_square, 255.0), 2)
+    );
+    println!(
+        "               Mean {}     SerCorr {}  Compress {}",
+        table::decimal(e.mean, 2),
+        table::decimal(e.serial_correlation, 4),
+        table::decimal(e.compression_ratio, 3)
+    );
+    if let Some((lag, r)) = a.max_autocorrelation {
+        println!(
+            "               Max |AutoCorr| {} at lag {} (lags 1..={})",
+            table::decimal(r.abs(), 4),
+            lag,
+            a.max_lag
+        );
+    }
+}
+
+pub fn run(args: &AnalyzeArgs) -> Result<(), Error> {
+    let (mut input, name): (Box<dyn Read>, String) = match args.input {
+        Some(ref path) => (
+            Box::new(File::open(path).map_err(|e| {
+                Error::InvalidArgs(format!("cannot open {}: {}", path.display(), e))
+            })?),
+            path.display().to_string(),
+        ),
+        None => (Box::new(io::stdin().lock()), "stdin".into()),
+    };
+    // Ctrl-C ends a live stream and reports on what was read
+    signals::install(&[Signal::Term, Signal::Int]);
+
+    let mut analyzer = Analyzer::new(args.max_lag);
+    let mut buf = vec![0u8; CHUNK];
+    let mut remaining = args.bytes.unwrap_or(u64::MAX);
+    while remaining > 0 && !signals::shutdown_requested() {
+        let want = remaining.min(CHUNK as u64) as usize;
+        let n = match input.read(&mut buf[..want]) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        analyzer.update(&buf[..n]);
+        remaining -= n as u64;
+    }
+
+    let analysis = analyzer.finish();
+    if analysis.bytes == 0 {
+        return Err(Error::InvalidArgs(format!("{} is empty", name)));
+    }
+    let environment = args.env.then(Environment::collect);
+    if args.json {
+        return print_json(&analysis, environment);
+    }
+    print_report(&analysis, &name, args.units.unwrap_or_default());
+    if let Some(env) = environment {
+        println!();
+        env.print();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyzer() {
+        let data = crate::csprng::generate([8u8; 32], 3 * stats::FIPS_WINDOW_BYTES + 7);
+        let mut analyzer = Analyzer::new(4);
+        for chunk in data.chunks(1000) {
+            analyzer.update(chunk);
+        }
+        let a = analyzer.finish();
+        assert_eq!((a.bytes, a.fips.windows, a.untested), (7507, 3, 7));
+        assert_eq!(a.fips.all, 3);
+        let whole = stats::entropy_estimates(&data);
+        assert!((a.estimates.shannon - whole.shannon).abs() < 1e-9);
+        assert!(a
+            .max_autocorrelation
+            .is_some_and(|(lag, _)| (1..=4).contains(&lag)));
+    }
+}
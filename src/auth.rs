@@ -0,0 +1,279 @@
+//! `[auth]`: bearer tokens for `serve-grpc` and `serve-socket`, a byte quota
+//! per client, and an audit line for what each client drew. Only the
+//! SHA-256 digests of the tokens are kept once they are loaded.
+
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+
+use crate::config::{Config, TokenConfig};
+use crate::error::Error;
+
+/// Quota accounting window.
+pub const QUOTA_WINDOW: Duration = Duration::from_secs(60);
+/// Shortest token accepted, so that tokens can't be guessed.
+const MIN_TOKEN_LEN: usize = 16;
+/// Longest token accepted, the socket protocol's longest request string.
+const MAX_TOKEN_LEN: usize = 256;
+
+struct Token {
+    name: String,
+    digest: [u8; 32],
+    bytes_per_minute: u64,
+}
+
+/// Who drew the bytes, for the quota and the audit log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Client {
+    /// A token's name, `cert:` and the client certificate's fingerprint,
+    /// or the peer's address
+    pub id: String,
+    /// Bytes it may draw per [`QUOTA_WINDOW`]; 0 is unlimited.
+    pub quota: u64,
+}
+
+pub struct Auth {
+    tokens: Vec<Token>,
+    /// `[limits] client_bytes_per_minute`, which token quotas can't exceed
+    cap: u64,
+}
+
+fn digest(token: &str) -> [u8; 32] {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+/// Compares two digests without stopping at the first difference.
+fn same(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Parses `name token [bytes_per_minute]` lines; `#` starts a comment.
+fn parse_tokens_file(text: &str) -> Result<Vec<TokenConfig>, String> {
+    let mut tokens = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let bytes_per_minute = match fields[..] {
+            [] => continue,
+            [_, _] => 0,
+            [_, _, limit] => limit
+                .parse()
+                .map_err(|_| format!("line {}: invalid bytes_per_minute '{}'", i + 1, limit))?,
+            _ => {
+                return Err(format!(
+                    "line {}: expected 'name token [bytes_per_minute]'",
+                    i + 1
+                ))
+            }
+        };
+        tokens.push(TokenConfig {
+            name: fields[0].to_string(),
+            token: fields[1].to_string(),
+            bytes_per_minute,
+        });
+    }
+    Ok(tokens)
+}
+
+impl Auth {
+    /// Loads the tokens in `[auth]` and its `tokens_file`.
+    pub fn load(config: &Config) -> Result<Self, Error> {
+        let mut entries = config.auth.tokens.clone();
+        if let Some(path) = &config.auth.tokens_file {
+            let invalid = |e: String| Error::InvalidArgs(format!("{}: {}", path.display(), e));
+            let text = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+            let mode = fs::metadata(path)?.permissions().mode();
+            if mode & 0o077 != 0 {
+                log::warn!(
+                    "{} is accessible to other users (mode {:o})",
+                    path.display(),
+                    mode & 0o777
+                );
+            }
+            entries.extend(parse_tokens_file(&text).map_err(invalid)?);
+        }
+
+        let mut tokens: Vec<Token> = Vec::new();
+        for entry in entries {
+            if entry.name.is_empty() {
+                return Err(Error::InvalidArgs("[auth] token without a name".into()));
+            }
+            if !(MIN_TOKEN_LEN..=MAX_TOKEN_LEN).contains(&entry.token.len()) {
+                return Err(Error::InvalidArgs(format!(
+                    "[auth] token '{}' must be {} to {} characters",
+                    entry.name, MIN_TOKEN_LEN, MAX_TOKEN_LEN
+                )));
+            }
+            let digest = digest(&entry.token);
+            if tokens.iter().any(|t| t.name == entry.name || same(&t.digest, &digest)) {
+                return Err(Error::InvalidArgs(format!(
+                    "[auth] token '{}' is listed twice, or shares its name or token",
+                    entry.name
+                )));
+            }
+            tokens.push(Token {
+                name: entry.name,
+                digest,
+                bytes_per_minute: entry.bytes_per_minute,
+            });
+        }
+        Ok(Self {
+            tokens,
+            cap: config.limits.client_bytes_per_minute,
+        })
+    }
+
+    /// Whether requests need a token.
+    pub fn enabled(&self) -> bool {
+        !self.tokens.is_empty()
+    }
+
+    pub fn count(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// The client holding `token`, with its own quota or else
+    /// `default_quota`. `None` without any tokens configured, when every
+    /// request is allowed. Every token is compared, so the time taken
+    /// doesn't say which one came close.
+    pub fn check(&self, token: Option<&str>, default_quota: u64) -> Result<Option<Client>, String> {
+        if !self.enabled() {
+            return Ok(None);
+        }
+        let presented = digest(token.ok_or("a token is required")?);
+        let found = self
+            .tokens
+            .iter()
+            .fold(None, |found, t| found.or(same(&t.digest, &presented).then_some(t)));
+        let token = found.ok_or("invalid token")?;
+        let quota = match (token.bytes_per_minute, self.cap) {
+            (0, _) => default_quota,
+            (n, 0) => n,
+            (n, cap) => n.min(cap),
+        };
+        Ok(Some(Client {
+            id: token.name.clone(),
+            quota,
+        }))
+    }
+}
+
+/// Logs that `client`, connected from `peer`, drew `bytes` through `via`.
+pub fn audit(client: &Client, peer: &str, via: &str, bytes: u64) {
+    log::info!(
+        target: "mixrand::audit",
+        "{} ({}) drew {}B via {}",
+        client.id,
+        peer,
+        bytes,
+        via
+    );
+}
+
+/// Fixed-window byte quota per client.
+#[derive(Default)]
+pub struct Quota {
+    usage: Mutex<HashMap<String, (Instant, u64)>>,
+}
+
+impl Quota {
+    /// Charges `bytes` to `client`, returning false (and charging nothing)
+    /// if that would exceed its quota for the current window.
+    pub fn take(&self, client: &Client, bytes: u64, now: Instant) -> bool {
+        if client.quota == 0 {
+            return true;
+        }
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        usage.retain(|_, (start, _)| now.duration_since(*start) < QUOTA_WINDOW);
+        let entry = usage.entry(client.id.clone()).or_insert((now, 0));
+        if entry.1 + bytes > client.quota {
+            return false;
+        }
+        entry.1 += bytes;
+        true
+    }
+}
+
+/// The error for a request over `client`'s quota.
+pub fn exceeded(client: &Client) -> String {
+    format!(
+        "quota of {} bytes per {}s exceeded",
+        client.quota,
+        QUOTA_WINDOW.as_secs()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AuthConfig;
+
+    fn client(id: &str, quota: u64) -> Client {
+        Client {
+            id: id.into(),
+            quota,
+        }
+    }
+
+    #[test]
+    fn test_quota_enforced_per_client() {
+        let q = Quota::default();
+        let (a, b) = (client("10.0.0.1", 100), client("backup", 100));
+        let now = Instant::now();
+        assert!(q.take(&a, 60, now));
+        assert!(!q.take(&a, 60, now));
+        assert!(q.take(&b, 60, now));
+        // Window rolls over
+        assert!(q.take(&a, 60, now + QUOTA_WINDOW));
+    }
+
+    #[test]
+    fn test_quota_disabled() {
+        let q = Quota::default();
+        assert!(q.take(&client("10.0.0.1", 0), u64::MAX / 2, Instant::now()));
+    }
+
+    #[test]
+    fn test_check_tokens() {
+        let config = Config {
+            auth: AuthConfig {
+                tokens: parse_tokens_file(
+                    "# name token limit\nbackup 0123456789abcdef 4096\n\nci fedcba9876543210\n",
+                )
+                .unwrap(),
+                tokens_file: None,
+            },
+            ..Default::default()
+        };
+        let auth = Auth::load(&config).unwrap();
+        assert_eq!(auth.count(), 2);
+        assert_eq!(
+            auth.check(Some("0123456789abcdef"), 100),
+            Ok(Some(client("backup", 4096)))
+        );
+        assert_eq!(
+            auth.check(Some("fedcba9876543210"), 100),
+            Ok(Some(client("ci", 100)))
+        );
+        assert!(auth.check(Some("0123456789abcdeF"), 100).is_err());
+        assert!(auth.check(None, 100).is_err());
+
+        // Anyone may draw without tokens configured
+        let open = Auth::load(&Config::default()).unwrap();
+        assert_eq!(open.check(None, 100), Ok(None));
+
+        assert!(parse_tokens_file("backup\n").is_err());
+        let short = Config {
+            auth: AuthConfig {
+                tokens: parse_tokens_file("backup short\n").unwrap(),
+                tokens_file: None,
+            },
+            ..Default::default()
+        };
+        assert!(Auth::load(&short).is_err());
+    }
+}
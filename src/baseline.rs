@@ -0,0 +1,203 @@
+//! Saved `check` results (`--save-baseline`) and the comparison of a later
+//! run against them (`--baseline`), e.g. across kernel or microcode updates.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// Min-entropy or Shannon drops larger than this (bits/byte) are regressions.
+pub const ENTROPY_DRIFT: f64 = 0.05;
+/// FIPS pass-rate drops larger than this (percentage points) are regressions.
+pub const FIPS_DROP_PP: f64 = 2.0;
+
+/// One source's final results.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceResult {
+    pub name: String,
+    pub samples: u64,
+    /// Bytes per second.
+    pub throughput: f64,
+    pub shannon: f64,
+    pub min_entropy: f64,
+    pub chi_square: f64,
+    /// All-tests FIPS 140-2 pass rate, absent when FIPS was skipped.
+    pub fips_pass_pct: Option<f64>,
+}
+
+/// A saved run and the environment it ran in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Baseline {
+    pub created_unix: u64,
+    pub sample_size: usize,
+    pub kernel: String,
+    pub microcode: Option<String>,
+    pub sources: Vec<SourceResult>,
+}
+
+/// Change in one metric of one source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delta {
+    pub source: String,
+    pub metric: &'static str,
+    pub old: f64,
+    pub new: f64,
+    pub regressed: bool,
+}
+
+impl Delta {
+    /// Percent change for throughput, absolute change otherwise.
+    pub fn change(&self) -> f64 {
+        if self.metric == "throughput" {
+            if self.old == 0.0 {
+                return 0.0;
+            }
+            (self.new - self.old) / self.old * 100.0
+        } else {
+            self.new - self.old
+        }
+    }
+}
+
+/// Running kernel release, e.g. `6.8.0-45-generic`.
+pub fn kernel_release() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/osrelease")
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Microcode revision of the first CPU in `/proc/cpuinfo`, if reported.
+pub fn microcode_revision() -> Option<String> {
+    let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").ok()?;
+    cpuinfo
+        .lines()
+        .find(|l| l.starts_with("microcode"))
+        .and_then(|l| l.split_once(':'))
+        .map(|(_, v)| v.trim().to_string())
+}
+
+pub fn save(path: &Path, baseline: &Baseline) -> Result<(), Error> {
+    let json =
+        serde_json::to_vec_pretty(baseline).map_err(|e| Error::Io(std::io::Error::other(e)))?;
+    std::fs::write(path, json)?;
+    Ok(())
+}
+
+pub fn load(path: &Path) -> Result<Baseline, Error> {
+    let json = std::fs::read(path).map_err(|e| {
+        Error::InvalidArgs(format!("cannot read baseline {}: {}", path.display(), e))
+    })?;
+    serde_json::from_slice(&json)
+        .map_err(|e| Error::InvalidArgs(format!("invalid baseline {}: {}", path.display(), e)))
+}
+
+/// Compares every source present in both runs. Throughput regresses when it
+/// falls by more than `throughput_pct` percent.
+pub fn compare(current: &[SourceResult], baseline: &Baseline, throughput_pct: f64) -> Vec<Delta> {
+    let mut deltas = Vec::new();
+    for cur in current {
+        let Some(old) = baseline.sources.iter().find(|s| s.name == cur.name) else {
+            continue;
+        };
+        let mut push = |metric, old: f64, new: f64, regressed| {
+            deltas.push(Delta {
+                source: cur.name.clone(),
+                metric,
+                old,
+                new,
+                regressed,
+            });
+        };
+        let slowdown = old.throughput > 0.0
+            && (old.throughput - cur.throughput) / old.throughput * 100.0 > throughput_pct;
+        push("throughput", old.throughput, cur.throughput, slowdown);
+        push(
+            "min-entropy",
+            old.min_entropy,
+            cur.min_entropy,
+            old.min_entropy - cur.min_entropy > ENTROPY_DRIFT,
+        );
+        push(
+            "shannon",
+            old.shannon,
+            cur.shannon,
+            old.shannon - cur.shannon > ENTROPY_DRIFT,
+        );
+        if let (Some(o), Some(n)) = (old.fips_pass_pct, cur.fips_pass_pct) {
+            push("fips-pass", o, n, o - n > FIPS_DROP_PP);
+        }
+    }
+    deltas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(name: &str, throughput: f64, min_entropy: f64, fips: Option<f64>) -> SourceResult {
+        SourceResult {
+            name: name.into(),
+            samples: 10,
+            throughput,
+            shannon: 7.99,
+            min_entropy,
+            chi_square: 255.0,
+            fips_pass_pct: fips,
+        }
+    }
+
+    #[test]
+    fn test_compare_flags_regressions() {
+        let baseline = Baseline {
+            created_unix: 0,
+            sample_size: 4096,
+            kernel: "6.1.0".into(),
+            microcode: None,
+            sources: vec![
+                result("rdseed", 1000.0, 7.9, Some(100.0)),
+                result("havege", 500.0, 7.9, None),
+            ],
+        };
+        let current = [
+            result("rdseed", 700.0, 7.88, Some(95.0)),
+            result("havege", 480.0, 7.5, None),
+            result("urandom", 9000.0, 7.9, None),
+        ];
+        let deltas = compare(&current, &baseline, 20.0);
+        let regressed: Vec<(&str, &str)> = deltas
+            .iter()
+            .filter(|d| d.regressed)
+            .map(|d| (d.source.as_str(), d.metric))
+            .collect();
+        assert_eq!(
+            regressed,
+            [
+                ("rdseed", "throughput"),
+                ("rdseed", "fips-pass"),
+                ("havege", "min-entropy")
+            ]
+        );
+        assert!(deltas.iter().all(|d| d.source != "urandom"));
+        assert_eq!(deltas[0].change(), -30.0);
+    }
+
+    #[test]
+    fn test_baseline_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("mixrand-baseline-{}.json", std::process::id()));
+        let baseline = Baseline {
+            created_unix: 1_700_000_000,
+            sample_size: 4096,
+            kernel: kernel_release(),
+            microcode: Some("0xf0".into()),
+            sources: vec![result("rdseed", 1000.0, 7.9, Some(99.5))],
+        };
+        save(&path, &baseline).unwrap();
+        let loaded = load(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(loaded.sources[0].fips_pass_pct, Some(99.5));
+        assert_eq!(loaded.microcode.as_deref(), Some("0xf0"));
+        assert!(load(Path::new("/nonexistent/baseline.json")).is_err());
+    }
+}
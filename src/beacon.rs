@@ -0,0 +1,322 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use blake2::{Blake2b512, Digest};
+use ed25519_dalek::pkcs8::DecodePrivateKey;
+use ed25519_dalek::{Signer, SigningKey};
+use serde::{Deserialize, Serialize};
+
+use crate::cli::BeaconArgs;
+use crate::config::Config;
+use crate::entropy;
+use crate::error::Error;
+use crate::http;
+use crate::output::to_hex;
+use crate::signals::{self, Signal};
+
+const BEACON_VERSION: u32 = 1;
+const DOMAIN: &[u8] = b"mixrand-beacon-v1";
+const POST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// One published beacon value. Pulses form a hash chain: each carries the
+/// BLAKE2b-512 hash of its predecessor (all zeros for the first pulse).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pulse {
+    pub version: u32,
+    pub index: u64,
+    pub timestamp: u64,
+    pub interval: u64,
+    pub source: String,
+    pub output_value: String,
+    pub previous_hash: String,
+    pub public_key: String,
+    pub signature: String,
+}
+
+impl Pulse {
+    /// Canonical, length-prefixed encoding of every field except the
+    /// signature. This is what gets signed.
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(512);
+        buf.extend_from_slice(DOMAIN);
+        buf.extend_from_slice(&self.version.to_be_bytes());
+        buf.extend_from_slice(&self.index.to_be_bytes());
+        buf.extend_from_slice(&self.timestamp.to_be_bytes());
+        buf.extend_from_slice(&self.interval.to_be_bytes());
+        for field in [
+            &self.source,
+            &self.output_value,
+            &self.previous_hash,
+            &self.public_key,
+        ] {
+            buf.extend_from_slice(&(field.len() as u64).to_be_bytes());
+            buf.extend_from_slice(field.as_bytes());
+        }
+        buf
+    }
+
+    /// Chain hash: BLAKE2b-512 over the signed bytes and the signature.
+    fn hash(&self) -> [u8; 64] {
+        let mut h = Blake2b512::new();
+        h.update(self.signed_bytes());
+        h.update(self.signature.as_bytes());
+        h.finalize().into()
+    }
+
+    fn sign(&mut self, key: &SigningKey) {
+        self.signature = to_hex(&key.sign(&self.signed_bytes()).to_bytes());
+    }
+}
+
+fn load_key(path: &Path) -> Result<SigningKey, Error> {
+    let pem = std::fs::read_to_string(path)
+        .map_err(|e| Error::InvalidArgs(format!("cannot read {}: {}", path.display(), e)))?;
+    SigningKey::from_pkcs8_pem(&pem).map_err(|e| {
+        Error::InvalidArgs(format!(
+            "{} is not a PKCS#8 Ed25519 private key: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+/// Where a chain left off.
+#[derive(Debug, PartialEq)]
+struct ChainTip {
+    next_index: u64,
+    previous_hash: [u8; 64],
+    /// Timestamp of the last pulse; later pulses must be after it.
+    last_timestamp: Option<u64>,
+}
+
+impl ChainTip {
+    fn genesis() -> Self {
+        Self {
+            next_index: 0,
+            previous_hash: [0u8; 64],
+            last_timestamp: None,
+        }
+    }
+
+    /// Whether a pulse at `timestamp` may follow, i.e. the wall clock
+    /// hasn't stepped back to or behind the last pulse.
+    fn accepts(&self, timestamp: u64) -> bool {
+        self.last_timestamp.is_none_or(|last| timestamp > last)
+    }
+}
+
+/// Reads the last pulse in `path` (JSON lines) so a restarted beacon
+/// continues the same chain.
+fn resume_chain(path: &Path) -> Result<ChainTip, Error> {
+    let f = match File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(ChainTip::genesis()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut last = None;
+    for line in BufReader::new(f).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            last = Some(line);
+        }
+    }
+    match last {
+        Some(line) => {
+            let pulse: Pulse = serde_json::from_str(&line).map_err(|e| {
+                Error::InvalidArgs(format!("corrupt beacon chain in {}: {}", path.display(), e))
+            })?;
+            Ok(ChainTip {
+                next_index: pulse.index + 1,
+                previous_hash: pulse.hash(),
+                last_timestamp: Some(pulse.timestamp),
+            })
+        }
+        None => Ok(ChainTip::genesis()),
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Publishes `pulse` to the chain file and/or HTTP endpoint. A failed file
+/// append is fatal (it would fork the chain); a failed POST is only logged.
+fn publish(pulse: &Pulse, args: &BeaconArgs) -> Result<(), Error> {
+    let line = serde_json::to_string(pulse).map_err(|e| Error::Io(std::io::Error::other(e)))?;
+
+    if let Some(ref path) = args.output {
+        let mut f = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(f, "{}", line)?;
+        f.sync_data()?;
+    }
+
+    if let Some(ref url) = args.post_url {
+        match http::post_json(url, &line, POST_TIMEOUT) {
+            Ok(status) if (200..300).contains(&status) => {}
+            Ok(status) => log::error!("beacon POST to {} returned HTTP {}", url, status),
+            Err(e) => log::error!("beacon POST to {} failed: {}", url, e),
+        }
+    }
+    Ok(())
+}
+
+pub fn run(args: &BeaconArgs, config: &Config) -> Result<(), Error> {
+    if args.interval == 0 {
+        return Err(Error::InvalidArgs("interval must be greater than 0".into()));
+    }
+    if args.output.is_none() && args.post_url.is_none() {
+        return Err(Error::InvalidArgs(
+            "beacon needs --output and/or --post-url".into(),
+        ));
+    }
+    config.limits.check_request(args.bytes)?;
+
+    let key = load_key(&args.sign_key)?;
+    let public_key = to_hex(key.verifying_key().as_bytes());
+
+    let mut tip = match args.output {
+        Some(ref path) => resume_chain(path)?,
+        None => {
+            log::warn!("no --output chain file; chain restarts at index 0");
+            ChainTip::genesis()
+        }
+    };
+
+    signals::install(&[Signal::Term, Signal::Int]);
+
+    log::info!(
+        "beacon started: interval={}s bytes={} next index={} key={}",
+        args.interval,
+        args.bytes,
+        tip.next_index,
+        public_key
+    );
+
+    loop {
+        // Align pulses to wall-clock multiples of the interval
+        let now = unix_now();
+        let timestamp = (now / args.interval + 1) * args.interval;
+        signals::interruptible_sleep(Duration::from_secs(timestamp - now));
+        if signals::shutdown_requested() {
+            break;
+        }
+        if !tip.accepts(timestamp) {
+            log::warn!(
+                "clock is at or behind the last pulse's timestamp {}; skipping the {} slot",
+                tip.last_timestamp.unwrap_or_default(),
+                timestamp
+            );
+            continue;
+        }
+
+        let result = match entropy::generate(args.bytes, config) {
+            Ok(r) => r,
+            Err(e) => {
+                log::error!("beacon pulse {} skipped: {}", tip.next_index, e);
+                continue;
+            }
+        };
+
+        let mut pulse = Pulse {
+            version: BEACON_VERSION,
+            index: tip.next_index,
+            timestamp,
+            interval: args.interval,
+            source: result.source,
+            output_value: to_hex(&result.bytes),
+            previous_hash: to_hex(&tip.previous_hash),
+            public_key: public_key.clone(),
+            signature: String::new(),
+        };
+        pulse.sign(&key);
+        publish(&pulse, args)?;
+
+        log::info!("published pulse {} at {}", tip.next_index, timestamp);
+        tip = ChainTip {
+            next_index: tip.next_index + 1,
+            previous_hash: pulse.hash(),
+            last_timestamp: Some(timestamp),
+        };
+    }
+
+    log::info!("beacon shutting down");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signature, Verifier};
+
+    fn pulse(index: u64, previous_hash: &[u8; 64]) -> Pulse {
+        Pulse {
+            version: BEACON_VERSION,
+            index,
+            timestamp: 1_700_000_000 + index * 60,
+            interval: 60,
+            source: "test".into(),
+            output_value: to_hex(&[index as u8; 64]),
+            previous_hash: to_hex(previous_hash),
+            public_key: String::new(),
+            signature: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_signature_verifies() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let mut p = pulse(0, &[0u8; 64]);
+        p.public_key = to_hex(key.verifying_key().as_bytes());
+        p.sign(&key);
+
+        let sig_bytes: Vec<u8> = (0..p.signature.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&p.signature[i..i + 2], 16).unwrap())
+            .collect();
+        let sig = Signature::from_slice(&sig_bytes).unwrap();
+        assert!(key.verifying_key().verify(&p.signed_bytes(), &sig).is_ok());
+
+        p.output_value = to_hex(&[0xFF; 64]);
+        assert!(key.verifying_key().verify(&p.signed_bytes(), &sig).is_err());
+    }
+
+    #[test]
+    fn test_chain_hash_links_pulses() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let mut first = pulse(0, &[0u8; 64]);
+        first.sign(&key);
+        let mut second = pulse(1, &first.hash());
+        second.sign(&key);
+        assert_ne!(first.hash(), second.hash());
+        assert_eq!(second.previous_hash, to_hex(&first.hash()));
+    }
+
+    #[test]
+    fn test_resume_chain() {
+        let path = std::env::temp_dir().join("mixrand_test_beacon_chain.jsonl");
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(resume_chain(&path).unwrap(), ChainTip::genesis());
+
+        let key = SigningKey::from_bytes(&[9u8; 32]);
+        let mut p = pulse(41, &[0u8; 64]);
+        p.sign(&key);
+        std::fs::write(&path, format!("{}\n", serde_json::to_string(&p).unwrap())).unwrap();
+
+        let tip = resume_chain(&path).unwrap();
+        assert_eq!(tip.next_index, 42);
+        assert_eq!(tip.previous_hash, p.hash());
+        assert_eq!(tip.last_timestamp, Some(p.timestamp));
+        let _ = std::fs::remove_file(&path);
+
+        // A clock stepped back must not publish at or before the last pulse
+        assert!(!tip.accepts(p.timestamp - 60));
+        assert!(!tip.accepts(p.timestamp));
+        assert!(tip.accepts(p.timestamp + 60));
+        assert!(ChainTip::genesis().accepts(0));
+    }
+}
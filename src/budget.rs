@@ -0,0 +1,130 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::LimitsConfig;
+use crate::error::Error;
+
+/// Usage recorded in the budget file: `(utc_day, bytes_used)`.
+type Usage = (u64, u64);
+
+fn parse_usage(text: &str) -> Option<Usage> {
+    let mut parts = text.split_whitespace();
+    let day = parts.next()?.parse().ok()?;
+    let used = parts.next()?.parse().ok()?;
+    Some((day, used))
+}
+
+/// New usage after reserving `count` bytes on `today`, or `None` if that
+/// would exceed `limit`. Usage from earlier days is discarded.
+fn reserve_usage(current: Option<Usage>, today: u64, count: u64, limit: u64) -> Option<Usage> {
+    let used = match current {
+        Some((day, used)) if day == today => used,
+        _ => 0,
+    };
+    let total = used.checked_add(count)?;
+    (total <= limit).then_some((today, total))
+}
+
+/// Exclusive flock(2) held until drop.
+struct Locked(File);
+
+impl Locked {
+    fn new(file: File) -> Result<Self, Error> {
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(Self(file))
+    }
+}
+
+impl Drop for Locked {
+    fn drop(&mut self) {
+        unsafe { libc::flock(self.0.as_raw_fd(), libc::LOCK_UN) };
+    }
+}
+
+/// Reserves `count` bytes of today's hwrng budget. Returns `Ok(false)` when
+/// the budget is spent; bytes are charged before the read, so a failed read
+/// still counts. Always succeeds when no daily limit is configured.
+pub fn reserve_hwrng(count: usize, limits: &LimitsConfig) -> Result<bool, Error> {
+    if limits.hwrng_daily_bytes == 0 {
+        return Ok(true);
+    }
+    let path = &limits.hwrng_budget_file;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o644)
+        .open(path)?;
+    let mut locked = Locked::new(file)?;
+
+    let mut text = String::new();
+    locked.0.read_to_string(&mut text)?;
+    let today = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / 86_400)
+        .unwrap_or(0);
+
+    match reserve_usage(
+        parse_usage(&text),
+        today,
+        count as u64,
+        limits.hwrng_daily_bytes,
+    ) {
+        Some((day, used)) => {
+            locked.0.set_len(0)?;
+            locked.0.seek(SeekFrom::Start(0))?;
+            writeln!(locked.0, "{} {}", day, used)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve_usage() {
+        assert_eq!(reserve_usage(None, 10, 100, 1000), Some((10, 100)));
+        assert_eq!(
+            reserve_usage(Some((10, 900)), 10, 100, 1000),
+            Some((10, 1000))
+        );
+        assert_eq!(reserve_usage(Some((10, 901)), 10, 100, 1000), None);
+        // A new day resets the budget
+        assert_eq!(
+            reserve_usage(Some((9, 1000)), 10, 100, 1000),
+            Some((10, 100))
+        );
+        assert_eq!(reserve_usage(Some((10, u64::MAX)), 10, 1, u64::MAX), None);
+    }
+
+    #[test]
+    fn test_reserve_hwrng_persists() {
+        let path = std::env::temp_dir().join("mixrand_test_hwrng_budget");
+        let _ = fs::remove_file(&path);
+        let limits = LimitsConfig {
+            hwrng_daily_bytes: 100,
+            hwrng_budget_file: path.clone(),
+            ..Default::default()
+        };
+        assert!(reserve_hwrng(60, &limits).unwrap());
+        assert!(!reserve_hwrng(60, &limits).unwrap());
+        assert!(reserve_hwrng(40, &limits).unwrap());
+        assert_eq!(
+            parse_usage(&fs::read_to_string(&path).unwrap()).unwrap().1,
+            100
+        );
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::{CacheConfig, Config};
+use crate::entropy::{self, cpurng, EntropyResult};
+use crate::fips;
+use crate::secmem;
+use crate::signals;
+
+/// How often the refill thread logs cache metrics.
+const STATS_INTERVAL: Duration = Duration::from_secs(60);
+/// Longest the refill thread sleeps before re-checking expiry and shutdown.
+const IDLE_WAIT: Duration = Duration::from_millis(250);
+/// Back-off after the entropy pipeline fails during refill.
+const ERROR_BACKOFF: Duration = Duration::from_secs(1);
+
+/// One generated block, consumed front to back. Served bytes are zeroized
+/// immediately so nothing is handed out twice or lingers in memory.
+struct Block {
+    /// Declared first so the pages are unlocked before `bytes` is freed.
+    _locked: secmem::Locked,
+    bytes: Vec<u8>,
+    pos: usize,
+    created: Instant,
+    source: String,
+}
+
+impl Block {
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+}
+
+#[derive(Default)]
+struct Pool {
+    /// Oldest block first.
+    blocks: VecDeque<Block>,
+    level: usize,
+}
+
+impl Pool {
+    /// Zeroizes and drops blocks created more than `max_age` before `now`,
+    /// returning how many unserved bytes were discarded.
+    fn expire(&mut self, now: Instant, max_age: Duration) -> usize {
+        let mut expired = 0;
+        while self
+            .blocks
+            .front()
+            .is_some_and(|b| now.duration_since(b.created) >= max_age)
+        {
+            let mut block = self.blocks.pop_front().expect("front exists");
+            expired += block.remaining();
+            self.level -= block.remaining();
+            cpurng::zeroize_vec(&mut block.bytes);
+        }
+        expired
+    }
+
+    /// Moves `count` bytes out of the pool; the caller checks `level` first.
+    fn take(&mut self, count: usize) -> EntropyResult {
+        let mut bytes = Vec::with_capacity(count);
+        let source = self
+            .blocks
+            .front()
+            .map(|b| b.source.clone())
+            .unwrap_or_default();
+        while bytes.len() < count {
+            let block = self.blocks.front_mut().expect("level checked by caller");
+            let n = block.remaining().min(count - bytes.len());
+            let served = &mut block.bytes[block.pos..block.pos + n];
+            bytes.extend_from_slice(served);
+            cpurng::zeroize_bytes(served);
+            block.pos += n;
+            if block.remaining() == 0 {
+                self.blocks.pop_front();
+            }
+        }
+        self.level -= count;
+        EntropyResult { bytes, source }
+    }
+}
+
+/// Point-in-time cache counters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub refilled_bytes: u64,
+    pub expired_bytes: u64,
+    pub level: usize,
+}
+
+/// Pool of pre-generated, mixed entropy that serve modes draw from, so a
+/// request costs a memcpy instead of a full pipeline run. A background
+/// thread keeps it topped up and discards bytes older than `max_age_ms`.
+pub struct EntropyCache {
+    config: CacheConfig,
+    pool: Mutex<Pool>,
+    wake: Condvar,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    refilled_bytes: AtomicU64,
+    expired_bytes: AtomicU64,
+}
+
+impl EntropyCache {
+    fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            pool: Mutex::new(Pool::default()),
+            wake: Condvar::new(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            refilled_bytes: AtomicU64::new(0),
+            expired_bytes: AtomicU64::new(0),
+        }
+    }
+
+    fn max_age(&self) -> Duration {
+        Duration::from_millis(self.config.max_age_ms)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Pool> {
+        self.pool.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Serves `count` fresh bytes from the pool, or `None` (a miss) if it
+    /// holds fewer than that or FIPS mode has disabled output. Callers fall
+    /// back to `entropy::generate`.
+    pub fn take(&self, count: usize) -> Option<EntropyResult> {
+        fips::check_operational().ok()?;
+        self.take_at(count, Instant::now())
+    }
+
+    fn take_at(&self, count: usize, now: Instant) -> Option<EntropyResult> {
+        let mut pool = self.lock();
+        let expired = pool.expire(now, self.max_age());
+        self.expired_bytes
+            .fetch_add(expired as u64, Ordering::Relaxed);
+        let result = if pool.level >= count {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            Some(pool.take(count))
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            None
+        };
+        drop(pool);
+        self.wake.notify_one();
+        result
+    }
+
+    fn insert(&self, result: EntropyResult, created: Instant) {
+        let len = result.bytes.len();
+        let mut pool = self.lock();
+        pool.level += len;
+        pool.blocks.push_back(Block {
+            _locked: secmem::protect(&result.bytes),
+            bytes: result.bytes,
+            pos: 0,
+            created,
+            source: result.source,
+        });
+        self.refilled_bytes.fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            refilled_bytes: self.refilled_bytes.load(Ordering::Relaxed),
+            expired_bytes: self.expired_bytes.load(Ordering::Relaxed),
+            level: self.lock().level,
+        }
+    }
+
+    /// Bytes to generate next, or 0 if the pool is full.
+    fn deficit(&self) -> usize {
+        let mut pool = self.lock();
+        let expired = pool.expire(Instant::now(), self.max_age());
+        self.expired_bytes
+            .fetch_add(expired as u64, Ordering::Relaxed);
+        self.config
+            .size_bytes
+            .saturating_sub(pool.level)
+            .min(self.config.block_bytes)
+    }
+
+    fn log_stats(&self, prev: &CacheStats, elapsed: Duration) -> CacheStats {
+        let now = self.stats();
+        let hits = now.hits - prev.hits;
+        let lookups = hits + now.misses - prev.misses;
+        let hit_rate = if lookups > 0 {
+            100.0 * hits as f64 / lookups as f64
+        } else {
+            0.0
+        };
+        log::info!(
+            "cache: level={}/{}B hits={} misses={} hit_rate={:.1}% refill={:.0}B/s expired={}B",
+            now.level,
+            self.config.size_bytes,
+            hits,
+            now.misses - prev.misses,
+            hit_rate,
+            (now.refilled_bytes - prev.refilled_bytes) as f64 / elapsed.as_secs_f64(),
+            now.expired_bytes - prev.expired_bytes
+        );
+        now
+    }
+
+    fn refill_loop(&self, config: &Config) {
+        let mut last_report = Instant::now();
+        let mut reported = self.stats();
+
+        while !signals::shutdown_requested() {
+            let want = self.deficit();
+            if want > 0 {
+                match entropy::generate(want, config) {
+                    Ok(result) => self.insert(result, Instant::now()),
+                    Err(e) => {
+                        log::warn!("cache refill failed: {}", e);
+                        thread::sleep(ERROR_BACKOFF);
+                    }
+                }
+            } else {
+                let pool = self.lock();
+                let _ = self.wake.wait_timeout(pool, IDLE_WAIT);
+            }
+
+            if last_report.elapsed() >= STATS_INTERVAL {
+                reported = self.log_stats(&reported, last_report.elapsed());
+                last_report = Instant::now();
+            }
+        }
+
+        let mut pool = self.lock();
+        let level = pool.level;
+        pool.expire(Instant::now(), Duration::ZERO);
+        log::debug!("cache stopped, discarded {}B", level);
+    }
+}
+
+/// Starts the cache and its refill thread, or returns `None` if `[cache]`
+/// is disabled. The thread exits once shutdown is requested.
+pub fn spawn(config: &Config) -> Option<Arc<EntropyCache>> {
+    if config.cache.size_bytes == 0 {
+        return None;
+    }
+    let cache = Arc::new(EntropyCache::new(config.cache.clone()));
+    let worker = Arc::clone(&cache);
+    let worker_config = config.clone();
+    thread::Builder::new()
+        .name("entropy-cache".into())
+        .spawn(move || worker.refill_loop(&worker_config))
+        .map_err(|e| log::error!("failed to start cache refill thread: {}", e))
+        .ok()?;
+    log::info!(
+        "entropy cache: {}B in {}B blocks, max age {}ms",
+        config.cache.size_bytes,
+        config.cache.block_bytes,
+        config.cache.max_age_ms
+    );
+    Some(cache)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(max_age_ms: u64) -> EntropyCache {
+        EntropyCache::new(CacheConfig {
+            size_bytes: 64,
+            block_bytes: 16,
+            max_age_ms,
+        })
+    }
+
+    fn block(fill: u8, len: usize) -> EntropyResult {
+        EntropyResult {
+            bytes: vec![fill; len],
+            source: format!("test-{}", fill),
+        }
+    }
+
+    #[test]
+    fn test_take_spans_blocks() {
+        let c = cache(10_000);
+        let now = Instant::now();
+        c.insert(block(1, 16), now);
+        c.insert(block(2, 16), now);
+
+        let r = c.take_at(20, now).unwrap();
+        assert_eq!(&r.bytes[..16], &[1u8; 16]);
+        assert_eq!(&r.bytes[16..], &[2u8; 4]);
+        assert_eq!(r.source, "test-1");
+        assert_eq!(c.stats().level, 12);
+
+        assert!(c.take_at(13, now).is_none());
+        let r = c.take_at(12, now).unwrap();
+        assert_eq!(r.bytes, vec![2u8; 12]);
+        assert_eq!(
+            c.stats(),
+            CacheStats {
+                hits: 2,
+                misses: 1,
+                refilled_bytes: 32,
+                expired_bytes: 0,
+                level: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_stale_blocks_expire() {
+        let c = cache(1000);
+        let now = Instant::now();
+        c.insert(block(1, 16), now);
+        c.insert(block(2, 16), now + Duration::from_millis(600));
+
+        let later = now + Duration::from_millis(1200);
+        let r = c.take_at(8, later).unwrap();
+        assert_eq!(r.bytes, vec![2u8; 8]);
+        assert_eq!(c.stats().expired_bytes, 16);
+    }
+
+    #[test]
+    fn test_deficit_bounded_by_block() {
+        let c = cache(10_000);
+        assert_eq!(c.deficit(), 16);
+        for i in 0..4 {
+            c.insert(block(i, 16), Instant::now());
+        }
+        assert_eq!(c.deficit(), 0);
+    }
+}
@@ -0,0 +1,113 @@
+//! The daemon's poll schedule. Rounds start on a fixed grid of deadlines
+//! on the monotonic clock (`next += interval`) rather than an interval
+//! after the previous round finished, so slow rounds do not stretch the
+//! cadence and clock steps do not move it. The spacing the rounds actually
+//! got is measured against the interval asked for.
+
+use std::time::{Duration, Instant};
+
+pub struct Cadence {
+    interval: Duration,
+    next: Instant,
+    last: Option<Instant>,
+    /// Rounds that ran on the grid, and the sum of their spacing.
+    rounds: u64,
+    spacing: Duration,
+    /// Grid points missed because a round ran past them.
+    skipped: u64,
+}
+
+impl Cadence {
+    /// A grid whose first deadline is `start`.
+    pub fn new(interval: Duration, start: Instant) -> Self {
+        Self {
+            interval,
+            next: start,
+            last: None,
+            rounds: 0,
+            spacing: Duration::ZERO,
+            skipped: 0,
+        }
+    }
+
+    /// The next deadline.
+    pub fn next(&self) -> Instant {
+        self.next
+    }
+
+    /// Starts a round at `now`. If a deadline has come, the round is on the
+    /// grid: its spacing from the previous one is recorded and the grid
+    /// moves past `now`, skipping deadlines already missed rather than
+    /// running them back to back. Returns whether it was on the grid; a
+    /// round woken early (a signal, resume, a scheduled injection) is not.
+    pub fn start(&mut self, now: Instant) -> bool {
+        if now < self.next {
+            return false;
+        }
+        if let Some(last) = self.last {
+            self.rounds += 1;
+            self.spacing += now - last;
+        }
+        self.last = Some(now);
+        self.next += self.interval;
+        if self.next <= now {
+            let behind = (now - self.next).as_nanos() / self.interval.as_nanos().max(1);
+            let missed = behind as u32 + 1;
+            self.skipped += u64::from(missed);
+            self.next += self.interval * missed;
+        }
+        true
+    }
+
+    /// Puts the next deadline at `at` and runs the grid on from there, as
+    /// after a failure backs off.
+    pub fn restart(&mut self, at: Instant) {
+        self.next = at;
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Mean spacing of the rounds on the grid, once there are two.
+    pub fn mean_spacing(&self) -> Option<Duration> {
+        (self.rounds > 0).then(|| self.spacing / self.rounds as u32)
+    }
+
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_ignores_round_length() {
+        let t0 = Instant::now();
+        let secs = Duration::from_secs;
+        let mut cadence = Cadence::new(secs(5), t0);
+        assert!(cadence.start(t0));
+        assert_eq!(cadence.next(), t0 + secs(5));
+
+        // A slow round does not push the next deadline back
+        assert!(cadence.start(t0 + secs(6)));
+        assert_eq!(cadence.next(), t0 + secs(10));
+
+        // An early wake is off the grid
+        assert!(!cadence.start(t0 + secs(8)));
+        assert!(cadence.start(t0 + secs(10)));
+        assert_eq!(cadence.mean_spacing(), Some(secs(5)));
+
+        // A round that overran two deadlines skips them
+        assert!(cadence.start(t0 + secs(26)));
+        assert_eq!(cadence.next(), t0 + secs(30));
+        assert_eq!(cadence.skipped(), 2);
+
+        cadence.restart(t0 + secs(40));
+        assert!(!cadence.start(t0 + secs(30)));
+        assert!(cadence.start(t0 + secs(40)));
+        assert_eq!(cadence.next(), t0 + secs(45));
+    }
+}
@@ -0,0 +1,251 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::check::{self, SourceKind};
+use crate::cli::CalibrateArgs;
+use crate::config::Config;
+use crate::entropy::cpurng;
+use crate::error::Error;
+use crate::estimators;
+use crate::signals::{self, Signal};
+use crate::table::{self, Units};
+
+/// Bytes requested from the source per read.
+const CHUNK_BYTES: usize = 4096;
+/// SP 800-90B §3.1.1 asks for at least one million samples.
+const RECOMMENDED_SAMPLES: usize = 1_000_000;
+/// Fewer samples than this give meaningless estimates.
+const MIN_SAMPLES: usize = 10_000;
+/// Give up after this many consecutive read failures.
+const MAX_CONSECUTIVE_ERRORS: u32 = 100;
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Whole bits per byte that can safely be credited for an assessed
+/// min-entropy `h`, after applying `safety_factor`.
+fn recommended_ratio(h: f64, safety_factor: f64) -> u32 {
+    (h * safety_factor).floor().clamp(0.0, 8.0) as u32
+}
+
+/// Sets `key = value` in `[section]` of a TOML document, preserving every
+/// other line (and comments). Adds the key or the section if missing.
+fn set_toml_value(text: &str, section: &str, key: &str, value: &str) -> String {
+    let header = format!("[{}]", section);
+    let assignment = format!("{} = {}", key, value);
+    let mut out: Vec<String> = Vec::new();
+    let mut in_section = false;
+    let mut header_index = None;
+    let mut done = false;
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            in_section = trimmed.split('#').next().unwrap_or("").trim() == header;
+            if in_section {
+                header_index = Some(out.len());
+            }
+        } else if in_section && !done {
+            let is_key = trimmed
+                .strip_prefix(key)
+                .is_some_and(|rest| rest.trim_start().starts_with('='));
+            if is_key {
+                out.push(assignment.clone());
+                done = true;
+                continue;
+            }
+        }
+        out.push(line.to_string());
+    }
+
+    if !done {
+        match header_index {
+            Some(i) => out.insert(i + 1, assignment),
+            None => {
+                if out.last().is_some_and(|l| !l.trim().is_empty()) {
+                    out.push(String::new());
+                }
+                out.push(header);
+                out.push(assignment);
+            }
+        }
+    }
+
+    let mut result = out.join("\n");
+    result.push('\n');
+    result
+}
+
+fn write_credit_ratio(path: &Path, ratio: u32) -> Result<(), Error> {
+    let text = match fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => {
+            return Err(Error::InvalidArgs(format!(
+                "cannot read {}: {}",
+                path.display(),
+                e
+            )))
+        }
+    };
+    let updated = set_toml_value(&text, "daemon", "credit_ratio", &ratio.to_string());
+    fs::write(path, updated)
+        .map_err(|e| Error::InvalidArgs(format!("cannot write {}: {}", path.display(), e)))
+}
+
+/// Reads from `source` until `duration` passes or `max_bytes` are collected.
+fn collect(
+    source: SourceKind,
+    duration: Duration,
+    max_bytes: usize,
+    config: &Config,
+    units: Units,
+) -> Result<Vec<u8>, Error> {
+    let start = Instant::now();
+    let mut last_report = start;
+    let mut data = Vec::with_capacity(max_bytes);
+    let mut consecutive_errors = 0;
+
+    while data.len() < max_bytes && start.elapsed() < duration && !signals::shutdown_requested() {
+        let want = CHUNK_BYTES.min(max_bytes - data.len());
+        match check::collect_sample(&source, want, config) {
+            Ok(mut chunk) => {
+                data.extend_from_slice(&chunk);
+                cpurng::zeroize_vec(&mut chunk);
+                consecutive_errors = 0;
+            }
+            Err(e) => {
+                consecutive_errors += 1;
+                if consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
+                    cpurng::zeroize_vec(&mut data);
+                    return Err(e);
+                }
+            }
+        }
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            eprintln!(
+                "  {} collected after {}",
+                table::bytes(data.len() as u64, units),
+                check::format_duration(start.elapsed())
+            );
+            last_report = Instant::now();
+        }
+    }
+    Ok(data)
+}
+
+pub fn run(args: &CalibrateArgs, config: &Config) -> Result<(), Error> {
+    let source = SourceKind::parse(&args.source, config)?;
+    let duration = check::parse_duration(&args.duration)?;
+    if !(args.safety_factor > 0.0 && args.safety_factor <= 1.0) {
+        return Err(Error::InvalidArgs("safety-factor must be in (0, 1]".into()));
+    }
+    if args.max_bytes < MIN_SAMPLES {
+        return Err(Error::InvalidArgs(format!(
+            "max-bytes must be at least {}",
+            MIN_SAMPLES
+        )));
+    }
+
+    let units = args.units.unwrap_or(config.display.units);
+    signals::install(&[Signal::Term, Signal::Int]);
+
+    eprintln!(
+        "Collecting up to {} from {} for at most {}...",
+        table::bytes(args.max_bytes as u64, units),
+        source.name(),
+        check::format_duration(duration)
+    );
+    let mut data = collect(source, duration, args.max_bytes, config, units)?;
+    if data.len() < MIN_SAMPLES {
+        cpurng::zeroize_vec(&mut data);
+        return Err(Error::NoEntropy(format!(
+            "only {} bytes collected from {}; need at least {}",
+            data.len(),
+            source.name(),
+            MIN_SAMPLES
+        )));
+    }
+    if data.len() < RECOMMENDED_SAMPLES {
+        eprintln!(
+            "Note: {} samples collected; SP 800-90B recommends at least {}",
+            data.len(),
+            RECOMMENDED_SAMPLES
+        );
+    }
+
+    eprintln!("Running SP 800-90B estimators...");
+    let estimates = estimators::estimate_all(&data);
+    cpurng::zeroize_vec(&mut data);
+    let assessed = estimators::assessed(&estimates);
+    let ratio = recommended_ratio(assessed, args.safety_factor);
+
+    println!("--- {} ({}) ---", source.name(), source.description());
+    for e in &estimates {
+        println!("  {:<22} {:>6.3} bits/byte", e.name, e.min_entropy);
+    }
+    println!("  {:<22} {:>6.3} bits/byte", "Assessed (minimum)", assessed);
+    println!();
+
+    if ratio == 0 {
+        println!(
+            "Recommended credit_ratio: 0 ({:.3} x {} safety factor < 1 bit/byte); stir the pool without crediting this source",
+            assessed, args.safety_factor
+        );
+    } else {
+        println!(
+            "Recommended credit_ratio: {} (floor of {:.3} x {} safety factor)",
+            ratio, assessed, args.safety_factor
+        );
+    }
+
+    if args.write {
+        let path = args
+            .config_file
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("/etc/mixrand.toml"));
+        write_credit_ratio(&path, ratio)?;
+        println!(
+            "Wrote [daemon] credit_ratio = {} to {}",
+            ratio,
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recommended_ratio() {
+        assert_eq!(recommended_ratio(7.9, 0.5), 3);
+        assert_eq!(recommended_ratio(8.0, 1.0), 8);
+        assert_eq!(recommended_ratio(1.5, 0.5), 0);
+    }
+
+    #[test]
+    fn test_set_toml_value_replaces_key() {
+        let text = "[cpu_rng]\ncredit_ratio = 9\n\n[daemon]  # defaults\ncredit_ratio = 4 # old\nother = 1\n";
+        assert_eq!(
+            set_toml_value(text, "daemon", "credit_ratio", "3"),
+            "[cpu_rng]\ncredit_ratio = 9\n\n[daemon]  # defaults\ncredit_ratio = 3\nother = 1\n"
+        );
+    }
+
+    #[test]
+    fn test_set_toml_value_adds_key_or_section() {
+        assert_eq!(
+            set_toml_value("[daemon]\nother = 1\n", "daemon", "credit_ratio", "2"),
+            "[daemon]\ncredit_ratio = 2\nother = 1\n"
+        );
+        assert_eq!(
+            set_toml_value("[mixer]\nversion = 2\n", "daemon", "credit_ratio", "2"),
+            "[mixer]\nversion = 2\n\n[daemon]\ncredit_ratio = 2\n"
+        );
+        assert_eq!(
+            set_toml_value("", "daemon", "credit_ratio", "2"),
+            "[daemon]\ncredit_ratio = 2\n"
+        );
+    }
+}
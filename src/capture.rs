@@ -0,0 +1,390 @@
+//! Integrity tags for the evidence `check` writes (`--capture-key`): a
+//! manifest beside the files lists each one's HMAC-SHA256 tag under a
+//! local key and is tagged itself, so `mixrand verify-capture` can show
+//! that captured samples, reports and baselines have not been altered or
+//! swapped since the run.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::atomicfile;
+use crate::cli::VerifyCaptureArgs;
+use crate::config::Config;
+use crate::entropy::{self, cpurng};
+use crate::error::Error;
+use crate::output::to_hex;
+
+/// Name of the manifest in each directory holding tagged files.
+pub const MANIFEST: &str = "mixrand-manifest.json";
+const VERSION: u32 = 1;
+const ALGORITHM: &str = "hmac-sha256";
+const KEY_BYTES: usize = 32;
+
+/// The key the tags are made with, wiped on drop.
+pub struct Key([u8; KEY_BYTES]);
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        cpurng::zeroize_bytes(&mut self.0);
+    }
+}
+
+impl Key {
+    /// Names the key in manifests without revealing it, so a manifest
+    /// checked with the wrong key says so instead of failing every file.
+    fn id(&self) -> String {
+        let digest = Sha256::new()
+            .chain_update(b"mixrand-capture-key-id")
+            .chain_update(self.0)
+            .finalize();
+        to_hex(&digest[..8])
+    }
+
+    fn mac(&self, domain: &str) -> Hmac<Sha256> {
+        let mut mac =
+            <Hmac<Sha256> as Mac>::new_from_slice(&self.0).expect("HMAC accepts any key length");
+        mac.update(domain.as_bytes());
+        mac.update(&[0]);
+        mac
+    }
+}
+
+/// One tagged file, by its name within the manifest's directory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    name: String,
+    bytes: u64,
+    tag: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    algorithm: String,
+    key_id: String,
+    files: Vec<Entry>,
+    /// Over everything above, with this field empty.
+    tag: String,
+}
+
+impl Manifest {
+    fn tag(&self, key: &Key) -> Vec<u8> {
+        let unsigned = Manifest {
+            version: self.version,
+            algorithm: self.algorithm.clone(),
+            key_id: self.key_id.clone(),
+            files: self.files.clone(),
+            tag: String::new(),
+        };
+        let mut mac = key.mac("mixrand-capture-manifest");
+        mac.update(&serde_json::to_vec(&unsigned).expect("manifest serializes"));
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    /// Whether the manifest's own tag holds under `key`.
+    fn verify(&self, key: &Key) -> bool {
+        self.version == VERSION
+            && self.algorithm == ALGORITHM
+            && self.key_id == key.id()
+            && tags_match(&self.tag, &self.tag(key))
+    }
+}
+
+/// Compares a hex tag against the expected bytes in constant time.
+fn tags_match(hex: &str, expected: &[u8]) -> bool {
+    let Some(given) = parse_hex(hex) else {
+        return false;
+    };
+    given.len() == expected.len()
+        && given
+            .iter()
+            .zip(expected)
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect(),
+    )
+}
+
+/// The size and tag of the file at `path`, stored as `name`.
+fn file_tag(key: &Key, name: &str, path: &Path) -> io::Result<(u64, Vec<u8>)> {
+    let mut f = File::open(path)?;
+    let mut mac = key.mac("mixrand-capture-file");
+    mac.update(name.as_bytes());
+    mac.update(&[0]);
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut bytes = 0u64;
+    loop {
+        let n = f.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        mac.update(&buf[..n]);
+        bytes += n as u64;
+    }
+    Ok((bytes, mac.finalize().into_bytes().to_vec()))
+}
+
+/// Reads the key in `path`: 64 hex digits on the first line.
+pub fn load_key(path: &Path) -> Result<Key, Error> {
+    let f = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)
+        .map_err(|e| Error::InvalidArgs(format!("cannot read {}: {}", path.display(), e)))?;
+    if f.metadata()?.mode() & 0o077 != 0 {
+        log::warn!(
+            "{} is accessible to group or others; anyone who can read it can forge tags",
+            path.display()
+        );
+    }
+    let mut text = String::new();
+    (&f).read_to_string(&mut text)?;
+    let mut bytes = text
+        .lines()
+        .next()
+        .and_then(|l| parse_hex(l.trim()))
+        .filter(|b| b.len() == KEY_BYTES)
+        .ok_or_else(|| {
+            Error::InvalidArgs(format!(
+                "{} does not hold a {}-byte hex key",
+                path.display(),
+                KEY_BYTES
+            ))
+        })?;
+    let mut key = Key([0u8; KEY_BYTES]);
+    key.0.copy_from_slice(&bytes);
+    cpurng::zeroize_vec(&mut bytes);
+    Ok(key)
+}
+
+/// The key in `path`, or a new one from the sources, saved there with
+/// mode 0600, if the file does not exist yet.
+pub fn load_or_create_key(path: &Path, config: &Config) -> Result<Key, Error> {
+    if fs::symlink_metadata(path).is_ok() {
+        return load_key(path);
+    }
+    let mut result = entropy::generate(KEY_BYTES, config)?;
+    let mut key = Key([0u8; KEY_BYTES]);
+    key.0.copy_from_slice(&result.bytes);
+    cpurng::zeroize_vec(&mut result.bytes);
+    let mut text = format!("{}\n", to_hex(&key.0)).into_bytes();
+    let written = atomicfile::write(path, &text);
+    cpurng::zeroize_vec(&mut text);
+    written?;
+    eprintln!("New capture key written to {}", path.display());
+    Ok(key)
+}
+
+fn read_manifest(dir: &Path) -> Result<Option<Manifest>, Error> {
+    let path = dir.join(MANIFEST);
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    serde_json::from_str(&text)
+        .map(Some)
+        .map_err(|e| Error::InvalidArgs(format!("{}: {}", path.display(), e)))
+}
+
+/// Tags `paths` and records them in the manifest of each one's directory,
+/// keeping the entries of earlier runs if that manifest still verifies.
+pub fn seal(key: &Key, paths: &[&Path]) -> Result<(), Error> {
+    let mut dirs: BTreeMap<PathBuf, Vec<(String, &Path)>> = BTreeMap::new();
+    for path in paths {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| Error::InvalidArgs(format!("{}: not a file name", path.display())))?;
+        let dir = match path.parent() {
+            Some(d) if !d.as_os_str().is_empty() => d.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        dirs.entry(dir).or_default().push((name.to_string(), path));
+    }
+    for (dir, files) in dirs {
+        let mut entries = match read_manifest(&dir) {
+            Ok(Some(m)) if m.verify(key) => m.files,
+            Ok(None) => Vec::new(),
+            Ok(Some(_)) | Err(_) => {
+                log::warn!(
+                    "{} does not verify with this key; replacing it",
+                    dir.join(MANIFEST).display()
+                );
+                Vec::new()
+            }
+        };
+        for (name, path) in files {
+            let (bytes, tag) = file_tag(key, &name, path)?;
+            entries.retain(|e| e.name != name);
+            entries.push(Entry {
+                name,
+                bytes,
+                tag: to_hex(&tag),
+            });
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut manifest = Manifest {
+            version: VERSION,
+            algorithm: ALGORITHM.into(),
+            key_id: key.id(),
+            files: entries,
+            tag: String::new(),
+        };
+        manifest.tag = to_hex(&manifest.tag(key));
+        let json = serde_json::to_vec_pretty(&manifest)
+            .map_err(|e| Error::InvalidArgs(format!("cannot serialize manifest: {}", e)))?;
+        atomicfile::write(&dir.join(MANIFEST), &json)?;
+    }
+    Ok(())
+}
+
+/// What is wrong with one file listed in a manifest.
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    Missing(String),
+    Altered(String),
+}
+
+/// Checks the manifest in `dir` and every file it lists, returning the
+/// names listed and the files that are missing or altered. A manifest
+/// that is itself altered, or made with another key, is an error.
+pub fn verify(dir: &Path, key: &Key) -> Result<(Vec<String>, Vec<Problem>), Error> {
+    let path = dir.join(MANIFEST);
+    let manifest = read_manifest(dir)?
+        .ok_or_else(|| Error::InvalidArgs(format!("{} not found", path.display())))?;
+    if manifest.key_id != key.id() {
+        return Err(Error::InvalidArgs(format!(
+            "{} was tagged with key {}, not this key ({})",
+            path.display(),
+            manifest.key_id,
+            key.id()
+        )));
+    }
+    if !manifest.verify(key) {
+        return Err(Error::Io(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} has been altered: its own tag does not match",
+                path.display()
+            ),
+        )));
+    }
+    let mut problems = Vec::new();
+    for entry in &manifest.files {
+        let plain = Path::new(&entry.name).file_name() == Some(entry.name.as_ref());
+        let file = dir.join(&entry.name);
+        if !plain || fs::symlink_metadata(&file).is_err() {
+            problems.push(Problem::Missing(entry.name.clone()));
+            continue;
+        }
+        match file_tag(key, &entry.name, &file) {
+            Ok((bytes, tag)) if bytes == entry.bytes && tags_match(&entry.tag, &tag) => {}
+            _ => problems.push(Problem::Altered(entry.name.clone())),
+        }
+    }
+    let names = manifest.files.into_iter().map(|e| e.name).collect();
+    Ok((names, problems))
+}
+
+/// `mixrand verify-capture`: checks a directory's manifest and reports each
+/// file. Fails if anything is missing or altered.
+pub fn run(args: &VerifyCaptureArgs) -> Result<(), Error> {
+    let key = load_key(&args.key)?;
+    let (files, problems) = verify(&args.dir, &key)?;
+    for name in &files {
+        let status = match problems.iter().find(|p| match p {
+            Problem::Missing(n) | Problem::Altered(n) => n == name,
+        }) {
+            Some(Problem::Missing(_)) => "MISSING",
+            Some(Problem::Altered(_)) => "ALTERED",
+            None => "ok",
+        };
+        println!("{:<8} {}", status, name);
+    }
+    if problems.is_empty() {
+        eprintln!("{} files verified", files.len());
+        return Ok(());
+    }
+    Err(Error::Io(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "{} of {} files missing or altered",
+            problems.len(),
+            files.len()
+        ),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_verify() {
+        let dir = std::env::temp_dir().join(format!("mixrand_test_capture_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let key = Key([7u8; KEY_BYTES]);
+        let samples = dir.join("samples.csv");
+        let report = dir.join("report.html");
+        fs::write(&samples, "timestamp,source\n1,rdrand\n").unwrap();
+        fs::write(&report, "<html></html>").unwrap();
+
+        seal(&key, &[&samples]).unwrap();
+        seal(&key, &[&report]).unwrap();
+        let (files, problems) = verify(&dir, &key).unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(problems.is_empty());
+
+        // Tampering with a file, removing one, or using another key
+        fs::write(&samples, "timestamp,source\n1,rdseed\n").unwrap();
+        fs::remove_file(&report).unwrap();
+        let (_, problems) = verify(&dir, &key).unwrap();
+        assert_eq!(
+            problems,
+            [
+                Problem::Missing("report.html".into()),
+                Problem::Altered("samples.csv".into())
+            ]
+        );
+        assert!(verify(&dir, &Key([8u8; KEY_BYTES])).is_err());
+
+        // Editing the manifest to match the altered file
+        let path = dir.join(MANIFEST);
+        let mut manifest: Manifest =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        manifest.files.retain(|e| e.name != "report.html");
+        fs::write(&path, serde_json::to_vec(&manifest).unwrap()).unwrap();
+        assert!(verify(&dir, &key).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_load_key() {
+        let path =
+            std::env::temp_dir().join(format!("mixrand_test_capture_{}.key", std::process::id()));
+        fs::write(&path, format!("{}\n", "ab".repeat(KEY_BYTES))).unwrap();
+        assert_eq!(load_key(&path).unwrap().0, [0xab; KEY_BYTES]);
+        fs::write(&path, "abcd\n").unwrap();
+        assert!(load_key(&path).is_err());
+        let _ = fs::remove_file(&path);
+    }
+}
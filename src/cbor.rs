@@ -0,0 +1,276 @@
+//! Minimal CBOR (RFC 8949) codec for the socket protocol in [`crate::wire`]:
+//! integers, byte and text strings, arrays, maps, booleans and null, with
+//! definite lengths only. Floats, tags and indefinite lengths are rejected.
+
+use std::io::{ErrorKind, Read};
+
+use crate::entropy::cpurng;
+use crate::error::Error;
+
+const UINT: u8 = 0;
+const NINT: u8 = 1;
+const BYTES: u8 = 2;
+const TEXT: u8 = 3;
+const ARRAY: u8 = 4;
+const MAP: u8 = 5;
+const SIMPLE: u8 = 7;
+
+const FALSE: u8 = 20;
+const TRUE: u8 = 21;
+const NULL: u8 = 22;
+
+/// Deepest nesting accepted when decoding.
+const MAX_DEPTH: usize = 8;
+/// Most array elements or map entries accepted when decoding.
+const MAX_ITEMS: u64 = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Uint(u64),
+    /// The negative integer `-1 - n`.
+    Nint(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    Bool(bool),
+    Null,
+}
+
+impl Value {
+    /// The value under text key `key`, if this is a map that has one.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| matches!(k, Value::Text(t) if t == key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Overwrites every byte and text string, for values carrying secrets.
+    pub fn zeroize(&mut self) {
+        match self {
+            Value::Bytes(b) => cpurng::zeroize_vec(b),
+            Value::Text(t) => cpurng::zeroize_vec(&mut std::mem::take(t).into_bytes()),
+            Value::Array(items) => items.iter_mut().for_each(Value::zeroize),
+            Value::Map(entries) => entries.iter_mut().for_each(|(k, v)| {
+                k.zeroize();
+                v.zeroize();
+            }),
+            _ => {}
+        }
+    }
+
+    /// A map with text keys, in the given order.
+    pub fn map<'a>(entries: impl IntoIterator<Item = (&'a str, Value)>) -> Self {
+        Value::Map(
+            entries
+                .into_iter()
+                .map(|(k, v)| (Value::Text(k.to_string()), v))
+                .collect(),
+        )
+    }
+}
+
+fn head(major: u8, n: u64, out: &mut Vec<u8>) {
+    let m = major << 5;
+    match n {
+        0..=23 => out.push(m | n as u8),
+        24..=0xff => out.extend_from_slice(&[m | 24, n as u8]),
+        0x100..=0xffff => {
+            out.push(m | 25);
+            out.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(m | 26);
+            out.extend_from_slice(&(n as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(m | 27);
+            out.extend_from_slice(&n.to_be_bytes());
+        }
+    }
+}
+
+/// Appends the encoding of `value`, using the shortest heads.
+pub fn encode(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Uint(n) => head(UINT, *n, out),
+        Value::Nint(n) => head(NINT, *n, out),
+        Value::Bytes(b) => {
+            head(BYTES, b.len() as u64, out);
+            out.extend_from_slice(b);
+        }
+        Value::Text(t) => {
+            head(TEXT, t.len() as u64, out);
+            out.extend_from_slice(t.as_bytes());
+        }
+        Value::Array(items) => {
+            head(ARRAY, items.len() as u64, out);
+            for item in items {
+                encode(item, out);
+            }
+        }
+        Value::Map(entries) => {
+            head(MAP, entries.len() as u64, out);
+            for (k, v) in entries {
+                encode(k, out);
+                encode(v, out);
+            }
+        }
+        Value::Bool(b) => out.push(SIMPLE << 5 | if *b { TRUE } else { FALSE }),
+        Value::Null => out.push(SIMPLE << 5 | NULL),
+    }
+}
+
+fn malformed(what: &str) -> Error {
+    Error::InvalidArgs(format!("malformed CBOR: {}", what))
+}
+
+fn read_exact<R: Read>(r: &mut R, buf: &mut [u8]) -> Result<(), Error> {
+    r.read_exact(buf).map_err(|e| match e.kind() {
+        ErrorKind::UnexpectedEof => malformed("truncated item"),
+        _ => e.into(),
+    })
+}
+
+/// Decodes the argument following initial byte `initial`.
+fn argument<R: Read>(r: &mut R, initial: u8) -> Result<u64, Error> {
+    let len = match initial & 0x1f {
+        n @ 0..=23 => return Ok(u64::from(n)),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => return Err(malformed("indefinite length or reserved value")),
+    };
+    let mut buf = [0u8; 8];
+    read_exact(r, &mut buf[8 - len..])?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+fn decode<R: Read>(r: &mut R, initial: u8, depth: usize, max_len: usize) -> Result<Value, Error> {
+    if depth > MAX_DEPTH {
+        return Err(malformed("nested too deeply"));
+    }
+    let major = initial >> 5;
+    let n = argument(r, initial)?;
+    let string = |r: &mut R| -> Result<Vec<u8>, Error> {
+        if n > max_len as u64 {
+            return Err(malformed("string too long"));
+        }
+        let mut buf = vec![0u8; n as usize];
+        read_exact(r, &mut buf)?;
+        Ok(buf)
+    };
+    let next = |r: &mut R| -> Result<Value, Error> {
+        let mut b = [0u8; 1];
+        read_exact(r, &mut b)?;
+        decode(r, b[0], depth + 1, max_len)
+    };
+    match major {
+        UINT => Ok(Value::Uint(n)),
+        NINT => Ok(Value::Nint(n)),
+        BYTES => Ok(Value::Bytes(string(r)?)),
+        TEXT => String::from_utf8(string(r)?)
+            .map(Value::Text)
+            .map_err(|_| malformed("text is not UTF-8")),
+        ARRAY | MAP if n > MAX_ITEMS => Err(malformed("too many items")),
+        ARRAY => (0..n)
+            .map(|_| next(r))
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+        MAP => (0..n)
+            .map(|_| Ok((next(r)?, next(r)?)))
+            .collect::<Result<_, Error>>()
+            .map(Value::Map),
+        SIMPLE => match initial & 0x1f {
+            FALSE => Ok(Value::Bool(false)),
+            TRUE => Ok(Value::Bool(true)),
+            NULL => Ok(Value::Null),
+            _ => Err(malformed(
+                "floats and other simple values are not supported",
+            )),
+        },
+        _ => Err(malformed("tags are not supported")),
+    }
+}
+
+/// Reads one item whose strings are at most `max_len` bytes, or `None` if
+/// the stream ended before it started.
+pub fn read<R: Read>(r: &mut R, max_len: usize) -> Result<Option<Value>, Error> {
+    let mut initial = [0u8; 1];
+    loop {
+        match r.read(&mut initial) {
+            Ok(0) => return Ok(None),
+            Ok(_) => break,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    decode(r, initial[0], 0, max_len).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoded(value: &Value) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode(value, &mut out);
+        out
+    }
+
+    #[test]
+    fn test_rfc8949_examples() {
+        assert_eq!(encoded(&Value::Uint(23)), [0x17]);
+        assert_eq!(encoded(&Value::Uint(24)), [0x18, 0x18]);
+        assert_eq!(encoded(&Value::Uint(1000)), [0x19, 0x03, 0xe8]);
+        assert_eq!(encoded(&Value::Nint(99)), [0x38, 0x63]);
+        assert_eq!(encoded(&Value::Text("IETF".into())), b"\x64IETF");
+        assert_eq!(encoded(&Value::Bytes(vec![1, 2, 3, 4])), [0x44, 1, 2, 3, 4]);
+        assert_eq!(
+            encoded(&Value::map([("a", Value::Uint(1))])),
+            [0xa1, 0x61, b'a', 0x01]
+        );
+        assert_eq!(encoded(&Value::Null), [0xf6]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let value = Value::map([
+            ("v", Value::Uint(1)),
+            ("id", Value::Uint(u64::MAX)),
+            ("data", Value::Bytes(vec![0xab; 300])),
+            ("list", Value::Array(vec![Value::Bool(true), Value::Null])),
+        ]);
+        let bytes = encoded(&value);
+        let decoded = read(&mut &bytes[..], 1024).unwrap().unwrap();
+        assert_eq!(decoded, value);
+        assert_eq!(decoded.get("id"), Some(&Value::Uint(u64::MAX)));
+        assert_eq!(decoded.get("missing"), None);
+        assert!(read(&mut &[][..], 1024).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_rejects_malformed() {
+        for bytes in [
+            &[0x5f][..],         // indefinite-length bytes
+            &[0xf9, 0x3c, 0x00], // half float
+            &[0xc0, 0x00],       // tag
+            &[0x44, 1, 2],       // truncated
+            &[0x62, 0xff, 0xfe], // invalid UTF-8
+            &[0x59, 0x10, 0x00], // longer than the limit
+        ] {
+            assert!(
+                matches!(read(&mut &bytes[..], 1024), Err(Error::InvalidArgs(_))),
+                "{:02x?}",
+                bytes
+            );
+        }
+        let deep = [0x81; MAX_DEPTH + 2];
+        assert!(read(&mut &deep[..], 1024).is_err());
+    }
+}
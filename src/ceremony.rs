@@ -0,0 +1,204 @@
+//! `ceremony`: multi-operator secret generation for documented key
+//! ceremonies. Each participant in turn types a phrase on the terminal; the
+//! phrase and the timing of every keystroke form their contribution, whose
+//! commitment hash is printed before the next participant starts. The
+//! secret is expanded from the contributions mixed with the hardware
+//! sources, so it is unpredictable as long as any one input is.
+
+use std::io::{self, IsTerminal, Read, Write};
+use std::os::unix::io::AsRawFd;
+
+use blake2::{
+    digest::{consts::U32, Digest},
+    Blake2b,
+};
+
+use crate::cli::{CeremonyArgs, OutputFormat};
+use crate::config::Config;
+use crate::csprng;
+use crate::entropy::input::{Collector, RawTerminal};
+use crate::entropy::{self, cpurng, EntropyResult};
+use crate::error::Error;
+use crate::mixer;
+use crate::output::{self, to_hex, Destination};
+use crate::secmem;
+
+type Blake2b256 = Blake2b<U32>;
+
+const CONTRIBUTION_DOMAIN: &[u8] = b"mixrand-ceremony-contribution-v1";
+const COMMITMENT_DOMAIN: &[u8] = b"mixrand-ceremony-commitment-v1";
+const TRANSCRIPT_DOMAIN: &[u8] = b"mixrand-ceremony-transcript-v1";
+
+const CTRL_C: u8 = 0x03;
+const CTRL_D: u8 = 0x04;
+const CTRL_H: u8 = 0x08;
+const DEL: u8 = 0x7f;
+
+/// Digest of a participant's phrase and their mixed keystroke timings.
+fn contribution(phrase: &[u8], timing: &[u8]) -> [u8; 32] {
+    let mut h = Blake2b256::new();
+    h.update(CONTRIBUTION_DOMAIN);
+    for part in [phrase, timing] {
+        h.update((part.len() as u64).to_be_bytes());
+        h.update(part);
+    }
+    h.finalize().into()
+}
+
+/// Binds participant `index` (from 1) to their contribution without
+/// revealing it.
+fn commitment(index: u32, contribution: &[u8; 32]) -> [u8; 32] {
+    let mut h = Blake2b256::new();
+    h.update(COMMITMENT_DOMAIN);
+    h.update(index.to_be_bytes());
+    h.update(contribution);
+    h.finalize().into()
+}
+
+/// Hash of every commitment in order, to record one value for the whole
+/// ceremony.
+fn transcript(commitments: &[[u8; 32]]) -> [u8; 32] {
+    let mut h = Blake2b256::new();
+    h.update(TRANSCRIPT_DOMAIN);
+    h.update((commitments.len() as u64).to_be_bytes());
+    for c in commitments {
+        h.update(c);
+    }
+    h.finalize().into()
+}
+
+/// Reads one participant's phrase, recording when each key arrived.
+fn read_phrase(min_keystrokes: usize) -> Result<(Vec<u8>, Collector), Error> {
+    let stdin = io::stdin();
+    let _raw = RawTerminal::enter(stdin.as_raw_fd())?;
+    let mut input = stdin.lock();
+    let mut phrase = Vec::with_capacity(256);
+    let mut timing = Collector::new();
+    let mut key = [0u8; 1];
+    loop {
+        input.read_exact(&mut key)?;
+        timing.record_now(u16::from(key[0]));
+        match key[0] {
+            CTRL_C | CTRL_D => {
+                cpurng::zeroize_vec(&mut phrase);
+                eprintln!();
+                return Err(Error::InvalidArgs("ceremony aborted".into()));
+            }
+            b'\r' | b'\n' if phrase.len() >= min_keystrokes => break,
+            b'\r' | b'\n' => eprint!(
+                "\n{} more characters needed: ",
+                min_keystrokes - phrase.len()
+            ),
+            CTRL_H | DEL => {
+                if let Some(last) = phrase.last_mut() {
+                    *last = 0;
+                    phrase.pop();
+                    eprint!("\x08 \x08");
+                }
+            }
+            k => {
+                phrase.push(k);
+                eprint!("*");
+            }
+        }
+        io::stderr().flush()?;
+    }
+    eprintln!();
+    Ok((phrase, timing))
+}
+
+pub fn run(args: &CeremonyArgs, config: &Config) -> Result<(), Error> {
+    if args.bytes == 0 {
+        return Err(Error::InvalidArgs(
+            "byte count must be greater than 0".into(),
+        ));
+    }
+    if !io::stdin().is_terminal() {
+        return Err(Error::InvalidArgs(
+            "ceremony needs an interactive terminal on stdin".into(),
+        ));
+    }
+
+    let mut contributions = Vec::with_capacity(args.participants as usize);
+    let mut commitments = Vec::with_capacity(args.participants as usize);
+    for index in 1..=args.participants {
+        eprintln!(
+            "Participant {} of {}: type a phrase of at least {} characters, then press Enter.",
+            index, args.participants, args.min_keystrokes
+        );
+        eprint!("> ");
+        io::stderr().flush()?;
+        let (mut phrase, timing) = read_phrase(args.min_keystrokes)?;
+        let timing_bits = timing.bits();
+        let mut timing = timing.finish();
+        let digest = contribution(&phrase, &timing);
+        cpurng::zeroize_vec(&mut phrase);
+        cpurng::zeroize_bytes(&mut timing);
+        let committed = commitment(index, &digest);
+        eprintln!(
+            "Participant {} commitment: {} (about {} bits from keystroke timing)\n",
+            index,
+            to_hex(&committed),
+            timing_bits
+        );
+        contributions.push(digest);
+        commitments.push(committed);
+    }
+    let _locked = secmem::protect(contributions.as_flattened());
+
+    let mut hardware = entropy::generate(32, config)?;
+    let labels: Vec<String> = (1..=args.participants)
+        .map(|i| format!("participant-{}", i))
+        .collect();
+    let mut inputs: Vec<(&str, &[u8])> = vec![("hardware", &hardware.bytes)];
+    inputs.extend(
+        labels
+            .iter()
+            .map(String::as_str)
+            .zip(contributions.iter().map(|c| &c[..])),
+    );
+    let mut seed = mixer::mix_entropy(&inputs);
+    cpurng::zeroize_vec(&mut hardware.bytes);
+    for c in &mut contributions {
+        cpurng::zeroize_bytes(c);
+    }
+
+    let mut expand = csprng::stream(seed);
+    cpurng::zeroize_bytes(&mut seed);
+    // For text, -n counts characters and rejected draws need more bytes
+    let (mut drawn, mut secret) = output::draw(args.bytes, &args.format, |n| {
+        Ok(EntropyResult {
+            bytes: expand(n),
+            source: hardware.source.clone(),
+        })
+    })?;
+    cpurng::zeroize_vec(&mut drawn.bytes);
+    let locked = secmem::protect(&secret);
+    eprintln!("Hardware source: {}", hardware.source);
+    eprintln!("Transcript: {}", to_hex(&transcript(&commitments)));
+    let dest = match &args.output_file {
+        Some(path) => Destination::File(path),
+        None => Destination::Stdout,
+    };
+    let written = output::write_output(&secret, &OutputFormat::Raw, dest, None);
+    cpurng::zeroize_vec(&mut secret);
+    drop(locked);
+    written?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commitments() {
+        let a = contribution(b"correct horse", &[1, 2, 3]);
+        assert_ne!(a, contribution(b"correct horse", &[1, 2, 4]));
+        assert_ne!(a, contribution(b"correct hors", b"e\x01\x02\x03"));
+        assert_eq!(commitment(1, &a), commitment(1, &a));
+        assert_ne!(commitment(1, &a), commitment(2, &a));
+        let (c1, c2) = (commitment(1, &a), commitment(2, &a));
+        assert_ne!(transcript(&[c1, c2]), transcript(&[c2, c1]));
+    }
+}
@@ -1,95 +1,298 @@
-use std::fs::File;
-use std::io::{Read, Write};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::{Duration, Instant};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, IsTerminal, Read, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+use serde::{Deserialize, Serialize};
+
+use crate::atomicfile;
+use crate::baseline::{self, Baseline, Delta, SourceResult};
+use crate::capture;
 use crate::cli::CheckArgs;
-use crate::config::CpuRngConfig;
-use crate::entropy::{cpurng, fallback, haveged, hwrng};
+use crate::config::Config;
+use crate::cpudiff;
+use crate::drift::{self, Drift};
+use crate::entropy::{
+    self, cpurng, devrandom, exec, fallback, havege, hwrng, plugin, serial, vsock,
+};
+use crate::environment::Environment;
 use crate::error::Error;
-use crate::stats;
-
-static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+use crate::histogram::{self, Histogram};
+use crate::latency::Latency;
+use crate::paired::{self, Rounds, Sample};
+use crate::report::{self, ReportMeta, ReportRow};
+use crate::signals::{self, Signal};
+use crate::stats::{self, EntropyStream, FipsTally, FipsWindows, FIPS_WINDOW_BYTES};
+use crate::table::{self, Align, Table, Units};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum SourceKind {
+pub enum SourceKind {
     Hwrng,
+    Serial,
+    Exec,
+    Vsock,
     Rdseed,
     Rdrand,
     Xstore,
-    Haveged,
+    DevRandom,
+    Havege,
     Urandom,
     Fallback,
+    /// Index into the loaded plugins.
+    Plugin(usize),
 }
 
 impl SourceKind {
-    fn name(&self) -> &'static str {
+    pub const ALL: [SourceKind; 11] = [
+        SourceKind::Hwrng,
+        SourceKind::Serial,
+        SourceKind::Exec,
+        SourceKind::Vsock,
+        SourceKind::Rdseed,
+        SourceKind::Rdrand,
+        SourceKind::Xstore,
+        SourceKind::DevRandom,
+        SourceKind::Havege,
+        SourceKind::Urandom,
+        SourceKind::Fallback,
+    ];
+
+    /// Built-in sources followed by the plugins in `[plugins] dir`.
+    pub fn available(config: &Config) -> Vec<SourceKind> {
+        let plugins = plugin::load(&config.plugins).plugins.len();
+        Self::ALL
+            .into_iter()
+            .chain((0..plugins).map(SourceKind::Plugin))
+            .collect()
+    }
+
+    pub fn from_name(name: &str, config: &Config) -> Option<Self> {
+        Self::available(config)
+            .into_iter()
+            .find(|s| s.is_named(name))
+    }
+
+    /// Like [`from_name`](Self::from_name), but an unknown name is an error
+    /// listing the known ones and suggesting the closest.
+    pub fn parse(name: &str, config: &Config) -> Result<Self, Error> {
+        if let Some(kind) = Self::from_name(name, config) {
+            return Ok(kind);
+        }
+        let available = Self::available(config);
+        let names: Vec<&str> = available.iter().map(|s| s.name()).collect();
+        let closest = names
+            .iter()
+            .map(|n| (edit_distance(&name.to_ascii_lowercase(), n), n))
+            .filter(|(d, _)| *d <= 2)
+            .min();
+        let hint = closest.map_or(String::new(), |(_, n)| format!("did you mean '{}'? ", n));
+        Err(Error::InvalidArgs(format!(
+            "unknown source '{}' ({}expected one of: {})",
+            name,
+            hint,
+            names.join(", ")
+        )))
+    }
+
+    /// Matches [`name`](Self::name) case-insensitively; `haveged` is still
+    /// accepted for `devrandom`.
+    pub fn is_named(&self, name: &str) -> bool {
+        self.name().eq_ignore_ascii_case(name)
+            || (*self == SourceKind::DevRandom && name.eq_ignore_ascii_case("haveged"))
+    }
+
+    pub fn name(&self) -> &'static str {
         match self {
             SourceKind::Hwrng => "hwrng",
+            SourceKind::Serial => "serial",
+            SourceKind::Exec => "exec",
+            SourceKind::Vsock => "vsock",
             SourceKind::Rdseed => "rdseed",
             SourceKind::Rdrand => "rdrand",
             SourceKind::Xstore => "xstore",
-            SourceKind::Haveged => "haveged",
+            SourceKind::DevRandom => "devrandom",
+            SourceKind::Havege => "havege",
             SourceKind::Urandom => "urandom",
             SourceKind::Fallback => "fallback",
+            SourceKind::Plugin(i) => &plugin::loaded()[*i].name,
         }
     }
 
-    fn description(&self) -> &'static str {
+    pub fn description(&self) -> &'static str {
         match self {
             SourceKind::Hwrng => "Hardware RNG (/dev/hwrng)",
+            SourceKind::Serial => "Serial TRNG ([serial] config)",
+            SourceKind::Exec => "External command ([exec] config)",
+            SourceKind::Vsock => "VM host serve-vsock ([vsock] config)",
             SourceKind::Rdseed => "CPU RDSEED instruction",
             SourceKind::Rdrand => "CPU RDRAND instruction",
             SourceKind::Xstore => "VIA PadLock XSTORE instruction",
-            SourceKind::Haveged => "haveged (/dev/random)",
+            SourceKind::DevRandom => "Kernel /dev/random with a feeder",
+            SourceKind::Havege => "Built-in HAVEGE collector",
             SourceKind::Urandom => "/dev/urandom",
             SourceKind::Fallback => "Fallback (urandom + procfs + jitter + cpu-rng)",
+            SourceKind::Plugin(i) => &plugin::loaded()[*i].description,
         }
     }
 }
 
+/// Levenshtein distance, for suggesting a source name after a typo.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substituted = diagonal + (ca != *cb) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// A parsed `--sources` list: names, `all`, and `!name` to leave one out.
+/// With only exclusions, the rest of the sources are selected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFilter {
+    /// `None` selects every source.
+    include: Option<Vec<SourceKind>>,
+    exclude: Vec<SourceKind>,
+}
+
+impl SourceFilter {
+    pub fn parse(names: &[String], config: &Config) -> Result<Self, Error> {
+        let mut all = false;
+        let mut include = Vec::new();
+        let mut exclude = Vec::new();
+        for raw in names {
+            let name = raw.trim();
+            if name.is_empty() {
+                return Err(Error::InvalidArgs("empty name in --sources".into()));
+            }
+            if name.eq_ignore_ascii_case("all") {
+                all = true;
+            } else if let Some(negated) = name.strip_prefix('!') {
+                exclude.push(SourceKind::parse(negated.trim(), config)?);
+            } else {
+                include.push(SourceKind::parse(name, config)?);
+            }
+        }
+        if let Some(both) = include.iter().find(|s| exclude.contains(s)) {
+            return Err(Error::InvalidArgs(format!(
+                "--sources both selects and excludes '{}'",
+                both.name()
+            )));
+        }
+        let include = (!all && !include.is_empty()).then_some(include);
+        Ok(Self { include, exclude })
+    }
+
+    /// The selected sources, in the order named or else the built-in order.
+    pub fn select(&self, config: &Config) -> Vec<SourceKind> {
+        let candidates = self
+            .include
+            .clone()
+            .unwrap_or_else(|| SourceKind::available(config));
+        let mut selected = Vec::new();
+        for s in candidates {
+            if !selected.contains(&s) && !self.exclude.contains(&s) {
+                selected.push(s);
+            }
+        }
+        selected
+    }
+
+    /// Whether the sources were named rather than taken by default, so
+    /// unavailable ones are worth reporting.
+    pub fn is_explicit(&self) -> bool {
+        self.include.is_some()
+    }
+}
+
+/// FIPS pass percentage with its 95% confidence interval, e.g.
+/// `99.2% (98.4-99.6)`.
+pub fn fips_pass_ci(tally: &FipsTally, pass_count: u64) -> String {
+    let (lo, hi) = stats::wilson_interval(pass_count, tally.windows, stats::Z_95);
+    format!(
+        "{} ({}-{})",
+        table::percent(tally.pass_pct(pass_count), 1),
+        table::decimal(100.0 * lo, 1),
+        table::decimal(100.0 * hi, 1)
+    )
+}
+
+#[derive(Serialize, Deserialize)]
 struct SourceStats {
     total_samples: u64,
     total_bytes: u64,
     total_time: Duration,
-    fips_monobit_pass: u64,
-    fips_poker_pass: u64,
-    fips_runs_pass: u64,
-    fips_long_runs_pass: u64,
-    fips_all_pass: u64,
+    /// Distribution of per-sample collection times.
+    latency: Latency,
+    fips: FipsTally,
+    fips_window: FipsWindows,
     shannon_sum: f64,
     min_entropy_sum: f64,
+    /// Sums of squares, for the standard errors of the two averages above.
+    shannon_sq_sum: f64,
+    min_entropy_sq_sum: f64,
     chi_square_sum: f64,
     mean_sum: f64,
     serial_corr_sum: f64,
+    compression_sum: f64,
+    /// Per-lag autocorrelation sums, starting at lag `first_lag`.
+    autocorr_sums: Vec<f64>,
+    first_lag: usize,
+    histogram: Histogram,
+    /// Windowed statistics, with --drift-window.
+    #[serde(skip)]
+    drift: Option<Drift>,
     errors: u64,
 }
 
 impl SourceStats {
-    fn new() -> Self {
+    fn new(lags: &RangeInclusive<usize>) -> Self {
         Self {
             total_samples: 0,
             total_bytes: 0,
             total_time: Duration::ZERO,
-            fips_monobit_pass: 0,
-            fips_poker_pass: 0,
-            fips_runs_pass: 0,
-            fips_long_runs_pass: 0,
-            fips_all_pass: 0,
+            latency: Latency::default(),
+            fips: FipsTally::default(),
+            fips_window: FipsWindows::new(),
             shannon_sum: 0.0,
             min_entropy_sum: 0.0,
+            shannon_sq_sum: 0.0,
+            min_entropy_sq_sum: 0.0,
             chi_square_sum: 0.0,
             mean_sum: 0.0,
             serial_corr_sum: 0.0,
+            compression_sum: 0.0,
+            autocorr_sums: vec![0.0; lags.clone().count()],
+            first_lag: *lags.start(),
+            histogram: Histogram::default(),
+            drift: None,
             errors: 0,
         }
     }
 
     fn fips_pass_pct(&self, pass_count: u64) -> f64 {
-        if self.total_samples == 0 {
-            return 0.0;
-        }
-        100.0 * pass_count as f64 / self.total_samples as f64
+        self.fips.pass_pct(pass_count)
+    }
+
+    fn fips_pass_ci(&self, pass_count: u64) -> String {
+        fips_pass_ci(&self.fips, pass_count)
+    }
+
+    /// Average of a per-sample estimate with its standard error, e.g.
+    /// `7.998 (SE 0.001)`.
+    fn avg_se(&self, sum: f64, sq_sum: f64) -> String {
+        format!(
+            "{} (SE {})",
+            table::decimal(self.avg(sum), 3),
+            table::decimal(stats::standard_error(sum, sq_sum, self.total_samples), 3)
+        )
     }
 
     fn avg(&self, sum: f64) -> f64 {
@@ -99,6 +302,12 @@ impl SourceStats {
         sum / self.total_samples as f64
     }
 
+    /// Lag whose mean autocorrelation across samples has the largest |r|.
+    fn max_autocorrelation(&self) -> Option<(usize, f64)> {
+        let means: Vec<f64> = self.autocorr_sums.iter().map(|&s| self.avg(s)).collect();
+        stats::max_abs_autocorrelation(&means, self.first_lag)
+    }
+
     fn throughput_bytes_per_sec(&self) -> f64 {
         let secs = self.total_time.as_secs_f64();
         if secs < f64::EPSILON {
@@ -108,19 +317,146 @@ impl SourceStats {
     }
 }
 
-fn collect_sample(
+/// Bumped when [`SavedCheck`] changes incompatibly.
+const STATE_VERSION: u32 = 1;
+
+/// An interrupted check, saved by `--save-state` for `--resume`. `S` is
+/// `&SourceStats` when saving and `SourceStats` when loading.
+#[derive(Serialize, Deserialize)]
+struct SavedCheck<S> {
+    version: u32,
+    sample_size: usize,
+    lags: RangeInclusive<usize>,
+    elapsed: Duration,
+    sources: Vec<(String, S)>,
+}
+
+/// Replaces `path` atomically, so a check killed mid-write still leaves
+/// the previous state to resume from.
+fn save_state(path: &Path, state: &SavedCheck<&SourceStats>) -> Result<(), Error> {
+    let json = serde_json::to_vec(state).map_err(|e| Error::Io(std::io::Error::other(e)))?;
+    atomicfile::write(path, &json)
+}
+
+fn load_state(path: &Path) -> Result<SavedCheck<SourceStats>, Error> {
+    let invalid =
+        |e: String| Error::InvalidArgs(format!("invalid check state {}: {}", path.display(), e));
+    let json = std::fs::read(path).map_err(|e| {
+        Error::InvalidArgs(format!("cannot read check state {}: {}", path.display(), e))
+    })?;
+    let state: SavedCheck<SourceStats> =
+        serde_json::from_slice(&json).map_err(|e| invalid(e.to_string()))?;
+    if state.version != STATE_VERSION {
+        return Err(invalid(format!(
+            "version {} (this mixrand reads {})",
+            state.version, STATE_VERSION
+        )));
+    }
+    if state.sources.is_empty() {
+        return Err(invalid("no sources".into()));
+    }
+    Ok(state)
+}
+
+/// Appends one CSV row per sample for offline analysis (`--samples-csv`).
+struct SampleLog {
+    out: BufWriter<File>,
+}
+
+impl SampleLog {
+    const HEADER: &'static str = "timestamp,source,bytes,latency_us,shannon,min_entropy,\
+chi_square,fips_monobit,fips_poker,fips_runs,fips_long_runs";
+
+    /// Opens `path` for appending, writing the header if the file is new or empty.
+    fn open(path: &Path) -> Result<Self, Error> {
+        let f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| Error::InvalidArgs(format!("cannot open {}: {}", path.display(), e)))?;
+        let is_empty = f.metadata()?.len() == 0;
+        let mut out = BufWriter::new(f);
+        if is_empty {
+            writeln!(out, "{}", Self::HEADER)?;
+        }
+        Ok(Self { out })
+    }
+
+    fn record(
+        &mut self,
+        source: SourceKind,
+        bytes: usize,
+        latency: Duration,
+        est: &stats::EntropyEstimates,
+        fips: Option<&FipsTally>,
+    ) -> Result<(), Error> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        write!(
+            self.out,
+            "{:.6},{},{},{},{:.6},{:.6},{:.3}",
+            timestamp,
+            source.name(),
+            bytes,
+            latency.as_micros(),
+            est.shannon,
+            est.min_entropy,
+            est.chi_square
+        )?;
+        // A column is 1 if every window this sample completed passed
+        match fips.filter(|f| f.windows > 0) {
+            Some(f) => writeln!(
+                self.out,
+                ",{},{},{},{}",
+                (f.monobit == f.windows) as u8,
+                (f.poker == f.windows) as u8,
+                (f.runs == f.windows) as u8,
+                (f.long_runs == f.windows) as u8
+            )?,
+            None => writeln!(self.out, ",,,,")?,
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+pub fn collect_sample(
     source: &SourceKind,
     count: usize,
-    config: &CpuRngConfig,
+    config: &Config,
 ) -> Result<Vec<u8>, Error> {
+    let cpu = &config.cpu_rng;
+    let timeouts = &config.timeouts;
+    let cpu_deadline = entropy::timeout_deadline(timeouts.cpurng_ms);
     match source {
-        SourceKind::Hwrng => hwrng::read_hwrng(count),
-        SourceKind::Rdseed => cpurng::collect_rdseed(count, config.rdseed_retries),
-        SourceKind::Rdrand => cpurng::collect_rdrand(count, config.rdrand_retries),
-        SourceKind::Xstore => cpurng::collect_xstore(count, config.xstore_quality),
-        SourceKind::Haveged => haveged::read_haveged(count),
+        SourceKind::Hwrng => hwrng::read_hwrng_within(count, timeouts.hwrng_ms),
+        SourceKind::Serial => serial::read_serial(count, &config.serial),
+        SourceKind::Exec => exec::read_exec(count, &config.exec),
+        SourceKind::Vsock => vsock::read_vsock(count, &config.vsock),
+        SourceKind::Rdseed => {
+            cpurng::collect_rdseed_parallel(count, cpu.rdseed_retries, 1, cpu_deadline)
+        }
+        SourceKind::Rdrand => {
+            cpurng::collect_rdrand_parallel(count, cpu.rdrand_retries, 1, cpu_deadline)
+        }
+        SourceKind::Xstore => cpurng::collect_xstore_until(count, cpu.xstore_quality, cpu_deadline),
+        SourceKind::DevRandom => {
+            devrandom::read_devrandom(count, timeouts.devrandom_ms).map(|(_, bytes)| bytes)
+        }
+        SourceKind::Havege => havege::read_havege(count),
         SourceKind::Urandom => read_urandom(count),
-        SourceKind::Fallback => fallback::generate_fallback(count, config),
+        SourceKind::Fallback => fallback::generate_fallback_until(
+            count,
+            config,
+            entropy::timeout_deadline(timeouts.fallback_ms),
+        ),
+        SourceKind::Plugin(i) => plugin::read_plugin(&plugin::loaded()[*i], count, &config.plugins),
     }
 }
 
@@ -132,7 +468,7 @@ fn read_urandom(count: usize) -> Result<Vec<u8>, Error> {
     Ok(buf)
 }
 
-fn parse_duration(s: &str) -> Result<Duration, Error> {
+pub fn parse_duration(s: &str) -> Result<Duration, Error> {
     let s = s.trim();
     if s.is_empty() {
         return Err(Error::InvalidArgs("empty duration".into()));
@@ -161,7 +497,7 @@ fn parse_duration(s: &str) -> Result<Duration, Error> {
     Ok(Duration::from_secs(num * multiplier))
 }
 
-fn format_duration(d: Duration) -> String {
+pub fn format_duration(d: Duration) -> String {
     let secs = d.as_secs();
     if secs < 60 {
         format!("{}s", secs)
@@ -184,156 +520,175 @@ fn format_duration(d: Duration) -> String {
     }
 }
 
-fn format_throughput(bytes_per_sec: f64) -> String {
-    if bytes_per_sec >= 1_000_000.0 {
-        format!("{:.2} MB/s", bytes_per_sec / 1_000_000.0)
-    } else if bytes_per_sec >= 1_000.0 {
-        format!("{:.2} KB/s", bytes_per_sec / 1_000.0)
-    } else {
-        format!("{:.0} B/s", bytes_per_sec)
-    }
-}
-
-fn format_bytes(bytes: u64) -> String {
-    if bytes >= 1_000_000 {
-        format!("{:.2} MB", bytes as f64 / 1_000_000.0)
-    } else if bytes >= 1_000 {
-        format!("{:.2} KB", bytes as f64 / 1_000.0)
-    } else {
-        format!("{} B", bytes)
-    }
-}
-
-extern "C" fn signal_handler(_sig: libc::c_int) {
-    SHUTDOWN.store(true, Ordering::Relaxed);
-}
-
-fn install_signal_handlers() {
-    unsafe {
-        let mut sa: libc::sigaction = std::mem::zeroed();
-        sa.sa_sigaction = signal_handler as *const () as usize;
-        sa.sa_flags = libc::SA_RESTART;
-        libc::sigemptyset(&mut sa.sa_mask);
-        libc::sigaction(libc::SIGTERM, &sa, std::ptr::null_mut());
-        libc::sigaction(libc::SIGINT, &sa, std::ptr::null_mut());
-    }
-}
-
-fn probe_sources(cpu_config: &CpuRngConfig) -> Vec<SourceKind> {
-    let candidates = [
-        SourceKind::Hwrng,
-        SourceKind::Rdseed,
-        SourceKind::Rdrand,
-        SourceKind::Xstore,
-        SourceKind::Haveged,
-        SourceKind::Urandom,
-        SourceKind::Fallback,
-    ];
-
+/// Probes `sources`, returning those that delivered and, for the rest,
+/// why not.
+fn probe_sources(
+    sources: Vec<SourceKind>,
+    config: &Config,
+) -> (Vec<SourceKind>, Vec<(SourceKind, Error)>) {
     let mut available = Vec::new();
+    let mut unavailable = Vec::new();
 
-    for &kind in &candidates {
+    for kind in sources {
         eprint!("  {:10} ... ", kind.name());
-        match collect_sample(&kind, 32, cpu_config) {
+        match collect_sample(&kind, 32, config) {
             Ok(_) => {
                 eprintln!("[ok]");
                 available.push(kind);
             }
             Err(e) => {
                 eprintln!("[skip] {}", e);
+                unavailable.push((kind, e));
             }
         }
     }
 
-    available
+    (available, unavailable)
+}
+
+/// Per-source goal of `--samples-per-source` or `--bytes-per-source`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Samples(u64),
+    Bytes(u64),
+}
+
+impl Target {
+    fn from_args(args: &CheckArgs) -> Result<Option<Self>, Error> {
+        let target = match (args.samples_per_source, args.bytes_per_source) {
+            (Some(n), _) => Some(Target::Samples(n)),
+            (None, Some(n)) => Some(Target::Bytes(n)),
+            (None, None) => None,
+        };
+        if matches!(target, Some(Target::Samples(0) | Target::Bytes(0))) {
+            return Err(Error::InvalidArgs(
+                "per-source target must be greater than 0".into(),
+            ));
+        }
+        Ok(target)
+    }
+
+    /// How far `stat` is towards the target, from 0 to 1.
+    fn progress(&self, stat: &SourceStats) -> f64 {
+        let (done, goal) = match *self {
+            Target::Samples(n) => (stat.total_samples, n),
+            Target::Bytes(n) => (stat.total_bytes, n),
+        };
+        (done as f64 / goal as f64).min(1.0)
+    }
+
+    fn reached(&self, stat: &SourceStats) -> bool {
+        self.progress(stat) >= 1.0
+    }
+
+    fn describe(&self, units: Units) -> String {
+        match *self {
+            Target::Samples(n) => format!("{} samples per source", n),
+            Target::Bytes(n) => format!("{} per source", table::bytes(n, units)),
+        }
+    }
+}
+
+/// Consecutive failed samples after which a source is dropped from a
+/// target-driven check, which would otherwise never finish.
+const MAX_CONSECUTIVE_ERRORS: u32 = 50;
+
+/// True once any source has completed a FIPS window.
+fn fips_ran(stats_vec: &[(SourceKind, SourceStats)]) -> bool {
+    stats_vec.iter().any(|(_, s)| s.fips.windows > 0)
 }
 
+/// `goal` describes what ends the run, e.g. `1m`, and `pct` how far along it is.
 fn print_progress(
     stats_vec: &[(SourceKind, SourceStats)],
     elapsed: Duration,
-    total: Duration,
+    goal: &str,
+    pct: f64,
     do_fips: bool,
+    units: Units,
 ) {
-    let pct = 100.0 * elapsed.as_secs_f64() / total.as_secs_f64();
     let mut stderr = std::io::stderr().lock();
 
     writeln!(
         stderr,
-        "--- Progress ({} / {}, {:.1}%) ---",
+        "--- Progress ({} / {}, {}) ---",
         format_duration(elapsed),
-        format_duration(total),
-        pct
+        goal,
+        table::percent(pct, 1)
     )
     .ok();
 
+    let mut columns = vec![("Source", Align::Left), ("Samples", Align::Right)];
     if do_fips {
-        writeln!(
-            stderr,
-            "{:<12} {:>8} {:>10} {:>8} {:>12} {:>7}",
-            "Source", "Samples", "FIPS Pass%", "Shannon", "Throughput", "Errors"
-        )
-        .ok();
-    } else {
-        writeln!(
-            stderr,
-            "{:<12} {:>8} {:>8} {:>12} {:>7}",
-            "Source", "Samples", "Shannon", "Throughput", "Errors"
-        )
-        .ok();
+        columns.push(("FIPS Pass%", Align::Right));
     }
-
+    columns.extend([
+        ("Shannon", Align::Right),
+        ("Throughput", Align::Right),
+        ("Errors", Align::Right),
+    ]);
+    let mut t = Table::new(&columns);
     for (kind, stat) in stats_vec {
-        let throughput = format_throughput(stat.throughput_bytes_per_sec());
-        let shannon = stat.avg(stat.shannon_sum);
-
+        let mut row = vec![kind.name().to_string(), stat.total_samples.to_string()];
         if do_fips {
-            let fips_pct = stat.fips_pass_pct(stat.fips_all_pass);
-            writeln!(
-                stderr,
-                "{:<12} {:>8} {:>9.1}% {:>8.3} {:>12} {:>7}",
-                kind.name(),
-                stat.total_samples,
-                fips_pct,
-                shannon,
-                throughput,
-                stat.errors
-            )
-            .ok();
-        } else {
-            writeln!(
-                stderr,
-                "{:<12} {:>8} {:>8.3} {:>12} {:>7}",
-                kind.name(),
-                stat.total_samples,
-                shannon,
-                throughput,
-                stat.errors
-            )
-            .ok();
+            row.push(table::percent(stat.fips_pass_pct(stat.fips.all), 1));
         }
+        row.extend([
+            table::decimal(stat.avg(stat.shannon_sum), 3),
+            table::rate(stat.throughput_bytes_per_sec(), units),
+            stat.errors.to_string(),
+        ]);
+        t.row(row);
     }
-    writeln!(stderr).ok();
+    writeln!(stderr, "{}", t.render()).ok();
 }
 
-fn print_final_report(stats_vec: &[(SourceKind, SourceStats)], do_fips: bool) {
+fn print_final_report(stats_vec: &[(SourceKind, SourceStats)], do_fips: bool, units: Units) {
     // Per-source detailed results
     for (kind, stat) in stats_vec {
         println!("--- {} ({}) ---", kind.name(), kind.description());
         println!(
             "  Samples: {} | Bytes: {} | Throughput: {} | Errors: {}",
             stat.total_samples,
-            format_bytes(stat.total_bytes),
-            format_throughput(stat.throughput_bytes_per_sec()),
+            table::bytes(stat.total_bytes, units),
+            table::rate(stat.throughput_bytes_per_sec(), units),
             stat.errors
         );
+        if let (SourceKind::Hwrng, Some(q)) = (kind, hwrng::quality()) {
+            println!(
+                "  Declared:    rng_quality {}/{} ({} bits/byte)",
+                q,
+                hwrng::QUALITY_SCALE,
+                table::decimal(8.0 * q as f64 / hwrng::QUALITY_SCALE as f64, 3)
+            );
+        }
+        if stat.latency.count() > 0 {
+            let l = &stat.latency;
+            println!(
+                "  Latency:     min {}  p50 {}  p90 {}  p99 {}  max {}  mean {} {} {}",
+                table::millis(l.min(), 3),
+                table::millis(l.percentile(50.0), 3),
+                table::millis(l.percentile(90.0), 3),
+                table::millis(l.percentile(99.0), 3),
+                table::millis(l.max(), 3),
+                table::millis(l.mean(), 3),
+                table::plus_minus(),
+                table::millis(l.stddev(), 3)
+            );
+        }
 
-        if do_fips && stat.total_samples > 0 {
+        if do_fips && stat.fips.windows > 0 {
+            println!(
+                "  FIPS 140-2:  Monobit {}  Poker {}  Runs {}  Long Runs {}",
+                stat.fips_pass_ci(stat.fips.monobit),
+                stat.fips_pass_ci(stat.fips.poker),
+                stat.fips_pass_ci(stat.fips.runs),
+                stat.fips_pass_ci(stat.fips.long_runs)
+            );
             println!(
-                "  FIPS 140-2:  Monobit {:.1}%  Poker {:.1}%  Runs {:.1}%  Long Runs {:.1}%",
-                stat.fips_pass_pct(stat.fips_monobit_pass),
-                stat.fips_pass_pct(stat.fips_poker_pass),
-                stat.fips_pass_pct(stat.fips_runs_pass),
-                stat.fips_pass_pct(stat.fips_long_runs_pass)
+                "               All {} over {} windows, 95% CI",
+                stat.fips_pass_ci(stat.fips.all),
+                stat.fips.windows
             );
         }
 
@@ -341,17 +696,27 @@ fn print_final_report(stats_vec: &[(SourceKind, SourceStats)], do_fips: bool) {
             let chi = stat.avg(stat.chi_square_sum);
             let p = stats::chi_square_p_value(chi, 255.0);
             println!(
-                "  Entropy:     Shannon {:.3}   Min-ent {:.3}  Chi-sq {:.1} (p={:.2})",
-                stat.avg(stat.shannon_sum),
-                stat.avg(stat.min_entropy_sum),
-                chi,
-                p
+                "  Entropy:     Shannon {}   Min-ent {}  Chi-sq {} (p={})",
+                stat.avg_se(stat.shannon_sum, stat.shannon_sq_sum),
+                stat.avg_se(stat.min_entropy_sum, stat.min_entropy_sq_sum),
+                table::decimal(chi, 1),
+                table::decimal(p, 2)
             );
             println!(
-                "               Mean {:.2}     SerCorr {:.3}",
-                stat.avg(stat.mean_sum),
-                stat.avg(stat.serial_corr_sum)
+                "               Mean {}     SerCorr {}  Compress {}",
+                table::decimal(stat.avg(stat.mean_sum), 2),
+                table::decimal(stat.avg(stat.serial_corr_sum), 3),
+                table::decimal(stat.avg(stat.compression_sum), 3)
             );
+            if let Some((lag, r)) = stat.max_autocorrelation() {
+                println!(
+                    "               Max |AutoCorr| {} at lag {} (lags {}..={})",
+                    table::decimal(r.abs(), 4),
+                    lag,
+                    stat.first_lag,
+                    stat.first_lag + stat.autocorr_sums.len() - 1
+                );
+            }
         }
         println!();
     }
@@ -359,44 +724,36 @@ fn print_final_report(stats_vec: &[(SourceKind, SourceStats)], do_fips: bool) {
     // Comparison table (only if multiple sources)
     if stats_vec.len() > 1 {
         println!("--- Comparison ---");
+        let mut columns = vec![("Source", Align::Left), ("Throughput", Align::Right)];
         if do_fips {
-            println!(
-                "{:<12} {:>12} {:>10} {:>8} {:>8}",
-                "Source", "Throughput", "FIPS Pass%", "Shannon", "Min-ent"
-            );
-        } else {
-            println!(
-                "{:<12} {:>12} {:>8} {:>8}",
-                "Source", "Throughput", "Shannon", "Min-ent"
-            );
+            columns.push(("FIPS Pass% (95% CI)", Align::Right));
         }
-
+        columns.extend([
+            ("Shannon", Align::Right),
+            ("Min-ent", Align::Right),
+            ("p50", Align::Right),
+            ("p99", Align::Right),
+            ("Max", Align::Right),
+        ]);
+        let mut t = Table::new(&columns);
         for (kind, stat) in stats_vec {
-            let throughput = format_throughput(stat.throughput_bytes_per_sec());
-            let shannon = stat.avg(stat.shannon_sum);
-            let min_ent = stat.avg(stat.min_entropy_sum);
-
+            let mut row = vec![
+                kind.name().to_string(),
+                table::rate(stat.throughput_bytes_per_sec(), units),
+            ];
             if do_fips {
-                let fips_pct = stat.fips_pass_pct(stat.fips_all_pass);
-                println!(
-                    "{:<12} {:>12} {:>9.1}% {:>8.3} {:>8.3}",
-                    kind.name(),
-                    throughput,
-                    fips_pct,
-                    shannon,
-                    min_ent
-                );
-            } else {
-                println!(
-                    "{:<12} {:>12} {:>8.3} {:>8.3}",
-                    kind.name(),
-                    throughput,
-                    shannon,
-                    min_ent
-                );
+                row.push(stat.fips_pass_ci(stat.fips.all));
             }
+            row.extend([
+                table::decimal(stat.avg(stat.shannon_sum), 3),
+                table::decimal(stat.avg(stat.min_entropy_sum), 3),
+                table::millis(stat.latency.percentile(50.0), 3),
+                table::millis(stat.latency.percentile(99.0), 3),
+                table::millis(stat.latency.max(), 3),
+            ]);
+            t.row(row);
         }
-        println!();
+        println!("{}", t.render());
 
         // Verdict
         let best_throughput = stats_vec
@@ -422,132 +779,817 @@ fn print_final_report(stats_vec: &[(SourceKind, SourceStats)], do_fips: bool) {
             println!(
                 "  Highest throughput:   {} ({})",
                 kind.name(),
-                format_throughput(stat.throughput_bytes_per_sec())
+                table::rate(stat.throughput_bytes_per_sec(), units)
             );
         }
         if let Some((kind, stat)) = best_min_entropy {
             println!(
-                "  Highest min-entropy:  {} ({:.3} bits/byte)",
+                "  Highest min-entropy:  {} ({} bits/byte)",
                 kind.name(),
-                stat.avg(stat.min_entropy_sum)
+                table::decimal(stat.avg(stat.min_entropy_sum), 3)
             );
         }
     }
 }
 
-pub fn run(args: &CheckArgs, cpu_config: &CpuRngConfig) -> Result<(), Error> {
-    let duration = parse_duration(&args.duration)?;
-    let do_fips = args.sample_size >= 2500;
+fn print_drift(stats_vec: &[(SourceKind, SourceStats)]) {
+    for (kind, stat) in stats_vec {
+        let Some(ref drift) = stat.drift else {
+            continue;
+        };
+        if drift.windows.is_empty() {
+            continue;
+        }
+        println!("--- {} drift ---", kind.name());
+        let mut t = Table::new(&[
+            ("Window", Align::Left),
+            ("Samples", Align::Right),
+            ("Min-Ent", Align::Right),
+            ("", Align::Right),
+            ("FIPS", Align::Right),
+            ("", Align::Right),
+            ("", Align::Left),
+        ])
+        .indent(2);
+        for w in &drift.windows {
+            t.row(vec![
+                drift.span(w.index),
+                w.samples.to_string(),
+                table::decimal(w.min_entropy, 3),
+                drift::format_z(w.min_entropy_z),
+                w.fips_pass_pct
+                    .map_or_else(|| "-".into(), |p| table::percent(p, 1)),
+                drift::format_z(w.fips_z),
+                if w.drifted {
+                    "DRIFT".into()
+                } else {
+                    String::new()
+                },
+            ]);
+        }
+        print!("{}", t.render());
+        println!();
+    }
+}
 
-    if !do_fips {
-        eprintln!(
-            "Warning: sample_size {} < 2500 bytes, FIPS 140-2 tests will be skipped",
-            args.sample_size
+fn print_paired(sources: &[SourceKind], rounds: &Rounds) {
+    let names: Vec<&str> = sources.iter().map(|s| s.name()).collect();
+    let comparisons = rounds.compare();
+    let alpha = paired::alpha(&comparisons);
+    let p = |p: f64| {
+        let mark = if p < alpha { "*" } else { "" };
+        format!("{}{}", paired::format_p(p), mark)
+    };
+    println!("--- Paired comparison ({} rounds) ---", rounds.len());
+    let mut t = Table::new(&[
+        ("Pair", Align::Left),
+        ("Rounds", Align::Right),
+        ("Latency Δ", Align::Right),
+        ("p", Align::Right),
+        ("Min-Ent Δ", Align::Right),
+        ("p", Align::Right),
+        ("FIPS Fails", Align::Right),
+        ("p", Align::Right),
+    ])
+    .indent(2);
+    for c in &comparisons {
+        let mut row = vec![
+            format!("{} - {}", names[c.a], names[c.b]),
+            c.rounds.to_string(),
+        ];
+        match c.latency {
+            Some(r) => row.extend([
+                format!("{} ms", table::signed(r.median * 1000.0, 3)),
+                p(r.p),
+            ]),
+            None => row.extend(["-".into(), "-".into()]),
+        }
+        match c.min_entropy {
+            Some(r) => row.extend([table::signed(r.median, 4), p(r.p)]),
+            None => row.extend(["-".into(), "-".into()]),
+        }
+        match c.fips {
+            Some(m) => row.extend([format!("{} / {}", m.only_a, m.only_b), p(m.p)]),
+            None => row.extend(["-".into(), "-".into()]),
+        }
+        t.row(row);
+    }
+    print!("{}", t.render());
+    println!(
+        "  Δ is the median of first minus second per round; * is significant at {} after Bonferroni correction for {} tests",
+        table::decimal(paired::ALPHA, 2),
+        paired::tests(&comparisons)
+    );
+    let verdicts = paired::verdicts(&comparisons, &names);
+    if verdicts.is_empty() {
+        println!("  No significant differences between sources.");
+    }
+    for v in verdicts {
+        println!("  {}", v);
+    }
+    println!();
+}
+
+fn print_histograms(stats_vec: &[(SourceKind, SourceStats)]) {
+    let color = std::io::stdout().is_terminal();
+    let beyond = format!(
+        "{}{}{}",
+        table::plus_minus(),
+        histogram::Z_HIGHLIGHT,
+        table::sigma()
+    );
+    for (kind, stat) in stats_vec {
+        let h = &stat.histogram;
+        if h.total == 0 {
+            continue;
+        }
+        println!(
+            "--- {} byte histogram ({} bytes, {} expected per value) ---",
+            kind.name(),
+            h.total,
+            table::decimal(h.expected(), 1)
         );
+        print!("{}", h.render(color));
+        let outliers = h.outliers();
+        if outliers.is_empty() {
+            println!("  No bucket beyond {}", beyond);
+        } else {
+            let list: Vec<String> = outliers
+                .iter()
+                .take(8)
+                .map(|&b| {
+                    format!(
+                        "0x{:02X} ({}{})",
+                        b,
+                        table::signed(h.z_score(b), 1),
+                        table::sigma()
+                    )
+                })
+                .collect();
+            println!(
+                "  {} bucket(s) beyond {}: {}{}",
+                outliers.len(),
+                beyond,
+                list.join(", "),
+                if outliers.len() > 8 { ", ..." } else { "" }
+            );
+        }
+        println!();
     }
+}
 
-    install_signal_handlers();
+fn source_results(stats_vec: &[(SourceKind, SourceStats)], do_fips: bool) -> Vec<SourceResult> {
+    stats_vec
+        .iter()
+        .filter(|(_, stat)| stat.total_samples > 0)
+        .map(|(kind, stat)| SourceResult {
+            name: kind.name().to_string(),
+            samples: stat.total_samples,
+            throughput: stat.throughput_bytes_per_sec(),
+            shannon: stat.avg(stat.shannon_sum),
+            min_entropy: stat.avg(stat.min_entropy_sum),
+            chi_square: stat.avg(stat.chi_square_sum),
+            fips_pass_pct: (do_fips && stat.fips.windows > 0)
+                .then(|| stat.fips_pass_pct(stat.fips.all)),
+        })
+        .collect()
+}
 
-    eprintln!("Probing entropy sources...");
-    let sources = probe_sources(cpu_config);
+fn print_baseline_comparison(
+    path: &Path,
+    old: &Baseline,
+    deltas: &[Delta],
+    sample_size: usize,
+    units: Units,
+) {
+    println!(
+        "--- Baseline comparison ({}, {}) ---",
+        path.display(),
+        report::format_utc(old.created_unix)
+    );
+    let arrow = table::arrow();
+    let kernel = baseline::kernel_release();
+    if kernel != old.kernel {
+        println!("  Kernel:     {} {} {}", old.kernel, arrow, kernel);
+    }
+    let microcode = baseline::microcode_revision();
+    if microcode != old.microcode {
+        println!(
+            "  Microcode:  {} {} {}",
+            old.microcode.as_deref().unwrap_or("unknown"),
+            arrow,
+            microcode.as_deref().unwrap_or("unknown")
+        );
+    }
+    if sample_size != old.sample_size {
+        println!(
+            "  Note: sample size {} differs from the baseline's {}; per-sample estimates are not directly comparable",
+            sample_size, old.sample_size
+        );
+    }
+    if deltas.is_empty() {
+        println!("  No source in common with the baseline");
+    }
+    let mut t = Table::new(&[
+        ("Source", Align::Left),
+        ("Metric", Align::Left),
+        ("Baseline", Align::Right),
+        ("", Align::Left),
+        ("Now", Align::Right),
+        ("Change", Align::Right),
+        ("", Align::Left),
+    ]);
+    for d in deltas {
+        let (old_s, new_s, change) = match d.metric {
+            "throughput" => (
+                table::rate(d.old, units),
+                table::rate(d.new, units),
+                format!("{}%", table::signed(d.change(), 1)),
+            ),
+            "fips-pass" => (
+                table::percent(d.old, 1),
+                table::percent(d.new, 1),
+                format!("{}pp", table::signed(d.change(), 1)),
+            ),
+            _ => (
+                table::decimal(d.old, 3),
+                table::decimal(d.new, 3),
+                table::signed(d.change(), 3),
+            ),
+        };
+        t.row(vec![
+            d.source.clone(),
+            d.metric.to_string(),
+            old_s,
+            arrow.to_string(),
+            new_s,
+            change,
+            if d.regressed { "REGRESSED" } else { "" }.to_string(),
+        ]);
+    }
+    if !deltas.is_empty() {
+        print!("{}", t.render());
+    }
+    println!();
+}
 
-    let sources: Vec<SourceKind> = if let Some(ref names) = args.sources {
-        sources
-            .into_iter()
-            .filter(|s| names.iter().any(|n| n.eq_ignore_ascii_case(s.name())))
-            .collect()
-    } else {
-        sources
+fn write_html_report(
+    path: &Path,
+    stats_vec: &[(SourceKind, SourceStats)],
+    do_fips: bool,
+    meta: &ReportMeta,
+    units: Units,
+) -> Result<(), Error> {
+    let rows: Vec<ReportRow> = stats_vec
+        .iter()
+        .map(|(kind, stat)| ReportRow {
+            name: kind.name().to_string(),
+            description: kind.description().to_string(),
+            samples: stat.total_samples,
+            bytes: table::bytes(stat.total_bytes, units),
+            throughput: stat.throughput_bytes_per_sec(),
+            throughput_label: table::rate(stat.throughput_bytes_per_sec(), units),
+            fips_pass_pct: (do_fips && stat.fips.windows > 0)
+                .then(|| stat.fips_pass_pct(stat.fips.all)),
+            shannon: stat.avg(stat.shannon_sum),
+            min_entropy: stat.avg(stat.min_entropy_sum),
+            chi_square: stat.avg(stat.chi_square_sum),
+            errors: stat.errors,
+        })
+        .collect();
+    report::write_html(path, meta, &rows)?;
+    eprintln!("HTML report written to {}", path.display());
+    Ok(())
+}
+
+pub fn run(args: &CheckArgs, config: &Config) -> Result<(), Error> {
+    if args.cpu_rng_diff {
+        let duration = args.duration.as_deref().map(parse_duration).transpose()?;
+        let units = args.units.unwrap_or(config.display.units);
+        let result = cpudiff::run(duration.unwrap_or(Duration::from_secs(10)), units);
+        if args.env {
+            println!();
+            Environment::collect().print();
+        }
+        return result;
+    }
+    let target = Target::from_args(args)?;
+    let duration = match (&args.duration, target) {
+        (Some(d), _) => Some(parse_duration(d)?),
+        (None, Some(_)) => None,
+        (None, None) => Some(Duration::from_secs(60)),
+    };
+    if args.sample_size < FIPS_WINDOW_BYTES {
+        eprintln!(
+            "Note: sample_size {} < {} bytes, FIPS 140-2 tests will run over windows aggregated across samples",
+            args.sample_size, FIPS_WINDOW_BYTES
+        );
+    }
+
+    if args.min_lag == 0 || args.min_lag > args.max_lag || args.max_lag >= args.sample_size {
+        return Err(Error::InvalidArgs(format!(
+            "autocorrelation lags must satisfy 1 <= min-lag <= max-lag < sample-size (got {}..={})",
+            args.min_lag, args.max_lag
+        )));
+    }
+    let lags = args.min_lag..=args.max_lag;
+    let drift_window = args
+        .drift_window
+        .as_deref()
+        .map(parse_duration)
+        .transpose()?;
+    if args.drift_sigma.is_nan() || args.drift_sigma <= 0.0 {
+        return Err(Error::InvalidArgs("--drift-sigma must be > 0".into()));
+    }
+    let units = args.units.unwrap_or(config.display.units);
+    let environment = args.env.then(Environment::collect);
+    let old_baseline = args.baseline.as_deref().map(baseline::load).transpose()?;
+    let capture_key = args
+        .capture_key
+        .as_deref()
+        .map(|path| capture::load_or_create_key(path, config))
+        .transpose()?;
+    let mut resumed = args.resume.as_deref().map(load_state).transpose()?;
+    let filter = match resumed {
+        Some(ref saved) => {
+            if saved.sample_size != args.sample_size || saved.lags != lags {
+                return Err(Error::InvalidArgs(format!(
+                    "the saved check used --sample-size {} --min-lag {} --max-lag {}",
+                    saved.sample_size,
+                    saved.lags.start(),
+                    saved.lags.end()
+                )));
+            }
+            let names: Vec<String> = saved.sources.iter().map(|(n, _)| n.clone()).collect();
+            SourceFilter::parse(&names, config)?
+        }
+        None => SourceFilter::parse(args.sources.as_deref().unwrap_or_default(), config)?,
     };
 
+    signals::install(&[Signal::Term, Signal::Int]);
+
+    eprintln!("Probing entropy sources...");
+    let (sources, unavailable) = probe_sources(filter.select(config), config);
+
+    if filter.is_explicit() && !unavailable.is_empty() {
+        eprintln!("\nRequested sources unavailable:");
+        for (kind, e) in &unavailable {
+            eprintln!("  {}: {}", kind.name(), e);
+        }
+    }
+    if resumed.is_some() && !unavailable.is_empty() {
+        return Err(Error::NoEntropy(
+            "cannot resume without every source of the saved check".into(),
+        ));
+    }
     if sources.is_empty() {
-        return Err(Error::NoEntropy("no entropy sources available".into()));
+        return Err(Error::NoEntropy(if filter.is_explicit() {
+            "none of the requested sources is available".into()
+        } else {
+            "no entropy sources available".into()
+        }));
+    }
+    if args.paired && sources.len() < 2 {
+        return Err(Error::InvalidArgs(format!(
+            "--paired needs two available sources, found only {}",
+            sources[0].name()
+        )));
     }
 
     let source_list: Vec<&str> = sources.iter().map(|s| s.name()).collect();
+    let goal = match (target, duration) {
+        (Some(t), Some(d)) => format!("{}, at most {}", t.describe(units), format_duration(d)),
+        (Some(t), None) => t.describe(units),
+        (None, Some(d)) => format_duration(d),
+        (None, None) => unreachable!("a check without a target defaults to 1m"),
+    };
     eprintln!(
-        "\nStatistical check: sources=[{}], duration={}, sample_size={} bytes",
+        "\nStatistical check: sources=[{}], until={}, sample_size={} bytes",
         source_list.join(", "),
-        format_duration(duration),
+        goal,
         args.sample_size
     );
+    let previous = resumed
+        .as_ref()
+        .map_or(Duration::ZERO, |saved| saved.elapsed);
+    if let Some(ref path) = args.resume {
+        eprintln!(
+            "Resuming {} after {}",
+            path.display(),
+            format_duration(previous)
+        );
+    }
     eprintln!();
 
-    let mut stats_vec: Vec<(SourceKind, SourceStats)> =
-        sources.iter().map(|&s| (s, SourceStats::new())).collect();
+    let mut stats_vec: Vec<(SourceKind, SourceStats)> = sources
+        .iter()
+        .map(|&s| {
+            let saved = resumed.as_mut().and_then(|saved| {
+                let i = saved.sources.iter().position(|(n, _)| s.is_named(n))?;
+                Some(saved.sources.swap_remove(i).1)
+            });
+            let mut stat = saved.unwrap_or_else(|| SourceStats::new(&lags));
+            stat.drift = drift_window.map(|w| Drift::new(w, args.drift_sigma));
+            (s, stat)
+        })
+        .collect();
+
+    let mut sample_log = args
+        .samples_csv
+        .as_deref()
+        .map(SampleLog::open)
+        .transpose()?;
 
     let start = Instant::now();
-    let deadline = start + duration;
+    let deadline = duration.map(|d| start + d.saturating_sub(previous));
     let mut last_report = start;
+    // Sources dropped after MAX_CONSECUTIVE_ERRORS, and their current streaks
+    let mut dropped = vec![false; sources.len()];
+    let mut streaks = vec![0u32; sources.len()];
+    let mut rounds = args.paired.then(|| Rounds::new(sources.len()));
+    let done = |stats_vec: &[(SourceKind, SourceStats)], dropped: &[bool]| {
+        target.is_some_and(|t| {
+            stats_vec
+                .iter()
+                .zip(dropped)
+                .all(|((_, stat), dropped)| *dropped || t.reached(stat))
+        })
+    };
 
     'outer: loop {
-        for i in 0..sources.len() {
-            if SHUTDOWN.load(Ordering::Relaxed) || Instant::now() >= deadline {
+        // Paired rounds rotate the first source, so no source always goes
+        // first, and keep sampling sources that reached the target
+        let first = rounds.as_ref().map_or(0, Rounds::len);
+        if let Some(ref mut rounds) = rounds {
+            rounds.start();
+        }
+        for k in 0..sources.len() {
+            let i = (first + k) % sources.len();
+            if signals::shutdown_requested()
+                || deadline.is_some_and(|d| Instant::now() >= d)
+                || done(&stats_vec, &dropped)
+            {
                 break 'outer;
             }
+            let reached = target.is_some_and(|t| t.reached(&stats_vec[i].1));
+            if dropped[i] || reached && !args.paired {
+                continue;
+            }
 
             let source = &sources[i];
             let sample_start = Instant::now();
 
-            match collect_sample(source, args.sample_size, cpu_config) {
+            match collect_sample(source, args.sample_size, config) {
                 Ok(data) => {
                     let elapsed = sample_start.elapsed();
                     let stat = &mut stats_vec[i].1;
                     stat.total_samples += 1;
                     stat.total_bytes += data.len() as u64;
                     stat.total_time += elapsed;
+                    stat.latency.record(elapsed);
 
-                    if do_fips {
-                        let fips_data: &[u8; 2500] = (&data[..2500]).try_into().unwrap();
-                        let fips = stats::fips_suite(fips_data);
-                        if fips.monobit.passed {
-                            stat.fips_monobit_pass += 1;
-                        }
-                        if fips.poker.passed {
-                            stat.fips_poker_pass += 1;
-                        }
-                        if fips.runs.passed {
-                            stat.fips_runs_pass += 1;
+                    let mut fips = FipsTally::default();
+                    stat.fips_window.push(&data, |window| {
+                        let result = stats::fips_suite(window);
+                        for t in [
+                            &result.monobit,
+                            &result.poker,
+                            &result.runs,
+                            &result.long_runs,
+                        ] {
+                            if !t.passed {
+                                log::debug!(
+                                    "{}: FIPS {} failed: {} (value {:.2}, bounds {}..{})",
+                                    source.name(),
+                                    t.name,
+                                    t.detail,
+                                    t.value,
+                                    t.range.0,
+                                    t.range.1
+                                );
+                            }
                         }
-                        if fips.long_runs.passed {
-                            stat.fips_long_runs_pass += 1;
-                        }
-                        if fips.all_passed() {
-                            stat.fips_all_pass += 1;
-                        }
-                    }
+                        fips.record(&result);
+                    });
+                    stat.fips.merge(&fips);
+                    stat.histogram.add(&data);
 
-                    let est = stats::entropy_estimates(&data);
+                    let mut stream = EntropyStream::new(*lags.end());
+                    stream.update(&data);
+                    let est = stream.estimates();
                     stat.shannon_sum += est.shannon;
                     stat.min_entropy_sum += est.min_entropy;
+                    stat.shannon_sq_sum += est.shannon * est.shannon;
+                    stat.min_entropy_sq_sum += est.min_entropy * est.min_entropy;
                     stat.chi_square_sum += est.chi_square;
                     stat.mean_sum += est.mean;
                     stat.serial_corr_sum += est.serial_correlation;
+                    stat.compression_sum += est.compression_ratio;
+                    for (sum, r) in stat
+                        .autocorr_sums
+                        .iter_mut()
+                        .zip(stream.autocorrelation_spectrum(lags.clone()))
+                    {
+                        *sum += r;
+                    }
+                    if let Some(ref mut drift) = stat.drift {
+                        if let Some(w) = drift.record(start.elapsed(), est.min_entropy, &fips) {
+                            if w.drifted {
+                                eprintln!("{}: drift in {}", source.name(), drift.describe(&w));
+                            }
+                        }
+                    }
+
+                    if let Some(ref mut rounds) = rounds {
+                        rounds.record(
+                            i,
+                            Sample {
+                                latency: elapsed,
+                                min_entropy: est.min_entropy,
+                                fips: (fips.windows > 0).then_some(fips.all == fips.windows),
+                            },
+                        );
+                    }
+
+                    if let Some(ref mut log) = sample_log {
+                        log.record(*source, data.len(), elapsed, &est, Some(&fips))?;
+                    }
+                    streaks[i] = 0;
                 }
-                Err(_) => {
+                // A read cut short by Ctrl-C isn't a source failure
+                Err(_) if signals::shutdown_requested() => break 'outer,
+                Err(e) => {
                     stats_vec[i].1.errors += 1;
+                    streaks[i] += 1;
+                    if target.is_some() && streaks[i] >= MAX_CONSECUTIVE_ERRORS {
+                        eprintln!(
+                            "{}: dropped after {} failed samples in a row: {}",
+                            source.name(),
+                            streaks[i],
+                            e
+                        );
+                        dropped[i] = true;
+                    }
                 }
             }
 
             if last_report.elapsed().as_secs() >= args.report_interval {
-                print_progress(&stats_vec, start.elapsed(), duration, do_fips);
+                let elapsed = previous + start.elapsed();
+                let time_pct = duration.map(|d| elapsed.as_secs_f64() / d.as_secs_f64());
+                let target_pct = target.map(|t| {
+                    stats_vec
+                        .iter()
+                        .zip(&dropped)
+                        .filter(|(_, dropped)| !**dropped)
+                        .map(|((_, stat), _)| t.progress(stat))
+                        .fold(1.0, f64::min)
+                });
+                let pct = time_pct.into_iter().chain(target_pct).fold(0.0, f64::max);
+                print_progress(
+                    &stats_vec,
+                    elapsed,
+                    &goal,
+                    100.0 * pct,
+                    fips_ran(&stats_vec),
+                    units,
+                );
+                if let Some(ref mut log) = sample_log {
+                    log.flush()?;
+                }
                 last_report = Instant::now();
             }
         }
     }
 
-    let total_elapsed = start.elapsed();
+    let total_elapsed = previous + start.elapsed();
 
-    if SHUTDOWN.load(Ordering::Relaxed) {
+    if signals::shutdown_requested() {
         eprintln!(
-            "\nInterrupted after {} — printing partial results\n",
-            format_duration(total_elapsed)
+            "\nInterrupted after {} {} printing partial results\n",
+            format_duration(total_elapsed),
+            table::dash()
         );
+        if let Some(ref path) = args.save_state {
+            let state = SavedCheck {
+                version: STATE_VERSION,
+                sample_size: args.sample_size,
+                lags: lags.clone(),
+                elapsed: total_elapsed,
+                sources: stats_vec
+                    .iter()
+                    .map(|(kind, stat)| (kind.name().to_string(), stat))
+                    .collect(),
+            };
+            save_state(path, &state)?;
+            eprintln!("State saved; continue with --resume {}\n", path.display());
+        }
     } else {
         eprintln!("\nCompleted {} check\n", format_duration(total_elapsed));
     }
 
-    print_final_report(&stats_vec, do_fips);
+    if let Some(ref mut log) = sample_log {
+        log.flush()?;
+    }
 
-    Ok(())
+    for (kind, stat) in stats_vec.iter_mut() {
+        if let Some(ref mut drift) = stat.drift {
+            if let Some(w) = drift.finish() {
+                if w.drifted {
+                    eprintln!("{}: drift in {}", kind.name(), drift.describe(&w));
+                }
+            }
+        }
+    }
+
+    let do_fips = fips_ran(&stats_vec);
+    print_final_report(&stats_vec, do_fips, units);
+    if drift_window.is_some() {
+        print_drift(&stats_vec);
+    }
+    if let Some(ref rounds) = rounds {
+        print_paired(&sources, rounds);
+    }
+
+    if args.histogram {
+        print_histograms(&stats_vec);
+    }
+    let histograms: Vec<(&str, &Histogram)> = stats_vec
+        .iter()
+        .map(|(kind, stat)| (kind.name(), &stat.histogram))
+        .collect();
+    if let Some(ref path) = args.histogram_csv {
+        histogram::write_csv(path, &histograms)?;
+        eprintln!("Histogram CSV written to {}", path.display());
+    }
+    if let Some(ref path) = args.histogram_json {
+        histogram::write_json(path, &histograms)?;
+        eprintln!("Histogram JSON written to {}", path.display());
+    }
+
+    if let Some(ref path) = args.report_html {
+        let meta = ReportMeta {
+            generated_unix: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            duration: format_duration(total_elapsed),
+            sample_size: args.sample_size,
+            interrupted: signals::shutdown_requested(),
+            environment: environment.clone(),
+        };
+        write_html_report(path, &stats_vec, do_fips, &meta, units)?;
+    }
+
+    let results = source_results(&stats_vec, do_fips);
+    if let Some(ref path) = args.save_baseline {
+        let saved = Baseline {
+            created_unix: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            sample_size: args.sample_size,
+            kernel: baseline::kernel_release(),
+            microcode: baseline::microcode_revision(),
+            sources: results.clone(),
+        };
+        baseline::save(path, &saved)?;
+        eprintln!("Baseline written to {}", path.display());
+    }
+    let mut regression = None;
+    if let (Some(old), Some(path)) = (old_baseline, args.baseline.as_deref()) {
+        let deltas = baseline::compare(&results, &old, args.baseline_threshold);
+        print_baseline_comparison(path, &old, &deltas, args.sample_size, units);
+        let regressed: Vec<&str> = deltas
+            .iter()
+            .filter(|d| d.regressed)
+            .map(|d| d.source.as_str())
+            .collect();
+        if !regressed.is_empty() {
+            let mut names = regressed;
+            names.dedup();
+            regression = Some(Error::NoEntropy(format!(
+                "regression against baseline in {}",
+                names.join(", ")
+            )));
+        }
+    }
+
+    let drifted: Vec<&str> = stats_vec
+        .iter()
+        .filter(|(_, stat)| stat.drift.as_ref().is_some_and(Drift::drifted))
+        .map(|(kind, _)| kind.name())
+        .collect();
+    if regression.is_none() && !drifted.is_empty() {
+        regression = Some(Error::NoEntropy(format!(
+            "drift detected in {}",
+            drifted.join(", ")
+        )));
+    }
+
+    if let Some(ref key) = capture_key {
+        let saved_state = args.save_state.as_deref().filter(|p| p.exists());
+        let written: Vec<&Path> = [
+            args.samples_csv.as_deref(),
+            args.report_html.as_deref(),
+            args.histogram_csv.as_deref(),
+            args.histogram_json.as_deref(),
+            args.save_baseline.as_deref(),
+            saved_state,
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        capture::seal(key, &written)?;
+        if !written.is_empty() {
+            eprintln!("Tagged {} files in {}", written.len(), capture::MANIFEST);
+        }
+    }
+
+    if let Some(ref env) = environment {
+        env.print();
+    }
+    regression.map_or(Ok(()), Err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(names: &[&str]) -> Result<Vec<SourceKind>, Error> {
+        let config = Config::default();
+        let names: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+        Ok(SourceFilter::parse(&names, &config)?.select(&config))
+    }
+
+    #[test]
+    fn test_source_filter() {
+        assert_eq!(
+            filter(&["rdrand", "hwrng", "rdrand"]).unwrap(),
+            [SourceKind::Rdrand, SourceKind::Hwrng]
+        );
+        assert_eq!(filter(&["haveged"]).unwrap(), [SourceKind::DevRandom]);
+
+        let rest = filter(&["!fallback", "!urandom"]).unwrap();
+        assert_eq!(rest.len(), SourceKind::ALL.len() - 2);
+        assert!(!rest.contains(&SourceKind::Fallback));
+        let all_but = filter(&["all", "!hwrng"]).unwrap();
+        assert_eq!(all_but.len(), SourceKind::ALL.len() - 1);
+        assert_eq!(all_but[0], SourceKind::Serial);
+
+        let typo = filter(&["rdssed"]).unwrap_err().to_string();
+        assert!(typo.contains("did you mean 'rdseed'"), "{}", typo);
+        assert!(filter(&["hwrng", "!hwrng"]).is_err());
+        assert!(filter(&[""]).is_err());
+    }
+
+    #[test]
+    fn test_target_progress() {
+        let mut stat = SourceStats::new(&(1..=1));
+        stat.total_samples = 5;
+        stat.total_bytes = 4096;
+        assert_eq!(Target::Samples(10).progress(&stat), 0.5);
+        assert!(!Target::Samples(10).reached(&stat));
+        assert!(Target::Bytes(4096).reached(&stat));
+        assert_eq!(Target::Bytes(1024).progress(&stat), 1.0);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("rdssed", "rdseed"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("fallback", "fallback"), 0);
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut stat = SourceStats::new(&(1..=4));
+        stat.total_samples = 3;
+        stat.total_time = Duration::from_millis(1500);
+        stat.latency.record(Duration::from_micros(40));
+        stat.histogram.add(&[7; 100]);
+        stat.fips_window.push(&[1; 100], |_| {});
+        stat.autocorr_sums[3] = 0.25;
+
+        let path = std::env::temp_dir().join("mixrand_test_check_state.json");
+        let state = SavedCheck {
+            version: STATE_VERSION,
+            sample_size: 2500,
+            lags: 1..=4,
+            elapsed: Duration::from_secs(90),
+            sources: vec![("urandom".to_string(), &stat)],
+        };
+        save_state(&path, &state).unwrap();
+        let loaded = load_state(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.elapsed, Duration::from_secs(90));
+        let (name, saved) = &loaded.sources[0];
+        assert_eq!(name, "urandom");
+        assert_eq!(saved.total_samples, 3);
+        assert_eq!(saved.total_time, Duration::from_millis(1500));
+        assert_eq!(saved.latency.count(), 1);
+        assert_eq!(saved.histogram.counts[7], 100);
+        assert_eq!(saved.fips_window.pending(), 100);
+        assert_eq!(saved.autocorr_sums, vec![0.0, 0.0, 0.0, 0.25]);
+    }
 }
@@ -0,0 +1,172 @@
+//! `--checksum`: a digest of exactly the bytes written, so whoever receives
+//! a key or an entropy file can check it arrived intact.
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use blake2::Blake2b512;
+use clap::ValueEnum;
+use sha2::{Digest, Sha256};
+
+use crate::split::Part;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Algorithm {
+    /// SHA-256, as `sha256sum` checks
+    Sha256,
+    /// BLAKE2b-512, as `b2sum` checks
+    Blake2b,
+}
+
+impl Algorithm {
+    /// Name in BSD-style lines, as `sha256sum --tag` and `b2sum --tag` print.
+    fn tag(self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "SHA256",
+            Algorithm::Blake2b => "BLAKE2b",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Blake2b => "b2",
+        }
+    }
+}
+
+enum State {
+    Sha256(Sha256),
+    Blake2b(Blake2b512),
+}
+
+/// Passes writes through to `inner`, digesting the bytes it accepted.
+pub struct Tee<W> {
+    inner: W,
+    state: Option<State>,
+}
+
+impl<W: Write> Tee<W> {
+    /// Without an algorithm this is a plain pass-through.
+    pub fn new(inner: W, algorithm: Option<Algorithm>) -> Self {
+        let state = algorithm.map(|a| match a {
+            Algorithm::Sha256 => State::Sha256(Sha256::new()),
+            Algorithm::Blake2b => State::Blake2b(Blake2b512::new()),
+        });
+        Self { inner, state }
+    }
+
+    /// Lowercase hex digest of everything written, if an algorithm was set.
+    pub fn finish(self) -> Option<String> {
+        let digest = match self.state? {
+            State::Sha256(h) => h.finalize().to_vec(),
+            State::Blake2b(h) => h.finalize().to_vec(),
+        };
+        Some(crate::output::to_hex(&digest))
+    }
+}
+
+impl<W: Write> Write for Tee<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        match &mut self.state {
+            Some(State::Sha256(h)) => h.update(&buf[..n]),
+            Some(State::Blake2b(h)) => h.update(&buf[..n]),
+            None => {}
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// `FILE.sha256` or `FILE.b2`.
+pub fn sidecar_path(algorithm: Algorithm, output_file: &Path) -> PathBuf {
+    let mut name = output_file.as_os_str().to_owned();
+    name.push(".");
+    name.push(algorithm.extension());
+    PathBuf::from(name)
+}
+
+/// Prints `digest` to stderr as `sha256sum --tag` would (`-` for stdout)
+/// and, with `sidecar`, writes it next to `output_file` in the untagged
+/// format, so `sha256sum -c FILE.sha256` checks it from that directory.
+pub fn report(
+    algorithm: Algorithm,
+    digest: &str,
+    output_file: Option<&Path>,
+    sidecar: bool,
+) -> io::Result<()> {
+    let shown = output_file.map_or("-".into(), |p| p.display().to_string());
+    eprintln!("{} ({}) = {}", algorithm.tag(), shown, digest);
+    if let (true, Some(path)) = (sidecar, output_file) {
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        std::fs::write(
+            sidecar_path(algorithm, path),
+            format!("{}  {}\n", digest, name.to_string_lossy()),
+        )?;
+    }
+    Ok(())
+}
+
+/// `--split-size`: prints each part's digest as [`report`] does when
+/// `print`, and writes them all to FILE.sha256 (or FILE.b2) next to
+/// `output_file`, so `sha256sum -c FILE.sha256` checks every part.
+pub fn report_parts(
+    algorithm: Algorithm,
+    parts: &[Part],
+    output_file: &Path,
+    print: bool,
+) -> io::Result<()> {
+    let mut manifest = String::new();
+    for part in parts {
+        if print {
+            eprintln!(
+                "{} ({}) = {}",
+                algorithm.tag(),
+                part.path.display(),
+                part.digest
+            );
+        }
+        let name = part.path.file_name().unwrap_or(part.path.as_os_str());
+        manifest.push_str(&format!("{}  {}\n", part.digest, name.to_string_lossy()));
+    }
+    std::fs::write(sidecar_path(algorithm, output_file), manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digests_what_was_written() {
+        let mut tee = Tee::new(Vec::new(), Some(Algorithm::Sha256));
+        tee.write_all(b"ab").unwrap();
+        tee.write_all(b"c").unwrap();
+        assert_eq!(
+            tee.finish().unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        let mut tee = Tee::new(Vec::new(), Some(Algorithm::Blake2b));
+        tee.write_all(b"abc").unwrap();
+        assert!(tee
+            .finish()
+            .unwrap()
+            .starts_with("ba80a53f981c4d0d6a2797b69f12f6e9"));
+
+        let mut tee = Tee::new(Vec::new(), None);
+        tee.write_all(b"abc").unwrap();
+        assert_eq!(tee.finish(), None);
+    }
+
+    #[test]
+    fn test_sidecar_path() {
+        assert_eq!(
+            sidecar_path(Algorithm::Blake2b, Path::new("/tmp/key.bin")),
+            PathBuf::from("/tmp/key.bin.b2")
+        );
+    }
+}
@@ -1,9 +1,16 @@
 use std::path::PathBuf;
 
-use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 
-use crate::config::CpuRngPreference;
+use crate::checksum;
+use crate::config::{CpuRngConfig, CpuRngPreference, DaemonFeed, DaemonMode, InjectTarget};
 use crate::logging::LogArgs;
+use crate::pattern::Pattern;
+use crate::split::SplitSize;
+use crate::table::Units;
+use crate::text::Grouping;
 
 #[derive(Debug, Clone, ValueEnum)]
 pub enum OutputFormat {
@@ -25,6 +32,44 @@ pub enum OutputFormat {
     Binary,
     /// Uppercase hexadecimal
     HexUpper,
+    /// Binary in 64 KiB frames with a length and CRC32C (see `unframe`)
+    Framed,
+}
+
+/// Encoding of `stream` output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StreamFormat {
+    /// Raw binary bytes
+    Raw,
+    /// 64 KiB frames with a length and CRC32C (see `unframe`)
+    Framed,
+}
+
+/// Device read directly by `stream --device`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StreamDevice {
+    /// /dev/hwrng, charged to the [limits] hwrng budget and rate cap
+    Hwrng,
+    /// /dev/urandom
+    Urandom,
+}
+
+impl StreamDevice {
+    pub fn path(self) -> &'static str {
+        match self {
+            StreamDevice::Hwrng => "/dev/hwrng",
+            StreamDevice::Urandom => "/dev/urandom",
+        }
+    }
+}
+
+/// What `--max-wait` does when a source misses the deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OnTimeout {
+    /// Continue with the next source, mixing in the bytes already read
+    Fallthrough,
+    /// Exit with a timeout error (status 124)
+    Fail,
 }
 
 #[derive(Debug, Args)]
@@ -64,12 +109,59 @@ pub struct CpuRngArgs {
     /// Standalone CPU RNG oversample ratio (1-16)
     #[arg(long)]
     pub oversample: Option<u32>,
+
+    /// Oversample until the raw output's measured min-entropy covers the
+    /// request (see [cpu_rng] oversample_margin and max_oversample)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub adaptive_oversample: Option<bool>,
+
+    /// CPU RNG collector threads for large requests (0 = one per core)
+    #[arg(long = "cpu-rng-threads")]
+    pub cpu_rng_threads: Option<usize>,
 }
 
-#[derive(Debug, Parser)]
-#[command(name = "mixrand", about = "Secure random byte generator for Linux")]
-#[command(args_conflicts_with_subcommands = true)]
-pub struct Cli {
+impl CpuRngArgs {
+    /// Overrides the `[cpu_rng]` settings given on the command line.
+    pub fn apply(&self, cfg: &mut CpuRngConfig) {
+        if let Some(v) = self.enable_rdseed {
+            cfg.enable_rdseed = v;
+        }
+        if let Some(v) = self.enable_rdrand {
+            cfg.enable_rdrand = v;
+        }
+        if let Some(v) = self.enable_xstore {
+            cfg.enable_xstore = v;
+        }
+        if let Some(v) = self.rdrand_retries {
+            cfg.rdrand_retries = v;
+        }
+        if let Some(v) = self.rdseed_retries {
+            cfg.rdseed_retries = v;
+        }
+        if let Some(v) = self.xstore_quality {
+            cfg.xstore_quality = v;
+        }
+        if let Some(v) = self.cpu_rng_prefer {
+            cfg.prefer = v;
+        }
+        if let Some(v) = self.fallback_mix_bytes {
+            cfg.fallback_mix_bytes = v;
+        }
+        if let Some(v) = self.oversample {
+            cfg.oversample = v;
+        }
+        if let Some(v) = self.adaptive_oversample {
+            cfg.adaptive_oversample = v;
+        }
+        if let Some(v) = self.cpu_rng_threads {
+            cfg.threads = v;
+        }
+    }
+}
+
+/// `generate` options, also accepted without the subcommand.
+#[derive(Debug, Args)]
+pub struct GenerateArgs {
     /// Number of random bytes to generate
     #[arg(short = 'n', long = "bytes", default_value_t = 32)]
     pub bytes: usize,
@@ -78,11 +170,112 @@ pub struct Cli {
     #[arg(short = 'f', long = "format", value_enum, default_value_t = OutputFormat::Hex)]
     pub format: OutputFormat,
 
+    /// With -f text, draw the -n characters from these instead of printable
+    /// ASCII, without bias
+    #[arg(long, value_name = "CHARS")]
+    pub alphabet: Option<String>,
+
+    /// With -f text, separate every N characters with SEP (default -),
+    /// e.g. 5- for XXXXX-XXXXX
+    #[arg(long, value_name = "N[SEP]")]
+    pub group: Option<Grouping>,
+
+    /// Derive the output for this purpose: other contexts get independent
+    /// output from the same request
+    #[arg(long, value_name = "NAME")]
+    pub context: Option<String>,
+
+    /// One labeled output per comma-separated context (e.g. disk,backup),
+    /// each derived independently from one request
+    #[arg(
+        long,
+        value_name = "NAME,...",
+        value_delimiter = ',',
+        conflicts_with = "context"
+    )]
+    pub contexts: Option<Vec<String>>,
+
     /// Write output to a file instead of stdout
     #[arg(short = 'o', long = "output-file")]
     pub output_file: Option<PathBuf>,
 
-    /// Configuration file path (default: /etc/mixrand.toml)
+    /// Write --output-file through a temporary file renamed into place and
+    /// fsync its directory, so the file is either complete or untouched
+    #[arg(long, requires = "output_file")]
+    pub sync: bool,
+
+    /// Write --output-file as numbered parts of at most SIZE (e.g. 1MiB,
+    /// 650MB), FILE.000, FILE.001, ..., with their digests in FILE.sha256
+    #[arg(
+        long,
+        value_name = "SIZE",
+        requires = "output_file",
+        conflicts_with_all = ["sync", "verify"]
+    )]
+    pub split_size: Option<SplitSize>,
+
+    /// Write the output into a sealed memfd called NAME instead, for
+    /// --fd-exec to inherit
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with = "output_file",
+        requires = "fd_exec"
+    )]
+    pub output_memfd: Option<String>,
+
+    /// Then exec CMD (the rest of the command line) with the memfd open;
+    /// "{fd}" in its arguments and $MIXRAND_FD give the descriptor number
+    #[arg(
+        long,
+        value_name = "CMD",
+        num_args = 1..,
+        allow_hyphen_values = true,
+        requires = "output_memfd"
+    )]
+    pub fd_exec: Vec<String>,
+
+    /// Print a digest of the written bytes to stderr
+    #[arg(long, value_enum, value_name = "ALGORITHM")]
+    pub checksum: Option<checksum::Algorithm>,
+
+    /// Also write the digest to FILE.sha256 (or FILE.b2) next to --output-file
+    #[arg(long, requires_all = ["checksum", "output_file"])]
+    pub checksum_sidecar: bool,
+
+    /// Re-read --output-file from disk after writing, check it against the
+    /// digest of what was written and run quick statistics; exit 1 on failure
+    #[arg(long, requires = "output_file")]
+    pub verify: bool,
+
+    /// Lock secret buffers in RAM and disable core dumps
+    #[arg(long)]
+    pub lock_memory: bool,
+
+    /// Give slow sources at most this long (e.g. 5s, 2m)
+    #[arg(long)]
+    pub max_wait: Option<String>,
+
+    /// Action when --max-wait expires
+    #[arg(long, value_enum, default_value_t = OnTimeout::Fallthrough, requires = "max_wait")]
+    pub on_timeout: OnTimeout,
+
+    /// Report on stderr how long each source attempt and pipeline stage took
+    #[arg(long)]
+    pub timing: bool,
+
+    /// Report on stderr the source used, the bytes read from each raw input
+    /// with its estimated min-entropy, and the total time
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Seal the formatted output to an age recipient (age1... or a
+    /// recipients file) and write it ASCII-armored; repeat for several
+    #[arg(long = "encrypt-to", value_name = "RECIPIENT")]
+    pub encrypt_to: Vec<String>,
+
+    /// Configuration file path (default: /etc/mixrand.toml); before any
+    /// subcommand it applies to that subcommand
     #[arg(long = "config")]
     pub config_file: Option<PathBuf>,
 
@@ -91,17 +284,171 @@ pub struct Cli {
 
     #[command(flatten)]
     pub log: LogArgs,
+}
+
+#[derive(Debug, Parser)]
+#[command(name = "mixrand", about = "Secure random byte generator for Linux")]
+pub struct Cli {
+    /// Without a subcommand, `generate`'s options; --config and the log
+    /// options here also apply to any subcommand
+    #[command(flatten)]
+    pub generate: GenerateArgs,
 
     #[command(subcommand)]
     pub command: Option<Command>,
 }
 
+impl Cli {
+    /// Parses `args`. Top-level --config and log options are copied into
+    /// the subcommand unless it sets its own; other `generate` options
+    /// before a subcommand are an error.
+    pub fn try_parse_args<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let mut cmd = Self::command();
+        let matches = cmd.try_get_matches_from_mut(args)?;
+        let mut cli = Self::from_arg_matches(&matches)?;
+        let Some(ref mut command) = cli.command else {
+            return Ok(cli);
+        };
+        let global = LogArgs::augment_args(clap::Command::new("log"));
+        let misplaced = GenerateArgs::augment_args(clap::Command::new("generate"))
+            .get_arguments()
+            .filter(|a| a.get_id() != "config_file")
+            .filter(|a| !global.get_arguments().any(|g| g.get_id() == a.get_id()))
+            .find(|a| matches.value_source(a.get_id().as_str()) == Some(ValueSource::CommandLine))
+            .map(|a| match (a.get_long(), a.get_short()) {
+                (Some(long), _) => format!("--{}", long),
+                (None, Some(short)) => format!("-{}", short),
+                (None, None) => a.get_id().to_string(),
+            });
+        if let Some(arg) = misplaced {
+            return Err(cmd.error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "{} is a `generate` option: put it after `generate`, or drop the `{}` subcommand",
+                    arg,
+                    matches.subcommand_name().unwrap_or_default()
+                ),
+            ));
+        }
+        let (config_file, log) = command.globals_mut();
+        if let Some(ref path) = cli.generate.config_file {
+            match config_file {
+                Some(own) => {
+                    own.get_or_insert_with(|| path.clone());
+                }
+                None => {
+                    return Err(cmd.error(
+                        ErrorKind::ArgumentConflict,
+                        format!(
+                            "--config does not apply to `{}`",
+                            matches.subcommand_name().unwrap_or_default()
+                        ),
+                    ))
+                }
+            }
+        }
+        if let Some(log) = log {
+            log.inherit(&cli.generate.log);
+        }
+        Ok(cli)
+    }
+
+    /// Parses the process arguments, exiting with usage on error.
+    pub fn parse_args() -> Self {
+        Self::try_parse_args(std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+}
+
 #[derive(Debug, Subcommand)]
 pub enum Command {
+    /// Generate random bytes (the default without a subcommand)
+    Generate(GenerateArgs),
     /// Monitor kernel entropy pool and inject mixed entropy when it runs low
     Daemon(DaemonArgs),
     /// Run FIPS 140-2 statistical tests and entropy estimates against each entropy source
     Check(CheckArgs),
+    /// Run FIPS 140-2 tests and entropy estimates on a file or stdin, streamed in chunks
+    Analyze(AnalyzeArgs),
+    /// Diagnose every entropy source and explain why it is or isn't usable
+    Probe(ProbeArgs),
+    /// Probe, sample and check permissions in one pass/fail report for bug reports
+    Doctor(DoctorArgs),
+    /// Generate a secret and exec a command with it in its environment or on a pipe
+    Exec(ExecArgs),
+    /// Show the kernel's entropy accounting and daemon injections every interval, like vmstat
+    Watch(WatchArgs),
+    /// Estimate a source's min-entropy (SP 800-90B) and recommend a daemon credit_ratio
+    Calibrate(CalibrateArgs),
+    /// Measure the entropy rate of the CPU jitter collector on this machine
+    ProfileJitter(ProfileJitterArgs),
+    /// Print floating-point samples from a uniform, normal or exponential distribution
+    Float(FloatArgs),
+    /// Write a continuous stream of random bytes (e.g. for piping into dd)
+    Stream(StreamArgs),
+    /// Check and strip the frames of --format framed output
+    Unframe(UnframeArgs),
+    /// Check the files `check --capture-key` tagged in a directory against its manifest
+    VerifyCapture(VerifyCaptureArgs),
+    /// Overwrite a disk or file with keyed stream output
+    Wipe(WipeArgs),
+    /// Periodically publish signed, hash-chained entropy values (randomness beacon)
+    Beacon(BeaconArgs),
+    /// Generate a secret from several operators' typed input and the hardware sources
+    Ceremony(CeremonyArgs),
+    /// Collect entropy from the timing of key presses or mouse movement
+    Input(InputArgs),
+    /// Serve mixed entropy to VM guests over AF_VSOCK
+    ServeVsock(ServeVsockArgs),
+    /// Serve mixed entropy to local clients over a unix socket (CBOR protocol)
+    ServeSocket(ServeSocketArgs),
+    /// Fetch random bytes from a running serve-socket
+    Client(ClientArgs),
+    /// Credit entropy sent by an unprivileged daemon (--inject-target injector:PATH)
+    Injector(InjectorArgs),
+    /// Run the daemon's [alerts] scripts on its behalf (started by the daemon)
+    #[command(hide = true)]
+    AlertRunner(AlertRunnerArgs),
+    /// Serve mixed entropy over gRPC (GetEntropy / StreamEntropy)
+    #[cfg(feature = "grpc")]
+    ServeGrpc(ServeGrpcArgs),
+}
+
+impl Command {
+    /// The subcommand's --config (`None` if it takes none) and log options
+    /// (`None` if it logs nowhere), for inheriting the top-level ones.
+    fn globals_mut(&mut self) -> (Option<&mut Option<PathBuf>>, Option<&mut LogArgs>) {
+        match self {
+            Command::Generate(a) => (Some(&mut a.config_file), Some(&mut a.log)),
+            Command::Daemon(a) => (Some(&mut a.config_file), Some(&mut a.log)),
+            Command::Check(a) => (Some(&mut a.config_file), Some(&mut a.log)),
+            Command::Analyze(a) => (None, Some(&mut a.log)),
+            Command::Probe(a) => (Some(&mut a.config_file), Some(&mut a.log)),
+            Command::Doctor(a) => (Some(&mut a.config_file), Some(&mut a.log)),
+            Command::Exec(a) => (Some(&mut a.config_file), Some(&mut a.log)),
+            Command::Watch(a) => (None, Some(&mut a.log)),
+            Command::Calibrate(a) => (Some(&mut a.config_file), Some(&mut a.log)),
+            Command::ProfileJitter(a) => (None, Some(&mut a.log)),
+            Command::Float(a) => (Some(&mut a.config_file), Some(&mut a.log)),
+            Command::Stream(a) => (Some(&mut a.config_file), Some(&mut a.log)),
+            Command::Unframe(a) => (None, Some(&mut a.log)),
+            Command::VerifyCapture(a) => (None, Some(&mut a.log)),
+            Command::Wipe(a) => (Some(&mut a.config_file), Some(&mut a.log)),
+            Command::Beacon(a) => (Some(&mut a.config_file), Some(&mut a.log)),
+            Command::Ceremony(a) => (Some(&mut a.config_file), Some(&mut a.log)),
+            Command::Input(a) => (None, Some(&mut a.log)),
+            Command::ServeVsock(a) => (Some(&mut a.config_file), Some(&mut a.log)),
+            Command::ServeSocket(a) => (Some(&mut a.config_file), Some(&mut a.log)),
+            Command::Client(a) => (None, Some(&mut a.log)),
+            Command::Injector(a) => (None, Some(&mut a.log)),
+            Command::AlertRunner(_) => (None, None),
+            #[cfg(feature = "grpc")]
+            Command::ServeGrpc(a) => (Some(&mut a.config_file), Some(&mut a.log)),
+        }
+    }
 }
 
 #[derive(Debug, Parser)]
@@ -114,13 +461,100 @@ pub struct DaemonArgs {
     #[arg(short = 'i', long, default_value_t = 5)]
     pub interval: u64,
 
+    /// Also inject a batch at this interval (e.g. 10m, 1h) whatever
+    /// entropy_avail says
+    #[arg(long, conflicts_with = "inject_cron")]
+    pub inject_every: Option<String>,
+
+    /// Also inject a batch on this cron schedule, in local time (e.g.
+    /// "*/10 * * * *"), whatever entropy_avail says
+    #[arg(long)]
+    pub inject_cron: Option<String>,
+
     /// Bytes to inject per round (default: 64)
     #[arg(short = 'b', long, default_value_t = 64)]
     pub batch_size: usize,
 
-    /// Bits of entropy credited per byte, 1-8 (default: 4)
-    #[arg(short = 'c', long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..=8))]
-    pub credit_ratio: u32,
+    /// Bits of entropy credited per byte, 0-8; 0 stirs the pool every round
+    /// without crediting (default: [daemon] credit_ratio, 4)
+    #[arg(short = 'c', long, value_parser = clap::value_parser!(u32).range(0..=8))]
+    pub credit_ratio: Option<u32>,
+
+    /// Collector for injected batches (default: [daemon] feed, fallback)
+    #[arg(long, value_enum)]
+    pub feed: Option<DaemonFeed>,
+
+    /// When to inject into the kernel pool: threshold (below --threshold),
+    /// reseed (on a timer, then reseed the crng) or auto, reseed on kernels
+    /// that keep entropy_avail at 256 (default: [daemon] mode, auto)
+    #[arg(long, value_enum)]
+    pub mode: Option<DaemonMode>,
+
+    /// Where batches go: /dev/random, file:PATH, fifo:PATH, hwrng-loop or
+    /// injector:SOCKET (default: [daemon] inject_target, /dev/random). Only
+    /// /dev/random and an injector are credited or gated on the threshold;
+    /// other targets get one batch per interval
+    #[arg(long)]
+    pub inject_target: Option<InjectTarget>,
+
+    /// Before the first round, run an extended multi-threaded jitter + procfs
+    /// collection and inject a large seed with full credit (for initramfs use
+    /// on boards without a hardware RNG)
+    #[arg(long)]
+    pub early_boot: bool,
+
+    /// Seconds spent collecting for --early-boot
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u64).range(1..=60))]
+    pub early_boot_secs: u64,
+
+    /// Bytes injected by --early-boot
+    #[arg(long, default_value_t = 512)]
+    pub early_boot_bytes: usize,
+
+    /// Bytes injected, with a crng reseed, on resume from suspend; the CPU
+    /// RNG is also retested then (0: ignore resumes)
+    #[arg(long, default_value_t = 512)]
+    pub resume_bytes: usize,
+
+    /// Persistent seed file: mixed in (uncredited) at startup, then rewritten
+    /// at startup and on shutdown
+    #[arg(long)]
+    pub seed_file: Option<PathBuf>,
+
+    /// Longest wait in seconds between retries while injections keep
+    /// failing; the interval doubles per failure up to this
+    #[arg(long, default_value_t = 300)]
+    pub max_backoff: u64,
+
+    /// Exit with status 75 after this many consecutive failed injections,
+    /// for a supervisor to restart or alert on (0: keep retrying)
+    #[arg(long, default_value_t = 0)]
+    pub max_failures: u32,
+
+    /// Inject one final batch on SIGTERM/SIGINT before exiting
+    #[arg(long)]
+    pub final_inject: bool,
+
+    /// Seconds allowed for shutdown work (final injection, seed save)
+    #[arg(long, default_value_t = 5)]
+    pub shutdown_timeout: u64,
+
+    /// Rewrite this JSON status file (uptime, injections, source health) every round
+    #[arg(long)]
+    pub status_file: Option<PathBuf>,
+
+    /// Lock secret buffers in RAM and disable core dumps (default: on)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub lock_memory: Option<bool>,
+
+    /// Don't restrict syscalls (seccomp) or filesystem access (Landlock) after startup
+    #[arg(long)]
+    pub no_sandbox: bool,
+
+    /// Prefixes for sizes and rates: si (KB, MB) or iec (KiB, MiB)
+    /// (default: [display] units, si)
+    #[arg(long, value_enum)]
+    pub units: Option<Units>,
 
     /// Configuration file path (default: /etc/mixrand.toml)
     #[arg(long = "config")]
@@ -135,11 +569,20 @@ pub struct DaemonArgs {
 
 #[derive(Debug, Parser)]
 pub struct CheckArgs {
-    /// Duration to run tests (e.g. 30s, 5m, 1h, 2d; bare number = minutes)
-    #[arg(short = 'd', long, default_value = "1m")]
-    pub duration: String,
+    /// Duration to run tests (e.g. 30s, 5m, 1h, 2d; bare number = minutes).
+    /// Default 1m; with a per-source target, a cap that is off by default
+    #[arg(short = 'd', long)]
+    pub duration: Option<String>,
+
+    /// Run until every source has delivered this many samples
+    #[arg(long, value_name = "N", conflicts_with = "bytes_per_source")]
+    pub samples_per_source: Option<u64>,
 
-    /// Bytes per sample (FIPS tests require >= 2500)
+    /// Run until every source has delivered this many bytes
+    #[arg(long, value_name = "N")]
+    pub bytes_per_source: Option<u64>,
+
+    /// Bytes per sample (FIPS tests run over 2500-byte windows, aggregated across samples)
     #[arg(short = 's', long, default_value_t = 2500)]
     pub sample_size: usize,
 
@@ -147,10 +590,383 @@ pub struct CheckArgs {
     #[arg(short = 'r', long, default_value_t = 10)]
     pub report_interval: u64,
 
-    /// Comma-separated list of sources to test (default: all available)
+    /// Smallest autocorrelation lag reported
+    #[arg(long, default_value_t = 1)]
+    pub min_lag: usize,
+
+    /// Largest autocorrelation lag reported (max |r| over min-lag..=max-lag)
+    #[arg(long, default_value_t = 32)]
+    pub max_lag: usize,
+
+    /// Comma-separated sources to test: names, `all`, or `!name` to leave
+    /// one out (default: all available)
     #[arg(long, value_delimiter = ',')]
     pub sources: Option<Vec<String>>,
 
+    /// Prefixes for sizes and rates: si (KB, MB) or iec (KiB, MiB)
+    /// (default: [display] units, si)
+    #[arg(long, value_enum)]
+    pub units: Option<Units>,
+
+    /// Append one CSV row per sample (timestamp, source, latency, estimates, FIPS results)
+    #[arg(long)]
+    pub samples_csv: Option<PathBuf>,
+
+    /// Write the final comparison as a standalone HTML page with charts
+    #[arg(long)]
+    pub report_html: Option<PathBuf>,
+
+    /// Print each source's byte histogram as deviation from uniform, marking buckets beyond ±3σ
+    #[arg(long)]
+    pub histogram: bool,
+
+    /// Export the byte histograms as CSV (source, byte, count, expected, deviation, z-score)
+    #[arg(long)]
+    pub histogram_csv: Option<PathBuf>,
+
+    /// Export the byte histograms as JSON
+    #[arg(long)]
+    pub histogram_json: Option<PathBuf>,
+
+    /// Compare the results against a baseline saved by --save-baseline and
+    /// exit non-zero if a source regressed
+    #[arg(long)]
+    pub baseline: Option<PathBuf>,
+
+    /// Save the results as a JSON baseline for later --baseline runs
+    #[arg(long)]
+    pub save_baseline: Option<PathBuf>,
+
+    /// Throughput drop, in percent, that counts as a regression
+    #[arg(long, default_value_t = 20.0)]
+    pub baseline_threshold: f64,
+
+    /// Split the run into windows of this length (e.g. 15m) and flag a
+    /// source whose min-entropy or FIPS pass rate in a window drifts from
+    /// its long-run mean
+    #[arg(long, value_name = "DURATION")]
+    pub drift_window: Option<String>,
+
+    /// Deviation, in standard errors, that counts as drift
+    #[arg(long, default_value_t = 4.0, requires = "drift_window")]
+    pub drift_sigma: f64,
+
+    /// Sample every source in lockstep rounds and compare each pair with
+    /// paired tests: Wilcoxon on latency and min-entropy, McNemar on FIPS
+    #[arg(long)]
+    pub paired: bool,
+
+    /// Tag every file this check writes with HMAC-SHA256 under the key in
+    /// this file (created if missing) and record the tags in a manifest
+    /// beside them, for `verify-capture`
+    #[arg(long, value_name = "KEY_FILE")]
+    pub capture_key: Option<PathBuf>,
+
+    /// If the check is interrupted, save its results so far to this file
+    /// for --resume
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["drift_window", "paired"])]
+    pub save_state: Option<PathBuf>,
+
+    /// Continue a check saved by --save-state, adding to its results and
+    /// counting its elapsed time towards -d. Checks the same sources
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["sources", "drift_window", "paired"])]
+    pub resume: Option<PathBuf>,
+
+    /// Sample RDRAND and RDSEED side by side for -d (default 10s) and flag
+    /// firmware bugs instead of checking the sources
+    #[arg(long, conflicts_with_all = ["sources", "samples_per_source", "bytes_per_source", "drift_window", "paired", "save_state", "resume", "capture_key"])]
+    pub cpu_rng_diff: bool,
+
+    /// Append an environment footer: kernel, CPU model, microcode,
+    /// virtualization, governor/turbo and mixrand version
+    #[arg(long)]
+    pub env: bool,
+
+    /// Configuration file path (default: /etc/mixrand.toml)
+    #[arg(long = "config")]
+    pub config_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub cpu_rng: CpuRngArgs,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct CalibrateArgs {
+    /// Source to assess (hwrng, serial, exec, vsock, rdseed, rdrand, xstore, devrandom, havege,
+    /// urandom, fallback, or a plugin name)
+    #[arg(long, default_value = "fallback")]
+    pub source: String,
+
+    /// Maximum collection time (e.g. 30s, 10m, 1h; bare number = minutes)
+    #[arg(short = 'd', long, default_value = "10m")]
+    pub duration: String,
+
+    /// Stop collecting after this many bytes (SP 800-90B asks for 1,000,000)
+    #[arg(long, default_value_t = 1_000_000)]
+    pub max_bytes: usize,
+
+    /// Fraction of the assessed min-entropy to credit, in (0, 1]
+    #[arg(long, default_value_t = 0.5)]
+    pub safety_factor: f64,
+
+    /// Write the recommendation to [daemon] credit_ratio in the config file
+    #[arg(long)]
+    pub write: bool,
+
+    /// Prefixes for sizes and rates: si (KB, MB) or iec (KiB, MiB)
+    /// (default: [display] units, si)
+    #[arg(long, value_enum)]
+    pub units: Option<Units>,
+
+    /// Configuration file path (default: /etc/mixrand.toml)
+    #[arg(long = "config")]
+    pub config_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub cpu_rng: CpuRngArgs,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct ProfileJitterArgs {
+    /// Maximum collection time (e.g. 30s, 10m, 1h; bare number = minutes)
+    #[arg(short = 'd', long, default_value = "60s")]
+    pub duration: String,
+
+    /// Stop collecting after this many timing samples (SP 800-90B asks for 1,000,000)
+    #[arg(long, default_value_t = 1_000_000)]
+    pub max_samples: usize,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct AnalyzeArgs {
+    /// File to analyze (default: stdin)
+    pub input: Option<PathBuf>,
+
+    /// Stop after this many bytes (default: to end of input or Ctrl-C)
+    #[arg(short = 'n', long)]
+    pub bytes: Option<u64>,
+
+    /// Largest autocorrelation lag reported (max |r| over 1..=max-lag)
+    #[arg(long, default_value_t = 32)]
+    pub max_lag: usize,
+
+    /// Print the results as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// Append an environment footer (an `environment` object with --json)
+    #[arg(long)]
+    pub env: bool,
+
+    /// Prefixes for sizes: si (KB, MB) or iec (KiB, MiB) (default: si)
+    #[arg(long, value_enum)]
+    pub units: Option<Units>,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct ProbeArgs {
+    /// Print the findings as JSON, for configuration management
+    #[arg(long)]
+    pub json: bool,
+
+    /// Configuration file path (default: /etc/mixrand.toml)
+    #[arg(long = "config")]
+    pub config_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub cpu_rng: CpuRngArgs,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct DoctorArgs {
+    /// How long to sample the working sources, split between them
+    #[arg(short = 'd', long, default_value = "10s")]
+    pub duration: String,
+
+    /// Configuration file path (default: /etc/mixrand.toml)
+    #[arg(long = "config")]
+    pub config_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub cpu_rng: CpuRngArgs,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Debug, Parser)]
+#[command(group(clap::ArgGroup::new("delivery").required(true).multiple(true).args(["env", "pipe"])))]
+pub struct ExecArgs {
+    /// Number of random bytes to generate
+    #[arg(short = 'n', long = "bytes", default_value_t = 32)]
+    pub bytes: usize,
+
+    /// Encoding of the secret
+    #[arg(short = 'f', long = "format", value_enum, default_value_t = OutputFormat::Hex)]
+    pub format: OutputFormat,
+
+    /// Put the secret in environment variable NAME
+    #[arg(long, value_name = "NAME")]
+    pub env: Option<String>,
+
+    /// Pass the secret on an inherited pipe; "{fd}" in the command and
+    /// $MIXRAND_FD give the descriptor number
+    #[arg(long)]
+    pub pipe: bool,
+
+    /// Command and arguments to run, after --
+    #[arg(last = true, required = true, value_name = "CMD")]
+    pub command: Vec<String>,
+
+    /// Configuration file path (default: /etc/mixrand.toml)
+    #[arg(long = "config")]
+    pub config_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub cpu_rng: CpuRngArgs,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct WatchArgs {
+    /// Time between lines (e.g. 1s, 5m; bare number = minutes)
+    #[arg(short = 'i', long, default_value = "1s")]
+    pub interval: String,
+
+    /// Stop after this many lines
+    #[arg(short = 'c', long)]
+    pub count: Option<u64>,
+
+    /// The daemon's --status-file, to show injections since the previous line
+    #[arg(long)]
+    pub status_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Distribution {
+    /// Uniform on [min, max)
+    Uniform,
+    /// Normal with --mean and --stddev (Box–Muller)
+    Normal,
+    /// Exponential with --rate (inverse CDF)
+    Exponential,
+}
+
+#[derive(Debug, Parser)]
+pub struct FloatArgs {
+    /// Distribution to sample
+    #[arg(short = 'd', long, value_enum, default_value_t = Distribution::Uniform)]
+    pub dist: Distribution,
+
+    /// Number of samples, one per line
+    #[arg(short = 'c', long, default_value_t = 1)]
+    pub count: usize,
+
+    /// Lower bound for uniform
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    pub min: f64,
+
+    /// Upper bound for uniform
+    #[arg(long, default_value_t = 1.0, allow_negative_numbers = true)]
+    pub max: f64,
+
+    /// Mean for normal
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    pub mean: f64,
+
+    /// Standard deviation for normal
+    #[arg(long, default_value_t = 1.0)]
+    pub stddev: f64,
+
+    /// Rate (1/mean) for exponential
+    #[arg(long, default_value_t = 1.0)]
+    pub rate: f64,
+
+    /// Write samples to a file instead of stdout
+    #[arg(short = 'o', long = "output-file")]
+    pub output_file: Option<PathBuf>,
+
+    /// Configuration file path (default: /etc/mixrand.toml)
+    #[arg(long = "config")]
+    pub config_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub cpu_rng: CpuRngArgs,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct StreamArgs {
+    /// Output an XChaCha20 keystream keyed from the entropy sources instead
+    /// of running every chunk through them (hundreds of MB/s)
+    #[arg(long)]
+    pub keyed: bool,
+
+    /// Write a deterministic test pattern instead of random data
+    #[arg(long, value_enum, default_value_t = Pattern::Random, conflicts_with = "keyed")]
+    pub pattern: Pattern,
+
+    /// Rekey from fresh entropy after this many MiB of --keyed output
+    #[arg(long, default_value_t = 256, value_parser = clap::value_parser!(u64).range(1..=1 << 20))]
+    pub reseed_mib: u64,
+
+    /// Copy a device's output as is instead of running every chunk through
+    /// the entropy sources
+    #[arg(long, value_enum, conflicts_with_all = ["keyed", "pattern"])]
+    pub device: Option<StreamDevice>,
+
+    /// --device reads kept queued through io_uring, in 64 KiB chunks
+    /// (builds with --features io-uring; otherwise reads one at a time)
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..=64), requires = "device")]
+    pub queue_depth: u32,
+
+    /// Stop after this many bytes (default: until the reader closes or SIGINT/SIGTERM)
+    #[arg(short = 'n', long)]
+    pub bytes: Option<u64>,
+
+    /// Output encoding
+    #[arg(short = 'f', long = "format", value_enum, default_value_t = StreamFormat::Raw)]
+    pub format: StreamFormat,
+
+    /// Write to a file instead of stdout
+    #[arg(short = 'o', long = "output-file")]
+    pub output_file: Option<PathBuf>,
+
+    /// Print a digest of the written bytes to stderr
+    #[arg(long, value_enum, value_name = "ALGORITHM")]
+    pub checksum: Option<checksum::Algorithm>,
+
+    /// Also write the digest to FILE.sha256 (or FILE.b2) next to --output-file
+    #[arg(long, requires_all = ["checksum", "output_file"])]
+    pub checksum_sidecar: bool,
+
+    /// Prefixes for sizes and rates: si (KB, MB) or iec (KiB, MiB)
+    /// (default: [display] units, si)
+    #[arg(long, value_enum)]
+    pub units: Option<Units>,
+
     /// Configuration file path (default: /etc/mixrand.toml)
     #[arg(long = "config")]
     pub config_file: Option<PathBuf>,
@@ -161,3 +977,440 @@ pub struct CheckArgs {
     #[command(flatten)]
     pub log: LogArgs,
 }
+
+#[derive(Debug, Parser)]
+pub struct UnframeArgs {
+    /// Framed input (default: stdin)
+    pub input: Option<PathBuf>,
+
+    /// Write the payload to a file instead of stdout
+    #[arg(short = 'o', long = "output-file")]
+    pub output_file: Option<PathBuf>,
+
+    /// Drop damaged frames and carry on instead of stopping at the first one;
+    /// the exit status is still 1 if anything was dropped
+    #[arg(long)]
+    pub skip_corrupt: bool,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct VerifyCaptureArgs {
+    /// Directory holding the files and their manifest
+    pub dir: PathBuf,
+
+    /// The key file given to `check --capture-key`
+    #[arg(long)]
+    pub key: PathBuf,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct WipeArgs {
+    /// Block device or file to overwrite (its current size is kept)
+    pub target: PathBuf,
+
+    /// Overwrite passes, each with fresh keystream
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=35))]
+    pub passes: u32,
+
+    /// Write a deterministic test pattern instead of keystream
+    #[arg(long, value_enum, default_value_t = Pattern::Random)]
+    pub pattern: Pattern,
+
+    /// After the last pass, read back a random sample of blocks and compare
+    #[arg(long)]
+    pub verify: bool,
+
+    /// Blocks read back by --verify
+    #[arg(long, default_value_t = 64)]
+    pub verify_samples: usize,
+
+    /// Bypass the page cache (O_DIRECT)
+    #[arg(long)]
+    pub direct: bool,
+
+    /// Write size in KiB (a multiple of 4 with --direct)
+    #[arg(long, default_value_t = 1024)]
+    pub block_kib: usize,
+
+    /// Rekey from fresh entropy after this many MiB
+    #[arg(long, default_value_t = 256, value_parser = clap::value_parser!(u64).range(1..=1 << 20))]
+    pub reseed_mib: u64,
+
+    /// Wipe even if the target is mounted
+    #[arg(long)]
+    pub force: bool,
+
+    /// Prefixes for sizes and rates: si (KB, MB) or iec (KiB, MiB)
+    /// (default: [display] units, si)
+    #[arg(long, value_enum)]
+    pub units: Option<Units>,
+
+    /// Configuration file path (default: /etc/mixrand.toml)
+    #[arg(long = "config")]
+    pub config_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub cpu_rng: CpuRngArgs,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct BeaconArgs {
+    /// Seconds between pulses; pulses align to multiples of this on the wall clock
+    #[arg(short = 'i', long, default_value_t = 60)]
+    pub interval: u64,
+
+    /// Ed25519 signing key (PKCS#8 PEM, e.g. from `openssl genpkey -algorithm ed25519`)
+    #[arg(long)]
+    pub sign_key: PathBuf,
+
+    /// Append pulses as JSON lines to this file; also used to resume the chain
+    #[arg(short = 'o', long)]
+    pub output: Option<PathBuf>,
+
+    /// POST each pulse as JSON to this http:// URL
+    #[arg(long)]
+    pub post_url: Option<String>,
+
+    /// Random bytes per pulse
+    #[arg(short = 'n', long = "bytes", default_value_t = 64)]
+    pub bytes: usize,
+
+    /// Configuration file path (default: /etc/mixrand.toml)
+    #[arg(long = "config")]
+    pub config_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub cpu_rng: CpuRngArgs,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct CeremonyArgs {
+    /// Operators contributing input, one after another (1-64)
+    #[arg(short = 'p', long, value_parser = clap::value_parser!(u32).range(1..=64))]
+    pub participants: u32,
+
+    /// Keystrokes each operator must type
+    #[arg(long, default_value_t = 20)]
+    pub min_keystrokes: usize,
+
+    /// Number of secret bytes to generate
+    #[arg(short = 'n', long = "bytes", default_value_t = 32)]
+    pub bytes: usize,
+
+    /// Output format
+    #[arg(short = 'f', long = "format", value_enum, default_value_t = OutputFormat::Hex)]
+    pub format: OutputFormat,
+
+    /// Write the secret to a file instead of stdout
+    #[arg(short = 'o', long = "output-file")]
+    pub output_file: Option<PathBuf>,
+
+    /// Lock secret buffers in RAM and disable core dumps (default: on)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub lock_memory: Option<bool>,
+
+    /// Configuration file path (default: /etc/mixrand.toml)
+    #[arg(long = "config")]
+    pub config_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub cpu_rng: CpuRngArgs,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct InputArgs {
+    /// Number of random bytes to generate
+    #[arg(short = 'n', long = "bytes", default_value_t = 32)]
+    pub bytes: usize,
+
+    /// Output format
+    #[arg(short = 'f', long = "format", value_enum, default_value_t = OutputFormat::Hex)]
+    pub format: OutputFormat,
+
+    /// Write output to a file instead of stdout
+    #[arg(short = 'o', long = "output-file")]
+    pub output_file: Option<PathBuf>,
+
+    /// Read key and motion events from this evdev device (e.g.
+    /// /dev/input/event3) instead of key presses on the terminal
+    #[arg(long)]
+    pub device: Option<PathBuf>,
+
+    /// Lock secret buffers in RAM and disable core dumps (default: on)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub lock_memory: Option<bool>,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct ServeVsockArgs {
+    /// Port to listen on
+    #[arg(short = 'p', long, default_value_t = 4000)]
+    pub port: u32,
+
+    /// Only accept this guest context ID (default: any guest)
+    #[arg(long)]
+    pub cid: Option<u32>,
+
+    /// Largest request served, in bytes
+    #[arg(long, default_value_t = 65536)]
+    pub max_request_bytes: u32,
+
+    /// Guests served at once; further connections are refused
+    #[arg(long, default_value_t = 16)]
+    pub max_clients: usize,
+
+    /// Lock secret buffers in RAM and disable core dumps (default: on)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub lock_memory: Option<bool>,
+
+    /// Don't install the seccomp syscall filter after startup
+    #[arg(long)]
+    pub no_sandbox: bool,
+
+    /// Configuration file path (default: /etc/mixrand.toml)
+    #[arg(long = "config")]
+    pub config_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub cpu_rng: CpuRngArgs,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct ServeSocketArgs {
+    /// Unix socket to listen on
+    #[arg(short = 's', long, default_value = crate::socket::DEFAULT_SOCKET)]
+    pub socket: PathBuf,
+
+    /// Largest request served, in bytes (at most 1 MiB)
+    #[arg(long, default_value_t = 65536)]
+    pub max_request_bytes: u32,
+
+    /// Clients served at once; further connections are refused
+    #[arg(long, default_value_t = 16)]
+    pub max_clients: usize,
+
+    /// Reservations one connection may hold at once (0 refuses `reserve`)
+    #[arg(long, default_value_t = 16)]
+    pub max_reservations: usize,
+
+    /// Longest a reservation is held before its bytes are wiped, in
+    /// milliseconds; also the hold when a client doesn't ask for one
+    #[arg(long, default_value_t = 30_000, value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_hold_ms: u64,
+
+    /// Lock secret buffers in RAM and disable core dumps (default: on)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub lock_memory: Option<bool>,
+
+    /// Don't install the seccomp syscall filter after startup
+    #[arg(long)]
+    pub no_sandbox: bool,
+
+    /// Configuration file path (default: /etc/mixrand.toml)
+    #[arg(long = "config")]
+    pub config_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub cpu_rng: CpuRngArgs,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct ClientArgs {
+    /// serve-socket socket to connect to
+    #[arg(short = 's', long, default_value = crate::socket::DEFAULT_SOCKET)]
+    pub socket: PathBuf,
+
+    /// Number of random bytes to fetch
+    #[arg(short = 'n', long = "bytes", default_value_t = 32)]
+    pub bytes: usize,
+
+    /// Output format
+    #[arg(short = 'f', long = "format", value_enum, default_value_t = OutputFormat::Hex)]
+    pub format: OutputFormat,
+
+    /// Write output to a file instead of stdout
+    #[arg(short = 'o', long = "output-file")]
+    pub output_file: Option<PathBuf>,
+
+    /// Have the server fail the request if its slow sources take longer
+    /// than this (e.g. 5s, 2m)
+    #[arg(long)]
+    pub max_wait: Option<String>,
+
+    /// File whose first line is the token to send, for a server with
+    /// [auth] tokens
+    #[arg(long)]
+    pub token_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct InjectorArgs {
+    /// Unix socket to listen on
+    #[arg(short = 's', long, default_value = crate::injector::DEFAULT_SOCKET)]
+    pub socket: PathBuf,
+
+    /// User (name or uid) allowed to inject; repeatable. Root is always allowed
+    #[arg(short = 'u', long)]
+    pub allow_user: Vec<String>,
+
+    /// Most bits of credit accepted per byte (1-8)
+    #[arg(long, default_value_t = 8)]
+    pub max_credit_ratio: u32,
+
+    /// Lock secret buffers in RAM and disable core dumps (default: on)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub lock_memory: Option<bool>,
+
+    /// Don't install the seccomp syscall filter after startup
+    #[arg(long)]
+    pub no_sandbox: bool,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct AlertRunnerArgs {
+    /// Seconds a script may run before it is killed
+    #[arg(long, default_value_t = 10)]
+    pub timeout_secs: u64,
+
+    /// Scripts the daemon may ask for, by index
+    pub scripts: Vec<PathBuf>,
+}
+
+#[cfg(feature = "grpc")]
+#[derive(Debug, Parser)]
+pub struct ServeGrpcArgs {
+    /// Address to listen on
+    #[arg(short = 'l', long, default_value = "127.0.0.1:50051")]
+    pub listen: std::net::SocketAddr,
+
+    /// PEM certificate chain for TLS (requires --tls-key)
+    #[arg(long, requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// PEM private key for TLS (requires --tls-cert)
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+
+    /// PEM CA certificates; clients must present a certificate signed by
+    /// one of them (mutual TLS, requires --tls-cert)
+    #[arg(long, requires = "tls_cert")]
+    pub tls_client_ca: Option<PathBuf>,
+
+    /// Largest GetEntropy request and stream chunk, in bytes
+    #[arg(long, default_value_t = 65536)]
+    pub max_request_bytes: u32,
+
+    /// Bytes each client may draw per minute, unless its [auth] token sets
+    /// its own limit (0 = unlimited)
+    #[arg(long, default_value_t = 16 * 1024 * 1024)]
+    pub quota_bytes_per_minute: u64,
+
+    /// Lock secret buffers in RAM and disable core dumps (default: on)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub lock_memory: Option<bool>,
+
+    /// Don't install the seccomp syscall filter after startup
+    #[arg(long)]
+    pub no_sandbox: bool,
+
+    /// Configuration file path (default: /etc/mixrand.toml)
+    #[arg(long = "config")]
+    pub config_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub cpu_rng: CpuRngArgs,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_args(std::iter::once("mixrand").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_generate_alias_and_globals() {
+        let bare = parse(&["-n", "64", "-f", "raw"]).unwrap();
+        assert!(bare.command.is_none());
+        assert_eq!(bare.generate.bytes, 64);
+
+        let Some(Command::Generate(args)) = parse(&["generate", "-n", "16"]).unwrap().command
+        else {
+            panic!("expected generate");
+        };
+        assert_eq!(args.bytes, 16);
+
+        let cli = parse(&["--config", "/a.toml", "--log-level", "debug", "check"]).unwrap();
+        let Some(Command::Check(args)) = cli.command else {
+            panic!("expected check");
+        };
+        assert_eq!(
+            args.config_file.as_deref(),
+            Some(std::path::Path::new("/a.toml"))
+        );
+        assert!(matches!(
+            args.log.log_level,
+            Some(crate::logging::LogLevel::Debug)
+        ));
+
+        // The subcommand's own --config wins
+        let cli = parse(&["--config", "/a.toml", "probe", "--config", "/b.toml"]).unwrap();
+        let Some(Command::Probe(args)) = cli.command else {
+            panic!("expected probe");
+        };
+        assert_eq!(
+            args.config_file.as_deref(),
+            Some(std::path::Path::new("/b.toml"))
+        );
+    }
+
+    #[test]
+    fn test_misplaced_generate_options() {
+        let e = parse(&["-n", "8", "check"]).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ArgumentConflict);
+        assert!(e.to_string().contains("--bytes is a `generate` option"));
+        let e = parse(&["--config", "/a.toml", "analyze"]).unwrap_err();
+        assert!(e
+            .to_string()
+            .contains("--config does not apply to `analyze`"));
+    }
+}
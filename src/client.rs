@@ -0,0 +1,274 @@
+//! Client for a running `serve-socket` ([`crate::wire`] protocol), and the
+//! `client` subcommand built on it.
+
+use std::io::{BufReader, BufWriter};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::check;
+use crate::cli::{ClientArgs, OutputFormat};
+use crate::entropy::{cpurng, EntropyResult};
+use crate::error::Error;
+use crate::output::{self, Destination};
+use crate::secmem;
+use crate::wire::{self, Data, Op, Reply, Request};
+
+/// Longest error message read from the server.
+const MAX_ERROR_BYTES: usize = 4096;
+
+/// Bytes a server has generated and is holding for [`Client::fetch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reservation {
+    pub id: u64,
+    pub bytes: usize,
+    /// How long the server holds them, from when it answered.
+    pub expires_in: Duration,
+}
+
+pub struct Client {
+    path: PathBuf,
+    reader: BufReader<UnixStream>,
+    writer: BufWriter<UnixStream>,
+    next_id: u64,
+    token: Option<String>,
+}
+
+impl Client {
+    pub fn connect(path: &Path) -> Result<Self, Error> {
+        let stream = UnixStream::connect(path).map_err(|e| {
+            Error::NoEntropy(format!("cannot connect to {}: {}", path.display(), e))
+        })?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+            next_id: 1,
+            token: None,
+        })
+    }
+
+    /// Sends `token` with each request, for a server with `[auth]` tokens.
+    pub fn set_token(&mut self, token: Option<String>) {
+        self.token = token;
+    }
+
+    /// Bounds each read and write on the socket; `None` waits forever.
+    pub fn set_timeout(&self, timeout: Option<Duration>) -> Result<(), Error> {
+        let stream = self.writer.get_ref();
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(timeout)?;
+        Ok(())
+    }
+
+    /// Sends one request and returns the matching reply.
+    fn call(&mut self, op: Op, count: usize, deadline: Option<Duration>) -> Result<Reply, Error> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = Request {
+            id,
+            op,
+            bytes: count,
+            format: OutputFormat::Raw,
+            deadline_ms: deadline.map(|d| d.as_millis().try_into().unwrap_or(u64::MAX)),
+            token: self.token.clone(),
+        };
+        wire::write(&mut self.writer, request.to_value())?;
+        let mut response = wire::read_response(&mut self.reader, count.max(MAX_ERROR_BYTES))?
+            .ok_or_else(|| {
+                Error::NoEntropy(format!("{} closed the connection", self.path.display()))
+            })?;
+        if response.id != id {
+            response.zeroize();
+            return Err(Error::NoEntropy(format!(
+                "{} answered request {} instead of {}",
+                self.path.display(),
+                response.id,
+                id
+            )));
+        }
+        if !response.healthy {
+            log::warn!("{} reports a failed health test", self.path.display());
+        }
+        response
+            .result
+            .map_err(|msg| Error::NoEntropy(format!("{}: {}", self.path.display(), msg)))
+    }
+
+    /// The `count` bytes in `reply`, which must carry exactly that many.
+    fn bytes(&self, reply: Reply, count: usize) -> Result<EntropyResult, Error> {
+        let served = match reply {
+            Reply::Served(served) => served,
+            _ => {
+                return Err(Error::NoEntropy(format!(
+                    "{} sent no data",
+                    self.path.display()
+                )))
+            }
+        };
+        match served.data {
+            Data::Bytes(bytes) if bytes.len() == count => Ok(EntropyResult {
+                bytes,
+                source: served.source,
+            }),
+            mut data => {
+                let len = data.as_bytes().len();
+                data.zeroize();
+                Err(Error::NoEntropy(format!(
+                    "{} returned {} bytes instead of {}",
+                    self.path.display(),
+                    len,
+                    count
+                )))
+            }
+        }
+    }
+
+    /// Fetches `count` bytes. With a `deadline`, the server fails the
+    /// request if its slow sources take longer.
+    pub fn get(
+        &mut self,
+        count: usize,
+        deadline: Option<Duration>,
+    ) -> Result<EntropyResult, Error> {
+        let reply = self.call(Op::Get, count, deadline)?;
+        self.bytes(reply, count)
+    }
+
+    /// Has the server generate `count` bytes now and hold them for up to
+    /// `hold` (or its own limit), so a later [`fetch`](Self::fetch) on this
+    /// connection returns at once.
+    pub fn reserve(
+        &mut self,
+        count: usize,
+        hold: Option<Duration>,
+        deadline: Option<Duration>,
+    ) -> Result<Reservation, Error> {
+        let hold_ms = hold.map(|d| d.as_millis().try_into().unwrap_or(u64::MAX));
+        match self.call(Op::Reserve { hold_ms }, count, deadline)? {
+            Reply::Reserved {
+                reservation,
+                expires_ms,
+            } => Ok(Reservation {
+                id: reservation,
+                bytes: count,
+                expires_in: Duration::from_millis(expires_ms),
+            }),
+            reply => {
+                if let Reply::Served(mut served) = reply {
+                    served.data.zeroize();
+                }
+                Err(Error::NoEntropy(format!(
+                    "{} does not support reservations",
+                    self.path.display()
+                )))
+            }
+        }
+    }
+
+    /// The bytes held for `reservation`.
+    pub fn fetch(&mut self, reservation: &Reservation) -> Result<EntropyResult, Error> {
+        let reply = self.call(Op::Fetch(reservation.id), reservation.bytes, None)?;
+        self.bytes(reply, reservation.bytes)
+    }
+
+    /// Has the server wipe the bytes held for `reservation`.
+    pub fn cancel(&mut self, reservation: &Reservation) -> Result<(), Error> {
+        self.call(Op::Cancel(reservation.id), 0, None).map(|_| ())
+    }
+}
+
+/// The token in the first line of `path`.
+fn read_token(path: &Path) -> Result<String, Error> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| Error::InvalidArgs(format!("cannot read {}: {}", path.display(), e)))?;
+    match text.lines().next().map(str::trim) {
+        Some(token) if !token.is_empty() => Ok(token.to_string()),
+        _ => Err(Error::InvalidArgs(format!("{} is empty", path.display()))),
+    }
+}
+
+pub fn run(args: &ClientArgs) -> Result<(), Error> {
+    if args.bytes == 0 {
+        return Err(Error::InvalidArgs(
+            "byte count must be greater than 0".into(),
+        ));
+    }
+    let deadline = args
+        .max_wait
+        .as_deref()
+        .map(check::parse_duration)
+        .transpose()?;
+
+    let token = match &args.token_file {
+        Some(path) => Some(read_token(path)?),
+        None => None,
+    };
+
+    let mut client = Client::connect(&args.socket)?;
+    client.set_token(token);
+    // For text, -n counts characters and rejected draws need more bytes
+    let (mut result, mut formatted) =
+        output::draw(args.bytes, &args.format, |n| client.get(n, deadline))?;
+    cpurng::zeroize_vec(&mut result.bytes);
+    let locked = secmem::protect(&formatted);
+    log::info!("entropy source: {}", result.source);
+    let dest = match &args.output_file {
+        Some(path) => Destination::File(path),
+        None => Destination::Stdout,
+    };
+    let written = output::write_output(&formatted, &OutputFormat::Raw, dest, None);
+    cpurng::zeroize_vec(&mut formatted);
+    drop(locked);
+    written?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wire::{Response, Served};
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    #[test]
+    fn test_get() {
+        let path = std::env::temp_dir().join(format!("mixrand-client-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = thread::spawn(move || {
+            let (mut conn, _) = listener.accept().unwrap();
+            for data in [vec![7u8; 4], vec![7u8; 3]] {
+                let value = wire::read_request(&mut conn).unwrap().unwrap();
+                let request = Request::from_value(&value).unwrap();
+                assert!(matches!(request.format, OutputFormat::Raw));
+                let response = Response {
+                    id: request.id,
+                    healthy: true,
+                    result: Ok(Reply::Served(Served {
+                        data: Data::Bytes(data),
+                        source: "test".into(),
+                    })),
+                };
+                wire::write(&mut conn, response.to_value()).unwrap();
+            }
+        });
+
+        let mut client = Client::connect(&path).unwrap();
+        let result = client.get(4, None).unwrap();
+        assert_eq!(
+            (result.bytes, result.source.as_str()),
+            (vec![7u8; 4], "test")
+        );
+        match client.get(4, None) {
+            Err(e) => assert!(
+                e.to_string().contains("returned 3 bytes instead of 4"),
+                "{}",
+                e
+            ),
+            Ok(_) => panic!("expected a length mismatch"),
+        }
+        server.join().unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,157 @@
+//! Deflate-style compressed size, as an entropy indicator: repeats and
+//! skewed byte frequencies that a chi-square test over a small sample can
+//! miss still make it compressible. Only the size is computed: greedy LZ77
+//! over a 32 KiB window, then the bits optimal Huffman codes for the
+//! resulting symbols would take, close to what gzip -1 produces.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Bytes compressed independently, like a deflate block.
+pub const BLOCK: usize = 64 * 1024;
+
+const WINDOW: usize = 32 * 1024;
+const MIN_MATCH: usize = 4;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+
+/// Block header, HLIT/HDIST/HCLEN and the code-length code, in bits.
+const HEADER_BITS: u64 = 3 + 14 + 19 * 3;
+/// Approximate cost of sending one code length in the block header.
+const CODE_LENGTH_BITS: u64 = 4;
+/// Stored (uncompressed) block overhead in bytes.
+const STORED_OVERHEAD: usize = 5;
+
+const LEN_BASE: [usize; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LEN_EXTRA: [u64; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [usize; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u64; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Index of the last `base` entry not above `value`.
+fn code(base: &[usize], value: usize) -> usize {
+    base.partition_point(|&b| b <= value) - 1
+}
+
+fn hash(data: &[u8]) -> usize {
+    let v = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    (v.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+/// Bits taken by `freqs` under an optimal prefix code: each merge of the
+/// two lightest subtrees adds one bit to every symbol below it.
+fn huffman_bits(freqs: &[u64]) -> u64 {
+    let mut heap: BinaryHeap<Reverse<u64>> = freqs
+        .iter()
+        .filter(|&&f| f > 0)
+        .map(|&f| Reverse(f))
+        .collect();
+    if heap.len() == 1 {
+        return heap.pop().map_or(0, |Reverse(f)| f);
+    }
+    let mut bits = 0;
+    while let (Some(Reverse(a)), Some(Reverse(b))) = (heap.pop(), heap.pop()) {
+        bits += a + b;
+        heap.push(Reverse(a + b));
+        if heap.len() == 1 {
+            break;
+        }
+    }
+    bits
+}
+
+/// Compressed size of one block of at most [`BLOCK`] bytes.
+fn block_size(data: &[u8]) -> usize {
+    let mut table = vec![0u32; 1 << HASH_BITS];
+    let mut litlen = [0u64; 286];
+    let mut dist = [0u64; 30];
+    let mut extra = 0u64;
+    let mut i = 0;
+    while i < data.len() {
+        let mut matched = 0;
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(&data[i..]);
+            // Positions are stored plus one so 0 means empty
+            let candidate = table[h] as usize;
+            table[h] = i as u32 + 1;
+            if candidate > 0 && i + 1 - candidate <= WINDOW {
+                let from = candidate - 1;
+                let limit = (data.len() - i).min(MAX_MATCH);
+                matched = data[from..]
+                    .iter()
+                    .zip(&data[i..i + limit])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if matched >= MIN_MATCH {
+                    let l = code(&LEN_BASE, matched);
+                    let d = code(&DIST_BASE, i - from);
+                    litlen[257 + l] += 1;
+                    dist[d] += 1;
+                    extra += LEN_EXTRA[l] + DIST_EXTRA[d];
+                }
+            }
+        }
+        if matched >= MIN_MATCH {
+            i += matched;
+        } else {
+            litlen[data[i] as usize] += 1;
+            i += 1;
+        }
+    }
+    litlen[256] += 1;
+
+    let used = litlen.iter().chain(&dist).filter(|&&f| f > 0).count() as u64;
+    let bits =
+        HEADER_BITS + CODE_LENGTH_BITS * used + huffman_bits(&litlen) + huffman_bits(&dist) + extra;
+    (bits.div_ceil(8) as usize).min(data.len() + STORED_OVERHEAD)
+}
+
+/// Approximate deflate-compressed size of `data`, in [`BLOCK`]-sized blocks.
+pub fn deflate_size(data: &[u8]) -> usize {
+    data.chunks(BLOCK).map(block_size).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ratio(data: &[u8]) -> f64 {
+        deflate_size(data) as f64 / data.len() as f64
+    }
+
+    #[test]
+    fn test_huffman_bits() {
+        assert_eq!(huffman_bits(&[]), 0);
+        assert_eq!(huffman_bits(&[0, 7]), 7);
+        // Two equal symbols: one bit each
+        assert_eq!(huffman_bits(&[5, 5]), 10);
+        // Codes 1, 2, 2 bits
+        assert_eq!(huffman_bits(&[4, 2, 2]), 4 + 4 + 4);
+    }
+
+    #[test]
+    fn test_deflate_size() {
+        let random = crate::csprng::generate([3u8; 32], 3 * BLOCK);
+        let r = ratio(&random);
+        assert!(r > 0.99 && r <= 1.001, "{}", r);
+
+        assert!(ratio(&vec![0u8; 100_000]) < 0.01);
+        let text = b"the quick brown fox jumps over the lazy dog. ".repeat(200);
+        assert!(ratio(&text) < 0.05);
+        // Uniform over 16 values: 4 bits per byte; its short repeats barely help
+        let nibbles: Vec<u8> = random.iter().map(|b| b & 0x0f).collect();
+        let r = ratio(&nibbles);
+        assert!((0.49..0.56).contains(&r), "{}", r);
+        assert_eq!(deflate_size(&[]), 0);
+    }
+}
@@ -1,10 +1,11 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::error::Error;
+use crate::table::Units;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
 #[serde(rename_all = "lowercase")]
 pub enum CpuRngPreference {
     Rdseed,
@@ -12,7 +13,7 @@ pub enum CpuRngPreference {
     Xstore,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct CpuRngConfig {
     pub enable_rdseed: bool,
@@ -24,6 +25,18 @@ pub struct CpuRngConfig {
     pub prefer: CpuRngPreference,
     pub fallback_mix_bytes: usize,
     pub oversample: u32,
+    /// Replace the fixed `oversample` ratio with one driven by the measured
+    /// min-entropy of the raw output.
+    pub adaptive_oversample: bool,
+    /// Adaptive mode collects until the estimated raw entropy is this many
+    /// times the output bits.
+    pub oversample_margin: f64,
+    /// Upper bound on the adaptive ratio.
+    pub max_oversample: u32,
+    /// Collector threads for large RDSEED/RDRAND requests; 0 = one per core.
+    pub threads: usize,
+    /// Requests at least this large are collected on `threads` threads.
+    pub parallel_min_bytes: usize,
 }
 
 impl Default for CpuRngConfig {
@@ -38,6 +51,11 @@ impl Default for CpuRngConfig {
             prefer: CpuRngPreference::Rdseed,
             fallback_mix_bytes: 32,
             oversample: 2,
+            adaptive_oversample: false,
+            oversample_margin: 2.0,
+            max_oversample: 16,
+            threads: 0,
+            parallel_min_bytes: 1 << 20,
         }
     }
 }
@@ -50,13 +68,823 @@ impl CpuRngConfig {
         self.xstore_quality = self.xstore_quality.clamp(0, 3);
         self.fallback_mix_bytes = self.fallback_mix_bytes.clamp(0, 1024);
         self.oversample = self.oversample.clamp(1, 16);
+        self.oversample_margin = self.oversample_margin.clamp(1.0, 8.0);
+        self.max_oversample = self.max_oversample.clamp(1, 64);
+        self.threads = self.threads.clamp(0, 256);
+    }
+}
+
+/// External command entropy source (`[exec]` section).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ExecConfig {
+    /// Program and arguments; `{bytes}` in an argument is replaced with the
+    /// requested byte count. Empty disables the source.
+    pub command: Vec<String>,
+    pub timeout_ms: u64,
+    /// Minimum raw bytes the command must produce to be accepted.
+    pub min_bytes: usize,
+    /// Assumed min-entropy of the command output in bits per byte, used to
+    /// derive health-test cutoffs.
+    pub min_entropy: f64,
+}
+
+impl Default for ExecConfig {
+    fn default() -> Self {
+        Self {
+            command: Vec::new(),
+            timeout_ms: 5000,
+            min_bytes: 32,
+            min_entropy: 1.0,
+        }
+    }
+}
+
+impl ExecConfig {
+    /// Clamp fields to valid ranges.
+    pub fn validate(&mut self) {
+        self.timeout_ms = self.timeout_ms.clamp(10, 600_000);
+        self.min_bytes = self.min_bytes.clamp(1, 1 << 20);
+        self.min_entropy = self.min_entropy.clamp(0.1, 8.0);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.command.is_empty()
+    }
+}
+
+/// Shared-object entropy source plugins (`[plugins]` section). Off unless
+/// `enabled = true`, since a plugin runs inside every mixrand process.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PluginsConfig {
+    pub enabled: bool,
+    /// Directory scanned for `*.so` plugins. Empty disables plugins.
+    pub dir: PathBuf,
+    /// Minimum raw bytes collected from a plugin per request.
+    pub min_bytes: usize,
+}
+
+impl Default for PluginsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: PathBuf::from("/usr/lib/mixrand/plugins"),
+            min_bytes: 64,
+        }
+    }
+}
+
+impl PluginsConfig {
+    /// Clamp fields to valid ranges.
+    pub fn validate(&mut self) {
+        self.min_bytes = self.min_bytes.clamp(1, 1 << 20);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled && !self.dir.as_os_str().is_empty()
+    }
+}
+
+/// Per-source time limits in milliseconds (`[timeouts]` section).
+/// 0 means no limit.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TimeoutsConfig {
+    /// Wait for /dev/hwrng before falling through with what it delivered.
+    pub hwrng_ms: u64,
+    /// Poll budget for /dev/random while a feeder keeps it going.
+    #[serde(alias = "haveged_ms")]
+    pub devrandom_ms: u64,
+    /// Time allowed for RDSEED/RDRAND/XSTORE, on top of the retry budget.
+    pub cpurng_ms: u64,
+    /// Time allowed for the fallback's CPU RNG mix-in.
+    pub fallback_ms: u64,
+}
+
+impl Default for TimeoutsConfig {
+    fn default() -> Self {
+        Self {
+            hwrng_ms: 0,
+            devrandom_ms: 2000,
+            cpurng_ms: 0,
+            fallback_ms: 0,
+        }
+    }
+}
+
+impl TimeoutsConfig {
+    /// Clamp fields to valid ranges.
+    pub fn validate(&mut self) {
+        const MAX_MS: u64 = 600_000;
+        self.hwrng_ms = self.hwrng_ms.min(MAX_MS);
+        self.devrandom_ms = self.devrandom_ms.min(MAX_MS);
+        self.cpurng_ms = self.cpurng_ms.min(MAX_MS);
+        self.fallback_ms = self.fallback_ms.min(MAX_MS);
+    }
+}
+
+/// How a serial TRNG frames its output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SerialFraming {
+    /// Raw random bytes (TrueRNG and most USB CDC dongles).
+    Raw,
+    /// ASCII hex digits; whitespace and line breaks are ignored.
+    Hex,
+    /// OneRNG: raw bytes, but output must be started and stopped with
+    /// `cmd0`/`cmdO`/`cmdo` commands.
+    Onerng,
+}
+
+/// Serial/USB TRNG entropy source (`[serial]` section).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SerialConfig {
+    /// TTY device, e.g. `/dev/ttyACM0`. Empty disables the source.
+    pub device: String,
+    pub baud: u32,
+    pub framing: SerialFraming,
+    pub timeout_ms: u64,
+    /// Assumed min-entropy of the device output in bits per byte. Sets the
+    /// health-test cutoffs and how many raw bytes are read per output byte.
+    pub min_entropy: f64,
+}
+
+impl Default for SerialConfig {
+    fn default() -> Self {
+        Self {
+            device: String::new(),
+            baud: 115_200,
+            framing: SerialFraming::Raw,
+            timeout_ms: 2000,
+            min_entropy: 4.0,
+        }
+    }
+}
+
+impl SerialConfig {
+    /// Clamp fields to valid ranges.
+    pub fn validate(&mut self) {
+        self.timeout_ms = self.timeout_ms.clamp(10, 600_000);
+        self.min_entropy = self.min_entropy.clamp(0.1, 8.0);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.device.is_empty()
+    }
+}
+
+/// Guest-side source fed by the host's `serve-vsock` (`[vsock]` section).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct VsockConfig {
+    /// Context ID of the host. 2 is the hypervisor host.
+    pub cid: u32,
+    /// Port `serve-vsock` listens on. 0 disables the source.
+    pub port: u32,
+    pub timeout_ms: u64,
+}
+
+impl Default for VsockConfig {
+    fn default() -> Self {
+        Self {
+            cid: 2,
+            port: 0,
+            timeout_ms: 2000,
+        }
+    }
+}
+
+impl VsockConfig {
+    /// Clamp fields to valid ranges.
+    pub fn validate(&mut self) {
+        self.timeout_ms = self.timeout_ms.clamp(10, 600_000);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.port != 0
+    }
+}
+
+/// Pre-generated entropy pool for serve modes (`[cache]` section).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Pool capacity in bytes. 0 disables the cache.
+    pub size_bytes: usize,
+    /// Bytes generated per refill step.
+    pub block_bytes: usize,
+    /// Cached bytes older than this are discarded unserved.
+    pub max_age_ms: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            size_bytes: 64 * 1024,
+            block_bytes: 4096,
+            max_age_ms: 10_000,
+        }
+    }
+}
+
+impl CacheConfig {
+    /// Clamp fields to valid ranges.
+    pub fn validate(&mut self) {
+        self.size_bytes = self.size_bytes.clamp(0, 64 << 20);
+        self.block_bytes = self.block_bytes.clamp(32, 1 << 20);
+        self.max_age_ms = self.max_age_ms.clamp(100, 3_600_000);
+    }
+}
+
+/// Pre-seeded generators that serve modes answer from (`[worker_pool]`
+/// section).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WorkerPoolConfig {
+    /// Generators, each keyed from the entropy pipeline at startup. 0
+    /// disables the pool.
+    pub size: usize,
+    /// Seconds between rekeys of each generator.
+    pub reseed_secs: u64,
+}
+
+impl Default for WorkerPoolConfig {
+    fn default() -> Self {
+        Self {
+            size: 0,
+            reseed_secs: 60,
+        }
+    }
+}
+
+impl WorkerPoolConfig {
+    /// Clamp fields to valid ranges.
+    pub fn validate(&mut self) {
+        self.size = self.size.min(64);
+        self.reseed_secs = self.reseed_secs.clamp(1, 86_400);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.size != 0
+    }
+}
+
+/// Entropy mixer construction (`[mixer]` section).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MixerConfig {
+    /// Pins the mixer version; 0 selects the newest supported.
+    pub version: u32,
+    /// Deployment-specific string mixed into every seed (v2 and later).
+    pub personalization: String,
+    /// Also mix the hostname and /etc/machine-id into every seed, so cloned
+    /// VMs diverge even if their other inputs match (v2 and later).
+    pub host_personalization: bool,
+}
+
+impl Default for MixerConfig {
+    fn default() -> Self {
+        Self {
+            version: 0,
+            personalization: String::new(),
+            host_personalization: true,
+        }
+    }
+}
+
+impl MixerConfig {
+    /// Clamp fields to valid ranges.
+    pub fn validate(&mut self) {
+        self.version = self.version.clamp(0, crate::mixer::LATEST_VERSION);
+    }
+}
+
+/// Request size policy (`[limits]` section). Limits cap whatever the
+/// command line asks for; 0 means unlimited.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Largest single request: one-shot `-n`, beacon values, daemon batches
+    /// and serve-mode requests.
+    pub max_request_bytes: usize,
+    /// Bytes each serve-mode client may draw per minute.
+    pub client_bytes_per_minute: u64,
+    /// Hard cap on /dev/hwrng bytes read per UTC day, shared by every
+    /// mixrand process through `hwrng_budget_file`.
+    pub hwrng_daily_bytes: u64,
+    pub hwrng_budget_file: PathBuf,
+    /// Cap on this process's /dev/hwrng read rate, so check and the daemon
+    /// don't starve the kernel's khwrngd.
+    pub hwrng_bytes_per_sec: u64,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_request_bytes: 0,
+            client_bytes_per_minute: 0,
+            hwrng_daily_bytes: 0,
+            hwrng_budget_file: PathBuf::from("/var/lib/mixrand/hwrng-budget"),
+            hwrng_bytes_per_sec: 0,
+        }
+    }
+}
+
+impl LimitsConfig {
+    /// Clamp fields to valid ranges.
+    pub fn validate(&mut self) {
+        if self.hwrng_budget_file.as_os_str().is_empty() {
+            self.hwrng_budget_file = Self::default().hwrng_budget_file;
+        }
+    }
+
+    /// Rejects a `count`-byte request larger than `max_request_bytes`.
+    pub fn check_request(&self, count: usize) -> Result<(), Error> {
+        if self.max_request_bytes != 0 && count > self.max_request_bytes {
+            return Err(Error::InvalidArgs(format!(
+                "request of {} bytes exceeds [limits] max_request_bytes = {}",
+                count, self.max_request_bytes
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Optional inputs of the fallback mixer (`[fallback]` section).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FallbackConfig {
+    /// Sample hwmon temperature, fan and voltage sensors. Some sensors sit
+    /// behind slow buses, so this is off by default.
+    pub hwmon: bool,
+    /// How long the hwmon sensors are sampled per fallback request.
+    pub hwmon_window_ms: u64,
+}
+
+impl Default for FallbackConfig {
+    fn default() -> Self {
+        Self {
+            hwmon: false,
+            hwmon_window_ms: 20,
+        }
+    }
+}
+
+impl FallbackConfig {
+    /// Clamp fields to valid ranges.
+    pub fn validate(&mut self) {
+        self.hwmon_window_ms = self.hwmon_window_ms.clamp(1, 1000);
+    }
+}
+
+/// Which sources worked on this boot, remembered between one-shot
+/// requests (`[source_cache]` section). Off unless `enabled = true`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SourceCacheConfig {
+    pub enabled: bool,
+    pub file: PathBuf,
+    /// Entries older than this are re-probed even within the same boot.
+    pub max_age_secs: u64,
+}
+
+impl Default for SourceCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            file: PathBuf::from("/var/cache/mixrand/sources.json"),
+            max_age_secs: 3600,
+        }
+    }
+}
+
+impl SourceCacheConfig {
+    /// Clamp fields to valid ranges.
+    pub fn validate(&mut self) {
+        if self.file.as_os_str().is_empty() {
+            self.file = Self::default().file;
+        }
+        self.max_age_secs = self.max_age_secs.clamp(1, 30 * 86_400);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+/// Comparison of each boot's first output with those of earlier boots, to
+/// catch a seed restored or cloned along with the disk (`[restart_test]`
+/// section). Off unless `enabled = true`, since it keeps a hash of output
+/// on disk.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RestartTestConfig {
+    pub enabled: bool,
+    pub file: PathBuf,
+    /// Leading bytes of the boot's first output that are hashed.
+    pub bytes: usize,
+    /// Earlier boots kept for comparison.
+    pub boots: usize,
+}
+
+impl Default for RestartTestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            file: PathBuf::from("/var/lib/mixrand/restart.json"),
+            bytes: 32,
+            boots: 16,
+        }
+    }
+}
+
+impl RestartTestConfig {
+    /// Clamp fields to valid ranges.
+    pub fn validate(&mut self) {
+        if self.file.as_os_str().is_empty() {
+            self.file = Self::default().file;
+        }
+        // Fewer bytes and the stored hash could be brute-forced back to them
+        self.bytes = self.bytes.clamp(16, 64);
+        self.boots = self.boots.clamp(1, 1000);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+/// The daemon's background check of its injection feed
+/// (`[quality_check]` section).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct QualityCheckConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    /// Bytes drawn from the feed per check; every full 2500-byte window is
+    /// FIPS 140-2 tested.
+    pub sample_bytes: usize,
+    /// A check fails below this min-entropy estimate, in bits per byte.
+    pub min_entropy: f64,
+    /// Failed checks in a row that raise an alert.
+    pub alert_after: u32,
+    /// `http://` URL that alerts and recoveries are POSTed to as JSON.
+    /// Empty disables it.
+    pub webhook: String,
+}
+
+impl Default for QualityCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 300,
+            sample_bytes: 25_000,
+            min_entropy: 7.0,
+            alert_after: 3,
+            webhook: String::new(),
+        }
+    }
+}
+
+impl QualityCheckConfig {
+    /// Clamp fields to valid ranges.
+    pub fn validate(&mut self) {
+        self.interval_secs = self.interval_secs.clamp(10, 86_400);
+        self.sample_bytes = self.sample_bytes.clamp(2500, 1 << 20);
+        self.min_entropy = self.min_entropy.clamp(0.0, 8.0);
+        self.alert_after = self.alert_after.clamp(1, 100);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+/// Daemon failure hooks (`[alerts]` section). Each hook is an `http://` URL
+/// the event is POSTed to as JSON, or the absolute path of a script run
+/// with the event in `MIXRAND_*` environment variables. Empty disables it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AlertsConfig {
+    /// The feed failed to produce a batch.
+    pub on_source_failure: String,
+    /// The background quality check raised an alert (`[quality_check]`).
+    pub on_health_failure: String,
+    /// The injection target rejected a batch.
+    pub on_injection_error: String,
+    /// Events of one kind within this long of the last one sent are only
+    /// counted, and the count goes out with the next.
+    pub min_interval_secs: u64,
+    /// How long a POST or script may take.
+    pub timeout_secs: u64,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            on_source_failure: String::new(),
+            on_health_failure: String::new(),
+            on_injection_error: String::new(),
+            min_interval_secs: 300,
+            timeout_secs: 10,
+        }
+    }
+}
+
+impl AlertsConfig {
+    /// Clamp fields to valid ranges.
+    pub fn validate(&mut self) {
+        self.min_interval_secs = self.min_interval_secs.clamp(0, 86_400);
+        self.timeout_secs = self.timeout_secs.clamp(1, 300);
+    }
+}
+
+/// One `[[auth.tokens]]` entry.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TokenConfig {
+    /// Names the client in the audit log, instead of the token.
+    pub name: String,
+    pub token: String,
+    /// Bytes the token may draw per minute; 0 = the server's quota.
+    pub bytes_per_minute: u64,
+}
+
+impl std::fmt::Debug for TokenConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenConfig")
+            .field("name", &self.name)
+            .field("bytes_per_minute", &self.bytes_per_minute)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Serve-mode client authentication (`[auth]` section). With any tokens,
+/// `serve-grpc` and `serve-socket` only answer requests carrying one, and
+/// log what each client drew.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    pub tokens: Vec<TokenConfig>,
+    /// More tokens, one `name token [bytes_per_minute]` per line.
+    pub tokens_file: Option<PathBuf>,
+}
+
+/// Human-readable output (`[display]` section).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    /// Prefixes for sizes and rates in reports, progress and daemon logs;
+    /// `--units` overrides.
+    pub units: Units,
+}
+
+/// How much a single source is trusted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Paranoia {
+    /// The first healthy source is used on its own
+    #[default]
+    Normal,
+    /// At least two sources are mixed
+    High,
+    /// At least three sources are mixed
+    Extreme,
+}
+
+impl Paranoia {
+    /// Sources every output mixes at this level.
+    pub fn min_sources(self) -> usize {
+        match self {
+            Paranoia::Normal => 1,
+            Paranoia::High => 2,
+            Paranoia::Extreme => 3,
+        }
+    }
+}
+
+/// Source trust policy (`[policy]` section).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PolicyConfig {
+    pub paranoia: Paranoia,
+    /// Independent sources mixed into every output; 0 = set by `paranoia`.
+    pub min_sources: usize,
+    /// Two disjoint source lists: every output is generated once from
+    /// each and the two are XORed. Empty = one run over every source.
+    pub xor_pipelines: Vec<Vec<String>>,
+}
+
+impl PolicyConfig {
+    /// Clamp fields to valid ranges.
+    pub fn validate(&mut self) {
+        self.min_sources = self.min_sources.clamp(0, 8);
+    }
+
+    /// Sources [`crate::entropy::generate`] must combine for each output.
+    pub fn required_sources(&self) -> usize {
+        match self.min_sources {
+            0 => self.paranoia.min_sources(),
+            n => n,
+        }
+    }
+}
+
+/// Collector the daemon injects from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DaemonFeed {
+    /// urandom + procfs + jitter + cpu-rng mixed through BLAKE2b → ChaCha20
+    Fallback,
+    /// Built-in HAVEGE-style cache/branch timing collector
+    Havege,
+    /// The VM host's `serve-vsock` (`[vsock]` section)
+    Vsock,
+    /// /dev/hwrng as is, credited in proportion to its rng_quality
+    Hwrng,
+}
+
+impl DaemonFeed {
+    pub fn name(self) -> &'static str {
+        match self {
+            DaemonFeed::Fallback => "fallback",
+            DaemonFeed::Havege => "havege",
+            DaemonFeed::Vsock => "vsock",
+            DaemonFeed::Hwrng => "hwrng",
+        }
+    }
+}
+
+/// How the daemon decides when to inject into the kernel pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DaemonMode {
+    /// Reseed on kernels with 5.18+ entropy accounting, threshold otherwise
+    Auto,
+    /// Inject when entropy_avail drops below the threshold
+    Threshold,
+    /// Inject on a timer and reseed the crng after each batch
+    Reseed,
+}
+
+impl DaemonMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            DaemonMode::Auto => "auto",
+            DaemonMode::Threshold => "threshold",
+            DaemonMode::Reseed => "reseed",
+        }
+    }
+}
+
+/// Where the daemon sends injected batches.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum InjectTarget {
+    /// The kernel pool, credited via RNDADDENTROPY.
+    Random,
+    /// A regular file, appended to.
+    File(PathBuf),
+    /// A named pipe, created if missing.
+    Fifo(PathBuf),
+    /// A FIFO for a hypervisor's virtio-rng backend (e.g. QEMU `rng-random`),
+    /// so guests see the stream on their /dev/hwrng.
+    HwrngLoop,
+    /// A privileged `mixrand injector` listening on this unix socket, which
+    /// credits batches to the pool on the daemon's behalf.
+    Injector(PathBuf),
+}
+
+impl std::str::FromStr for InjectTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let path = |p: &str| {
+            if p.is_empty() {
+                Err(format!("missing path in inject target '{}'", s))
+            } else {
+                Ok(PathBuf::from(p))
+            }
+        };
+        match s {
+            "/dev/random" => Ok(InjectTarget::Random),
+            "hwrng-loop" => Ok(InjectTarget::HwrngLoop),
+            _ => match s.split_once(':') {
+                Some(("file", p)) => Ok(InjectTarget::File(path(p)?)),
+                Some(("fifo", p)) => Ok(InjectTarget::Fifo(path(p)?)),
+                Some(("injector", p)) => Ok(InjectTarget::Injector(path(p)?)),
+                _ => Err(format!(
+                    "unknown inject target '{}' (expected /dev/random, file:PATH, fifo:PATH, hwrng-loop or injector:PATH)",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for InjectTarget {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        s.parse()
+    }
+}
+
+impl std::fmt::Display for InjectTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InjectTarget::Random => write!(f, "/dev/random"),
+            InjectTarget::File(p) => write!(f, "file:{}", p.display()),
+            InjectTarget::Fifo(p) => write!(f, "fifo:{}", p.display()),
+            InjectTarget::HwrngLoop => write!(f, "hwrng-loop"),
+            InjectTarget::Injector(p) => write!(f, "injector:{}", p.display()),
+        }
+    }
+}
+
+/// Daemon defaults (`[daemon]` section); command-line flags override them.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DaemonConfig {
+    /// Bits of entropy credited per injected byte (0-8); see `mixrand calibrate`.
+    /// 0 writes batches to /dev/random every round without crediting them.
+    pub credit_ratio: u32,
+    /// Collector for injected batches.
+    pub feed: DaemonFeed,
+    /// Destination for injected batches.
+    pub inject_target: InjectTarget,
+    /// When batches for the kernel pool are injected.
+    pub mode: DaemonMode,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            credit_ratio: 4,
+            feed: DaemonFeed::Fallback,
+            inject_target: InjectTarget::Random,
+            mode: DaemonMode::Auto,
+        }
+    }
+}
+
+impl DaemonConfig {
+    /// Clamp fields to valid ranges.
+    pub fn validate(&mut self) {
+        self.credit_ratio = self.credit_ratio.min(8);
     }
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
 #[serde(default)]
 pub struct Config {
+    /// FIPS mode: output only from HMAC_DRBG, startup self-tests, health
+    /// tests on every entropy input, and no output after a failure.
+    pub fips: bool,
     pub cpu_rng: CpuRngConfig,
+    pub fallback: FallbackConfig,
+    pub exec: ExecConfig,
+    pub serial: SerialConfig,
+    pub vsock: VsockConfig,
+    pub plugins: PluginsConfig,
+    pub timeouts: TimeoutsConfig,
+    pub cache: CacheConfig,
+    pub worker_pool: WorkerPoolConfig,
+    pub mixer: MixerConfig,
+    pub daemon: DaemonConfig,
+    pub quality_check: QualityCheckConfig,
+    pub alerts: AlertsConfig,
+    pub limits: LimitsConfig,
+    pub source_cache: SourceCacheConfig,
+    pub restart_test: RestartTestConfig,
+    pub display: DisplayConfig,
+    pub policy: PolicyConfig,
+    pub auth: AuthConfig,
+}
+
+impl Config {
+    /// Clamp every section to valid ranges.
+    pub fn validate(&mut self) {
+        self.cpu_rng.validate();
+        self.fallback.validate();
+        self.exec.validate();
+        self.serial.validate();
+        self.vsock.validate();
+        self.plugins.validate();
+        self.timeouts.validate();
+        self.cache.validate();
+        self.worker_pool.validate();
+        self.mixer.validate();
+        self.daemon.validate();
+        self.quality_check.validate();
+        self.alerts.validate();
+        self.limits.validate();
+        self.source_cache.validate();
+        self.restart_test.validate();
+        self.policy.validate();
+    }
 }
 
 /// Load configuration from a TOML file.
@@ -121,6 +949,8 @@ mod tests {
             xstore_quality: 10,
             fallback_mix_bytes: 2000,
             oversample: 50,
+            oversample_margin: 20.0,
+            max_oversample: 100,
             ..Default::default()
         };
         cfg.validate();
@@ -129,6 +959,8 @@ mod tests {
         assert_eq!(cfg.xstore_quality, 3);
         assert_eq!(cfg.fallback_mix_bytes, 1024);
         assert_eq!(cfg.oversample, 16);
+        assert_eq!(cfg.oversample_margin, 8.0);
+        assert_eq!(cfg.max_oversample, 64);
     }
 
     #[test]
@@ -175,6 +1007,156 @@ prefer = "rdrand"
         let _ = std::fs::remove_file(&path);
     }
 
+    #[test]
+    fn test_exec_section_parsing() {
+        let config: Config = toml::from_str(
+            r#"
+[exec]
+command = ["tpm2_getrandom", "{bytes}"]
+timeout_ms = 1
+"#,
+        )
+        .unwrap();
+        assert!(config.exec.is_enabled());
+        assert_eq!(config.exec.command[1], "{bytes}");
+        let mut exec = config.exec;
+        exec.validate();
+        assert_eq!(exec.timeout_ms, 10);
+        assert_eq!(exec.min_bytes, 32);
+        assert!(!Config::default().exec.is_enabled());
+    }
+
+    #[test]
+    fn test_serial_section_parsing() {
+        let config: Config = toml::from_str(
+            r#"
+[serial]
+device = "/dev/ttyACM0"
+framing = "onerng"
+min_entropy = 12.0
+"#,
+        )
+        .unwrap();
+        assert!(config.serial.is_enabled());
+        assert_eq!(config.serial.framing, SerialFraming::Onerng);
+        assert_eq!(config.serial.baud, 115_200);
+        let mut serial = config.serial;
+        serial.validate();
+        assert_eq!(serial.min_entropy, 8.0);
+        assert!(!Config::default().serial.is_enabled());
+    }
+
+    #[test]
+    fn test_vsock_section_parsing() {
+        let config: Config = toml::from_str(
+            r#"
+[vsock]
+port = 4000
+"#,
+        )
+        .unwrap();
+        assert!(config.vsock.is_enabled());
+        assert_eq!(config.vsock.cid, 2);
+        assert_eq!(config.vsock.timeout_ms, 2000);
+        assert!(!Config::default().vsock.is_enabled());
+    }
+
+    #[test]
+    fn test_restart_test_section_parsing() {
+        let config: Config = toml::from_str(
+            r#"
+[restart_test]
+enabled = true
+bytes = 8
+"#,
+        )
+        .unwrap();
+        assert!(config.restart_test.is_enabled());
+        let mut restart_test = config.restart_test;
+        restart_test.validate();
+        assert_eq!(restart_test.bytes, 16);
+        assert!(!Config::default().restart_test.is_enabled());
+    }
+
+    #[test]
+    fn test_display_section_parsing() {
+        let config: Config = toml::from_str("[display]\nunits = \"iec\"\n").unwrap();
+        assert_eq!(config.display.units, Units::Iec);
+        assert_eq!(Config::default().display.units, Units::Si);
+        assert!(toml::from_str::<Config>("[display]\nunits = \"kib\"\n").is_err());
+    }
+
+    #[test]
+    fn test_fips_flag_parsing() {
+        let config: Config = toml::from_str("fips = true\n[daemon]\ncredit_ratio = 2\n").unwrap();
+        assert!(config.fips);
+        assert!(!Config::default().fips);
+    }
+
+    #[test]
+    fn test_policy_section_parsing() {
+        let config: Config = toml::from_str("[policy]\nparanoia = \"high\"\n").unwrap();
+        assert_eq!(config.policy.required_sources(), 2);
+        let config: Config =
+            toml::from_str("[policy]\nparanoia = \"extreme\"\nmin_sources = 4\n").unwrap();
+        assert_eq!(config.policy.required_sources(), 4);
+        assert_eq!(Config::default().policy.required_sources(), 1);
+        assert!(toml::from_str::<Config>("[policy]\nparanoia = \"tinfoil\"\n").is_err());
+    }
+
+    #[test]
+    fn test_daemon_section_parsing() {
+        let config: Config = toml::from_str(
+            r#"
+[daemon]
+feed = "havege"
+inject_target = "fifo:/run/entropy.fifo"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.daemon.feed, DaemonFeed::Havege);
+        assert_eq!(
+            config.daemon.inject_target,
+            InjectTarget::Fifo(PathBuf::from("/run/entropy.fifo"))
+        );
+        assert_eq!(config.daemon.credit_ratio, 4);
+        assert_eq!(Config::default().daemon.feed, DaemonFeed::Fallback);
+    }
+
+    #[test]
+    fn test_inject_target_parsing() {
+        for s in [
+            "/dev/random",
+            "file:/tmp/pool",
+            "fifo:/run/e",
+            "hwrng-loop",
+            "injector:/run/mixrand/injector.sock",
+        ] {
+            assert_eq!(s.parse::<InjectTarget>().unwrap().to_string(), s);
+        }
+        assert!("file:".parse::<InjectTarget>().is_err());
+        assert!("injector:".parse::<InjectTarget>().is_err());
+        assert!("/dev/urandom".parse::<InjectTarget>().is_err());
+        assert!(toml::from_str::<Config>("[daemon]\ninject_target = \"tcp:1\"\n").is_err());
+    }
+
+    #[test]
+    fn test_timeouts_section_parsing() {
+        let mut config: Config = toml::from_str(
+            r#"
+[timeouts]
+hwrng_ms = 500
+cpurng_ms = 99999999
+"#,
+        )
+        .unwrap();
+        config.validate();
+        assert_eq!(config.timeouts.hwrng_ms, 500);
+        assert_eq!(config.timeouts.devrandom_ms, 2000);
+        assert_eq!(config.timeouts.cpurng_ms, 600_000);
+        assert_eq!(config.timeouts.fallback_ms, 0);
+    }
+
     #[test]
     fn test_missing_explicit_config_errors() {
         let path = std::path::Path::new("/tmp/mixrand_nonexistent_config.toml");
@@ -0,0 +1,347 @@
+//! `check --cpu-rng-diff`: RDRAND and RDSEED sampled side by side on the
+//! same core. Both should pass the statistical tests, but RDSEED reads the
+//! conditioner directly and should be slower and underflow more often than
+//! RDRAND's DRBG. Buggy firmware shows up as constant words, repeats, or an
+//! RDSEED that behaves exactly like RDRAND.
+
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use crate::analyze::{Analysis, Analyzer};
+use crate::check;
+use crate::entropy::cpurng;
+use crate::error::Error;
+use crate::probe::{self, Finding, Status};
+use crate::signals::{self, Signal};
+use crate::stats;
+use crate::table::{self, Align, Table, Units};
+
+/// Attempts per instruction before switching to the other one.
+const BATCH: usize = 256;
+
+/// Distinct words remembered per instruction for the repeat checks.
+const MAX_SEEN: usize = 1 << 20;
+
+/// Upper bound of the FIPS pass-rate interval below which an instruction
+/// fails. Good sources fail about one window in a thousand.
+const MIN_FIPS_PASS: f64 = 0.98;
+
+/// RDSEED at this fraction of RDRAND's rate with no underflows looks like
+/// RDRAND output.
+const SUSPICIOUS_RATE_RATIO: f64 = 0.9;
+
+/// Words RDSEED must deliver before its rate and underflows say anything.
+const MIN_RATE_WORDS: u64 = 4096;
+
+/// One instruction's samples.
+struct Profile {
+    name: &'static str,
+    attempts: u64,
+    underflows: u64,
+    time: Duration,
+    words: u64,
+    /// All-zero or all-one words, the signature of known firmware bugs.
+    constant: u64,
+    repeats: u64,
+    seen: HashSet<u64>,
+    analyzer: Analyzer,
+}
+
+impl Profile {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            attempts: 0,
+            underflows: 0,
+            time: Duration::ZERO,
+            words: 0,
+            constant: 0,
+            repeats: 0,
+            seen: HashSet::new(),
+            analyzer: Analyzer::new(8),
+        }
+    }
+
+    /// Records a delivered word. Returns false if it was seen before.
+    fn record(&mut self, word: u64) -> bool {
+        self.words += 1;
+        if word == 0 || word == u64::MAX {
+            self.constant += 1;
+        }
+        let fresh = if self.seen.len() < MAX_SEEN {
+            self.seen.insert(word)
+        } else {
+            !self.seen.contains(&word)
+        };
+        if !fresh {
+            self.repeats += 1;
+        }
+        fresh
+    }
+
+    /// Words per second.
+    fn rate(&self) -> f64 {
+        match self.time.as_secs_f64() {
+            t if t > 0.0 => self.words as f64 / t,
+            _ => 0.0,
+        }
+    }
+
+    fn underflow_pct(&self) -> f64 {
+        match self.attempts {
+            0 => 0.0,
+            n => 100.0 * self.underflows as f64 / n as f64,
+        }
+    }
+}
+
+/// RDRAND and RDSEED profiles plus the words both returned.
+struct Differential {
+    rdrand: Profile,
+    rdseed: Profile,
+    shared: u64,
+}
+
+impl Differential {
+    fn new() -> Self {
+        Self {
+            rdrand: Profile::new("rdrand"),
+            rdseed: Profile::new("rdseed"),
+            shared: 0,
+        }
+    }
+
+    /// Runs `BATCH` attempts of `step` into `profile`, checking fresh words
+    /// against `other`.
+    fn batch(
+        profile: &mut Profile,
+        other: &Profile,
+        shared: &mut u64,
+        step: fn() -> Option<u64>,
+        buf: &mut Vec<u8>,
+    ) {
+        buf.clear();
+        let start = Instant::now();
+        for _ in 0..BATCH {
+            match step() {
+                Some(word) => buf.extend_from_slice(&word.to_ne_bytes()),
+                None => profile.underflows += 1,
+            }
+        }
+        profile.time += start.elapsed();
+        profile.attempts += BATCH as u64;
+        for word in buf.chunks_exact(8) {
+            let word = u64::from_ne_bytes(word.try_into().unwrap());
+            if profile.record(word) && other.seen.contains(&word) {
+                *shared += 1;
+            }
+        }
+        profile.analyzer.update(buf);
+        cpurng::zeroize_vec(buf);
+    }
+
+    /// Alternates batches of both instructions until `deadline`.
+    fn sample(&mut self, deadline: Instant) {
+        let mut buf = Vec::with_capacity(BATCH * 8);
+        while Instant::now() < deadline && !signals::shutdown_requested() {
+            Self::batch(
+                &mut self.rdrand,
+                &self.rdseed,
+                &mut self.shared,
+                cpurng::rdrand_once,
+                &mut buf,
+            );
+            Self::batch(
+                &mut self.rdseed,
+                &self.rdrand,
+                &mut self.shared,
+                cpurng::rdseed_once,
+                &mut buf,
+            );
+        }
+    }
+
+    fn findings(&self, rdrand: &Analysis, rdseed: &Analysis) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for (p, a) in [(&self.rdrand, rdrand), (&self.rdseed, rdseed)] {
+            if p.words == 0 {
+                findings.push(Finding::new(
+                    p.name,
+                    Status::Fail,
+                    format!("delivered nothing in {} attempts", p.attempts),
+                ));
+                continue;
+            }
+            if p.constant > 0 {
+                let mut f = Finding::new(
+                    p.name,
+                    Status::Fail,
+                    format!("returned {} all-zero or all-one words", p.constant),
+                );
+                f.hints
+                    .push("known firmware bug; update the BIOS/microcode".into());
+                findings.push(f);
+            }
+            if p.repeats > 0 {
+                findings.push(Finding::new(
+                    p.name,
+                    Status::Fail,
+                    format!("repeated {} of {} 64-bit words", p.repeats, p.words),
+                ));
+            }
+            let (_, hi) = stats::wilson_interval(a.fips.all, a.fips.windows, stats::Z_95);
+            if a.fips.windows > 0 && hi < MIN_FIPS_PASS {
+                findings.push(Finding::new(
+                    p.name,
+                    Status::Fail,
+                    format!(
+                        "FIPS 140-2 pass rate {} over {} windows",
+                        check::fips_pass_ci(&a.fips, a.fips.all),
+                        a.fips.windows
+                    ),
+                ));
+            }
+        }
+        if self.shared > 0 {
+            findings.push(Finding::new(
+                "cpu-rng",
+                Status::Fail,
+                format!("{} words came from both RDRAND and RDSEED", self.shared),
+            ));
+        }
+        let (rdrand, rdseed) = (&self.rdrand, &self.rdseed);
+        if rdseed.words >= MIN_RATE_WORDS
+            && rdrand.words > 0
+            && rdseed.underflows == 0
+            && rdseed.rate() >= SUSPICIOUS_RATE_RATIO * rdrand.rate()
+        {
+            let mut f = Finding::new(
+                "rdseed",
+                Status::Warn,
+                format!(
+                    "never underflowed and ran at {} of RDRAND's rate",
+                    table::percent(100.0 * rdseed.rate() / rdrand.rate(), 0)
+                ),
+            );
+            f.details
+                .push("RDSEED may be returning RDRAND's expanded output".into());
+            f.hints
+                .push("common under hypervisors that emulate RDSEED; check on bare metal".into());
+            findings.push(f);
+        }
+        findings
+    }
+}
+
+fn print_table(profiles: [(&Profile, &Analysis); 2], units: Units) {
+    let mut t = Table::new(&[
+        ("Instruction", Align::Left),
+        ("Words", Align::Right),
+        ("Rate", Align::Right),
+        ("Underflow", Align::Right),
+        ("FIPS all (95% CI)", Align::Right),
+        ("Shannon", Align::Right),
+        ("Min-ent", Align::Right),
+    ])
+    .indent(2);
+    for (p, a) in profiles {
+        t.row(vec![
+            p.name.to_uppercase(),
+            p.words.to_string(),
+            table::rate(8.0 * p.rate(), units),
+            table::percent(p.underflow_pct(), 2),
+            check::fips_pass_ci(&a.fips, a.fips.all),
+            table::decimal(a.estimates.shannon, 4),
+            table::decimal(a.estimates.min_entropy, 4),
+        ]);
+    }
+    print!("{}", t.render());
+}
+
+/// Samples both instructions for `duration` and reports. Fails if either
+/// instruction looks broken.
+pub fn run(duration: Duration, units: Units) -> Result<(), Error> {
+    let features = cpurng::cpu_features();
+    if !features.rdrand || !features.rdseed {
+        return Err(Error::NoEntropy(
+            "--cpu-rng-diff needs a CPU with both RDRAND and RDSEED".into(),
+        ));
+    }
+    signals::install(&[Signal::Term, Signal::Int]);
+    eprintln!(
+        "Sampling RDRAND and RDSEED for {}...",
+        check::format_duration(duration)
+    );
+
+    let mut diff = Differential::new();
+    diff.sample(Instant::now() + duration);
+    let rdrand = diff.rdrand.analyzer.finish();
+    let rdseed = diff.rdseed.analyzer.finish();
+
+    println!("--- RDRAND vs RDSEED ---");
+    print_table([(&diff.rdrand, &rdrand), (&diff.rdseed, &rdseed)], units);
+    println!();
+    let findings = diff.findings(&rdrand, &rdseed);
+    if findings.is_empty() {
+        println!("Both instructions look healthy.");
+    }
+    for f in &findings {
+        probe::print_finding(f);
+    }
+    if findings.iter().any(|f| f.status == Status::Fail) {
+        return Err(Error::NoEntropy("CPU RNG differential check failed".into()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(p: &mut Profile, words: impl Iterator<Item = u64>) {
+        let mut buf = Vec::new();
+        for w in words {
+            p.record(w);
+            buf.extend_from_slice(&w.to_ne_bytes());
+        }
+        p.attempts = p.words;
+        p.time = Duration::from_millis(p.words);
+        p.analyzer.update(&buf);
+    }
+
+    fn statuses(diff: &Differential) -> Vec<(&'static str, Status)> {
+        let (a, b) = (diff.rdrand.analyzer.finish(), diff.rdseed.analyzer.finish());
+        diff.findings(&a, &b)
+            .iter()
+            .map(|f| (f.name, f.status))
+            .collect()
+    }
+
+    fn words(seed: u8, n: usize) -> impl Iterator<Item = u64> {
+        let bytes = crate::csprng::generate([seed; 32], n * 8);
+        (0..n).map(move |i| u64::from_ne_bytes(bytes[i * 8..i * 8 + 8].try_into().unwrap()))
+    }
+
+    #[test]
+    fn test_findings() {
+        let mut diff = Differential::new();
+        fill(&mut diff.rdrand, words(1, 5000));
+        fill(&mut diff.rdseed, words(2, 5000));
+        diff.rdseed.underflows = 100;
+        assert!(statuses(&diff).is_empty());
+
+        // RDSEED as fast as RDRAND and never underflowing
+        diff.rdseed.underflows = 0;
+        assert_eq!(statuses(&diff), [("rdseed", Status::Warn)]);
+
+        let mut diff = Differential::new();
+        fill(&mut diff.rdrand, words(1, 5000));
+        fill(&mut diff.rdseed, std::iter::repeat_n(u64::MAX, 5000));
+        diff.rdseed.underflows = 100;
+        let found = statuses(&diff);
+        assert!(found
+            .iter()
+            .all(|&(name, s)| name == "rdseed" && s == Status::Fail));
+        assert_eq!(found.len(), 3);
+    }
+}
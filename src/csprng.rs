@@ -1,17 +1,47 @@
+use chacha20::cipher::KeyIvInit;
+use chacha20::XChaCha20;
 use rand_chacha::ChaCha20Rng;
 use rand_core::{RngCore, SeedableRng};
 
+use crate::timing::{self, Stage};
+
 /// Seeds a ChaCha20Rng with the given 32-byte seed and generates `count` random bytes.
 pub fn generate(seed: [u8; 32], count: usize) -> Vec<u8> {
+    timing::time(Stage::Csprng, || {
+        let mut rng = ChaCha20Rng::from_seed(seed);
+        let mut buf = vec![0u8; count];
+        rng.fill_bytes(&mut buf);
+        buf
+    })
+}
+
+/// Like [`generate`], but each call returns the next `count` bytes of the
+/// same stream, for callers that need more after the first batch.
+pub fn stream(seed: [u8; 32]) -> impl FnMut(usize) -> Vec<u8> {
     let mut rng = ChaCha20Rng::from_seed(seed);
-    let mut buf = vec![0u8; count];
-    rng.fill_bytes(&mut buf);
-    buf
+    move |count| {
+        timing::time(Stage::Csprng, || {
+            let mut buf = vec![0u8; count];
+            rng.fill_bytes(&mut buf);
+            buf
+        })
+    }
+}
+
+/// XChaCha20 (draft-irtf-cfrg-xchacha) keystream for `key` and a 24-byte
+/// `nonce`. Its 32-bit block counter limits one key to
+/// [`XCHACHA20_KEYSTREAM_BYTES`].
+pub fn xchacha20(key: &[u8; 32], nonce: &[u8; 24]) -> XChaCha20 {
+    XChaCha20::new(key.into(), nonce.into())
 }
 
+/// Keystream one [`xchacha20`] key and nonce can produce.
+pub const XCHACHA20_KEYSTREAM_BYTES: u64 = u32::MAX as u64 * 64;
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chacha20::cipher::StreamCipher;
 
     #[test]
     fn test_deterministic_same_seed() {
@@ -35,4 +65,28 @@ mod tests {
             assert_eq!(out.len(), size);
         }
     }
+
+    fn unhex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_xchacha20_keystream() {
+        let key: [u8; 32] = std::array::from_fn(|i| 0x80 + i as u8);
+        let nonce: [u8; 24] = std::array::from_fn(|i| 0x40 + i as u8);
+        let mut buf = [0u8; 160];
+        xchacha20(&key, &nonce).apply_keystream(&mut buf);
+        assert_eq!(
+            buf[..32].to_vec(),
+            unhex("7b191f80f361f099094f6f4b8fb97df847cc6873a8f2b190dd73807183f907d5")
+        );
+        // Third block: the counter advances independently of the nonce
+        assert_eq!(
+            buf[128..].to_vec(),
+            unhex("41e0e28462dbbc65c5721999e4aec9be4b57c90ba51c3cfa04d7141516a6918a")
+        );
+    }
 }
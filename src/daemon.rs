@@ -1,18 +1,58 @@
+use std::cell::RefCell;
+use std::ffi::CString;
 use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
 use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 
+use crate::alerts::{Alerts, EventKind};
+use crate::baseline;
+use crate::cadence::Cadence;
 use crate::cli::DaemonArgs;
-use crate::config::CpuRngConfig;
-use crate::entropy::fallback;
+use crate::config::{self, Config, DaemonFeed, DaemonMode, InjectTarget};
+use crate::entropy::{self, cpurng, devrandom, earlyboot, fallback, havege, hwrng, vsock};
 use crate::error::Error;
+use crate::fips;
+use crate::http::Endpoint;
+use crate::injector;
+use crate::landlock::{self, Access};
+use crate::logging;
+use crate::mixer::{self, Sponge};
+use crate::qualitycheck::{self, Transition};
+use crate::restart;
+use crate::resume::{self, ResumeDetector};
+use crate::sandbox::{self, Policy};
+use crate::schedule::Schedule;
+use crate::secmem;
+use crate::seedfile;
+use crate::signals::{self, Signal};
+use crate::status::DaemonStatus;
+use crate::table::{self, Units};
+use crate::usdt;
 
 /// ioctl number for RNDADDENTROPY: _IOW('R', 0x03, int[2])
-const RNDADDENTROPY: libc::c_ulong = 0x40085203;
+pub const RNDADDENTROPY: libc::c_ulong = 0x40085203;
 
-static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+/// ioctl number for RNDRESEEDCRNG: _IO('R', 0x07)
+pub const RNDRESEEDCRNG: libc::c_ulong = 0x5207;
+
+/// First kernel whose pool is a 256-bit BLAKE2s hash, which keeps
+/// entropy_avail at 256 once the crng is seeded.
+const FIXED_POOL_KERNEL: (u32, u32) = (5, 18);
+const FIXED_POOL_BITS: u32 = 256;
+
+/// Injection cadence in reseed mode without `--inject-every`/`--inject-cron`,
+/// matching the crng's own reseed interval.
+const RESEED_EVERY: Duration = Duration::from_secs(60);
+
+/// FIFO created for `--inject-target hwrng-loop`.
+const HWRNG_LOOP_PATH: &str = "/run/mixrand/hwrng";
 
 /// Build the `rand_pool_info` struct as a raw byte buffer:
 /// ```text
@@ -22,7 +62,7 @@ static SHUTDOWN: AtomicBool = AtomicBool::new(false);
 ///     __u32 buf[];        // entropy data (must be u32-aligned)
 /// };
 /// ```
-fn build_rand_pool_info(data: &[u8], entropy_bits: u32) -> Vec<u8> {
+pub fn build_rand_pool_info(data: &[u8], entropy_bits: u32) -> Vec<u8> {
     let buf_size = data.len() as i32;
     // Pad data to 4-byte alignment
     let padded_len = (data.len() + 3) & !3;
@@ -34,16 +74,52 @@ fn build_rand_pool_info(data: &[u8], entropy_bits: u32) -> Vec<u8> {
     buf
 }
 
-/// Inject entropy into the kernel pool via ioctl(RNDADDENTROPY).
-fn inject_entropy(dev_random: &File, data: &[u8], entropy_bits: u32) -> Result<(), Error> {
-    let buf = build_rand_pool_info(data, entropy_bits);
-    let ret = unsafe { libc::ioctl(dev_random.as_raw_fd(), RNDADDENTROPY, buf.as_ptr()) };
-    if ret < 0 {
+/// Asks the kernel to reseed the crng from the pool via
+/// ioctl(RNDRESEEDCRNG), so an injected batch reaches readers at once.
+fn reseed_crng(dev_random: &File) -> Result<(), Error> {
+    if unsafe { libc::ioctl(dev_random.as_raw_fd(), RNDRESEEDCRNG) } < 0 {
         return Err(std::io::Error::last_os_error().into());
     }
     Ok(())
 }
 
+/// Resolves `auto` from the pool size the kernel reports (`poolsize`), or
+/// from its release where that sysctl is missing.
+fn detect_mode(poolsize: Option<u32>, release: &str) -> DaemonMode {
+    let fixed = match poolsize {
+        Some(bits) => bits == FIXED_POOL_BITS,
+        None => devrandom::parse_release(release).is_some_and(|v| v >= FIXED_POOL_KERNEL),
+    };
+    if fixed {
+        DaemonMode::Reseed
+    } else {
+        DaemonMode::Threshold
+    }
+}
+
+fn read_poolsize() -> Option<u32> {
+    fs::read_to_string("/proc/sys/kernel/random/poolsize")
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Inject entropy into the kernel pool via ioctl(RNDADDENTROPY).
+pub fn inject_entropy(dev_random: &File, data: &[u8], entropy_bits: u32) -> Result<(), Error> {
+    let buf = build_rand_pool_info(data, entropy_bits);
+    loop {
+        let ret = unsafe { libc::ioctl(dev_random.as_raw_fd(), RNDADDENTROPY, buf.as_ptr()) };
+        if ret >= 0 {
+            return Ok(());
+        }
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted || signals::shutdown_requested() {
+            return Err(err.into());
+        }
+    }
+}
+
 /// Read the current kernel entropy estimate from procfs.
 fn read_entropy_avail() -> Result<u32, Error> {
     let s = fs::read_to_string("/proc/sys/kernel/random/entropy_avail")?;
@@ -53,7 +129,7 @@ fn read_entropy_avail() -> Result<u32, Error> {
 }
 
 /// Validate that we can open /dev/random for writing (requires root).
-fn validate_permissions() -> Result<File, Error> {
+pub fn validate_permissions() -> Result<File, Error> {
     OpenOptions::new()
         .write(true)
         .open("/dev/random")
@@ -68,96 +144,1086 @@ fn validate_permissions() -> Result<File, Error> {
         })
 }
 
-extern "C" fn signal_handler(_sig: libc::c_int) {
-    SHUTDOWN.store(true, Ordering::Relaxed);
+/// Creates the FIFO at `path` if missing and opens it read-write and
+/// non-blocking: holding the read end keeps opens and writes from blocking
+/// or failing while no reader is attached.
+fn open_fifo(path: &Path) -> Result<File, Error> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_fifo() => {}
+        Ok(_) => {
+            return Err(Error::InvalidArgs(format!(
+                "{} exists and is not a FIFO",
+                path.display()
+            )))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let c_path = CString::new(path.as_os_str().as_bytes())
+                .map_err(|e| Error::InvalidArgs(e.to_string()))?;
+            if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+        }
+        Err(e) => return Err(e.into()),
+    }
+    Ok(OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)?)
+}
+
+/// Destination of injected batches.
+enum Sink {
+    /// /dev/random; batches are credited via RNDADDENTROPY.
+    Pool(File),
+    /// A file or FIFO; batches are written without credit.
+    Stream { file: File, target: String },
+    /// A privileged `mixrand injector`, which credits batches like the
+    /// pool. A broken connection is reopened on the next batch.
+    Injector {
+        conn: RefCell<Option<File>>,
+        path: PathBuf,
+        target: String,
+    },
+}
+
+impl Sink {
+    fn open(target: &InjectTarget) -> Result<Self, Error> {
+        let file = match target {
+            InjectTarget::Random => return Ok(Sink::Pool(validate_permissions()?)),
+            InjectTarget::Injector(path) => {
+                return Ok(Sink::Injector {
+                    conn: RefCell::new(Some(injector::connect(path)?)),
+                    path: path.clone(),
+                    target: target.to_string(),
+                })
+            }
+            InjectTarget::File(path) => OpenOptions::new()
+                .create(true)
+                .append(true)
+                .mode(0o600)
+                .open(path)
+                .map_err(|e| {
+                    Error::InvalidArgs(format!("cannot open {}: {}", path.display(), e))
+                })?,
+            InjectTarget::Fifo(path) => open_fifo(path)?,
+            InjectTarget::HwrngLoop => {
+                let path = Path::new(HWRNG_LOOP_PATH);
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                open_fifo(path)?
+            }
+        };
+        Ok(Sink::Stream {
+            file,
+            target: target.to_string(),
+        })
+    }
+
+    fn pool(&self) -> Option<&File> {
+        match self {
+            Sink::Pool(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    /// Whether batches are credited, and so gated on the threshold.
+    fn credited(&self) -> bool {
+        !matches!(self, Sink::Stream { .. })
+    }
+
+    /// Prefix for injection errors.
+    fn label(&self) -> &str {
+        match self {
+            Sink::Pool(_) => "ioctl",
+            Sink::Stream { target, .. } | Sink::Injector { target, .. } => target,
+        }
+    }
+
+    /// Bits credited for a `len`-byte batch.
+    fn credit_bits(&self, len: usize, credit_ratio: u32) -> u32 {
+        match self {
+            Sink::Pool(_) | Sink::Injector { .. } => len as u32 * credit_ratio,
+            Sink::Stream { .. } => 0,
+        }
+    }
+
+    /// Sends `data`, returning how many bytes were taken. A FIFO whose reader
+    /// has fallen behind takes only what fits in the pipe. Uncredited data
+    /// is written to /dev/random, which mixes it in like the ioctl would.
+    fn inject(&self, data: &[u8], credit_bits: u32) -> Result<usize, Error> {
+        let taken = self.send(data, credit_bits)?;
+        usdt::injection(taken, credit_bits);
+        Ok(taken)
+    }
+
+    /// [`inject`](Self::inject) without the USDT probe.
+    fn send(&self, data: &[u8], credit_bits: u32) -> Result<usize, Error> {
+        let mut file = match self {
+            Sink::Pool(f) if credit_bits == 0 => f,
+            Sink::Pool(f) => return inject_entropy(f, data, credit_bits).map(|()| data.len()),
+            Sink::Stream { file, .. } => file,
+            Sink::Injector { conn, path, .. } => {
+                let mut conn = conn.borrow_mut();
+                let c = match conn.take() {
+                    Some(c) => c,
+                    None => injector::connect(path)?,
+                };
+                injector::send(&c, data, credit_bits)?;
+                *conn = Some(c);
+                return Ok(data.len());
+            }
+        };
+        let mut written = 0;
+        while written < data.len() {
+            match file.write(&data[written..]) {
+                Ok(0) => break,
+                Ok(n) => written += n,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(written)
+    }
+}
+
+/// Collects and injects the `--early-boot` seed, crediting 8 bits per byte.
+/// Returns the credited bits.
+fn early_boot_inject(
+    sink: &Sink,
+    args: &DaemonArgs,
+    config: &Config,
+    units: Units,
+) -> Result<u32, Error> {
+    log::info!(
+        target: "mixrand::daemon",
+        "early boot: collecting for {}s", args.early_boot_secs,
+    );
+    let mut seed = earlyboot::collect_early_boot_seed(
+        args.early_boot_bytes,
+        Duration::from_secs(args.early_boot_secs),
+        config,
+    )?;
+    if config.fips {
+        seed = fips::condition(seed, args.early_boot_bytes)?;
+    }
+    let locked = secmem::protect(&seed);
+    let credit_bits = (seed.len() * 8) as u32;
+    let result = sink.inject(&seed, credit_bits);
+    cpurng::zeroize_vec(&mut seed);
+    drop(locked);
+    result?;
+    log::info!(
+        target: "mixrand::daemon",
+        "early boot: injected {} ({}bits credit)",
+        table::bytes(args.early_boot_bytes as u64, units), credit_bits,
+    );
+    Ok(credit_bits)
+}
+
+/// Collects one injection batch from the configured feed, giving the
+/// fallback feed `[timeouts] fallback_ms` for its CPU RNG mix-in. In FIPS
+/// mode the feed only seeds the HMAC_DRBG that produces the batch.
+fn generate_feed(feed: DaemonFeed, count: usize, config: &Config) -> Result<Vec<u8>, Error> {
+    let want = if config.fips {
+        fips::input_bytes(count)
+    } else {
+        count
+    };
+    let data = match feed {
+        DaemonFeed::Fallback => fallback::generate_fallback_until(
+            want,
+            config,
+            entropy::timeout_deadline(config.timeouts.fallback_ms),
+        ),
+        DaemonFeed::Havege => havege::read_havege(want),
+        DaemonFeed::Vsock => vsock::read_vsock(want, &config.vsock),
+        DaemonFeed::Hwrng => hwrng::read_hwrng_within(want, config.timeouts.hwrng_ms),
+    }?;
+    if config.fips {
+        fips::condition(data, count)
+    } else {
+        Ok(data)
+    }
+}
+
+/// The feed and a mixer sponge that has absorbed every batch collected
+/// from it. Each batch is XORed with output extracted before the batch is
+/// absorbed, so one from a briefly weak source is still masked by all the
+/// batches before it, without keeping them around. Not in FIPS mode, where
+/// batches only come from the HMAC_DRBG.
+struct FeedPool {
+    feed: DaemonFeed,
+    sponge: Option<Sponge>,
+    /// `rng_quality` of `--feed hwrng`, which scales its credit.
+    quality: Option<u32>,
+}
+
+impl FeedPool {
+    fn new(feed: DaemonFeed, config: &Config, quality: Option<u32>) -> Self {
+        Self {
+            feed,
+            sponge: (!config.fips).then(mixer::sponge),
+            quality,
+        }
+    }
+
+    /// Bits `sink` credits for a `len`-byte batch, scaled down to the
+    /// feed's declared quality.
+    fn credit_bits(&self, sink: &Sink, len: usize, credit_ratio: u32) -> u32 {
+        let bits = sink.credit_bits(len, credit_ratio);
+        match self.quality {
+            Some(quality) => hwrng::scale_credit(bits, quality),
+            None => bits,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        self.feed.name()
+    }
+
+    fn collect(&mut self, count: usize, config: &Config) -> Result<Vec<u8>, Error> {
+        let mut data = generate_feed(self.feed, count, config)?;
+        if let Some(ref mut sponge) = self.sponge {
+            let mut mask = sponge.expand(data.len());
+            sponge.absorb(self.feed.name(), &data);
+            for (d, m) in data.iter_mut().zip(&mask) {
+                *d ^= m;
+            }
+            cpurng::zeroize_vec(&mut mask);
+        }
+        Ok(data)
+    }
+}
+
+/// Reads the declared quality of `--feed hwrng` and says what it does to
+/// the credit. Read at startup, as the sandbox hides `/sys/module`.
+fn hwrng_feed_quality(credit_ratio: u32, credited: bool) -> Option<u32> {
+    let quality = hwrng::quality();
+    match quality {
+        _ if !credited => {}
+        Some(0) => log::warn!(
+            target: "mixrand::daemon",
+            "/dev/hwrng declares rng_quality 0; its batches are injected without credit",
+        ),
+        Some(hwrng::QUALITY_SCALE) => log::info!(
+            target: "mixrand::daemon",
+            "/dev/hwrng declares full quality (rng_quality {0}/{0})", hwrng::QUALITY_SCALE,
+        ),
+        Some(q) => log::info!(
+            target: "mixrand::daemon",
+            "/dev/hwrng declares rng_quality {}/{}; crediting {} bits per byte instead of {}",
+            q, hwrng::QUALITY_SCALE,
+            table::decimal(credit_ratio as f64 * q as f64 / hwrng::QUALITY_SCALE as f64, 2),
+            credit_ratio,
+        ),
+        None => log::warn!(
+            target: "mixrand::daemon",
+            "cannot read /dev/hwrng's rng_quality; crediting {}bits per byte as configured",
+            credit_ratio,
+        ),
+    }
+    quality
+}
+
+/// Replaces the seed file with fresh fallback output.
+fn refresh_seed_file(path: &Path, config: &Config) -> Result<(), Error> {
+    let mut seed = if config.fips {
+        let input = fallback::generate_fallback(fips::input_bytes(seedfile::SEED_BYTES), config)?;
+        fips::condition(input, seedfile::SEED_BYTES)?
+    } else {
+        fallback::generate_fallback(seedfile::SEED_BYTES, config)?
+    };
+    let locked = secmem::protect(&seed);
+    let result = seedfile::save(path, &seed);
+    cpurng::zeroize_vec(&mut seed);
+    drop(locked);
+    result
+}
+
+/// Mixes the saved seed into the pool without credit (it may have been
+/// cloned along with the disk image), then immediately replaces it so the
+/// same seed is never used twice.
+fn restore_seed(sink: &Sink, path: &Path, config: &Config, units: Units) -> Result<(), Error> {
+    if let Some(mut saved) = seedfile::load(path)? {
+        let locked = secmem::protect(&saved);
+        let result = sink.inject(&saved, 0);
+        let len = saved.len();
+        cpurng::zeroize_vec(&mut saved);
+        drop(locked);
+        result?;
+        log::info!(
+            target: "mixrand::daemon",
+            "mixed {} from seed file {} (no credit)",
+            table::bytes(len as u64, units), path.display(),
+        );
+    }
+    refresh_seed_file(path, config)
+}
+
+/// Work done after shutdown is requested, run on its own thread so a hung
+/// entropy source can't hold up exit past `--shutdown-timeout`.
+struct ShutdownTasks {
+    sink: Sink,
+    final_inject: Option<(usize, u32)>,
+    seed_file: Option<PathBuf>,
+    pool: FeedPool,
+    config: Config,
+    units: Units,
+}
+
+impl ShutdownTasks {
+    fn run(mut self) {
+        if let Some((batch_size, credit_ratio)) = self.final_inject {
+            match self.pool.collect(batch_size, &self.config) {
+                Ok(mut data) => {
+                    let locked = secmem::protect(&data);
+                    let credit_bits = self.pool.credit_bits(&self.sink, batch_size, credit_ratio);
+                    match self.sink.inject(&data, credit_bits) {
+                        Ok(n) => log::info!(
+                            target: "mixrand::daemon",
+                            "final injection: {} ({}bits credit)",
+                            table::bytes(n as u64, self.units), credit_bits,
+                        ),
+                        Err(e) => log::error!(
+                            target: "mixrand::daemon",
+                            "final injection failed: {}", e,
+                        ),
+                    }
+                    cpurng::zeroize_vec(&mut data);
+                    drop(locked);
+                }
+                Err(e) => log::error!(
+                    target: "mixrand::daemon",
+                    "final injection failed: {}", e,
+                ),
+            }
+        }
+
+        if let Some(ref path) = self.seed_file {
+            match refresh_seed_file(path, &self.config) {
+                Ok(()) => log::info!(
+                    target: "mixrand::daemon",
+                    "saved seed file {}", path.display(),
+                ),
+                Err(e) => log::error!(
+                    target: "mixrand::daemon",
+                    "failed to save seed file {}: {}", path.display(), e,
+                ),
+            }
+        }
+    }
+}
+
+fn run_shutdown_tasks(tasks: ShutdownTasks, timeout: Duration) {
+    if tasks.final_inject.is_none() && tasks.seed_file.is_none() {
+        return;
+    }
+    let (done_tx, done_rx) = mpsc::channel();
+    let spawned = thread::Builder::new()
+        .name("shutdown".into())
+        .spawn(move || {
+            tasks.run();
+            let _ = done_tx.send(());
+        });
+    if let Err(e) = spawned {
+        log::error!(target: "mixrand::daemon", "cannot run shutdown tasks: {}", e);
+        return;
+    }
+    if done_rx.recv_timeout(timeout).is_err() {
+        log::warn!(
+            target: "mixrand::daemon",
+            "shutdown tasks did not finish within {}s; exiting anyway", timeout.as_secs(),
+        );
+    }
+}
+
+/// Paths the daemon touches after startup: the pool and its counters, the
+/// fallback inputs (read-only), /dev/hwrng for `--feed hwrng`, and the
+/// directories of the seed, status and log files, which are replaced or
+/// reopened in place.
+fn landlock_rules(args: &DaemonArgs, pool: bool, feed: DaemonFeed) -> Vec<(PathBuf, Access)> {
+    let mut rules: Vec<(PathBuf, Access)> = [
+        "/proc/sys/kernel/random",
+        "/dev/urandom",
+        "/proc/interrupts",
+        "/proc/stat",
+        "/proc/diskstats",
+        "/proc/net/dev",
+        "/proc/net/softnet_stat",
+        "/etc/machine-id",
+        "/sys/class",
+        "/sys/devices",
+    ]
+    .into_iter()
+    .map(|p| (PathBuf::from(p), Access::Read))
+    .collect();
+    if pool {
+        rules.push((PathBuf::from("/dev/random"), Access::Write));
+    }
+    if feed == DaemonFeed::Hwrng {
+        rules.push((PathBuf::from("/dev/hwrng"), Access::Read));
+    }
+    // Kept readable for SIGHUP reloads
+    let config = config_path(args);
+    if config.exists() {
+        rules.push((config, Access::Read));
+    }
+
+    let files = [&args.seed_file, &args.status_file, &args.log.log_file];
+    for path in files.into_iter().flatten() {
+        let dir = match path.parent() {
+            Some(d) if !d.as_os_str().is_empty() => d.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        rules.push((dir, Access::Write));
+    }
+    rules
+}
+
+/// Applies Landlock and then seccomp (which would block the Landlock
+/// syscalls). Failures are logged; the daemon keeps running unsandboxed.
+fn install_sandbox(args: &DaemonArgs, sink: &Sink, feed: DaemonFeed, webhook: bool) {
+    if args.no_sandbox {
+        log::warn!(target: "mixrand::daemon", "sandbox disabled");
+        return;
+    }
+    match landlock::restrict(&landlock_rules(args, sink.pool().is_some(), feed)) {
+        Ok(landlock::Status::Enforced(abi)) => log::info!(
+            target: "mixrand::daemon",
+            "landlock filesystem restriction enabled (ABI v{})", abi,
+        ),
+        Ok(landlock::Status::Unsupported) => log::info!(
+            target: "mixrand::daemon",
+            "landlock not supported by this kernel; filesystem access unrestricted",
+        ),
+        Err(e) => log::warn!(
+            target: "mixrand::daemon",
+            "cannot apply landlock restriction: {}", e,
+        ),
+    }
+    let mut policy = match sink {
+        // Reseed mode, resume top-ups and SIGUSR2 all reseed the crng
+        Sink::Pool(dev_random) => Policy::daemon(dev_random.as_raw_fd(), RNDADDENTROPY)
+            .reseed_crng(dev_random.as_raw_fd(), RNDRESEEDCRNG),
+        Sink::Injector { .. } => Policy::base().unix_client(),
+        Sink::Stream { .. } => Policy::base(),
+    };
+    if feed == DaemonFeed::Vsock {
+        policy = policy.vsock_client();
+    }
+    if webhook {
+        policy = policy.http_client();
+    }
+    match sandbox::install(&policy) {
+        Ok(()) => log::info!(target: "mixrand::daemon", "seccomp sandbox installed"),
+        Err(e) => log::warn!(
+            target: "mixrand::daemon",
+            "cannot install seccomp sandbox: {}", e,
+        ),
+    }
+}
+
+/// Exit status once `--max-failures` consecutive injections have failed
+/// (EX_TEMPFAIL), so a supervisor can tell it from a startup error.
+pub const EXIT_INJECTION_FAILED: i32 = 75;
+
+/// Wait before the next round after `failures` consecutive failed
+/// injections: the interval, doubled per failure, at most `max`.
+fn backoff(interval: Duration, failures: u32, max: Duration) -> Duration {
+    if failures == 0 {
+        return interval;
+    }
+    interval
+        .saturating_mul(1 << failures.min(16))
+        .min(max.max(interval))
+}
+
+/// Collects `count` bytes from `feed` and sends them to `sink`. Returns the
+/// sink's error if it rejected the batch; collection failures are only
+/// logged, recorded and alerted on.
+fn inject_round(
+    sink: &Sink,
+    feed: &mut FeedPool,
+    count: usize,
+    args: &DaemonArgs,
+    config: &Config,
+    status: &mut DaemonStatus,
+    alerts: &mut Option<Alerts>,
+) -> Result<(), Error> {
+    let avail = status.entropy_avail;
+    let credit_ratio = args.credit_ratio.unwrap_or(config.daemon.credit_ratio);
+    let mut data = match feed.collect(count, config) {
+        Ok(data) => data,
+        Err(e) => {
+            log::error!(
+                target: "mixrand::daemon",
+                "entropy generation failed: {}", e,
+            );
+            status.source_failed(feed.name(), &e);
+            if let Some(ref mut alerts) = alerts {
+                alerts.fire(EventKind::SourceFailure, feed.name(), &e);
+            }
+            return Ok(());
+        }
+    };
+    let locked = secmem::protect(&data);
+    let units = args.units.unwrap_or(config.display.units);
+    status.source_ok(feed.name());
+    let credit_bits = feed.credit_bits(sink, data.len(), credit_ratio);
+    let result = sink.inject(&data, credit_bits);
+    cpurng::zeroize_vec(&mut data);
+    drop(locked);
+    match result {
+        Ok(0) => log::debug!(
+            target: "mixrand::daemon",
+            "{} is full; reader is not draining it", sink.label(),
+        ),
+        Ok(n) => {
+            status.injected(n, credit_bits);
+            match (sink, avail) {
+                (Sink::Pool(_) | Sink::Injector { .. }, Some(avail)) => log::info!(
+                    target: "mixrand::daemon",
+                    "injected {} ({}bits credit), entropy was {}bits",
+                    table::bytes(n as u64, units), credit_bits, avail,
+                ),
+                _ if n < data.len() => log::info!(
+                    target: "mixrand::daemon",
+                    "wrote {} of {} to {}; reader is behind",
+                    table::bytes(n as u64, units), table::bytes(data.len() as u64, units), sink.label(),
+                ),
+                _ => log::info!(
+                    target: "mixrand::daemon",
+                    "wrote {} to {}", table::bytes(n as u64, units), sink.label(),
+                ),
+            }
+        }
+        Err(e) => {
+            status.injection_failed(sink.label(), &e);
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// Handles a resume after `slept` in suspend: retests the CPU RNG, turning
+/// off for the rest of the run any instruction that fails, then injects
+/// `--resume-bytes` and reseeds the crng.
+fn resume_topup(
+    slept: Duration,
+    sink: &Sink,
+    feed: &mut FeedPool,
+    args: &DaemonArgs,
+    config: &mut Config,
+    status: &mut DaemonStatus,
+    alerts: &mut Option<Alerts>,
+) {
+    log::warn!(
+        target: "mixrand::daemon",
+        "resumed after {}s suspended; retesting the CPU RNG and topping up",
+        slept.as_secs(),
+    );
+    for (name, why) in resume::check_cpu_rng() {
+        log::error!(
+            target: "mixrand::daemon",
+            "{} failed its check after resume ({}); disabled", name, why,
+        );
+        match name {
+            "rdrand" => config.cpu_rng.enable_rdrand = false,
+            _ => config.cpu_rng.enable_rdseed = false,
+        }
+        let e = Error::NoEntropy(why);
+        status.source_failed(name, &e);
+        if let Some(ref mut alerts) = alerts {
+            alerts.fire(EventKind::HealthFailure, name, &e);
+        }
+    }
+    let injected = inject_round(sink, feed, args.resume_bytes, args, config, status, alerts);
+    match (injected, sink.pool()) {
+        (Ok(()), Some(pool)) => match reseed_crng(pool) {
+            Ok(()) => log::debug!(target: "mixrand::daemon", "reseeded the crng"),
+            Err(e) => log::warn!(
+                target: "mixrand::daemon",
+                "cannot reseed the crng: {}", e,
+            ),
+        },
+        (Ok(()), None) => {}
+        (Err(e), _) => {
+            log::error!(target: "mixrand::daemon", "{} failed: {}", sink.label(), e);
+            if let Some(ref mut alerts) = alerts {
+                alerts.fire(EventKind::InjectionError, sink.label(), &e);
+            }
+        }
+    }
 }
 
-fn install_signal_handlers() {
-    unsafe {
-        let mut sa: libc::sigaction = std::mem::zeroed();
-        sa.sa_sigaction = signal_handler as *const () as usize;
-        sa.sa_flags = libc::SA_RESTART;
-        libc::sigemptyset(&mut sa.sa_mask);
-        libc::sigaction(libc::SIGTERM, &sa, std::ptr::null_mut());
-        libc::sigaction(libc::SIGINT, &sa, std::ptr::null_mut());
+/// Re-reads the config file on SIGHUP, applying the `--config` path and
+/// CPU RNG overrides the daemon started with. The feed, sink, sandbox and
+/// FIPS mode are fixed at startup, so `fips` keeps its running value.
+fn reload_config(args: &DaemonArgs, current: &Config) -> Result<Config, Error> {
+    let mut config = config::load_config(args.config_file.as_deref())?;
+    args.cpu_rng.apply(&mut config.cpu_rng);
+    config.validate();
+    entropy::check_policy(&config)?;
+    if config.fips != current.fips {
+        log::warn!(
+            target: "mixrand::daemon",
+            "fips = {} takes effect only on restart", config.fips,
+        );
+        config.fips = current.fips;
     }
+    Ok(config)
+}
+
+/// Path the daemon reads its config from, for the Landlock rules.
+fn config_path(args: &DaemonArgs) -> PathBuf {
+    args.config_file
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("/etc/mixrand.toml"))
 }
 
-/// Interruptible sleep: sleeps in 250ms steps, checking SHUTDOWN between each.
-fn interruptible_sleep(total: Duration) {
-    let step = Duration::from_millis(250);
-    let mut remaining = total;
-    while remaining > Duration::ZERO && !SHUTDOWN.load(Ordering::Relaxed) {
-        let s = remaining.min(step);
-        thread::sleep(s);
-        remaining = remaining.saturating_sub(s);
+/// Rewrites `--status-file`, if set; failures are logged, not fatal.
+fn write_status(status: &mut DaemonStatus, args: &DaemonArgs) {
+    if let Some(ref path) = args.status_file {
+        if let Err(e) = status.write(path) {
+            log::warn!(
+                target: "mixrand::daemon",
+                "cannot write status file {}: {}", path.display(), e,
+            );
+        }
     }
 }
 
-pub fn run(args: &DaemonArgs, cpu_config: &CpuRngConfig) -> Result<(), Error> {
+pub fn run(args: &DaemonArgs, config: &Config) -> Result<(), Error> {
+    let credit_ratio = args.credit_ratio.unwrap_or(config.daemon.credit_ratio);
+    let units = args.units.unwrap_or(config.display.units);
+    let feed = args.feed.unwrap_or(config.daemon.feed);
+    let target = args
+        .inject_target
+        .clone()
+        .unwrap_or_else(|| config.daemon.inject_target.clone());
+
     if args.batch_size == 0 {
         return Err(Error::InvalidArgs("batch-size must be greater than 0".into()));
     }
+    if args.early_boot && !(1..=65536).contains(&args.early_boot_bytes) {
+        return Err(Error::InvalidArgs(
+            "early-boot-bytes must be between 1 and 65536".into(),
+        ));
+    }
+    config.limits.check_request(args.batch_size)?;
+    if args.early_boot {
+        config.limits.check_request(args.early_boot_bytes)?;
+    }
+    config.limits.check_request(args.resume_bytes)?;
 
-    let dev_random = validate_permissions()?;
+    let pool_target = matches!(target, InjectTarget::Random | InjectTarget::Injector(_));
+    if !pool_target && (args.early_boot || args.seed_file.is_some()) {
+        return Err(Error::InvalidArgs(
+            "--early-boot and --seed-file need a /dev/random or injector target".into(),
+        ));
+    }
+    if feed == DaemonFeed::Hwrng && target == InjectTarget::HwrngLoop {
+        return Err(Error::InvalidArgs(
+            "--feed hwrng cannot inject into hwrng-loop, which feeds /dev/hwrng".into(),
+        ));
+    }
+
+    // Resolved now, as the sandbox rules out name lookups later
+    let quality = &config.quality_check;
+    let webhook = if quality.is_enabled() && !quality.webhook.is_empty() {
+        Some(Endpoint::resolve(&quality.webhook)?)
+    } else {
+        None
+    };
+    let mut alerts = Alerts::start(&config.alerts)?;
+    let network = webhook.is_some() || alerts.as_ref().is_some_and(Alerts::needs_network);
 
-    install_signal_handlers();
+    let sink = Sink::open(&target)?;
+
+    // Only credited sinks are gated on entropy_avail
+    let mode = match args.mode.unwrap_or(config.daemon.mode) {
+        DaemonMode::Auto if sink.credited() => {
+            let release = baseline::kernel_release();
+            let poolsize = read_poolsize();
+            let mode = detect_mode(poolsize, &release);
+            match mode {
+                DaemonMode::Reseed => log::info!(
+                    target: "mixrand::daemon",
+                    "kernel {} keeps entropy_avail at {} once seeded; using reseed mode",
+                    release, FIXED_POOL_BITS,
+                ),
+                _ => log::info!(
+                    target: "mixrand::daemon",
+                    "kernel {} tracks entropy_avail; using threshold mode", release,
+                ),
+            }
+            mode
+        }
+        mode => mode,
+    };
+    let reseed = mode == DaemonMode::Reseed && sink.credited();
+    let schedule =
+        match Schedule::from_args(args.inject_every.as_deref(), args.inject_cron.as_deref())? {
+            None if reseed => Some(Schedule::Every(RESEED_EVERY)),
+            schedule => schedule,
+        };
+    // Also loads the time zone while /etc/localtime is still readable
+    let mut next_scheduled = match &schedule {
+        Some(schedule) => Some(Instant::now() + schedule.until_next(SystemTime::now())?),
+        None => None,
+    };
+    let mut status = DaemonStatus::new(args.threshold);
+
+    signals::install(&[
+        Signal::Term,
+        Signal::Int,
+        Signal::Hup,
+        Signal::Usr1,
+        Signal::Usr2,
+    ]);
+    signals::on(Signal::Usr1, logging::request_reopen);
+    // SIGHUP: re-read the config file before the next round
+    let reload = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&reload);
+    signals::on(Signal::Hup, move || {
+        flag.store(true, Ordering::Relaxed);
+        signals::request_wake();
+    });
+    // SIGUSR2: inject a batch now, whatever the threshold
+    let inject_now = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&inject_now);
+    signals::on(Signal::Usr2, move || {
+        flag.store(true, Ordering::Relaxed);
+        signals::request_wake();
+    });
 
     log::info!(
         target: "mixrand::daemon",
-        "started: threshold={}bits interval={}s batch={}B credit={}bits/byte",
-        args.threshold, args.interval, args.batch_size, args.credit_ratio,
+        "started: mode={} threshold={}bits interval={}s batch={} credit={}bits/byte feed={} target={}",
+        mode.name(), args.threshold, args.interval, table::bytes(args.batch_size as u64, units),
+        credit_ratio, feed.name(), target,
     );
+    if let Some(ref schedule) = schedule {
+        log::info!(target: "mixrand::daemon", "scheduled injections {}", schedule);
+    }
+    if reseed && sink.pool().is_none() {
+        log::info!(
+            target: "mixrand::daemon",
+            "crng reseeds are left to the kernel through {}", sink.label(),
+        );
+    }
 
-    while !SHUTDOWN.load(Ordering::Relaxed) {
-        match read_entropy_avail() {
-            Ok(avail) => {
-                if avail < args.threshold {
-                    match fallback::generate_fallback(args.batch_size, cpu_config) {
-                        Ok(data) => {
-                            let credit_bits = args.batch_size as u32 * args.credit_ratio;
-                            match inject_entropy(&dev_random, &data, credit_bits) {
-                                Ok(()) => {
-                                    log::info!(
-                                        target: "mixrand::daemon",
-                                        "injected {}B ({}bits credit), entropy was {}bits",
-                                        args.batch_size, credit_bits, avail,
-                                    );
-                                }
-                                Err(e) => {
-                                    log::error!(
-                                        target: "mixrand::daemon",
-                                        "ioctl failed: {}", e,
-                                    );
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            log::error!(
-                                target: "mixrand::daemon",
-                                "entropy generation failed: {}", e,
-                            );
-                        }
-                    }
-                } else {
-                    log::debug!(
+    if sink.credited() {
+        if args.early_boot {
+            match early_boot_inject(&sink, args, config, units) {
+                Ok(credit_bits) => {
+                    status.source_ok("early-boot");
+                    status.injected(args.early_boot_bytes, credit_bits);
+                }
+                Err(e) => {
+                    log::error!(target: "mixrand::daemon", "early boot injection failed: {}", e);
+                    status.source_failed("early-boot", &e);
+                }
+            }
+        }
+
+        if let Some(ref path) = args.seed_file {
+            match restore_seed(&sink, path, config, units) {
+                Ok(()) => status.source_ok("seed-file"),
+                Err(e) => {
+                    log::error!(
                         target: "mixrand::daemon",
-                        "entropy OK: {}bits (threshold {})",
-                        avail, args.threshold,
+                        "seed file {}: {}", path.display(), e,
                     );
+                    status.source_failed("seed-file", &e);
                 }
             }
+        }
+    }
+
+    let hwrng_quality = match feed {
+        DaemonFeed::Hwrng => hwrng_feed_quality(credit_ratio, sink.credited()),
+        _ => None,
+    };
+
+    // The state file is out of reach once the sandbox is in, so the test
+    // takes its own sample of the feed
+    if restart::enable(config) {
+        match generate_feed(feed, config.restart_test.bytes, config) {
+            Ok(mut sample) => {
+                restart::observe(&sample);
+                cpurng::zeroize_vec(&mut sample);
+            }
+            Err(e) => log::warn!(target: "mixrand::daemon", "restart test: {}", e),
+        }
+    }
+
+    install_sandbox(args, &sink, feed, network);
+
+    let (quality_tx, quality_rx) = mpsc::channel();
+    if quality.is_enabled() {
+        let check_config = config.clone();
+        match qualitycheck::spawn(
+            quality.clone(),
+            feed.name(),
+            webhook,
+            move |n| generate_feed(feed, n, &check_config),
+            quality_tx,
+        ) {
+            Ok(()) => log::info!(
+                target: "mixrand::daemon",
+                "quality check of {} every {}s", feed.name(), quality.interval_secs,
+            ),
+            Err(e) => log::error!(
+                target: "mixrand::daemon",
+                "cannot start quality check: {}", e,
+            ),
+        }
+    }
+
+    let interval = Duration::from_secs(args.interval);
+    let max_backoff = Duration::from_secs(args.max_backoff);
+    let mut gave_up = None;
+    let mut resume = (args.resume_bytes > 0).then(ResumeDetector::new);
+    let mut resumed = None;
+    // A resume check may turn off a broken CPU RNG instruction
+    let mut config = config.clone();
+    let mut feed_pool = FeedPool::new(feed, &config, hwrng_quality);
+    let mut cadence = Cadence::new(interval, Instant::now());
+    while !signals::shutdown_requested() {
+        let skipped = cadence.skipped();
+        if cadence.start(Instant::now()) && cadence.skipped() > skipped {
+            log::warn!(
+                target: "mixrand::daemon",
+                "the last round overran --interval; skipped {} poll deadline(s)",
+                cadence.skipped() - skipped,
+            );
+        }
+        if reload.swap(false, Ordering::Relaxed) {
+            match reload_config(args, &config) {
+                Ok(reloaded) => {
+                    config = reloaded;
+                    log::info!(target: "mixrand::daemon", "reloaded the config (SIGHUP)");
+                }
+                Err(e) => log::error!(
+                    target: "mixrand::daemon",
+                    "config reload failed, keeping the running config: {}", e,
+                ),
+            }
+        }
+        if let Some(slept) = resumed.take() {
+            resume_topup(
+                slept,
+                &sink,
+                &mut feed_pool,
+                args,
+                &mut config,
+                &mut status,
+                &mut alerts,
+            );
+        }
+        let avail = match read_entropy_avail() {
+            Ok(avail) => {
+                status.entropy_avail = Some(avail);
+                Some(avail)
+            }
             Err(e) => {
                 log::error!(
                     target: "mixrand::daemon",
                     "failed to read entropy_avail: {}", e,
                 );
+                status.entropy_avail = None;
+                status.last_error = Some(format!("entropy_avail: {}", e));
+                None
+            }
+        };
+        // Uncredited batches never raise entropy_avail, so they go every round
+        // In reseed mode the threshold means nothing, so only the schedule counts
+        let on_demand = inject_now.swap(false, Ordering::Relaxed);
+        signals::take_wake();
+        let mut due = on_demand
+            || !sink.credited()
+            || !reseed && (credit_ratio == 0 || avail.is_some_and(|a| a < args.threshold));
+        let mut scheduled = false;
+        let mut wake = cadence.next();
+        if let (Some(schedule), Some(next)) = (&schedule, next_scheduled) {
+            let now = Instant::now();
+            if now >= next {
+                log::debug!(target: "mixrand::daemon", "scheduled injection ({})", schedule);
+                (due, scheduled) = (true, true);
+                // Recomputed from the wall clock each time, so a cron schedule
+                // follows clock changes
+                next_scheduled = match schedule.until_next(SystemTime::now()) {
+                    Ok(until) => Some(now + until),
+                    Err(e) => {
+                        log::error!(
+                            target: "mixrand::daemon",
+                            "{}; no more scheduled injections", e,
+                        );
+                        None
+                    }
+                };
+            }
+            if let Some(next) = next_scheduled {
+                wake = wake.min(next);
+            }
+        }
+        if due {
+            if on_demand {
+                log::info!(target: "mixrand::daemon", "on-demand injection (SIGUSR2)");
+            }
+            let injected = inject_round(
+                &sink,
+                &mut feed_pool,
+                args.batch_size,
+                args,
+                &config,
+                &mut status,
+                &mut alerts,
+            );
+            if let (Ok(()), true, Some(pool)) =
+                (&injected, reseed && scheduled || on_demand, sink.pool())
+            {
+                match reseed_crng(pool) {
+                    Ok(()) => log::debug!(target: "mixrand::daemon", "reseeded the crng"),
+                    Err(e) => log::warn!(
+                        target: "mixrand::daemon",
+                        "cannot reseed the crng: {}", e,
+                    ),
+                }
+            }
+            if let Err(e) = injected {
+                if let Some(ref mut alerts) = alerts {
+                    alerts.fire(EventKind::InjectionError, sink.label(), &e);
+                }
+                let failures = status.consecutive_failures;
+                if args.max_failures > 0 && failures >= args.max_failures {
+                    log::error!(
+                        target: "mixrand::daemon",
+                        "{} failed: {} ({} in a row; giving up)", sink.label(), e, failures,
+                    );
+                    gave_up = Some(format!("{} consecutive failures, last: {}", failures, e));
+                    write_status(&mut status, args);
+                    break;
+                }
+                let wait = backoff(interval, failures - 1, max_backoff);
+                log::error!(
+                    target: "mixrand::daemon",
+                    "{} failed: {} ({} in a row; retrying in {}s)",
+                    sink.label(), e, failures, wait.as_secs(),
+                );
+                wake = Instant::now() + wait;
+                cadence.restart(wake);
             }
+        } else if let (Some(avail), false) = (avail, reseed) {
+            log::debug!(
+                target: "mixrand::daemon",
+                "entropy OK: {}bits (threshold {})",
+                avail, args.threshold,
+            );
         }
 
-        interruptible_sleep(Duration::from_secs(args.interval));
+        for report in quality_rx.try_iter() {
+            status.quality_checked(&report);
+            if let (Some(Transition::Degraded), Some(ref mut alerts)) =
+                (report.transition, &mut alerts)
+            {
+                let why = report.failure.as_deref().unwrap_or_default();
+                alerts.fire(EventKind::HealthFailure, feed.name(), why);
+            }
+        }
+        status.polled(&cadence);
+        write_status(&mut status, args);
+        // Whatever this round took comes out of the wait
+        let wait = wake.saturating_duration_since(Instant::now());
+        resumed = match resume {
+            Some(ref mut resume) => resume.sleep(wait),
+            None => {
+                signals::interruptible_sleep(wait);
+                None
+            }
+        };
     }
 
     log::info!(target: "mixrand::daemon", "shutting down");
-    Ok(())
+    run_shutdown_tasks(
+        ShutdownTasks {
+            sink,
+            final_inject: args.final_inject.then_some((args.batch_size, credit_ratio)),
+            seed_file: args.seed_file.clone(),
+            pool: feed_pool,
+            config,
+            units,
+        },
+        Duration::from_secs(args.shutdown_timeout),
+    );
+    for report in quality_rx.try_iter() {
+        status.quality_checked(&report);
+    }
+    write_status(&mut status, args);
+    match gave_up {
+        Some(why) => Err(Error::Injection(why)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Args, FromArgMatches};
+
+    fn daemon_args(argv: &[&str]) -> DaemonArgs {
+        let matches = DaemonArgs::augment_args(clap::Command::new("daemon"))
+            .try_get_matches_from(argv)
+            .unwrap();
+        DaemonArgs::from_arg_matches(&matches).unwrap()
+    }
+
+    #[test]
+    fn test_reload_config_keeps_overrides_and_fips() {
+        let path = std::env::temp_dir().join("mixrand_test_reload.toml");
+        let toml = "fips = true\n[cpu_rng]\noversample = 4\nrdrand_retries = 7\n";
+        fs::write(&path, toml).unwrap();
+        let config = path.to_str().unwrap();
+        let args = daemon_args(&["daemon", "--config", config, "--oversample", "2"]);
+
+        let reloaded = reload_config(&args, &Config::default()).unwrap();
+        assert_eq!(reloaded.cpu_rng.oversample, 2);
+        assert_eq!(reloaded.cpu_rng.rdrand_retries, 7);
+        assert!(!reloaded.fips);
+
+        fs::write(&path, "not toml [").unwrap();
+        assert!(reload_config(&args, &Config::default()).is_err());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_detect_mode() {
+        assert_eq!(
+            detect_mode(Some(256), "6.8.0-45-generic"),
+            DaemonMode::Reseed
+        );
+        assert_eq!(detect_mode(Some(4096), "5.15.0"), DaemonMode::Threshold);
+        // poolsize wins over the release, e.g. for backports
+        assert_eq!(detect_mode(Some(256), "5.10.0"), DaemonMode::Reseed);
+        assert_eq!(detect_mode(None, "5.18.1"), DaemonMode::Reseed);
+        assert_eq!(detect_mode(None, "4.19.0"), DaemonMode::Threshold);
+        assert_eq!(detect_mode(None, ""), DaemonMode::Threshold);
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let interval = Duration::from_secs(5);
+        let max = Duration::from_secs(60);
+        let waits: Vec<u64> = (0..6)
+            .map(|n| backoff(interval, n, max).as_secs())
+            .collect();
+        assert_eq!(waits, [5, 10, 20, 40, 60, 60]);
+        assert_eq!(backoff(interval, 100, max), max);
+        // A max below the interval never shortens it
+        assert_eq!(backoff(interval, 3, Duration::from_secs(1)), interval);
+    }
 }
@@ -0,0 +1,265 @@
+//! `mixrand doctor`: the config file, every source, a short statistical
+//! check, the kernel RNG and daemon-mode permissions in one pass, ending in
+//! PASS or FAIL. The first thing to ask for in a bug report.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::check::{self, SourceKind};
+use crate::cli::DoctorArgs;
+use crate::config::{self, Config};
+use crate::entropy::{self, cpurng};
+use crate::error::Error;
+use crate::probe::{self, Finding, Status};
+use crate::signals::{self, Signal};
+use crate::stats;
+use crate::table;
+
+/// Probed sources whose absence is normal: their failures are reported but
+/// don't fail the run.
+const OPTIONAL: [&str; 2] = ["hwrng", "cpu-rng"];
+
+/// Bytes per sample in the mini-check: one FIPS 140-2 window.
+const SAMPLE_BYTES: usize = 2500;
+
+/// Mini-check FIPS pass rate below which a source fails. Good sources fail
+/// about one window in a thousand.
+const MIN_FIPS_PASS_PCT: f64 = 98.0;
+
+/// Secret buffers `secmem` locks are small; below this `mlock` may fail.
+const MIN_MEMLOCK_BYTES: libc::rlim_t = 64 * 1024;
+
+fn check_config(path: Option<&Path>) -> Finding {
+    let shown = path.unwrap_or(Path::new("/etc/mixrand.toml"));
+    match config::load_config(path) {
+        Ok(_) if path.is_none() && !shown.exists() => {
+            Finding::new("config", Status::Ok, "no config file; using defaults")
+        }
+        Ok(_) => Finding::new("config", Status::Ok, format!("{} parsed", shown.display())),
+        Err(e) => {
+            let mut f = Finding::new("config", Status::Fail, e.to_string());
+            f.hints.push(
+                "every command falls back to defaults with this file; fix it or pass another \
+                 with --config"
+                    .into(),
+            );
+            f
+        }
+    }
+}
+
+/// Whether the kernel crng has been seeded, i.e. `getrandom()` won't block.
+fn check_crng() -> Finding {
+    let mut byte = 0u8;
+    let ret = unsafe { libc::getrandom((&mut byte as *mut u8).cast(), 1, libc::GRND_NONBLOCK) };
+    if ret == 1 {
+        return Finding::new(
+            "crng",
+            Status::Ok,
+            "initialized; getrandom() does not block",
+        );
+    }
+    let err = std::io::Error::last_os_error();
+    let mut f = Finding::new("crng", Status::Fail, format!("getrandom() failed: {}", err));
+    if err.raw_os_error() == Some(libc::EAGAIN) {
+        f.summary = "not yet initialized; getrandom() blocks".into();
+        f.hints.push(
+            "run `mixrand daemon --early-boot` from early boot, or give the machine a \
+             hardware RNG (virtio-rng in VMs)"
+                .into(),
+        );
+    }
+    f
+}
+
+fn check_daemon_permissions() -> Finding {
+    let writable = std::fs::OpenOptions::new()
+        .write(true)
+        .open("/dev/random")
+        .is_ok();
+    let mut f = if writable {
+        Finding::new("daemon", Status::Ok, "can open /dev/random for writing")
+    } else {
+        let mut f = Finding::new(
+            "daemon",
+            Status::Warn,
+            "cannot open /dev/random for writing",
+        );
+        f.hints.push(
+            "run the daemon as root, or run `mixrand injector` as root and the daemon \
+             with --inject-target injector:PATH"
+                .into(),
+        );
+        f
+    };
+
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) } == 0 {
+        let unlimited = limit.rlim_cur == libc::RLIM_INFINITY;
+        f.details.push(if unlimited {
+            "RLIMIT_MEMLOCK unlimited".into()
+        } else {
+            format!("RLIMIT_MEMLOCK {} bytes", limit.rlim_cur)
+        });
+        let is_root = unsafe { libc::geteuid() } == 0;
+        if !unlimited && !is_root && limit.rlim_cur < MIN_MEMLOCK_BYTES {
+            f.status = Status::Warn;
+            f.hints.push(
+                "secret buffers may not be locked in RAM: raise LimitMEMLOCK= in the \
+                 service unit"
+                    .into(),
+            );
+        }
+    }
+    f
+}
+
+/// Samples every working source for its share of `duration` and runs the
+/// FIPS 140-2 tests on each sample.
+fn mini_check(config: &Config, duration: Duration) -> Vec<Finding> {
+    let working: Vec<SourceKind> = SourceKind::available(config)
+        .into_iter()
+        .filter(|s| check::collect_sample(s, 32, config).is_ok())
+        .collect();
+    if working.is_empty() {
+        return vec![Finding::new(
+            "mini-check",
+            Status::Fail,
+            "no source delivered",
+        )];
+    }
+    let share = duration / working.len() as u32;
+
+    let mut findings = Vec::new();
+    for source in working {
+        let deadline = Instant::now() + share;
+        let (mut samples, mut windows, mut passed, mut errors) = (0u64, 0u64, 0u64, 0u64);
+        let mut min_entropy = f64::INFINITY;
+        while Instant::now() < deadline && !signals::shutdown_requested() {
+            samples += 1;
+            match check::collect_sample(&source, SAMPLE_BYTES, config) {
+                Ok(mut data) => {
+                    if let Ok(window) = <&[u8; SAMPLE_BYTES]>::try_from(&data[..]) {
+                        windows += 1;
+                        passed += stats::fips_suite(window).all_passed() as u64;
+                        min_entropy = min_entropy.min(stats::min_entropy(&data));
+                    }
+                    cpurng::zeroize_vec(&mut data);
+                }
+                Err(_) => errors += 1,
+            }
+        }
+        if signals::shutdown_requested() {
+            break;
+        }
+
+        let name = source.name();
+        let mut f = if windows == 0 {
+            Finding::new(name, Status::Warn, "too slow to test in the time given")
+        } else {
+            let pct = 100.0 * passed as f64 / windows as f64;
+            let status = if pct < MIN_FIPS_PASS_PCT {
+                Status::Fail
+            } else {
+                Status::Ok
+            };
+            let mut f = Finding::new(
+                name,
+                status,
+                format!(
+                    "FIPS 140-2 {} of {} windows ({}%)",
+                    passed,
+                    windows,
+                    table::decimal(pct, 1)
+                ),
+            );
+            f.details.push(format!(
+                "lowest per-sample min-entropy {} bits/byte",
+                table::decimal(min_entropy, 3)
+            ));
+            if status == Status::Fail {
+                f.hints.push(format!(
+                    "run `mixrand check --sources {} -d 10m` for a full report",
+                    name
+                ));
+            }
+            f
+        };
+        if errors > 0 {
+            if f.status == Status::Ok {
+                f.status = Status::Warn;
+            }
+            f.details
+                .push(format!("{} of {} samples failed", errors, samples));
+        }
+        findings.push(f);
+    }
+    findings
+}
+
+fn section(title: &str, findings: &[Finding]) {
+    println!("== {} ==", title);
+    for f in findings {
+        probe::print_finding(f);
+    }
+    println!();
+}
+
+/// Prints the report. Returns `Ok(false)` if anything failed.
+pub fn run(args: &DoctorArgs, config: &Config) -> Result<bool, Error> {
+    let duration = check::parse_duration(&args.duration)?;
+    signals::install(&[Signal::Term, Signal::Int]);
+
+    let config_findings = vec![check_config(args.config_file.as_deref())];
+    section("Configuration", &config_findings);
+
+    let mut sources = probe::probe_sources(config);
+    let selected = match entropy::generate(32, config) {
+        Ok(result) => Finding::new("selected", Status::Ok, result.source.clone()),
+        Err(e) => Finding::new("selected", Status::Fail, e.to_string()),
+    };
+    sources.push(selected);
+    section("Sources", &sources);
+
+    println!(
+        "Sampling sources for {}...",
+        check::format_duration(duration)
+    );
+    let check_findings = mini_check(config, duration);
+    section("Mini-check", &check_findings);
+
+    let kernel_findings = vec![probe::probe_kernel(), check_crng()];
+    section("Kernel RNG", &kernel_findings);
+    let daemon_findings = vec![probe::probe_environment(), check_daemon_permissions()];
+    section("Daemon mode", &daemon_findings);
+
+    let problems: Vec<&Finding> = sources
+        .iter()
+        .filter(|f| !OPTIONAL.contains(&f.name))
+        .chain(
+            [
+                &config_findings,
+                &check_findings,
+                &kernel_findings,
+                &daemon_findings,
+            ]
+            .into_iter()
+            .flatten(),
+        )
+        .filter(|f| f.status == Status::Fail)
+        .collect();
+    if signals::shutdown_requested() {
+        println!("Result: INCOMPLETE (interrupted)");
+        return Ok(false);
+    }
+    if problems.is_empty() {
+        println!("Result: PASS");
+        return Ok(true);
+    }
+    let names: Vec<&str> = problems.iter().map(|f| f.name).collect();
+    println!("Result: FAIL ({})", names.join(", "));
+    Ok(false)
+}
@@ -0,0 +1,258 @@
+//! `check --drift-window`: per-source statistics in fixed time windows, each
+//! compared as it closes against the long-run totals of the windows before
+//! it, so slow degradation (thermal, firmware) in a multi-hour run is
+//! flagged instead of averaged away.
+
+use std::time::Duration;
+
+use crate::check::format_duration;
+use crate::stats::{self, FipsTally};
+use crate::table;
+
+/// Samples a window, and the windows before it together, need before they
+/// are compared.
+const MIN_SAMPLES: u64 = 10;
+
+/// Samples and FIPS results of one window, or of several merged.
+#[derive(Debug, Clone, Copy, Default)]
+struct Totals {
+    samples: u64,
+    min_entropy_sum: f64,
+    min_entropy_sq_sum: f64,
+    fips: FipsTally,
+}
+
+impl Totals {
+    fn add(&mut self, min_entropy: f64, fips: &FipsTally) {
+        self.samples += 1;
+        self.min_entropy_sum += min_entropy;
+        self.min_entropy_sq_sum += min_entropy * min_entropy;
+        self.fips.merge(fips);
+    }
+
+    fn merge(&mut self, other: &Totals) {
+        self.samples += other.samples;
+        self.min_entropy_sum += other.min_entropy_sum;
+        self.min_entropy_sq_sum += other.min_entropy_sq_sum;
+        self.fips.merge(&other.fips);
+    }
+
+    fn min_entropy(&self) -> f64 {
+        self.min_entropy_sum / self.samples.max(1) as f64
+    }
+
+    fn min_entropy_se(&self) -> f64 {
+        stats::standard_error(self.min_entropy_sum, self.min_entropy_sq_sum, self.samples)
+    }
+}
+
+/// Signed z-score of `diff` with standard error `se`; infinite when the
+/// values differ but neither varies.
+fn z_score(diff: f64, se: f64) -> f64 {
+    if se > 0.0 {
+        diff / se
+    } else if diff == 0.0 {
+        0.0
+    } else {
+        diff.signum() * f64::INFINITY
+    }
+}
+
+/// z-score of a window's mean min-entropy against the long run.
+fn min_entropy_z(window: &Totals, long_run: &Totals) -> f64 {
+    let se = window.min_entropy_se().hypot(long_run.min_entropy_se());
+    z_score(window.min_entropy() - long_run.min_entropy(), se)
+}
+
+/// Two-proportion z-score of a window's FIPS pass rate against the long
+/// run, if both ran FIPS windows.
+fn fips_z(window: &FipsTally, long_run: &FipsTally) -> Option<f64> {
+    if window.windows == 0 || long_run.windows == 0 {
+        return None;
+    }
+    let (n1, n2) = (window.windows as f64, long_run.windows as f64);
+    let (p1, p2) = (window.all as f64 / n1, long_run.all as f64 / n2);
+    let p = (window.all + long_run.all) as f64 / (n1 + n2);
+    let se = (p * (1.0 - p) * (1.0 / n1 + 1.0 / n2)).sqrt();
+    Some(z_score(p1 - p2, se))
+}
+
+fn pass_pct(fips: &FipsTally) -> Option<f64> {
+    (fips.windows > 0).then(|| fips.pass_pct(fips.all))
+}
+
+/// A closed window and how it compared with the ones before it.
+#[derive(Debug, Clone)]
+pub struct Window {
+    /// Zero-based position from the start of the run.
+    pub index: u64,
+    pub samples: u64,
+    pub min_entropy: f64,
+    pub fips_pass_pct: Option<f64>,
+    /// Means over the windows before it.
+    pub long_run_min_entropy: f64,
+    pub long_run_fips_pass_pct: Option<f64>,
+    /// `None` until enough samples came before it.
+    pub min_entropy_z: Option<f64>,
+    pub fips_z: Option<f64>,
+    pub drifted: bool,
+}
+
+/// One source's windows.
+pub struct Drift {
+    window: Duration,
+    sigma: f64,
+    current_index: u64,
+    current: Totals,
+    long_run: Totals,
+    pub windows: Vec<Window>,
+}
+
+impl Drift {
+    pub fn new(window: Duration, sigma: f64) -> Self {
+        Self {
+            window,
+            sigma,
+            current_index: 0,
+            current: Totals::default(),
+            long_run: Totals::default(),
+            windows: Vec::new(),
+        }
+    }
+
+    /// Records a sample finished `elapsed` into the run. Returns the window
+    /// it closed, if it is the first sample of a later one.
+    pub fn record(
+        &mut self,
+        elapsed: Duration,
+        min_entropy: f64,
+        fips: &FipsTally,
+    ) -> Option<Window> {
+        let index = (elapsed.as_nanos() / self.window.as_nanos().max(1)) as u64;
+        let closed = index > self.current_index && self.close();
+        self.current_index = index;
+        self.current.add(min_entropy, fips);
+        closed.then(|| self.windows.last().cloned()).flatten()
+    }
+
+    /// Closes the window in progress at the end of the run, if it has
+    /// enough samples to say anything.
+    pub fn finish(&mut self) -> Option<Window> {
+        if self.current.samples < MIN_SAMPLES {
+            return None;
+        }
+        self.close().then(|| self.windows.last().cloned()).flatten()
+    }
+
+    /// Whether any window drifted.
+    pub fn drifted(&self) -> bool {
+        self.windows.iter().any(|w| w.drifted)
+    }
+
+    fn close(&mut self) -> bool {
+        let window = std::mem::take(&mut self.current);
+        if window.samples == 0 {
+            return false;
+        }
+        let comparable = window.samples >= MIN_SAMPLES && self.long_run.samples >= MIN_SAMPLES;
+        let min_entropy_z = comparable.then(|| min_entropy_z(&window, &self.long_run));
+        let fips_z = comparable
+            .then(|| fips_z(&window.fips, &self.long_run.fips))
+            .flatten();
+        let beyond = |z: Option<f64>| z.is_some_and(|z| z.abs() > self.sigma);
+        self.windows.push(Window {
+            index: self.current_index,
+            samples: window.samples,
+            min_entropy: window.min_entropy(),
+            fips_pass_pct: pass_pct(&window.fips),
+            long_run_min_entropy: self.long_run.min_entropy(),
+            long_run_fips_pass_pct: pass_pct(&self.long_run.fips),
+            min_entropy_z,
+            fips_z,
+            drifted: beyond(min_entropy_z) || beyond(fips_z),
+        });
+        self.long_run.merge(&window);
+        true
+    }
+
+    /// One line on a drifted window, e.g. `window 45m-1h: min-entropy
+    /// 7.412 vs 7.904 (-6.2σ)`.
+    pub fn describe(&self, w: &Window) -> String {
+        let mut parts = vec![format!(
+            "min-entropy {} vs {} ({})",
+            table::decimal(w.min_entropy, 3),
+            table::decimal(w.long_run_min_entropy, 3),
+            format_z(w.min_entropy_z)
+        )];
+        if let (Some(now), Some(before)) = (w.fips_pass_pct, w.long_run_fips_pass_pct) {
+            parts.push(format!(
+                "FIPS {} vs {} ({})",
+                table::percent(now, 1),
+                table::percent(before, 1),
+                format_z(w.fips_z)
+            ));
+        }
+        format!("window {}: {}", self.span(w.index), parts.join(", "))
+    }
+
+    /// "0-15m", the span of window `index`.
+    pub fn span(&self, index: u64) -> String {
+        let at = |i: u64| format_duration(self.window * i as u32);
+        match index {
+            0 => format!("0-{}", at(1)),
+            i => format!("{}-{}", at(i), at(i + 1)),
+        }
+    }
+}
+
+/// `+2.1σ`, or `-` when not compared.
+pub fn format_z(z: Option<f64>) -> String {
+    match z {
+        Some(z) => format!("{}{}", table::signed(z, 1), table::sigma()),
+        None => "-".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fips(windows: u64, all: u64) -> FipsTally {
+        FipsTally {
+            windows,
+            all,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_drift_flags_degraded_window() {
+        let mut drift = Drift::new(Duration::from_secs(60), 4.0);
+        let mut closed = Vec::new();
+        for s in 0..300u64 {
+            let elapsed = Duration::from_secs(s);
+            // Noise of ±0.01 around 7.9, dropping to 7.7 in the last window
+            let noise = if s % 2 == 0 { 0.01 } else { -0.01 };
+            let min_entropy = if s >= 240 { 7.7 } else { 7.9 } + noise;
+            if let Some(w) = drift.record(elapsed, min_entropy, &fips(1, 1)) {
+                closed.push((w.index, w.drifted));
+            }
+        }
+        assert_eq!(closed, [(0, false), (1, false), (2, false), (3, false)]);
+        let last = drift.finish().unwrap();
+        assert_eq!(last.index, 4);
+        assert!(last.drifted);
+        assert!(last.min_entropy_z.unwrap() < -4.0);
+        assert_eq!(last.fips_z, Some(0.0));
+        assert!(drift.drifted());
+        assert_eq!(drift.span(4), "4m-5m");
+    }
+
+    #[test]
+    fn test_fips_z() {
+        assert_eq!(fips_z(&fips(0, 0), &fips(100, 99)), None);
+        // 90% against 99.5% over hundreds of windows is far outside noise
+        assert!(fips_z(&fips(200, 180), &fips(2000, 1990)).unwrap() < -4.0);
+        assert!(fips_z(&fips(200, 199), &fips(2000, 1990)).unwrap().abs() < 1.0);
+    }
+}
@@ -1,6 +1,8 @@
 use crate::config::{CpuRngConfig, CpuRngPreference};
 use crate::error::Error;
+use crate::estimators;
 use core::sync::atomic::{fence, Ordering};
+use std::time::Instant;
 
 // ---------------------------------------------------------------------------
 // Zeroize utilities (not arch-gated)
@@ -140,44 +142,37 @@ mod x86 {
         present
     }
 
-    /// Executes RDRAND and returns the 64-bit result, retrying up to `retries` times.
-    pub fn rdrand64(retries: u32) -> Option<u64> {
-        for _ in 0..retries {
-            let value: u64;
-            let success: u8;
-            unsafe {
-                asm!(
-                    "rdrand {val}",
-                    "setc {ok}",
-                    val = out(reg) value,
-                    ok = out(reg_byte) success,
-                );
-            }
-            if success != 0 {
-                return Some(value);
-            }
+    /// Executes RDRAND once, returning `None` if the DRNG had no value ready (CF=0).
+    #[inline]
+    pub fn rdrand64_once() -> Option<u64> {
+        let value: u64;
+        let success: u8;
+        unsafe {
+            asm!(
+                "rdrand {val}",
+                "setc {ok}",
+                val = out(reg) value,
+                ok = out(reg_byte) success,
+            );
         }
-        None
+        (success != 0).then_some(value)
     }
 
-    /// Executes RDSEED and returns the 64-bit result, retrying up to `retries` times.
-    pub fn rdseed64(retries: u32) -> Option<u64> {
-        for _ in 0..retries {
-            let value: u64;
-            let success: u8;
-            unsafe {
-                asm!(
-                    "rdseed {val}",
-                    "setc {ok}",
-                    val = out(reg) value,
-                    ok = out(reg_byte) success,
-                );
-            }
-            if success != 0 {
-                return Some(value);
-            }
+    /// Executes RDSEED once, returning `None` if the entropy conditioner had
+    /// no seed ready (CF=0).
+    #[inline]
+    pub fn rdseed64_once() -> Option<u64> {
+        let value: u64;
+        let success: u8;
+        unsafe {
+            asm!(
+                "rdseed {val}",
+                "setc {ok}",
+                val = out(reg) value,
+                ok = out(reg_byte) success,
+            );
         }
-        None
+        (success != 0).then_some(value)
     }
 
     /// Fills an 8-byte buffer using the VIA PadLock XSTORE instruction.
@@ -211,32 +206,294 @@ mod x86 {
 pub struct CpuRngResult {
     pub bytes: Vec<u8>,
     pub source_label: &'static str,
+    /// Raw bytes drawn from the instruction; more than `bytes.len()` when
+    /// oversampled.
+    pub raw_bytes: usize,
+}
+
+// ---------------------------------------------------------------------------
+// Bulk collection with PAUSE backoff and adaptive chunking
+// ---------------------------------------------------------------------------
+
+/// Smallest chunk (in 64-bit words) between scheduler yields when the
+/// instruction is underflowing.
+const BULK_MIN_CHUNK_WORDS: usize = 4;
+/// Largest chunk (in 64-bit words) collected back-to-back while the
+/// instruction keeps up.
+const BULK_MAX_CHUNK_WORDS: usize = 512;
+/// Initial chunk size before any underflow has been observed.
+const BULK_INITIAL_CHUNK_WORDS: usize = 64;
+/// Upper bound on the exponent of the PAUSE backoff (2^6 = 64 PAUSEs).
+const BULK_MAX_PAUSE_SHIFT: u32 = 6;
+
+/// Adapts how many words are collected back-to-back before yielding.
+///
+/// RDSEED is rate-limited by the entropy conditioner and, on contended
+/// cores, underflows (CF=0) long before the caller's retry budget is spent.
+/// A chunk that saw any underflow halves the chunk size so we yield to other
+/// threads sooner; a clean chunk doubles it again.
+#[derive(Debug)]
+struct ChunkController {
+    chunk_words: usize,
+}
+
+impl ChunkController {
+    fn new() -> Self {
+        Self {
+            chunk_words: BULK_INITIAL_CHUNK_WORDS,
+        }
+    }
+
+    fn chunk_words(&self) -> usize {
+        self.chunk_words
+    }
+
+    /// Records the outcome of a chunk. Returns true if the caller should
+    /// yield before starting the next chunk.
+    fn record(&mut self, underflows: u32) -> bool {
+        if underflows > 0 {
+            self.chunk_words = (self.chunk_words / 2).max(BULK_MIN_CHUNK_WORDS);
+            true
+        } else {
+            self.chunk_words = (self.chunk_words * 2).min(BULK_MAX_CHUNK_WORDS);
+            false
+        }
+    }
+}
+
+/// Executes `step` until it yields a value, issuing an exponentially growing
+/// run of PAUSE instructions between attempts. Returns the value and the
+/// number of failed attempts, or `None` after `retries` consecutive failures.
+fn step_with_backoff<F>(step: &mut F, retries: u32) -> Option<(u64, u32)>
+where
+    F: FnMut() -> Option<u64>,
+{
+    for attempt in 0..retries {
+        if let Some(v) = step() {
+            return Some((v, attempt));
+        }
+        for _ in 0..(1u32 << attempt.min(BULK_MAX_PAUSE_SHIFT)) {
+            core::hint::spin_loop();
+        }
+    }
+    None
+}
+
+/// `Err` once `deadline` has passed.
+fn check_deadline(
+    deadline: Option<Instant>,
+    name: &str,
+    done: usize,
+    count: usize,
+) -> Result<(), Error> {
+    match deadline {
+        Some(d) if Instant::now() >= d => Err(Error::Timeout(format!(
+            "{} delivered {} of {} bytes before its timeout",
+            name, done, count
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Fills `count` bytes from a 64-bit instruction `step` in adaptive chunks.
+/// `retries` bounds consecutive failures for any single word; `deadline`,
+/// checked between chunks, bounds the whole collection.
+fn collect_bulk<F>(
+    count: usize,
+    retries: u32,
+    deadline: Option<Instant>,
+    name: &str,
+    step: F,
+) -> Result<Vec<u8>, Error>
+where
+    F: FnMut() -> Option<u64>,
+{
+    let mut buf = vec![0u8; count];
+    if let Err(e) = fill_bulk(&mut buf, retries, deadline, name, step) {
+        zeroize_vec(&mut buf);
+        return Err(e);
+    }
+    Ok(buf)
+}
+
+fn fill_bulk<F>(
+    buf: &mut [u8],
+    retries: u32,
+    deadline: Option<Instant>,
+    name: &str,
+    mut step: F,
+) -> Result<(), Error>
+where
+    F: FnMut() -> Option<u64>,
+{
+    let count = buf.len();
+    let mut ctl = ChunkController::new();
+    let mut offset = 0;
+
+    while offset < count {
+        check_deadline(deadline, name, offset, count)?;
+        let mut underflows = 0u32;
+        for _ in 0..ctl.chunk_words() {
+            if offset >= count {
+                break;
+            }
+            let (val, failed) = match step_with_backoff(&mut step, retries) {
+                Some(r) => r,
+                None => {
+                    return Err(Error::NoEntropy(format!(
+                        "{} failed after {} retries",
+                        name, retries
+                    )));
+                }
+            };
+            underflows += failed;
+            let bytes = val.to_ne_bytes();
+            let to_copy = (count - offset).min(8);
+            buf[offset..offset + to_copy].copy_from_slice(&bytes[..to_copy]);
+            offset += to_copy;
+        }
+        if ctl.record(underflows) && offset < count {
+            std::thread::yield_now();
+        }
+    }
+
+    Ok(())
+}
+
+/// Like [`collect_bulk`], but splits the buffer into `threads` contiguous
+/// parts filled concurrently. Each core has its own path to the DRNG, so
+/// large requests scale with core count.
+fn collect_bulk_parallel(
+    count: usize,
+    threads: usize,
+    retries: u32,
+    deadline: Option<Instant>,
+    name: &str,
+    step: fn() -> Option<u64>,
+) -> Result<Vec<u8>, Error> {
+    if threads <= 1 {
+        return collect_bulk(count, retries, deadline, name, step);
+    }
+
+    // Keep parts word-aligned so no thread wastes a partial word
+    let part = count.div_ceil(threads).next_multiple_of(8).max(8);
+    let mut buf = vec![0u8; count];
+    let result = std::thread::scope(|scope| {
+        let handles: Vec<_> = buf
+            .chunks_mut(part)
+            .map(|chunk| scope.spawn(move || fill_bulk(chunk, retries, deadline, name, step)))
+            .collect();
+        handles.into_iter().try_for_each(|h| {
+            h.join().unwrap_or_else(|_| {
+                Err(Error::NoEntropy(format!(
+                    "{} collector thread panicked",
+                    name
+                )))
+            })
+        })
+    });
+
+    if let Err(e) = result {
+        zeroize_vec(&mut buf);
+        return Err(e);
+    }
+    Ok(buf)
+}
+
+/// Collector threads to use for a `count`-byte request: one per core (or
+/// `config.threads`) once `count` reaches `config.parallel_min_bytes`.
+fn collector_threads(count: usize, config: &CpuRngConfig) -> usize {
+    if count < config.parallel_min_bytes {
+        return 1;
+    }
+    match config.threads {
+        0 => std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1),
+        n => n,
+    }
+}
+
+/// CPU RNG instructions this processor advertises via CPUID.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CpuFeatures {
+    pub rdrand: bool,
+    pub rdseed: bool,
+    pub xstore: bool,
+}
+
+/// Reports which CPU RNG instructions are available (none off x86_64).
+pub fn cpu_features() -> CpuFeatures {
+    #[cfg(target_arch = "x86_64")]
+    {
+        CpuFeatures {
+            rdrand: x86::has_rdrand(),
+            rdseed: x86::has_rdseed(),
+            xstore: x86::has_xstore(),
+        }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        CpuFeatures::default()
+    }
+}
+
+/// Executes RDRAND once: `None` on underflow, when the CPU lacks RDRAND,
+/// or off x86_64.
+pub fn rdrand_once() -> Option<u64> {
+    #[cfg(target_arch = "x86_64")]
+    {
+        x86::has_rdrand().then(x86::rdrand64_once).flatten()
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        None
+    }
+}
+
+/// Executes RDSEED once, like [`rdrand_once`].
+pub fn rdseed_once() -> Option<u64> {
+    #[cfg(target_arch = "x86_64")]
+    {
+        x86::has_rdseed().then(x86::rdseed64_once).flatten()
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        None
+    }
 }
 
 /// Collects `count` bytes of entropy from RDSEED.
 pub fn collect_rdseed(count: usize, retries: u32) -> Result<Vec<u8>, Error> {
+    collect_rdseed_parallel(count, retries, 1, None)
+}
+
+/// Collects `count` bytes of entropy from RDSEED on `threads` threads,
+/// giving up at `deadline`.
+pub fn collect_rdseed_parallel(
+    count: usize,
+    retries: u32,
+    threads: usize,
+    deadline: Option<Instant>,
+) -> Result<Vec<u8>, Error> {
     #[cfg(target_arch = "x86_64")]
     {
         if !x86::has_rdseed() {
             return Err(Error::NoEntropy("RDSEED not supported on this CPU".into()));
         }
-        let mut buf = vec![0u8; count];
-        let mut offset = 0;
-        while offset < count {
-            let val = x86::rdseed64(retries).ok_or_else(|| {
-                Error::NoEntropy(format!("RDSEED failed after {} retries", retries))
-            })?;
-            let bytes = val.to_ne_bytes();
-            let to_copy = (count - offset).min(8);
-            buf[offset..offset + to_copy].copy_from_slice(&bytes[..to_copy]);
-            offset += to_copy;
-        }
-        Ok(buf)
+        collect_bulk_parallel(
+            count,
+            threads,
+            retries,
+            deadline,
+            "RDSEED",
+            x86::rdseed64_once,
+        )
     }
 
     #[cfg(not(target_arch = "x86_64"))]
     {
-        let _ = (count, retries);
+        let _ = (count, retries, threads, deadline);
         Err(Error::NoEntropy(
             "CPU hardware RNG not available on this architecture".into(),
         ))
@@ -245,28 +502,35 @@ pub fn collect_rdseed(count: usize, retries: u32) -> Result<Vec<u8>, Error> {
 
 /// Collects `count` bytes of entropy from RDRAND.
 pub fn collect_rdrand(count: usize, retries: u32) -> Result<Vec<u8>, Error> {
+    collect_rdrand_parallel(count, retries, 1, None)
+}
+
+/// Collects `count` bytes of entropy from RDRAND on `threads` threads,
+/// giving up at `deadline`.
+pub fn collect_rdrand_parallel(
+    count: usize,
+    retries: u32,
+    threads: usize,
+    deadline: Option<Instant>,
+) -> Result<Vec<u8>, Error> {
     #[cfg(target_arch = "x86_64")]
     {
         if !x86::has_rdrand() {
             return Err(Error::NoEntropy("RDRAND not supported on this CPU".into()));
         }
-        let mut buf = vec![0u8; count];
-        let mut offset = 0;
-        while offset < count {
-            let val = x86::rdrand64(retries).ok_or_else(|| {
-                Error::NoEntropy(format!("RDRAND failed after {} retries", retries))
-            })?;
-            let bytes = val.to_ne_bytes();
-            let to_copy = (count - offset).min(8);
-            buf[offset..offset + to_copy].copy_from_slice(&bytes[..to_copy]);
-            offset += to_copy;
-        }
-        Ok(buf)
+        collect_bulk_parallel(
+            count,
+            threads,
+            retries,
+            deadline,
+            "RDRAND",
+            x86::rdrand64_once,
+        )
     }
 
     #[cfg(not(target_arch = "x86_64"))]
     {
-        let _ = (count, retries);
+        let _ = (count, retries, threads, deadline);
         Err(Error::NoEntropy(
             "CPU hardware RNG not available on this architecture".into(),
         ))
@@ -275,6 +539,15 @@ pub fn collect_rdrand(count: usize, retries: u32) -> Result<Vec<u8>, Error> {
 
 /// Collects `count` bytes of entropy from VIA PadLock XSTORE.
 pub fn collect_xstore(count: usize, quality: u32) -> Result<Vec<u8>, Error> {
+    collect_xstore_until(count, quality, None)
+}
+
+/// Like [`collect_xstore`], giving up at `deadline`.
+pub fn collect_xstore_until(
+    count: usize,
+    quality: u32,
+    deadline: Option<Instant>,
+) -> Result<Vec<u8>, Error> {
     #[cfg(target_arch = "x86_64")]
     {
         if !x86::has_xstore() {
@@ -283,6 +556,10 @@ pub fn collect_xstore(count: usize, quality: u32) -> Result<Vec<u8>, Error> {
         let mut buf = vec![0u8; count];
         let mut offset = 0;
         while offset < count {
+            if let Err(e) = check_deadline(deadline, "XSTORE", offset, count) {
+                zeroize_vec(&mut buf);
+                return Err(e);
+            }
             let mut tmp = [0u8; 8];
             if !x86::xstore_bytes(&mut tmp, quality) {
                 zeroize_bytes(&mut tmp);
@@ -298,7 +575,7 @@ pub fn collect_xstore(count: usize, quality: u32) -> Result<Vec<u8>, Error> {
 
     #[cfg(not(target_arch = "x86_64"))]
     {
-        let _ = (count, quality);
+        let _ = (count, quality, deadline);
         Err(Error::NoEntropy(
             "CPU hardware RNG not available on this architecture".into(),
         ))
@@ -340,18 +617,20 @@ fn try_instruction(
     pref: CpuRngPreference,
     count: usize,
     config: &CpuRngConfig,
+    deadline: Option<Instant>,
 ) -> Result<(Vec<u8>, &'static str), Error> {
+    let threads = collector_threads(count, config);
     match pref {
         CpuRngPreference::Rdseed => {
-            let bytes = collect_rdseed(count, config.rdseed_retries)?;
+            let bytes = collect_rdseed_parallel(count, config.rdseed_retries, threads, deadline)?;
             Ok((bytes, "RDSEED"))
         }
         CpuRngPreference::Rdrand => {
-            let bytes = collect_rdrand(count, config.rdrand_retries)?;
+            let bytes = collect_rdrand_parallel(count, config.rdrand_retries, threads, deadline)?;
             Ok((bytes, "RDRAND"))
         }
         CpuRngPreference::Xstore => {
-            let bytes = collect_xstore(count, config.xstore_quality)?;
+            let bytes = collect_xstore_until(count, config.xstore_quality, deadline)?;
             Ok((bytes, "XSTORE"))
         }
     }
@@ -360,6 +639,16 @@ fn try_instruction(
 /// Collects `count` bytes of CPU entropy using the configured instruction preference
 /// and fallback order. Returns the bytes and which instruction succeeded.
 pub fn collect_cpu_entropy(count: usize, config: &CpuRngConfig) -> Result<CpuRngResult, Error> {
+    collect_cpu_entropy_until(count, config, None)
+}
+
+/// Like [`collect_cpu_entropy`], giving up at `deadline`, which is shared
+/// by all instructions tried.
+pub fn collect_cpu_entropy_until(
+    count: usize,
+    config: &CpuRngConfig,
+    deadline: Option<Instant>,
+) -> Result<CpuRngResult, Error> {
     let order = instruction_order(config);
 
     if order.is_empty() {
@@ -370,9 +659,10 @@ pub fn collect_cpu_entropy(count: usize, config: &CpuRngConfig) -> Result<CpuRng
 
     let mut last_err = None;
     for pref in order {
-        match try_instruction(pref, count, config) {
+        match try_instruction(pref, count, config, deadline) {
             Ok((bytes, label)) => {
                 return Ok(CpuRngResult {
+                    raw_bytes: bytes.len(),
                     bytes,
                     source_label: label,
                 });
@@ -386,19 +676,70 @@ pub fn collect_cpu_entropy(count: usize, config: &CpuRngConfig) -> Result<CpuRng
     Err(last_err.unwrap_or_else(|| Error::NoEntropy("no CPU RNG instruction succeeded".into())))
 }
 
+/// True once `raw` holds an estimated `margin` times the entropy of `count`
+/// output bytes, by the SP 800-90B Most Common Value estimate.
+fn enough_entropy(raw: &[u8], count: usize, margin: f64) -> bool {
+    let estimated = estimators::most_common_value(raw) * raw.len() as f64;
+    estimated >= (count * 8) as f64 * margin
+}
+
+/// Collects `count`-byte blocks until [`enough_entropy`] holds or
+/// `max_oversample` blocks have been drawn.
+fn collect_adaptive(
+    count: usize,
+    config: &CpuRngConfig,
+    deadline: Option<Instant>,
+) -> Result<CpuRngResult, Error> {
+    let max_raw = count.saturating_mul(config.max_oversample as usize);
+    let mut raw = Vec::with_capacity(count.saturating_mul(2).min(max_raw));
+    let mut label = "";
+    while raw.len() < max_raw {
+        let block = count.min(max_raw - raw.len());
+        match collect_cpu_entropy_until(block, config, deadline) {
+            Ok(mut r) => {
+                raw.extend_from_slice(&r.bytes);
+                zeroize_vec(&mut r.bytes);
+                label = r.source_label;
+            }
+            Err(e) => {
+                zeroize_vec(&mut raw);
+                return Err(e);
+            }
+        }
+        if enough_entropy(&raw, count, config.oversample_margin) {
+            break;
+        }
+    }
+    if !enough_entropy(&raw, count, config.oversample_margin) {
+        log::debug!(
+            "adaptive oversample stopped at the {}x cap short of its entropy target",
+            config.max_oversample
+        );
+    }
+    Ok(CpuRngResult {
+        raw_bytes: raw.len(),
+        bytes: raw,
+        source_label: label,
+    })
+}
+
 /// Collects CPU entropy with optional oversampling for the standalone path.
 /// If `oversample > 1`, collects `count * oversample` raw bytes and compresses
-/// through BLAKE2b → ChaCha20 to produce `count` output bytes.
+/// through BLAKE2b → ChaCha20 to produce `count` output bytes. With
+/// `adaptive_oversample` the ratio is instead set by [`collect_adaptive`].
 pub fn collect_cpu_entropy_standalone(
     count: usize,
     config: &CpuRngConfig,
+    deadline: Option<Instant>,
 ) -> Result<CpuRngResult, Error> {
-    if config.oversample <= 1 {
-        return collect_cpu_entropy(count, config);
-    }
-
-    let raw_count = count.saturating_mul(config.oversample as usize);
-    let result = collect_cpu_entropy(raw_count, config)?;
+    let result = if config.adaptive_oversample {
+        collect_adaptive(count, config, deadline)?
+    } else if config.oversample > 1 {
+        let raw_count = count.saturating_mul(config.oversample as usize);
+        collect_cpu_entropy_until(raw_count, config, deadline)?
+    } else {
+        return collect_cpu_entropy_until(count, config, deadline);
+    };
 
     let mut raw_bytes = result.bytes;
     let seed = crate::mixer::mix_entropy(&[("cpu-rng-oversample", &raw_bytes)]);
@@ -409,12 +750,18 @@ pub fn collect_cpu_entropy_standalone(
     Ok(CpuRngResult {
         bytes: output,
         source_label: result.source_label,
+        raw_bytes: result.raw_bytes,
     })
 }
 
-/// Best-effort CPU entropy collection. Returns an empty Vec on failure.
-pub fn collect_cpu_entropy_best_effort(count: usize, config: &CpuRngConfig) -> Vec<u8> {
-    collect_cpu_entropy(count, config)
+/// Best-effort CPU entropy collection. Returns an empty Vec on failure or
+/// once `deadline` has passed.
+pub fn collect_cpu_entropy_best_effort(
+    count: usize,
+    config: &CpuRngConfig,
+    deadline: Option<Instant>,
+) -> Vec<u8> {
+    collect_cpu_entropy_until(count, config, deadline)
         .map(|r| r.bytes)
         .unwrap_or_default()
 }
@@ -485,6 +832,101 @@ mod tests {
         assert!(!order.contains(&CpuRngPreference::Rdrand));
     }
 
+    #[test]
+    fn test_chunk_controller_adapts() {
+        let mut ctl = ChunkController::new();
+        assert_eq!(ctl.chunk_words(), BULK_INITIAL_CHUNK_WORDS);
+        assert!(ctl.record(3));
+        assert_eq!(ctl.chunk_words(), BULK_INITIAL_CHUNK_WORDS / 2);
+        for _ in 0..20 {
+            ctl.record(1);
+        }
+        assert_eq!(ctl.chunk_words(), BULK_MIN_CHUNK_WORDS);
+        for _ in 0..20 {
+            assert!(!ctl.record(0));
+        }
+        assert_eq!(ctl.chunk_words(), BULK_MAX_CHUNK_WORDS);
+    }
+
+    #[test]
+    fn test_collect_bulk_tolerates_intermittent_underflow() {
+        let mut n = 0u64;
+        let out = collect_bulk(100, 3, None, "TEST", || {
+            n += 1;
+            // Every other attempt underflows
+            n.is_multiple_of(2).then_some(n)
+        })
+        .unwrap();
+        assert_eq!(out.len(), 100);
+        assert_eq!(&out[..8], &2u64.to_ne_bytes());
+        assert_eq!(&out[8..16], &4u64.to_ne_bytes());
+    }
+
+    #[test]
+    fn test_collect_bulk_gives_up_after_retries() {
+        let mut calls = 0u32;
+        let result = collect_bulk(16, 5, None, "TEST", || {
+            calls += 1;
+            None
+        });
+        assert_eq!(calls, 5);
+        let msg = format!("{}", result.unwrap_err());
+        assert!(msg.contains("TEST failed after 5 retries"));
+    }
+
+    #[test]
+    fn test_collect_bulk_stops_at_deadline() {
+        let result = collect_bulk(16, 5, Some(Instant::now()), "TEST", || Some(1));
+        assert!(matches!(result, Err(Error::Timeout(_))));
+    }
+
+    fn counting_step() -> Option<u64> {
+        static NEXT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+        Some(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+
+    #[test]
+    fn test_collect_bulk_parallel_fills_every_part() {
+        for count in [0, 7, 100, 4096, 100_003] {
+            let out = collect_bulk_parallel(count, 4, 3, None, "TEST", counting_step).unwrap();
+            assert_eq!(out.len(), count);
+            // counting_step never yields 0, so every word was written
+            assert!(out.chunks(8).all(|w| w.iter().any(|&b| b != 0)));
+        }
+    }
+
+    #[test]
+    fn test_collect_bulk_parallel_propagates_failure() {
+        let result = collect_bulk_parallel(1 << 16, 4, 2, None, "TEST", || None);
+        assert!(format!("{}", result.unwrap_err()).contains("TEST failed after 2 retries"));
+    }
+
+    #[test]
+    fn test_enough_entropy() {
+        let uniform: Vec<u8> = (0..=255u8).cycle().take(4096).collect();
+        assert!(enough_entropy(&uniform, 32, 2.0));
+        // 32 distinct bytes are too few samples to support 256 bits at 2x
+        assert!(!enough_entropy(&uniform[..32], 32, 2.0));
+        assert!(!enough_entropy(&[0x55; 4096], 32, 1.0));
+    }
+
+    #[test]
+    fn test_collector_threads() {
+        let config = CpuRngConfig {
+            threads: 3,
+            parallel_min_bytes: 1024,
+            ..Default::default()
+        };
+        assert_eq!(collector_threads(1023, &config), 1);
+        assert_eq!(collector_threads(1024, &config), 3);
+        let auto = CpuRngConfig {
+            threads: 0,
+            parallel_min_bytes: 0,
+            ..Default::default()
+        };
+        assert!(collector_threads(1, &auto) >= 1);
+    }
+
     #[test]
     fn test_all_disabled_error() {
         let config = CpuRngConfig {
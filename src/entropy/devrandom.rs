@@ -0,0 +1,200 @@
+use std::fs;
+use std::io::Read;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+use crate::baseline;
+use crate::error::Error;
+use crate::signals;
+
+/// What keeps /dev/random from stalling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feeder {
+    Haveged,
+    JitterentropyRngd,
+    /// rng-tools, forwarding /dev/hwrng or CPU RNG output into the pool
+    Rngd,
+    /// No daemon; a 5.6+ kernel's crng never blocks once seeded (at boot,
+    /// e.g. by systemd-random-seed)
+    Crng,
+}
+
+impl Feeder {
+    /// Userspace feeders in preference order.
+    const DAEMONS: [Feeder; 3] = [Feeder::Haveged, Feeder::JitterentropyRngd, Feeder::Rngd];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Feeder::Haveged => "haveged",
+            Feeder::JitterentropyRngd => "jitterentropy-rngd",
+            Feeder::Rngd => "rngd",
+            Feeder::Crng => "crng",
+        }
+    }
+
+    /// `/proc/PID/comm` of the daemon, which the kernel cuts to 15 bytes.
+    fn comm(self) -> &'static str {
+        match self {
+            Feeder::JitterentropyRngd => "jitterentropy-r",
+            _ => self.name(),
+        }
+    }
+
+    /// Source label, e.g. `kernel /dev/random (fed by haveged)`.
+    pub fn label(self) -> String {
+        match self {
+            Feeder::Crng => "kernel /dev/random (crng)".into(),
+            _ => format!("kernel /dev/random (fed by {})", self.name()),
+        }
+    }
+}
+
+/// Before 5.6, /dev/random blocked whenever the pool's entropy estimate
+/// ran low; since then it only blocks until the crng is first seeded.
+const CRNG_KERNEL: (u32, u32) = (5, 6);
+
+/// Major and minor version of a release string like `6.8.0-45-generic`.
+pub fn parse_release(release: &str) -> Option<(u32, u32)> {
+    let mut parts = release.split(|c: char| !c.is_ascii_digit());
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+fn crng_kernel() -> bool {
+    parse_release(&baseline::kernel_release()).is_some_and(|v| v >= CRNG_KERNEL)
+}
+
+/// Names in /proc/*/comm.
+fn running_commands() -> Vec<String> {
+    let entries = match fs::read_dir("/proc") {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .flatten()
+        .filter(|e| {
+            e.file_name()
+                .to_string_lossy()
+                .bytes()
+                .all(|b| b.is_ascii_digit())
+        })
+        .filter_map(|e| fs::read_to_string(e.path().join("comm")).ok())
+        .map(|comm| comm.trim().to_string())
+        .collect()
+}
+
+/// Picks the feeder from the running commands: the first daemon found,
+/// else the crng on kernels that have it.
+fn select(commands: &[String], crng: bool) -> Option<Feeder> {
+    Feeder::DAEMONS
+        .into_iter()
+        .find(|f| commands.iter().any(|c| c == f.comm()))
+        .or(crng.then_some(Feeder::Crng))
+}
+
+/// The feeder /dev/random can rely on, if any.
+pub fn detect() -> Option<Feeder> {
+    select(&running_commands(), crng_kernel())
+}
+
+/// Checks if the kernel entropy pool has sufficient entropy (>= 1024 bits).
+fn has_sufficient_entropy() -> bool {
+    match fs::read_to_string("/proc/sys/kernel/random/entropy_avail") {
+        Ok(s) => s.trim().parse::<u32>().unwrap_or(0) >= 1024,
+        Err(_) => false,
+    }
+}
+
+/// Attempts to read `count` bytes from /dev/random with non-blocking I/O
+/// and a `timeout_ms` poll budget (0 waits indefinitely). Needs a feeder:
+/// a running haveged, jitterentropy-rngd or rngd, whose credits must
+/// have reached 1024 bits on pre-5.6 kernels, or a 5.6+ kernel's crng.
+pub fn read_devrandom(count: usize, timeout_ms: u64) -> Result<(Feeder, Vec<u8>), Error> {
+    let crng = crng_kernel();
+    let feeder = select(&running_commands(), crng).ok_or_else(|| {
+        Error::NoEntropy(
+            "no /dev/random feeder (haveged, jitterentropy-rngd or rngd) and no 5.6+ crng".into(),
+        )
+    })?;
+    if !crng && !has_sufficient_entropy() {
+        return Err(Error::NoEntropy(format!(
+            "insufficient kernel entropy (< 1024 bits) despite {}",
+            feeder.name()
+        )));
+    }
+
+    // Open /dev/random with O_NONBLOCK
+    let f = fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open("/dev/random")
+        .map_err(|e| Error::NoEntropy(format!("/dev/random not available: {}", e)))?;
+
+    let fd = f.as_raw_fd();
+    let mut buf = vec![0u8; count];
+    let mut filled = 0;
+    let deadline =
+        (timeout_ms > 0).then(|| std::time::Instant::now() + Duration::from_millis(timeout_ms));
+
+    while filled < count {
+        let remaining = deadline.map(|d| d.saturating_duration_since(std::time::Instant::now()));
+
+        // Poll for readability; Ctrl-C cuts the wait short
+        match signals::wait_readable(fd, remaining) {
+            Ok(true) => {}
+            Ok(false) => return Err(Error::NoEntropy("timeout waiting for /dev/random".into())),
+            Err(e) => {
+                return Err(Error::NoEntropy(format!(
+                    "poll on /dev/random failed: {}",
+                    e
+                )))
+            }
+        }
+
+        // Use the File wrapper for reading
+        let n = match (&f).read(&mut buf[filled..]) {
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        if n == 0 {
+            return Err(Error::NoEntropy(
+                "/dev/random returned 0 bytes".into(),
+            ));
+        }
+        filled += n;
+    }
+
+    Ok((feeder, buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_release() {
+        assert_eq!(parse_release("6.8.0-45-generic"), Some((6, 8)));
+        assert_eq!(parse_release("5.4.0"), Some((5, 4)));
+        assert_eq!(parse_release("4.19-rc1"), Some((4, 19)));
+        assert_eq!(parse_release(""), None);
+    }
+
+    #[test]
+    fn test_select_feeder() {
+        let running = |names: &[&str]| names.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            select(&running(&["rngd", "haveged"]), true),
+            Some(Feeder::Haveged)
+        );
+        assert_eq!(
+            select(&running(&["jitterentropy-r"]), false),
+            Some(Feeder::JitterentropyRngd)
+        );
+        assert_eq!(select(&running(&["sshd"]), true), Some(Feeder::Crng));
+        assert_eq!(select(&running(&["sshd"]), false), None);
+    }
+}
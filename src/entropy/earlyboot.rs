@@ -0,0 +1,123 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::mixer;
+
+use super::cpurng;
+use super::fallback;
+use super::jitter;
+use super::procfs;
+
+/// Jitter samples gathered per round before absorbing into the sponge.
+const JITTER_ROUND: usize = 256;
+/// Interval between procfs snapshots on the coordinating thread.
+const PROCFS_INTERVAL: Duration = Duration::from_millis(100);
+/// Upper bound on collector threads.
+const MAX_THREADS: usize = 8;
+
+/// Collects jitter rounds until `deadline`, absorbing each into a mixer
+/// sponge so memory stays constant however long it runs.
+fn jitter_chain(thread_index: usize, deadline: Instant) -> ([u8; 32], u64) {
+    let mut sponge = mixer::sponge();
+    sponge.absorb("early-boot-thread", &thread_index.to_le_bytes());
+    let mut rounds = 0u64;
+    while Instant::now() < deadline {
+        let mut samples = jitter::collect_jitter_samples(JITTER_ROUND);
+        sponge.absorb("jitter", &samples);
+        cpurng::zeroize_vec(&mut samples);
+        rounds += 1;
+    }
+    (sponge.extract(), rounds)
+}
+
+/// Extended early-boot collection for boards without a hardware RNG: runs
+/// CPU jitter collectors on several threads for `duration` while snapshotting
+/// procfs counters, then mixes everything (plus one fallback batch) into a
+/// `count`-byte seed.
+pub fn collect_early_boot_seed(
+    count: usize,
+    duration: Duration,
+    config: &Config,
+) -> Result<Vec<u8>, Error> {
+    let threads = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(MAX_THREADS);
+    let deadline = Instant::now() + duration;
+
+    let handles: Vec<_> = (0..threads)
+        .map(|i| {
+            thread::Builder::new()
+                .name(format!("early-boot-{}", i))
+                .spawn(move || jitter_chain(i, deadline))
+        })
+        .collect::<Result<_, _>>()?;
+
+    // Snapshot interrupt/scheduler/disk counters while the collectors run
+    let mut procfs_sponge = mixer::sponge();
+    let mut snapshots = 0u64;
+    while Instant::now() < deadline {
+        let mut interrupts = procfs::read_interrupts();
+        let mut stat = procfs::read_stat();
+        let mut diskstats = procfs::read_diskstats();
+        procfs_sponge.absorb("interrupts", &interrupts);
+        procfs_sponge.absorb("stat", &stat);
+        procfs_sponge.absorb("diskstats", &diskstats);
+        cpurng::zeroize_vec(&mut interrupts);
+        cpurng::zeroize_vec(&mut stat);
+        cpurng::zeroize_vec(&mut diskstats);
+        snapshots += 1;
+        thread::sleep(PROCFS_INTERVAL.min(deadline.saturating_duration_since(Instant::now())));
+    }
+
+    let mut sponge = mixer::sponge();
+    let mut rounds = 0u64;
+    for handle in handles {
+        let (mut state, n) = handle
+            .join()
+            .map_err(|_| Error::NoEntropy("early-boot collector thread panicked".into()))?;
+        sponge.absorb("early-boot-jitter", &state);
+        cpurng::zeroize_bytes(&mut state);
+        rounds += n;
+    }
+
+    let mut procfs_state = procfs_sponge.extract();
+    let mut fallback_bytes = fallback::generate_fallback(32, config)?;
+    sponge.absorb("early-boot-procfs", &procfs_state);
+    sponge.absorb("fallback", &fallback_bytes);
+    let output = sponge.expand(count);
+
+    log::debug!(
+        "early-boot collection: {} threads, {} jitter rounds, {} procfs snapshots",
+        threads,
+        rounds,
+        snapshots
+    );
+
+    cpurng::zeroize_bytes(&mut procfs_state);
+    cpurng::zeroize_vec(&mut fallback_bytes);
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_early_boot_seed() {
+        let config = Config::default();
+        let a = collect_early_boot_seed(512, Duration::from_millis(150), &config).unwrap();
+        let b = collect_early_boot_seed(512, Duration::from_millis(150), &config).unwrap();
+        assert_eq!(a.len(), 512);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_jitter_chain_advances() {
+        let (_, rounds) = jitter_chain(0, Instant::now() + Duration::from_millis(50));
+        assert!(rounds > 0);
+    }
+}
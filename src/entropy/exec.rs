@@ -0,0 +1,176 @@
+use std::io::Read;
+use std::os::unix::io::AsRawFd;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::config::ExecConfig;
+use crate::csprng;
+use crate::error::Error;
+use crate::mixer;
+use crate::signals;
+
+use super::cpurng;
+use super::health::HealthTests;
+
+/// Upper bound on captured stdout, so a runaway command can't exhaust memory.
+const MAX_CAPTURE_BYTES: usize = 1 << 20;
+
+/// Substitutes `{bytes}` in each argument with the requested byte count.
+fn expand_args(command: &[String], count: usize) -> Vec<String> {
+    command
+        .iter()
+        .map(|a| a.replace("{bytes}", &count.to_string()))
+        .collect()
+}
+
+/// Runs the configured command and captures up to `MAX_CAPTURE_BYTES` of its
+/// stdout, killing it if it does not exit within the configured timeout.
+fn run_command(config: &ExecConfig, count: usize) -> Result<Vec<u8>, Error> {
+    let argv = expand_args(&config.command, count);
+    let (program, args) = argv
+        .split_first()
+        .ok_or_else(|| Error::NoEntropy("exec source not configured".into()))?;
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| Error::NoEntropy(format!("failed to run {}: {}", program, e)))?;
+
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let fd = stdout.as_raw_fd();
+    let deadline = Instant::now() + Duration::from_millis(config.timeout_ms);
+    let mut captured = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    let outcome = loop {
+        let remaining_ms = deadline
+            .saturating_duration_since(Instant::now())
+            .as_millis() as i32;
+        if remaining_ms <= 0 {
+            break Err(Error::NoEntropy(format!(
+                "{} timed out after {}ms",
+                program, config.timeout_ms
+            )));
+        }
+
+        match signals::wait_readable(fd, Some(Duration::from_millis(remaining_ms as u64))) {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => break Err(e.into()),
+        }
+
+        match stdout.read(&mut chunk) {
+            Ok(0) => break Ok(()),
+            Ok(n) => {
+                let take = n.min(MAX_CAPTURE_BYTES - captured.len());
+                captured.extend_from_slice(&chunk[..take]);
+                if captured.len() >= MAX_CAPTURE_BYTES {
+                    break Ok(());
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => break Err(e.into()),
+        }
+    };
+    cpurng::zeroize_bytes(&mut chunk);
+    drop(stdout);
+
+    if outcome.is_err() {
+        let _ = child.kill();
+    }
+    let status = child.wait()?;
+
+    if let Err(e) = outcome {
+        cpurng::zeroize_vec(&mut captured);
+        return Err(e);
+    }
+    if !status.success() {
+        cpurng::zeroize_vec(&mut captured);
+        return Err(Error::NoEntropy(format!(
+            "{} exited with {}",
+            program, status
+        )));
+    }
+    Ok(captured)
+}
+
+/// External command entropy source: runs the configured collector (e.g.
+/// `tpm2_getrandom`), health-tests its raw stdout, and conditions it through
+/// BLAKE2b-256 → ChaCha20 to produce `count` bytes.
+pub fn read_exec(count: usize, config: &ExecConfig) -> Result<Vec<u8>, Error> {
+    if !config.is_enabled() {
+        return Err(Error::NoEntropy("exec source not configured".into()));
+    }
+
+    let mut raw = run_command(config, count)?;
+
+    if raw.len() < config.min_bytes {
+        let got = raw.len();
+        cpurng::zeroize_vec(&mut raw);
+        return Err(Error::NoEntropy(format!(
+            "exec source produced {} bytes (minimum {})",
+            got, config.min_bytes
+        )));
+    }
+
+    if let Err(e) = HealthTests::new(config.min_entropy).check(&raw) {
+        cpurng::zeroize_vec(&mut raw);
+        return Err(e);
+    }
+
+    let mut seed = mixer::mix_entropy(&[("exec", &raw)]);
+    let output = csprng::generate(seed, count);
+
+    cpurng::zeroize_vec(&mut raw);
+    cpurng::zeroize_bytes(&mut seed);
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(command: &[&str]) -> ExecConfig {
+        ExecConfig {
+            command: command.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_expand_args() {
+        let argv = expand_args(&["tool".into(), "-n{bytes}".into()], 64);
+        assert_eq!(argv, vec!["tool", "-n64"]);
+    }
+
+    #[test]
+    fn test_read_exec_from_command() {
+        let cfg = config(&["head", "-c", "{bytes}", "/dev/urandom"]);
+        let out = read_exec(256, &cfg).unwrap();
+        assert_eq!(out.len(), 256);
+    }
+
+    #[test]
+    fn test_read_exec_rejects_stuck_output() {
+        let cfg = config(&["head", "-c", "{bytes}", "/dev/zero"]);
+        let msg = format!("{}", read_exec(256, &cfg).unwrap_err());
+        assert!(msg.contains("repetition count"));
+    }
+
+    #[test]
+    fn test_read_exec_timeout() {
+        let mut cfg = config(&["sleep", "5"]);
+        cfg.timeout_ms = 50;
+        let msg = format!("{}", read_exec(32, &cfg).unwrap_err());
+        assert!(msg.contains("timed out"));
+    }
+
+    #[test]
+    fn test_read_exec_disabled() {
+        assert!(read_exec(32, &ExecConfig::default()).is_err());
+    }
+}
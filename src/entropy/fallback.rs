@@ -1,19 +1,82 @@
 use std::fs::File;
 use std::io::Read;
+use std::time::{Duration, Instant};
 
-use crate::config::CpuRngConfig;
+use crate::config::Config;
 use crate::csprng;
 use crate::error::Error;
+use crate::metrics;
 use crate::mixer;
 
 use super::cpurng;
+use super::hwmon;
 use super::jitter;
+use super::net;
 use super::procfs;
+use super::sysid;
 
-/// Fallback entropy source: mixes /dev/urandom, procfs data, CPU jitter, and
-/// CPU hardware RNG through BLAKE2b-256 to seed a ChaCha20Rng.
+/// CPU jitter timing samples mixed into each fallback seed.
+pub const JITTER_SAMPLES: usize = 64;
+
+/// Fallback entropy source: mixes /dev/urandom, procfs data, network counter
+/// timing, CPU jitter, and CPU hardware RNG through BLAKE2b-256 to seed a
+/// ChaCha20Rng. Machine and boot identifiers are mixed in too, so VMs cloned
+/// from one image diverge even if urandom was identically seeded early in boot.
+/// With `[fallback] hwmon = true`, hardware sensor readings are mixed in too.
 /// All intermediate buffers are zeroized after use.
-pub fn generate_fallback(count: usize, config: &CpuRngConfig) -> Result<Vec<u8>, Error> {
+pub fn generate_fallback(count: usize, config: &Config) -> Result<Vec<u8>, Error> {
+    generate_fallback_until(count, config, None)
+}
+
+/// Like [`generate_fallback`], but the CPU RNG mix-in, the only input that
+/// can stall, is dropped if it isn't collected by `deadline`.
+pub fn generate_fallback_until(
+    count: usize,
+    config: &Config,
+    deadline: Option<Instant>,
+) -> Result<Vec<u8>, Error> {
+    generate_fallback_mixed(count, config, deadline).map(|(bytes, _)| bytes)
+}
+
+/// Which of the fallback's optional inputs went into one output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mixed {
+    pub hwmon: bool,
+    pub cpu_rng: bool,
+}
+
+impl Mixed {
+    /// Pipeline stages the output is not independent of: the kernel pool,
+    /// through /dev/urandom, and the CPU RNG if it was mixed in.
+    pub fn shared_stages(&self) -> Vec<&'static str> {
+        let mut stages = vec!["devrandom"];
+        if self.cpu_rng {
+            stages.push("cpu-rng");
+        }
+        stages
+    }
+
+    /// e.g. `fallback (urandom + procfs + net + jitter + cpu-rng → BLAKE2b → ChaCha20)`
+    pub fn label(&self) -> String {
+        let mut inputs = vec!["urandom", "procfs", "net"];
+        if self.hwmon {
+            inputs.push("hwmon");
+        }
+        inputs.push("jitter");
+        if self.cpu_rng {
+            inputs.push("cpu-rng");
+        }
+        format!("fallback ({} → BLAKE2b → ChaCha20)", inputs.join(" + "))
+    }
+}
+
+/// Like [`generate_fallback_until`], and also reports which optional
+/// inputs delivered.
+pub fn generate_fallback_mixed(
+    count: usize,
+    config: &Config,
+    deadline: Option<Instant>,
+) -> Result<(Vec<u8>, Mixed), Error> {
     // Seed 32 bytes from /dev/urandom
     let mut urandom_seed = [0u8; 32];
     File::open("/dev/urandom")?.read_exact(&mut urandom_seed)?;
@@ -23,22 +86,51 @@ pub fn generate_fallback(count: usize, config: &CpuRngConfig) -> Result<Vec<u8>,
     let mut stat = procfs::read_stat();
     let mut diskstats = procfs::read_diskstats();
 
-    // Collect 64 CPU jitter timing samples
-    let mut jitter = jitter::collect_jitter_samples(64);
+    // Packet and softirq counter timing, for hosts whose disks are idle
+    let mut net = net::collect_net_samples(net::WINDOW);
+
+    // Temperature, fan and voltage readings (opt-in; empty without sensors)
+    let mut sensors = if config.fallback.hwmon {
+        hwmon::collect_hwmon_samples(Duration::from_millis(config.fallback.hwmon_window_ms))
+    } else {
+        Vec::new()
+    };
+
+    // Per-machine and per-boot identifiers (best-effort, empty if unreadable)
+    let mut machine_id = sysid::read_machine_id();
+    let mut boot_id = sysid::read_boot_id();
+    let mut dmi = sysid::read_dmi_ids();
+    let mut macs = sysid::read_mac_addresses();
+
+    // Collect CPU jitter timing samples
+    let mut jitter = jitter::collect_jitter_samples(JITTER_SAMPLES);
 
     // Collect CPU hardware entropy (best-effort, empty Vec if unavailable)
+    let cpu = &config.cpu_rng;
     let mut cpu_entropy =
-        cpurng::collect_cpu_entropy_best_effort(config.fallback_mix_bytes, config);
+        cpurng::collect_cpu_entropy_best_effort(cpu.fallback_mix_bytes, cpu, deadline);
 
     // Mix all inputs through BLAKE2b-256 with domain separation
-    let mut seed = mixer::mix_entropy(&[
+    let inputs: [(&str, &[u8]); 12] = [
         ("urandom", &urandom_seed),
         ("interrupts", &interrupts),
         ("stat", &stat),
         ("diskstats", &diskstats),
+        ("net", &net),
+        ("hwmon", &sensors),
         ("jitter", &jitter),
         ("cpu-rng", &cpu_entropy),
-    ]);
+        ("machine-id", &machine_id),
+        ("boot-id", &boot_id),
+        ("dmi", &dmi),
+        ("mac", &macs),
+    ];
+    metrics::inputs("fallback", &inputs);
+    let mixed = Mixed {
+        hwmon: !sensors.is_empty(),
+        cpu_rng: !cpu_entropy.is_empty(),
+    };
+    let mut seed = mixer::mix_entropy(&inputs);
 
     // Seed ChaCha20Rng and generate output bytes
     let output = csprng::generate(seed, count);
@@ -48,9 +140,15 @@ pub fn generate_fallback(count: usize, config: &CpuRngConfig) -> Result<Vec<u8>,
     cpurng::zeroize_vec(&mut interrupts);
     cpurng::zeroize_vec(&mut stat);
     cpurng::zeroize_vec(&mut diskstats);
+    cpurng::zeroize_vec(&mut net);
+    cpurng::zeroize_vec(&mut sensors);
     cpurng::zeroize_vec(&mut jitter);
     cpurng::zeroize_vec(&mut cpu_entropy);
+    cpurng::zeroize_vec(&mut machine_id);
+    cpurng::zeroize_vec(&mut boot_id);
+    cpurng::zeroize_vec(&mut dmi);
+    cpurng::zeroize_vec(&mut macs);
     cpurng::zeroize_bytes(&mut seed);
 
-    Ok(output)
+    Ok((output, mixed))
 }
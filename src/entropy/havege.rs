@@ -0,0 +1,157 @@
+//! Built-in HAVEGE-style collector: times short pointer-chasing walks over a
+//! table larger than the CPU caches, with data-dependent branches, so each
+//! sample depends on cache, TLB and branch-predictor state that the rest of
+//! the system keeps disturbing. A replacement for running haveged.
+
+use crate::csprng;
+use crate::error::Error;
+use crate::mixer;
+
+use super::cpurng;
+use super::health::HealthTests;
+
+/// Walk table size in `u32` words (4 MiB, past most L2 and many L3 caches).
+const WALK_WORDS: usize = 1 << 20;
+
+/// Table reads and writes between two timer reads.
+const WALK_STEPS: usize = 32;
+
+/// Timing samples collected per output byte.
+const SAMPLES_PER_BYTE: usize = 8;
+
+/// Fewest samples per call, so small requests still fill an APT window.
+const MIN_SAMPLES: usize = 1024;
+
+/// Assumed min-entropy per sample byte, used for the health-test cutoffs.
+const MIN_ENTROPY: f64 = 1.0;
+
+#[cfg(target_arch = "x86_64")]
+fn timestamp() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn timestamp() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts);
+    }
+    (ts.tv_sec as u64)
+        .wrapping_mul(1_000_000_000)
+        .wrapping_add(ts.tv_nsec as u64)
+}
+
+/// Folds a timer delta into one sample byte.
+fn fold(delta: u64) -> u8 {
+    let d = delta ^ (delta >> 8) ^ (delta >> 16);
+    d as u8
+}
+
+/// Walk state: the table, the current position and a running pool that
+/// steers the walk.
+struct Walker {
+    table: Vec<u32>,
+    pos: usize,
+    pool: u64,
+}
+
+impl Walker {
+    fn new(words: usize) -> Self {
+        debug_assert!(words.is_power_of_two());
+        // Initial contents only need to be irregular, not secret
+        let mut x = timestamp() | 1;
+        let table = (0..words)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x as u32
+            })
+            .collect();
+        Self {
+            table,
+            pos: 0,
+            pool: x,
+        }
+    }
+
+    /// Times one walk and returns the folded delta.
+    fn sample(&mut self) -> u8 {
+        let mask = self.table.len() - 1;
+        let start = timestamp();
+        for step in 0..WALK_STEPS {
+            let v = self.table[self.pos];
+            // Branches on loaded data keep the predictor guessing
+            if v & 1 == 0 {
+                self.pool = self.pool.rotate_left(7) ^ u64::from(v);
+            } else {
+                self.pool = self.pool.wrapping_add(u64::from(v)).rotate_right(3);
+            }
+            if v & 6 == 6 {
+                self.pool ^= self.pool >> 29;
+            }
+            self.table[self.pos] = v.rotate_left(step as u32 + 1) ^ self.pool as u32;
+            self.pos = (v as usize ^ self.pool as usize) & mask;
+        }
+        let delta = timestamp().wrapping_sub(start);
+        self.pool ^= delta;
+        fold(delta)
+    }
+}
+
+/// Collects `count` raw timing-sample bytes (unconditioned).
+pub fn collect_havege_samples(count: usize) -> Vec<u8> {
+    let mut walker = Walker::new(WALK_WORDS);
+    (0..count).map(|_| walker.sample()).collect()
+}
+
+/// HAVEGE-style source: health-tests the raw timing samples, then conditions
+/// them through BLAKE2b-256 → ChaCha20 to produce `count` bytes.
+pub fn read_havege(count: usize) -> Result<Vec<u8>, Error> {
+    let samples = count.saturating_mul(SAMPLES_PER_BYTE).max(MIN_SAMPLES);
+    let mut raw = collect_havege_samples(samples);
+
+    if let Err(e) = HealthTests::new(MIN_ENTROPY).check(&raw) {
+        cpurng::zeroize_vec(&mut raw);
+        return Err(e);
+    }
+
+    let mut seed = mixer::mix_entropy(&[("havege", &raw)]);
+    let output = csprng::generate(seed, count);
+    cpurng::zeroize_vec(&mut raw);
+    cpurng::zeroize_bytes(&mut seed);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(fold(0), 0);
+        assert_eq!(fold(0x01_00), 0x01);
+        assert_eq!(fold(0x01_00_00), 0x01);
+        assert_eq!(fold(0x03_02_01), 0x00);
+    }
+
+    #[test]
+    fn test_walk_changes_table() {
+        let mut walker = Walker::new(1 << 10);
+        let before = walker.table.clone();
+        for _ in 0..64 {
+            walker.sample();
+        }
+        assert!(walker.pos < 1 << 10);
+        assert_ne!(walker.table, before);
+    }
+
+    #[test]
+    fn test_read_havege_length() {
+        let out = read_havege(48).unwrap();
+        assert_eq!(out.len(), 48);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::error::Error;
+use crate::usdt::{self, HealthTest};
+
+/// APT window size for non-binary (byte) samples, SP 800-90B §4.4.2.
+pub const APT_WINDOW: usize = 512;
+
+/// Target false-positive probability exponent: alpha = 2^-20.
+const ALPHA_LOG2: f64 = 20.0;
+
+/// SP 800-90B §4.4 continuous health tests (Repetition Count and Adaptive
+/// Proportion) over byte-valued samples, with cutoffs derived from the
+/// assessed min-entropy of the source.
+#[derive(Debug, Clone, Copy)]
+pub struct HealthTests {
+    rct_cutoff: u32,
+    apt_cutoff: u32,
+}
+
+impl HealthTests {
+    /// Derives cutoffs for a source with `min_entropy` bits per byte.
+    pub fn new(min_entropy: f64) -> Self {
+        let h = min_entropy.clamp(0.1, 8.0);
+        Self {
+            rct_cutoff: 1 + (ALPHA_LOG2 / h).ceil() as u32,
+            apt_cutoff: apt_cutoff(h),
+        }
+    }
+
+    /// Runs both tests over `data`, returning an error naming the first
+    /// test that tripped.
+    pub fn check(&self, data: &[u8]) -> Result<(), Error> {
+        if let Some(run) = longest_repetition(data).filter(|&r| r >= self.rct_cutoff) {
+            usdt::health_failed(HealthTest::RepetitionCount, run, self.rct_cutoff);
+            return Err(Error::NoEntropy(format!(
+                "repetition count test failed: {} identical samples (cutoff {})",
+                run, self.rct_cutoff
+            )));
+        }
+
+        for window in data.chunks(APT_WINDOW) {
+            let first = window[0];
+            let count = window.iter().filter(|&&b| b == first).count() as u32;
+            if count >= self.apt_cutoff {
+                usdt::health_failed(HealthTest::AdaptiveProportion, count, self.apt_cutoff);
+                return Err(Error::NoEntropy(format!(
+                    "adaptive proportion test failed: {} of {} samples equal (cutoff {})",
+                    count,
+                    window.len(),
+                    self.apt_cutoff
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Length of the longest run of identical bytes, or `None` for empty input.
+fn longest_repetition(data: &[u8]) -> Option<u32> {
+    let (&first, rest) = data.split_first()?;
+    let mut prev = first;
+    let mut run = 1u32;
+    let mut longest = 1u32;
+    for &b in rest {
+        if b == prev {
+            run += 1;
+            longest = longest.max(run);
+        } else {
+            prev = b;
+            run = 1;
+        }
+    }
+    Some(longest)
+}
+
+/// APT cutoff: 1 + CRITBINOM(W, 2^-H, 1 - alpha), evaluated in log space so
+/// low-entropy sources don't underflow the binomial pmf.
+fn apt_cutoff(h: f64) -> u32 {
+    let n = APT_WINDOW as f64;
+    let p = 2f64.powf(-h);
+    if p >= 1.0 {
+        return APT_WINDOW as u32;
+    }
+    let target = 1.0 - 2f64.powf(-ALPHA_LOG2);
+    let log_odds = (p / (1.0 - p)).ln();
+
+    let mut log_pmf = n * (1.0 - p).ln();
+    let mut cdf = 0.0;
+    for k in 0..APT_WINDOW {
+        cdf += log_pmf.exp();
+        if cdf >= target {
+            return 1 + k as u32;
+        }
+        let kf = k as f64;
+        log_pmf += ((n - kf) / (kf + 1.0)).ln() + log_odds;
+    }
+    APT_WINDOW as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cutoffs_match_sp800_90b_tables() {
+        let t = HealthTests::new(1.0);
+        assert_eq!(t.rct_cutoff, 21);
+        assert_eq!(t.apt_cutoff, 311);
+        assert_eq!(HealthTests::new(0.5).apt_cutoff, 410);
+        assert_eq!(HealthTests::new(2.0).apt_cutoff, 177);
+
+        let t = HealthTests::new(8.0);
+        assert_eq!(t.rct_cutoff, 4);
+        assert_eq!(t.apt_cutoff, 13);
+    }
+
+    #[test]
+    fn test_rct_detects_stuck_source() {
+        let t = HealthTests::new(1.0);
+        let data = vec![0x55u8; 64];
+        let msg = format!("{}", t.check(&data).unwrap_err());
+        assert!(msg.contains("repetition count"));
+    }
+
+    #[test]
+    fn test_apt_detects_biased_source() {
+        let t = HealthTests::new(4.0);
+        // Alternate 0 with other values: never repeats, but 0 dominates.
+        let data: Vec<u8> = (0..1024)
+            .map(|i| if i % 2 == 0 { 0 } else { (i % 255 + 1) as u8 })
+            .collect();
+        let msg = format!("{}", t.check(&data).unwrap_err());
+        assert!(msg.contains("adaptive proportion"));
+    }
+
+    #[test]
+    fn test_random_data_passes() {
+        let data = crate::csprng::generate([7u8; 32], 4096);
+        assert!(HealthTests::new(8.0).check(&data).is_ok());
+        assert!(HealthTests::new(1.0).check(&data).is_ok());
+        assert!(HealthTests::new(1.0).check(&[]).is_ok());
+    }
+}
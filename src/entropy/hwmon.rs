@@ -0,0 +1,115 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const HWMON_ROOT: &str = "/sys/class/hwmon";
+
+/// Temperature, fan speed and voltage inputs.
+const SENSOR_KINDS: [&str; 3] = ["temp", "fan", "in"];
+
+/// Bounds the time one round of reads can take on boards with many sensors.
+const MAX_SENSORS: usize = 64;
+
+/// Pause between rounds; most drivers refresh their readings no faster.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Whether `name` is a sensor reading such as `temp1_input` or `in0_input`.
+fn is_sensor(name: &str) -> bool {
+    let Some(stem) = name.strip_suffix("_input") else {
+        return false;
+    };
+    SENSOR_KINDS.iter().any(|kind| {
+        stem.strip_prefix(kind)
+            .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+    })
+}
+
+fn sensor_files(root: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|chip| fs::read_dir(chip.path()).ok())
+        .flatten()
+        .flatten()
+        .filter(|e| is_sensor(&e.file_name().to_string_lossy()))
+        .map(|e| e.path())
+        .collect();
+    files.sort();
+    files.truncate(MAX_SENSORS);
+    files
+}
+
+fn read_sensor(path: &Path) -> Option<i64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Appends, for one round, its offset into the window in nanoseconds, then
+/// per sensor the low 16 bits of the reading and its change since `prev`.
+/// Sensors that failed to read contribute nothing.
+fn record(samples: &mut Vec<u8>, elapsed_ns: u64, prev: &[Option<i64>], now: &[Option<i64>]) {
+    samples.extend_from_slice(&elapsed_ns.to_le_bytes());
+    for (before, value) in prev.iter().zip(now) {
+        if let Some(v) = value {
+            samples.extend_from_slice(&(*v as u16).to_le_bytes());
+            let delta = before.map_or(0, |b| v.wrapping_sub(b));
+            samples.extend_from_slice(&(delta as i32).to_le_bytes());
+        }
+    }
+}
+
+/// Reads every hwmon temperature, fan and voltage input repeatedly for
+/// `window`. Readings change slowly, so the value is in their low-order
+/// bits, the deltas and the read timing. Raw, unconditioned bytes for the
+/// mixer; empty on machines without sensors (most VMs).
+pub fn collect_hwmon_samples(window: Duration) -> Vec<u8> {
+    let files = sensor_files(Path::new(HWMON_ROOT));
+    if files.is_empty() {
+        return Vec::new();
+    }
+    let start = Instant::now();
+    let mut prev = vec![None; files.len()];
+    let mut samples = Vec::new();
+    loop {
+        let now: Vec<Option<i64>> = files.iter().map(|f| read_sensor(f)).collect();
+        record(&mut samples, start.elapsed().as_nanos() as u64, &prev, &now);
+        prev = now;
+        if start.elapsed() >= window {
+            break;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_sensor() {
+        assert!(is_sensor("temp1_input"));
+        assert!(is_sensor("fan12_input"));
+        assert!(is_sensor("in0_input"));
+        assert!(!is_sensor("temp1_label"));
+        assert!(!is_sensor("temp_input"));
+        assert!(!is_sensor("power1_input"));
+        assert!(!is_sensor("intrusion0_input"));
+    }
+
+    #[test]
+    fn test_record_low_bits_and_deltas() {
+        let mut samples = Vec::new();
+        record(
+            &mut samples,
+            3,
+            &[Some(41_000), None],
+            &[Some(41_250), None],
+        );
+        let mut expected = 3u64.to_le_bytes().to_vec();
+        expected.extend_from_slice(&(41_250u32 as u16).to_le_bytes());
+        expected.extend_from_slice(&250i32.to_le_bytes());
+        assert_eq!(samples, expected);
+    }
+}
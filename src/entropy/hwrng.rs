@@ -1,7 +1,128 @@
-use std::fs::File;
+use std::fs::{self, File, OpenOptions};
 use std::io::Read;
+use std::os::unix::fs::OpenOptionsExt;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::error::Error;
+use crate::signals;
+
+use super::cpurng;
+
+/// Denominator of `rng_quality`: bits of entropy per this many bits read.
+pub const QUALITY_SCALE: u32 = 1024;
+
+/// Sleep between non-blocking reads while the device has nothing ready.
+const RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Longest sleep while rate-limited before checking for shutdown, and the
+/// rate-limited read size: this much of the limit per read.
+const PACE_SLICE: Duration = Duration::from_millis(100);
+
+/// Spaces reads so they average at most `rate` bytes per second.
+struct Pacer {
+    rate: u64,
+    /// When the bytes read so far have been paid for.
+    next: Option<Instant>,
+}
+
+impl Pacer {
+    /// When the next read may start.
+    fn ready_at(&self, now: Instant) -> Instant {
+        self.next.map_or(now, |next| next.max(now))
+    }
+
+    /// Accounts for `n` bytes read at `now`.
+    fn charge(&mut self, n: usize, now: Instant) {
+        let from = self.ready_at(now);
+        self.next = Some(from + Duration::from_secs_f64(n as f64 / self.rate as f64));
+    }
+
+    /// Bytes to read at once.
+    fn chunk(&self) -> usize {
+        ((self.rate as f64 * PACE_SLICE.as_secs_f64()) as usize).max(1)
+    }
+}
+
+static PACER: Mutex<Pacer> = Mutex::new(Pacer {
+    rate: 0,
+    next: None,
+});
+
+fn pacer() -> std::sync::MutexGuard<'static, Pacer> {
+    PACER.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Caps this process's /dev/hwrng reads at `bytes_per_sec` (0 for no cap),
+/// leaving the rest of the device's output to the kernel's khwrngd.
+pub fn set_rate_limit(bytes_per_sec: u64) {
+    let mut pacer = pacer();
+    pacer.rate = bytes_per_sec;
+    pacer.next = None;
+}
+
+/// Waits until the rate limit allows another read and returns how many of
+/// `want` bytes it may ask for; 0 if the wait would pass `deadline`.
+fn throttle(want: usize, deadline: Option<Instant>) -> Result<usize, Error> {
+    let (n, at) = {
+        let pacer = pacer();
+        if pacer.rate == 0 {
+            return Ok(want);
+        }
+        (want.min(pacer.chunk()), pacer.ready_at(Instant::now()))
+    };
+    if deadline.is_some_and(|d| at > d) {
+        return Ok(0);
+    }
+    loop {
+        let left = at.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Ok(n);
+        }
+        if signals::shutdown_requested() {
+            return Err(signals::interrupted().into());
+        }
+        thread::sleep(left.min(PACE_SLICE));
+    }
+}
+
+/// Charges `n` bytes read against the rate limit.
+fn charge(n: usize) {
+    let mut pacer = pacer();
+    if pacer.rate != 0 {
+        pacer.charge(n, Instant::now());
+    }
+}
+
+/// The current driver's self-declared quality, in bits of entropy per
+/// [`QUALITY_SCALE`] bits (`rng_quality`, or rng-core's `default_quality`
+/// on kernels without it), if sysfs has it.
+pub fn quality() -> Option<u32> {
+    [
+        "/sys/class/misc/hw_random/rng_quality",
+        "/sys/module/rng_core/parameters/default_quality",
+    ]
+    .iter()
+    .find_map(|path| fs::read_to_string(path).ok()?.trim().parse::<u32>().ok())
+    .map(|q| q.min(QUALITY_SCALE))
+}
+
+/// Source label, with the driver's declared quality when sysfs has it.
+pub fn label() -> String {
+    match quality() {
+        Some(q) => format!(
+            "hardware RNG (/dev/hwrng, rng_quality {}/{})",
+            q, QUALITY_SCALE
+        ),
+        None => "hardware RNG (/dev/hwrng)".into(),
+    }
+}
+
+/// `bits` of credit scaled down to a device's declared `quality`.
+pub fn scale_credit(bits: u32, quality: u32) -> u32 {
+    (u64::from(bits) * u64::from(quality.min(QUALITY_SCALE)) / u64::from(QUALITY_SCALE)) as u32
+}
 
 /// Attempts to read `count` bytes from /dev/hwrng (hardware RNG).
 pub fn read_hwrng(count: usize) -> Result<Vec<u8>, Error> {
@@ -9,6 +130,150 @@ pub fn read_hwrng(count: usize) -> Result<Vec<u8>, Error> {
         Error::NoEntropy(format!("/dev/hwrng not available: {}", e))
     })?;
     let mut buf = vec![0u8; count];
-    f.read_exact(&mut buf)?;
+    // /dev/hwrng has no poll support, so this blocks in read(); handlers
+    // are installed without SA_RESTART, so a signal still gets us out.
+    let mut filled = 0;
+    while filled < count {
+        let want = match throttle(count - filled, None) {
+            Ok(n) => n,
+            Err(e) => {
+                cpurng::zeroize_vec(&mut buf);
+                return Err(e);
+            }
+        };
+        match f.read(&mut buf[filled..filled + want]) {
+            Ok(0) => return Err(Error::NoEntropy("/dev/hwrng returned 0 bytes".into())),
+            Ok(n) => {
+                charge(n);
+                filled += n;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {
+                if signals::shutdown_requested() {
+                    cpurng::zeroize_vec(&mut buf);
+                    return Err(signals::interrupted().into());
+                }
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
     Ok(buf)
 }
+
+/// Like [`read_hwrng`], but reads non-blocking and stops at `deadline`.
+/// Returns fewer than `count` bytes only when the deadline passed.
+pub fn read_hwrng_until(count: usize, deadline: Instant) -> Result<Vec<u8>, Error> {
+    let mut f = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open("/dev/hwrng")
+        .map_err(|e| Error::NoEntropy(format!("/dev/hwrng not available: {}", e)))?;
+    let mut buf = vec![0u8; count];
+    let mut filled = 0;
+    while filled < count && Instant::now() < deadline {
+        if signals::shutdown_requested() {
+            cpurng::zeroize_vec(&mut buf);
+            return Err(signals::interrupted().into());
+        }
+        let want = match throttle(count - filled, Some(deadline)) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) => {
+                cpurng::zeroize_vec(&mut buf);
+                return Err(e);
+            }
+        };
+        match f.read(&mut buf[filled..filled + want]) {
+            Ok(0) => return Err(Error::NoEntropy("/dev/hwrng returned 0 bytes".into())),
+            Ok(n) => {
+                charge(n);
+                filled += n;
+            }
+            Err(e)
+                if e.kind() == std::io::ErrorKind::WouldBlock
+                    || e.kind() == std::io::ErrorKind::Interrupted =>
+            {
+                thread::sleep(
+                    RETRY_INTERVAL.min(deadline.saturating_duration_since(Instant::now())),
+                );
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    buf.truncate(filled);
+    Ok(buf)
+}
+
+/// Reads `count` bytes from /dev/hwrng, failing with [`Error::Timeout`] if
+/// they don't arrive within `timeout_ms` (0 waits indefinitely).
+pub fn read_hwrng_within(count: usize, timeout_ms: u64) -> Result<Vec<u8>, Error> {
+    if timeout_ms == 0 {
+        return read_hwrng(count);
+    }
+    let mut buf = read_hwrng_until(count, Instant::now() + Duration::from_millis(timeout_ms))?;
+    if buf.len() < count {
+        let got = buf.len();
+        cpurng::zeroize_vec(&mut buf);
+        return Err(Error::Timeout(format!(
+            "/dev/hwrng delivered {} of {} bytes within {} ms",
+            got, count, timeout_ms
+        )));
+    }
+    Ok(buf)
+}
+
+/// Whether `[limits] hwrng_bytes_per_sec` caps reads.
+pub fn rate_limited() -> bool {
+    pacer().rate != 0
+}
+
+/// /dev/hwrng held open for a long run of blocking reads, each one paced
+/// by the rate limit; for `stream --device hwrng`.
+pub struct Paced(File);
+
+impl Paced {
+    pub fn new(file: File) -> Self {
+        Self(file)
+    }
+}
+
+impl Read for Paced {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // throttle only fails once shutdown is requested
+        let want = throttle(buf.len(), None).map_err(|_| signals::interrupted())?;
+        let n = self.0.read(&mut buf[..want])?;
+        charge(n);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale_credit() {
+        assert_eq!(scale_credit(256, QUALITY_SCALE), 256);
+        assert_eq!(scale_credit(256, 512), 128);
+        assert_eq!(scale_credit(256, 0), 0);
+        assert_eq!(scale_credit(256, 4096), 256);
+    }
+
+    #[test]
+    fn test_pacer() {
+        let now = Instant::now();
+        let mut pacer = Pacer {
+            rate: 1000,
+            next: None,
+        };
+        assert_eq!(pacer.chunk(), 100);
+        assert_eq!(pacer.ready_at(now), now);
+        pacer.charge(100, now);
+        pacer.charge(500, now);
+        assert_eq!(pacer.ready_at(now), now + Duration::from_millis(600));
+        // Idle time is not banked
+        let later = now + Duration::from_secs(10);
+        assert_eq!(pacer.ready_at(later), later);
+        pacer.charge(250, later);
+        assert_eq!(pacer.ready_at(later), later + Duration::from_millis(250));
+    }
+}
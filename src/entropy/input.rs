@@ -0,0 +1,300 @@
+//! Interactive collector: the arrival times of key presses on the terminal
+//! (raw mode) or of key and motion events from a /dev/input device. Each
+//! event is credited with the kernel's old input-randomness estimate (the
+//! smallest of the first, second and third timing differences), capped at
+//! [`MAX_BITS_PER_EVENT`]. A meter on stderr shows the bits collected; the
+//! samples are absorbed into a mixer sponge as they arrive and expanded
+//! like the other collectors.
+
+use std::fs::File;
+use std::io::{self, IsTerminal, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use crate::cli::{InputArgs, OutputFormat};
+use crate::csprng;
+use crate::error::Error;
+use crate::mixer::{self, Sponge};
+use crate::output::{self, Destination};
+use crate::secmem;
+
+use super::{cpurng, EntropyResult};
+
+/// Most bits credited for one event, however irregular its timing.
+pub const MAX_BITS_PER_EVENT: u32 = 2;
+
+/// Most bits collected for one request: the size of the mixer's seed.
+const MAX_BITS: u32 = 256;
+
+const CTRL_C: u8 = 0x03;
+const CTRL_D: u8 = 0x04;
+
+const EV_KEY: u16 = 0x01;
+const EV_REL: u16 = 0x02;
+const KEY_PRESS: i32 = 1;
+
+const METER_WIDTH: usize = 30;
+
+/// Where events come from.
+#[derive(Debug, Clone, Copy)]
+pub enum Device<'a> {
+    /// Key presses on the controlling terminal (stdin).
+    Terminal,
+    /// An evdev device such as /dev/input/event3.
+    Evdev(&'a Path),
+}
+
+/// Non-canonical, non-echoing terminal mode with signals off, restored on
+/// drop.
+pub struct RawTerminal {
+    fd: libc::c_int,
+    saved: libc::termios,
+}
+
+impl RawTerminal {
+    pub fn enter(fd: libc::c_int) -> Result<Self, Error> {
+        unsafe {
+            let mut saved: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(fd, &mut saved) != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            let mut tio = saved;
+            tio.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+            tio.c_cc[libc::VMIN] = 1;
+            tio.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(fd, libc::TCSAFLUSH, &tio) != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            Ok(Self { fd, saved })
+        }
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(self.fd, libc::TCSAFLUSH, &self.saved);
+        }
+    }
+}
+
+fn now_ns() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts);
+    }
+    (ts.tv_sec as u64)
+        .wrapping_mul(1_000_000_000)
+        .wrapping_add(ts.tv_nsec as u64)
+}
+
+/// Records events and estimates the entropy of their timing.
+pub struct Collector {
+    sponge: Sponge,
+    bits: u32,
+    last_us: Option<u64>,
+    last_delta: i64,
+    last_delta2: i64,
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector {
+    pub fn new() -> Self {
+        Self {
+            sponge: mixer::sponge(),
+            bits: 0,
+            last_us: None,
+            last_delta: 0,
+            last_delta2: 0,
+        }
+    }
+
+    /// Records an event with `code` (a key or axis) at `at_ns`, returning
+    /// the bits credited for it.
+    pub fn record(&mut self, at_ns: u64, code: u16) -> u32 {
+        let mut sample = [0u8; 10];
+        sample[..8].copy_from_slice(&at_ns.to_le_bytes());
+        sample[8..].copy_from_slice(&code.to_le_bytes());
+        self.sponge.absorb("input", &sample);
+        cpurng::zeroize_bytes(&mut sample);
+        let us = at_ns / 1000;
+        let Some(last) = self.last_us.replace(us) else {
+            return 0;
+        };
+        let delta = us.wrapping_sub(last) as i64;
+        let delta2 = delta - self.last_delta;
+        let delta3 = delta2 - self.last_delta2;
+        self.last_delta = delta;
+        self.last_delta2 = delta2;
+        let smallest = delta.abs().min(delta2.abs()).min(delta3.abs()) as u64;
+        let credit = (u64::BITS - (smallest >> 1).leading_zeros()).min(MAX_BITS_PER_EVENT);
+        self.bits += credit;
+        credit
+    }
+
+    /// [`record`](Self::record) at the current time.
+    pub fn record_now(&mut self, code: u16) -> u32 {
+        self.record(now_ns(), code)
+    }
+
+    /// Bits credited so far.
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// A seed from every event recorded.
+    pub fn finish(mut self) -> [u8; 32] {
+        self.sponge.extract()
+    }
+}
+
+fn show_meter(bits: u32, needed: u32) -> io::Result<()> {
+    let filled = METER_WIDTH * bits.min(needed) as usize / needed as usize;
+    eprint!(
+        "\r[{}{}] {}/{} bits",
+        "#".repeat(filled),
+        " ".repeat(METER_WIDTH - filled),
+        bits.min(needed),
+        needed
+    );
+    io::stderr().flush()
+}
+
+fn collect_terminal(collector: &mut Collector, needed: u32) -> Result<(), Error> {
+    let stdin = io::stdin();
+    let _raw = RawTerminal::enter(stdin.as_raw_fd())?;
+    let mut input = stdin.lock();
+    let mut key = [0u8; 1];
+    while collector.bits() < needed {
+        input.read_exact(&mut key)?;
+        if matches!(key[0], CTRL_C | CTRL_D) {
+            return Err(Error::InvalidArgs("input collection aborted".into()));
+        }
+        collector.record_now(u16::from(key[0]));
+        show_meter(collector.bits(), needed)?;
+    }
+    Ok(())
+}
+
+fn collect_evdev(mut device: File, collector: &mut Collector, needed: u32) -> Result<(), Error> {
+    let mut buf = [0u8; std::mem::size_of::<libc::input_event>()];
+    while collector.bits() < needed {
+        device.read_exact(&mut buf)?;
+        let event: libc::input_event = unsafe { std::ptr::read_unaligned(buf.as_ptr().cast()) };
+        let counted = match event.type_ {
+            EV_KEY => event.value == KEY_PRESS,
+            EV_REL => true,
+            _ => false,
+        };
+        if counted {
+            collector.record_now(event.code);
+            show_meter(collector.bits(), needed)?;
+        }
+    }
+    Ok(())
+}
+
+/// Collects events until their timing is credited with `count * 8` bits (at
+/// most 256), then returns the seed mixed from them.
+pub fn read_input(count: usize, device: Device) -> Result<[u8; 32], Error> {
+    let needed = u32::try_from(count.saturating_mul(8)).map_or(MAX_BITS, |b| b.min(MAX_BITS));
+    let evdev = match device {
+        Device::Terminal => None,
+        Device::Evdev(path) => Some(
+            File::open(path)
+                .map_err(|e| Error::NoEntropy(format!("cannot open {}: {}", path.display(), e)))?,
+        ),
+    };
+    if evdev.is_some() {
+        eprintln!("Move the mouse or press keys until the meter is full.");
+    } else {
+        eprintln!("Type at random until the meter is full; Ctrl-C aborts.");
+    }
+    let mut collector = Collector::new();
+    show_meter(0, needed)?;
+    let collected = match evdev {
+        None => collect_terminal(&mut collector, needed),
+        Some(file) => collect_evdev(file, &mut collector, needed),
+    };
+    eprintln!();
+    let mut seed = collector.finish();
+    if let Err(e) = collected {
+        cpurng::zeroize_bytes(&mut seed);
+        return Err(e);
+    }
+    Ok(seed)
+}
+
+/// `mixrand input`: collects events and writes the bytes like one-shot
+/// output.
+pub fn run(args: &InputArgs) -> Result<(), Error> {
+    if args.bytes == 0 {
+        return Err(Error::InvalidArgs(
+            "byte count must be greater than 0".into(),
+        ));
+    }
+    let device = match &args.device {
+        Some(path) => Device::Evdev(path),
+        None if io::stdin().is_terminal() => Device::Terminal,
+        None => {
+            return Err(Error::InvalidArgs(
+                "input needs an interactive terminal on stdin, or --device".into(),
+            ))
+        }
+    };
+    let mut seed = read_input(args.bytes, device)?;
+    let mut expand = csprng::stream(seed);
+    cpurng::zeroize_bytes(&mut seed);
+    // For text, -n counts characters and rejected draws need more bytes
+    let (mut drawn, mut formatted) = output::draw(args.bytes, &args.format, |n| {
+        Ok(EntropyResult {
+            bytes: expand(n),
+            source: "input".into(),
+        })
+    })?;
+    cpurng::zeroize_vec(&mut drawn.bytes);
+    let locked = secmem::protect(&formatted);
+    let dest = match &args.output_file {
+        Some(path) => Destination::File(path),
+        None => Destination::Stdout,
+    };
+    let written = output::write_output(&formatted, &OutputFormat::Raw, dest, None);
+    cpurng::zeroize_vec(&mut formatted);
+    drop(locked);
+    written?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collector_credit() {
+        let mut regular = Collector::new();
+        for i in 0..10 {
+            regular.record(i * 100_000_000, 0);
+        }
+        assert_eq!(regular.bits(), MAX_BITS_PER_EVENT);
+
+        let mut irregular = Collector::new();
+        assert_eq!(irregular.record(0, 0), 0);
+        for (i, gap_us) in [180_000, 95_000, 240_123, 130_777].into_iter().enumerate() {
+            let at = irregular.last_us.unwrap() + gap_us;
+            assert_eq!(irregular.record(at * 1000, i as u16), MAX_BITS_PER_EVENT);
+        }
+        assert_eq!(irregular.bits(), 4 * MAX_BITS_PER_EVENT);
+
+        let mut burst = Collector::new();
+        burst.record(0, 0);
+        assert_eq!(burst.record(1000, 0), 0);
+    }
+}
@@ -1,12 +1,35 @@
 pub mod cpurng;
+pub mod devrandom;
+pub mod earlyboot;
+pub mod exec;
 pub mod fallback;
-pub mod haveged;
+pub mod havege;
+pub mod health;
+pub mod hwmon;
 pub mod hwrng;
+pub mod input;
 pub mod jitter;
+pub mod net;
+pub mod plugin;
 pub mod procfs;
+pub mod serial;
+pub mod sysid;
+pub mod vsock;
 
-use crate::config::CpuRngConfig;
+use std::time::{Duration, Instant};
+
+use crate::budget;
+use crate::cli::OnTimeout;
+use crate::config::{Config, ExecConfig, SerialConfig, VsockConfig};
+use crate::csprng;
 use crate::error::Error;
+use crate::fips;
+use crate::metrics;
+use crate::mixer;
+use crate::restart;
+use crate::sourcecache;
+use crate::timing;
+use crate::usdt;
 
 /// Result of entropy generation, including the bytes and which source was used.
 pub struct EntropyResult {
@@ -14,63 +37,737 @@ pub struct EntropyResult {
     pub source: String,
 }
 
-/// Attempts entropy sources in priority order:
-/// 1. Hardware RNG (/dev/hwrng)
-/// 2. CPU hardware RNG (RDSEED/RDRAND/XSTORE) with standalone oversampling
-/// 3. Haveged (/dev/random with haveged)
-/// 4. Fallback (urandom + procfs + jitter mixed through BLAKE2b → ChaCha20)
-pub fn generate(count: usize, config: &CpuRngConfig) -> Result<EntropyResult, Error> {
-    // Try hardware RNG first
-    match hwrng::read_hwrng(count) {
-        Ok(bytes) => {
-            return Ok(EntropyResult {
-                bytes,
-                source: "hardware RNG (/dev/hwrng)".into(),
-            });
+/// Bound on how long [`generate_within`] may wait on slow sources.
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline {
+    pub at: Instant,
+    pub on_timeout: OnTimeout,
+}
+
+impl Deadline {
+    pub fn after(wait: Duration, on_timeout: OnTimeout) -> Self {
+        Self {
+            at: Instant::now() + wait,
+            on_timeout,
         }
-        Err(e) => {
-            log::debug!("hwrng unavailable: {}", e);
+    }
+
+    fn remaining(&self) -> Duration {
+        self.at.saturating_duration_since(Instant::now())
+    }
+}
+
+/// `Err(Timeout)` if the deadline has passed and the caller asked to fail
+/// rather than fall through.
+fn check_deadline(deadline: Option<Deadline>, what: &str) -> Result<(), Error> {
+    match deadline {
+        Some(d) if d.remaining().is_zero() && d.on_timeout == OnTimeout::Fail => {
+            Err(Error::Timeout(what.to_string()))
         }
+        _ => Ok(()),
     }
+}
 
-    // Try CPU hardware RNG (RDSEED/RDRAND/XSTORE) with standalone oversampling
-    match cpurng::collect_cpu_entropy_standalone(count, config) {
-        Ok(result) => {
-            let source = if config.oversample > 1 {
-                format!(
-                    "CPU hardware RNG ({}, {}x oversample)",
-                    result.source_label, config.oversample
-                )
+/// Caps a slow source's own timeout to what is left of the deadline, or
+/// `None` once nothing is left.
+fn capped_timeout_ms(timeout_ms: u64, deadline: Option<Deadline>) -> Option<u64> {
+    match deadline {
+        None => Some(timeout_ms),
+        Some(d) => {
+            let left = d.remaining().as_millis() as u64;
+            (left > 0).then(|| timeout_ms.min(left))
+        }
+    }
+}
+
+/// Deadline for a source with a `[timeouts]` budget of `timeout_ms`
+/// (0 = none).
+pub fn timeout_deadline(timeout_ms: u64) -> Option<Instant> {
+    (timeout_ms > 0).then(|| Instant::now() + Duration::from_millis(timeout_ms))
+}
+
+/// XORs `partial` into the front of `bytes`; each output byte is at least
+/// as unpredictable as the stronger of its two inputs.
+fn mix_partial(bytes: &mut [u8], partial: &[u8]) {
+    for (b, p) in bytes.iter_mut().zip(partial) {
+        *b ^= p;
+    }
+}
+
+/// Source outputs gathered until `[policy]` is satisfied. Zeroized on drop,
+/// so an early return leaves nothing behind.
+struct Collected {
+    need: usize,
+    /// (short source name, result), in priority order
+    results: Vec<(String, EntropyResult)>,
+    /// Results that count towards `need`
+    independent: usize,
+    /// Results that don't, noted for the error if too few do
+    dependent: Vec<String>,
+}
+
+impl Collected {
+    fn new(need: usize) -> Self {
+        Self {
+            need: need.max(1),
+            results: Vec::new(),
+            independent: 0,
+            dependent: Vec::new(),
+        }
+    }
+
+    /// Adds one source's output; `true` once enough sources are in.
+    fn push(&mut self, name: &str, result: EntropyResult) -> bool {
+        self.push_sharing(name, &[], result)
+    }
+
+    /// Like [`push`](Self::push) for a source that also reads the stages
+    /// in `shares`: it is still mixed in, but only counts towards `need`
+    /// if none of them has delivered.
+    fn push_sharing(&mut self, name: &str, shares: &[&str], result: EntropyResult) -> bool {
+        usdt::source_selected(name, result.bytes.len());
+        let overlap: Vec<&str> = shares
+            .iter()
+            .copied()
+            .filter(|s| self.results.iter().any(|(n, _)| n == s))
+            .collect();
+        if overlap.is_empty() {
+            self.independent += 1;
+        } else {
+            self.dependent
+                .push(format!("{} shares {}", name, overlap.join(" and ")));
+        }
+        self.results.push((name.to_string(), result));
+        self.independent >= self.need
+    }
+
+    /// A lone result as is, or several mixed through BLAKE2b and expanded
+    /// to `count` bytes with ChaCha20. `Err` if too few independent sources
+    /// delivered.
+    fn finish(mut self, count: usize) -> Result<EntropyResult, Error> {
+        if self.independent < self.need {
+            let names: Vec<&str> = self.results.iter().map(|(n, _)| n.as_str()).collect();
+            let dependent = if self.dependent.is_empty() {
+                String::new()
             } else {
-                format!("CPU hardware RNG ({})", result.source_label)
+                format!("; {}", self.dependent.join("; "))
             };
-            return Ok(EntropyResult {
-                bytes: result.bytes,
-                source,
-            });
+            return Err(Error::NoEntropy(format!(
+                "[policy] requires {} independent sources but only {} delivered ({}{})",
+                self.need,
+                self.independent,
+                if names.is_empty() {
+                    "none".to_string()
+                } else {
+                    names.join(", ")
+                },
+                dependent
+            )));
         }
-        Err(e) => {
-            log::debug!("cpurng unavailable: {}", e);
+        if self.results.len() == 1 {
+            return Ok(self.results.remove(0).1);
         }
+        let inputs: Vec<(&str, &[u8])> = self
+            .results
+            .iter()
+            .map(|(name, r)| (name.as_str(), r.bytes.as_slice()))
+            .collect();
+        let mut seed = mixer::mix_entropy(&inputs);
+        let bytes = csprng::generate(seed, count);
+        cpurng::zeroize_bytes(&mut seed);
+        let labels: Vec<&str> = self.results.iter().map(|(_, r)| &r.source[..]).collect();
+        Ok(EntropyResult {
+            bytes,
+            source: format!("{} → BLAKE2b → ChaCha20", labels.join(" + ")),
+        })
     }
+}
 
-    // Try haveged
-    match haveged::read_haveged(count) {
-        Ok(bytes) => {
-            return Ok(EntropyResult {
-                bytes,
-                source: "haveged (/dev/random)".into(),
-            });
+impl Drop for Collected {
+    fn drop(&mut self) {
+        for (_, r) in &mut self.results {
+            cpurng::zeroize_vec(&mut r.bytes);
         }
-        Err(e) => {
-            log::debug!("haveged unavailable: {}", e);
+    }
+}
+
+/// Attempts entropy sources in priority order:
+/// 1. Hardware RNG (/dev/hwrng)
+/// 2. Serial/USB TRNG (if `[serial]` is configured)
+/// 3. External command (if `[exec]` is configured)
+/// 4. VM host's `serve-vsock` (if `[vsock]` is configured)
+/// 5. Plugins from `[plugins] dir`, in file name order
+/// 6. CPU hardware RNG (RDSEED/RDRAND/XSTORE) with standalone oversampling
+/// 7. Kernel /dev/random, if haveged, jitterentropy-rngd, rngd or a 5.6+ crng feeds it
+/// 8. Fallback (urandom + procfs + network counter timing + jitter + machine/boot ids mixed through BLAKE2b → ChaCha20)
+///
+/// The first source that delivers is used, unless `[policy]` asks for more:
+/// then the chain continues until that many have delivered, and their
+/// outputs are mixed through BLAKE2b and expanded with ChaCha20. With
+/// `[policy] xor_pipelines` the chain runs twice, each time over one half's
+/// sources only, and the two outputs are XORed.
+///
+/// Requests over `[limits] max_request_bytes` are rejected, and /dev/hwrng
+/// is skipped once its daily budget is spent.
+pub fn generate(count: usize, config: &Config) -> Result<EntropyResult, Error> {
+    generate_within(count, config, None)
+}
+
+/// [`generate`] with an optional deadline on the slow sources (/dev/hwrng,
+/// serial, exec, vsock). On expiry it either fails with [`Error::Timeout`] or moves
+/// on, XOR-ing whatever /dev/hwrng delivered into the next source's output.
+/// `[timeouts] hwrng_ms` expiring always moves on.
+///
+/// With `fips = true` the source output is only entropy input: it is
+/// health-tested and seeds a fresh HMAC_DRBG, which produces the bytes.
+pub fn generate_within(
+    count: usize,
+    config: &Config,
+    deadline: Option<Deadline>,
+) -> Result<EntropyResult, Error> {
+    config.limits.check_request(count)?;
+    let result = if config.fips {
+        fips::check_operational()?;
+        let input = collect(fips::input_bytes(count), config, deadline)?;
+        EntropyResult {
+            bytes: fips::condition(input.bytes, count)?,
+            source: format!("{} → HMAC_DRBG", input.source),
+        }
+    } else {
+        collect(count, config, deadline)?
+    };
+    restart::observe(&result.bytes);
+    usdt::bytes_generated(result.bytes.len(), &result.source);
+    Ok(result)
+}
+
+/// Stages of the chain by the names `[policy] xor_pipelines` takes;
+/// plugins go by their own names.
+const STAGES: [&str; 7] = [
+    "hwrng",
+    "serial",
+    "exec",
+    "vsock",
+    "cpu-rng",
+    "devrandom",
+    "fallback",
+];
+
+/// Stages one run of the chain may try: all of them, or one half of
+/// `[policy] xor_pipelines`.
+#[derive(Debug, Clone, Copy)]
+enum Pipeline<'a> {
+    All,
+    Only(&'a [String]),
+}
+
+impl Pipeline<'_> {
+    fn allows(&self, stage: &str) -> bool {
+        match self {
+            Pipeline::All => true,
+            Pipeline::Only(names) => names.iter().any(|n| n.eq_ignore_ascii_case(stage)),
         }
     }
+}
 
-    // Fallback
-    let bytes = fallback::generate_fallback(count, config)?;
+/// The two halves of `[policy] xor_pipelines`, or `None` when it is unset.
+/// Anything but two non-empty, disjoint lists of known stages is an error,
+/// as is a half whose fallback reads a source the other half uses.
+fn xor_halves(config: &Config) -> Result<Option<[Pipeline<'_>; 2]>, Error> {
+    let halves = &config.policy.xor_pipelines;
+    if halves.is_empty() {
+        return Ok(None);
+    }
+    let [a, b] = halves.as_slice() else {
+        return Err(Error::InvalidArgs(format!(
+            "[policy] xor_pipelines needs two source lists, not {}",
+            halves.len()
+        )));
+    };
+    if a.is_empty() || b.is_empty() {
+        return Err(Error::InvalidArgs(
+            "[policy] xor_pipelines has an empty source list".into(),
+        ));
+    }
+    let plugins = &plugin::load(&config.plugins).plugins;
+    for name in a.iter().chain(b) {
+        let known = STAGES.iter().any(|s| s.eq_ignore_ascii_case(name))
+            || plugins.iter().any(|p| p.name.eq_ignore_ascii_case(name));
+        if !known {
+            return Err(Error::InvalidArgs(format!(
+                "unknown source '{}' in [policy] xor_pipelines (expected one of: {}, or a plugin name)",
+                name,
+                STAGES.join(", ")
+            )));
+        }
+    }
+    if let Some(shared) = a
+        .iter()
+        .find(|n| b.iter().any(|m| m.eq_ignore_ascii_case(n)))
+    {
+        return Err(Error::InvalidArgs(format!(
+            "'{}' is in both halves of [policy] xor_pipelines",
+            shared
+        )));
+    }
+    // The fallback mixes in /dev/urandom, and the CPU RNG unless its
+    // mix-in is off, so the stages reading those can't be set against it
+    let mut fallback_reads = vec![("devrandom", "the kernel pool")];
+    if config.cpu_rng.fallback_mix_bytes > 0 {
+        fallback_reads.push(("cpu-rng", "the CPU RNG"));
+    }
+    for (this, other) in [(a, b), (b, a)] {
+        if !this.iter().any(|n| n.eq_ignore_ascii_case("fallback")) {
+            continue;
+        }
+        if let Some((stage, what)) = fallback_reads
+            .iter()
+            .find(|(stage, _)| other.iter().any(|n| n.eq_ignore_ascii_case(stage)))
+        {
+            return Err(Error::InvalidArgs(format!(
+                "'fallback' also reads {}, so it can't be in the other half of [policy] xor_pipelines from '{}'{}",
+                what,
+                stage,
+                if *stage == "cpu-rng" {
+                    " (or set [cpu_rng] fallback_mix_bytes = 0)"
+                } else {
+                    ""
+                }
+            )));
+        }
+    }
+    Ok(Some([Pipeline::Only(a), Pipeline::Only(b)]))
+}
+
+/// Fails if `[policy] xor_pipelines` is set but unusable, so a bad config
+/// stops mixrand at startup rather than on the first request.
+pub fn check_policy(config: &Config) -> Result<(), Error> {
+    xor_halves(config).map(|_| ())
+}
+
+/// Runs the chain once, or with `[policy] xor_pipelines` once per half,
+/// each restricted to its own sources, and XORs the two outputs: the
+/// result is at least as unpredictable as the stronger half.
+fn collect(
+    count: usize,
+    config: &Config,
+    deadline: Option<Deadline>,
+) -> Result<EntropyResult, Error> {
+    let Some([a, b]) = xor_halves(config)? else {
+        return collect_within(count, config, deadline, Pipeline::All);
+    };
+    let mut first = collect_within(count, config, deadline, a)?;
+    let mut second = match collect_within(count, config, deadline, b) {
+        Ok(r) => r,
+        Err(e) => {
+            cpurng::zeroize_vec(&mut first.bytes);
+            return Err(e);
+        }
+    };
+    mix_partial(&mut first.bytes, &second.bytes);
+    cpurng::zeroize_vec(&mut second.bytes);
     Ok(EntropyResult {
-        bytes,
-        source: "fallback (urandom + procfs + jitter + cpu-rng → BLAKE2b → ChaCha20)".into(),
+        bytes: first.bytes,
+        source: format!("({}) ⊕ ({})", first.source, second.source),
     })
 }
+
+/// Sources 1-8 of [`generate`] that `pipeline` allows, without the
+/// FIPS-mode DRBG.
+fn collect_within(
+    count: usize,
+    config: &Config,
+    deadline: Option<Deadline>,
+    pipeline: Pipeline,
+) -> Result<EntropyResult, Error> {
+    // Try hardware RNG first, within its daily budget
+    let mut found = Collected::new(config.policy.required_sources());
+    let mut partial = Vec::new();
+    if pipeline.allows("hwrng") {
+        match budget::reserve_hwrng(count, &config.limits) {
+            Ok(true) => {
+                let until = [
+                    deadline.map(|d| d.at),
+                    timeout_deadline(config.timeouts.hwrng_ms),
+                ]
+                .into_iter()
+                .flatten()
+                .min();
+                let read = timing::attempt("hwrng", || {
+                    sourcecache::attempt("hwrng", count, || match until {
+                        Some(at) => hwrng::read_hwrng_until(count, at),
+                        None => hwrng::read_hwrng(count),
+                    })
+                });
+                if let Ok(ref bytes) = read {
+                    metrics::input("hwrng", bytes.len(), bytes);
+                }
+                match read {
+                    Ok(bytes) if bytes.len() == count => {
+                        let result = EntropyResult {
+                            bytes,
+                            source: hwrng::label(),
+                        };
+                        if found.push("hwrng", result) {
+                            return found.finish(count);
+                        }
+                    }
+                    Ok(mut bytes) => {
+                        let what =
+                            format!("/dev/hwrng delivered {} of {} bytes", bytes.len(), count);
+                        if let Err(e) = check_deadline(deadline, &what) {
+                            cpurng::zeroize_vec(&mut bytes);
+                            return Err(e);
+                        }
+                        log::info!("{} in time; falling through", what);
+                        partial = bytes;
+                    }
+                    Err(e) => {
+                        log::debug!("hwrng unavailable: {}", e);
+                    }
+                }
+            }
+            Ok(false) => {
+                log::info!("hwrng daily budget exhausted; skipping /dev/hwrng");
+            }
+            Err(e) => {
+                log::warn!(
+                    "hwrng budget file {}: {}; skipping /dev/hwrng",
+                    config.limits.hwrng_budget_file.display(),
+                    e
+                );
+            }
+        }
+    }
+
+    let mut result = generate_after_hwrng(count, config, deadline, pipeline, found);
+    if !partial.is_empty() {
+        if let Ok(ref mut r) = result {
+            mix_partial(&mut r.bytes, &partial);
+            r.source = format!("{} + {}B from /dev/hwrng", r.source, partial.len());
+        }
+        cpurng::zeroize_vec(&mut partial);
+    }
+    result
+}
+
+/// Sources 2-8 of [`generate`], adding to what /dev/hwrng put in `found`.
+fn generate_after_hwrng(
+    count: usize,
+    config: &Config,
+    deadline: Option<Deadline>,
+    pipeline: Pipeline,
+    mut found: Collected,
+) -> Result<EntropyResult, Error> {
+    // Try a serial TRNG dongle the kernel doesn't bind to /dev/hwrng
+    if config.serial.is_enabled() && pipeline.allows("serial") {
+        match capped_timeout_ms(config.serial.timeout_ms, deadline) {
+            Some(timeout_ms) => {
+                let serial_config = SerialConfig {
+                    timeout_ms,
+                    ..config.serial.clone()
+                };
+                match timing::attempt("serial", || serial::read_serial(count, &serial_config)) {
+                    Ok(bytes) => {
+                        metrics::input("serial", bytes.len(), &bytes);
+                        let result = EntropyResult {
+                            bytes,
+                            source: format!("serial TRNG ({})", config.serial.device),
+                        };
+                        if found.push("serial", result) {
+                            return found.finish(count);
+                        }
+                    }
+                    Err(e) => {
+                        log::debug!("serial source unavailable: {}", e);
+                        check_deadline(deadline, "serial TRNG did not respond")?;
+                    }
+                }
+            }
+            None => {
+                check_deadline(deadline, "no time left for the serial TRNG")?;
+                log::debug!("serial source skipped: --max-wait expired");
+            }
+        }
+    }
+
+    // Try the site-configured external collector
+    if config.exec.is_enabled() && pipeline.allows("exec") {
+        match capped_timeout_ms(config.exec.timeout_ms, deadline) {
+            Some(timeout_ms) => {
+                let exec_config = ExecConfig {
+                    timeout_ms,
+                    ..config.exec.clone()
+                };
+                match timing::attempt("exec", || exec::read_exec(count, &exec_config)) {
+                    Ok(bytes) => {
+                        metrics::input("exec", bytes.len(), &bytes);
+                        let result = EntropyResult {
+                            bytes,
+                            source: format!("external command ({})", config.exec.command[0]),
+                        };
+                        if found.push("exec", result) {
+                            return found.finish(count);
+                        }
+                    }
+                    Err(e) => {
+                        log::debug!("exec source unavailable: {}", e);
+                        check_deadline(deadline, "external command did not finish")?;
+                    }
+                }
+            }
+            None => {
+                check_deadline(deadline, "no time left for the external command")?;
+                log::debug!("exec source skipped: --max-wait expired");
+            }
+        }
+    }
+
+    // Try the VM host
+    if config.vsock.is_enabled() && pipeline.allows("vsock") {
+        match capped_timeout_ms(config.vsock.timeout_ms, deadline) {
+            Some(timeout_ms) => {
+                let vsock_config = VsockConfig {
+                    timeout_ms,
+                    ..config.vsock.clone()
+                };
+                match timing::attempt("vsock", || vsock::read_vsock(count, &vsock_config)) {
+                    Ok(bytes) => {
+                        metrics::input("vsock", bytes.len(), &bytes);
+                        let result = EntropyResult {
+                            bytes,
+                            source: format!("VM host (vsock {})", config.vsock.port),
+                        };
+                        if found.push("vsock", result) {
+                            return found.finish(count);
+                        }
+                    }
+                    Err(e) => {
+                        log::debug!("vsock source unavailable: {}", e);
+                        check_deadline(deadline, "VM host did not respond")?;
+                    }
+                }
+            }
+            None => {
+                check_deadline(deadline, "no time left for the VM host")?;
+                log::debug!("vsock source skipped: --max-wait expired");
+            }
+        }
+    }
+
+    // Try third-party plugins
+    for p in &plugin::load(&config.plugins).plugins {
+        if !pipeline.allows(&p.name) {
+            continue;
+        }
+        let name = format!("plugin {}", p.name);
+        match timing::attempt(&name, || plugin::read_plugin(p, count, &config.plugins)) {
+            Ok(bytes) => {
+                metrics::input(&name, bytes.len(), &bytes);
+                let result = EntropyResult {
+                    bytes,
+                    source: format!("plugin {} ({})", p.name, p.path.display()),
+                };
+                if found.push(&name, result) {
+                    return found.finish(count);
+                }
+            }
+            Err(e) => {
+                log::debug!("plugin {} unavailable: {}", p.name, e);
+            }
+        }
+    }
+
+    // Try CPU hardware RNG (RDSEED/RDRAND/XSTORE) with standalone oversampling
+    if pipeline.allows("cpu-rng") {
+        let cpu_config = &config.cpu_rng;
+        let cpu_deadline = timeout_deadline(config.timeouts.cpurng_ms);
+        match timing::attempt("cpu-rng", || {
+            sourcecache::attempt("cpu-rng", count, || {
+                cpurng::collect_cpu_entropy_standalone(count, cpu_config, cpu_deadline)
+            })
+        }) {
+            Ok(result) => {
+                metrics::input("cpu-rng", result.raw_bytes, &result.bytes);
+                let ratio = result.raw_bytes / count.max(1);
+                let source = if cpu_config.adaptive_oversample {
+                    format!(
+                        "CPU hardware RNG ({}, adaptive {}x oversample)",
+                        result.source_label, ratio
+                    )
+                } else if ratio > 1 {
+                    format!(
+                        "CPU hardware RNG ({}, {}x oversample)",
+                        result.source_label, ratio
+                    )
+                } else {
+                    format!("CPU hardware RNG ({})", result.source_label)
+                };
+                let result = EntropyResult {
+                    bytes: result.bytes,
+                    source,
+                };
+                if found.push("cpu-rng", result) {
+                    return found.finish(count);
+                }
+            }
+            Err(e) => {
+                log::debug!("cpurng unavailable: {}", e);
+            }
+        }
+    }
+
+    // Try the kernel pool, if something feeds it
+    if pipeline.allows("devrandom") {
+        match timing::attempt("devrandom", || {
+            sourcecache::attempt("devrandom", count, || {
+                devrandom::read_devrandom(count, config.timeouts.devrandom_ms)
+            })
+        }) {
+            Ok((feeder, bytes)) => {
+                metrics::input("devrandom", bytes.len(), &bytes);
+                let result = EntropyResult {
+                    bytes,
+                    source: feeder.label(),
+                };
+                if found.push("devrandom", result) {
+                    return found.finish(count);
+                }
+            }
+            Err(e) => {
+                log::debug!("/dev/random unavailable: {}", e);
+            }
+        }
+    }
+
+    // Fallback
+    if !pipeline.allows("fallback") {
+        return found.finish(count);
+    }
+    let fallback_deadline = timeout_deadline(config.timeouts.fallback_ms);
+    let (bytes, mixed) = timing::attempt("fallback", || {
+        fallback::generate_fallback_mixed(count, config, fallback_deadline)
+    })?;
+    found.push_sharing(
+        "fallback",
+        &mixed.shared_stages(),
+        EntropyResult {
+            bytes,
+            source: mixed.label(),
+        },
+    );
+    found.finish(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_deadline() {
+        let past = Deadline::after(Duration::ZERO, OnTimeout::Fail);
+        assert!(matches!(
+            check_deadline(Some(past), "hwrng"),
+            Err(Error::Timeout(_))
+        ));
+        let past = Deadline::after(Duration::ZERO, OnTimeout::Fallthrough);
+        assert!(check_deadline(Some(past), "hwrng").is_ok());
+        let future = Deadline::after(Duration::from_secs(60), OnTimeout::Fail);
+        assert!(check_deadline(Some(future), "hwrng").is_ok());
+        assert!(check_deadline(None, "hwrng").is_ok());
+    }
+
+    #[test]
+    fn test_capped_timeout() {
+        assert_eq!(capped_timeout_ms(2000, None), Some(2000));
+        let d = Deadline::after(Duration::from_secs(60), OnTimeout::Fail);
+        assert_eq!(capped_timeout_ms(2000, Some(d)), Some(2000));
+        assert!(capped_timeout_ms(120_000, Some(d)).unwrap() <= 60_000);
+        let d = Deadline::after(Duration::ZERO, OnTimeout::Fail);
+        assert_eq!(capped_timeout_ms(2000, Some(d)), None);
+    }
+
+    #[test]
+    fn test_mix_partial() {
+        let mut bytes = vec![0xFFu8; 4];
+        mix_partial(&mut bytes, &[0x0F, 0xF0]);
+        assert_eq!(bytes, [0xF0, 0x0F, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_collected_policy() {
+        let result = |b: u8| EntropyResult {
+            bytes: vec![b; 16],
+            source: format!("source {}", b),
+        };
+
+        let mut one = Collected::new(1);
+        assert!(one.push("cpu-rng", result(1)));
+        assert_eq!(one.finish(16).unwrap().bytes, vec![1; 16]);
+
+        let mut short = Collected::new(2);
+        assert!(!short.push("cpu-rng", result(1)));
+        assert!(matches!(short.finish(16), Err(Error::NoEntropy(_))));
+
+        let mixed = |a: u8| {
+            let mut two = Collected::new(2);
+            two.push("cpu-rng", result(a));
+            assert!(two.push("fallback", result(2)));
+            two.finish(64).unwrap()
+        };
+        let r = mixed(1);
+        assert_eq!(r.bytes.len(), 64);
+        assert_eq!(r.bytes, mixed(1).bytes);
+        assert_ne!(r.bytes, mixed(3).bytes);
+        assert!(r.source.starts_with("source 1 + source 2"));
+
+        // The fallback doesn't count alongside a stage it reads
+        let mut shared = Collected::new(2);
+        shared.push("cpu-rng", result(1));
+        assert!(!shared.push_sharing("fallback", &["devrandom", "cpu-rng"], result(2)));
+        match shared.finish(16) {
+            Err(Error::NoEntropy(msg)) => assert!(msg.contains("fallback shares cpu-rng")),
+            other => panic!("expected NoEntropy, got {:?}", other.map(|r| r.source)),
+        }
+        let mut apart = Collected::new(2);
+        apart.push("cpu-rng", result(1));
+        assert!(apart.push_sharing("fallback", &["devrandom"], result(2)));
+    }
+
+    #[test]
+    fn test_xor_halves() {
+        let with = |halves: &[&[&str]]| {
+            let mut config = Config::default();
+            config.policy.xor_pipelines = halves
+                .iter()
+                .map(|h| h.iter().map(|s| s.to_string()).collect())
+                .collect();
+            config
+        };
+        assert!(xor_halves(&with(&[])).unwrap().is_none());
+
+        let config = with(&[&["hwrng", "Serial"], &["fallback"]]);
+        let [a, b] = xor_halves(&config).unwrap().unwrap();
+        assert!(a.allows("hwrng") && a.allows("serial") && !a.allows("fallback"));
+        assert!(b.allows("fallback") && !b.allows("cpu-rng"));
+
+        for bad in [
+            with(&[&["hwrng"]]),
+            with(&[&["hwrng"], &[]]),
+            with(&[&["hwrng"], &["fallback"], &["cpu-rng"]]),
+            with(&[&["hwrng"], &["urandom"]]),
+            with(&[&["hwrng", "cpu-rng"], &["CPU-RNG"]]),
+            // The fallback reads the CPU RNG and the kernel pool
+            with(&[&["cpu-rng"], &["fallback"]]),
+            with(&[&["hwrng", "fallback"], &["Cpu-Rng"]]),
+            with(&[&["devrandom"], &["fallback"]]),
+        ] {
+            assert!(matches!(xor_halves(&bad), Err(Error::InvalidArgs(_))));
+        }
+
+        let mut config = with(&[&["cpu-rng"], &["devrandom", "fallback"]]);
+        assert!(xor_halves(&config).is_err());
+        config.cpu_rng.fallback_mix_bytes = 0;
+        assert!(xor_halves(&config).unwrap().is_some());
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long [`collect_net_samples`] watches the counters.
+pub const WINDOW: Duration = Duration::from_millis(5);
+
+/// Pause between reads, long enough for softirqs to land in between.
+const POLL_INTERVAL: Duration = Duration::from_micros(250);
+
+/// Every counter in /proc/net/dev, interface by interface.
+fn parse_net_dev(text: &str) -> Vec<u64> {
+    text.lines()
+        .filter_map(|line| line.split_once(':'))
+        .flat_map(|(_, counters)| counters.split_whitespace())
+        .filter_map(|v| v.parse().ok())
+        .collect()
+}
+
+/// Every counter in /proc/net/softnet_stat (hex, one row per CPU).
+fn parse_softnet(text: &str) -> Vec<u64> {
+    text.split_whitespace()
+        .filter_map(|v| u64::from_str_radix(v, 16).ok())
+        .collect()
+}
+
+fn read_counters() -> Vec<u64> {
+    let mut counters = parse_net_dev(&fs::read_to_string("/proc/net/dev").unwrap_or_default());
+    counters.extend(parse_softnet(
+        &fs::read_to_string("/proc/net/softnet_stat").unwrap_or_default(),
+    ));
+    counters
+}
+
+/// Appends, for one read, its offset into the window in nanoseconds and
+/// the index and delta of every counter that moved since `prev`.
+fn record(samples: &mut Vec<u8>, elapsed_ns: u64, prev: &[u64], now: &[u64]) {
+    samples.extend_from_slice(&elapsed_ns.to_le_bytes());
+    for (i, (a, b)) in prev.iter().zip(now).enumerate() {
+        if a != b {
+            samples.extend_from_slice(&(i as u32).to_le_bytes());
+            samples.extend_from_slice(&b.wrapping_sub(*a).to_le_bytes());
+        }
+    }
+}
+
+/// Samples packet and softirq counters from /proc/net/dev and
+/// /proc/net/softnet_stat for `window`, recording when each read finished
+/// and which counters moved. Packet arrival and NAPI polling timing keep
+/// this moving on headless VMs whose disks are idle. Raw, unconditioned
+/// bytes for the mixer; empty if neither file is readable.
+pub fn collect_net_samples(window: Duration) -> Vec<u8> {
+    let start = Instant::now();
+    let mut prev = read_counters();
+    if prev.is_empty() {
+        return Vec::new();
+    }
+    let mut samples = Vec::new();
+    while start.elapsed() < window {
+        thread::sleep(POLL_INTERVAL);
+        let now = read_counters();
+        record(&mut samples, start.elapsed().as_nanos() as u64, &prev, &now);
+        prev = now;
+    }
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_counters() {
+        let dev = "Inter-|   Receive |  Transmit\n \
+                   face |bytes packets|bytes packets\n    \
+                   lo: 2679 20 2679 20\n  eth0:  100   3  40   1\n";
+        assert_eq!(parse_net_dev(dev), [2679, 20, 2679, 20, 100, 3, 40, 1]);
+        assert_eq!(
+            parse_softnet("00006afd 00000000 0000001f\n00000010 00000000 00000000\n"),
+            [0x6afd, 0, 0x1f, 0x10, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_record_only_moved_counters() {
+        let mut samples = Vec::new();
+        record(&mut samples, 7, &[1, 5, 9], &[1, 8, 9]);
+        let mut expected = 7u64.to_le_bytes().to_vec();
+        expected.extend_from_slice(&1u32.to_le_bytes());
+        expected.extend_from_slice(&3u64.to_le_bytes());
+        assert_eq!(samples, expected);
+    }
+}
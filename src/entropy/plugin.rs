@@ -0,0 +1,399 @@
+//! Third-party entropy sources loaded from shared objects.
+//!
+//! Each `*.so` in `[plugins] dir` must export `mixrand_plugin_v1`, returning
+//! a pointer to a static descriptor (see `include/mixrand_plugin.h`):
+//!
+//! ```text
+//! struct mixrand_plugin {
+//!     uint32_t abi_version;                       /* MIXRAND_PLUGIN_ABI */
+//!     const char *name;                           /* e.g. "tpm" */
+//!     const char *description;
+//!     double min_entropy;                         /* bits per raw byte */
+//!     int (*init)(void);                          /* 0 on success */
+//!     long (*collect)(uint8_t *buf, size_t len);  /* bytes written, < 0 on error */
+//!     void (*teardown)(void);                     /* optional */
+//! };
+//! ```
+//!
+//! Raw plugin output is health-tested and conditioned like the exec source.
+//! Calls into one plugin are serialized.
+
+use std::ffi::{c_char, c_int, c_long, c_void, CStr, CString};
+use std::fs::{self, File};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::config::PluginsConfig;
+use crate::csprng;
+use crate::error::Error;
+use crate::mixer;
+
+use super::cpurng;
+use super::health::HealthTests;
+
+/// Descriptor layout version this build understands.
+pub const PLUGIN_ABI: u32 = 1;
+
+/// Symbol every plugin exports.
+const ENTRY_SYMBOL: &str = "mixrand_plugin_v1";
+
+/// `collect` calls allowed to fill one request before giving up.
+const MAX_COLLECT_CALLS: usize = 64;
+
+#[repr(C)]
+struct Descriptor {
+    abi_version: u32,
+    name: *const c_char,
+    description: *const c_char,
+    min_entropy: f64,
+    init: Option<unsafe extern "C" fn() -> c_int>,
+    collect: Option<unsafe extern "C" fn(*mut u8, usize) -> c_long>,
+    teardown: Option<unsafe extern "C" fn()>,
+}
+
+/// A loaded and initialized plugin. The library stays loaded for the life
+/// of the process.
+pub struct Plugin {
+    pub name: String,
+    pub description: String,
+    pub path: PathBuf,
+    pub min_entropy: f64,
+    collect: unsafe extern "C" fn(*mut u8, usize) -> c_long,
+    teardown: Option<unsafe extern "C" fn()>,
+    lock: Mutex<()>,
+    handle: *mut c_void,
+    /// The library was loaded as `/proc/self/fd/N`, and the loader matches
+    /// later loads by that name, so `N` must not be reused while it is
+    /// loaded.
+    _file: File,
+}
+
+// The function pointers refer to code in a library that is never unloaded
+// while they are reachable, and `lock` serializes every call into it.
+unsafe impl Send for Plugin {}
+unsafe impl Sync for Plugin {}
+
+/// Plugins loaded from the plugin directory, plus the files that failed.
+#[derive(Default)]
+pub struct Registry {
+    pub plugins: Vec<Plugin>,
+    pub failures: Vec<(PathBuf, String)>,
+}
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+/// Why a plugin file or directory with `uid` and `mode` must not be trusted:
+/// it has to be owned by root or the current user, and not writable by
+/// anyone else.
+fn untrusted(uid: u32, mode: u32, euid: u32) -> Option<&'static str> {
+    if uid != 0 && uid != euid {
+        Some("not owned by root or the current user")
+    } else if mode & 0o022 != 0 {
+        Some("writable by group or others")
+    } else {
+        None
+    }
+}
+
+fn check_trusted(meta: &fs::Metadata) -> Result<(), String> {
+    let euid = unsafe { libc::geteuid() };
+    match untrusted(meta.uid(), meta.mode(), euid) {
+        Some(why) => Err(why.into()),
+        None => Ok(()),
+    }
+}
+
+/// Plugin names share the `--sources` namespace with built-in sources.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn dl_error() -> String {
+    let msg = unsafe { libc::dlerror() };
+    if msg.is_null() {
+        "unknown dlopen error".into()
+    } else {
+        unsafe { CStr::from_ptr(msg) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+unsafe fn c_string(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(ptr).to_string_lossy().into_owned()
+    }
+}
+
+fn open_plugin(path: &Path) -> Result<Plugin, String> {
+    // Check and load the same open file, so it can't be swapped for
+    // another between the two
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let meta = file.metadata().map_err(|e| e.to_string())?;
+    if !meta.is_file() {
+        return Err("not a regular file".into());
+    }
+    check_trusted(&meta)?;
+    let fd_path = CString::new(format!("/proc/self/fd/{}", file.as_raw_fd())).unwrap();
+    let handle = unsafe { libc::dlopen(fd_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+    if handle.is_null() {
+        return Err(dl_error());
+    }
+
+    let close = |why: String| {
+        unsafe { libc::dlclose(handle) };
+        Err(why)
+    };
+    let symbol = CString::new(ENTRY_SYMBOL).unwrap();
+    let entry = unsafe { libc::dlsym(handle, symbol.as_ptr()) };
+    if entry.is_null() {
+        return close(format!("no {} symbol", ENTRY_SYMBOL));
+    }
+    let entry: unsafe extern "C" fn() -> *const Descriptor =
+        unsafe { std::mem::transmute::<*mut c_void, _>(entry) };
+    let desc = unsafe { entry() };
+    if desc.is_null() {
+        return close(format!("{} returned NULL", ENTRY_SYMBOL));
+    }
+    let desc = unsafe { &*desc };
+
+    if desc.abi_version != PLUGIN_ABI {
+        return close(format!(
+            "plugin ABI {} (expected {})",
+            desc.abi_version, PLUGIN_ABI
+        ));
+    }
+    let name = unsafe { c_string(desc.name) };
+    if !valid_name(&name) {
+        return close(format!("invalid plugin name '{}'", name));
+    }
+    let Some(collect) = desc.collect else {
+        return close("no collect function".into());
+    };
+    if !(desc.min_entropy > 0.0 && desc.min_entropy <= 8.0) {
+        return close(format!("min_entropy {} outside (0, 8]", desc.min_entropy));
+    }
+    if let Some(init) = desc.init {
+        let rc = unsafe { init() };
+        if rc != 0 {
+            return close(format!("init failed ({})", rc));
+        }
+    }
+
+    Ok(Plugin {
+        name,
+        description: unsafe { c_string(desc.description) },
+        path: path.to_path_buf(),
+        min_entropy: desc.min_entropy,
+        collect,
+        teardown: desc.teardown,
+        lock: Mutex::new(()),
+        handle,
+        _file: file,
+    })
+}
+
+fn scan(config: &PluginsConfig) -> Registry {
+    let mut registry = Registry::default();
+    if !config.is_enabled() {
+        return registry;
+    }
+    let entries = match fs::read_dir(&config.dir) {
+        Ok(e) => e,
+        Err(e) => {
+            log::debug!("plugin dir {}: {}", config.dir.display(), e);
+            return registry;
+        }
+    };
+    if let Err(why) = fs::metadata(&config.dir)
+        .map_err(|e| e.to_string())
+        .and_then(|meta| check_trusted(&meta))
+    {
+        registry.failures.push((config.dir.clone(), why));
+        return registry;
+    }
+
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "so"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        match open_plugin(&path) {
+            Ok(p) if registry.plugins.iter().any(|q| q.name == p.name) => {
+                let why = format!("duplicate plugin name '{}'", p.name);
+                p.unload();
+                registry.failures.push((path, why));
+            }
+            Ok(p) => {
+                log::debug!("loaded plugin {} from {}", p.name, path.display());
+                registry.plugins.push(p);
+            }
+            Err(why) => {
+                log::warn!("plugin {}: {}", path.display(), why);
+                registry.failures.push((path, why));
+            }
+        }
+    }
+    registry
+}
+
+extern "C" fn teardown_all() {
+    if let Some(registry) = REGISTRY.get() {
+        for p in &registry.plugins {
+            p.teardown();
+        }
+    }
+}
+
+/// Loads the plugin directory on first call; later calls return the same
+/// registry. Plugins are torn down at process exit.
+pub fn load(config: &PluginsConfig) -> &'static Registry {
+    REGISTRY.get_or_init(|| {
+        let registry = scan(config);
+        if !registry.plugins.is_empty() {
+            unsafe { libc::atexit(teardown_all) };
+        }
+        registry
+    })
+}
+
+/// Plugins loaded so far (empty before the first [`load`]).
+pub fn loaded() -> &'static [Plugin] {
+    REGISTRY.get().map_or(&[], |r| &r.plugins)
+}
+
+impl Plugin {
+    fn teardown(&self) {
+        if let Some(teardown) = self.teardown {
+            let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+            unsafe { teardown() };
+        }
+    }
+
+    /// Tears down a plugin that won't be used and unloads its library
+    /// before its file is closed.
+    fn unload(self) {
+        self.teardown();
+        unsafe { libc::dlclose(self.handle) };
+    }
+
+    /// Fills `buf` from `collect`, stopping early if the plugin returns 0.
+    fn collect_raw(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut filled = 0;
+        for _ in 0..MAX_COLLECT_CALLS {
+            if filled == buf.len() {
+                break;
+            }
+            let want = buf.len() - filled;
+            let rc = unsafe { (self.collect)(buf[filled..].as_mut_ptr(), want) };
+            if rc < 0 {
+                return Err(Error::NoEntropy(format!(
+                    "plugin {} collect failed ({})",
+                    self.name, rc
+                )));
+            }
+            if rc as usize > want {
+                return Err(Error::NoEntropy(format!(
+                    "plugin {} reported {} bytes for a {}-byte buffer",
+                    self.name, rc, want
+                )));
+            }
+            if rc == 0 {
+                break;
+            }
+            filled += rc as usize;
+        }
+        Ok(filled)
+    }
+}
+
+/// Plugin source: collects at least `[plugins] min_bytes` raw bytes,
+/// health-tests them against the plugin's claimed min-entropy, and
+/// conditions them through BLAKE2b-256 → ChaCha20 to produce `count` bytes.
+pub fn read_plugin(
+    plugin: &Plugin,
+    count: usize,
+    config: &PluginsConfig,
+) -> Result<Vec<u8>, Error> {
+    let mut raw = vec![0u8; count.max(config.min_bytes)];
+    let got = match plugin.collect_raw(&mut raw) {
+        Ok(n) => n,
+        Err(e) => {
+            cpurng::zeroize_vec(&mut raw);
+            return Err(e);
+        }
+    };
+    if got < raw.len() {
+        cpurng::zeroize_vec(&mut raw);
+        return Err(Error::NoEntropy(format!(
+            "plugin {} produced {} of {} bytes",
+            plugin.name,
+            got,
+            count.max(config.min_bytes)
+        )));
+    }
+
+    if let Err(e) = HealthTests::new(plugin.min_entropy).check(&raw) {
+        cpurng::zeroize_vec(&mut raw);
+        return Err(e);
+    }
+
+    let mut seed = mixer::mix_entropy(&[("plugin-name", plugin.name.as_bytes()), ("plugin", &raw)]);
+    let output = csprng::generate(seed, count);
+
+    cpurng::zeroize_vec(&mut raw);
+    cpurng::zeroize_bytes(&mut seed);
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_untrusted() {
+        assert_eq!(untrusted(0, 0o100755, 1000), None);
+        assert_eq!(untrusted(1000, 0o100644, 1000), None);
+        assert!(untrusted(1001, 0o100755, 1000).is_some());
+        assert!(untrusted(0, 0o100775, 0).is_some());
+        assert!(untrusted(0, 0o40757, 0).is_some());
+    }
+
+    #[test]
+    fn test_valid_name() {
+        assert!(valid_name("tpm"));
+        assert!(valid_name("vendor-qrng_2"));
+        assert!(!valid_name(""));
+        assert!(!valid_name("a b"));
+        assert!(!valid_name(&"x".repeat(33)));
+    }
+
+    #[test]
+    fn test_disabled_by_default() {
+        assert!(!PluginsConfig::default().is_enabled());
+    }
+
+    #[test]
+    fn test_missing_dir_loads_nothing() {
+        let config = PluginsConfig {
+            enabled: true,
+            dir: PathBuf::from("/nonexistent/mixrand-plugins"),
+            ..Default::default()
+        };
+        let registry = scan(&config);
+        assert!(registry.plugins.is_empty() && registry.failures.is_empty());
+    }
+}
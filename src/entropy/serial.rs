@@ -0,0 +1,321 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, Instant};
+
+use crate::config::{SerialConfig, SerialFraming};
+use crate::csprng;
+use crate::error::Error;
+use crate::mixer;
+use crate::signals;
+
+use super::cpurng;
+use super::health::HealthTests;
+
+/// Fewest raw bytes read per request, so health tests see a useful sample.
+const MIN_RAW_BYTES: usize = 64;
+/// Upper bound on raw bytes read per request.
+const MAX_RAW_BYTES: usize = 1 << 20;
+
+/// OneRNG: avalanche noise with the on-board whitener, then enable output.
+const ONERNG_START: &[u8] = b"cmd0\ncmdO\n";
+/// OneRNG: disable output and flush the device's buffer.
+const ONERNG_STOP: &[u8] = b"cmdo\ncmdw\n";
+
+fn baud_constant(baud: u32) -> Option<libc::speed_t> {
+    Some(match baud {
+        9600 => libc::B9600,
+        19_200 => libc::B19200,
+        38_400 => libc::B38400,
+        57_600 => libc::B57600,
+        115_200 => libc::B115200,
+        230_400 => libc::B230400,
+        460_800 => libc::B460800,
+        921_600 => libc::B921600,
+        1_000_000 => libc::B1000000,
+        2_000_000 => libc::B2000000,
+        3_000_000 => libc::B3000000,
+        4_000_000 => libc::B4000000,
+        _ => return None,
+    })
+}
+
+/// Raw bytes to read for `count` output bytes at `min_entropy` bits/byte.
+fn raw_bytes_needed(count: usize, min_entropy: f64) -> usize {
+    let needed = ((count * 8) as f64 / min_entropy).ceil() as usize;
+    needed.clamp(MIN_RAW_BYTES, MAX_RAW_BYTES)
+}
+
+/// Puts the tty into raw 8N1 mode at `baud` and discards stale input.
+fn configure_tty(fd: libc::c_int, baud: u32) -> Result<(), Error> {
+    let speed = baud_constant(baud)
+        .ok_or_else(|| Error::InvalidArgs(format!("unsupported baud rate {}", baud)))?;
+    unsafe {
+        let mut tio: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut tio) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        libc::cfmakeraw(&mut tio);
+        tio.c_cflag |= libc::CLOCAL | libc::CREAD;
+        tio.c_cc[libc::VMIN] = 0;
+        tio.c_cc[libc::VTIME] = 0;
+        libc::cfsetispeed(&mut tio, speed);
+        libc::cfsetospeed(&mut tio, speed);
+        if libc::tcsetattr(fd, libc::TCSANOW, &tio) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        libc::tcflush(fd, libc::TCIFLUSH);
+    }
+    Ok(())
+}
+
+/// Incremental ASCII-hex decoder; whitespace is skipped and a nibble may
+/// straddle two reads.
+#[derive(Default)]
+struct HexDecoder {
+    high: Option<u8>,
+}
+
+impl HexDecoder {
+    fn push(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<(), Error> {
+        for &c in input {
+            let nibble = match c {
+                b'0'..=b'9' => c - b'0',
+                b'a'..=b'f' => c - b'a' + 10,
+                b'A'..=b'F' => c - b'A' + 10,
+                c if c.is_ascii_whitespace() => continue,
+                c => {
+                    return Err(Error::NoEntropy(format!(
+                        "unexpected byte 0x{:02x} in hex stream",
+                        c
+                    )))
+                }
+            };
+            match self.high.take() {
+                Some(high) => out.push(high << 4 | nibble),
+                None => self.high = Some(nibble),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads until `raw` holds `needed` decoded bytes or the timeout expires.
+fn read_frames(
+    port: &mut File,
+    framing: SerialFraming,
+    needed: usize,
+    timeout: Duration,
+    raw: &mut Vec<u8>,
+) -> Result<(), Error> {
+    let fd = port.as_raw_fd();
+    let deadline = Instant::now() + timeout;
+    let mut hex = HexDecoder::default();
+    let mut chunk = [0u8; 4096];
+
+    let outcome = loop {
+        if raw.len() >= needed {
+            break Ok(());
+        }
+        let remaining_ms = deadline
+            .saturating_duration_since(Instant::now())
+            .as_millis() as i32;
+        if remaining_ms <= 0 {
+            break Err(Error::NoEntropy(format!(
+                "serial device timed out after {}ms ({} of {} bytes)",
+                timeout.as_millis(),
+                raw.len(),
+                needed
+            )));
+        }
+
+        match signals::wait_readable(fd, Some(Duration::from_millis(remaining_ms as u64))) {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => break Err(e.into()),
+        }
+
+        let want = match framing {
+            SerialFraming::Hex => chunk.len(),
+            SerialFraming::Raw | SerialFraming::Onerng => chunk.len().min(needed - raw.len()),
+        };
+        match port.read(&mut chunk[..want]) {
+            Ok(0) => break Err(Error::NoEntropy("serial device hung up".into())),
+            Ok(n) => match framing {
+                SerialFraming::Hex => {
+                    if let Err(e) = hex.push(&chunk[..n], raw) {
+                        break Err(e);
+                    }
+                }
+                SerialFraming::Raw | SerialFraming::Onerng => {
+                    raw.extend_from_slice(&chunk[..n]);
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => break Err(e.into()),
+        }
+    };
+    cpurng::zeroize_bytes(&mut chunk);
+    raw.truncate(needed);
+    outcome
+}
+
+/// Serial/USB TRNG entropy source (TrueRNG, OneRNG, ...): opens the
+/// configured tty in raw mode, strips the device's framing, health-tests the
+/// raw stream, and conditions it through BLAKE2b-256 → ChaCha20.
+pub fn read_serial(count: usize, config: &SerialConfig) -> Result<Vec<u8>, Error> {
+    if !config.is_enabled() {
+        return Err(Error::NoEntropy("serial source not configured".into()));
+    }
+
+    let mut port = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(&config.device)
+        .map_err(|e| Error::NoEntropy(format!("{} not available: {}", config.device, e)))?;
+    configure_tty(port.as_raw_fd(), config.baud)?;
+
+    if config.framing == SerialFraming::Onerng {
+        port.write_all(ONERNG_START)?;
+    }
+
+    let needed = raw_bytes_needed(count, config.min_entropy);
+    let mut raw = Vec::with_capacity(needed);
+    let result = read_frames(
+        &mut port,
+        config.framing,
+        needed,
+        Duration::from_millis(config.timeout_ms),
+        &mut raw,
+    );
+
+    if config.framing == SerialFraming::Onerng {
+        let _ = port.write_all(ONERNG_STOP);
+    }
+
+    if let Err(e) = result.and_then(|_| HealthTests::new(config.min_entropy).check(&raw)) {
+        cpurng::zeroize_vec(&mut raw);
+        return Err(e);
+    }
+
+    let mut seed = mixer::mix_entropy(&[("serial", &raw)]);
+    let output = csprng::generate(seed, count);
+
+    cpurng::zeroize_vec(&mut raw);
+    cpurng::zeroize_bytes(&mut seed);
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::io::FromRawFd;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    /// Opens a pseudo-terminal pair, returning the master and the slave path.
+    fn open_pty() -> (File, String) {
+        unsafe {
+            let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(fd >= 0);
+            assert_eq!(libc::grantpt(fd), 0);
+            assert_eq!(libc::unlockpt(fd), 0);
+            let name = std::ffi::CStr::from_ptr(libc::ptsname(fd));
+            (File::from_raw_fd(fd), name.to_string_lossy().into_owned())
+        }
+    }
+
+    fn config(device: &str, framing: SerialFraming) -> SerialConfig {
+        SerialConfig {
+            device: device.into(),
+            framing,
+            ..Default::default()
+        }
+    }
+
+    /// Writes chunks from `pattern` into the pty master until stopped.
+    fn feed(master: File, pattern: fn() -> Vec<u8>) -> Arc<AtomicBool> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let s = Arc::clone(&stop);
+        std::thread::spawn(move || {
+            let mut master = master;
+            while !s.load(Ordering::Relaxed) {
+                if master.write_all(&pattern()).is_err() {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(5));
+            }
+        });
+        stop
+    }
+
+    fn urandom_chunk() -> Vec<u8> {
+        let mut buf = vec![0u8; 256];
+        File::open("/dev/urandom")
+            .unwrap()
+            .read_exact(&mut buf)
+            .unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_baud_constant() {
+        assert_eq!(baud_constant(115_200), Some(libc::B115200));
+        assert_eq!(baud_constant(12_345), None);
+    }
+
+    #[test]
+    fn test_raw_bytes_needed() {
+        assert_eq!(raw_bytes_needed(32, 8.0), 64);
+        assert_eq!(raw_bytes_needed(256, 4.0), 512);
+        assert_eq!(raw_bytes_needed(1 << 20, 1.0), MAX_RAW_BYTES);
+    }
+
+    #[test]
+    fn test_hex_decoder_across_chunks() {
+        let mut dec = HexDecoder::default();
+        let mut out = Vec::new();
+        dec.push(b"de a", &mut out).unwrap();
+        dec.push(b"D\r\nbE", &mut out).unwrap();
+        assert_eq!(out, vec![0xde, 0xad, 0xbe]);
+        assert!(dec.push(b"zz", &mut out).is_err());
+    }
+
+    #[test]
+    fn test_read_serial_raw_pty() {
+        let (master, slave) = open_pty();
+        let stop = feed(master, urandom_chunk);
+        let out = read_serial(128, &config(&slave, SerialFraming::Raw));
+        stop.store(true, Ordering::Relaxed);
+        assert_eq!(out.unwrap().len(), 128);
+    }
+
+    #[test]
+    fn test_read_serial_rejects_stuck_device() {
+        let (master, slave) = open_pty();
+        let stop = feed(master, || vec![0x55; 256]);
+        let msg = format!(
+            "{}",
+            read_serial(128, &config(&slave, SerialFraming::Raw)).unwrap_err()
+        );
+        stop.store(true, Ordering::Relaxed);
+        assert!(msg.contains("repetition count"));
+    }
+
+    #[test]
+    fn test_read_serial_timeout() {
+        let (_master, slave) = open_pty();
+        let mut cfg = config(&slave, SerialFraming::Raw);
+        cfg.timeout_ms = 50;
+        let msg = format!("{}", read_serial(32, &cfg).unwrap_err());
+        assert!(msg.contains("timed out"));
+    }
+
+    #[test]
+    fn test_read_serial_disabled() {
+        assert!(read_serial(32, &SerialConfig::default()).is_err());
+    }
+}
@@ -0,0 +1,58 @@
+use std::fs;
+
+/// DMI attributes under /sys/class/dmi/id that tend to differ between
+/// otherwise identical machines. Serials and the UUID are root-only.
+const DMI_FIELDS: [&str; 5] = [
+    "product_uuid",
+    "product_serial",
+    "board_serial",
+    "chassis_serial",
+    "bios_date",
+];
+
+/// Reads raw bytes from /etc/machine-id.
+pub fn read_machine_id() -> Vec<u8> {
+    fs::read("/etc/machine-id").unwrap_or_default()
+}
+
+/// Reads raw bytes from /proc/sys/kernel/random/boot_id (fresh each boot).
+pub fn read_boot_id() -> Vec<u8> {
+    fs::read("/proc/sys/kernel/random/boot_id").unwrap_or_default()
+}
+
+/// Reads whichever DMI identifiers are accessible, as `name=value` lines.
+pub fn read_dmi_ids() -> Vec<u8> {
+    let mut out = Vec::new();
+    for field in DMI_FIELDS {
+        if let Ok(value) = fs::read(format!("/sys/class/dmi/id/{}", field)) {
+            out.extend_from_slice(field.as_bytes());
+            out.push(b'=');
+            out.extend_from_slice(&value);
+        }
+    }
+    out
+}
+
+/// Reads the MAC address of every non-loopback interface, sorted by name,
+/// as `iface=address` lines.
+pub fn read_mac_addresses() -> Vec<u8> {
+    let mut names: Vec<String> = match fs::read_dir("/sys/class/net") {
+        Ok(dir) => dir
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .filter(|n| n != "lo")
+            .collect(),
+        Err(_) => return Vec::new(),
+    };
+    names.sort();
+
+    let mut out = Vec::new();
+    for name in names {
+        if let Ok(addr) = fs::read(format!("/sys/class/net/{}/address", name)) {
+            out.extend_from_slice(name.as_bytes());
+            out.push(b'=');
+            out.extend_from_slice(&addr);
+        }
+    }
+    out
+}
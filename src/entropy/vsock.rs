@@ -0,0 +1,320 @@
+//! AF_VSOCK transport between `serve-vsock` on a VM host and the `vsock`
+//! source in its guests.
+//!
+//! A request is `MXRV`, a version byte and a big-endian u32 byte count. A
+//! response is a status byte, a big-endian u32 length and that many bytes:
+//! entropy on success, otherwise a UTF-8 error message. A connection may
+//! carry any number of requests.
+
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::time::{Duration, Instant};
+
+use crate::config::VsockConfig;
+use crate::error::Error;
+use crate::signals;
+
+const MAGIC: &[u8; 4] = b"MXRV";
+const VERSION: u8 = 1;
+const REQUEST_LEN: usize = 9;
+const STATUS_OK: u8 = 0;
+const STATUS_ERROR: u8 = 1;
+
+/// Largest payload either side sends or accepts in one frame.
+pub const MAX_FRAME_BYTES: usize = 1 << 20;
+
+pub fn write_request<W: Write>(w: &mut W, count: usize) -> Result<(), Error> {
+    if count == 0 || count > MAX_FRAME_BYTES {
+        return Err(Error::InvalidArgs(format!(
+            "vsock request must be between 1 and {} bytes",
+            MAX_FRAME_BYTES
+        )));
+    }
+    let mut frame = [0u8; REQUEST_LEN];
+    frame[..4].copy_from_slice(MAGIC);
+    frame[4] = VERSION;
+    frame[5..].copy_from_slice(&(count as u32).to_be_bytes());
+    w.write_all(&frame)?;
+    Ok(())
+}
+
+/// Reads the next request's byte count, or `None` if the peer closed the
+/// connection between requests.
+pub fn read_request<R: Read>(r: &mut R) -> Result<Option<usize>, Error> {
+    let mut frame = [0u8; REQUEST_LEN];
+    let mut got = 0;
+    while got < REQUEST_LEN {
+        match r.read(&mut frame[got..]) {
+            Ok(0) if got == 0 => return Ok(None),
+            Ok(0) => return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into()),
+            Ok(n) => got += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    if &frame[..4] != MAGIC {
+        return Err(Error::InvalidArgs("not a mixrand vsock request".into()));
+    }
+    if frame[4] != VERSION {
+        return Err(Error::InvalidArgs(format!(
+            "unsupported vsock protocol version {}",
+            frame[4]
+        )));
+    }
+    let count = u32::from_be_bytes([frame[5], frame[6], frame[7], frame[8]]) as usize;
+    if count == 0 || count > MAX_FRAME_BYTES {
+        return Err(Error::InvalidArgs(format!(
+            "vsock request must be between 1 and {} bytes",
+            MAX_FRAME_BYTES
+        )));
+    }
+    Ok(Some(count))
+}
+
+/// Sends entropy, or the error message if generation failed.
+pub fn write_response<W: Write>(w: &mut W, result: Result<&[u8], &str>) -> Result<(), Error> {
+    let (status, payload) = match result {
+        Ok(bytes) => (STATUS_OK, bytes),
+        Err(msg) => (STATUS_ERROR, msg.as_bytes()),
+    };
+    let len = payload.len().min(MAX_FRAME_BYTES);
+    let mut head = [status, 0, 0, 0, 0];
+    head[1..].copy_from_slice(&(len as u32).to_be_bytes());
+    w.write_all(&head)?;
+    w.write_all(&payload[..len])?;
+    w.flush()?;
+    Ok(())
+}
+
+/// Reads a response, turning an error frame into [`Error::NoEntropy`].
+pub fn read_response<R: Read>(r: &mut R) -> Result<Vec<u8>, Error> {
+    let mut head = [0u8; 5];
+    r.read_exact(&mut head)?;
+    let len = u32::from_be_bytes([head[1], head[2], head[3], head[4]]) as usize;
+    if len > MAX_FRAME_BYTES {
+        return Err(Error::NoEntropy(format!(
+            "vsock response of {} bytes exceeds the protocol limit",
+            len
+        )));
+    }
+    let mut payload = vec![0u8; len];
+    r.read_exact(&mut payload)?;
+    match head[0] {
+        STATUS_OK => Ok(payload),
+        STATUS_ERROR => Err(Error::NoEntropy(format!(
+            "vsock host: {}",
+            String::from_utf8_lossy(&payload)
+        ))),
+        s => Err(Error::NoEntropy(format!(
+            "unknown vsock response status {}",
+            s
+        ))),
+    }
+}
+
+fn sockaddr(cid: u32, port: u32) -> libc::sockaddr_vm {
+    let mut addr: libc::sockaddr_vm = unsafe { std::mem::zeroed() };
+    addr.svm_family = libc::AF_VSOCK as libc::sa_family_t;
+    addr.svm_cid = cid;
+    addr.svm_port = port;
+    addr
+}
+
+fn socket() -> Result<OwnedFd, Error> {
+    let fd = unsafe { libc::socket(libc::AF_VSOCK, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Sets SO_RCVTIMEO and SO_SNDTIMEO on a socket.
+pub fn set_timeouts(fd: RawFd, timeout: Duration) -> Result<(), Error> {
+    let tv = libc::timeval {
+        tv_sec: timeout.as_secs() as libc::time_t,
+        tv_usec: timeout.subsec_micros() as libc::suseconds_t,
+    };
+    for opt in [libc::SO_RCVTIMEO, libc::SO_SNDTIMEO] {
+        let ret = unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                opt,
+                &tv as *const libc::timeval as *const libc::c_void,
+                std::mem::size_of::<libc::timeval>() as libc::socklen_t,
+            )
+        };
+        if ret != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(())
+}
+
+/// Binds and listens on `port` for connections from `cid`
+/// (`VMADDR_CID_ANY` for every guest).
+pub fn listen(cid: u32, port: u32, backlog: i32) -> Result<OwnedFd, Error> {
+    let fd = socket()?;
+    let addr = sockaddr(cid, port);
+    let ret = unsafe {
+        libc::bind(
+            fd.as_raw_fd(),
+            &addr as *const libc::sockaddr_vm as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t,
+        )
+    };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    if unsafe { libc::listen(fd.as_raw_fd(), backlog) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(fd)
+}
+
+/// Accepts a connection, returning it with the peer's context ID.
+pub fn accept(listener: &OwnedFd) -> Result<(File, u32), Error> {
+    let mut addr = sockaddr(0, 0);
+    let mut len = std::mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t;
+    let fd = unsafe {
+        libc::accept4(
+            listener.as_raw_fd(),
+            &mut addr as *mut libc::sockaddr_vm as *mut libc::sockaddr,
+            &mut len,
+            libc::SOCK_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok((unsafe { File::from_raw_fd(fd) }, addr.svm_cid))
+}
+
+/// Connects to `cid:port`, giving up after `timeout`.
+pub fn connect(cid: u32, port: u32, timeout: Duration) -> Result<File, Error> {
+    let fd = socket()?;
+    let raw = fd.as_raw_fd();
+    let addr = sockaddr(cid, port);
+    unsafe {
+        let flags = libc::fcntl(raw, libc::F_GETFL);
+        libc::fcntl(raw, libc::F_SETFL, flags | libc::O_NONBLOCK);
+        let ret = libc::connect(
+            raw,
+            &addr as *const libc::sockaddr_vm as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t,
+        );
+        if ret != 0 {
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::EINPROGRESS) {
+                return Err(err.into());
+            }
+            let mut pfd = libc::pollfd {
+                fd: raw,
+                events: libc::POLLOUT,
+                revents: 0,
+            };
+            let deadline = Instant::now() + timeout;
+            loop {
+                let left = deadline.saturating_duration_since(Instant::now());
+                let ms = left.as_millis().min(i32::MAX as u128) as i32;
+                match libc::poll(&mut pfd, 1, ms) {
+                    0 => return Err(std::io::Error::from(ErrorKind::TimedOut).into()),
+                    n if n < 0 => {
+                        let err = std::io::Error::last_os_error();
+                        if err.kind() != ErrorKind::Interrupted || signals::shutdown_requested() {
+                            return Err(err.into());
+                        }
+                    }
+                    _ => break,
+                }
+            }
+            let mut so_error: libc::c_int = 0;
+            let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+            libc::getsockopt(
+                raw,
+                libc::SOL_SOCKET,
+                libc::SO_ERROR,
+                &mut so_error as *mut libc::c_int as *mut libc::c_void,
+                &mut len,
+            );
+            if so_error != 0 {
+                return Err(std::io::Error::from_raw_os_error(so_error).into());
+            }
+        }
+        libc::fcntl(raw, libc::F_SETFL, flags);
+    }
+    set_timeouts(raw, timeout)?;
+    Ok(File::from(fd))
+}
+
+/// Requests `count` bytes from the host's `serve-vsock`.
+pub fn read_vsock(count: usize, config: &VsockConfig) -> Result<Vec<u8>, Error> {
+    if !config.is_enabled() {
+        return Err(Error::NoEntropy("vsock source not configured".into()));
+    }
+    let timeout = Duration::from_millis(config.timeout_ms);
+    let mut conn = connect(config.cid, config.port, timeout).map_err(|e| {
+        Error::NoEntropy(format!(
+            "vsock {}:{} not available: {}",
+            config.cid, config.port, e
+        ))
+    })?;
+    let mut out = Vec::with_capacity(count);
+    while out.len() < count {
+        let n = (count - out.len()).min(MAX_FRAME_BYTES);
+        write_request(&mut conn, n)?;
+        let mut chunk = read_response(&mut conn)?;
+        if chunk.len() != n {
+            super::cpurng::zeroize_vec(&mut chunk);
+            super::cpurng::zeroize_vec(&mut out);
+            return Err(Error::NoEntropy(format!(
+                "vsock host sent {} of {} bytes",
+                chunk.len(),
+                n
+            )));
+        }
+        out.extend_from_slice(&chunk);
+        super::cpurng::zeroize_vec(&mut chunk);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_request_round_trip() {
+        let mut buf = Vec::new();
+        write_request(&mut buf, 4096).unwrap();
+        assert_eq!(&buf[..5], b"MXRV\x01");
+        let mut r = Cursor::new(buf);
+        assert_eq!(read_request(&mut r).unwrap(), Some(4096));
+        assert_eq!(read_request(&mut r).unwrap(), None);
+        assert!(write_request(&mut Vec::new(), 0).is_err());
+        assert!(write_request(&mut Vec::new(), MAX_FRAME_BYTES + 1).is_err());
+    }
+
+    #[test]
+    fn test_bad_requests_rejected() {
+        assert!(read_request(&mut Cursor::new(b"GET / HTTP/1.1")).is_err());
+        assert!(read_request(&mut Cursor::new(b"MXRV\x02\0\0\0\x10")).is_err());
+        assert!(read_request(&mut Cursor::new(b"MXRV\x01\0\0\0\0")).is_err());
+        assert!(read_request(&mut Cursor::new(b"MXRV\x01\0")).is_err());
+    }
+
+    #[test]
+    fn test_response_round_trip() {
+        let mut buf = Vec::new();
+        write_response(&mut buf, Ok(&[7u8; 32])).unwrap();
+        write_response(&mut buf, Err("no entropy source")).unwrap();
+        let mut r = Cursor::new(buf);
+        assert_eq!(read_response(&mut r).unwrap(), vec![7u8; 32]);
+        match read_response(&mut r) {
+            Err(Error::NoEntropy(msg)) => assert!(msg.contains("no entropy source")),
+            other => panic!("expected an error frame, got {:?}", other.map(|v| v.len())),
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Environment fingerprint for the `--env` footer of `check` and `analyze`
+//! reports: what a reader of a shared report needs to interpret its numbers.
+
+use std::fs;
+
+use serde::Serialize;
+
+use crate::baseline;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Environment {
+    pub mixrand: String,
+    pub kernel: String,
+    pub cpu_model: Option<String>,
+    pub microcode: Option<String>,
+    /// `None` on bare metal; the hypervisor's name if known, else "unknown".
+    pub hypervisor: Option<String>,
+    /// cpufreq governor of CPU 0; absent in most VMs.
+    pub governor: Option<String>,
+    pub turbo: Option<bool>,
+}
+
+fn read_trim(path: &str) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// Value of the first `key` line in /proc/cpuinfo text.
+fn cpuinfo_field<'a>(cpuinfo: &'a str, key: &str) -> Option<&'a str> {
+    cpuinfo.lines().find_map(|l| {
+        let (k, v) = l.split_once(':')?;
+        (k.trim() == key).then(|| v.trim())
+    })
+}
+
+fn hypervisor(cpuinfo: &str) -> Option<String> {
+    let flags = cpuinfo_field(cpuinfo, "flags")?;
+    if !flags.split_whitespace().any(|f| f == "hypervisor") {
+        return None;
+    }
+    let name = read_trim("/sys/hypervisor/type")
+        .or_else(|| read_trim("/sys/class/dmi/id/product_name"))
+        .or_else(|| read_trim("/sys/class/dmi/id/sys_vendor"));
+    Some(name.unwrap_or_else(|| "unknown".into()))
+}
+
+/// Whether turbo/boost is enabled, from intel_pstate or cpufreq.
+fn turbo() -> Option<bool> {
+    if let Some(no_turbo) = read_trim("/sys/devices/system/cpu/intel_pstate/no_turbo") {
+        return Some(no_turbo == "0");
+    }
+    read_trim("/sys/devices/system/cpu/cpufreq/boost").map(|b| b == "1")
+}
+
+impl Environment {
+    pub fn collect() -> Self {
+        let cpuinfo = fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+        Self {
+            mixrand: env!("CARGO_PKG_VERSION").into(),
+            kernel: baseline::kernel_release(),
+            cpu_model: cpuinfo_field(&cpuinfo, "model name").map(String::from),
+            microcode: baseline::microcode_revision(),
+            hypervisor: hypervisor(&cpuinfo),
+            governor: read_trim("/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor"),
+            turbo: turbo(),
+        }
+    }
+
+    /// Label/value pairs in display order.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let unknown = |v: &Option<String>| v.clone().unwrap_or_else(|| "unknown".into());
+        vec![
+            ("mixrand", self.mixrand.clone()),
+            ("Kernel", self.kernel.clone()),
+            ("CPU", unknown(&self.cpu_model)),
+            ("Microcode", unknown(&self.microcode)),
+            (
+                "Virtualized",
+                match self.hypervisor {
+                    Some(ref name) => format!("yes ({})", name),
+                    None => "no".into(),
+                },
+            ),
+            ("Governor", unknown(&self.governor)),
+            (
+                "Turbo",
+                match self.turbo {
+                    Some(true) => "on".into(),
+                    Some(false) => "off".into(),
+                    None => "unknown".into(),
+                },
+            ),
+        ]
+    }
+
+    /// Prints the footer.
+    pub fn print(&self) {
+        println!("--- Environment ---");
+        for (label, value) in self.fields() {
+            println!("  {:<12} {}", label, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpuinfo_field() {
+        let cpuinfo = "processor\t: 0\nmodel name\t: Intel(R) Xeon(R) Gold 6338\n\
+                       microcode\t: 0xd0003a5\nflags\t\t: fpu rdrand rdseed\n";
+        assert_eq!(
+            cpuinfo_field(cpuinfo, "model name"),
+            Some("Intel(R) Xeon(R) Gold 6338")
+        );
+        assert_eq!(cpuinfo_field(cpuinfo, "model"), None);
+        assert_eq!(hypervisor(cpuinfo), None);
+    }
+}
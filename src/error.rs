@@ -6,6 +6,9 @@ pub enum Error {
     Io(io::Error),
     NoEntropy(String),
     InvalidArgs(String),
+    Timeout(String),
+    /// The daemon gave up after repeated failed injections.
+    Injection(String),
 }
 
 impl fmt::Display for Error {
@@ -14,6 +17,8 @@ impl fmt::Display for Error {
             Error::Io(e) => write!(f, "I/O error: {}", e),
             Error::NoEntropy(msg) => write!(f, "entropy error: {}", msg),
             Error::InvalidArgs(msg) => write!(f, "invalid arguments: {}", msg),
+            Error::Timeout(msg) => write!(f, "timed out: {}", msg),
+            Error::Injection(msg) => write!(f, "injection failed: {}", msg),
         }
     }
 }
@@ -54,6 +59,18 @@ mod tests {
         assert!(msg.contains("bad value"));
     }
 
+    #[test]
+    fn test_display_timeout() {
+        let err = Error::Timeout("hwrng".into());
+        assert_eq!(format!("{}", err), "timed out: hwrng");
+    }
+
+    #[test]
+    fn test_display_injection() {
+        let err = Error::Injection("ioctl: EPERM".into());
+        assert_eq!(format!("{}", err), "injection failed: ioctl: EPERM");
+    }
+
     #[test]
     fn test_from_io_error() {
         let io_err = io::Error::new(io::ErrorKind::PermissionDenied, "denied");
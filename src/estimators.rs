@@ -0,0 +1,321 @@
+//! SP 800-90B §6.3 min-entropy estimators for byte-valued (non-IID) samples.
+//!
+//! Byte estimators run on the samples directly; the binary-only estimators
+//! (Collision, Markov) run on the bitstring expansion and are scaled by 8,
+//! as §6.3 allows for non-binary sources.
+
+use std::collections::HashMap;
+
+/// 99% upper confidence bound multiplier.
+const Z_99: f64 = 2.576;
+/// Number of lags tracked by the Lag prediction estimate.
+const LAG_D: usize = 128;
+/// t-Tuple stops once the most common tuple occurs fewer times than this.
+const TUPLE_CUTOFF: usize = 35;
+/// Upper bound on t, keeping degenerate (constant) input from going quadratic.
+const TUPLE_MAX_T: usize = 16;
+
+/// One estimator's result in bits per byte.
+#[derive(Debug, Clone, Copy)]
+pub struct Estimate {
+    pub name: &'static str,
+    pub min_entropy: f64,
+}
+
+fn upper_bound(p: f64, n: usize) -> f64 {
+    if n < 2 {
+        return 1.0;
+    }
+    (p + Z_99 * (p * (1.0 - p) / (n as f64 - 1.0)).sqrt()).min(1.0)
+}
+
+fn bits(data: &[u8]) -> Vec<u8> {
+    data.iter()
+        .flat_map(|&b| (0..8).rev().map(move |i| (b >> i) & 1))
+        .collect()
+}
+
+/// §6.3.1 Most Common Value estimate.
+pub fn most_common_value(data: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for &b in data {
+        counts[b as usize] += 1;
+    }
+    most_common_value_counts(&counts, data.len())
+}
+
+/// [`most_common_value`] from the byte counts of `n` samples.
+pub fn most_common_value_counts(counts: &[usize; 256], n: usize) -> f64 {
+    let mode = *counts.iter().max().unwrap_or(&0);
+    let p = mode as f64 / n.max(1) as f64;
+    -upper_bound(p, n).log2()
+}
+
+/// §6.3.2 Collision estimate on a bitstring, in bits per bit.
+pub fn collision_bits(bits: &[u8]) -> f64 {
+    let mut times = Vec::new();
+    let mut i = 0;
+    while i + 1 < bits.len() {
+        // A binary collision happens within two or three samples
+        let t = if bits[i] == bits[i + 1] {
+            2
+        } else if i + 2 < bits.len() {
+            3
+        } else {
+            break;
+        };
+        times.push(t as f64);
+        i += t;
+    }
+    let v = times.len();
+    if v < 2 {
+        return 1.0;
+    }
+    let mean = times.iter().sum::<f64>() / v as f64;
+    let var = times.iter().map(|t| (t - mean).powi(2)).sum::<f64>() / (v as f64 - 1.0);
+    let sigma = 0.5907 * var.sqrt();
+    let lower = mean - Z_99 * sigma / (v as f64).sqrt();
+
+    // Expected collision time for a binary source with max probability p;
+    // decreases from 2.5 at p = 0.5 to 2 at p = 1
+    let expected = |p: f64| {
+        let q = 1.0 - p;
+        let z = 1.0 / q;
+        let f = 2.0 * (1.0 + z + z * z / 2.0) / z.powi(3);
+        let half_diff = 0.5 * (1.0 / p - 1.0 / q);
+        p / (q * q) * (1.0 + half_diff) * f - p / q * half_diff
+    };
+    if lower >= expected(0.5) {
+        return 1.0;
+    }
+    let (mut lo, mut hi) = (0.5, 1.0 - 1e-12);
+    if lower <= expected(hi) {
+        return -hi.log2();
+    }
+    for _ in 0..100 {
+        let mid = (lo + hi) / 2.0;
+        if expected(mid) > lower {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    -hi.log2()
+}
+
+/// §6.3.3 Markov estimate on a bitstring, in bits per bit.
+pub fn markov_bits(bits: &[u8]) -> f64 {
+    if bits.len() < 2 {
+        return 0.0;
+    }
+    let ones = bits.iter().filter(|&&b| b == 1).count() as f64;
+    let p1 = ones / bits.len() as f64;
+    let p0 = 1.0 - p1;
+    let mut t = [[0f64; 2]; 2];
+    for w in bits.windows(2) {
+        t[w[0] as usize][w[1] as usize] += 1.0;
+    }
+    let row = |a: usize| {
+        let n = t[a][0] + t[a][1];
+        if n == 0.0 {
+            [0.0, 0.0]
+        } else {
+            [t[a][0] / n, t[a][1] / n]
+        }
+    };
+    let (r0, r1) = (row(0), row(1));
+    let (p00, p01, p10, p11) = (r0[0], r0[1], r1[0], r1[1]);
+
+    // log2 of the most likely 128-bit sequences (§6.3.3 step 4)
+    let lg = |x: f64| if x > 0.0 { x.log2() } else { f64::NEG_INFINITY };
+    let candidates = [
+        lg(p0) + 127.0 * lg(p00),
+        lg(p0) + 64.0 * lg(p01) + 63.0 * lg(p10),
+        lg(p0) + lg(p01) + 126.0 * lg(p11),
+        lg(p1) + lg(p10) + 126.0 * lg(p00),
+        lg(p1) + 64.0 * lg(p10) + 63.0 * lg(p01),
+        lg(p1) + 127.0 * lg(p11),
+    ];
+    let max = candidates.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    (-max / 128.0).min(1.0)
+}
+
+/// §6.3.5 t-Tuple estimate.
+pub fn t_tuple(data: &[u8]) -> f64 {
+    let mut p_max = 0.0f64;
+    for t in 1..=TUPLE_MAX_T.min(data.len()) {
+        let mut counts: HashMap<&[u8], usize> = HashMap::new();
+        for w in data.windows(t) {
+            *counts.entry(w).or_default() += 1;
+        }
+        let q = counts.values().copied().max().unwrap_or(0);
+        if q < TUPLE_CUTOFF {
+            break;
+        }
+        let p = q as f64 / (data.len() - t + 1) as f64;
+        p_max = p_max.max(p.powf(1.0 / t as f64));
+    }
+    if p_max == 0.0 {
+        return 8.0;
+    }
+    -upper_bound(p_max, data.len()).log2()
+}
+
+/// Probability that a run of `r` correct predictions appears nowhere in `n`
+/// trials with per-trial success `p` (§6.3.7 step 7).
+fn no_run_probability(p: f64, r: usize, n: usize) -> f64 {
+    let q = 1.0 - p;
+    let mut x = 1.0f64;
+    for _ in 0..10 {
+        x = 1.0 + q * p.powi(r as i32) * x.powi(r as i32 + 1);
+    }
+    (1.0 - p * x) / ((r as f64 + 1.0 - r as f64 * x) * q) / x.powf(n as f64 + 1.0)
+}
+
+/// Combines global and local predictability into a min-entropy (§6.3.7
+/// steps 5-9, shared by the prediction estimators).
+fn prediction_entropy(correct: usize, trials: usize, longest_run: usize, k: f64) -> f64 {
+    let p_global = correct as f64 / trials.max(1) as f64;
+    let p_global_upper = if correct == 0 {
+        1.0 - 0.01f64.powf(1.0 / trials.max(1) as f64)
+    } else {
+        upper_bound(p_global, trials)
+    };
+
+    let r = longest_run + 1;
+    let (mut lo, mut hi) = (0.0f64, 1.0f64);
+    let p_local = if no_run_probability(1e-9, r, trials) < 0.99 {
+        0.0
+    } else {
+        for _ in 0..60 {
+            let mid = (lo + hi) / 2.0;
+            if no_run_probability(mid, r, trials) > 0.99 {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    };
+
+    -p_global_upper.max(p_local).max(1.0 / k).log2()
+}
+
+/// §6.3.8 Lag prediction estimate.
+pub fn lag_prediction(data: &[u8]) -> f64 {
+    if data.len() < 2 {
+        return 0.0;
+    }
+    let mut scoreboard = [0usize; LAG_D + 1];
+    let mut winner = 1;
+    let (mut correct, mut run, mut longest) = (0usize, 0usize, 0usize);
+
+    for i in 1..data.len() {
+        let hit = winner <= i && data[i - winner] == data[i];
+        if hit {
+            correct += 1;
+            run += 1;
+            longest = longest.max(run);
+        } else {
+            run = 0;
+        }
+        for d in 1..=LAG_D.min(i) {
+            if data[i - d] == data[i] {
+                scoreboard[d] += 1;
+                if scoreboard[d] >= scoreboard[winner] {
+                    winner = d;
+                }
+            }
+        }
+    }
+    prediction_entropy(correct, data.len() - 1, longest, 256.0)
+}
+
+/// Runs every estimator; the assessed min-entropy is the minimum.
+pub fn estimate_all(data: &[u8]) -> Vec<Estimate> {
+    let bitstring = bits(data);
+    vec![
+        Estimate {
+            name: "Most Common Value",
+            min_entropy: most_common_value(data),
+        },
+        Estimate {
+            name: "Collision (bitwise)",
+            min_entropy: 8.0 * collision_bits(&bitstring),
+        },
+        Estimate {
+            name: "Markov (bitwise)",
+            min_entropy: 8.0 * markov_bits(&bitstring),
+        },
+        Estimate {
+            name: "t-Tuple",
+            min_entropy: t_tuple(data),
+        },
+        Estimate {
+            name: "Lag Prediction",
+            min_entropy: lag_prediction(data),
+        },
+    ]
+}
+
+/// Minimum over `estimates`, clamped to 0..=8 bits per byte.
+pub fn assessed(estimates: &[Estimate]) -> f64 {
+    estimates
+        .iter()
+        .map(|e| e.min_entropy)
+        .fold(8.0, f64::min)
+        .clamp(0.0, 8.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn xorshift(n: usize) -> Vec<u8> {
+        let mut x = 0x2545F4914F6CDD1Du64;
+        (0..n)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                (x >> 32) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_uniform_data_scores_high() {
+        let data = xorshift(100_000);
+        for e in estimate_all(&data) {
+            assert!(e.min_entropy > 6.5, "{} = {}", e.name, e.min_entropy);
+        }
+    }
+
+    #[test]
+    fn test_constant_data_scores_zero() {
+        let data = vec![0x5Au8; 10_000];
+        assert!(most_common_value(&data) < 0.01);
+        assert!(t_tuple(&data) < 0.01);
+        assert!(lag_prediction(&data) < 0.01);
+        assert!(markov_bits(&bits(&data)) < 0.6);
+        assert!(assessed(&estimate_all(&data)) < 0.01);
+    }
+
+    #[test]
+    fn test_periodic_data_caught_by_prediction() {
+        // Flat histogram over 100 values, but fully predictable from lag 100
+        let data: Vec<u8> = (0..50_000).map(|i| (i % 100) as u8).collect();
+        assert!(most_common_value(&data) > 6.0);
+        assert!(lag_prediction(&data) < 0.1);
+    }
+
+    #[test]
+    fn test_biased_bits() {
+        // Each bit is 1 with probability ~3/4: min-entropy ~0.415 bits/bit
+        let data: Vec<u8> = xorshift(40_000).chunks(2).map(|c| c[0] | c[1]).collect();
+        let h = markov_bits(&bits(&data));
+        assert!((0.3..0.5).contains(&h), "markov {}", h);
+        let h = collision_bits(&bits(&data));
+        assert!(h < 0.8, "collision {}", h);
+    }
+}
@@ -0,0 +1,135 @@
+//! `mixrand exec`: generates a secret and execs a command with it in an
+//! environment variable or on an inherited pipe, so generation and delivery
+//! are one logged step and the secret never reaches a file or the terminal.
+
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+use crate::cli::ExecArgs;
+use crate::config::Config;
+use crate::entropy::{self, cpurng};
+use crate::error::Error;
+use crate::memfd;
+use crate::output;
+
+/// Default pipe capacity on Linux. Larger secrets would block the write,
+/// as nothing reads the pipe until the command runs.
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+/// A pipe holding `secret`: the read end, inherited across exec, and
+/// nothing else, as the close-on-exec write end is closed before returning.
+fn secret_pipe(secret: &[u8]) -> Result<OwnedFd, Error> {
+    if secret.len() > PIPE_CAPACITY {
+        return Err(Error::InvalidArgs(format!(
+            "--pipe carries at most {} bytes of formatted output",
+            PIPE_CAPACITY
+        )));
+    }
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let read = unsafe { OwnedFd::from_raw_fd(fds[0]) };
+    let mut write = unsafe { File::from_raw_fd(fds[1]) };
+    if unsafe { libc::fcntl(write.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    write.write_all(secret)?;
+    Ok(read)
+}
+
+fn validate(args: &ExecArgs) -> Result<(), Error> {
+    if args.bytes == 0 {
+        return Err(Error::InvalidArgs(
+            "byte count must be greater than 0".into(),
+        ));
+    }
+    if args
+        .env
+        .as_deref()
+        .is_some_and(|name| name.is_empty() || name.contains('='))
+    {
+        return Err(Error::InvalidArgs(
+            "--env needs a variable name without '='".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// The command to exec, carrying `secret` as `args` asks. `--env` drops the
+/// trailing newline text formats end with.
+fn command(args: &ExecArgs, secret: &[u8]) -> Result<Command, Error> {
+    let mut env = None;
+    if let Some(ref name) = args.env {
+        let value = secret.strip_suffix(b"\n").unwrap_or(secret);
+        if value.contains(&0) {
+            return Err(Error::InvalidArgs(
+                "--env cannot carry NUL bytes; pick a text --format".into(),
+            ));
+        }
+        env = Some((name, OsStr::from_bytes(value)));
+    }
+
+    let mut argv = args.command.clone();
+    let pipe = if args.pipe {
+        let fd = secret_pipe(secret)?;
+        argv = memfd::substitute(&argv, fd.as_raw_fd());
+        Some(fd)
+    } else {
+        None
+    };
+    let mut cmd = Command::new(&argv[0]);
+    cmd.args(&argv[1..]);
+    if let Some((name, value)) = env {
+        cmd.env(name, value);
+    }
+    if let Some(fd) = pipe {
+        cmd.env(memfd::FD_ENV, fd.as_raw_fd().to_string());
+        // Leaked on purpose: it has to stay open across exec
+        std::mem::forget(fd);
+    }
+    Ok(cmd)
+}
+
+/// Replaces this process with the command. Only returns on failure.
+pub fn run(args: &ExecArgs, config: &Config) -> Result<(), Error> {
+    validate(args)?;
+    let (mut result, mut secret) =
+        output::draw(args.bytes, &args.format, |n| entropy::generate(n, config))?;
+    cpurng::zeroize_vec(&mut result.bytes);
+    let cmd = command(args, &secret);
+    cpurng::zeroize_vec(&mut secret);
+    let mut cmd = cmd?;
+
+    log::info!(
+        "exec {}: {} bytes from {}{}{}",
+        args.command[0],
+        args.bytes,
+        result.source,
+        args.env
+            .as_ref()
+            .map_or(String::new(), |name| format!(", in ${}", name)),
+        if args.pipe { ", on a pipe" } else { "" }
+    );
+    Err(Error::Io(cmd.exec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_secret_pipe() {
+        let fd = secret_pipe(b"c0ffee\n").unwrap();
+        let mut read = String::new();
+        File::from(fd).read_to_string(&mut read).unwrap();
+        assert_eq!(read, "c0ffee\n");
+        assert!(secret_pipe(&vec![b'a'; PIPE_CAPACITY + 1]).is_err());
+    }
+}
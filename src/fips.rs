@@ -0,0 +1,284 @@
+//! FIPS mode (`fips = true`): every output byte comes from an SP 800-90A
+//! HMAC_DRBG (SHA-256) instantiated from health-tested source output.
+//!
+//! The source chain and its conditioners (BLAKE2b, ChaCha20) act as
+//! non-vetted conditioning in SP 800-90B terms; nothing they produce is
+//! output directly. Known-answer tests run at startup, RCT/APT run on every
+//! entropy input, and the first failure disables output for the life of
+//! the process.
+
+use std::sync::OnceLock;
+
+use hmac::{Hmac, Mac};
+use rand_core::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::entropy::{self, cpurng, health::HealthTests};
+use crate::error::Error;
+use crate::timing::{self, Stage};
+
+/// Entropy input plus nonce for a 256-bit security strength instantiation.
+pub const SEED_BYTES: usize = 48;
+
+/// Min-entropy assumed of source output, in bits per byte, for the health
+/// test cutoffs and for sizing entropy inputs.
+pub const ASSESSED_MIN_ENTROPY: usize = 2;
+
+/// Source bytes health-tested at startup (SP 800-90B §4.3 asks for 1024).
+const STARTUP_SAMPLES: usize = 1024;
+
+/// Largest single generate request: 2^19 bits.
+const MAX_REQUEST_BYTES: usize = 1 << 16;
+
+/// Generate requests allowed between reseeds.
+const RESEED_INTERVAL: u64 = 1 << 48;
+
+const PERSONALIZATION: &[u8] = b"mixrand-fips";
+
+/// Source bytes to collect for `count` output bytes: never less than what
+/// carries [`SEED_BYTES`] of entropy at [`ASSESSED_MIN_ENTROPY`].
+pub fn input_bytes(count: usize) -> usize {
+    count.max(SEED_BYTES * 8 / ASSESSED_MIN_ENTROPY)
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// HMAC-SHA256 keyed with `key`, cloned for each MAC so the padded key
+/// states are computed once.
+fn keyed(key: &[u8]) -> HmacSha256 {
+    <HmacSha256 as Mac>::new_from_slice(key).expect("HMAC accepts any key length")
+}
+
+/// The MAC of `parts`, concatenated, under `hmac`'s key.
+fn mac(hmac: &HmacSha256, parts: &[&[u8]]) -> [u8; 32] {
+    let mut hmac = hmac.clone();
+    for part in parts {
+        hmac.update(part);
+    }
+    hmac.finalize().into_bytes().into()
+}
+
+/// HMAC_DRBG with SHA-256, SP 800-90A §10.1.2, without prediction
+/// resistance.
+pub struct HmacDrbg {
+    k: [u8; 32],
+    v: [u8; 32],
+    hmac: HmacSha256,
+    reseed_counter: u64,
+}
+
+impl HmacDrbg {
+    /// Instantiates from the entropy input and nonce, concatenated.
+    pub fn new(entropy_and_nonce: &[u8], personalization: &[u8]) -> Self {
+        let mut drbg = Self {
+            k: [0u8; 32],
+            v: [1u8; 32],
+            hmac: keyed(&[0u8; 32]),
+            reseed_counter: 1,
+        };
+        drbg.update(&[entropy_and_nonce, personalization]);
+        drbg
+    }
+
+    pub fn reseed(&mut self, entropy: &[u8], additional: &[u8]) {
+        self.update(&[entropy, additional]);
+        self.reseed_counter = 1;
+    }
+
+    fn update(&mut self, provided: &[&[u8]]) {
+        let rounds = if provided.iter().all(|p| p.is_empty()) {
+            1
+        } else {
+            2
+        };
+        for tag in 0..rounds {
+            let (v, tag) = (self.v, [tag]);
+            let mut parts: Vec<&[u8]> = vec![&v, &tag];
+            parts.extend_from_slice(provided);
+            self.k = mac(&self.hmac, &parts);
+            self.hmac = keyed(&self.k);
+            self.v = mac(&self.hmac, &[&self.v]);
+        }
+    }
+
+    /// Fills `out`, one generate request per [`MAX_REQUEST_BYTES`].
+    pub fn generate(&mut self, out: &mut [u8]) -> Result<(), Error> {
+        for request in out.chunks_mut(MAX_REQUEST_BYTES) {
+            if self.reseed_counter > RESEED_INTERVAL {
+                return Err(Error::NoEntropy("HMAC_DRBG must be reseeded".into()));
+            }
+            for block in request.chunks_mut(32) {
+                self.v = mac(&self.hmac, &[&self.v]);
+                block.copy_from_slice(&self.v[..block.len()]);
+            }
+            self.update(&[]);
+            self.reseed_counter += 1;
+        }
+        Ok(())
+    }
+}
+
+impl RngCore for HmacDrbg {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    /// Panics past the reseed interval, 2^48 requests away.
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        self.generate(dst)
+            .expect("HMAC_DRBG reseed interval exceeded");
+    }
+}
+
+impl Drop for HmacDrbg {
+    fn drop(&mut self) {
+        cpurng::zeroize_bytes(&mut self.k);
+        cpurng::zeroize_bytes(&mut self.v);
+    }
+}
+
+static FAILURE: OnceLock<String> = OnceLock::new();
+
+/// Enters the error state; output stays disabled until restart.
+pub fn fail(reason: &str) {
+    if FAILURE.set(reason.to_string()).is_ok() {
+        log::error!("FIPS mode: {}; output disabled", reason);
+    }
+}
+
+/// `Err` once a self-test or health test has failed.
+pub fn check_operational() -> Result<(), Error> {
+    match FAILURE.get() {
+        Some(reason) => Err(Error::NoEntropy(format!(
+            "FIPS mode: output disabled after a failed self-test or health test ({})",
+            reason
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn health_check(input: &[u8]) -> Result<(), Error> {
+    HealthTests::new(ASSESSED_MIN_ENTROPY as f64).check(input)
+}
+
+/// Health-tests `input` and, if it passes, returns `count` bytes from a
+/// fresh HMAC_DRBG instantiated from it. `input` is zeroized either way.
+pub fn condition(mut input: Vec<u8>, count: usize) -> Result<Vec<u8>, Error> {
+    let result = check_operational().and_then(|()| {
+        health_check(&input).inspect_err(|e| fail(&e.to_string()))?;
+        let mut out = vec![0u8; count];
+        timing::time(Stage::Csprng, || {
+            HmacDrbg::new(&input, PERSONALIZATION).generate(&mut out)
+        })?;
+        Ok(out)
+    });
+    cpurng::zeroize_vec(&mut input);
+    result
+}
+
+fn unhex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+/// Known-answer tests for SHA-256, HMAC-SHA256, HMAC_DRBG and the health
+/// tests.
+pub fn self_test() -> Result<(), Error> {
+    let kat = |name: &str, ok: bool| {
+        if ok {
+            Ok(())
+        } else {
+            Err(Error::NoEntropy(format!(
+                "{} known-answer test failed",
+                name
+            )))
+        }
+    };
+
+    kat(
+        "SHA-256",
+        Sha256::digest(b"abc")[..]
+            == unhex("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+    )?;
+
+    // RFC 4231 test case 2
+    kat(
+        "HMAC-SHA256",
+        mac(&keyed(b"Jefe"), &[b"what do ya want for nothing?"])[..]
+            == unhex("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"),
+    )?;
+
+    // CAVP HMAC_DRBG SHA-256, no prediction resistance, no reseed, COUNT 0
+    let mut drbg = HmacDrbg::new(
+        &unhex(concat!(
+            "ca851911349384bffe89de1cbdc46e6831e44d34a4fb935ee285dd14b71a7488",
+            "659ba96c601dc69fc902940805ec0ca8",
+        )),
+        &[],
+    );
+    let mut out = [0u8; 128];
+    drbg.generate(&mut out)?;
+    drbg.generate(&mut out)?;
+    kat(
+        "HMAC_DRBG",
+        out[..]
+            == unhex(concat!(
+                "e528e9abf2dece54d47c7e75e5fe302149f817ea9fb4bee6f4199697d04d5b89",
+                "d54fbb978a15b5c443c9ec21036d2460b6f73ebad0dc2aba6e624abf07745bc1",
+                "07694bb7547bb0995f70de25d6b29e2d3011bb19d27676c07162c8b5ccde0668",
+                "961df86803482cb37ed6d5c0bb8d50cf1f50d476aa0458bdaba806f48be9dcb8",
+            )),
+    )?;
+
+    kat("health test", health_check(&[0x55; 64]).is_err())
+}
+
+/// Power-on self-tests: the known-answer tests, then the startup health
+/// test over [`STARTUP_SAMPLES`] bytes from the source chain.
+pub fn startup(config: &Config) -> Result<(), Error> {
+    if let Err(e) = self_test() {
+        fail(&e.to_string());
+        return Err(e);
+    }
+    let mut result = entropy::generate(STARTUP_SAMPLES, config)?;
+    cpurng::zeroize_vec(&mut result.bytes);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_test_passes() {
+        assert!(self_test().is_ok());
+    }
+
+    #[test]
+    fn test_requests_are_split() {
+        let seed = [9u8; SEED_BYTES];
+        let mut whole = vec![0u8; MAX_REQUEST_BYTES + 10];
+        HmacDrbg::new(&seed, &[]).generate(&mut whole).unwrap();
+
+        let mut drbg = HmacDrbg::new(&seed, &[]);
+        let mut first = vec![0u8; MAX_REQUEST_BYTES];
+        let mut second = [0u8; 10];
+        drbg.generate(&mut first).unwrap();
+        drbg.generate(&mut second).unwrap();
+        assert_eq!(whole, [first, second.to_vec()].concat());
+    }
+
+    #[test]
+    fn test_stuck_input_fails_health_check() {
+        assert!(health_check(&vec![0u8; input_bytes(32)]).is_err());
+        let input = crate::csprng::generate([3u8; 32], input_bytes(32));
+        assert!(health_check(&input).is_ok());
+    }
+}
@@ -0,0 +1,189 @@
+use std::f64::consts::TAU;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use rand_chacha::ChaCha20Rng;
+use rand_core::{RngCore, SeedableRng};
+
+use crate::cli::{Distribution, FloatArgs};
+use crate::config::Config;
+use crate::entropy::{self, cpurng};
+use crate::error::Error;
+use crate::fips::{self, HmacDrbg};
+use crate::mixer;
+
+/// 2^-53: spacing of the 53-bit mantissa grid on [0, 1).
+const UNIT: f64 = 1.0 / (1u64 << 53) as f64;
+
+/// Uniform on [0, 1) from the top 53 bits.
+fn unit_closed_open(bits: u64) -> f64 {
+    (bits >> 11) as f64 * UNIT
+}
+
+/// Uniform on (0, 1], safe to pass to `ln`.
+fn unit_open_closed(bits: u64) -> f64 {
+    ((bits >> 11) + 1) as f64 * UNIT
+}
+
+/// Box–Muller: two independent standard normals from two uniforms.
+fn box_muller(u1: f64, u2: f64) -> (f64, f64) {
+    let r = (-2.0 * u1.ln()).sqrt();
+    let theta = TAU * u2;
+    (r * theta.cos(), r * theta.sin())
+}
+
+/// Inverse CDF of Exp(rate), with `u` on (0, 1].
+fn exponential(u: f64, rate: f64) -> f64 {
+    -u.ln() / rate
+}
+
+/// Draws samples of one distribution from a ChaCha20 stream, or from an
+/// HMAC_DRBG in FIPS mode.
+pub struct Sampler {
+    rng: Box<dyn RngCore>,
+    spare_normal: Option<f64>,
+}
+
+impl Sampler {
+    pub fn new(seed: [u8; 32]) -> Self {
+        Self::from_rng(ChaCha20Rng::from_seed(seed))
+    }
+
+    pub fn from_rng(rng: impl RngCore + 'static) -> Self {
+        Self {
+            rng: Box::new(rng),
+            spare_normal: None,
+        }
+    }
+
+    pub fn uniform(&mut self) -> f64 {
+        unit_closed_open(self.rng.next_u64())
+    }
+
+    pub fn standard_normal(&mut self) -> f64 {
+        if let Some(z) = self.spare_normal.take() {
+            return z;
+        }
+        let u1 = unit_open_closed(self.rng.next_u64());
+        let u2 = unit_closed_open(self.rng.next_u64());
+        let (z0, z1) = box_muller(u1, u2);
+        self.spare_normal = Some(z1);
+        z0
+    }
+
+    pub fn exponential(&mut self, rate: f64) -> f64 {
+        exponential(unit_open_closed(self.rng.next_u64()), rate)
+    }
+
+    pub fn sample(&mut self, args: &FloatArgs) -> f64 {
+        match args.dist {
+            Distribution::Uniform => args.min + (args.max - args.min) * self.uniform(),
+            Distribution::Normal => args.mean + args.stddev * self.standard_normal(),
+            Distribution::Exponential => self.exponential(args.rate),
+        }
+    }
+}
+
+fn validate(args: &FloatArgs) -> Result<(), Error> {
+    if args.count == 0 {
+        return Err(Error::InvalidArgs("count must be greater than 0".into()));
+    }
+    let finite = [args.min, args.max, args.mean, args.stddev, args.rate];
+    if finite.iter().any(|v| !v.is_finite()) {
+        return Err(Error::InvalidArgs("parameters must be finite".into()));
+    }
+    match args.dist {
+        Distribution::Uniform if args.min >= args.max => {
+            Err(Error::InvalidArgs("min must be less than max".into()))
+        }
+        Distribution::Normal if args.stddev <= 0.0 => {
+            Err(Error::InvalidArgs("stddev must be greater than 0".into()))
+        }
+        Distribution::Exponential if args.rate <= 0.0 => {
+            Err(Error::InvalidArgs("rate must be greater than 0".into()))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn write_samples(sampler: &mut Sampler, args: &FloatArgs, out: &mut dyn Write) -> io::Result<()> {
+    for _ in 0..args.count {
+        writeln!(out, "{}", sampler.sample(args))?;
+    }
+    out.flush()
+}
+
+pub fn run(args: &FloatArgs, config: &Config) -> Result<(), Error> {
+    validate(args)?;
+
+    let want = if config.fips { fips::SEED_BYTES } else { 32 };
+    let mut result = entropy::generate(want, config)?;
+    log::info!("entropy source: {}", result.source);
+    let mut sampler = if config.fips {
+        Sampler::from_rng(HmacDrbg::new(&result.bytes, b"mixrand-float"))
+    } else {
+        Sampler::new(mixer::mix_entropy(&[("float-seed", &result.bytes)]))
+    };
+    cpurng::zeroize_vec(&mut result.bytes);
+
+    match args.output_file {
+        Some(ref path) => {
+            let mut out = BufWriter::new(File::create(path)?);
+            write_samples(&mut sampler, args, &mut out)?;
+        }
+        None => {
+            let stdout = io::stdout();
+            let mut out = BufWriter::new(stdout.lock());
+            write_samples(&mut sampler, args, &mut out)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mean_var(xs: &[f64]) -> (f64, f64) {
+        let n = xs.len() as f64;
+        let mean = xs.iter().sum::<f64>() / n;
+        let var = xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        (mean, var)
+    }
+
+    #[test]
+    fn test_unit_ranges() {
+        assert_eq!(unit_closed_open(0), 0.0);
+        assert!(unit_closed_open(u64::MAX) < 1.0);
+        assert!(unit_open_closed(0) > 0.0);
+        assert_eq!(unit_open_closed(u64::MAX), 1.0);
+        assert_eq!(exponential(1.0, 2.0), 0.0);
+        assert!(exponential(unit_open_closed(0), 1.0).is_finite());
+    }
+
+    #[test]
+    fn test_box_muller() {
+        let (z0, z1) = box_muller(1.0, 0.25);
+        assert_eq!((z0, z1), (0.0, 0.0));
+        let (z0, z1) = box_muller((-0.5f64).exp(), 0.0);
+        assert!((z0 - 1.0).abs() < 1e-12 && z1.abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_sample_moments() {
+        let mut s = Sampler::new([7u8; 32]);
+        let n = 200_000;
+
+        let (mean, var) = mean_var(&(0..n).map(|_| s.uniform()).collect::<Vec<_>>());
+        assert!((mean - 0.5).abs() < 0.01, "uniform mean {}", mean);
+        assert!((var - 1.0 / 12.0).abs() < 0.005, "uniform var {}", var);
+
+        let (mean, var) = mean_var(&(0..n).map(|_| s.standard_normal()).collect::<Vec<_>>());
+        assert!(mean.abs() < 0.01, "normal mean {}", mean);
+        assert!((var - 1.0).abs() < 0.02, "normal var {}", var);
+
+        let (mean, var) = mean_var(&(0..n).map(|_| s.exponential(4.0)).collect::<Vec<_>>());
+        assert!((mean - 0.25).abs() < 0.005, "exponential mean {}", mean);
+        assert!((var - 0.0625).abs() < 0.005, "exponential var {}", var);
+    }
+}
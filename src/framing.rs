@@ -0,0 +1,311 @@
+//! Framed output (`--format framed`) and the `unframe` filter, for streams
+//! crossing transports that may drop or corrupt bytes (serial links,
+//! netcat).
+//!
+//! A frame is `MXFR`, a big-endian u32 sequence number, a big-endian u32
+//! payload length (1..=64 KiB), the payload, and a big-endian CRC32C of
+//! the sequence number, length and payload. Sequence numbers start at 0,
+//! so dropped frames show up as gaps.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+use crate::cli::UnframeArgs;
+use crate::entropy::cpurng;
+use crate::error::Error;
+
+const MAGIC: &[u8; 4] = b"MXFR";
+const HEADER_LEN: usize = 12;
+const TRAILER_LEN: usize = 4;
+
+/// Largest payload in one frame.
+pub const FRAME_BYTES: usize = 64 * 1024;
+
+/// CRC32C (Castagnoli, reflected polynomial 0x82F63B78) lookup table.
+const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x82F6_3B78
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+pub fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc = CRC32C_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+/// Appends `data` to `out` as frames of at most [`FRAME_BYTES`], numbering
+/// them from `*seq`.
+pub fn encode(data: &[u8], seq: &mut u32, out: &mut Vec<u8>) {
+    for payload in data.chunks(FRAME_BYTES) {
+        let start = out.len();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&seq.to_be_bytes());
+        out.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        out.extend_from_slice(payload);
+        let crc = crc32c(&out[start + MAGIC.len()..]);
+        out.extend_from_slice(&crc.to_be_bytes());
+        *seq = seq.wrapping_add(1);
+    }
+}
+
+/// What [`FrameReader::next_frame`] found.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Frame {
+    /// A frame whose CRC matched.
+    Valid { seq: u32, payload: Vec<u8> },
+    /// Bytes that were not a valid frame, skipped while resynchronizing.
+    Corrupt { skipped: usize, reason: String },
+}
+
+/// Splits a byte stream into frames, resynchronizing on the next `MXFR`
+/// after damage.
+pub struct FrameReader<R> {
+    inner: R,
+    pending: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: Read> FrameReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            pending: Vec::new(),
+            eof: false,
+        }
+    }
+
+    /// Reads until at least `n` bytes are pending or the input ends.
+    fn fill(&mut self, n: usize) -> io::Result<()> {
+        let mut chunk = [0u8; 8192];
+        while self.pending.len() < n && !self.eof {
+            match self.inner.read(&mut chunk) {
+                Ok(0) => self.eof = true,
+                Ok(k) => self.pending.extend_from_slice(&chunk[..k]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        cpurng::zeroize_bytes(&mut chunk);
+        Ok(())
+    }
+
+    /// Drops `n` pending bytes, zeroizing them.
+    fn consume(&mut self, n: usize) {
+        cpurng::zeroize_bytes(&mut self.pending[..n]);
+        self.pending.drain(..n);
+    }
+
+    /// Drops bytes up to the next `MXFR` after the first pending byte.
+    fn resync(&mut self, reason: String) -> io::Result<Frame> {
+        let mut skipped = 0;
+        let mut from = 1;
+        loop {
+            let found = self
+                .pending
+                .get(from..)
+                .and_then(|rest| rest.windows(MAGIC.len()).position(|w| w == MAGIC))
+                .map(|p| p + from);
+            if let Some(p) = found {
+                self.consume(p);
+                return Ok(Frame::Corrupt {
+                    skipped: skipped + p,
+                    reason,
+                });
+            }
+            if self.eof {
+                skipped += self.pending.len();
+                self.consume(self.pending.len());
+                return Ok(Frame::Corrupt { skipped, reason });
+            }
+            // Keep a tail that may be the start of a split magic
+            let drop = self
+                .pending
+                .len()
+                .saturating_sub(MAGIC.len() - 1)
+                .max(from.min(self.pending.len()));
+            skipped += drop;
+            self.consume(drop);
+            from = 0;
+            self.fill(self.pending.len() + 8192)?;
+        }
+    }
+
+    /// Returns the next frame or damaged stretch, or `None` at the end of
+    /// the input.
+    pub fn next_frame(&mut self) -> io::Result<Option<Frame>> {
+        self.fill(HEADER_LEN)?;
+        if self.pending.is_empty() {
+            return Ok(None);
+        }
+        if self.pending.len() < HEADER_LEN {
+            let skipped = self.pending.len();
+            self.consume(skipped);
+            return Ok(Some(Frame::Corrupt {
+                skipped,
+                reason: "input ends inside a frame header".into(),
+            }));
+        }
+        if &self.pending[..4] != MAGIC {
+            return self.resync("not a frame header".into()).map(Some);
+        }
+        let field = |b: &[u8]| u32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+        let seq = field(&self.pending[4..8]);
+        let len = field(&self.pending[8..12]) as usize;
+        if len == 0 || len > FRAME_BYTES {
+            return self
+                .resync(format!("frame {} has invalid length {}", seq, len))
+                .map(Some);
+        }
+        let total = HEADER_LEN + len + TRAILER_LEN;
+        self.fill(total)?;
+        if self.pending.len() < total {
+            let skipped = self.pending.len();
+            self.consume(skipped);
+            return Ok(Some(Frame::Corrupt {
+                skipped,
+                reason: format!("input ends inside frame {}", seq),
+            }));
+        }
+        let crc = field(&self.pending[HEADER_LEN + len..total]);
+        if crc32c(&self.pending[4..HEADER_LEN + len]) != crc {
+            return self
+                .resync(format!("frame {} fails its CRC check", seq))
+                .map(Some);
+        }
+        let payload = self.pending[HEADER_LEN..HEADER_LEN + len].to_vec();
+        self.consume(total);
+        Ok(Some(Frame::Valid { seq, payload }))
+    }
+}
+
+/// Copies validated payloads from `input` to `out`, returning the payload
+/// bytes written and the number of problems found. Without `skip_corrupt`
+/// the first problem is an error.
+fn unframe<R: Read>(
+    input: R,
+    out: &mut dyn Write,
+    skip_corrupt: bool,
+) -> Result<(u64, u64), Error> {
+    let mut reader = FrameReader::new(input);
+    let mut next_seq = 0u32;
+    let (mut written, mut problems) = (0u64, 0u64);
+    while let Some(frame) = reader.next_frame()? {
+        let problem = match frame {
+            Frame::Valid { seq, mut payload } => {
+                let gap = (seq != next_seq)
+                    .then(|| format!("frames {} to {} are missing", next_seq, seq.wrapping_sub(1)));
+                next_seq = seq.wrapping_add(1);
+                let result = match gap {
+                    Some(_) if !skip_corrupt => Ok(0),
+                    _ => out.write_all(&payload).map(|()| payload.len() as u64),
+                };
+                cpurng::zeroize_vec(&mut payload);
+                written += result?;
+                gap
+            }
+            Frame::Corrupt { skipped, reason } => {
+                Some(format!("{} ({} bytes dropped)", reason, skipped))
+            }
+        };
+        if let Some(msg) = problem {
+            problems += 1;
+            if !skip_corrupt {
+                return Err(Error::InvalidArgs(msg));
+            }
+            log::warn!("{}", msg);
+        }
+    }
+    out.flush()?;
+    Ok((written, problems))
+}
+
+pub fn run(args: &UnframeArgs) -> Result<(), Error> {
+    let input: Box<dyn Read> = match args.input {
+        Some(ref path) => Box::new(File::open(path)?),
+        None => Box::new(io::stdin().lock()),
+    };
+    let (written, problems) = match args.output_file {
+        Some(ref path) => unframe(input, &mut File::create(path)?, args.skip_corrupt)?,
+        None => unframe(input, &mut io::stdout().lock(), args.skip_corrupt)?,
+    };
+    log::info!("wrote {} bytes", written);
+    if problems > 0 {
+        return Err(Error::InvalidArgs(format!(
+            "{} damaged or missing stretches skipped",
+            problems
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn framed(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode(data, &mut 0, &mut out);
+        out
+    }
+
+    #[test]
+    fn test_crc32c_check_value() {
+        assert_eq!(crc32c(b"123456789"), 0xE306_9283);
+    }
+
+    #[test]
+    fn test_round_trip_splits_frames() {
+        let data: Vec<u8> = (0..FRAME_BYTES + 100).map(|i| i as u8).collect();
+        let stream = framed(&data);
+        assert_eq!(stream.len(), data.len() + 2 * (HEADER_LEN + TRAILER_LEN));
+        let mut out = Vec::new();
+        assert_eq!(
+            unframe(&stream[..], &mut out, false).unwrap(),
+            (data.len() as u64, 0)
+        );
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn test_corruption_and_truncation_detected() {
+        let mut stream = framed(&[7u8; 100]);
+        encode(&[8u8; 50], &mut 1, &mut stream);
+        encode(&[9u8; 50], &mut 2, &mut stream);
+
+        let mut flipped = stream.clone();
+        flipped[HEADER_LEN + 10] ^= 1;
+        assert!(unframe(&flipped[..], &mut Vec::new(), false).is_err());
+        let mut out = Vec::new();
+        assert_eq!(unframe(&flipped[..], &mut out, true).unwrap(), (100, 2));
+        assert_eq!(out, [[8u8; 50], [9u8; 50]].concat());
+
+        let truncated = &stream[..stream.len() - 3];
+        let mut out = Vec::new();
+        assert_eq!(unframe(truncated, &mut out, true).unwrap(), (150, 1));
+    }
+
+    #[test]
+    fn test_dropped_frame_is_a_gap() {
+        let mut stream = framed(&[1u8; 10]);
+        encode(&[3u8; 10], &mut 2, &mut stream);
+        let err = unframe(&stream[..], &mut Vec::new(), false).unwrap_err();
+        assert!(err.to_string().contains("frames 1 to 1 are missing"));
+    }
+}
@@ -0,0 +1,409 @@
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
+use tonic::{Request, Response, Status};
+
+use crate::auth::{self, Auth, Client, Quota};
+use crate::cache::{self, EntropyCache};
+use crate::cli::ServeGrpcArgs;
+use crate::config::Config;
+use crate::entropy::{self, plugin};
+use crate::error::Error;
+use crate::output;
+use crate::sandbox::{self, Policy};
+use crate::signals::{self, Signal};
+use crate::workerpool::{self, WorkerPool};
+
+pub mod pb {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct GetEntropyRequest {
+        #[prost(uint32, tag = "1")]
+        pub bytes: u32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct StreamEntropyRequest {
+        #[prost(uint32, tag = "1")]
+        pub bytes_per_second: u32,
+        #[prost(uint32, tag = "2")]
+        pub chunk_size: u32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct EntropyResponse {
+        #[prost(bytes = "vec", tag = "1")]
+        pub data: Vec<u8>,
+        #[prost(string, tag = "2")]
+        pub source: String,
+    }
+
+    include!(concat!(env!("OUT_DIR"), "/mixrand.v1.Entropy.rs"));
+}
+
+use pb::entropy_server::{Entropy, EntropyServer};
+use pb::{EntropyResponse, GetEntropyRequest, StreamEntropyRequest};
+
+/// Default stream chunk size when the client sends 0.
+const DEFAULT_CHUNK_SIZE: u32 = 4096;
+
+struct EntropyService {
+    config: Arc<Config>,
+    pool: Option<Arc<WorkerPool>>,
+    cache: Option<Arc<EntropyCache>>,
+    max_request_bytes: u32,
+    /// Bytes per minute for clients without a quota of their own
+    quota_bytes: u64,
+    quota: Arc<Quota>,
+    auth: Auth,
+    /// Whether clients are authenticated, by token or certificate, and
+    /// what they draw goes to the audit log
+    audited: bool,
+}
+
+/// A client known only by its address; without one it goes unmetered.
+fn anonymous(addr: Option<IpAddr>, quota: u64) -> Client {
+    match addr {
+        Some(ip) => Client {
+            id: ip.to_string(),
+            quota,
+        },
+        None => Client {
+            id: "unknown".into(),
+            quota: 0,
+        },
+    }
+}
+
+impl EntropyService {
+    /// Who sent `request`, and from where: the holder of its bearer token,
+    /// else the owner of its client certificate, else its address. Fails
+    /// without a valid token when `[auth]` has any.
+    fn authorize<T>(&self, request: &Request<T>) -> Result<(Client, String), String> {
+        let addr = request.remote_addr().map(|a| a.ip());
+        let peer = addr.map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        let client = self.auth.check(token, self.quota_bytes).inspect_err(|e| {
+            log::warn!(target: "mixrand::audit", "refused {}: {}", peer, e);
+        })?;
+        let client = client.unwrap_or_else(|| match request.peer_certs() {
+            Some(certs) if !certs.is_empty() => Client {
+                id: format!("cert:{}", output::to_hex(&Sha256::digest(&certs[0]))),
+                quota: self.quota_bytes,
+            },
+            _ => anonymous(addr, self.quota_bytes),
+        });
+        Ok((client, peer))
+    }
+}
+
+/// Serves from the worker pool if there is one, else from the entropy
+/// cache when it can. The pool, which may wait for a busy generator, and
+/// the pipeline both run off the async executor.
+async fn generate(
+    config: Arc<Config>,
+    pool: Option<Arc<WorkerPool>>,
+    cache: Option<Arc<EntropyCache>>,
+    count: usize,
+) -> Result<EntropyResponse, Status> {
+    if pool.is_none() {
+        if let Some(hit) = cache.and_then(|c| c.take(count)) {
+            return Ok(EntropyResponse {
+                data: hit.bytes,
+                source: hit.source,
+            });
+        }
+    }
+    let result = tokio::task::spawn_blocking(move || match pool {
+        Some(pool) => pool.fill(count),
+        None => entropy::generate(count, &config),
+    })
+    .await
+    .map_err(|e| Status::internal(e.to_string()))?
+    .map_err(|e| Status::unavailable(e.to_string()))?;
+    Ok(EntropyResponse {
+        data: result.bytes,
+        source: result.source,
+    })
+}
+
+#[tonic::async_trait]
+impl Entropy for EntropyService {
+    async fn get_entropy(
+        &self,
+        request: Request<GetEntropyRequest>,
+    ) -> Result<Response<EntropyResponse>, Status> {
+        let (client, peer) = self.authorize(&request).map_err(Status::unauthenticated)?;
+        let bytes = request.into_inner().bytes;
+        if bytes == 0 || bytes > self.max_request_bytes {
+            return Err(Status::invalid_argument(format!(
+                "bytes must be between 1 and {}",
+                self.max_request_bytes
+            )));
+        }
+        if !self.quota.take(&client, bytes as u64, Instant::now()) {
+            return Err(Status::resource_exhausted(auth::exceeded(&client)));
+        }
+
+        let response = generate(
+            Arc::clone(&self.config),
+            self.pool.clone(),
+            self.cache.clone(),
+            bytes as usize,
+        )
+        .await?;
+        log::debug!("served {}B to {} from {}", bytes, peer, response.source);
+        if self.audited {
+            auth::audit(&client, &peer, "GetEntropy", bytes as u64);
+        }
+        Ok(Response::new(response))
+    }
+
+    type StreamEntropyStream = Pin<Box<dyn Stream<Item = Result<EntropyResponse, Status>> + Send>>;
+
+    async fn stream_entropy(
+        &self,
+        request: Request<StreamEntropyRequest>,
+    ) -> Result<Response<Self::StreamEntropyStream>, Status> {
+        let (client, peer) = self.authorize(&request).map_err(Status::unauthenticated)?;
+        let req = request.into_inner();
+        if req.bytes_per_second == 0 {
+            return Err(Status::invalid_argument("bytes_per_second must be > 0"));
+        }
+        let chunk = match req.chunk_size {
+            0 => DEFAULT_CHUNK_SIZE,
+            n => n,
+        }
+        .min(self.max_request_bytes)
+        .min(req.bytes_per_second);
+        let period = Duration::from_secs_f64(chunk as f64 / req.bytes_per_second as f64);
+
+        let (tx, rx) = tokio::sync::mpsc::channel(4);
+        let config = Arc::clone(&self.config);
+        let pool = self.pool.clone();
+        let cache = self.cache.clone();
+        let quota = Arc::clone(&self.quota);
+        let audited = self.audited;
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(period);
+            log::info!(
+                "stream opened for {}: {}B/s in {}B chunks",
+                peer,
+                req.bytes_per_second,
+                chunk
+            );
+            let mut drew = 0u64;
+            loop {
+                ticker.tick().await;
+                if signals::shutdown_requested() {
+                    break;
+                }
+                let item = if quota.take(&client, chunk as u64, Instant::now()) {
+                    generate(
+                        Arc::clone(&config),
+                        pool.clone(),
+                        cache.clone(),
+                        chunk as usize,
+                    )
+                    .await
+                } else {
+                    Err(Status::resource_exhausted(auth::exceeded(&client)))
+                };
+                let done = item.is_err();
+                if tx.send(item).await.is_err() || done {
+                    break;
+                }
+                drew += chunk as u64;
+            }
+            log::info!("stream closed for {}", peer);
+            if audited {
+                auth::audit(&client, &peer, "StreamEntropy", drew);
+            }
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+}
+
+fn load_tls(args: &ServeGrpcArgs) -> Result<Option<ServerTlsConfig>, Error> {
+    let (Some(cert), Some(key)) = (&args.tls_cert, &args.tls_key) else {
+        return Ok(None);
+    };
+    let read = |p: &std::path::Path| {
+        std::fs::read(p)
+            .map_err(|e| Error::InvalidArgs(format!("failed to read {}: {}", p.display(), e)))
+    };
+    let identity = Identity::from_pem(read(cert)?, read(key)?);
+    let mut tls = ServerTlsConfig::new().identity(identity);
+    if let Some(ca) = &args.tls_client_ca {
+        tls = tls.client_ca_root(Certificate::from_pem(read(ca)?));
+    }
+    Ok(Some(tls))
+}
+
+/// Resolves once SIGTERM/SIGINT has been received.
+async fn shutdown_signal() {
+    while !signals::shutdown_requested() {
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+}
+
+/// Tightest of a command-line limit and a `[limits]` cap (0 = unlimited).
+fn tighter(requested: u64, policy: u64) -> u64 {
+    match (requested, policy) {
+        (0, p) => p,
+        (r, 0) => r,
+        (r, p) => r.min(p),
+    }
+}
+
+/// Installs the seccomp filter once the listener is bound. An `[exec]`
+/// command would inherit the filter and fail, so it disables the sandbox.
+fn install_sandbox(args: &ServeGrpcArgs, config: &Config) {
+    if args.no_sandbox {
+        log::warn!("seccomp sandbox disabled");
+        return;
+    }
+    if config.exec.is_enabled() {
+        log::warn!("seccomp sandbox disabled: [exec] command would inherit the filter");
+        return;
+    }
+    if !plugin::load(&config.plugins).plugins.is_empty() {
+        log::warn!("seccomp sandbox disabled: plugins may need syscalls outside the allow-list");
+        return;
+    }
+    match sandbox::install(&Policy::serve(config.serial.is_enabled())) {
+        Ok(()) => log::info!("seccomp sandbox installed"),
+        Err(e) => log::warn!("cannot install seccomp sandbox: {}", e),
+    }
+}
+
+pub fn run(args: &ServeGrpcArgs, config: &Config) -> Result<(), Error> {
+    let tls = load_tls(args)?;
+    let auth = Auth::load(config)?;
+    let client_certs = args.tls_client_ca.is_some();
+    if !args.listen.ip().is_loopback() {
+        if tls.is_none() {
+            log::warn!("serving entropy on {} without TLS", args.listen);
+        }
+        if !auth.enabled() && !client_certs {
+            log::warn!(
+                "serving entropy on {} without client authentication",
+                args.listen
+            );
+        }
+    }
+
+    signals::install(&[Signal::Term, Signal::Int]);
+
+    let max_request_bytes = tighter(
+        u64::from(args.max_request_bytes),
+        config.limits.max_request_bytes as u64,
+    )
+    .min(u64::from(u32::MAX)) as u32;
+    let quota_bytes_per_minute = tighter(
+        args.quota_bytes_per_minute,
+        config.limits.client_bytes_per_minute,
+    );
+
+    // Requests never miss with a pool, so the cache would only burn entropy
+    let pool = workerpool::spawn(config)?;
+    let cache = match pool {
+        Some(_) => None,
+        None => cache::spawn(config),
+    };
+    let token_count = auth.count();
+    let service = EntropyService {
+        config: Arc::new(config.clone()),
+        pool: pool.clone(),
+        cache: cache.clone(),
+        max_request_bytes,
+        quota_bytes: quota_bytes_per_minute,
+        quota: Arc::new(Quota::default()),
+        audited: auth.enabled() || client_certs,
+        auth,
+    };
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+
+    runtime.block_on(async {
+        let mut builder = Server::builder();
+        if let Some(tls) = tls {
+            builder = builder
+                .tls_config(tls)
+                .map_err(|e| Error::InvalidArgs(format!("invalid TLS configuration: {}", e)))?;
+        }
+        let incoming = TcpIncoming::new(args.listen, true, None)
+            .map_err(|e| Error::Io(std::io::Error::other(e)))?;
+        install_sandbox(args, config);
+        log::info!(
+            "gRPC server listening on {} (tls={}, client certs={}, tokens={}, quota={}B/min, max request={}B)",
+            args.listen,
+            args.tls_cert.is_some(),
+            client_certs,
+            token_count,
+            quota_bytes_per_minute,
+            max_request_bytes
+        );
+        builder
+            .add_service(EntropyServer::new(service))
+            .serve_with_incoming_shutdown(incoming, shutdown_signal())
+            .await
+            .map_err(|e| Error::Io(std::io::Error::other(e)))
+    })?;
+
+    if let Some(cache) = cache {
+        let stats = cache.stats();
+        log::info!(
+            "cache totals: hits={} misses={} refilled={}B expired={}B",
+            stats.hits,
+            stats.misses,
+            stats.refilled_bytes,
+            stats.expired_bytes
+        );
+    }
+    if let Some(pool) = pool {
+        pool.log_totals();
+    }
+    log::info!("gRPC server shut down");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quota_keyed_by_address() {
+        let q = Quota::default();
+        let a = anonymous(Some("10.0.0.1".parse().unwrap()), 100);
+        let b = anonymous(Some("10.0.0.2".parse().unwrap()), 100);
+        let now = Instant::now();
+        assert!(q.take(&a, 60, now));
+        assert!(!q.take(&a, 60, now));
+        assert!(q.take(&b, 60, now));
+        // A peer without an address goes unmetered
+        assert!(q.take(&anonymous(None, 1), 10, now));
+    }
+
+    #[test]
+    fn test_limits_only_tighten() {
+        assert_eq!(tighter(65536, 0), 65536);
+        assert_eq!(tighter(0, 4096), 4096);
+        assert_eq!(tighter(65536, 4096), 4096);
+        assert_eq!(tighter(1024, 4096), 1024);
+    }
+}
@@ -0,0 +1,230 @@
+//! 256-bucket byte frequency histograms for `check --histogram`.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::Error;
+use crate::stats;
+use crate::table;
+
+/// Buckets whose |z| exceeds this are highlighted. Under uniform input about
+/// 0.7 of the 256 buckets are expected past it by chance.
+pub const Z_HIGHLIGHT: f64 = 3.0;
+
+/// Byte counts accumulated across a source's samples.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Histogram {
+    #[serde(serialize_with = "save_counts", deserialize_with = "load_counts")]
+    pub counts: [u64; 256],
+    pub total: u64,
+}
+
+#[derive(Serialize)]
+struct JsonBucket {
+    byte: u8,
+    count: u64,
+    deviation_pct: f64,
+    z: f64,
+}
+
+#[derive(Serialize)]
+struct JsonSource<'a> {
+    source: &'a str,
+    bytes: u64,
+    chi_square: f64,
+    p_value: f64,
+    outliers: usize,
+    buckets: Vec<JsonBucket>,
+}
+
+// serde only derives arrays of up to 32 elements
+fn save_counts<S: Serializer>(counts: &[u64; 256], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(counts)
+}
+
+fn load_counts<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u64; 256], D::Error> {
+    let counts = Vec::<u64>::deserialize(deserializer)?;
+    let len = counts.len();
+    counts
+        .try_into()
+        .map_err(|_| serde::de::Error::invalid_length(len, &"256 byte counts"))
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: [0; 256],
+            total: 0,
+        }
+    }
+}
+
+impl Histogram {
+    pub fn add(&mut self, data: &[u8]) {
+        for (count, n) in self.counts.iter_mut().zip(stats::byte_frequencies(data)) {
+            *count += n;
+        }
+        self.total += data.len() as u64;
+    }
+
+    pub fn expected(&self) -> f64 {
+        self.total as f64 / 256.0
+    }
+
+    /// Percent above (+) or below (-) the uniform expectation.
+    pub fn deviation_pct(&self, byte: u8) -> f64 {
+        let expected = self.expected();
+        if expected == 0.0 {
+            return 0.0;
+        }
+        100.0 * (self.counts[byte as usize] as f64 - expected) / expected
+    }
+
+    /// Standard score of a bucket under a binomial(total, 1/256) model.
+    pub fn z_score(&self, byte: u8) -> f64 {
+        let sd = (self.total as f64 * (1.0 / 256.0) * (255.0 / 256.0)).sqrt();
+        if sd == 0.0 {
+            return 0.0;
+        }
+        (self.counts[byte as usize] as f64 - self.expected()) / sd
+    }
+
+    /// Chi-square statistic over all buckets (df=255).
+    pub fn chi_square(&self) -> f64 {
+        let expected = self.expected();
+        if expected == 0.0 {
+            return 0.0;
+        }
+        self.counts
+            .iter()
+            .map(|&obs| {
+                let diff = obs as f64 - expected;
+                diff * diff / expected
+            })
+            .sum()
+    }
+
+    /// Bytes whose |z| exceeds [`Z_HIGHLIGHT`], most extreme first.
+    pub fn outliers(&self) -> Vec<u8> {
+        let mut out: Vec<u8> = (0..=255u8)
+            .filter(|&b| self.z_score(b).abs() > Z_HIGHLIGHT)
+            .collect();
+        out.sort_by(|&a, &b| self.z_score(b).abs().total_cmp(&self.z_score(a).abs()));
+        out
+    }
+
+    /// 16x16 grid of deviations in percent, rows by high nibble. Outliers are
+    /// marked with `*`, and shown in bold red if `color` is set.
+    pub fn render(&self, color: bool) -> String {
+        let mut s = String::from("      ");
+        for lo in 0..16 {
+            s.push_str(&format!("{:>7}", format!("_{:X}", lo)));
+        }
+        s.push('\n');
+        for hi in 0..16u8 {
+            s.push_str(&format!("  {:X}_  ", hi));
+            for lo in 0..16u8 {
+                let b = hi << 4 | lo;
+                let cell = table::signed(self.deviation_pct(b), 1);
+                if self.z_score(b).abs() > Z_HIGHLIGHT {
+                    let cell = format!("{:>7}", format!("{}*", cell));
+                    if color {
+                        s.push_str(&format!("\x1b[1;31m{}\x1b[0m", cell));
+                    } else {
+                        s.push_str(&cell);
+                    }
+                } else {
+                    s.push_str(&format!("{:>7}", cell));
+                }
+            }
+            s.push('\n');
+        }
+        s
+    }
+}
+
+/// Writes one row per source and byte value.
+pub fn write_csv(path: &Path, sources: &[(&str, &Histogram)]) -> Result<(), Error> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "source,byte,count,expected,deviation_pct,z_score")?;
+    for (name, h) in sources {
+        for b in 0..=255u8 {
+            writeln!(
+                out,
+                "{},{},{},{:.3},{:.4},{:.4}",
+                name,
+                b,
+                h.counts[b as usize],
+                h.expected(),
+                h.deviation_pct(b),
+                h.z_score(b)
+            )?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes an array with one object per source, each holding 256 buckets.
+pub fn write_json(path: &Path, sources: &[(&str, &Histogram)]) -> Result<(), Error> {
+    let doc: Vec<JsonSource> = sources
+        .iter()
+        .map(|(name, h)| {
+            let chi = h.chi_square();
+            JsonSource {
+                source: name,
+                bytes: h.total,
+                chi_square: chi,
+                p_value: stats::chi_square_p_value(chi, 255.0),
+                outliers: h.outliers().len(),
+                buckets: (0..=255u8)
+                    .map(|b| JsonBucket {
+                        byte: b,
+                        count: h.counts[b as usize],
+                        deviation_pct: h.deviation_pct(b),
+                        z: h.z_score(b),
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    let json = serde_json::to_vec_pretty(&doc).map_err(|e| Error::Io(std::io::Error::other(e)))?;
+    std::fs::write(path, json)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uniform_has_no_deviation() {
+        let data: Vec<u8> = (0..=255u8).cycle().take(256 * 40).collect();
+        let mut h = Histogram::default();
+        h.add(&data[..5000]);
+        h.add(&data[5000..]);
+        assert_eq!(h.total, 256 * 40);
+        assert!(h.counts.iter().all(|&c| c == 40));
+        assert_eq!(h.chi_square(), 0.0);
+        assert!(h.outliers().is_empty());
+        assert!(!h.render(false).contains('*'));
+    }
+
+    #[test]
+    fn test_skewed_bucket_is_highlighted() {
+        let mut data: Vec<u8> = (0..=255u8).cycle().take(256 * 100).collect();
+        data.extend(std::iter::repeat_n(0x41, 100));
+        data.extend(std::iter::repeat_n(0x42, 50));
+        let mut h = Histogram::default();
+        h.add(&data);
+        assert_eq!(h.outliers(), vec![0x41, 0x42]);
+        assert!(h.deviation_pct(0x41) > 90.0);
+        assert!(h.deviation_pct(0x00) < 0.0);
+        let grid = h.render(false);
+        assert_eq!(grid.matches('*').count(), 2);
+        assert!(h.render(true).contains("\x1b[1;31m"));
+    }
+}
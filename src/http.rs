@@ -0,0 +1,144 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::error::Error;
+
+/// Parsed `http://host[:port]/path` URL.
+#[derive(Debug, PartialEq, Eq)]
+struct HttpUrl {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+fn parse_url(url: &str) -> Result<HttpUrl, Error> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| Error::InvalidArgs(format!("only http:// URLs are supported: {}", url)))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let (host, port) = if let Some(bracketed) = authority.strip_prefix('[') {
+        let (h, after) = bracketed
+            .split_once(']')
+            .ok_or_else(|| Error::InvalidArgs(format!("invalid IPv6 host in URL: {}", url)))?;
+        (h, after.strip_prefix(':'))
+    } else {
+        match authority.split_once(':') {
+            Some((h, p)) => (h, Some(p)),
+            None => (authority, None),
+        }
+    };
+    if host.is_empty() {
+        return Err(Error::InvalidArgs(format!("missing host in URL: {}", url)));
+    }
+    let port = match port {
+        Some(p) => p
+            .parse()
+            .map_err(|_| Error::InvalidArgs(format!("invalid port in URL: {}", url)))?,
+        None => 80,
+    };
+    Ok(HttpUrl {
+        host: host.to_string(),
+        port,
+        path: path.to_string(),
+    })
+}
+
+/// A plain-HTTP URL whose host has been resolved, so it can be POSTed to
+/// later without name lookups (e.g. once the daemon is sandboxed).
+#[derive(Debug)]
+pub struct Endpoint {
+    url: String,
+    parsed: HttpUrl,
+    addr: SocketAddr,
+}
+
+impl Endpoint {
+    pub fn resolve(url: &str) -> Result<Self, Error> {
+        let parsed = parse_url(url)?;
+        let addr = (parsed.host.as_str(), parsed.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| Error::InvalidArgs(format!("cannot resolve {}", parsed.host)))?;
+        Ok(Self {
+            url: url.to_string(),
+            parsed,
+            addr,
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// POSTs `body` as `application/json` and returns the response status
+    /// code.
+    pub fn post_json(&self, body: &str, timeout: Duration) -> Result<u16, Error> {
+        let u = &self.parsed;
+        let mut stream = TcpStream::connect_timeout(&self.addr, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: mixrand/{}\r\n\
+             Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            u.path,
+            u.host,
+            env!("CARGO_PKG_VERSION"),
+            body.len(),
+            body
+        )?;
+
+        let mut head = [0u8; 64];
+        let n = stream.read(&mut head)?;
+        let status_line = String::from_utf8_lossy(&head[..n]);
+        status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| {
+                Error::Io(std::io::Error::other(format!(
+                    "malformed HTTP response from {}",
+                    self.url
+                )))
+            })
+    }
+}
+
+/// POSTs `body` as `application/json` to a plain-HTTP `url` and returns the
+/// response status code. Intended for local collectors and webhook relays;
+/// TLS endpoints should be fronted by a local proxy.
+pub fn post_json(url: &str, body: &str, timeout: Duration) -> Result<u16, Error> {
+    Endpoint::resolve(url)?.post_json(body, timeout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(
+            parse_url("http://localhost:8080/beacon").unwrap(),
+            HttpUrl {
+                host: "localhost".into(),
+                port: 8080,
+                path: "/beacon".into()
+            }
+        );
+        let u = parse_url("http://example.org").unwrap();
+        assert_eq!((u.port, u.path.as_str()), (80, "/"));
+        let u = parse_url("http://[::1]:9000/x").unwrap();
+        assert_eq!((u.host.as_str(), u.port), ("::1", 9000));
+    }
+
+    #[test]
+    fn test_parse_url_rejects_https() {
+        assert!(parse_url("https://example.org/").is_err());
+        assert!(parse_url("http://:80/").is_err());
+    }
+}
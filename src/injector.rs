@@ -0,0 +1,427 @@
+//! `injector`: a small privileged process that credits entropy to the kernel
+//! pool on behalf of an unprivileged daemon (`--inject-target injector:PATH`),
+//! so the entropy sources and mixing never run as root.
+//!
+//! The daemon connects over a SOCK_SEQPACKET unix socket. Each message is
+//! the `rand_pool_info` struct that would otherwise be passed to
+//! RNDADDENTROPY, in native byte order; each reply is a native-endian i32,
+//! 0 on success or the errno of the failure.
+
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+
+use crate::cli::InjectorArgs;
+use crate::daemon;
+use crate::error::Error;
+use crate::sandbox::{self, Policy};
+use crate::signals::{self, Signal};
+
+/// Default socket for `mixrand injector` and `injector:` targets.
+pub const DEFAULT_SOCKET: &str = "/run/mixrand/injector.sock";
+/// Largest entropy payload in one message.
+pub const MAX_BATCH_BYTES: usize = 65536;
+
+const HEADER_LEN: usize = 8;
+/// How often the accept loop checks for shutdown.
+const ACCEPT_POLL_MS: libc::c_int = 250;
+
+/// Checks a request and returns its credit in bits and its entropy bytes.
+/// The credit may not exceed `max_credit_ratio` bits per byte.
+pub fn parse_request(msg: &[u8], max_credit_ratio: u32) -> Result<(u32, &[u8]), String> {
+    if msg.len() < HEADER_LEN {
+        return Err(format!("short message of {} bytes", msg.len()));
+    }
+    let bits = i32::from_ne_bytes([msg[0], msg[1], msg[2], msg[3]]);
+    let size = i32::from_ne_bytes([msg[4], msg[5], msg[6], msg[7]]);
+    if size <= 0 || size as usize > MAX_BATCH_BYTES {
+        return Err(format!(
+            "buffer size {} outside 1..={}",
+            size, MAX_BATCH_BYTES
+        ));
+    }
+    let size = size as usize;
+    let body = msg.len() - HEADER_LEN;
+    // The buffer is padded to a whole number of u32s
+    if body < size || body - size >= 4 {
+        return Err(format!(
+            "buffer size {} does not match a {}-byte message",
+            size,
+            msg.len()
+        ));
+    }
+    let max_bits = size as u64 * max_credit_ratio as u64;
+    if bits < 0 || bits as u64 > max_bits {
+        return Err(format!(
+            "credit of {} bits for {} bytes exceeds {} bits",
+            bits, size, max_bits
+        ));
+    }
+    Ok((bits as u32, &msg[HEADER_LEN..HEADER_LEN + size]))
+}
+
+fn unix_addr(path: &Path) -> Result<(libc::sockaddr_un, libc::socklen_t), Error> {
+    let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+    let bytes = path.as_os_str().as_bytes();
+    if bytes.is_empty() || bytes.len() >= addr.sun_path.len() {
+        return Err(Error::InvalidArgs(format!(
+            "invalid socket path {}",
+            path.display()
+        )));
+    }
+    for (dst, &src) in addr.sun_path.iter_mut().zip(bytes) {
+        *dst = src as libc::c_char;
+    }
+    let len = std::mem::size_of::<libc::sa_family_t>() + bytes.len() + 1;
+    Ok((addr, len as libc::socklen_t))
+}
+
+fn socket() -> Result<OwnedFd, Error> {
+    let fd = unsafe { libc::socket(libc::AF_UNIX, libc::SOCK_SEQPACKET | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Connects to an injector's socket.
+pub fn connect(path: &Path) -> Result<File, Error> {
+    let fd = socket()?;
+    let (addr, len) = unix_addr(path)?;
+    let ret = unsafe {
+        libc::connect(
+            fd.as_raw_fd(),
+            &addr as *const libc::sockaddr_un as *const libc::sockaddr,
+            len,
+        )
+    };
+    if ret != 0 {
+        return Err(Error::InvalidArgs(format!(
+            "cannot connect to injector {}: {}",
+            path.display(),
+            io::Error::last_os_error()
+        )));
+    }
+    Ok(File::from(fd))
+}
+
+/// Sends one batch and waits for the injector's verdict.
+pub fn send(conn: &File, data: &[u8], credit_bits: u32) -> Result<(), Error> {
+    let msg = daemon::build_rand_pool_info(data, credit_bits);
+    let sent = loop {
+        let n = unsafe {
+            libc::send(
+                conn.as_raw_fd(),
+                msg.as_ptr() as *const libc::c_void,
+                msg.len(),
+                libc::MSG_NOSIGNAL,
+            )
+        };
+        if n >= 0 {
+            break n as usize;
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err.into());
+        }
+    };
+    if sent != msg.len() {
+        return Err(io::Error::from(io::ErrorKind::WriteZero).into());
+    }
+    let mut reply = [0u8; 4];
+    let n = loop {
+        let n = unsafe {
+            libc::recv(
+                conn.as_raw_fd(),
+                reply.as_mut_ptr() as *mut libc::c_void,
+                reply.len(),
+                0,
+            )
+        };
+        if n >= 0 {
+            break n as usize;
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err.into());
+        }
+    };
+    if n != reply.len() {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    match i32::from_ne_bytes(reply) {
+        0 => Ok(()),
+        errno => Err(io::Error::from_raw_os_error(errno).into()),
+    }
+}
+
+/// Resolves `--allow-user` values, given as names or numeric uids.
+fn resolve_users(users: &[String]) -> Result<Vec<u32>, Error> {
+    users
+        .iter()
+        .map(|u| {
+            if let Ok(uid) = u.parse::<u32>() {
+                return Ok(uid);
+            }
+            let name = CString::new(u.as_str()).map_err(|e| Error::InvalidArgs(e.to_string()))?;
+            let pw = unsafe { libc::getpwnam(name.as_ptr()) };
+            if pw.is_null() {
+                return Err(Error::InvalidArgs(format!("unknown user '{}'", u)));
+            }
+            Ok(unsafe { (*pw).pw_uid })
+        })
+        .collect()
+}
+
+/// Binds the listening socket, replacing a stale one left by a previous
+/// run. The socket is world-connectable; peers are checked by uid.
+fn listen(path: &Path) -> Result<OwnedFd, Error> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => fs::remove_file(path)?,
+        Ok(_) => {
+            return Err(Error::InvalidArgs(format!(
+                "{} exists and is not a socket",
+                path.display()
+            )))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let fd = socket()?;
+    let (addr, len) = unix_addr(path)?;
+    let ret = unsafe {
+        libc::bind(
+            fd.as_raw_fd(),
+            &addr as *const libc::sockaddr_un as *const libc::sockaddr,
+            len,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    fs::set_permissions(path, fs::Permissions::from_mode(0o666))?;
+    if unsafe { libc::listen(fd.as_raw_fd(), 8) } != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(fd)
+}
+
+/// Accepts a connection, returning it with the peer's uid.
+fn accept(listener: &OwnedFd) -> Result<(OwnedFd, u32), Error> {
+    let fd = unsafe {
+        libc::accept4(
+            listener.as_raw_fd(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            libc::SOCK_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    let conn = unsafe { OwnedFd::from_raw_fd(fd) };
+    let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok((conn, cred.uid))
+}
+
+/// Injects each batch on one connection until the peer hangs up, returning
+/// the bytes and bits credited. Invalid requests are refused with EINVAL.
+fn serve_conn(
+    conn: &OwnedFd,
+    dev_random: &File,
+    max_credit_ratio: u32,
+) -> Result<(u64, u64), Error> {
+    let mut buf = vec![0u8; HEADER_LEN + MAX_BATCH_BYTES];
+    let (mut bytes, mut bits) = (0u64, 0u64);
+    loop {
+        let n = unsafe {
+            libc::recv(
+                conn.as_raw_fd(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                libc::MSG_TRUNC,
+            )
+        };
+        if n < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err.into());
+        }
+        if n == 0 {
+            return Ok((bytes, bits));
+        }
+        let n = n as usize;
+        let errno = if n > buf.len() {
+            log::warn!("refused a {}-byte message", n);
+            libc::EINVAL
+        } else {
+            match parse_request(&buf[..n], max_credit_ratio) {
+                Ok((credit, data)) => match daemon::inject_entropy(dev_random, data, credit) {
+                    Ok(()) => {
+                        bytes += data.len() as u64;
+                        bits += credit as u64;
+                        log::debug!("injected {}B ({}bits credit)", data.len(), credit);
+                        0
+                    }
+                    Err(Error::Io(e)) => {
+                        log::error!("ioctl failed: {}", e);
+                        e.raw_os_error().unwrap_or(libc::EIO)
+                    }
+                    Err(e) => {
+                        log::error!("ioctl failed: {}", e);
+                        libc::EIO
+                    }
+                },
+                Err(msg) => {
+                    log::warn!("refused request: {}", msg);
+                    libc::EINVAL
+                }
+            }
+        };
+        let len = n.min(buf.len());
+        buf[..len].fill(0);
+        let reply = errno.to_ne_bytes();
+        let sent = unsafe {
+            libc::send(
+                conn.as_raw_fd(),
+                reply.as_ptr() as *const libc::c_void,
+                reply.len(),
+                libc::MSG_NOSIGNAL,
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+    }
+}
+
+pub fn run(args: &InjectorArgs) -> Result<(), Error> {
+    if !(1..=8).contains(&args.max_credit_ratio) {
+        return Err(Error::InvalidArgs(
+            "max-credit-ratio must be between 1 and 8".into(),
+        ));
+    }
+    let allowed = resolve_users(&args.allow_user)?;
+    let dev_random = daemon::validate_permissions()?;
+
+    signals::install(&[Signal::Term, Signal::Int]);
+
+    let listener = listen(&args.socket).map_err(|e| {
+        Error::InvalidArgs(format!("cannot listen on {}: {}", args.socket.display(), e))
+    })?;
+    if args.no_sandbox {
+        log::warn!("seccomp sandbox disabled");
+    } else {
+        let policy = Policy::injector(dev_random.as_raw_fd(), daemon::RNDADDENTROPY);
+        match sandbox::install(&policy) {
+            Ok(()) => log::info!("seccomp sandbox installed"),
+            Err(e) => log::warn!("cannot install seccomp sandbox: {}", e),
+        }
+    }
+    if allowed.is_empty() {
+        log::warn!("no --allow-user given; only root may inject");
+    }
+    log::info!(
+        "injector listening on {} (uids={:?}, max credit={}bits/byte)",
+        args.socket.display(),
+        allowed,
+        args.max_credit_ratio
+    );
+
+    let dev_random = Arc::new(dev_random);
+    while !signals::shutdown_requested() {
+        let mut pfd = libc::pollfd {
+            fd: listener.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut pfd, 1, ACCEPT_POLL_MS) } <= 0 {
+            continue;
+        }
+        let (conn, uid) = match accept(&listener) {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                log::warn!("accept failed: {}", e);
+                continue;
+            }
+        };
+        if uid != 0 && !allowed.contains(&uid) {
+            log::warn!("refused uid {}: not in --allow-user", uid);
+            continue;
+        }
+        let dev_random = Arc::clone(&dev_random);
+        let max_credit_ratio = args.max_credit_ratio;
+        let spawned = thread::Builder::new()
+            .name(format!("inject-{}", uid))
+            .spawn(move || {
+                log::info!("uid {} connected", uid);
+                match serve_conn(&conn, &dev_random, max_credit_ratio) {
+                    Ok((bytes, bits)) => log::info!(
+                        "uid {} disconnected after {}B ({}bits credit)",
+                        uid,
+                        bytes,
+                        bits
+                    ),
+                    Err(e) => log::warn!("uid {}: {}", uid, e),
+                }
+            });
+        if let Err(e) = spawned {
+            log::error!("cannot serve uid {}: {}", uid, e);
+        }
+    }
+
+    let _ = fs::remove_file(&args.socket);
+    log::info!("injector shut down");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let msg = daemon::build_rand_pool_info(&[0xAB; 10], 80);
+        assert_eq!(msg.len(), 20);
+        let (bits, data) = parse_request(&msg, 8).unwrap();
+        assert_eq!((bits, data), (80, &[0xAB; 10][..]));
+
+        assert!(parse_request(&msg, 4).is_err());
+        assert!(parse_request(&msg[..7], 8).is_err());
+        assert!(parse_request(&msg[..16], 8).is_err());
+        let mut padded = msg.clone();
+        padded.extend_from_slice(&[0; 4]);
+        assert!(parse_request(&padded, 8).is_err());
+        let mut negative = msg.clone();
+        negative[..4].copy_from_slice(&(-1i32).to_ne_bytes());
+        assert!(parse_request(&negative, 8).is_err());
+        let mut empty = msg;
+        empty[4..8].copy_from_slice(&0i32.to_ne_bytes());
+        assert!(parse_request(&empty, 8).is_err());
+    }
+}
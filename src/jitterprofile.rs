@@ -0,0 +1,159 @@
+//! `profile-jitter`: how much entropy the CPU jitter collector yields on
+//! this machine. The collector's timestamps are turned into deltas, the low
+//! byte of each delta is one sample, and the SP 800-90B estimators bound
+//! its min-entropy. Times the sampling rate gives bits per second.
+
+use std::time::{Duration, Instant};
+
+use crate::check;
+use crate::cli::ProfileJitterArgs;
+use crate::entropy::cpurng;
+use crate::entropy::fallback::JITTER_SAMPLES;
+use crate::entropy::jitter;
+use crate::error::Error;
+use crate::estimators;
+use crate::signals::{self, Signal};
+
+/// Timestamps taken per call to the collector.
+const ROUND_TIMESTAMPS: usize = 4096;
+/// SP 800-90B §3.1.1 asks for at least one million samples.
+const RECOMMENDED_SAMPLES: usize = 1_000_000;
+/// Fewer samples than this give meaningless estimates.
+const MIN_SAMPLES: usize = 10_000;
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+/// Bits the fallback's BLAKE2b-256 seed can hold.
+const SEED_BITS: f64 = 256.0;
+
+/// Deltas between consecutive little-endian u64 timestamps, as the low
+/// byte of each plus their sum in nanoseconds.
+fn deltas(timestamps: &[u8]) -> (Vec<u8>, u64) {
+    let ts: Vec<u64> = timestamps
+        .chunks_exact(8)
+        .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+        .collect();
+    let mut sum = 0u64;
+    let samples = ts
+        .windows(2)
+        .map(|w| {
+            let delta = w[1].wrapping_sub(w[0]);
+            sum = sum.saturating_add(delta);
+            delta as u8
+        })
+        .collect();
+    (samples, sum)
+}
+
+pub fn run(args: &ProfileJitterArgs) -> Result<(), Error> {
+    let duration = check::parse_duration(&args.duration)?;
+    if args.max_samples < MIN_SAMPLES {
+        return Err(Error::InvalidArgs(format!(
+            "max-samples must be at least {}",
+            MIN_SAMPLES
+        )));
+    }
+    signals::install(&[Signal::Term, Signal::Int]);
+
+    eprintln!(
+        "Collecting up to {} jitter samples for at most {}...",
+        args.max_samples,
+        check::format_duration(duration)
+    );
+    let start = Instant::now();
+    let mut last_report = start;
+    let mut samples: Vec<u8> = Vec::with_capacity(args.max_samples);
+    let mut delta_ns = 0u64;
+    while samples.len() < args.max_samples
+        && start.elapsed() < duration
+        && !signals::shutdown_requested()
+    {
+        let want = ROUND_TIMESTAMPS.min(args.max_samples - samples.len() + 1);
+        let mut timestamps = jitter::collect_jitter_samples(want.max(2));
+        let (mut round, sum) = deltas(&timestamps);
+        samples.extend_from_slice(&round);
+        delta_ns = delta_ns.saturating_add(sum);
+        cpurng::zeroize_vec(&mut timestamps);
+        cpurng::zeroize_vec(&mut round);
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            eprintln!(
+                "  {} samples after {}",
+                samples.len(),
+                check::format_duration(start.elapsed())
+            );
+            last_report = Instant::now();
+        }
+    }
+    let elapsed = start.elapsed();
+
+    if samples.len() < MIN_SAMPLES {
+        cpurng::zeroize_vec(&mut samples);
+        return Err(Error::NoEntropy(format!(
+            "only {} jitter samples collected; need at least {}",
+            samples.len(),
+            MIN_SAMPLES
+        )));
+    }
+    if samples.len() < RECOMMENDED_SAMPLES {
+        eprintln!(
+            "Note: {} samples collected; SP 800-90B recommends at least {}",
+            samples.len(),
+            RECOMMENDED_SAMPLES
+        );
+    }
+
+    eprintln!("Running SP 800-90B estimators...");
+    let estimates = estimators::estimate_all(&samples);
+    let count = samples.len();
+    cpurng::zeroize_vec(&mut samples);
+    let assessed = estimators::assessed(&estimates);
+    let per_second = count as f64 / elapsed.as_secs_f64();
+
+    println!("--- CPU jitter (low byte of clock_gettime deltas) ---");
+    println!(
+        "  {:<22} {} in {} ({:.0}/s)",
+        "Samples",
+        count,
+        check::format_duration(elapsed),
+        per_second
+    );
+    println!("  {:<22} {} ns", "Mean delta", delta_ns / count as u64);
+    for e in &estimates {
+        println!("  {:<22} {:>6.3} bits/sample", e.name, e.min_entropy);
+    }
+    println!(
+        "  {:<22} {:>6.3} bits/sample",
+        "Assessed (minimum)", assessed
+    );
+    println!("  {:<22} {:.0} bits/s", "Rate", assessed * per_second);
+    println!();
+
+    // The first timestamp of a fallback seed has no predecessor
+    let per_seed = assessed * (JITTER_SAMPLES - 1) as f64;
+    if per_seed >= SEED_BITS {
+        println!(
+            "Fallback: its {} jitter samples per seed carry about {:.0} bits, more than the seed's {:.0}",
+            JITTER_SAMPLES, per_seed, SEED_BITS
+        );
+    } else {
+        println!(
+            "Fallback: its {} jitter samples per seed carry about {:.0} of the seed's {:.0} bits; the other inputs must supply the rest",
+            JITTER_SAMPLES, per_seed, SEED_BITS
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deltas() {
+        let timestamps: Vec<u8> = [1000u64, 1300, 1301, 1813]
+            .iter()
+            .flat_map(|t| t.to_le_bytes())
+            .collect();
+        let (samples, sum) = deltas(&timestamps);
+        assert_eq!(samples, vec![44, 1, 0]);
+        assert_eq!(sum, 813);
+    }
+}
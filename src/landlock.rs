@@ -0,0 +1,217 @@
+//! Landlock filesystem restriction (Linux 5.13+) via the raw syscalls.
+//!
+//! Complements the seccomp filter: seccomp decides which syscalls may run,
+//! Landlock which paths they may touch. Descriptors opened before
+//! [`restrict`] keep working.
+
+use std::fs::File;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+
+const CREATE_RULESET_VERSION: libc::c_uint = 1 << 0;
+const RULE_PATH_BENEATH: libc::c_int = 1;
+
+const ACCESS_FS_EXECUTE: u64 = 1 << 0;
+const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+const ACCESS_FS_REMOVE_FILE: u64 = 1 << 5;
+const ACCESS_FS_MAKE_REG: u64 = 1 << 8;
+/// Everything ABI v1 can restrict (EXECUTE through MAKE_SYM).
+const ACCESS_FS_V1: u64 = (1 << 13) - 1;
+const ACCESS_FS_REFER: u64 = 1 << 13;
+const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+
+/// Rights that apply to a regular file rather than a directory.
+const FILE_RIGHTS: u64 =
+    ACCESS_FS_EXECUTE | ACCESS_FS_WRITE_FILE | ACCESS_FS_READ_FILE | ACCESS_FS_TRUNCATE;
+
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+/// What a rule grants beneath its path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Read files and list directories.
+    Read,
+    /// Also create, overwrite, truncate and replace regular files.
+    Write,
+}
+
+/// Outcome of [`restrict`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Enforced with this Landlock ABI version.
+    Enforced(u32),
+    /// The kernel has no Landlock (too old, or disabled at boot).
+    Unsupported,
+}
+
+/// Access rights the kernel's ABI version can restrict. Only filesystem
+/// rights up to TRUNCATE are handled; later ones (device ioctls) stay
+/// unrestricted.
+fn handled_access(abi: u32) -> u64 {
+    let mut handled = ACCESS_FS_V1;
+    if abi >= 2 {
+        handled |= ACCESS_FS_REFER;
+    }
+    if abi >= 3 {
+        handled |= ACCESS_FS_TRUNCATE;
+    }
+    handled
+}
+
+/// Rights granted by `access` beneath a directory or on a single file.
+fn rule_access(access: Access, is_dir: bool, handled: u64) -> u64 {
+    let rights = match access {
+        Access::Read => ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR,
+        Access::Write => {
+            ACCESS_FS_READ_FILE
+                | ACCESS_FS_READ_DIR
+                | ACCESS_FS_WRITE_FILE
+                | ACCESS_FS_TRUNCATE
+                | ACCESS_FS_MAKE_REG
+                | ACCESS_FS_REMOVE_FILE
+        }
+    };
+    let rights = if is_dir { rights } else { rights & FILE_RIGHTS };
+    rights & handled
+}
+
+fn abi_version() -> Option<u32> {
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<RulesetAttr>(),
+            0usize,
+            CREATE_RULESET_VERSION,
+        )
+    };
+    (ret > 0).then_some(ret as u32)
+}
+
+fn add_rule(ruleset: &OwnedFd, path: &Path, access: Access, handled: u64) -> Result<(), Error> {
+    let file = match File::options()
+        .read(true)
+        .custom_flags(libc::O_PATH | libc::O_CLOEXEC)
+        .open(path)
+    {
+        Ok(f) => f,
+        // Nothing to grant for a path that doesn't exist
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(Error::InvalidArgs(format!(
+                "landlock: cannot open {}: {}",
+                path.display(),
+                e
+            )))
+        }
+    };
+    let is_dir = file.metadata()?.is_dir();
+    let attr = PathBeneathAttr {
+        allowed_access: rule_access(access, is_dir, handled),
+        parent_fd: file.as_raw_fd(),
+    };
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_add_rule,
+            ruleset.as_raw_fd(),
+            RULE_PATH_BENEATH,
+            &attr as *const PathBeneathAttr,
+            0u32,
+        )
+    };
+    if ret != 0 {
+        return Err(Error::InvalidArgs(format!(
+            "landlock: cannot add rule for {}: {}",
+            path.display(),
+            std::io::Error::last_os_error()
+        )));
+    }
+    Ok(())
+}
+
+/// Restricts this process (all threads created afterwards, and the calling
+/// thread) to `rules`. Sets `PR_SET_NO_NEW_PRIVS`. Irreversible.
+pub fn restrict(rules: &[(PathBuf, Access)]) -> Result<Status, Error> {
+    let Some(abi) = abi_version() else {
+        return Ok(Status::Unsupported);
+    };
+    let handled = handled_access(abi);
+    let attr = RulesetAttr {
+        handled_access_fs: handled,
+    };
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            &attr as *const RulesetAttr,
+            std::mem::size_of::<RulesetAttr>(),
+            0u32,
+        )
+    };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let ruleset = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+
+    for (path, access) in rules {
+        add_rule(&ruleset, path, *access, handled)?;
+    }
+
+    unsafe {
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        if libc::syscall(libc::SYS_landlock_restrict_self, ruleset.as_raw_fd(), 0u32) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(Status::Enforced(abi))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handled_access_by_abi() {
+        assert_eq!(handled_access(1), ACCESS_FS_V1);
+        assert_eq!(handled_access(2) & ACCESS_FS_REFER, ACCESS_FS_REFER);
+        assert_eq!(handled_access(2) & ACCESS_FS_TRUNCATE, 0);
+        assert_eq!(handled_access(6) >> 15, 0);
+    }
+
+    #[test]
+    fn test_rule_access() {
+        let handled = handled_access(3);
+        let dir = rule_access(Access::Write, true, handled);
+        assert_ne!(dir & ACCESS_FS_MAKE_REG, 0);
+        assert_ne!(dir & ACCESS_FS_TRUNCATE, 0);
+        assert_eq!(dir & ACCESS_FS_EXECUTE, 0);
+
+        // Directory-only rights are invalid on a file rule
+        let file = rule_access(Access::Write, false, handled);
+        assert_eq!(file & !FILE_RIGHTS, 0);
+        assert_eq!(
+            rule_access(Access::Read, false, handled),
+            ACCESS_FS_READ_FILE
+        );
+
+        // TRUNCATE is dropped when the kernel can't handle it
+        assert_eq!(
+            rule_access(Access::Write, false, handled_access(1)),
+            ACCESS_FS_READ_FILE | ACCESS_FS_WRITE_FILE
+        );
+    }
+}
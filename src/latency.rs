@@ -0,0 +1,154 @@
+//! Per-sample latency distributions for `check`: exact min, max, mean and
+//! standard deviation, and percentiles from log-spaced buckets, in constant
+//! memory however long the run.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Buckets per doubling; each spans 2^(1/16), so percentiles are within
+/// about 4.4%.
+const BUCKETS_PER_OCTAVE: f64 = 16.0;
+
+/// Covers 1 ns to 2^40 ns (about 18 minutes); slower samples share the
+/// last bucket.
+const BUCKETS: usize = 40 * 16;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Latency {
+    count: u64,
+    min_ns: u64,
+    max_ns: u64,
+    /// Running mean and sum of squared deviations (Welford), in ns.
+    mean: f64,
+    m2: f64,
+    #[serde(deserialize_with = "buckets")]
+    buckets: Vec<u64>,
+}
+
+/// Rejects a saved distribution with the wrong number of buckets.
+fn buckets<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u64>, D::Error> {
+    let buckets = Vec::<u64>::deserialize(deserializer)?;
+    if buckets.len() != BUCKETS {
+        return Err(serde::de::Error::invalid_length(
+            buckets.len(),
+            &"640 latency buckets",
+        ));
+    }
+    Ok(buckets)
+}
+
+impl Default for Latency {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            min_ns: u64::MAX,
+            max_ns: 0,
+            mean: 0.0,
+            m2: 0.0,
+            buckets: vec![0; BUCKETS],
+        }
+    }
+}
+
+fn bucket(ns: u64) -> usize {
+    if ns <= 1 {
+        return 0;
+    }
+    ((ns as f64).log2() * BUCKETS_PER_OCTAVE).min((BUCKETS - 1) as f64) as usize
+}
+
+impl Latency {
+    pub fn record(&mut self, elapsed: Duration) {
+        let ns = elapsed.as_nanos().min(u64::MAX as u128) as u64;
+        self.count += 1;
+        self.min_ns = self.min_ns.min(ns);
+        self.max_ns = self.max_ns.max(ns);
+        let delta = ns as f64 - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (ns as f64 - self.mean);
+        self.buckets[bucket(ns)] += 1;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn min(&self) -> Duration {
+        Duration::from_nanos(if self.count == 0 { 0 } else { self.min_ns })
+    }
+
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max_ns)
+    }
+
+    pub fn mean(&self) -> Duration {
+        Duration::from_nanos(self.mean as u64)
+    }
+
+    /// Sample standard deviation.
+    pub fn stddev(&self) -> Duration {
+        if self.count < 2 {
+            return Duration::ZERO;
+        }
+        Duration::from_nanos((self.m2 / (self.count - 1) as f64).sqrt() as u64)
+    }
+
+    /// The `p`th percentile (0-100): the geometric middle of the bucket
+    /// holding it, kept within the observed min and max. The 0th and 100th
+    /// are exact.
+    pub fn percentile(&self, p: f64) -> Duration {
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * self.count as f64).ceil() as u64;
+        if rank <= 1 {
+            return self.min();
+        }
+        if rank >= self.count {
+            return self.max();
+        }
+        let mut seen = 0;
+        let idx = self
+            .buckets
+            .iter()
+            .position(|&n| {
+                seen += n;
+                seen >= rank
+            })
+            .unwrap_or(BUCKETS - 1);
+        let mid = 2f64.powf((idx as f64 + 0.5) / BUCKETS_PER_OCTAVE) as u64;
+        Duration::from_nanos(mid.clamp(self.min_ns, self.max_ns))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_moments_and_extremes() {
+        let mut l = Latency::default();
+        for ms in [2, 4, 4, 4, 5, 5, 7, 9] {
+            l.record(Duration::from_millis(ms));
+        }
+        assert_eq!(l.count(), 8);
+        assert_eq!(l.min(), Duration::from_millis(2));
+        assert_eq!(l.max(), Duration::from_millis(9));
+        assert_eq!(l.mean(), Duration::from_millis(5));
+        let sd = l.stddev().as_secs_f64() * 1000.0;
+        assert!((sd - 2.138).abs() < 0.001, "{}", sd);
+    }
+
+    #[test]
+    fn test_percentiles_within_bucket_width() {
+        let mut l = Latency::default();
+        for us in 1..=1000 {
+            l.record(Duration::from_micros(us));
+        }
+        for (p, exact) in [(50.0, 500.0), (90.0, 900.0), (99.0, 990.0)] {
+            let got = l.percentile(p).as_secs_f64() * 1e6;
+            assert!((got / exact - 1.0).abs() < 0.045, "p{} = {}", p, got);
+        }
+        assert_eq!(l.percentile(100.0), Duration::from_micros(1000));
+        assert_eq!(l.percentile(0.0), Duration::from_micros(1));
+        assert_eq!(Latency::default().percentile(50.0), Duration::ZERO);
+    }
+}
@@ -0,0 +1,82 @@
+//! Library half of `mixrand`: every module the binary uses, also exposed
+//! for the benchmarks under `benches/`.
+
+pub mod age;
+pub mod alerts;
+pub mod analyze;
+pub mod atomicfile;
+pub mod auth;
+pub mod baseline;
+pub mod beacon;
+pub mod budget;
+#[cfg(feature = "grpc")]
+pub mod cache;
+pub mod cadence;
+pub mod calibrate;
+pub mod capture;
+pub mod cbor;
+pub mod ceremony;
+pub mod check;
+pub mod checksum;
+pub mod cli;
+pub mod client;
+pub mod compress;
+pub mod config;
+pub mod context;
+pub mod cpudiff;
+pub mod csprng;
+pub mod daemon;
+pub mod doctor;
+pub mod drift;
+pub mod entropy;
+pub mod environment;
+pub mod error;
+pub mod estimators;
+pub mod exec;
+pub mod fips;
+pub mod float;
+pub mod framing;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod histogram;
+pub mod http;
+pub mod injector;
+pub mod jitterprofile;
+pub mod landlock;
+pub mod latency;
+pub mod logging;
+pub mod memfd;
+pub mod metrics;
+pub mod mixer;
+pub mod output;
+pub mod paired;
+pub mod pattern;
+pub mod probe;
+pub mod qualitycheck;
+pub mod report;
+pub mod reservation;
+pub mod restart;
+pub mod resume;
+pub mod sandbox;
+pub mod schedule;
+pub mod secmem;
+pub mod seedfile;
+pub mod signals;
+pub mod socket;
+pub mod sourcecache;
+pub mod split;
+pub mod stats;
+pub mod status;
+pub mod stream;
+pub mod table;
+pub mod text;
+pub mod timing;
+#[cfg(feature = "io-uring")]
+pub mod uring;
+pub mod usdt;
+pub mod verify;
+pub mod vsock;
+pub mod watch;
+pub mod wipe;
+pub mod wire;
+pub mod workerpool;
@@ -1,6 +1,7 @@
 use std::fs::{File, OpenOptions};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 
 use clap::{Args, ValueEnum};
@@ -42,11 +43,35 @@ pub struct LogArgs {
     pub syslog: bool,
 }
 
+impl LogArgs {
+    /// Fills options not given here from `outer` (the top-level ones).
+    pub fn inherit(&mut self, outer: &LogArgs) {
+        self.log_level = self.log_level.or(outer.log_level);
+        if self.log_file.is_none() {
+            self.log_file.clone_from(&outer.log_file);
+        }
+        self.syslog |= outer.syslog;
+    }
+}
+
+/// Set by [`request_reopen`]; the logger reopens its file before the next write.
+static REOPEN: AtomicBool = AtomicBool::new(false);
+
 struct MixrandLogger {
+    log_path: Option<PathBuf>,
     log_file: Option<Mutex<File>>,
     syslog: Option<Mutex<SyslogLogger>>,
 }
 
+fn open_log_file(path: &Path) -> Option<File> {
+    OpenOptions::new().create(true).append(true).open(path).ok()
+}
+
+/// Asks the logger to reopen its log file (e.g. after logrotate moved it).
+pub fn request_reopen() {
+    REOPEN.store(true, Ordering::Relaxed);
+}
+
 fn level_tag(level: Level) -> &'static str {
     match level {
         Level::Error => "error",
@@ -69,6 +94,8 @@ impl Log for MixrandLogger {
 
         let prefix = if record.target().contains("daemon") {
             "mixrand daemon"
+        } else if record.target().contains("audit") {
+            "mixrand audit"
         } else {
             "mixrand"
         };
@@ -81,6 +108,11 @@ impl Log for MixrandLogger {
         // Optionally write to log file
         if let Some(ref file) = self.log_file {
             if let Ok(mut f) = file.lock() {
+                if REOPEN.swap(false, Ordering::Relaxed) {
+                    if let Some(new) = self.log_path.as_deref().and_then(open_log_file) {
+                        *f = new;
+                    }
+                }
                 let _ = writeln!(f, "{}", msg);
             }
         }
@@ -115,14 +147,11 @@ pub fn init(args: &LogArgs, is_daemon: bool) {
         LogLevel::Warn
     });
 
-    let log_file = args.log_file.as_ref().and_then(|path| {
-        OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(path)
-            .ok()
-            .map(|f| Mutex::new(f))
-    });
+    let log_file = args
+        .log_file
+        .as_deref()
+        .and_then(open_log_file)
+        .map(Mutex::new);
 
     let syslog = if args.syslog {
         syslog::unix(syslog::Formatter3164 {
@@ -132,12 +161,16 @@ pub fn init(args: &LogArgs, is_daemon: bool) {
             pid: std::process::id(),
         })
         .ok()
-        .map(|l| Mutex::new(l))
+        .map(Mutex::new)
     } else {
         None
     };
 
-    let logger = MixrandLogger { log_file, syslog };
+    let logger = MixrandLogger {
+        log_path: args.log_file.clone(),
+        log_file,
+        syslog,
+    };
 
     let _ = log::set_boxed_logger(Box::new(logger));
     log::set_max_level(level.to_level_filter());
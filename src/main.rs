@@ -1,114 +1,508 @@
-mod check;
-mod cli;
-mod config;
-mod csprng;
-mod daemon;
-mod entropy;
-mod error;
-mod logging;
-mod mixer;
-mod output;
-mod stats;
-
 use std::path::Path;
 use std::process;
+use std::time::Instant;
 
-use clap::Parser;
-
-use cli::{Cli, Command, CpuRngArgs};
-use config::CpuRngConfig;
+use mixrand::cli::{Cli, Command, CpuRngArgs, GenerateArgs, OutputFormat};
+use mixrand::config::{self, Config};
+use mixrand::entropy::{cpurng, EntropyResult};
+use mixrand::error::Error;
+#[cfg(feature = "grpc")]
+use mixrand::grpc;
+use mixrand::output::Destination;
+use mixrand::{
+    age, alerts, analyze, beacon, calibrate, capture, ceremony, check, checksum, client, context, daemon,
+    doctor, entropy, exec, fips, float, framing, injector, jitterprofile, logging, memfd, metrics,
+    mixer, output, probe, restart, secmem, socket, sourcecache, split, stream, text, timing,
+    verify, vsock, watch, wipe,
+};
 
-/// Build a CpuRngConfig by layering: defaults → TOML file → CLI overrides.
-fn build_cpu_rng_config(config_file: Option<&Path>, cpu_rng_args: &CpuRngArgs) -> CpuRngConfig {
-    let mut cfg = match config::load_config(config_file) {
-        Ok(c) => c.cpu_rng,
+/// Build a Config by layering: defaults → TOML file → CLI overrides.
+fn build_config(config_file: Option<&Path>, cpu_rng_args: &CpuRngArgs) -> Config {
+    let mut config = match config::load_config(config_file) {
+        Ok(c) => c,
         Err(e) => {
             log::warn!("{}", e);
-            CpuRngConfig::default()
+            Config::default()
         }
     };
+    cpu_rng_args.apply(&mut config.cpu_rng);
 
-    // Apply CLI overrides (only if explicitly set)
-    if let Some(v) = cpu_rng_args.enable_rdseed {
-        cfg.enable_rdseed = v;
-    }
-    if let Some(v) = cpu_rng_args.enable_rdrand {
-        cfg.enable_rdrand = v;
-    }
-    if let Some(v) = cpu_rng_args.enable_xstore {
-        cfg.enable_xstore = v;
+    config.validate();
+    if let Err(e) = entropy::check_policy(&config) {
+        log::error!("{}", e);
+        process::exit(1);
     }
-    if let Some(v) = cpu_rng_args.rdrand_retries {
-        cfg.rdrand_retries = v;
+    mixer::init(&config.mixer);
+    entropy::hwrng::set_rate_limit(config.limits.hwrng_bytes_per_sec);
+    if config.fips {
+        if let Err(e) = fips::startup(&config) {
+            log::error!("FIPS self-tests failed: {}", e);
+            process::exit(1);
+        }
+        log::info!("FIPS mode: self-tests passed");
     }
-    if let Some(v) = cpu_rng_args.rdseed_retries {
-        cfg.rdseed_retries = v;
+    config
+}
+
+/// Turns on secret-buffer locking; a failure only weakens hardening.
+fn lock_memory(enabled: bool) {
+    if enabled {
+        if let Err(e) = secmem::enable() {
+            log::warn!("cannot disable core dumps: {}", e);
+        }
     }
-    if let Some(v) = cpu_rng_args.xstore_quality {
-        cfg.xstore_quality = v;
+}
+
+/// The alphabet and grouping of `-f text` output.
+fn text_style(args: &GenerateArgs) -> Result<Option<text::Style>, Error> {
+    let is_text = matches!(args.format, OutputFormat::Text);
+    if !is_text && (args.alphabet.is_some() || args.group.is_some()) {
+        return Err(Error::InvalidArgs(
+            "--alphabet and --group need -f text".into(),
+        ));
     }
-    if let Some(v) = cpu_rng_args.cpu_rng_prefer {
-        cfg.prefer = v;
+    if !is_text {
+        return Ok(None);
     }
-    if let Some(v) = cpu_rng_args.fallback_mix_bytes {
-        cfg.fallback_mix_bytes = v;
+    let alphabet = match args.alphabet {
+        Some(ref chars) => text::Alphabet::parse(chars)?,
+        None => text::Alphabet::printable(),
+    };
+    text::Style::new(alphabet, args.group).map(Some)
+}
+
+/// `--context` or `--contexts`: the context names, and whether each output
+/// is labeled with its name.
+fn contexts(args: &GenerateArgs) -> Result<Option<(Vec<String>, bool)>, Error> {
+    if let Some(ref name) = args.context {
+        context::validate(name)?;
+        return Ok(Some((vec![name.clone()], false)));
     }
-    if let Some(v) = cpu_rng_args.oversample {
-        cfg.oversample = v;
+    let Some(ref names) = args.contexts else {
+        return Ok(None);
+    };
+    context::validate_all(names)?;
+    if matches!(
+        args.format,
+        OutputFormat::Raw | OutputFormat::Uuencode | OutputFormat::Framed
+    ) {
+        return Err(Error::InvalidArgs(
+            "--contexts prints one labeled line per context; use a line format such as hex or base64".into(),
+        ));
     }
+    Ok(Some((names.clone(), true)))
+}
 
-    cfg.validate();
-    cfg
+/// Collects one seed and derives each context's output from it, formatted
+/// and, if `labeled`, prefixed with `name: `. Returns every byte derived
+/// and the formatted output.
+fn generate_contexts(
+    args: &GenerateArgs,
+    style: Option<&text::Style>,
+    names: &[String],
+    labeled: bool,
+    config: &Config,
+    deadline: Option<entropy::Deadline>,
+) -> Result<(EntropyResult, Vec<u8>), Error> {
+    let mut seed = entropy::generate_within(context::seed_bytes(config.fips), config, deadline)?;
+    let mut drawn = EntropyResult {
+        bytes: Vec::new(),
+        source: seed.source.clone(),
+    };
+    let mut out = Vec::new();
+    let result = names.iter().try_for_each(|name| {
+        let mut derivation = context::Derivation::new(&seed.bytes, name, config.fips);
+        if labeled {
+            out.extend_from_slice(format!("{}: ", name).as_bytes());
+        }
+        let mut bytes = match style {
+            Some(style) => {
+                let (bytes, text) = style.generate(args.bytes, |n| {
+                    Ok(EntropyResult {
+                        bytes: derivation.generate(n)?,
+                        source: String::new(),
+                    })
+                })?;
+                out.extend_from_slice(text.as_bytes());
+                bytes.bytes
+            }
+            None => {
+                let bytes = derivation.generate(args.bytes)?;
+                output::format_output(&bytes, &args.format, &mut out)?;
+                bytes
+            }
+        };
+        drawn.bytes.extend_from_slice(&bytes);
+        cpurng::zeroize_vec(&mut bytes);
+        Ok(())
+    });
+    cpurng::zeroize_vec(&mut seed.bytes);
+    result.map(|()| (drawn, out))
 }
 
-fn run_generate(cli: &Cli, cpu_config: &CpuRngConfig) {
-    if cli.bytes == 0 {
+fn run_generate(args: &GenerateArgs, config: &Config) {
+    if args.bytes == 0 {
         log::error!("byte count must be greater than 0");
         process::exit(1);
     }
+    let (style, contexts) = match text_style(args).and_then(|s| Ok((s, contexts(args)?))) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            log::error!("{}", e);
+            process::exit(1);
+        }
+    };
 
-    match entropy::generate(cli.bytes, cpu_config) {
-        Ok(result) => {
-            log::info!("entropy source: {}", result.source);
-            if let Err(e) = output::write_output(&result.bytes, &cli.format, cli.output_file.as_deref()) {
-                log::error!("error writing output: {}", e);
+    let deadline = match args.max_wait.as_deref().map(check::parse_duration) {
+        Some(Ok(wait)) => Some(entropy::Deadline::after(wait, args.on_timeout)),
+        Some(Err(e)) => {
+            log::error!("--max-wait: {}", e);
+            process::exit(1);
+        }
+        None => None,
+    };
+
+    let mut sealing = None;
+    if !args.encrypt_to.is_empty() {
+        if config.fips {
+            log::error!("--encrypt-to: X25519 and ChaCha20-Poly1305 are not allowed in FIPS mode");
+            process::exit(1);
+        }
+        match age::load_recipients(&args.encrypt_to).and_then(|r| Ok((r, age::key_rng(config)?))) {
+            Ok(s) => sealing = Some(s),
+            Err(e) => {
+                log::error!("{}", e);
                 process::exit(1);
             }
         }
+    }
+
+    if args.timing {
+        timing::enable();
+    }
+    if args.stats {
+        metrics::enable();
+    }
+    sourcecache::enable(config);
+    restart::enable(config);
+    let mut memfd = None;
+    let mut source = None;
+    let start = Instant::now();
+    // For text, -n counts characters and rejected draws need more bytes
+    let collected = match (&contexts, &style) {
+        (Some((names, labeled)), _) => {
+            generate_contexts(args, style.as_ref(), names, *labeled, config, deadline)
+                .map(|(result, out)| (result, Some(out)))
+        }
+        (None, Some(style)) => style
+            .generate(args.bytes, |n| {
+                entropy::generate_within(n, config, deadline)
+            })
+            .map(|(result, text)| (result, Some(text.into_bytes()))),
+        (None, None) => entropy::generate_within(args.bytes, config, deadline).map(|r| (r, None)),
+    };
+    let status = match collected {
+        Ok((result, formatted)) => {
+            let _locked = secmem::protect(&result.bytes);
+            let _formatted_locked = formatted.as_deref().map(secmem::protect);
+            // Already formatted: text, and context output
+            let (bytes, format) = match formatted {
+                Some(ref formatted) => (&formatted[..], &OutputFormat::Raw),
+                None => (&result.bytes[..], &args.format),
+            };
+            log::info!("entropy source: {}", result.source);
+            source = Some(result.source.clone());
+            let output_file = args.output_file.as_deref();
+            let dest = match (&args.output_memfd, output_file, args.split_size) {
+                (Some(name), _, _) => Destination::Memfd(name),
+                (None, Some(path), Some(size)) => Destination::Split(path, size),
+                (None, Some(path), None) if args.sync => Destination::Atomic(path),
+                (None, Some(path), None) => Destination::File(path),
+                (None, None, _) => Destination::Stdout,
+            };
+            // --verify needs a digest of what was written even without --checksum
+            let digest = args
+                .checksum
+                .or(args.verify.then_some(checksum::Algorithm::Sha256));
+            let written = timing::time(timing::Stage::Format, || match &mut sealing {
+                Some((recipients, rng)) => {
+                    output::write_encrypted(bytes, format, dest, recipients, rng, digest)
+                }
+                None => output::write_output(bytes, format, dest, digest),
+            })
+            .and_then(|written| {
+                if let (Some(algorithm), Some(digest)) = (args.checksum, &written.digest) {
+                    checksum::report(algorithm, digest, output_file, args.checksum_sidecar)?;
+                }
+                if let (false, Some(path)) = (written.parts.is_empty(), output_file) {
+                    let algorithm = args.checksum.unwrap_or(checksum::Algorithm::Sha256);
+                    checksum::report_parts(
+                        algorithm,
+                        &written.parts,
+                        path,
+                        args.checksum.is_some(),
+                    )?;
+                    let next = split::part_path(path, written.parts.len());
+                    if next.exists() {
+                        log::warn!("{} is left from an earlier run", next.display());
+                    }
+                }
+                Ok(written)
+            });
+            match written {
+                Ok(written) => {
+                    memfd = written.memfd;
+                    // Pasted into password fields, so say what it is worth
+                    if let Some(ref style) = style {
+                        eprintln!("{}", style.describe(args.bytes));
+                    }
+                    let verified = match (digest, output_file, &written.digest) {
+                        (Some(algorithm), Some(path), Some(written)) if args.verify => {
+                            verify::run(path, algorithm, written, &result.bytes)
+                        }
+                        _ => Ok(()),
+                    };
+                    match verified {
+                        Ok(()) => 0,
+                        Err(e) => {
+                            log::error!("--verify: {}", e);
+                            1
+                        }
+                    }
+                }
+                Err(e) => {
+                    log::error!("error writing output: {}", e);
+                    1
+                }
+            }
+        }
         Err(e) => {
             log::error!("{}", e);
-            process::exit(1);
+            // Same status as timeout(1), so scripts can tell a missed deadline apart
+            match e {
+                Error::Timeout(_) => 124,
+                _ => 1,
+            }
         }
+    };
+    if args.stats {
+        let source = source.as_deref().unwrap_or("none");
+        eprint!("{}", metrics::report(source, start.elapsed()));
+    }
+    if args.timing {
+        eprint!("{}", timing::report(start.elapsed()));
     }
+    if status != 0 {
+        process::exit(status);
+    }
+    if let Some(fd) = memfd {
+        let e = memfd::exec_with(fd, &args.fd_exec);
+        log::error!("--fd-exec {}: {}", args.fd_exec[0], e);
+        process::exit(1);
+    }
+}
+
+fn generate(args: &GenerateArgs) {
+    logging::init(&args.log, false);
+    lock_memory(args.lock_memory);
+    let config = build_config(args.config_file.as_deref(), &args.cpu_rng);
+    run_generate(args, &config);
 }
 
 fn main() {
-    let cli = Cli::parse();
+    let cli = Cli::parse_args();
 
     match &cli.command {
         Some(Command::Daemon(args)) => {
             logging::init(&args.log, true);
-            let cpu_config =
-                build_cpu_rng_config(args.config_file.as_deref(), &args.cpu_rng);
-            if let Err(e) = daemon::run(args, &cpu_config) {
+            lock_memory(args.lock_memory.unwrap_or(true));
+            let config = build_config(args.config_file.as_deref(), &args.cpu_rng);
+            if let Err(e) = daemon::run(args, &config) {
                 log::error!("{}", e);
-                process::exit(1);
+                process::exit(match e {
+                    Error::Injection(_) => daemon::EXIT_INJECTION_FAILED,
+                    _ => 1,
+                });
             }
         }
         Some(Command::Check(args)) => {
             logging::init(&args.log, false);
-            let cpu_config =
-                build_cpu_rng_config(args.config_file.as_deref(), &args.cpu_rng);
-            if let Err(e) = check::run(args, &cpu_config) {
+            let config = build_config(args.config_file.as_deref(), &args.cpu_rng);
+            if let Err(e) = check::run(args, &config) {
+                log::error!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Calibrate(args)) => {
+            logging::init(&args.log, false);
+            let config = build_config(args.config_file.as_deref(), &args.cpu_rng);
+            if let Err(e) = calibrate::run(args, &config) {
                 log::error!("{}", e);
                 process::exit(1);
             }
         }
-        None => {
-            logging::init(&cli.log, false);
-            let cpu_config =
-                build_cpu_rng_config(cli.config_file.as_deref(), &cli.cpu_rng);
-            run_generate(&cli, &cpu_config);
+        Some(Command::ProfileJitter(args)) => {
+            logging::init(&args.log, false);
+            if let Err(e) = jitterprofile::run(args) {
+                log::error!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Probe(args)) => {
+            logging::init(&args.log, false);
+            let config = build_config(args.config_file.as_deref(), &args.cpu_rng);
+            if let Err(e) = probe::run(args, &config) {
+                log::error!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Doctor(args)) => {
+            logging::init(&args.log, false);
+            let config = build_config(args.config_file.as_deref(), &args.cpu_rng);
+            match doctor::run(args, &config) {
+                Ok(true) => {}
+                Ok(false) => process::exit(1),
+                Err(e) => {
+                    log::error!("{}", e);
+                    process::exit(1);
+                }
+            }
+        }
+        Some(Command::Exec(args)) => {
+            logging::init(&args.log, false);
+            let config = build_config(args.config_file.as_deref(), &args.cpu_rng);
+            if let Err(e) = exec::run(args, &config) {
+                log::error!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Watch(args)) => {
+            logging::init(&args.log, false);
+            if let Err(e) = watch::run(args) {
+                log::error!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Float(args)) => {
+            logging::init(&args.log, false);
+            let config = build_config(args.config_file.as_deref(), &args.cpu_rng);
+            if let Err(e) = float::run(args, &config) {
+                log::error!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Stream(args)) => {
+            logging::init(&args.log, false);
+            let config = build_config(args.config_file.as_deref(), &args.cpu_rng);
+            if let Err(e) = stream::run(args, &config) {
+                log::error!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Analyze(args)) => {
+            logging::init(&args.log, false);
+            if let Err(e) = analyze::run(args) {
+                log::error!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Unframe(args)) => {
+            logging::init(&args.log, false);
+            if let Err(e) = framing::run(args) {
+                log::error!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::VerifyCapture(args)) => {
+            logging::init(&args.log, false);
+            if let Err(e) = capture::run(args) {
+                log::error!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Wipe(args)) => {
+            logging::init(&args.log, false);
+            let config = build_config(args.config_file.as_deref(), &args.cpu_rng);
+            if let Err(e) = wipe::run(args, &config) {
+                log::error!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Beacon(args)) => {
+            logging::init(&args.log, true);
+            let config = build_config(args.config_file.as_deref(), &args.cpu_rng);
+            if let Err(e) = beacon::run(args, &config) {
+                log::error!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Ceremony(args)) => {
+            logging::init(&args.log, false);
+            lock_memory(args.lock_memory.unwrap_or(true));
+            let config = build_config(args.config_file.as_deref(), &args.cpu_rng);
+            if let Err(e) = ceremony::run(args, &config) {
+                log::error!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Input(args)) => {
+            logging::init(&args.log, false);
+            lock_memory(args.lock_memory.unwrap_or(true));
+            if let Err(e) = entropy::input::run(args) {
+                log::error!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::ServeVsock(args)) => {
+            logging::init(&args.log, true);
+            lock_memory(args.lock_memory.unwrap_or(true));
+            let config = build_config(args.config_file.as_deref(), &args.cpu_rng);
+            if let Err(e) = vsock::run(args, &config) {
+                log::error!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::ServeSocket(args)) => {
+            logging::init(&args.log, true);
+            lock_memory(args.lock_memory.unwrap_or(true));
+            let config = build_config(args.config_file.as_deref(), &args.cpu_rng);
+            if let Err(e) = socket::run(args, &config) {
+                log::error!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Client(args)) => {
+            logging::init(&args.log, false);
+            if let Err(e) = client::run(args) {
+                log::error!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Injector(args)) => {
+            logging::init(&args.log, true);
+            lock_memory(args.lock_memory.unwrap_or(true));
+            if let Err(e) = injector::run(args) {
+                log::error!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::AlertRunner(args)) => {
+            if let Err(e) = alerts::run_runner(args) {
+                eprintln!("mixrand alert-runner: {}", e);
+                process::exit(1);
+            }
+        }
+        #[cfg(feature = "grpc")]
+        Some(Command::ServeGrpc(args)) => {
+            logging::init(&args.log, true);
+            lock_memory(args.lock_memory.unwrap_or(true));
+            let config = build_config(args.config_file.as_deref(), &args.cpu_rng);
+            if let Err(e) = grpc::run(args, &config) {
+                log::error!("{}", e);
+                process::exit(1);
+            }
         }
+        Some(Command::Generate(args)) => generate(args),
+        None => generate(&cli.generate),
     }
 }
@@ -0,0 +1,77 @@
+//! `--output-memfd` / `--fd-exec`: one-shot output in a sealed, anonymous
+//! memory file handed to a child process, so a secret never touches the
+//! filesystem or a pipe another process could read.
+
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+/// Seals that freeze the contents: no writes, no resizing, no unsealing.
+const SEALS: libc::c_int =
+    libc::F_SEAL_WRITE | libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_SEAL;
+
+/// Environment variable carrying the descriptor number to `--fd-exec`.
+pub const FD_ENV: &str = "MIXRAND_FD";
+
+/// Creates a memfd called `name` holding `data`, sealed read-only and
+/// positioned at the start. It is not close-on-exec, so children inherit it.
+pub fn create_sealed(name: &str, data: &[u8]) -> io::Result<OwnedFd> {
+    let c_name = CString::new(name)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "memfd name contains NUL"))?;
+    let fd = unsafe { libc::memfd_create(c_name.as_ptr(), libc::MFD_ALLOW_SEALING) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    file.write_all(data)?;
+    file.seek(SeekFrom::Start(0))?;
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_ADD_SEALS, SEALS) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(file.into())
+}
+
+/// `command` with every `{fd}` replaced by the descriptor number.
+pub fn substitute(command: &[String], fd: i32) -> Vec<String> {
+    command
+        .iter()
+        .map(|arg| arg.replace("{fd}", &fd.to_string()))
+        .collect()
+}
+
+/// Replaces this process with `command`, which inherits `fd`. Only returns
+/// if the exec failed.
+pub fn exec_with(fd: OwnedFd, command: &[String]) -> io::Error {
+    let raw = fd.as_raw_fd();
+    let argv = substitute(command, raw);
+    log::info!("running {} with the output on fd {}", argv[0], raw);
+    // Leaked on purpose: it has to stay open across exec
+    std::mem::forget(fd);
+    Command::new(&argv[0])
+        .args(&argv[1..])
+        .env(FD_ENV, raw.to_string())
+        .exec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_sealed_contents() {
+        let fd = create_sealed("mixrand-test", b"secret").unwrap();
+        let mut file = File::from(fd);
+        assert!(file.write_all(b"x").is_err());
+        let mut read = String::new();
+        file.read_to_string(&mut read).unwrap();
+        assert_eq!(read, "secret");
+        assert_eq!(
+            substitute(&["cat".into(), "/proc/self/fd/{fd}".into()], 7),
+            ["cat", "/proc/self/fd/7"]
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! `--stats`: what a one-shot request was made from. Each raw input the
+//! source chain read is counted, with a most-common-value estimate of its
+//! min-entropy. Only byte counts are kept, never the input itself.
+//! Recording is a no-op until [`enable`] is called.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::estimators;
+use crate::table::{self, Align, Table};
+
+/// Samples below which a min-entropy estimate says nothing: the MCV bound
+/// on a few dozen bytes is too loose to read.
+const MIN_SAMPLES: usize = 64;
+
+struct Input {
+    name: String,
+    /// Bytes read from the input, before any oversampling reduction
+    collected: usize,
+    counts: [usize; 256],
+    samples: usize,
+}
+
+#[derive(Default)]
+struct Inputs(Vec<Input>);
+
+impl Inputs {
+    fn add(&mut self, name: &str, collected: usize, sample: &[u8]) {
+        let i = match self.0.iter().position(|i| i.name == name) {
+            Some(i) => i,
+            None => {
+                self.0.push(Input {
+                    name: name.to_string(),
+                    collected: 0,
+                    counts: [0; 256],
+                    samples: 0,
+                });
+                self.0.len() - 1
+            }
+        };
+        let input = &mut self.0[i];
+        input.collected += collected;
+        input.samples += sample.len();
+        for &b in sample {
+            input.counts[b as usize] += 1;
+        }
+    }
+
+    fn render(&self, source: &str, total: Duration) -> String {
+        let mut table = Table::new(&[
+            ("Input", Align::Left),
+            ("Bytes", Align::Right),
+            ("Min-entropy", Align::Right),
+        ]);
+        for i in &self.0 {
+            let min_entropy = if i.samples < MIN_SAMPLES {
+                table::dash().to_string()
+            } else {
+                let estimate = estimators::most_common_value_counts(&i.counts, i.samples);
+                // -log2(1) is -0.0
+                format!("{} bits/byte", table::decimal(estimate + 0.0, 3))
+            };
+            table.row(vec![i.name.clone(), i.collected.to_string(), min_entropy]);
+        }
+        format!(
+            "source: {}\ntime:   {}\n{}",
+            source,
+            table::millis(total, 3),
+            table.render()
+        )
+    }
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static INPUTS: Mutex<Option<Inputs>> = Mutex::new(None);
+
+pub fn enable() {
+    *INPUTS.lock().unwrap_or_else(|e| e.into_inner()) = Some(Inputs::default());
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Records `collected` bytes read from input `name`, of which `sample` is
+/// what it delivered. Repeated inputs add up.
+pub fn input(name: &str, collected: usize, sample: &[u8]) {
+    if !enabled() {
+        return;
+    }
+    if let Some(inputs) = INPUTS.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        inputs.add(name, collected, sample);
+    }
+}
+
+/// Records each of `inputs` in full, as labeled for the mixer, with
+/// `prefix/` before each label.
+pub fn inputs(prefix: &str, inputs: &[(&str, &[u8])]) {
+    if !enabled() {
+        return;
+    }
+    for (label, data) in inputs {
+        input(&format!("{}/{}", prefix, label), data.len(), data);
+    }
+}
+
+/// The source used, the request's `total` time, and every input recorded.
+pub fn report(source: &str, total: Duration) -> String {
+    let guard = INPUTS.lock().unwrap_or_else(|e| e.into_inner());
+    match guard.as_ref() {
+        Some(inputs) => inputs.render(source, total),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut inputs = Inputs::default();
+        inputs.add("hwrng", 16, &[0u8; 16]);
+        inputs.add("hwrng", 16, &[0u8; 16]);
+        inputs.add("fallback/urandom", 32, &[7u8; 32]);
+        inputs.add("fallback/jitter", 128, &[1u8; 128]);
+        let report = inputs.render("test", Duration::from_millis(2));
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "source: test");
+        assert!(lines[3].starts_with("hwrng") && lines[3].contains("32"));
+        assert!(lines[4].contains(table::dash()));
+        // A constant input has no min-entropy
+        assert!(lines[5].starts_with("fallback/jitter") && lines[5].ends_with(" 0.000 bits/byte"));
+    }
+}
@@ -1,33 +1,233 @@
+use std::sync::OnceLock;
+
 use blake2::{
     digest::{consts::U32, Digest},
     Blake2b,
 };
 
+use crate::config::MixerConfig;
+use crate::csprng;
+use crate::entropy::cpurng;
+use crate::timing::{self, Stage};
+
 type Blake2b256 = Blake2b<U32>;
 
-/// Mixes multiple entropy inputs through BLAKE2b-256 with domain separation
-/// and length-prefixed feeding to produce a 32-byte seed.
-pub fn mix_entropy(inputs: &[(&str, &[u8])]) -> [u8; 32] {
-    let mut hasher = Blake2b256::new();
+/// Newest mixer construction; selected when the config doesn't pin one.
+pub const LATEST_VERSION: u32 = 2;
+
+/// Mixer constructions. Each version has its own domain-separation tag, so
+/// seeds from different versions never collide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MixerVersion {
+    /// `mixrand-entropy-v1`: tag, then length-prefixed (label, data) pairs.
+    V1,
+    /// `mixrand-entropy-v2`: tag, length-prefixed personalization string,
+    /// then length-prefixed (label, data) pairs.
+    V2,
+}
+
+impl MixerVersion {
+    /// Maps a configured version number; 0 selects the newest.
+    pub fn from_number(version: u32) -> Option<Self> {
+        match version {
+            0 => Self::from_number(LATEST_VERSION),
+            1 => Some(MixerVersion::V1),
+            2 => Some(MixerVersion::V2),
+            _ => None,
+        }
+    }
+
+    pub fn number(self) -> u32 {
+        match self {
+            MixerVersion::V1 => 1,
+            MixerVersion::V2 => 2,
+        }
+    }
+
+    fn tag(self) -> &'static [u8] {
+        match self {
+            MixerVersion::V1 => b"mixrand-entropy-v1",
+            MixerVersion::V2 => b"mixrand-entropy-v2",
+        }
+    }
+}
+
+/// Versioned BLAKE2b-256 entropy mixer with an optional deployment-specific
+/// personalization string.
+#[derive(Debug, Clone)]
+pub struct Mixer {
+    version: MixerVersion,
+    personalization: Vec<u8>,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self::new(MixerVersion::V2, Vec::new())
+    }
+}
 
-    // Domain separation tag
-    hasher.update(b"mixrand-entropy-v1");
+fn push_field(buf: &mut Vec<u8>, label: &str, data: &[u8]) {
+    buf.extend_from_slice(&(label.len() as u64).to_le_bytes());
+    buf.extend_from_slice(label.as_bytes());
+    buf.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    buf.extend_from_slice(data);
+}
 
-    for (label, data) in inputs {
-        // Length-prefixed label
-        let label_bytes = label.as_bytes();
-        hasher.update(&(label_bytes.len() as u64).to_le_bytes());
-        hasher.update(label_bytes);
+fn read_trimmed(path: &str) -> Option<Vec<u8>> {
+    let s = std::fs::read_to_string(path).ok()?;
+    let s = s.trim();
+    (!s.is_empty()).then(|| s.as_bytes().to_vec())
+}
 
-        // Length-prefixed data
-        hasher.update(&(data.len() as u64).to_le_bytes());
-        hasher.update(data);
+impl Mixer {
+    pub fn new(version: MixerVersion, personalization: Vec<u8>) -> Self {
+        Self {
+            version,
+            personalization,
+        }
     }
 
-    let result = hasher.finalize();
-    let mut seed = [0u8; 32];
-    seed.copy_from_slice(&result);
-    seed
+    /// Builds the mixer described by `[mixer]`, reading the hostname and
+    /// machine-id if host personalization is enabled.
+    pub fn from_config(config: &MixerConfig) -> Self {
+        let version = MixerVersion::from_number(config.version).unwrap_or(MixerVersion::V2);
+
+        let mut personalization = Vec::new();
+        if !config.personalization.is_empty() {
+            push_field(
+                &mut personalization,
+                "config",
+                config.personalization.as_bytes(),
+            );
+        }
+        if config.host_personalization {
+            for (label, path) in [
+                ("hostname", "/proc/sys/kernel/hostname"),
+                ("machine-id", "/etc/machine-id"),
+            ] {
+                if let Some(value) = read_trimmed(path) {
+                    push_field(&mut personalization, label, &value);
+                }
+            }
+        }
+
+        if version == MixerVersion::V1 && !personalization.is_empty() {
+            log::warn!("mixer v1 does not support personalization; ignoring it");
+            personalization.clear();
+        }
+        Self::new(version, personalization)
+    }
+
+    pub fn version(&self) -> MixerVersion {
+        self.version
+    }
+
+    /// A hasher that has taken the domain separation tag and, from v2, the
+    /// personalization string.
+    fn start(&self) -> Blake2b256 {
+        let mut hasher = Blake2b256::new();
+        hasher.update(self.version.tag());
+        if self.version == MixerVersion::V2 {
+            hasher.update((self.personalization.len() as u64).to_le_bytes());
+            hasher.update(&self.personalization);
+        }
+        hasher
+    }
+
+    /// An empty [`Sponge`] with this construction.
+    pub fn sponge(&self) -> Sponge {
+        Sponge {
+            mixer: self.clone(),
+            hasher: self.start(),
+            extracts: 0,
+        }
+    }
+
+    /// Mixes multiple entropy inputs through BLAKE2b-256 with domain
+    /// separation and length-prefixed feeding to produce a 32-byte seed.
+    pub fn mix(&self, inputs: &[(&str, &[u8])]) -> [u8; 32] {
+        let mut sponge = self.sponge();
+        for (label, data) in inputs {
+            sponge.absorb(label, data);
+        }
+        sponge.extract()
+    }
+}
+
+/// Incremental form of [`Mixer::mix`], for inputs collected over time:
+/// absorbs labelled inputs as they arrive, without buffering them, and
+/// extracts 32-byte seeds on demand. The first extract equals `mix` over
+/// the same inputs. Each extract also ratchets the state: the next one
+/// starts from a second, independent hash of the state, so the seeds
+/// already handed out cannot be recovered from a later copy of it.
+pub struct Sponge {
+    mixer: Mixer,
+    hasher: Blake2b256,
+    extracts: u64,
+}
+
+impl Sponge {
+    /// Absorbs `data` as a length-prefixed (label, data) pair.
+    pub fn absorb(&mut self, label: &str, data: &[u8]) {
+        self.hasher.update((label.len() as u64).to_le_bytes());
+        self.hasher.update(label.as_bytes());
+        self.hasher.update((data.len() as u64).to_le_bytes());
+        self.hasher.update(data);
+    }
+
+    /// A seed from everything absorbed since the last extract, and from
+    /// the state before it.
+    pub fn extract(&mut self) -> [u8; 32] {
+        let mut ratchet = self.hasher.clone();
+        ratchet.update(b"ratchet");
+        let mut chain: [u8; 32] = ratchet.finalize().into();
+        let seed: [u8; 32] = self.hasher.finalize_reset().into();
+
+        self.hasher = self.mixer.start();
+        self.extracts += 1;
+        self.absorb("chain", &chain);
+        cpurng::zeroize_bytes(&mut chain);
+        seed
+    }
+
+    /// `count` bytes from ChaCha20 keyed with an extracted seed.
+    pub fn expand(&mut self, count: usize) -> Vec<u8> {
+        let mut seed = self.extract();
+        let output = csprng::generate(seed, count);
+        cpurng::zeroize_bytes(&mut seed);
+        output
+    }
+
+    /// Seeds extracted so far.
+    pub fn extracts(&self) -> u64 {
+        self.extracts
+    }
+}
+
+static ACTIVE: OnceLock<Mixer> = OnceLock::new();
+
+/// Installs the process-wide mixer from `[mixer]`. Only the first call takes
+/// effect; until then [`mix_entropy`] uses the newest version without
+/// personalization.
+pub fn init(config: &MixerConfig) {
+    let mixer = ACTIVE.get_or_init(|| Mixer::from_config(config));
+    log::debug!(
+        "mixer v{} ({} personalization bytes)",
+        mixer.version().number(),
+        mixer.personalization.len()
+    );
+}
+
+/// Mixes `inputs` with the process-wide mixer.
+pub fn mix_entropy(inputs: &[(&str, &[u8])]) -> [u8; 32] {
+    timing::time(Stage::Mix, || {
+        ACTIVE.get_or_init(Mixer::default).mix(inputs)
+    })
+}
+
+/// An empty [`Sponge`] with the process-wide mixer.
+pub fn sponge() -> Sponge {
+    ACTIVE.get_or_init(Mixer::default).sponge()
 }
 
 #[cfg(test)]
@@ -68,4 +268,84 @@ mod tests {
         let b = mix_entropy(&[("y", b"2"), ("x", b"1")]);
         assert_ne!(a, b);
     }
+
+    #[test]
+    fn test_v1_matches_legacy_construction() {
+        let mut hasher = Blake2b256::new();
+        hasher.update(b"mixrand-entropy-v1");
+        hasher.update(5u64.to_le_bytes());
+        hasher.update(b"label");
+        hasher.update(4u64.to_le_bytes());
+        hasher.update(b"data");
+        let expected: [u8; 32] = hasher.finalize().into();
+
+        let v1 = Mixer::new(MixerVersion::V1, Vec::new());
+        assert_eq!(v1.mix(&[("label", b"data")]), expected);
+    }
+
+    #[test]
+    fn test_sponge() {
+        let mixer = Mixer::new(MixerVersion::V2, b"host-a".to_vec());
+        let mut sponge = mixer.sponge();
+        sponge.absorb("x", b"1");
+        sponge.absorb("y", b"2");
+        let first = sponge.extract();
+        assert_eq!(first, mixer.mix(&[("x", b"1"), ("y", b"2")]));
+
+        // Same inputs again: the ratchet makes the next seed differ, and
+        // it is not simply mix() of the previous seed
+        sponge.absorb("x", b"1");
+        sponge.absorb("y", b"2");
+        let second = sponge.extract();
+        assert_ne!(second, first);
+        assert_ne!(
+            second,
+            mixer.mix(&[("chain", &first), ("x", b"1"), ("y", b"2")])
+        );
+        assert_eq!(sponge.extracts(), 2);
+
+        // Deterministic for the same history
+        let mut again = mixer.sponge();
+        again.absorb("x", b"1");
+        again.absorb("y", b"2");
+        again.extract();
+        again.absorb("x", b"1");
+        again.absorb("y", b"2");
+        assert_eq!(again.extract(), second);
+        assert_ne!(again.expand(64)[..32], second);
+    }
+
+    #[test]
+    fn test_versions_and_personalization_separate() {
+        let inputs: &[(&str, &[u8])] = &[("label", b"data")];
+        let v1 = Mixer::new(MixerVersion::V1, Vec::new()).mix(inputs);
+        let v2 = Mixer::new(MixerVersion::V2, Vec::new()).mix(inputs);
+        let host_a = Mixer::new(MixerVersion::V2, b"host-a".to_vec()).mix(inputs);
+        let host_b = Mixer::new(MixerVersion::V2, b"host-b".to_vec()).mix(inputs);
+        assert_ne!(v1, v2);
+        assert_ne!(v2, host_a);
+        assert_ne!(host_a, host_b);
+    }
+
+    #[test]
+    fn test_from_config() {
+        assert_eq!(MixerVersion::from_number(0), Some(MixerVersion::V2));
+        assert_eq!(MixerVersion::from_number(3), None);
+
+        let pinned = Mixer::from_config(&MixerConfig {
+            version: 1,
+            personalization: "rack-7".into(),
+            host_personalization: true,
+        });
+        assert_eq!(pinned.version(), MixerVersion::V1);
+        assert!(pinned.personalization.is_empty());
+
+        let latest = Mixer::from_config(&MixerConfig {
+            version: 0,
+            personalization: "rack-7".into(),
+            host_personalization: false,
+        });
+        assert_eq!(latest.version(), MixerVersion::V2);
+        assert!(latest.personalization.ends_with(b"rack-7"));
+    }
 }
@@ -1,35 +1,201 @@
-use std::fs::File;
+use std::fs::{self, File, OpenOptions};
 use std::io::{self, BufWriter, Write};
+use std::os::unix::io::OwnedFd;
 use std::path::Path;
 
 use base64::Engine;
 use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use rand_core::RngCore;
 
+use crate::age::{self, Recipient};
+use crate::checksum::{self, Tee};
 use crate::cli::OutputFormat;
+use crate::entropy::{cpurng, EntropyResult};
+use crate::error::Error;
+use crate::fips;
+use crate::framing;
+use crate::memfd;
+use crate::secmem;
+use crate::split::{self, SplitSize};
+use crate::text::Style;
 
-/// Writes the random bytes to stdout or a file in the specified format.
+/// Lowercase hex encoding of `bytes`.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Where one-shot output goes.
+#[derive(Debug, Clone, Copy)]
+pub enum Destination<'a> {
+    Stdout,
+    File(&'a Path),
+    /// `--sync`: written to a temporary file next to the path, which is
+    /// renamed over it once complete, then the directory is fsynced
+    Atomic(&'a Path),
+    /// A sealed memfd with this name, for `--fd-exec`
+    Memfd(&'a str),
+    /// `--split-size`: numbered parts of the path, each digested with
+    /// `checksum` (SHA-256 by default) instead of the whole
+    Split(&'a Path, SplitSize),
+}
+
+/// What was written: the digest when `checksum` is set, the memfd for
+/// [`Destination::Memfd`], and the parts for [`Destination::Split`].
+pub struct Written {
+    pub digest: Option<String>,
+    pub memfd: Option<OwnedFd>,
+    pub parts: Vec<split::Part>,
+}
+
+/// Writes the random bytes to `dest` in the specified format.
 pub fn write_output(
     bytes: &[u8],
     format: &OutputFormat,
-    output_file: Option<&Path>,
-) -> io::Result<()> {
-    match output_file {
-        Some(path) => {
+    dest: Destination,
+    checksum: Option<checksum::Algorithm>,
+) -> io::Result<Written> {
+    fips::check_operational().map_err(|e| io::Error::other(e.to_string()))?;
+    write_to(dest, checksum, |out| {
+        format_output(bytes, format, out)
+    })
+}
+
+/// Like [`write_output`], but the formatted bytes are sealed to
+/// `recipients` in memory and only the armored ciphertext is written.
+pub fn write_encrypted(
+    bytes: &[u8],
+    format: &OutputFormat,
+    dest: Destination,
+    recipients: &[Recipient],
+    rng: &mut dyn RngCore,
+    checksum: Option<checksum::Algorithm>,
+) -> io::Result<Written> {
+    fips::check_operational().map_err(|e| io::Error::other(e.to_string()))?;
+    let mut plaintext = Vec::new();
+    format_output(bytes, format, &mut plaintext)?;
+    let sealed = {
+        let _locked = secmem::protect(&plaintext);
+        age::encrypt(recipients, &plaintext, rng)
+    };
+    cpurng::zeroize_vec(&mut plaintext);
+    let armored = age::armor(&sealed.map_err(|e| io::Error::other(e.to_string()))?);
+    write_to(dest, checksum, |out| {
+        out.write_all(armored.as_bytes())
+    })
+}
+
+fn write_to(
+    dest: Destination,
+    checksum: Option<checksum::Algorithm>,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<Written> {
+    let mut parts = Vec::new();
+    let (digest, memfd) = match dest {
+        Destination::File(path) => {
             let f = File::create(path)?;
-            let mut out = BufWriter::new(f);
-            format_output(bytes, format, &mut out)?;
-            out.flush()
+            let regular = f.metadata()?.is_file();
+            match write_file(&f, regular, checksum, write) {
+                Ok(digest) => (digest, None),
+                // Not a device or pipe the user pointed us at
+                Err(e) if regular => {
+                    drop(f);
+                    let _ = fs::remove_file(path);
+                    return Err(failed(path, e, Some("removed the partial file")));
+                }
+                Err(e) => return Err(failed(path, e, None)),
+            }
+        }
+        Destination::Atomic(path) => {
+            let mut tmp_name = path.as_os_str().to_owned();
+            tmp_name.push(".tmp");
+            let tmp = Path::new(&tmp_name);
+            let _ = fs::remove_file(tmp);
+            let f = OpenOptions::new().write(true).create_new(true).open(tmp)?;
+            let written = write_file(&f, true, checksum, write);
+            drop(f);
+            let digest = written
+                .and_then(|digest| fs::rename(tmp, path).map(|()| digest))
+                .map_err(|e| {
+                    let _ = fs::remove_file(tmp);
+                    failed(path, e, Some("left it unchanged"))
+                })?;
+            let dir = path.parent().filter(|d| !d.as_os_str().is_empty());
+            File::open(dir.unwrap_or(Path::new(".")))
+                .and_then(|d| d.sync_all())
+                .map_err(|e| failed(path, e, Some("written, but its directory not synced")))?;
+            (digest, None)
         }
-        None => {
+        Destination::Stdout => {
             let stdout = io::stdout();
-            let mut out = stdout.lock();
-            format_output(bytes, format, &mut out)?;
-            out.flush()
+            let mut out = Tee::new(stdout.lock(), checksum);
+            write(&mut out)?;
+            out.flush()?;
+            (out.finish(), None)
         }
+        Destination::Memfd(name) => {
+            let mut buf = Vec::new();
+            let mut out = Tee::new(&mut buf, checksum);
+            let written = write(&mut out);
+            let digest = out.finish();
+            let fd = written.and_then(|()| memfd::create_sealed(name, &buf));
+            cpurng::zeroize_vec(&mut buf);
+            (digest, Some(fd?))
+        }
+        Destination::Split(path, size) => {
+            let algorithm = checksum.unwrap_or(checksum::Algorithm::Sha256);
+            let mut out = split::Writer::new(path, size, algorithm);
+            match write(&mut out).and_then(|()| out.finish()) {
+                Ok(written) => parts = written,
+                Err(e) => {
+                    out.discard();
+                    return Err(failed(out.part(), e, Some("removed the parts written")));
+                }
+            }
+            (None, None)
+        }
+    };
+    Ok(Written {
+        digest,
+        memfd,
+        parts,
+    })
+}
+
+/// Writes to `f` through `write`, then flushes and, for a regular file,
+/// fsyncs it, so a full disk or failed writeback is reported here instead
+/// of leaving a silently truncated key file.
+fn write_file(
+    f: &File,
+    regular: bool,
+    checksum: Option<checksum::Algorithm>,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<Option<String>> {
+    let mut out = Tee::new(BufWriter::new(f), checksum);
+    write(&mut out)?;
+    out.flush()?;
+    let digest = out.finish();
+    if regular {
+        f.sync_all()?;
     }
+    Ok(digest)
+}
+
+/// `e` from writing `path`, naming a full disk explicitly and saying what
+/// was done about the file.
+fn failed(path: &Path, e: io::Error, outcome: Option<&str>) -> io::Error {
+    let why = match e.raw_os_error() {
+        Some(libc::ENOSPC) => "no space left on device".to_string(),
+        _ => e.to_string(),
+    };
+    let message = match outcome {
+        Some(outcome) => format!("{}: {}; {}", path.display(), why, outcome),
+        None => format!("{}: {}", path.display(), why),
+    };
+    io::Error::new(e.kind(), message)
 }
 
-fn format_output(bytes: &[u8], format: &OutputFormat, out: &mut dyn Write) -> io::Result<()> {
+/// Encodes `bytes` in `format`, as one-shot output prints them.
+pub fn format_output(bytes: &[u8], format: &OutputFormat, out: &mut dyn Write) -> io::Result<()> {
     match format {
         OutputFormat::Hex => {
             for b in bytes {
@@ -55,8 +221,13 @@ fn format_output(bytes: &[u8], format: &OutputFormat, out: &mut dyn Write) -> io
         OutputFormat::Uuencode => {
             write_uuencode(bytes, out)?;
         }
+        // -n counts characters, which bytes already collected cannot
+        // promise once rejected draws are dropped
         OutputFormat::Text => {
-            write_printable_text(bytes, out)?;
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "-f text is drawn a character at a time, not encoded from bytes",
+            ));
         }
         OutputFormat::Octal => {
             let parts: Vec<String> = bytes.iter().map(|b| format!("{:03o}", b)).collect();
@@ -66,19 +237,42 @@ fn format_output(bytes: &[u8], format: &OutputFormat, out: &mut dyn Write) -> io
             let parts: Vec<String> = bytes.iter().map(|b| format!("{:08b}", b)).collect();
             writeln!(out, "{}", parts.join(" "))?;
         }
+        OutputFormat::Framed => {
+            let mut framed = Vec::new();
+            framing::encode(bytes, &mut 0, &mut framed);
+            let result = out.write_all(&framed);
+            cpurng::zeroize_vec(&mut framed);
+            result?;
+        }
     }
     Ok(())
 }
 
-/// Maps random bytes into printable ASCII characters (33..=126, i.e. '!' through '~').
-fn write_printable_text(bytes: &[u8], out: &mut dyn Write) -> io::Result<()> {
-    // 94 printable ASCII characters: '!' (33) through '~' (126)
-    for &b in bytes {
-        let ch = (b % 94) + 33;
-        out.write_all(&[ch])?;
+/// Draws `count` bytes from `collect` and encodes them in `format`. For
+/// `-f text`, `count` is characters: printable ASCII through
+/// [`Style::printable`], calling `collect` again to replace rejected
+/// draws. Returns every byte drawn and the encoded output.
+pub fn draw(
+    count: usize,
+    format: &OutputFormat,
+    mut collect: impl FnMut(usize) -> Result<EntropyResult, Error>,
+) -> Result<(EntropyResult, Vec<u8>), Error> {
+    if let OutputFormat::Text = format {
+        let (drawn, text) = Style::printable().generate(count, collect)?;
+        return Ok((drawn, text.into_bytes()));
     }
-    writeln!(out)?;
-    Ok(())
+    let mut drawn = collect(count)?;
+    let mut out = Vec::new();
+    let formatted = {
+        let _locked = secmem::protect(&drawn.bytes);
+        format_output(&drawn.bytes, format, &mut out)
+    };
+    if let Err(e) = formatted {
+        cpurng::zeroize_vec(&mut drawn.bytes);
+        cpurng::zeroize_vec(&mut out);
+        return Err(e.into());
+    }
+    Ok((drawn, out))
 }
 
 /// Writes bytes in traditional uuencode format.
@@ -119,6 +313,44 @@ fn write_uuencode(bytes: &[u8], out: &mut dyn Write) -> io::Result<()> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_disk_full_is_explicit() {
+        let full = Path::new("/dev/full");
+        if !full.exists() {
+            return;
+        }
+        let err = write_output(
+            &[1u8; 1 << 16],
+            &OutputFormat::Raw,
+            Destination::File(full),
+            None,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.to_string(), "/dev/full: no space left on device");
+        // A device is never removed
+        assert!(full.exists());
+    }
+
+    #[test]
+    fn test_atomic_replaces_file() {
+        let path = std::env::temp_dir().join("mixrand_test_atomic.txt");
+        fs::write(&path, "old").unwrap();
+        let written = write_output(
+            &[0xab],
+            &OutputFormat::Hex,
+            Destination::Atomic(&path),
+            Some(checksum::Algorithm::Sha256),
+        )
+        .unwrap();
+        assert!(written.digest.is_some());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "ab\n");
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        assert!(!Path::new(&tmp).exists());
+        fs::remove_file(&path).unwrap();
+    }
+
     fn format_to_string(bytes: &[u8], fmt: &OutputFormat) -> String {
         let mut buf = Vec::new();
         format_output(bytes, fmt, &mut buf).unwrap();
@@ -131,6 +363,12 @@ mod tests {
         assert_eq!(out, "deadbeef\n");
     }
 
+    #[test]
+    fn test_to_hex() {
+        assert_eq!(to_hex(&[0x00, 0x0f, 0xff]), "000fff");
+        assert_eq!(to_hex(&[]), "");
+    }
+
     #[test]
     fn test_hex_upper() {
         let out = format_to_string(&[0xde, 0xad, 0xbe, 0xef], &OutputFormat::HexUpper);
@@ -158,7 +396,7 @@ mod tests {
         // base64url should not contain + or /
         assert!(!out.contains('+'));
         assert!(!out.contains('/'));
-        assert!(out.trim().len() > 0);
+        assert!(!out.trim().is_empty());
     }
 
     #[test]
@@ -174,11 +412,23 @@ mod tests {
     }
 
     #[test]
-    fn test_text() {
-        let out = format_to_string(&[0, 93, 94], &OutputFormat::Text);
-        // Each byte maps to (b % 94) + 33
+    fn test_draw_text() {
+        // Bytes below 188 map to (b % 94) + 33; the rest are rejected and
+        // replaced from the next call
         // 0 → 33 = '!', 93 → 126 = '~', 94 → 33 = '!'
-        assert_eq!(out, "!~!\n");
+        let mut batches = vec![vec![200u8; 8], vec![0, 93, 200, 94, 5, 6, 7, 8]].into_iter();
+        let (drawn, out) = draw(3, &OutputFormat::Text, |_| {
+            Ok(EntropyResult {
+                bytes: batches.next().unwrap(),
+                source: "test".into(),
+            })
+        })
+        .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "!~!\n");
+        assert_eq!(drawn.bytes.len(), 16);
+
+        let mut out = Vec::new();
+        assert!(format_output(&[0, 93], &OutputFormat::Text, &mut out).is_err());
     }
 
     #[test]
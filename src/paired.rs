@@ -0,0 +1,340 @@
+//! `check --paired`: every source sampled in the same lockstep rounds, so
+//! each pair can be compared round by round with paired tests instead of
+//! by eyeballing the averages of independent streams: Wilcoxon signed-rank
+//! on per-sample latency and min-entropy, McNemar on per-sample FIPS
+//! pass/fail. Load or CPU frequency changes during the run hit both sides
+//! of a pair alike and cancel out.
+
+use std::time::Duration;
+
+use crate::stats;
+use crate::table;
+
+/// Non-zero differences a signed-rank test needs before its normal
+/// approximation means anything.
+const MIN_PAIRS: usize = 10;
+
+/// Discordant pairs up to which McNemar's test is computed exactly.
+const MAX_EXACT: u64 = 50;
+
+/// Family-wise significance level, split across every test of a run.
+pub const ALPHA: f64 = 0.05;
+
+/// One source's result in one round.
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    pub latency: Duration,
+    pub min_entropy: f64,
+    /// Whether every FIPS window the sample completed passed, if it
+    /// completed one.
+    pub fips: Option<bool>,
+}
+
+/// Wilcoxon signed-rank test of paired differences `a - b`.
+#[derive(Debug, Clone, Copy)]
+pub struct SignedRank {
+    /// Median difference, zeros included.
+    pub median: f64,
+    pub z: f64,
+    pub p: f64,
+}
+
+/// Signed-rank test of `diffs`, with average ranks for ties and a
+/// continuity correction. Zero differences are dropped; `None` with fewer
+/// than [`MIN_PAIRS`] left.
+pub fn signed_rank(diffs: &[f64]) -> Option<SignedRank> {
+    let mut nonzero: Vec<f64> = diffs.iter().copied().filter(|d| *d != 0.0).collect();
+    if nonzero.len() < MIN_PAIRS {
+        return None;
+    }
+    nonzero.sort_by(|a, b| a.abs().total_cmp(&b.abs()));
+    let n = nonzero.len() as f64;
+    let (mut w_plus, mut ties) = (0.0, 0.0);
+    let mut i = 0;
+    while i < nonzero.len() {
+        let j = i + nonzero[i..]
+            .iter()
+            .take_while(|d| d.abs() == nonzero[i].abs())
+            .count();
+        // Ranks i+1..=j share their average
+        let rank = (i + 1 + j) as f64 / 2.0;
+        w_plus += rank * nonzero[i..j].iter().filter(|d| **d > 0.0).count() as f64;
+        let t = (j - i) as f64;
+        ties += t * t * t - t;
+        i = j;
+    }
+    let mean = n * (n + 1.0) / 4.0;
+    let var = n * (n + 1.0) * (2.0 * n + 1.0) / 24.0 - ties / 48.0;
+    let diff = w_plus - mean;
+    let z = (diff - 0.5 * diff.signum()) / var.sqrt();
+    let mut sorted = diffs.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let mid = sorted.len() / 2;
+    let median = if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    };
+    Some(SignedRank {
+        median,
+        z,
+        p: 2.0 * (1.0 - stats::normal_cdf(z.abs())),
+    })
+}
+
+/// McNemar's test over the rounds where exactly one side failed.
+#[derive(Debug, Clone, Copy)]
+pub struct McNemar {
+    /// Rounds only `a` failed.
+    pub only_a: u64,
+    /// Rounds only `b` failed.
+    pub only_b: u64,
+    pub p: f64,
+}
+
+/// Two-sided McNemar test: exact binomial up to [`MAX_EXACT`] discordant
+/// rounds, continuity-corrected chi-square above.
+pub fn mcnemar(only_a: u64, only_b: u64) -> McNemar {
+    let n = only_a + only_b;
+    let p = if n == 0 {
+        1.0
+    } else if n <= MAX_EXACT {
+        let mut pmf = 0.5f64.powi(n as i32);
+        let mut tail = 0.0;
+        for k in 0..=only_a.min(only_b) {
+            tail += pmf;
+            pmf *= (n - k) as f64 / (k + 1) as f64;
+        }
+        (2.0 * tail).min(1.0)
+    } else {
+        let d = (only_a as f64 - only_b as f64).abs() - 1.0;
+        let chi = d.max(0.0).powi(2) / n as f64;
+        2.0 * (1.0 - stats::normal_cdf(chi.sqrt()))
+    };
+    McNemar { only_a, only_b, p }
+}
+
+/// Sources `a` and `b` compared over the rounds both delivered in.
+#[derive(Debug, Clone, Copy)]
+pub struct Comparison {
+    pub a: usize,
+    pub b: usize,
+    pub rounds: usize,
+    pub latency: Option<SignedRank>,
+    pub min_entropy: Option<SignedRank>,
+    /// `None` unless some round gave both a FIPS verdict.
+    pub fips: Option<McNemar>,
+}
+
+impl Comparison {
+    /// P-values of the tests that ran.
+    fn p_values(&self) -> impl Iterator<Item = f64> {
+        let ranks = [self.latency, self.min_entropy];
+        ranks
+            .into_iter()
+            .flatten()
+            .map(|r| r.p)
+            .chain(self.fips.map(|m| m.p))
+    }
+}
+
+/// Every source's sample in each round, `None` where it failed.
+pub struct Rounds {
+    sources: usize,
+    rounds: Vec<Vec<Option<Sample>>>,
+}
+
+impl Rounds {
+    pub fn new(sources: usize) -> Self {
+        Self {
+            sources,
+            rounds: Vec::new(),
+        }
+    }
+
+    /// Starts the next round.
+    pub fn start(&mut self) {
+        self.rounds.push(vec![None; self.sources]);
+    }
+
+    /// Records `source`'s sample in the current round.
+    pub fn record(&mut self, source: usize, sample: Sample) {
+        if let Some(round) = self.rounds.last_mut() {
+            round[source] = Some(sample);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rounds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rounds.is_empty()
+    }
+
+    /// Every pair of sources, over the rounds both delivered in.
+    pub fn compare(&self) -> Vec<Comparison> {
+        let mut comparisons = Vec::new();
+        for a in 0..self.sources {
+            for b in a + 1..self.sources {
+                let pairs: Vec<(Sample, Sample)> = self
+                    .rounds
+                    .iter()
+                    .filter_map(|r| Some((r[a]?, r[b]?)))
+                    .collect();
+                let diffs = |f: fn(&Sample) -> f64| -> Vec<f64> {
+                    pairs.iter().map(|(x, y)| f(x) - f(y)).collect()
+                };
+                let verdicts: Vec<(bool, bool)> = pairs
+                    .iter()
+                    .filter_map(|(x, y)| Some((x.fips?, y.fips?)))
+                    .collect();
+                let only = |side: fn(&(bool, bool)) -> bool| {
+                    verdicts.iter().filter(|v| side(v)).count() as u64
+                };
+                comparisons.push(Comparison {
+                    a,
+                    b,
+                    rounds: pairs.len(),
+                    latency: signed_rank(&diffs(|s| s.latency.as_secs_f64())),
+                    min_entropy: signed_rank(&diffs(|s| s.min_entropy)),
+                    fips: (!verdicts.is_empty())
+                        .then(|| mcnemar(only(|v| !v.0 && v.1), only(|v| v.0 && !v.1))),
+                });
+            }
+        }
+        comparisons
+    }
+}
+
+/// Tests run across `comparisons`, for the Bonferroni correction.
+pub fn tests(comparisons: &[Comparison]) -> usize {
+    comparisons.iter().map(|c| c.p_values().count()).sum()
+}
+
+/// Per-test significance level after the Bonferroni correction.
+pub fn alpha(comparisons: &[Comparison]) -> f64 {
+    ALPHA / tests(comparisons).max(1) as f64
+}
+
+/// `0.0123`, or `<0.0001`.
+pub fn format_p(p: f64) -> String {
+    if p < 0.0001 {
+        format!("<{}", table::decimal(0.0001, 4))
+    } else {
+        table::decimal(p, 4)
+    }
+}
+
+/// One sentence per significant difference, naming the worse source.
+pub fn verdicts(comparisons: &[Comparison], names: &[&str]) -> Vec<String> {
+    let alpha = alpha(comparisons);
+    let mut found = Vec::new();
+    for c in comparisons {
+        let (a, b) = (names[c.a], names[c.b]);
+        // The first of each pair is the worse side
+        let order = |a_worse: bool| if a_worse { (a, b) } else { (b, a) };
+        if let Some(r) = c.latency.filter(|r| r.p < alpha) {
+            let (slow, fast) = order(r.median > 0.0);
+            found.push(format!(
+                "{} is slower than {}: median {} per sample (p {})",
+                slow,
+                fast,
+                table::millis(Duration::from_secs_f64(r.median.abs()), 3),
+                format_p(r.p)
+            ));
+        }
+        if let Some(r) = c.min_entropy.filter(|r| r.p < alpha) {
+            let (low, high) = order(r.median < 0.0);
+            found.push(format!(
+                "{} has lower min-entropy than {}: median {} bits/byte per sample (p {})",
+                low,
+                high,
+                table::decimal(r.median.abs(), 4),
+                format_p(r.p)
+            ));
+        }
+        if let Some(m) = c.fips.filter(|m| m.p < alpha) {
+            let (worse, better) = order(m.only_a > m.only_b);
+            found.push(format!(
+                "{} fails FIPS more often than {}: {} rounds against {} (p {})",
+                worse,
+                better,
+                m.only_a.max(m.only_b),
+                m.only_a.min(m.only_b),
+                format_p(m.p)
+            ));
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_rank() {
+        // Too few non-zero differences
+        assert!(signed_rank(&[0.0, 1.0, -1.0]).is_none());
+
+        // Symmetric noise: no difference
+        let noise: Vec<f64> = (1..=40)
+            .map(|i| if i % 2 == 0 { i as f64 } else { -(i as f64) })
+            .collect();
+        let r = signed_rank(&noise).unwrap();
+        assert!(r.p > 0.5, "{:?}", r);
+
+        // A consistent shift, with ties
+        let shifted: Vec<f64> = noise.iter().map(|d| d / 40.0 + 2.0).collect();
+        let r = signed_rank(&shifted).unwrap();
+        assert!(r.p < 1e-6 && r.z > 0.0, "{:?}", r);
+        assert!((r.median - 2.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_mcnemar() {
+        assert_eq!(mcnemar(0, 0).p, 1.0);
+        // 0 against 10 discordant rounds: 2 * 0.5^10
+        assert!((mcnemar(0, 10).p - 2.0 / 1024.0).abs() < 1e-12);
+        assert!(mcnemar(5, 6).p > 0.9);
+        assert!(mcnemar(100, 40).p < 1e-4);
+    }
+
+    #[test]
+    fn test_rounds_compare() {
+        let sample = |ms: u64, min_entropy: f64, fips: bool| Sample {
+            latency: Duration::from_millis(ms),
+            min_entropy,
+            fips: Some(fips),
+        };
+        let mut rounds = Rounds::new(3);
+        for i in 0..30u64 {
+            rounds.start();
+            rounds.record(0, sample(10 + i % 3, 7.9, true));
+            rounds.record(
+                1,
+                sample(2 + i % 2, 7.9 + (i % 2) as f64 * 0.001, i % 3 != 0),
+            );
+            // Source 2 misses every other round
+            if i % 2 == 0 {
+                rounds.record(2, sample(10 + i % 3, 7.9, true));
+            }
+        }
+        let c = rounds.compare();
+        assert_eq!(c.len(), 3);
+        assert_eq!((c[0].a, c[0].b, c[0].rounds), (0, 1, 30));
+        assert_eq!(c[1].rounds, 15);
+        let names = ["slow", "fast", "sparse"];
+        let found = verdicts(&c, &names);
+        assert!(
+            found[0].starts_with("slow is slower than fast"),
+            "{:?}",
+            found
+        );
+        assert!(found
+            .iter()
+            .any(|v| v.starts_with("fast fails FIPS more often than slow")));
+        assert_eq!(c[0].fips.unwrap().only_b, 10);
+    }
+}
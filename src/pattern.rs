@@ -0,0 +1,65 @@
+//! `--pattern` for `stream` and `wipe`: deterministic test patterns for
+//! disk testing, through the same writers as random output.
+
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Pattern {
+    /// All 0x00
+    Zeros,
+    /// All 0xff
+    Ones,
+    /// 0x00, 0x01, ... 0xff, repeating; each byte is its offset mod 256
+    Incrementing,
+    /// Random output, as without --pattern
+    #[default]
+    Random,
+}
+
+impl Pattern {
+    /// The name `--pattern` takes.
+    pub fn name(self) -> &'static str {
+        match self {
+            Pattern::Zeros => "zeros",
+            Pattern::Ones => "ones",
+            Pattern::Incrementing => "incrementing",
+            Pattern::Random => "random",
+        }
+    }
+
+    /// Fills `buf`, which starts `offset` bytes into the output, so a chunk
+    /// looks the same wherever it is written from. Not for [`Pattern::Random`].
+    pub fn fill(self, offset: u64, buf: &mut [u8]) {
+        match self {
+            Pattern::Zeros => buf.fill(0),
+            Pattern::Ones => buf.fill(0xff),
+            Pattern::Incrementing => {
+                for (i, b) in buf.iter_mut().enumerate() {
+                    *b = (offset + i as u64) as u8;
+                }
+            }
+            Pattern::Random => unreachable!("random output comes from the entropy pipeline"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incrementing_follows_offset() {
+        let mut whole = [0u8; 600];
+        Pattern::Incrementing.fill(0, &mut whole);
+        assert_eq!(whole[255], 0xff);
+        assert_eq!(whole[256], 0x00);
+
+        let mut tail = [0u8; 100];
+        Pattern::Incrementing.fill(500, &mut tail);
+        assert_eq!(tail[..], whole[500..]);
+
+        let mut ones = [0u8; 4];
+        Pattern::Ones.fill(7, &mut ones);
+        assert_eq!(ones, [0xff; 4]);
+    }
+}
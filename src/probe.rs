@@ -0,0 +1,731 @@
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::cli::ProbeArgs;
+use crate::config::Config;
+use crate::entropy::{self, cpurng, devrandom, exec, hwrng, plugin, serial, vsock};
+use crate::error::Error;
+use crate::sourcecache;
+use crate::table;
+
+/// Linux capability needed for RNDADDENTROPY.
+const CAP_SYS_ADMIN: u32 = 21;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Warn,
+    Fail,
+    /// Not configured or not applicable.
+    Off,
+}
+
+impl Status {
+    fn tag(self) -> &'static str {
+        match self {
+            Status::Ok => " ok ",
+            Status::Warn => "warn",
+            Status::Fail => "fail",
+            Status::Off => "off ",
+        }
+    }
+
+    /// A source that delivered, possibly with warnings.
+    pub fn available(self) -> bool {
+        matches!(self, Status::Ok | Status::Warn)
+    }
+}
+
+/// Result of probing one source or environment aspect.
+pub struct Finding {
+    pub name: &'static str,
+    pub status: Status,
+    pub summary: String,
+    pub details: Vec<String>,
+    pub hints: Vec<String>,
+    /// How long the 32-byte test read took.
+    pub latency: Option<Duration>,
+    /// Kernel driver behind the device (hwrng's `rng_current`).
+    pub driver: Option<String>,
+    /// RNG instructions CPUID advertises.
+    pub flags: Vec<&'static str>,
+}
+
+impl Finding {
+    pub fn new(name: &'static str, status: Status, summary: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            summary: summary.into(),
+            details: Vec::new(),
+            hints: Vec::new(),
+            latency: None,
+            driver: None,
+            flags: Vec::new(),
+        }
+    }
+
+    fn timed(mut self, start: Instant) -> Self {
+        self.latency = Some(start.elapsed());
+        self
+    }
+}
+
+fn read_trim(path: &str) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// `ls -l`-style type and permission string, e.g. `crw-rw----`.
+fn format_mode(meta: &fs::Metadata) -> String {
+    let ft = meta.file_type();
+    let kind = if ft.is_char_device() {
+        'c'
+    } else if ft.is_block_device() {
+        'b'
+    } else if ft.is_dir() {
+        'd'
+    } else if ft.is_symlink() {
+        'l'
+    } else {
+        '-'
+    };
+    let mode = meta.permissions().mode();
+    let mut s = String::with_capacity(10);
+    s.push(kind);
+    for shift in [6, 3, 0] {
+        let bits = (mode >> shift) & 7;
+        s.push(if bits & 4 != 0 { 'r' } else { '-' });
+        s.push(if bits & 2 != 0 { 'w' } else { '-' });
+        s.push(if bits & 1 != 0 { 'x' } else { '-' });
+    }
+    s
+}
+
+fn describe_path(path: &Path) -> Option<String> {
+    let meta = fs::metadata(path).ok()?;
+    Some(format!(
+        "{} {} uid={} gid={}",
+        path.display(),
+        format_mode(&meta),
+        meta.uid(),
+        meta.gid()
+    ))
+}
+
+/// access(2) check against the real uid, as the kernel would for open().
+fn can_access(path: &str, mode: libc::c_int) -> bool {
+    let Ok(c) = CString::new(path) else {
+        return false;
+    };
+    unsafe { libc::access(c.as_ptr(), mode) == 0 }
+}
+
+/// Parses a hex capability mask line (e.g. `CapEff`) from /proc/self/status.
+fn status_field<'a>(status: &'a str, key: &str) -> Option<&'a str> {
+    status
+        .lines()
+        .find_map(|l| l.strip_prefix(key)?.strip_prefix(':'))
+        .map(str::trim)
+}
+
+fn has_capability(status: &str, cap: u32) -> Option<bool> {
+    let mask = u64::from_str_radix(status_field(status, "CapEff")?, 16).ok()?;
+    Some(mask & (1 << cap) != 0)
+}
+
+/// Names the container runtime suggested by PID 1's cgroup path, if any.
+fn container_from_cgroup(cgroup: &str) -> Option<&'static str> {
+    [
+        ("kubepods", "Kubernetes"),
+        ("docker", "Docker"),
+        ("libpod", "Podman"),
+        ("containerd", "containerd"),
+        ("lxc", "LXC"),
+    ]
+    .into_iter()
+    .find(|(needle, _)| cgroup.contains(needle))
+    .map(|(_, name)| name)
+}
+
+fn detect_container() -> Option<&'static str> {
+    if Path::new("/.dockerenv").exists() {
+        return Some("Docker");
+    }
+    if Path::new("/run/.containerenv").exists() {
+        return Some("Podman");
+    }
+    fs::read_to_string("/proc/1/cgroup")
+        .ok()
+        .and_then(|c| container_from_cgroup(&c))
+}
+
+/// PID of the kernel's khwrngd, which feeds /dev/hwrng into the input pool.
+fn find_khwrngd() -> Option<u32> {
+    fs::read_dir("/proc").ok()?.flatten().find_map(|e| {
+        let pid = e.file_name().to_str()?.parse().ok()?;
+        let comm = fs::read_to_string(e.path().join("comm")).ok()?;
+        // Kernel threads have an empty command line
+        let cmdline = fs::read(e.path().join("cmdline")).ok()?;
+        (comm.trim() == "hwrng" && cmdline.is_empty()).then_some(pid)
+    })
+}
+
+/// Describes how the kernel itself consumes /dev/hwrng. `quality` is the
+/// entropy credited per 1024 bits read.
+fn kernel_consumer(quality: Option<u32>, khwrngd: Option<u32>) -> String {
+    match (quality, khwrngd) {
+        (Some(0), _) => "kernel: rng_quality 0, khwrngd does not feed the input pool".into(),
+        (Some(q), Some(pid)) => format!(
+            "kernel: khwrngd (pid {}) feeds the input pool, crediting {}/1024 (rng_quality)",
+            pid, q
+        ),
+        (Some(q), None) => format!("kernel: rng_quality {}, khwrngd not running", q),
+        (None, Some(pid)) => format!("kernel: khwrngd (pid {}) feeds the input pool", pid),
+        (None, None) => "kernel: khwrngd not running".into(),
+    }
+}
+
+fn probe_hwrng(config: &Config) -> Finding {
+    let current = read_trim("/sys/class/misc/hw_random/rng_current");
+    let available = read_trim("/sys/class/misc/hw_random/rng_available");
+
+    let start = Instant::now();
+    let mut f = match hwrng::read_hwrng_within(32, config.timeouts.hwrng_ms) {
+        Ok(_) => Finding::new("hwrng", Status::Ok, "/dev/hwrng readable"),
+        Err(e) => Finding::new("hwrng", Status::Fail, e.to_string()),
+    }
+    .timed(start);
+    f.driver = current.clone().filter(|c| c != "none");
+
+    match (&current, &available) {
+        (Some(cur), avail) => f.details.push(format!(
+            "driver: {} (available: {})",
+            cur,
+            avail.as_deref().unwrap_or("none")
+        )),
+        (None, _) => f
+            .details
+            .push("no hw_random class in sysfs (rng-core not loaded)".into()),
+    }
+
+    match describe_path(Path::new("/dev/hwrng")) {
+        Some(d) => f.details.push(format!("device: {}", d)),
+        None => f.details.push("device: /dev/hwrng missing".into()),
+    }
+
+    let quality = hwrng::quality();
+    if let (Some(q), Some(_)) = (quality, &current) {
+        f.details.push(format!(
+            "declared quality: {}/{} ({} bits per byte), which scales --feed hwrng credit",
+            q,
+            hwrng::QUALITY_SCALE,
+            table::decimal(8.0 * q as f64 / hwrng::QUALITY_SCALE as f64, 2)
+        ));
+    }
+    let khwrngd = find_khwrngd();
+    if current.is_some() {
+        f.details.push(kernel_consumer(quality, khwrngd));
+    }
+    let limit = config.limits.hwrng_bytes_per_sec;
+    if limit > 0 {
+        f.details.push(format!(
+            "read rate capped at {} ([limits] hwrng_bytes_per_sec)",
+            table::rate(limit as f64, config.display.units)
+        ));
+    } else if khwrngd.is_some() && quality != Some(0) {
+        f.hints.push(
+            "khwrngd also reads /dev/hwrng: set [limits] hwrng_bytes_per_sec to leave it headroom"
+                .into(),
+        );
+    }
+
+    if f.status == Status::Fail {
+        if current.is_none() || !Path::new("/dev/hwrng").exists() {
+            f.hints.push(
+                "no hardware RNG driver is bound: load one (e.g. `modprobe virtio-rng` in VMs, \
+                 `modprobe tpm-rng` with a TPM) or add a virtio-rng device to the VM"
+                    .into(),
+            );
+        } else if current.as_deref() == Some("none") {
+            f.hints.push(
+                "no driver selected: echo <driver> > /sys/class/misc/hw_random/rng_current".into(),
+            );
+        } else if !can_access("/dev/hwrng", libc::R_OK) {
+            f.hints.push(
+                "permission denied: run as root or add a udev rule such as \
+                 KERNEL==\"hwrng\", GROUP=\"mixrand\", MODE=\"0640\""
+                    .into(),
+            );
+        }
+    }
+    f
+}
+
+fn probe_serial(config: &Config) -> Finding {
+    let cfg = &config.serial;
+    if !cfg.is_enabled() {
+        return Finding::new("serial", Status::Off, "not configured ([serial] device)");
+    }
+    let start = Instant::now();
+    let mut f = match serial::read_serial(32, cfg) {
+        Ok(_) => Finding::new("serial", Status::Ok, format!("{} readable", cfg.device)),
+        Err(e) => Finding::new("serial", Status::Fail, e.to_string()),
+    }
+    .timed(start);
+    f.details.push(format!(
+        "baud {} framing {:?} min-entropy {}",
+        cfg.baud, cfg.framing, cfg.min_entropy
+    ));
+    match describe_path(Path::new(&cfg.device)) {
+        Some(d) => f.details.push(format!("device: {}", d)),
+        None => f.hints.push(format!(
+            "{} does not exist: check that the dongle is plugged in (dmesg | grep tty)",
+            cfg.device
+        )),
+    }
+    if f.status == Status::Fail && Path::new(&cfg.device).exists() {
+        if !can_access(&cfg.device, libc::R_OK | libc::W_OK) {
+            f.hints.push(
+                "permission denied: add the user to the device's group (usually dialout)".into(),
+            );
+        } else {
+            f.hints.push(
+                "device opened but data was rejected or late: check baud, framing \
+                 (OneRNG needs framing = \"onerng\") and timeout_ms"
+                    .into(),
+            );
+        }
+    }
+    f
+}
+
+fn find_in_path(program: &str) -> Option<String> {
+    if program.contains('/') {
+        return Path::new(program).exists().then(|| program.to_string());
+    }
+    std::env::var("PATH")
+        .ok()?
+        .split(':')
+        .map(|dir| Path::new(dir).join(program))
+        .find(|p| p.is_file())
+        .map(|p| p.display().to_string())
+}
+
+fn probe_exec(config: &Config) -> Finding {
+    let cfg = &config.exec;
+    if !cfg.is_enabled() {
+        return Finding::new("exec", Status::Off, "not configured ([exec] command)");
+    }
+    let start = Instant::now();
+    let mut f = match exec::read_exec(32, cfg) {
+        Ok(_) => Finding::new("exec", Status::Ok, "command produced healthy output"),
+        Err(e) => Finding::new("exec", Status::Fail, e.to_string()),
+    }
+    .timed(start);
+    f.details
+        .push(format!("command: {}", cfg.command.join(" ")));
+    match find_in_path(&cfg.command[0]) {
+        Some(p) => f.details.push(format!("resolved: {}", p)),
+        None => f
+            .hints
+            .push(format!("{} not found in PATH", cfg.command[0])),
+    }
+    f
+}
+
+fn probe_vsock(config: &Config) -> Finding {
+    let cfg = &config.vsock;
+    if !cfg.is_enabled() {
+        return Finding::new("vsock", Status::Off, "not configured ([vsock] port)");
+    }
+    let start = Instant::now();
+    let mut f = match vsock::read_vsock(32, cfg) {
+        Ok(_) => Finding::new(
+            "vsock",
+            Status::Ok,
+            format!("host {}:{} answered", cfg.cid, cfg.port),
+        ),
+        Err(e) => Finding::new("vsock", Status::Fail, e.to_string()),
+    }
+    .timed(start);
+    if f.status == Status::Fail {
+        if !Path::new("/dev/vsock").exists() {
+            f.hints.push(
+                "/dev/vsock missing: give the VM a vsock device (qemu -device \
+                 vhost-vsock-pci,guest-cid=N) and load vmw_vsock_virtio_transport"
+                    .into(),
+            );
+        } else {
+            f.hints.push(format!(
+                "run `mixrand serve-vsock --port {}` on the host",
+                cfg.port
+            ));
+        }
+    }
+    f
+}
+
+/// One finding per loaded plugin and per plugin file that failed to load.
+fn probe_plugins(config: &Config) -> Vec<Finding> {
+    let cfg = &config.plugins;
+    if !cfg.is_enabled() {
+        return vec![Finding::new(
+            "plugins",
+            Status::Off,
+            "disabled (set enabled = true under [plugins])",
+        )];
+    }
+    let registry = plugin::load(cfg);
+    if registry.plugins.is_empty() && registry.failures.is_empty() {
+        return vec![Finding::new(
+            "plugins",
+            Status::Off,
+            format!("none installed in {}", cfg.dir.display()),
+        )];
+    }
+
+    let mut findings = Vec::new();
+    for p in &registry.plugins {
+        let start = Instant::now();
+        let mut f = match plugin::read_plugin(p, 32, cfg) {
+            Ok(_) => Finding::new(&p.name, Status::Ok, "plugin produced healthy output"),
+            Err(e) => Finding::new(&p.name, Status::Fail, e.to_string()),
+        }
+        .timed(start);
+        f.details
+            .push(format!("{} ({})", p.description, p.path.display()));
+        f.details
+            .push(format!("claimed min-entropy {} bits/byte", p.min_entropy));
+        findings.push(f);
+    }
+    for (path, why) in &registry.failures {
+        let mut f = Finding::new(
+            "plugin",
+            Status::Fail,
+            format!("{}: {}", path.display(), why),
+        );
+        f.hints.push(format!(
+            "plugins must be owned by root, not group/world-writable, and export \
+             mixrand_plugin_v1 with ABI {}",
+            plugin::PLUGIN_ABI
+        ));
+        findings.push(f);
+    }
+    findings
+}
+
+fn probe_cpu(config: &Config) -> Finding {
+    let cfg = &config.cpu_rng;
+    let feat = cpurng::cpu_features();
+    let mut f = Finding::new("cpu-rng", Status::Fail, "no usable CPU RNG instruction");
+    let start = Instant::now();
+
+    let candidates = [
+        ("RDSEED", feat.rdseed, cfg.enable_rdseed),
+        ("RDRAND", feat.rdrand, cfg.enable_rdrand),
+        ("XSTORE", feat.xstore, cfg.enable_xstore),
+    ];
+    let mut working = Vec::new();
+    let mut failing = false;
+    for (name, present, enabled) in candidates {
+        if present {
+            f.flags.push(name);
+        }
+        let state = if !present {
+            "not advertised by CPUID".to_string()
+        } else if !enabled {
+            "disabled by config".to_string()
+        } else {
+            let result = match name {
+                "RDSEED" => cpurng::collect_rdseed(32, cfg.rdseed_retries),
+                "RDRAND" => cpurng::collect_rdrand(32, cfg.rdrand_retries),
+                _ => cpurng::collect_xstore(32, cfg.xstore_quality),
+            };
+            match result {
+                Ok(_) => {
+                    working.push(name);
+                    "ok".to_string()
+                }
+                Err(e) => {
+                    failing = true;
+                    e.to_string()
+                }
+            }
+        };
+        f.details.push(format!("{}: {}", name, state));
+    }
+    f.latency = Some(start.elapsed());
+
+    if !working.is_empty() {
+        f.status = if failing { Status::Warn } else { Status::Ok };
+        f.summary = format!("{} working", working.join(", "));
+    }
+    if failing {
+        f.hints.push(
+            "an advertised instruction failed: RDSEED underflows under load; raise \
+             rdseed_retries or prefer = \"rdrand\""
+                .into(),
+        );
+    }
+    if !feat.rdrand && !feat.rdseed && !feat.xstore {
+        f.hints.push(
+            "CPU or hypervisor hides RDRAND/RDSEED: in VMs expose the host CPU model \
+             (e.g. QEMU `-cpu host`)"
+                .into(),
+        );
+    }
+    f
+}
+
+fn probe_devrandom(config: &Config) -> Finding {
+    let start = Instant::now();
+    let f = match devrandom::read_devrandom(32, config.timeouts.devrandom_ms) {
+        Ok((feeder, _)) => Finding::new(
+            "devrandom",
+            Status::Ok,
+            format!("{} readable", feeder.label()),
+        ),
+        Err(e) => {
+            let mut f = Finding::new("devrandom", Status::Off, e.to_string());
+            f.hints.push(
+                "optional: start haveged, jitterentropy-rngd or rngd to enable this source"
+                    .into(),
+            );
+            f
+        }
+    };
+    f.timed(start)
+}
+
+pub fn probe_kernel() -> Finding {
+    let release = unsafe {
+        let mut uts: libc::utsname = std::mem::zeroed();
+        if libc::uname(&mut uts) == 0 {
+            std::ffi::CStr::from_ptr(uts.release.as_ptr())
+                .to_string_lossy()
+                .into_owned()
+        } else {
+            "unknown".into()
+        }
+    };
+    let avail = read_trim("/proc/sys/kernel/random/entropy_avail");
+    let poolsize = read_trim("/proc/sys/kernel/random/poolsize");
+
+    let writable = can_access("/dev/random", libc::W_OK);
+    let mut f = Finding::new(
+        "kernel",
+        if writable { Status::Ok } else { Status::Warn },
+        format!("Linux {}", release),
+    );
+    f.details.push(format!(
+        "entropy_avail {} / poolsize {}",
+        avail.as_deref().unwrap_or("?"),
+        poolsize.as_deref().unwrap_or("?")
+    ));
+    f.details.push(format!(
+        "/dev/random {}",
+        if writable { "writable" } else { "not writable" }
+    ));
+    if !writable {
+        f.hints
+            .push("daemon mode needs root to open /dev/random for writing".into());
+    }
+    f
+}
+
+pub fn probe_environment() -> Finding {
+    let status = fs::read_to_string("/proc/self/status").unwrap_or_default();
+    let euid = unsafe { libc::geteuid() };
+    let sys_admin = has_capability(&status, CAP_SYS_ADMIN);
+    let seccomp = status_field(&status, "Seccomp").unwrap_or("?");
+    let container = detect_container();
+
+    let mut f = Finding::new(
+        "environment",
+        Status::Ok,
+        match container {
+            Some(c) => format!("running in a {} container", c),
+            None => "no container detected".into(),
+        },
+    );
+    f.details.push(format!(
+        "euid {}, CAP_SYS_ADMIN {}, seccomp mode {}",
+        euid,
+        match sys_admin {
+            Some(true) => "yes",
+            Some(false) => "no",
+            None => "?",
+        },
+        seccomp
+    ));
+
+    if container.is_some() {
+        f.status = Status::Warn;
+        f.hints.push(
+            "containers rarely see /dev/hwrng: pass it through (docker run --device /dev/hwrng) \
+             or run mixrand on the host"
+                .into(),
+        );
+    }
+    if sys_admin == Some(false) {
+        f.status = Status::Warn;
+        f.hints
+            .push("without CAP_SYS_ADMIN the daemon cannot credit entropy (RNDADDENTROPY)".into());
+    }
+    f
+}
+
+/// Probes every source, in chain order.
+pub fn probe_sources(config: &Config) -> Vec<Finding> {
+    let mut findings = vec![
+        probe_hwrng(config),
+        probe_serial(config),
+        probe_exec(config),
+        probe_vsock(config),
+    ];
+    findings.extend(probe_plugins(config));
+    findings.extend([probe_cpu(config), probe_devrandom(config)]);
+    findings
+}
+
+/// Probes every source and the runtime environment.
+pub fn probe_all(config: &Config) -> Vec<Finding> {
+    let mut findings = probe_sources(config);
+    findings.extend([probe_kernel(), probe_environment()]);
+    findings
+}
+
+pub fn print_finding(f: &Finding) {
+    println!("[{}] {:<12} {}", f.status.tag(), f.name, f.summary);
+    for d in &f.details {
+        println!("       {}", d);
+    }
+    for h in &f.hints {
+        println!("       hint: {}", h);
+    }
+}
+
+#[derive(Serialize)]
+struct JsonFinding<'a> {
+    source: &'a str,
+    status: &'a str,
+    available: bool,
+    reason: &'a str,
+    latency_ms: Option<f64>,
+    driver: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    flags: &'a [&'static str],
+    details: &'a [String],
+    hints: &'a [String],
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    findings: Vec<JsonFinding<'a>>,
+    selected: Option<String>,
+    error: Option<String>,
+}
+
+fn print_json(findings: &[Finding], selected: &Result<String, Error>) -> Result<(), Error> {
+    let report = JsonReport {
+        findings: findings
+            .iter()
+            .map(|f| JsonFinding {
+                source: f.name,
+                status: f.status.tag().trim(),
+                available: f.status.available(),
+                reason: &f.summary,
+                latency_ms: f.latency.map(|d| d.as_micros() as f64 / 1000.0),
+                driver: f.driver.as_deref(),
+                flags: &f.flags,
+                details: &f.details,
+                hints: &f.hints,
+            })
+            .collect(),
+        selected: selected.as_ref().ok().cloned(),
+        error: selected.as_ref().err().map(|e| e.to_string()),
+    };
+    let json =
+        serde_json::to_string_pretty(&report).map_err(|e| Error::Io(std::io::Error::other(e)))?;
+    println!("{}", json);
+    Ok(())
+}
+
+pub fn run(args: &ProbeArgs, config: &Config) -> Result<(), Error> {
+    // Probing is the way to refresh what one-shot requests remember
+    if let Err(e) = sourcecache::clear(&config.source_cache) {
+        log::warn!("source cache {}: {}", config.source_cache.file.display(), e);
+    }
+    let findings = probe_all(config);
+    let selected = entropy::generate(32, config).map(|r| r.source);
+    if args.json {
+        return print_json(&findings, &selected);
+    }
+
+    for f in &findings {
+        print_finding(f);
+    }
+    println!();
+    match selected {
+        Ok(source) => {
+            println!("Selected source: {}", source);
+            if source.starts_with("fallback") {
+                println!("Only the fallback mixer is usable; see the hints above.");
+            }
+        }
+        Err(e) => println!("No source usable: {}", e),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUS: &str = "Name:\tmixrand\nSeccomp:\t2\nCapEff:\t0000000000200000\n";
+
+    #[test]
+    fn test_status_fields() {
+        assert_eq!(status_field(STATUS, "Seccomp"), Some("2"));
+        assert_eq!(has_capability(STATUS, CAP_SYS_ADMIN), Some(true));
+        assert_eq!(has_capability(STATUS, 0), Some(false));
+        assert_eq!(has_capability("Name:\tx\n", CAP_SYS_ADMIN), None);
+    }
+
+    #[test]
+    fn test_container_from_cgroup() {
+        assert_eq!(
+            container_from_cgroup("0::/kubepods/besteffort/pod1/abc"),
+            Some("Kubernetes")
+        );
+        assert_eq!(
+            container_from_cgroup("12:pids:/docker/0123abcd"),
+            Some("Docker")
+        );
+        assert_eq!(container_from_cgroup("0::/init.scope"), None);
+    }
+
+    #[test]
+    fn test_kernel_consumer() {
+        assert_eq!(
+            kernel_consumer(Some(1024), Some(45)),
+            "kernel: khwrngd (pid 45) feeds the input pool, crediting 1024/1024 (rng_quality)"
+        );
+        assert!(kernel_consumer(Some(0), Some(45)).contains("does not feed"));
+        assert_eq!(kernel_consumer(None, None), "kernel: khwrngd not running");
+    }
+
+    #[test]
+    fn test_format_mode() {
+        let meta = fs::metadata("/dev/null").unwrap();
+        assert_eq!(format_mode(&meta), "crw-rw-rw-");
+    }
+}
@@ -0,0 +1,255 @@
+//! The daemon's background quality check (`[quality_check]`): draws a
+//! sample from the injection feed every `interval_secs`, FIPS 140-2 tests it
+//! and estimates its min-entropy, and alerts once `alert_after` checks in a
+//! row fail.
+
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::config::QualityCheckConfig;
+use crate::entropy::cpurng;
+use crate::error::Error;
+use crate::http::Endpoint;
+use crate::signals;
+use crate::stats;
+
+const FIPS_WINDOW_BYTES: usize = 2500;
+
+const POST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Outcome of one check, sent to the daemon loop for the status file.
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// Why the check failed; `None` if it passed.
+    pub failure: Option<String>,
+    /// Set on the check that raised an alert or cleared one.
+    pub transition: Option<Transition>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transition {
+    Degraded,
+    Recovered,
+}
+
+/// FIPS failures and min-entropy of one sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Assessment {
+    windows: usize,
+    fips_failures: usize,
+    min_entropy: f64,
+}
+
+impl Assessment {
+    fn of(data: &[u8]) -> Self {
+        let windows: Vec<&[u8; FIPS_WINDOW_BYTES]> = data
+            .chunks_exact(FIPS_WINDOW_BYTES)
+            .map(|w| w.try_into().unwrap())
+            .collect();
+        Self {
+            windows: windows.len(),
+            fips_failures: windows
+                .iter()
+                .filter(|w| !stats::fips_suite(w).all_passed())
+                .count(),
+            min_entropy: stats::min_entropy(data),
+        }
+    }
+
+    /// Why the sample fails the check, if it does.
+    fn failure(&self, min_entropy: f64) -> Option<String> {
+        if self.fips_failures > 0 {
+            Some(format!(
+                "{} of {} FIPS 140-2 windows failed",
+                self.fips_failures, self.windows
+            ))
+        } else if self.min_entropy < min_entropy {
+            Some(format!(
+                "min-entropy {:.3} bits/byte, below {:.3}",
+                self.min_entropy, min_entropy
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// Counts failed checks in a row and reports when an alert starts or ends.
+#[derive(Debug)]
+struct Tracker {
+    alert_after: u32,
+    consecutive: u32,
+    degraded: bool,
+}
+
+impl Tracker {
+    fn new(alert_after: u32) -> Self {
+        Self {
+            alert_after,
+            consecutive: 0,
+            degraded: false,
+        }
+    }
+
+    fn record(&mut self, passed: bool) -> Option<Transition> {
+        if passed {
+            self.consecutive = 0;
+            return std::mem::take(&mut self.degraded).then_some(Transition::Recovered);
+        }
+        self.consecutive += 1;
+        if !self.degraded && self.consecutive >= self.alert_after {
+            self.degraded = true;
+            return Some(Transition::Degraded);
+        }
+        None
+    }
+}
+
+/// Body of a webhook POST.
+#[derive(Serialize)]
+struct Alert<'a> {
+    event: Transition,
+    feed: &'a str,
+    consecutive_failures: u32,
+    reason: Option<&'a str>,
+    unix: u64,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn notify(webhook: &Endpoint, alert: &Alert) {
+    let body = match serde_json::to_string(alert) {
+        Ok(body) => body,
+        Err(e) => {
+            log::error!(target: "mixrand::daemon", "cannot encode quality alert: {}", e);
+            return;
+        }
+    };
+    match webhook.post_json(&body, POST_TIMEOUT) {
+        Ok(status) if (200..300).contains(&status) => {}
+        Ok(status) => log::error!(
+            target: "mixrand::daemon",
+            "quality webhook {} returned HTTP {}", webhook.url(), status,
+        ),
+        Err(e) => log::error!(
+            target: "mixrand::daemon",
+            "quality webhook {} failed: {}", webhook.url(), e,
+        ),
+    }
+}
+
+/// Starts the check thread. `collect` draws a sample the way the daemon
+/// draws a batch; reports go to `reports`. The thread exits once shutdown
+/// is requested.
+pub fn spawn<F>(
+    config: QualityCheckConfig,
+    feed: &'static str,
+    webhook: Option<Endpoint>,
+    collect: F,
+    reports: Sender<Report>,
+) -> Result<(), Error>
+where
+    F: Fn(usize) -> Result<Vec<u8>, Error> + Send + 'static,
+{
+    thread::Builder::new()
+        .name("quality-check".into())
+        .spawn(move || {
+            let mut tracker = Tracker::new(config.alert_after);
+            let interval = Duration::from_secs(config.interval_secs);
+            loop {
+                signals::interruptible_sleep(interval);
+                if signals::shutdown_requested() {
+                    break;
+                }
+                let failure = match collect(config.sample_bytes) {
+                    Ok(mut data) => {
+                        let assessment = Assessment::of(&data);
+                        cpurng::zeroize_vec(&mut data);
+                        assessment.failure(config.min_entropy)
+                    }
+                    Err(e) => Some(format!("cannot collect a sample: {}", e)),
+                };
+                let transition = tracker.record(failure.is_none());
+                match (&failure, transition) {
+                    (Some(why), Some(Transition::Degraded)) => log::error!(
+                        target: "mixrand::daemon",
+                        "quality check of {} failed {} times in a row: {}",
+                        feed, tracker.consecutive, why,
+                    ),
+                    (Some(why), _) => log::warn!(
+                        target: "mixrand::daemon",
+                        "quality check of {} failed: {}", feed, why,
+                    ),
+                    (None, Some(_)) => log::info!(
+                        target: "mixrand::daemon",
+                        "quality check of {} passed again; alert cleared", feed,
+                    ),
+                    (None, None) => log::debug!(
+                        target: "mixrand::daemon",
+                        "quality check of {} passed", feed,
+                    ),
+                }
+                if let (Some(event), Some(ref webhook)) = (transition, &webhook) {
+                    notify(
+                        webhook,
+                        &Alert {
+                            event,
+                            feed,
+                            consecutive_failures: tracker.consecutive,
+                            reason: failure.as_deref(),
+                            unix: unix_now(),
+                        },
+                    );
+                }
+                let report = Report {
+                    failure,
+                    transition,
+                };
+                if reports.send(report).is_err() {
+                    break;
+                }
+            }
+        })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assessment() {
+        let data = crate::csprng::generate([7u8; 32], 3 * FIPS_WINDOW_BYTES + 100);
+        let good = Assessment::of(&data);
+        assert_eq!((good.windows, good.fips_failures), (3, 0));
+        assert_eq!(good.failure(7.0), None);
+        assert!(good.failure(8.0).unwrap().contains("min-entropy"));
+
+        let bad = Assessment::of(&[0u8; FIPS_WINDOW_BYTES]);
+        assert_eq!(
+            bad.failure(0.0).unwrap(),
+            "1 of 1 FIPS 140-2 windows failed"
+        );
+    }
+
+    #[test]
+    fn test_tracker_alerts_once() {
+        let mut t = Tracker::new(2);
+        assert_eq!(t.record(false), None);
+        assert_eq!(t.record(false), Some(Transition::Degraded));
+        assert_eq!(t.record(false), None);
+        assert_eq!(t.record(true), Some(Transition::Recovered));
+        assert_eq!(t.record(true), None);
+        assert_eq!(t.record(false), None);
+        assert_eq!(t.record(true), None);
+    }
+}
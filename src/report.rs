@@ -0,0 +1,324 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::environment::Environment;
+use crate::error::Error;
+
+/// One source's final results, as shown in the HTML report.
+pub struct ReportRow {
+    pub name: String,
+    pub description: String,
+    pub samples: u64,
+    pub bytes: String,
+    pub throughput: f64,
+    pub throughput_label: String,
+    /// All-tests FIPS 140-2 pass rate, or `None` when FIPS was skipped.
+    pub fips_pass_pct: Option<f64>,
+    pub shannon: f64,
+    pub min_entropy: f64,
+    pub chi_square: f64,
+    pub errors: u64,
+}
+
+/// Run parameters printed in the report header.
+pub struct ReportMeta {
+    pub generated_unix: u64,
+    pub duration: String,
+    pub sample_size: usize,
+    pub interrupted: bool,
+    pub environment: Option<Environment>,
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse;margin:1em 0}\
+th,td{border:1px solid #ccc;padding:4px 10px;text-align:right}\
+th:first-child,td:first-child,td.desc{text-align:left}\
+th{background:#f0f0f0}\
+.note{color:#666;font-size:90%}\
+svg text{font-size:12px;font-family:sans-serif}";
+
+const CHART_WIDTH: u32 = 640;
+const LABEL_WIDTH: u32 = 110;
+const BAR_AREA: u32 = 400;
+const ROW_HEIGHT: u32 = 24;
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Formats Unix seconds as an ISO 8601 UTC timestamp.
+pub fn format_utc(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Civil-from-days (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+/// Horizontal SVG bar chart; each bar is `(label, value, value_label)` and
+/// bar lengths are scaled so `max` spans the full bar area.
+fn bar_chart(title: &str, max: f64, bars: &[(&str, f64, String)]) -> String {
+    let height = ROW_HEIGHT * (bars.len() as u32 + 1);
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        "<h3>{}</h3>\n<svg width=\"{}\" height=\"{}\" role=\"img\" aria-label=\"{}\">\n",
+        escape(title),
+        CHART_WIDTH,
+        height,
+        escape(title)
+    );
+    for (i, (label, value, value_label)) in bars.iter().enumerate() {
+        let y = ROW_HEIGHT * i as u32 + 4;
+        let frac = if max > 0.0 {
+            (value / max).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let width = (frac * BAR_AREA as f64).round() as u32;
+        let _ = writeln!(
+            svg,
+            "<text x=\"0\" y=\"{}\">{}</text>\
+<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#4a7ebb\"/>\
+<text x=\"{}\" y=\"{}\">{}</text>",
+            y + 13,
+            escape(label),
+            LABEL_WIDTH,
+            y,
+            width,
+            ROW_HEIGHT - 8,
+            LABEL_WIDTH + width + 6,
+            y + 13,
+            escape(value_label)
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Renders the comparison as a standalone HTML page (inline CSS and SVG, no
+/// external assets).
+pub fn render_html(meta: &ReportMeta, rows: &[ReportRow]) -> String {
+    let fips = rows.iter().any(|r| r.fips_pass_pct.is_some());
+    let mut html = String::new();
+
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+<title>mixrand entropy source comparison</title>\n<style>{}</style>\n</head>\n<body>\n\
+<h1>Entropy source comparison</h1>\n<p class=\"note\">mixrand {} &middot; generated {} \
+&middot; duration {}{} &middot; sample size {} bytes{}</p>\n",
+        STYLE,
+        env!("CARGO_PKG_VERSION"),
+        format_utc(meta.generated_unix),
+        escape(&meta.duration),
+        if meta.interrupted {
+            " (interrupted)"
+        } else {
+            ""
+        },
+        meta.sample_size,
+        if fips { "" } else { " &middot; FIPS skipped" }
+    );
+
+    html.push_str("<table>\n<tr><th>Source</th><th>Description</th><th>Samples</th>");
+    html.push_str("<th>Bytes</th><th>Throughput</th>");
+    if fips {
+        html.push_str("<th>FIPS pass</th>");
+    }
+    html.push_str("<th>Shannon</th><th>Min-entropy</th><th>Chi-sq</th><th>Errors</th></tr>\n");
+    for r in rows {
+        let _ = write!(
+            html,
+            "<tr><td>{}</td><td class=\"desc\">{}</td><td>{}</td><td>{}</td><td>{}</td>",
+            escape(&r.name),
+            escape(&r.description),
+            r.samples,
+            escape(&r.bytes),
+            escape(&r.throughput_label)
+        );
+        if fips {
+            match r.fips_pass_pct {
+                Some(p) => {
+                    let _ = write!(html, "<td>{:.1}%</td>", p);
+                }
+                None => html.push_str("<td>&ndash;</td>"),
+            }
+        }
+        let _ = writeln!(
+            html,
+            "<td>{:.3}</td><td>{:.3}</td><td>{:.1}</td><td>{}</td></tr>",
+            r.shannon, r.min_entropy, r.chi_square, r.errors
+        );
+    }
+    html.push_str("</table>\n");
+
+    let max_throughput = rows.iter().map(|r| r.throughput).fold(0.0, f64::max);
+    let bars: Vec<_> = rows
+        .iter()
+        .map(|r| (r.name.as_str(), r.throughput, r.throughput_label.clone()))
+        .collect();
+    html.push_str(&bar_chart("Throughput", max_throughput, &bars));
+
+    if fips {
+        let bars: Vec<_> = rows
+            .iter()
+            .map(|r| {
+                let p = r.fips_pass_pct.unwrap_or(0.0);
+                (r.name.as_str(), p, format!("{:.1}%", p))
+            })
+            .collect();
+        html.push_str(&bar_chart("FIPS 140-2 pass rate", 100.0, &bars));
+    }
+
+    let bars: Vec<_> = rows
+        .iter()
+        .map(|r| {
+            (
+                r.name.as_str(),
+                r.min_entropy,
+                format!("{:.3} bits/byte", r.min_entropy),
+            )
+        })
+        .collect();
+    html.push_str(&bar_chart("Min-entropy (of 8 bits/byte)", 8.0, &bars));
+
+    if let Some(ref env) = meta.environment {
+        html.push_str("<h2>Environment</h2>\n<table>\n");
+        for (label, value) in env.fields() {
+            let _ = writeln!(
+                html,
+                "<tr><th>{}</th><td class=\"desc\">{}</td></tr>",
+                escape(label),
+                escape(&value)
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Writes the HTML report to `path`.
+pub fn write_html(path: &Path, meta: &ReportMeta, rows: &[ReportRow]) -> Result<(), Error> {
+    std::fs::write(path, render_html(meta, rows))
+        .map_err(|e| Error::InvalidArgs(format!("cannot write {}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(name: &str, throughput: f64, fips: Option<f64>) -> ReportRow {
+        ReportRow {
+            name: name.into(),
+            description: "<test> & co".into(),
+            samples: 10,
+            bytes: "40.96 KB".into(),
+            throughput,
+            throughput_label: format!("{} B/s", throughput),
+            fips_pass_pct: fips,
+            shannon: 7.95,
+            min_entropy: 7.2,
+            chi_square: 250.0,
+            errors: 0,
+        }
+    }
+
+    fn meta() -> ReportMeta {
+        ReportMeta {
+            generated_unix: 0,
+            duration: "5m".into(),
+            sample_size: 4096,
+            interrupted: false,
+            environment: None,
+        }
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a<b>&\"'"), "a&lt;b&gt;&amp;&quot;&#39;");
+    }
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_utc(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_utc(1_700_000_000), "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn test_bar_widths_scale_to_max() {
+        let svg = bar_chart(
+            "t",
+            200.0,
+            &[("a", 200.0, "x".into()), ("b", 50.0, "y".into())],
+        );
+        assert!(svg.contains(&format!("width=\"{}\"", BAR_AREA)));
+        assert!(svg.contains(&format!("width=\"{}\"", BAR_AREA / 4)));
+    }
+
+    #[test]
+    fn test_render_html_with_fips() {
+        let html = render_html(
+            &meta(),
+            &[
+                row("rdseed", 8e6, Some(99.0)),
+                row("rdrand", 1e8, Some(100.0)),
+            ],
+        );
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<td>rdseed</td>"));
+        assert!(html.contains("&lt;test&gt; &amp; co"));
+        assert!(html.contains("FIPS 140-2 pass rate"));
+        assert_eq!(html.matches("<svg").count(), 3);
+    }
+
+    #[test]
+    fn test_render_html_without_fips() {
+        let html = render_html(&meta(), &[row("hwrng", 1000.0, None)]);
+        assert!(!html.contains("FIPS pass"));
+        assert!(html.contains("FIPS skipped"));
+        assert_eq!(html.matches("<svg").count(), 2);
+        assert!(!html.contains("<h2>Environment"));
+    }
+
+    #[test]
+    fn test_render_html_with_environment() {
+        let meta = ReportMeta {
+            environment: Some(Environment::collect()),
+            ..meta()
+        };
+        let html = render_html(&meta, &[row("hwrng", 1000.0, None)]);
+        assert!(html.contains("<h2>Environment</h2>"));
+        assert!(html.contains("<tr><th>Kernel</th>"));
+    }
+}
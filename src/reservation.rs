@@ -0,0 +1,173 @@
+//! Reservations for `serve-socket`: bytes generated ahead of need and held
+//! for the connection that reserved them, so its later `fetch` is answered
+//! at once, without touching a source. Held bytes are wiped when they are
+//! cancelled or expire, or when the connection closes.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+use crate::wire::Served;
+
+struct Held {
+    /// The connection that reserved the bytes; no other may fetch them.
+    owner: String,
+    expires: Instant,
+    served: Served,
+}
+
+impl Held {
+    fn discard(mut self) {
+        self.served.data.zeroize();
+    }
+}
+
+pub struct Reservations {
+    held: Mutex<HashMap<u64, Held>>,
+    next_id: AtomicU64,
+    max_per_owner: usize,
+    max_hold: Duration,
+}
+
+impl Reservations {
+    /// Up to `max_per_owner` reservations per connection (0 refuses them
+    /// all), each held for at most `max_hold`.
+    pub fn new(max_per_owner: usize, max_hold: Duration) -> Self {
+        Self {
+            held: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+            max_per_owner,
+            max_hold,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<u64, Held>> {
+        self.held.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// `Err` if `owner` may not reserve anything more; checked before the
+    /// bytes are generated.
+    pub fn check_room(&self, owner: &str) -> Result<(), Error> {
+        let count = self.lock().values().filter(|h| h.owner == owner).count();
+        if count >= self.max_per_owner {
+            return Err(Error::InvalidArgs(format!(
+                "at most {} reservations per connection",
+                self.max_per_owner
+            )));
+        }
+        Ok(())
+    }
+
+    /// Holds `served` for `owner` until `hold` (capped at the server's
+    /// limit) has passed. Returns the reservation number and the hold
+    /// granted.
+    pub fn hold(&self, owner: &str, served: Served, hold: Option<Duration>) -> (u64, Duration) {
+        let hold = hold.map_or(self.max_hold, |h| h.min(self.max_hold));
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let held = Held {
+            owner: owner.to_string(),
+            expires: Instant::now() + hold,
+            served,
+        };
+        self.lock().insert(id, held);
+        (id, hold)
+    }
+
+    /// Removes `owner`'s reservation `id`. Someone else's reservation is
+    /// reported as missing, like one that never existed.
+    fn remove(&self, owner: &str, id: u64) -> Result<Held, Error> {
+        let mut held = self.lock();
+        match held.get(&id) {
+            Some(h) if h.owner == owner => Ok(held.remove(&id).expect("entry exists")),
+            _ => Err(Error::InvalidArgs(format!(
+                "no reservation {} (it may have expired)",
+                id
+            ))),
+        }
+    }
+
+    /// Hands over the bytes of reservation `id`, ending it.
+    pub fn fetch(&self, owner: &str, id: u64) -> Result<Served, Error> {
+        let Held {
+            expires,
+            mut served,
+            ..
+        } = self.remove(owner, id)?;
+        if expires <= Instant::now() {
+            served.data.zeroize();
+            return Err(Error::InvalidArgs(format!("reservation {} expired", id)));
+        }
+        Ok(served)
+    }
+
+    /// Wipes the bytes of reservation `id` unsent.
+    pub fn cancel(&self, owner: &str, id: u64) -> Result<(), Error> {
+        self.remove(owner, id)?.discard();
+        Ok(())
+    }
+
+    /// Wipes every reservation `owner` still holds, once its connection
+    /// has closed. Returns how many there were.
+    pub fn release(&self, owner: &str) -> usize {
+        self.discard_where(|h| h.owner == owner)
+    }
+
+    /// Wipes the reservations whose hold has run out by `now`.
+    pub fn expire(&self, now: Instant) -> usize {
+        self.discard_where(|h| h.expires <= now)
+    }
+
+    fn discard_where(&self, matches: impl Fn(&Held) -> bool) -> usize {
+        let gone: Vec<Held> = {
+            let mut held = self.lock();
+            let ids: Vec<u64> = held
+                .iter()
+                .filter(|(_, h)| matches(h))
+                .map(|(&id, _)| id)
+                .collect();
+            ids.iter().filter_map(|id| held.remove(id)).collect()
+        };
+        let count = gone.len();
+        gone.into_iter().for_each(Held::discard);
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wire::Data;
+
+    fn served(byte: u8) -> Served {
+        Served {
+            data: Data::Bytes(vec![byte; 4]),
+            source: "test".into(),
+        }
+    }
+
+    #[test]
+    fn test_reservations() {
+        let r = Reservations::new(2, Duration::from_secs(60));
+        let (a, hold) = r.hold("client 1", served(1), None);
+        assert_eq!(hold, Duration::from_secs(60));
+        let (b, hold) = r.hold("client 1", served(2), Some(Duration::from_millis(10)));
+        assert_eq!(hold, Duration::from_millis(10));
+        assert!(r.check_room("client 1").is_err());
+        assert!(r.check_room("client 2").is_ok());
+
+        assert!(r.fetch("client 2", a).is_err());
+        assert_eq!(r.fetch("client 1", a).unwrap(), served(1));
+        assert!(r.fetch("client 1", a).is_err());
+
+        assert_eq!(r.expire(Instant::now() + Duration::from_secs(1)), 1);
+        assert!(r.cancel("client 1", b).is_err());
+
+        let (c, _) = r.hold("client 1", served(3), None);
+        r.hold("client 3", served(4), None);
+        r.cancel("client 1", c).unwrap();
+        assert_eq!(r.release("client 3"), 1);
+        assert_eq!(r.release("client 3"), 0);
+    }
+}
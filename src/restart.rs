@@ -0,0 +1,177 @@
+//! `[restart_test]`: a restart test across reboots. A hash of each boot's
+//! first output is kept, and compared with those of the boots before it: a
+//! match means the generator started from the same state twice, as it would
+//! from a seed restored with a disk image or cloned with a VM. Recording is
+//! a no-op until [`enable`] is called.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::atomicfile;
+use crate::config::Config;
+use crate::entropy::sysid;
+use crate::error::Error;
+use crate::output;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Boot {
+    boot_id: String,
+    unix: u64,
+    /// SHA-256 of the boot's first output, labeled, never the output itself.
+    sha256: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct History {
+    boots: Vec<Boot>,
+}
+
+impl History {
+    fn has(&self, boot_id: &str) -> bool {
+        self.boots.iter().any(|b| b.boot_id == boot_id)
+    }
+
+    /// Adds `boot`, keeping the latest `keep`, and returns the earlier boot
+    /// whose first output it repeats, if any.
+    fn add(&mut self, boot: Boot, keep: usize) -> Option<Boot> {
+        let repeat = self
+            .boots
+            .iter()
+            .rev()
+            .find(|b| b.sha256 == boot.sha256)
+            .cloned();
+        self.boots.push(boot);
+        let excess = self.boots.len().saturating_sub(keep);
+        self.boots.drain(..excess);
+        repeat
+    }
+}
+
+/// This boot's first output has yet to be recorded.
+struct Pending {
+    file: PathBuf,
+    bytes: usize,
+    keep: usize,
+    boot_id: String,
+}
+
+static PENDING: Mutex<Option<Pending>> = Mutex::new(None);
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn load(path: &Path) -> History {
+    fs::read(path)
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Atomically replaces `path`, creating its directory if needed.
+fn save(path: &Path, history: &History) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut json =
+        serde_json::to_vec_pretty(history).map_err(|e| Error::Io(std::io::Error::other(e)))?;
+    json.push(b'\n');
+    atomicfile::write(path, &json)
+}
+
+fn digest(first: &[u8]) -> String {
+    let mut h = Sha256::new();
+    h.update(b"mixrand-restart-test");
+    h.update(first);
+    output::to_hex(&h.finalize())
+}
+
+/// Arms the test for this process, unless it is disabled, the boot ID is
+/// unreadable, or this boot's first output is already recorded. Returns
+/// whether it was armed.
+pub fn enable(config: &Config) -> bool {
+    let settings = &config.restart_test;
+    if !settings.is_enabled() {
+        return false;
+    }
+    let boot_id = String::from_utf8_lossy(&sysid::read_boot_id())
+        .trim()
+        .to_string();
+    if boot_id.is_empty() || load(&settings.file).has(&boot_id) {
+        return false;
+    }
+    *PENDING.lock().unwrap_or_else(|e| e.into_inner()) = Some(Pending {
+        file: settings.file.clone(),
+        bytes: settings.bytes,
+        keep: settings.boots,
+        boot_id,
+    });
+    true
+}
+
+/// Records `output` as this boot's first, if it is long enough and none is
+/// recorded yet, and logs an error if an earlier boot's began the same way.
+pub fn observe(output: &[u8]) {
+    let mut guard = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(pending) = guard.as_ref() else {
+        return;
+    };
+    // Too short to hash safely; wait for a longer output
+    if output.len() < pending.bytes {
+        return;
+    }
+    let Some(pending) = guard.take() else {
+        return;
+    };
+    // Another process may have got there first
+    let mut history = load(&pending.file);
+    if history.has(&pending.boot_id) {
+        return;
+    }
+    let boot = Boot {
+        boot_id: pending.boot_id,
+        unix: unix_now(),
+        sha256: digest(&output[..pending.bytes]),
+    };
+    if let Some(earlier) = history.add(boot, pending.keep) {
+        log::error!(
+            "restart test failed: the first {} bytes output on this boot repeat those of boot {} (unix {}); the generator started from the same state twice, as from a cloned or restored seed",
+            pending.bytes,
+            earlier.boot_id,
+            earlier.unix
+        );
+    }
+    if let Err(e) = save(&pending.file, &history) {
+        log::debug!("restart test {}: {}", pending.file.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_add() {
+        let boot = |id: &str, first: &[u8]| Boot {
+            boot_id: id.into(),
+            unix: 0,
+            sha256: digest(first),
+        };
+        let mut history = History::default();
+        assert!(history.add(boot("a", b"first"), 2).is_none());
+        assert!(history.add(boot("b", b"second"), 2).is_none());
+        assert!(history.has("a"));
+        assert_eq!(history.add(boot("c", b"second"), 2).unwrap().boot_id, "b");
+        // Only the latest two are kept
+        assert!(!history.has("a"));
+        assert!(history.add(boot("d", b"first"), 2).is_none());
+    }
+}
@@ -0,0 +1,171 @@
+//! Suspend/resume detection for the daemon. CLOCK_BOOTTIME keeps counting
+//! while the system is suspended and CLOCK_MONOTONIC does not, so growth in
+//! the gap between them means the machine slept. On resume the crng has
+//! been frozen for however long that was, and some CPUs come back with a
+//! broken RDRAND (AMD family 15h/16h returned all ones after S3 until a
+//! microcode update), so the daemon tops up the pool and retests the CPU RNG.
+
+use std::time::Duration;
+
+use crate::entropy::cpurng;
+use crate::signals;
+
+/// Smallest growth of the BOOTTIME/MONOTONIC gap taken as a suspend;
+/// smaller steps are clock adjustment noise.
+const MIN_SUSPEND: Duration = Duration::from_secs(1);
+
+/// How often [`ResumeDetector::sleep`] looks at the clocks. MONOTONIC sleeps
+/// stop during suspend, so this bounds how late a resume is noticed.
+const POLL: Duration = Duration::from_secs(1);
+
+/// Words drawn from each instruction by [`check_cpu_rng`].
+const SANITY_WORDS: usize = 64;
+/// Attempts per word; RDSEED underflows often, but not this often.
+const SANITY_ATTEMPTS: usize = 32;
+
+fn clock(id: libc::clockid_t) -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(id, &mut ts) };
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// Time spent suspended: how much BOOTTIME has gained on MONOTONIC.
+fn suspended() -> Duration {
+    clock(libc::CLOCK_BOOTTIME).saturating_sub(clock(libc::CLOCK_MONOTONIC))
+}
+
+/// Growth from gap `before` to `after`, if it is a suspend.
+fn slept(before: Duration, after: Duration) -> Option<Duration> {
+    after.checked_sub(before).filter(|d| *d >= MIN_SUSPEND)
+}
+
+pub struct ResumeDetector {
+    gap: Duration,
+}
+
+impl Default for ResumeDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResumeDetector {
+    pub fn new() -> Self {
+        Self { gap: suspended() }
+    }
+
+    /// How long the system was suspended since the last call, if it was.
+    pub fn poll(&mut self) -> Option<Duration> {
+        let gap = suspended();
+        let slept = slept(self.gap, gap);
+        self.gap = gap;
+        slept
+    }
+
+    /// Sleeps like [`signals::interruptible_sleep`], but returns early,
+    /// with the time suspended, once the system resumes.
+    pub fn sleep(&mut self, total: Duration) -> Option<Duration> {
+        let mut remaining = total;
+        while remaining > Duration::ZERO
+            && !signals::shutdown_requested()
+            && !signals::wake_requested()
+        {
+            let step = remaining.min(POLL);
+            signals::interruptible_sleep(step);
+            remaining = remaining.saturating_sub(step);
+            if let Some(slept) = self.poll() {
+                return Some(slept);
+            }
+        }
+        None
+    }
+}
+
+/// What is wrong with `words`, drawn in `attempts` tries, if anything.
+fn verdict(words: &[u64], attempts: usize) -> Option<String> {
+    if words.is_empty() {
+        return Some(format!("no output in {} attempts", attempts));
+    }
+    let constant = words.iter().filter(|&&w| w == 0 || w == u64::MAX).count();
+    if constant > 0 {
+        return Some(format!(
+            "{} of {} words all zeros or all ones",
+            constant,
+            words.len()
+        ));
+    }
+    let mut sorted = words.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    if sorted.len() < words.len() {
+        return Some(format!(
+            "{} repeated words in {}",
+            words.len() - sorted.len(),
+            words.len()
+        ));
+    }
+    None
+}
+
+fn sample(once: fn() -> Option<u64>) -> Option<String> {
+    let attempts = SANITY_WORDS * SANITY_ATTEMPTS;
+    let words: Vec<u64> = (0..attempts)
+        .filter_map(|_| once())
+        .take(SANITY_WORDS)
+        .collect();
+    verdict(&words, attempts)
+}
+
+/// Draws a few words from each CPU RNG instruction the CPU has and returns
+/// the ones that look broken, with the reason.
+pub fn check_cpu_rng() -> Vec<(&'static str, String)> {
+    let features = cpurng::cpu_features();
+    let mut failed = Vec::new();
+    if features.rdrand {
+        failed.extend(sample(cpurng::rdrand_once).map(|why| ("rdrand", why)));
+    }
+    if features.rdseed {
+        failed.extend(sample(cpurng::rdseed_once).map(|why| ("rdseed", why)));
+    }
+    failed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slept() {
+        let s = Duration::from_secs;
+        assert_eq!(slept(s(10), s(10)), None);
+        assert_eq!(slept(s(10), s(10) + Duration::from_millis(3)), None);
+        assert_eq!(slept(s(10), s(3610)), Some(s(3600)));
+        // A smaller gap (clock step) is not a suspend
+        assert_eq!(slept(s(10), s(5)), None);
+    }
+
+    #[test]
+    fn test_verdict() {
+        let good: Vec<u64> = (1..=64u64)
+            .map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+            .collect();
+        assert_eq!(verdict(&good, 100), None);
+        assert_eq!(
+            verdict(&[], 2048).as_deref(),
+            Some("no output in 2048 attempts")
+        );
+        assert_eq!(
+            verdict(&[u64::MAX; 64], 64).as_deref(),
+            Some("64 of 64 words all zeros or all ones")
+        );
+        let mut repeats = good.clone();
+        repeats[5] = repeats[4];
+        assert_eq!(
+            verdict(&repeats, 64).as_deref(),
+            Some("1 repeated words in 64")
+        );
+    }
+}
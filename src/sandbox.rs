@@ -0,0 +1,500 @@
+//! seccomp-bpf allow-list for the long-running modes.
+//!
+//! Installed once initialization is done; from then on any syscall outside
+//! the policy raises SIGSYS, whose handler logs the syscall number to stderr
+//! and exits.
+
+use std::os::unix::io::RawFd;
+
+use crate::error::Error;
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xC000_003E;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xC000_00B7;
+
+// Classic BPF opcodes (linux/filter.h)
+const LD_W_ABS: u16 = 0x20;
+const JEQ_K: u16 = 0x15;
+const RET_K: u16 = 0x06;
+
+// Offsets into struct seccomp_data; args are 64-bit, low word first on
+// little-endian targets
+const OFFSET_NR: u32 = 0;
+const OFFSET_ARCH: u32 = 4;
+const OFFSET_ARGS: u32 = 16;
+
+/// Exit status after a violation, as if killed by SIGSYS.
+const VIOLATION_EXIT: libc::c_int = 128 + libc::SIGSYS;
+
+/// Syscalls shared by the daemon and the server: file and procfs access for
+/// the fallback sources, seed and status files, memory management, threads,
+/// signals, timers, and logging to an already-connected syslog socket.
+const BASE_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_read,
+    libc::SYS_write,
+    libc::SYS_readv,
+    libc::SYS_writev,
+    libc::SYS_pread64,
+    libc::SYS_openat,
+    libc::SYS_close,
+    libc::SYS_fstat,
+    libc::SYS_newfstatat,
+    libc::SYS_statx,
+    libc::SYS_lseek,
+    libc::SYS_fcntl,
+    libc::SYS_fsync,
+    libc::SYS_fdatasync,
+    libc::SYS_ftruncate,
+    libc::SYS_fchmod,
+    libc::SYS_flock,
+    libc::SYS_getdents64,
+    libc::SYS_readlinkat,
+    libc::SYS_renameat,
+    libc::SYS_renameat2,
+    libc::SYS_unlinkat,
+    libc::SYS_mkdirat,
+    libc::SYS_ppoll,
+    libc::SYS_getrandom,
+    libc::SYS_uname,
+    libc::SYS_getpid,
+    libc::SYS_gettid,
+    libc::SYS_brk,
+    libc::SYS_mmap,
+    libc::SYS_munmap,
+    libc::SYS_mremap,
+    libc::SYS_mprotect,
+    libc::SYS_madvise,
+    libc::SYS_mlock,
+    libc::SYS_munlock,
+    libc::SYS_futex,
+    libc::SYS_clone,
+    libc::SYS_clone3,
+    libc::SYS_set_robust_list,
+    libc::SYS_rseq,
+    libc::SYS_sched_yield,
+    libc::SYS_sched_getaffinity,
+    libc::SYS_rt_sigaction,
+    libc::SYS_rt_sigprocmask,
+    libc::SYS_rt_sigreturn,
+    libc::SYS_sigaltstack,
+    libc::SYS_clock_gettime,
+    libc::SYS_clock_nanosleep,
+    libc::SYS_nanosleep,
+    libc::SYS_gettimeofday,
+    libc::SYS_sendto,
+    libc::SYS_sendmsg,
+    libc::SYS_exit,
+    libc::SYS_exit_group,
+];
+
+/// Legacy syscalls glibc still issues on x86_64.
+#[cfg(target_arch = "x86_64")]
+const LEGACY_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_open,
+    libc::SYS_stat,
+    libc::SYS_lstat,
+    libc::SYS_access,
+    libc::SYS_readlink,
+    libc::SYS_rename,
+    libc::SYS_mkdir,
+    libc::SYS_unlink,
+    libc::SYS_poll,
+    libc::SYS_epoll_wait,
+];
+#[cfg(not(target_arch = "x86_64"))]
+const LEGACY_SYSCALLS: &[libc::c_long] = &[];
+
+/// Event loop and connection handling for `serve-grpc`; the listener is
+/// bound before the filter is installed.
+#[cfg(feature = "grpc")]
+const SERVE_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_epoll_create1,
+    libc::SYS_epoll_ctl,
+    libc::SYS_epoll_pwait,
+    libc::SYS_epoll_pwait2,
+    libc::SYS_eventfd2,
+    libc::SYS_accept4,
+    libc::SYS_recvfrom,
+    libc::SYS_recvmsg,
+    libc::SYS_shutdown,
+    libc::SYS_getsockname,
+    libc::SYS_getpeername,
+    libc::SYS_getsockopt,
+    libc::SYS_setsockopt,
+];
+
+/// Connecting to a VM host for the `vsock` source; `socket` itself is
+/// limited to AF_VSOCK.
+const VSOCK_CLIENT_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_connect,
+    libc::SYS_getsockopt,
+    libc::SYS_setsockopt,
+];
+
+/// Handling daemon connections in `injector`; the listener is bound
+/// before the filter is installed.
+const INJECTOR_SYSCALLS: &[libc::c_long] =
+    &[libc::SYS_accept4, libc::SYS_recvfrom, libc::SYS_getsockopt];
+
+/// Connection handling for `serve-vsock` and `serve-socket`; the listener
+/// is bound before the filter is installed.
+const VSOCK_SERVE_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_accept4,
+    libc::SYS_recvfrom,
+    libc::SYS_setsockopt,
+    libc::SYS_shutdown,
+];
+
+/// A syscall allowed only when its first arguments match (`None` = any).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ArgRule {
+    nr: libc::c_long,
+    args: [Option<u32>; 2],
+}
+
+/// Syscalls a mode may make once sandboxed.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    syscalls: Vec<libc::c_long>,
+    rules: Vec<ArgRule>,
+}
+
+impl Policy {
+    /// [`BASE_SYSCALLS`] and friends, for modes that need nothing more.
+    pub fn base() -> Self {
+        let mut policy = Self::default();
+        policy.allow(BASE_SYSCALLS);
+        policy.allow(LEGACY_SYSCALLS);
+        // Thread names and isatty() checks
+        policy.allow_args(libc::SYS_prctl, [Some(libc::PR_SET_NAME as u32), None]);
+        policy.allow_args(libc::SYS_ioctl, [None, Some(libc::TCGETS as u32)]);
+        policy
+    }
+
+    fn allow(&mut self, syscalls: &[libc::c_long]) {
+        for &nr in syscalls {
+            if !self.syscalls.contains(&nr) {
+                self.syscalls.push(nr);
+            }
+        }
+    }
+
+    fn allow_args(&mut self, nr: libc::c_long, args: [Option<u32>; 2]) {
+        self.rules.push(ArgRule { nr, args });
+    }
+
+    /// The daemon: base syscalls plus RNDADDENTROPY on `dev_random`.
+    pub fn daemon(dev_random: RawFd, rndaddentropy: libc::c_ulong) -> Self {
+        let mut policy = Self::base();
+        policy.allow_args(
+            libc::SYS_ioctl,
+            [Some(dev_random as u32), Some(rndaddentropy as u32)],
+        );
+        policy
+    }
+
+    /// Adds RNDRESEEDCRNG on `dev_random`, for the daemon's reseed mode.
+    pub fn reseed_crng(mut self, dev_random: RawFd, rndreseedcrng: libc::c_ulong) -> Self {
+        self.allow_args(
+            libc::SYS_ioctl,
+            [Some(dev_random as u32), Some(rndreseedcrng as u32)],
+        );
+        self
+    }
+
+    /// Adds what the `vsock` source needs to reach the host.
+    pub fn vsock_client(mut self) -> Self {
+        self.allow(VSOCK_CLIENT_SYSCALLS);
+        self.allow_args(libc::SYS_socket, [Some(libc::AF_VSOCK as u32), None]);
+        self
+    }
+
+    /// Adds talking to an `injector`, reconnecting included; `socket` is
+    /// limited to AF_UNIX.
+    pub fn unix_client(mut self) -> Self {
+        self.allow(&[libc::SYS_connect, libc::SYS_recvfrom]);
+        self.allow_args(libc::SYS_socket, [Some(libc::AF_UNIX as u32), None]);
+        self
+    }
+
+    /// Adds POSTing to a webhook whose address was resolved beforehand;
+    /// `socket` is limited to AF_INET and AF_INET6.
+    pub fn http_client(mut self) -> Self {
+        self.allow(&[
+            libc::SYS_connect,
+            libc::SYS_recvfrom,
+            libc::SYS_getsockopt,
+            libc::SYS_setsockopt,
+        ]);
+        self.allow_args(libc::SYS_socket, [Some(libc::AF_INET as u32), None]);
+        self.allow_args(libc::SYS_socket, [Some(libc::AF_INET6 as u32), None]);
+        // connect_timeout() toggles O_NONBLOCK with FIONBIO
+        self.allow_args(libc::SYS_ioctl, [None, Some(libc::FIONBIO as u32)]);
+        self
+    }
+
+    /// `injector`: the daemon policy plus accepting and reading from
+    /// daemon connections.
+    pub fn injector(dev_random: RawFd, rndaddentropy: libc::c_ulong) -> Self {
+        let mut policy = Self::daemon(dev_random, rndaddentropy);
+        policy.allow(INJECTOR_SYSCALLS);
+        policy
+    }
+
+    /// `serve-vsock` and `serve-socket`: base syscalls, accepting and
+    /// serving clients, and terminal setup for a configured serial source.
+    pub fn serve_vsock(serial: bool) -> Self {
+        let mut policy = Self::base();
+        policy.allow(VSOCK_SERVE_SYSCALLS);
+        if serial {
+            policy.allow_args(libc::SYS_ioctl, [None, Some(libc::TCSETS as u32)]);
+            policy.allow_args(libc::SYS_ioctl, [None, Some(libc::TCFLSH as u32)]);
+        }
+        policy
+    }
+
+    /// `serve-grpc`: base syscalls, the event loop, and terminal setup for
+    /// a configured serial source.
+    #[cfg(feature = "grpc")]
+    pub fn serve(serial: bool) -> Self {
+        let mut policy = Self::base();
+        policy.allow(SERVE_SYSCALLS);
+        if serial {
+            policy.allow_args(libc::SYS_ioctl, [None, Some(libc::TCSETS as u32)]);
+            policy.allow_args(libc::SYS_ioctl, [None, Some(libc::TCFLSH as u32)]);
+        }
+        policy
+    }
+}
+
+fn stmt(code: u16, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn jeq(k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter {
+        code: JEQ_K,
+        jt,
+        jf,
+        k,
+    }
+}
+
+/// Compiles `policy` to classic BPF: wrong architecture kills the process,
+/// listed syscalls are allowed, everything else traps.
+fn build_filter(policy: &Policy, arch: u32) -> Vec<libc::sock_filter> {
+    let mut prog = vec![
+        stmt(LD_W_ABS, OFFSET_ARCH),
+        jeq(arch, 1, 0),
+        stmt(RET_K, libc::SECCOMP_RET_KILL_PROCESS),
+        stmt(LD_W_ABS, OFFSET_NR),
+    ];
+    for &nr in &policy.syscalls {
+        prog.push(jeq(nr as u32, 0, 1));
+        prog.push(stmt(RET_K, libc::SECCOMP_RET_ALLOW));
+    }
+    for rule in &policy.rules {
+        let checks: Vec<(u32, u32)> = rule
+            .args
+            .iter()
+            .enumerate()
+            .filter_map(|(i, v)| v.map(|v| (OFFSET_ARGS + 8 * i as u32, v)))
+            .collect();
+        // Each check is a load and a compare, then the final return
+        let rule_len = 2 * checks.len() + 1;
+        prog.push(stmt(LD_W_ABS, OFFSET_NR));
+        prog.push(jeq(rule.nr as u32, 0, rule_len as u8));
+        for (i, &(offset, value)) in checks.iter().enumerate() {
+            let remaining = 2 * (checks.len() - 1 - i) + 1;
+            prog.push(stmt(LD_W_ABS, offset));
+            prog.push(jeq(value, 0, remaining as u8));
+        }
+        prog.push(stmt(RET_K, libc::SECCOMP_RET_ALLOW));
+    }
+    prog.push(stmt(RET_K, libc::SECCOMP_RET_TRAP));
+    prog
+}
+
+/// Offset of `si_syscall` in the SIGSYS `siginfo_t`.
+const SI_SYSCALL_OFFSET: usize = if cfg!(target_pointer_width = "64") {
+    24
+} else {
+    16
+};
+
+/// Async-signal-safe: formats the syscall number by hand, writes one line to
+/// stderr and `_exit`s.
+extern "C" fn sigsys_handler(
+    _sig: libc::c_int,
+    info: *mut libc::siginfo_t,
+    _ctx: *mut libc::c_void,
+) {
+    const PREFIX: &[u8] = b"mixrand: sandbox blocked syscall ";
+    const SUFFIX: &[u8] = b" (run with --no-sandbox to disable)\n";
+    let nr = unsafe {
+        std::ptr::read_unaligned((info as *const u8).add(SI_SYSCALL_OFFSET) as *const i32)
+    };
+    let mut digits = [0u8; 12];
+    let mut n = nr.unsigned_abs();
+    let mut i = digits.len();
+    loop {
+        i -= 1;
+        digits[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    unsafe {
+        libc::write(2, PREFIX.as_ptr().cast(), PREFIX.len());
+        libc::write(2, digits[i..].as_ptr().cast(), digits.len() - i);
+        libc::write(2, SUFFIX.as_ptr().cast(), SUFFIX.len());
+        libc::_exit(VIOLATION_EXIT);
+    }
+}
+
+/// Installs `policy` on every thread of the process. Irreversible.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+pub fn install(policy: &Policy) -> Result<(), Error> {
+    let prog = build_filter(policy, AUDIT_ARCH);
+    let fprog = libc::sock_fprog {
+        len: prog.len() as libc::c_ushort,
+        filter: prog.as_ptr() as *mut libc::sock_filter,
+    };
+    unsafe {
+        let mut sa: libc::sigaction = std::mem::zeroed();
+        sa.sa_sigaction = sigsys_handler as *const () as usize;
+        sa.sa_flags = libc::SA_SIGINFO;
+        libc::sigemptyset(&mut sa.sa_mask);
+        if libc::sigaction(libc::SIGSYS, &sa, std::ptr::null_mut()) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let ret = libc::syscall(
+            libc::SYS_seccomp,
+            libc::SECCOMP_SET_MODE_FILTER,
+            libc::SECCOMP_FILTER_FLAG_TSYNC,
+            &fprog as *const libc::sock_fprog,
+        );
+        if ret != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub fn install(_policy: &Policy) -> Result<(), Error> {
+    Err(Error::InvalidArgs(
+        "seccomp sandbox is not supported on this architecture".into(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal interpreter for the opcodes `build_filter` emits.
+    fn eval(prog: &[libc::sock_filter], arch: u32, nr: libc::c_long, args: [u64; 2]) -> u32 {
+        let mut data = [0u8; 64];
+        data[0..4].copy_from_slice(&(nr as u32).to_le_bytes());
+        data[4..8].copy_from_slice(&arch.to_le_bytes());
+        data[16..24].copy_from_slice(&args[0].to_le_bytes());
+        data[24..32].copy_from_slice(&args[1].to_le_bytes());
+        let (mut pc, mut acc) = (0usize, 0u32);
+        loop {
+            let insn = prog[pc];
+            pc += 1;
+            match insn.code {
+                LD_W_ABS => {
+                    let k = insn.k as usize;
+                    acc = u32::from_le_bytes(data[k..k + 4].try_into().unwrap());
+                }
+                JEQ_K => pc += if acc == insn.k { insn.jt } else { insn.jf } as usize,
+                RET_K => return insn.k,
+                code => panic!("unexpected opcode {:#x}", code),
+            }
+        }
+    }
+
+    const ARCH: u32 = 0xC000_003E;
+
+    #[test]
+    fn test_filter_allows_listed_syscalls() {
+        let prog = build_filter(&Policy::daemon(5, 0x40085203), ARCH);
+        for &nr in BASE_SYSCALLS {
+            assert_eq!(eval(&prog, ARCH, nr, [0, 0]), libc::SECCOMP_RET_ALLOW);
+        }
+        assert_eq!(
+            eval(&prog, ARCH, libc::SYS_execve, [0, 0]),
+            libc::SECCOMP_RET_TRAP
+        );
+        assert_eq!(
+            eval(&prog, ARCH, libc::SYS_accept4, [0, 0]),
+            libc::SECCOMP_RET_TRAP
+        );
+        assert_eq!(
+            eval(&prog, 0x4000_0003, libc::SYS_read, [0, 0]),
+            libc::SECCOMP_RET_KILL_PROCESS
+        );
+    }
+
+    #[test]
+    fn test_filter_checks_ioctl_arguments() {
+        let prog = build_filter(&Policy::daemon(5, 0x40085203), ARCH);
+        let ioctl = |fd: u64, req: u32| eval(&prog, ARCH, libc::SYS_ioctl, [fd, u64::from(req)]);
+        assert_eq!(ioctl(5, 0x40085203), libc::SECCOMP_RET_ALLOW);
+        assert_eq!(ioctl(6, 0x40085203), libc::SECCOMP_RET_TRAP);
+        assert_eq!(ioctl(5, 0x40085204), libc::SECCOMP_RET_TRAP);
+        assert_eq!(ioctl(2, libc::TCGETS as u32), libc::SECCOMP_RET_ALLOW);
+        assert_eq!(ioctl(7, libc::TCSETS as u32), libc::SECCOMP_RET_TRAP);
+
+        let prctl = |option: u64| eval(&prog, ARCH, libc::SYS_prctl, [option, 0]);
+        assert_eq!(prctl(libc::PR_SET_NAME as u64), libc::SECCOMP_RET_ALLOW);
+        assert_eq!(prctl(libc::PR_SET_DUMPABLE as u64), libc::SECCOMP_RET_TRAP);
+    }
+
+    #[test]
+    fn test_http_client_policy() {
+        let prog = build_filter(&Policy::base().http_client(), ARCH);
+        let socket = |family: i32| eval(&prog, ARCH, libc::SYS_socket, [family as u64, 0]);
+        assert_eq!(socket(libc::AF_INET), libc::SECCOMP_RET_ALLOW);
+        assert_eq!(socket(libc::AF_INET6), libc::SECCOMP_RET_ALLOW);
+        assert_eq!(socket(libc::AF_UNIX), libc::SECCOMP_RET_TRAP);
+        assert_eq!(
+            eval(&prog, ARCH, libc::SYS_connect, [3, 0]),
+            libc::SECCOMP_RET_ALLOW
+        );
+    }
+
+    #[cfg(feature = "grpc")]
+    #[test]
+    fn test_serve_policy() {
+        let prog = build_filter(&Policy::serve(true), ARCH);
+        assert_eq!(
+            eval(&prog, ARCH, libc::SYS_accept4, [0, 0]),
+            libc::SECCOMP_RET_ALLOW
+        );
+        assert_eq!(
+            eval(
+                &prog,
+                ARCH,
+                libc::SYS_ioctl,
+                [7, u64::from(libc::TCSETS as u32)]
+            ),
+            libc::SECCOMP_RET_ALLOW
+        );
+        assert_eq!(
+            eval(&prog, ARCH, libc::SYS_ioctl, [5, 0x40085203]),
+            libc::SECCOMP_RET_TRAP
+        );
+    }
+}
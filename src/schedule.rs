@@ -0,0 +1,251 @@
+//! Time-based daemon injections: `--inject-every` intervals and
+//! `--inject-cron` five-field cron expressions, in local time.
+
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::check;
+use crate::error::Error;
+
+/// How far ahead a cron expression is searched for its next match.
+const MAX_SEARCH_DAYS: i64 = 5 * 366;
+
+/// One cron field as a bitmask of the values it matches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Field {
+    mask: u64,
+    /// Written as `*` (or `*/n`), which matters for day-of-month/day-of-week.
+    any: bool,
+}
+
+impl Field {
+    fn parse(s: &str, name: &str, min: u32, max: u32) -> Result<Self, Error> {
+        let invalid = || Error::InvalidArgs(format!("invalid cron {} field: {}", name, s));
+        let mut mask = 0u64;
+        for part in s.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+                None => (part, 1),
+            };
+            let (lo, hi) = match range {
+                "*" => (min, max),
+                _ => match range.split_once('-') {
+                    Some((lo, hi)) => (
+                        lo.parse().map_err(|_| invalid())?,
+                        hi.parse().map_err(|_| invalid())?,
+                    ),
+                    None => {
+                        let v = range.parse().map_err(|_| invalid())?;
+                        (v, if step > 1 { max } else { v })
+                    }
+                },
+            };
+            if step == 0 || lo < min || hi > max || lo > hi {
+                return Err(invalid());
+            }
+            for v in (lo..=hi).step_by(step as usize) {
+                mask |= 1 << v;
+            }
+        }
+        Ok(Self {
+            mask,
+            any: s.starts_with('*'),
+        })
+    }
+
+    fn matches(&self, v: i32) -> bool {
+        (0..64).contains(&v) && self.mask & (1 << v) != 0
+    }
+}
+
+/// A parsed `minute hour day-of-month month day-of-week` expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cron {
+    expr: String,
+    minute: Field,
+    hour: Field,
+    dom: Field,
+    month: Field,
+    dow: Field,
+}
+
+impl Cron {
+    pub fn parse(expr: &str) -> Result<Self, Error> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields[..] else {
+            return Err(Error::InvalidArgs(format!(
+                "cron expression needs 5 fields (minute hour day month weekday): {}",
+                expr
+            )));
+        };
+        let mut dow = Field::parse(dow, "day-of-week", 0, 7)?;
+        // 7 is Sunday too
+        if dow.mask & (1 << 7) != 0 {
+            dow.mask = (dow.mask | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            expr: fields.join(" "),
+            minute: Field::parse(minute, "minute", 0, 59)?,
+            hour: Field::parse(hour, "hour", 0, 23)?,
+            dom: Field::parse(dom, "day-of-month", 1, 31)?,
+            month: Field::parse(month, "month", 1, 12)?,
+            dow,
+        })
+    }
+
+    /// Whether the day matches. As in cron, when both day fields are
+    /// restricted, either one matching is enough.
+    fn day_matches(&self, tm: &libc::tm) -> bool {
+        let dom = self.dom.matches(tm.tm_mday);
+        let dow = self.dow.matches(tm.tm_wday);
+        if !self.month.matches(tm.tm_mon + 1) {
+            return false;
+        }
+        match (self.dom.any, self.dow.any) {
+            (true, true) => true,
+            (true, false) => dow,
+            (false, true) => dom,
+            (false, false) => dom || dow,
+        }
+    }
+
+    fn matches(&self, tm: &libc::tm) -> bool {
+        self.day_matches(tm) && self.hour.matches(tm.tm_hour) && self.minute.matches(tm.tm_min)
+    }
+
+    /// The first matching minute after `after` (unix seconds), if any within
+    /// the next few years.
+    fn next_after(&self, after: i64) -> Option<i64> {
+        let mut t = after - after.rem_euclid(60) + 60;
+        let end = after + MAX_SEARCH_DAYS * 86400;
+        while t <= end {
+            let tm = local_time(t);
+            // Whole hours are skipped rather than days, which would miss
+            // the first hour after a DST change
+            if self.matches(&tm) {
+                return Some(t);
+            } else if !self.day_matches(&tm) || !self.hour.matches(tm.tm_hour) {
+                t += 3600 - i64::from(tm.tm_min * 60);
+            } else {
+                t += 60;
+            }
+        }
+        None
+    }
+}
+
+fn local_time(t: i64) -> libc::tm {
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&(t as libc::time_t), &mut tm);
+        tm
+    }
+}
+
+/// When the daemon injects regardless of `entropy_avail`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schedule {
+    Every(Duration),
+    Cron(Cron),
+}
+
+impl Schedule {
+    /// The schedule from `--inject-every` or `--inject-cron`, if either is
+    /// given.
+    pub fn from_args(every: Option<&str>, cron: Option<&str>) -> Result<Option<Self>, Error> {
+        match (every, cron) {
+            (Some(every), None) => Ok(Some(Self::Every(check::parse_duration(every)?))),
+            (None, Some(cron)) => Ok(Some(Self::Cron(Cron::parse(cron)?))),
+            (None, None) => Ok(None),
+            (Some(_), Some(_)) => Err(Error::InvalidArgs(
+                "--inject-every and --inject-cron cannot be combined".into(),
+            )),
+        }
+    }
+
+    /// Time from `now` until the next scheduled injection.
+    pub fn until_next(&self, now: SystemTime) -> Result<Duration, Error> {
+        match self {
+            Self::Every(interval) => Ok(*interval),
+            Self::Cron(cron) => {
+                let since_epoch = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+                let secs = since_epoch.as_secs() as i64;
+                let next = cron.next_after(secs).ok_or_else(|| {
+                    Error::InvalidArgs(format!("cron expression never matches: {}", cron.expr))
+                })?;
+                Ok(Duration::from_secs((next - secs) as u64)
+                    .saturating_sub(Duration::from_nanos(u64::from(since_epoch.subsec_nanos()))))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Every(interval) => write!(f, "every {}", check::format_duration(*interval)),
+            Self::Cron(cron) => write!(f, "cron \"{}\"", cron.expr),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tm(mon: i32, mday: i32, wday: i32, hour: i32, min: i32) -> libc::tm {
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        (tm.tm_mon, tm.tm_mday, tm.tm_wday) = (mon - 1, mday, wday);
+        (tm.tm_hour, tm.tm_min) = (hour, min);
+        tm
+    }
+
+    #[test]
+    fn test_cron_matches() {
+        let every10 = Cron::parse("*/10 * * * *").unwrap();
+        assert!(every10.matches(&tm(1, 1, 0, 3, 40)));
+        assert!(!every10.matches(&tm(1, 1, 0, 3, 41)));
+
+        // 09:30 on weekdays, and on the 1st and 15th whatever the weekday
+        let cron = Cron::parse("30 9 1,15 * 1-5").unwrap();
+        assert!(cron.matches(&tm(6, 3, 3, 9, 30)));
+        assert!(!cron.matches(&tm(6, 6, 6, 9, 30)));
+        assert!(cron.matches(&tm(6, 15, 0, 9, 30)));
+        assert!(!cron.matches(&tm(6, 3, 3, 10, 30)));
+
+        assert!(Cron::parse("0 0 * * 7")
+            .unwrap()
+            .matches(&tm(2, 4, 0, 0, 0)));
+        assert!(Cron::parse("0 12 * 3-4/1 *")
+            .unwrap()
+            .matches(&tm(4, 1, 2, 12, 0)));
+        for bad in [
+            "* * * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "a * * * *",
+        ] {
+            assert!(Cron::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_until_next() {
+        let now = UNIX_EPOCH + Duration::from_millis(1_700_000_012_500);
+        let minutely = Schedule::from_args(None, Some("* * * * *"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            minutely.until_next(now).unwrap(),
+            Duration::from_millis(27_500)
+        );
+        let every = Schedule::from_args(Some("10m"), None).unwrap().unwrap();
+        assert_eq!(every.until_next(now).unwrap(), Duration::from_secs(600));
+        assert_eq!(every.to_string(), "every 10m");
+        let never = Schedule::from_args(None, Some("0 0 31 2 *"))
+            .unwrap()
+            .unwrap();
+        assert!(never.until_next(now).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+//! Keeps secret buffers out of swap and core dumps.
+//!
+//! Off until [`enable`] is called; [`protect`] is then a no-op cheap enough
+//! to call on every seed and output buffer.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::Error;
+
+/// Buffers at least this large are also excluded from core dumps
+/// (MADV_DONTDUMP only applies to whole pages).
+const DONTDUMP_MIN_BYTES: usize = 64 * 1024;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Marks the process non-dumpable (which also blocks ptrace attach by
+/// unprivileged users) and turns on buffer locking.
+pub fn enable() -> Result<(), Error> {
+    if unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0, 0, 0, 0) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    ENABLED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+fn page_size() -> usize {
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if size > 0 {
+        size as usize
+    } else {
+        4096
+    }
+}
+
+/// The whole pages inside `[addr, addr + len)`, as `(start, len)`.
+fn inner_pages(addr: usize, len: usize, page: usize) -> Option<(usize, usize)> {
+    let start = addr.checked_add(page - 1)? / page * page;
+    let end = addr.checked_add(len)? / page * page;
+    (end > start).then(|| (start, end - start))
+}
+
+/// Pages of one buffer held resident until drop. Must be dropped before the
+/// buffer is freed or reallocated.
+pub struct Locked {
+    addr: usize,
+    len: usize,
+    dontdump: Option<(usize, usize)>,
+}
+
+impl Drop for Locked {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some((start, len)) = self.dontdump {
+                libc::madvise(start as *mut libc::c_void, len, libc::MADV_DODUMP);
+            }
+            if self.len > 0 {
+                libc::munlock(self.addr as *const libc::c_void, self.len);
+            }
+        }
+    }
+}
+
+/// mlock()s `buf` and, if it is large, excludes it from core dumps. Failures
+/// (typically RLIMIT_MEMLOCK) are logged once and otherwise ignored.
+pub fn protect(buf: &[u8]) -> Locked {
+    let mut locked = Locked {
+        addr: buf.as_ptr() as usize,
+        len: 0,
+        dontdump: None,
+    };
+    if !enabled() || buf.is_empty() {
+        return locked;
+    }
+
+    if unsafe { libc::mlock(buf.as_ptr() as *const libc::c_void, buf.len()) } == 0 {
+        locked.len = buf.len();
+    } else if !WARNED.swap(true, Ordering::Relaxed) {
+        log::warn!(
+            target: "mixrand::secmem",
+            "mlock failed: {} (raise RLIMIT_MEMLOCK); secrets may be swapped",
+            std::io::Error::last_os_error(),
+        );
+    }
+
+    if buf.len() >= DONTDUMP_MIN_BYTES {
+        if let Some((start, len)) = inner_pages(locked.addr, buf.len(), page_size()) {
+            let ret =
+                unsafe { libc::madvise(start as *mut libc::c_void, len, libc::MADV_DONTDUMP) };
+            if ret == 0 {
+                locked.dontdump = Some((start, len));
+            }
+        }
+    }
+    locked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inner_pages() {
+        assert_eq!(inner_pages(0, 8192, 4096), Some((0, 8192)));
+        assert_eq!(inner_pages(100, 8192, 4096), Some((4096, 4096)));
+        assert_eq!(inner_pages(100, 4000, 4096), None);
+        assert_eq!(inner_pages(4096, 4095, 4096), None);
+        assert_eq!(inner_pages(usize::MAX - 10, 100, 4096), None);
+    }
+
+    #[test]
+    fn test_protect_disabled_is_noop() {
+        let buf = vec![0u8; 128];
+        let locked = protect(&buf);
+        assert_eq!(locked.len, 0);
+        assert!(locked.dontdump.is_none());
+    }
+}
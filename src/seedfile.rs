@@ -0,0 +1,45 @@
+use std::fs;
+use std::path::Path;
+
+use crate::atomicfile;
+use crate::error::Error;
+
+/// Size of a saved seed: one full kernel input pool.
+pub const SEED_BYTES: usize = 512;
+
+/// Reads the saved seed, or `None` if the file is missing or empty.
+pub fn load(path: &Path) -> Result<Option<Vec<u8>>, Error> {
+    match fs::read(path) {
+        Ok(data) if data.is_empty() => Ok(None),
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Atomically replaces the seed file with `seed` (see [`atomicfile::write`]),
+/// so a crash never leaves a truncated or reused seed behind.
+pub fn save(path: &Path, seed: &[u8]) -> Result<(), Error> {
+    atomicfile::write(path, seed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_save_load_roundtrip() {
+        let path = std::env::temp_dir().join("mixrand_test_seed.bin");
+        let _ = fs::remove_file(&path);
+        assert!(load(&path).unwrap().is_none());
+
+        save(&path, &[1u8; SEED_BYTES]).unwrap();
+        save(&path, &[2u8; SEED_BYTES]).unwrap();
+        assert_eq!(load(&path).unwrap().unwrap(), vec![2u8; SEED_BYTES]);
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,297 @@
+use std::io;
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Signals the process can register interest in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Term,
+    Int,
+    Hup,
+    Usr1,
+    Usr2,
+}
+
+impl Signal {
+    const ALL: [Signal; 5] = [
+        Signal::Term,
+        Signal::Int,
+        Signal::Hup,
+        Signal::Usr1,
+        Signal::Usr2,
+    ];
+
+    fn number(self) -> libc::c_int {
+        match self {
+            Signal::Term => libc::SIGTERM,
+            Signal::Int => libc::SIGINT,
+            Signal::Hup => libc::SIGHUP,
+            Signal::Usr1 => libc::SIGUSR1,
+            Signal::Usr2 => libc::SIGUSR2,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn from_number(sig: libc::c_int) -> Option<Signal> {
+        Signal::ALL.into_iter().find(|s| s.number() == sig)
+    }
+}
+
+type Callback = Box<dyn FnMut() + Send>;
+
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+static SIGINT_COUNT: AtomicU32 = AtomicU32::new(0);
+static WAKE: AtomicBool = AtomicBool::new(false);
+static PENDING: [AtomicBool; 5] = [
+    AtomicBool::new(false),
+    AtomicBool::new(false),
+    AtomicBool::new(false),
+    AtomicBool::new(false),
+    AtomicBool::new(false),
+];
+static CALLBACKS: Mutex<Vec<(Signal, Callback)>> = Mutex::new(Vec::new());
+
+const FORCED_EXIT_MSG: &[u8] = b"\nmixrand: second interrupt, exiting immediately\n";
+
+/// Async-signal-safe handler: only touches atomics, and on a second SIGINT
+/// writes a fixed message and `_exit`s.
+extern "C" fn signal_handler(sig: libc::c_int) {
+    if sig == libc::SIGINT && SIGINT_COUNT.fetch_add(1, Ordering::Relaxed) >= 1 {
+        unsafe {
+            libc::write(2, FORCED_EXIT_MSG.as_ptr().cast(), FORCED_EXIT_MSG.len());
+            libc::_exit(130);
+        }
+    }
+    if sig == libc::SIGTERM || sig == libc::SIGINT {
+        SHUTDOWN.store(true, Ordering::Relaxed);
+    }
+    if let Some(s) = Signal::from_number(sig) {
+        PENDING[s.index()].store(true, Ordering::Relaxed);
+    }
+}
+
+/// Installs the shared handler for each of `signals`. SIGTERM and SIGINT
+/// request shutdown; a second SIGINT exits immediately.
+///
+/// Handlers are installed without `SA_RESTART`, so a blocking syscall
+/// returns `EINTR` when a signal lands; callers check
+/// [`shutdown_requested`] before retrying.
+pub fn install(signals: &[Signal]) {
+    for &s in signals {
+        unsafe {
+            let mut sa: libc::sigaction = std::mem::zeroed();
+            sa.sa_sigaction = signal_handler as *const () as usize;
+            sa.sa_flags = 0;
+            libc::sigemptyset(&mut sa.sa_mask);
+            libc::sigaction(s.number(), &sa, std::ptr::null_mut());
+        }
+    }
+}
+
+/// Registers `callback` to run from [`dispatch`] after `signal` arrives.
+pub fn on<F>(signal: Signal, callback: F)
+where
+    F: FnMut() + Send + 'static,
+{
+    if let Ok(mut cbs) = CALLBACKS.lock() {
+        cbs.push((signal, Box::new(callback)));
+    }
+}
+
+/// Runs callbacks for every signal received since the last call. Must be
+/// called from normal (non-handler) context, typically the main loop.
+/// Callbacks run without the registry locked, so they may register more.
+pub fn dispatch() {
+    for s in Signal::ALL {
+        if !PENDING[s.index()].swap(false, Ordering::Relaxed) {
+            continue;
+        }
+        log::debug!("received {:?}", s);
+        // Run the callbacks with the lock released, so they may call [`on`]
+        let mut matching: Vec<(Signal, Callback)> = match CALLBACKS.lock() {
+            Ok(mut cbs) => {
+                let (matching, rest) = std::mem::take(&mut *cbs)
+                    .into_iter()
+                    .partition(|(sig, _)| *sig == s);
+                *cbs = rest;
+                matching
+            }
+            Err(_) => continue,
+        };
+        for (_, cb) in matching.iter_mut() {
+            cb();
+        }
+        if let Ok(mut cbs) = CALLBACKS.lock() {
+            cbs.extend(matching);
+        }
+    }
+}
+
+/// True once SIGTERM or SIGINT has been received.
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN.load(Ordering::Relaxed)
+}
+
+/// Ends the current [`interruptible_sleep`], and any started before
+/// [`take_wake`], early: for a callback that has work for the main loop.
+pub fn request_wake() {
+    WAKE.store(true, Ordering::Relaxed);
+}
+
+/// True once [`request_wake`] has been called, until [`take_wake`].
+pub fn wake_requested() -> bool {
+    WAKE.load(Ordering::Relaxed)
+}
+
+/// Clears a wake request, returning whether there was one.
+pub fn take_wake() -> bool {
+    WAKE.swap(false, Ordering::Relaxed)
+}
+
+/// Sleeps in 250ms steps, dispatching callbacks and returning early once
+/// shutdown or a wake has been requested.
+pub fn interruptible_sleep(total: Duration) {
+    let step = Duration::from_millis(250);
+    let mut remaining = total;
+    while remaining > Duration::ZERO && !shutdown_requested() && !wake_requested() {
+        let s = remaining.min(step);
+        thread::sleep(s);
+        remaining = remaining.saturating_sub(s);
+        dispatch();
+    }
+}
+
+/// Poll step for [`wait_readable`]: the longest a signal that raced the
+/// `EINTR` window can go unnoticed.
+const POLL_STEP_MS: u64 = 100;
+
+/// Waits until `fd` is readable or `timeout` (forever with `None`)
+/// elapses, returning `Ok(false)` on timeout. Polls in short steps and
+/// retries `EINTR`, but fails with `ErrorKind::Interrupted` once shutdown
+/// has been requested.
+pub fn wait_readable(fd: RawFd, timeout: Option<Duration>) -> io::Result<bool> {
+    let deadline = timeout.map(|t| Instant::now() + t);
+    loop {
+        if shutdown_requested() {
+            return Err(interrupted());
+        }
+        let step = match deadline {
+            Some(d) => {
+                let left = d.saturating_duration_since(Instant::now());
+                if left.is_zero() {
+                    return Ok(false);
+                }
+                (left.as_millis() as u64).clamp(1, POLL_STEP_MS)
+            }
+            None => POLL_STEP_MS,
+        };
+        let mut pfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ret = unsafe { libc::poll(&mut pfd, 1, step as libc::c_int) };
+        if ret > 0 {
+            return Ok(true);
+        }
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+}
+
+/// The error a blocking read returns when Ctrl-C or SIGTERM cut it short.
+pub fn interrupted() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "interrupted by signal")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU32;
+    use std::sync::Arc;
+
+    /// Held by every test that raises a signal or dispatches, since
+    /// PENDING and CALLBACKS are process-wide.
+    static DISPATCH: Mutex<()> = Mutex::new(());
+
+    fn serialized() -> std::sync::MutexGuard<'static, ()> {
+        DISPATCH.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[test]
+    fn test_from_number_roundtrip() {
+        for s in Signal::ALL {
+            assert_eq!(Signal::from_number(s.number()), Some(s));
+        }
+        assert_eq!(Signal::from_number(libc::SIGWINCH), None);
+    }
+
+    #[test]
+    fn test_callback_dispatched_once() {
+        let _serial = serialized();
+        let hits = Arc::new(AtomicU32::new(0));
+        let h = Arc::clone(&hits);
+        install(&[Signal::Usr1]);
+        on(Signal::Usr1, move || {
+            h.fetch_add(1, Ordering::Relaxed);
+        });
+
+        unsafe { libc::raise(libc::SIGUSR1) };
+        dispatch();
+        dispatch();
+
+        assert_eq!(hits.load(Ordering::Relaxed), 1);
+        assert!(!shutdown_requested());
+    }
+
+    #[test]
+    fn test_hup_callback_may_register_another() {
+        let _serial = serialized();
+        let hits = Arc::new(AtomicU32::new(0));
+        let h = Arc::clone(&hits);
+        install(&[Signal::Hup]);
+        on(Signal::Hup, move || {
+            h.fetch_add(1, Ordering::Relaxed);
+            let h = Arc::clone(&h);
+            // Would deadlock if dispatch held the registry lock
+            on(Signal::Hup, move || {
+                h.fetch_add(10, Ordering::Relaxed);
+            });
+        });
+
+        unsafe { libc::raise(libc::SIGHUP) };
+        dispatch();
+        assert_eq!(hits.load(Ordering::Relaxed), 1);
+
+        unsafe { libc::raise(libc::SIGHUP) };
+        dispatch();
+        assert_eq!(hits.load(Ordering::Relaxed), 12);
+        assert!(!shutdown_requested());
+    }
+
+    #[test]
+    fn test_wait_readable() {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let start = Instant::now();
+        assert!(!wait_readable(fds[0], Some(Duration::from_millis(150))).unwrap());
+        assert!(start.elapsed() >= Duration::from_millis(150));
+
+        unsafe { libc::write(fds[1], b"x".as_ptr().cast(), 1) };
+        assert!(wait_readable(fds[0], None).unwrap());
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+    }
+}
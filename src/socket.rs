@@ -0,0 +1,477 @@
+//! `serve-socket`: hands mixed entropy to local clients over a unix stream
+//! socket, speaking the CBOR protocol in [`crate::wire`].
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixListener;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::auth::{self, Auth, Client, Quota};
+use crate::cli::{OnTimeout, ServeSocketArgs};
+use crate::config::Config;
+use crate::entropy::{self, cpurng, Deadline};
+use crate::error::Error;
+use crate::fips;
+use crate::reservation::Reservations;
+use crate::signals::{self, Signal};
+use crate::vsock::{self, ClientSlot};
+use crate::wire::{self, Data, Op, Reply, Request, Response, Served};
+use crate::workerpool::{self, WorkerPool};
+
+/// Default socket for `serve-socket` and `client`.
+pub const DEFAULT_SOCKET: &str = "/run/mixrand/mixrand.sock";
+/// Largest request the server can be configured to accept.
+pub const MAX_REQUEST_BYTES: usize = 1 << 20;
+
+/// How often the accept loop checks for shutdown.
+const ACCEPT_POLL_MS: libc::c_int = 250;
+/// Clients idle for longer than this are disconnected.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What every connection is served under.
+struct Limits {
+    max_request_bytes: usize,
+    auth: Auth,
+    /// `[limits] client_bytes_per_minute`, for tokens without a quota
+    quota_bytes: u64,
+    quota: Quota,
+    reservations: Reservations,
+}
+
+/// Generates and encodes the bytes (characters for `-f text`) for one
+/// request, charging them to `client` if it was authenticated. With a worker pool the deadline is
+/// moot, since no source is read.
+fn generate(
+    request: &Request,
+    config: &Config,
+    limits: &Limits,
+    pool: Option<&WorkerPool>,
+    client: Option<&Client>,
+) -> Result<Served, Error> {
+    if request.bytes == 0 || request.bytes > limits.max_request_bytes {
+        return Err(Error::InvalidArgs(format!(
+            "request must be between 1 and {} bytes",
+            limits.max_request_bytes
+        )));
+    }
+    if let Some(client) = client {
+        if !limits
+            .quota
+            .take(client, request.bytes as u64, Instant::now())
+        {
+            return Err(Error::InvalidArgs(auth::exceeded(client)));
+        }
+    }
+    let deadline = request
+        .deadline_ms
+        .map(|ms| Deadline::after(Duration::from_millis(ms), OnTimeout::Fail));
+    let collect = |n| match pool {
+        Some(pool) => pool.fill(n),
+        None => entropy::generate_within(n, config, deadline),
+    };
+    let (mut result, data) = Data::draw(request.bytes, &request.format, collect)?;
+    cpurng::zeroize_vec(&mut result.bytes);
+    Ok(Served {
+        data,
+        source: result.source,
+    })
+}
+
+/// Answers one request from the connection `peer`, which owns any
+/// reservations it makes.
+fn answer(
+    request: &Request,
+    config: &Config,
+    limits: &Limits,
+    pool: Option<&WorkerPool>,
+    client: Option<&Client>,
+    peer: &str,
+) -> Result<Reply, Error> {
+    let reservations = &limits.reservations;
+    match request.op {
+        Op::Get => generate(request, config, limits, pool, client).map(Reply::Served),
+        Op::Reserve { hold_ms } => {
+            reservations.check_room(peer)?;
+            let served = generate(request, config, limits, pool, client)?;
+            let (reservation, hold) =
+                reservations.hold(peer, served, hold_ms.map(Duration::from_millis));
+            Ok(Reply::Reserved {
+                reservation,
+                expires_ms: hold.as_millis() as u64,
+            })
+        }
+        Op::Fetch(id) => reservations.fetch(peer, id).map(Reply::Served),
+        Op::Cancel(id) => reservations.cancel(peer, id).map(|()| Reply::Cancelled),
+    }
+}
+
+/// Answers requests on one connection from `peer` until the client hangs
+/// up, returning the bytes generated for it. Malformed CBOR gets an error
+/// response and ends the connection; a well-formed request that cannot be
+/// served, or lacks a valid token, only fails that request.
+fn serve_conn<S: Read + Write>(
+    conn: &mut S,
+    config: &Config,
+    limits: &Limits,
+    pool: Option<&WorkerPool>,
+    peer: &str,
+) -> Result<u64, Error> {
+    let mut served = 0;
+    loop {
+        let value = match wire::read_request(conn) {
+            Ok(Some(value)) => value,
+            Ok(None) => return Ok(served),
+            Err(Error::InvalidArgs(msg)) => {
+                let response = Response {
+                    id: 0,
+                    healthy: fips::check_operational().is_ok(),
+                    result: Err(msg.clone()),
+                };
+                let _ = wire::write(conn, response.to_value());
+                return Err(Error::InvalidArgs(msg));
+            }
+            Err(e) => return Err(e),
+        };
+        let result = Request::from_value(&value).and_then(|request| {
+            let client = limits
+                .auth
+                .check(request.token.as_deref(), limits.quota_bytes)
+                .inspect_err(|e| log::warn!(target: "mixrand::audit", "refused {}: {}", peer, e))?;
+            let answered = answer(&request, config, limits, pool, client.as_ref(), peer)
+                .map_err(|e| e.to_string());
+            if let Ok(reply) = &answered {
+                match reply {
+                    Reply::Served(ok) if request.op == Op::Get => {
+                        log::debug!("served {}B from {}", request.bytes, ok.source)
+                    }
+                    Reply::Reserved { reservation, .. } => {
+                        log::debug!("holding {}B as reservation {}", request.bytes, reservation)
+                    }
+                    _ => {}
+                }
+                // Counted and audited when generated, not when fetched
+                if request.bytes > 0 {
+                    served += request.bytes as u64;
+                    if let Some(client) = &client {
+                        auth::audit(client, peer, "serve-socket", request.bytes as u64);
+                    }
+                }
+            }
+            answered
+        });
+        let mut response = Response {
+            id: wire::request_id(&value),
+            healthy: fips::check_operational().is_ok(),
+            result,
+        };
+        let written = wire::write(conn, response.to_value());
+        response.zeroize();
+        written?;
+    }
+}
+
+/// Binds the listening socket, replacing a stale one left by a previous
+/// run. The socket is world-connectable, like /dev/urandom.
+fn listen(path: &Path) -> Result<UnixListener, Error> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => fs::remove_file(path)?,
+        Ok(_) => {
+            return Err(Error::InvalidArgs(format!(
+                "{} exists and is not a socket",
+                path.display()
+            )))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o666))?;
+    Ok(listener)
+}
+
+pub fn run(args: &ServeSocketArgs, config: &Config) -> Result<(), Error> {
+    if args.max_clients == 0 {
+        return Err(Error::InvalidArgs(
+            "max-clients must be greater than 0".into(),
+        ));
+    }
+    let limits = Arc::new(Limits {
+        max_request_bytes: (args.max_request_bytes as usize).min(MAX_REQUEST_BYTES),
+        auth: Auth::load(config)?,
+        quota_bytes: config.limits.client_bytes_per_minute,
+        quota: Quota::default(),
+        reservations: Reservations::new(
+            args.max_reservations,
+            Duration::from_millis(args.max_hold_ms),
+        ),
+    });
+
+    signals::install(&[Signal::Term, Signal::Int]);
+    let pool = workerpool::spawn(config)?;
+
+    let listener = listen(&args.socket).map_err(|e| {
+        Error::InvalidArgs(format!("cannot listen on {}: {}", args.socket.display(), e))
+    })?;
+    vsock::install_sandbox(args.no_sandbox, config);
+    log::info!(
+        "socket server listening on {} (protocol v{}, max clients={}, max request={}B, tokens={})",
+        args.socket.display(),
+        wire::VERSION,
+        args.max_clients,
+        limits.max_request_bytes,
+        limits.auth.count()
+    );
+
+    let config = Arc::new(config.clone());
+    let active = Arc::new(AtomicUsize::new(0));
+    let mut accepted = 0u64;
+    while !signals::shutdown_requested() {
+        let expired = limits.reservations.expire(Instant::now());
+        if expired > 0 {
+            log::debug!("wiped {} expired reservations", expired);
+        }
+        let mut pfd = libc::pollfd {
+            fd: listener.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut pfd, 1, ACCEPT_POLL_MS) } <= 0 {
+            continue;
+        }
+        let mut conn = match listener.accept() {
+            Ok((conn, _)) => conn,
+            Err(e) => {
+                log::warn!("accept failed: {}", e);
+                continue;
+            }
+        };
+        accepted += 1;
+        let client = accepted;
+        if active.load(Ordering::Relaxed) >= args.max_clients {
+            log::warn!(
+                "refused client {}: {} clients already connected",
+                client,
+                args.max_clients
+            );
+            continue;
+        }
+        let timeouts = conn
+            .set_read_timeout(Some(IDLE_TIMEOUT))
+            .and_then(|()| conn.set_write_timeout(Some(IDLE_TIMEOUT)));
+        if let Err(e) = timeouts {
+            log::warn!("client {}: {}", client, e);
+            continue;
+        }
+
+        active.fetch_add(1, Ordering::Relaxed);
+        let slot = ClientSlot(Arc::clone(&active));
+        let config = Arc::clone(&config);
+        let limits = Arc::clone(&limits);
+        let pool = pool.clone();
+        let spawned = thread::Builder::new()
+            .name(format!("socket-{}", client))
+            .spawn(move || {
+                let _slot = slot;
+                log::info!("client {} connected", client);
+                let peer = format!("client {}", client);
+                match serve_conn(&mut conn, &config, &limits, pool.as_deref(), &peer) {
+                    Ok(served) => log::info!("client {} disconnected after {}B", client, served),
+                    Err(e) => log::warn!("client {}: {}", client, e),
+                }
+                let unfetched = limits.reservations.release(&peer);
+                if unfetched > 0 {
+                    log::info!(
+                        "client {}: wiped {} unfetched reservations",
+                        client,
+                        unfetched
+                    );
+                }
+            });
+        if let Err(e) = spawned {
+            log::error!("cannot serve client {}: {}", client, e);
+        }
+    }
+
+    let _ = fs::remove_file(&args.socket);
+    if let Some(pool) = pool {
+        pool.log_totals();
+    }
+    log::info!("socket server shut down");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{ClientArgs, OutputFormat};
+    use crate::client;
+    use crate::config::TokenConfig;
+    use clap::{Args, FromArgMatches};
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn test_serve_conn() {
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        let oversized = Request {
+            id: 1,
+            op: Op::Get,
+            bytes: 1024,
+            format: OutputFormat::Hex,
+            deadline_ms: None,
+            token: Some("0123456789abcdef".into()),
+        };
+        wire::write(&mut client, oversized.to_value()).unwrap();
+        let anonymous = Request {
+            id: 2,
+            bytes: 16,
+            token: None,
+            ..oversized
+        };
+        wire::write(&mut client, anonymous.to_value()).unwrap();
+        client.write_all(&[0x5f]).unwrap();
+        let mut config = Config::default();
+        config.auth.tokens.push(TokenConfig {
+            name: "test".into(),
+            token: "0123456789abcdef".into(),
+            bytes_per_minute: 0,
+        });
+        let limits = Limits {
+            max_request_bytes: 512,
+            auth: Auth::load(&config).unwrap(),
+            quota_bytes: 0,
+            quota: Quota::default(),
+            reservations: Reservations::new(1, Duration::from_secs(60)),
+        };
+        assert!(matches!(
+            serve_conn(&mut server, &config, &limits, None, "test"),
+            Err(Error::InvalidArgs(_))
+        ));
+        for (id, expected) in [
+            (1, "between 1 and 512 bytes"),
+            (2, "a token is required"),
+            (0, "malformed CBOR"),
+        ] {
+            let response = wire::read_response(&mut client, 1024).unwrap().unwrap();
+            assert_eq!(response.id, id);
+            match response.result {
+                Err(msg) => assert!(msg.contains(expected), "{}", msg),
+                Ok(_) => panic!("expected an error response"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_text_counts_characters() {
+        let limits = || Limits {
+            max_request_bytes: 512,
+            auth: Auth::load(&Config::default()).unwrap(),
+            quota_bytes: 0,
+            quota: Quota::default(),
+            reservations: Reservations::new(1, Duration::from_secs(60)),
+        };
+
+        // Served as text
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        let request = Request {
+            id: 1,
+            op: Op::Get,
+            bytes: 64,
+            format: OutputFormat::Text,
+            deadline_ms: None,
+            token: None,
+        };
+        wire::write(&mut client, request.to_value()).unwrap();
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+        serve_conn(&mut server, &Config::default(), &limits(), None, "test").unwrap();
+        match wire::read_response(&mut client, 1024)
+            .unwrap()
+            .unwrap()
+            .result
+        {
+            Ok(Reply::Served(Served {
+                data: Data::Text(text),
+                ..
+            })) => assert_eq!(text.trim_end().chars().count(), 64),
+            other => panic!("expected text, got {:?}", other),
+        }
+
+        // Formatted as text by `mixrand client`
+        let path = std::env::temp_dir().join(format!("mixrand-text-{}.sock", std::process::id()));
+        let out = path.with_extension("txt");
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = thread::spawn(move || {
+            let (mut conn, _) = listener.accept().unwrap();
+            serve_conn(&mut conn, &Config::default(), &limits(), None, "test").unwrap()
+        });
+        let argv = ["client", "-n", "64", "-f", "text", "-s"];
+        let matches = ClientArgs::augment_args(clap::Command::new("client"))
+            .try_get_matches_from(argv.iter().copied().chain([
+                path.to_str().unwrap(),
+                "-o",
+                out.to_str().unwrap(),
+            ]))
+            .unwrap();
+        client::run(&ClientArgs::from_arg_matches(&matches).unwrap()).unwrap();
+        server.join().unwrap();
+        let text = fs::read_to_string(&out).unwrap();
+        assert_eq!(text.trim_end().chars().count(), 64);
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(&out);
+    }
+
+    #[test]
+    fn test_reservation_ops() {
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        let config = Config::default();
+        let limits = Limits {
+            max_request_bytes: 512,
+            auth: Auth::load(&config).unwrap(),
+            quota_bytes: 0,
+            quota: Quota::default(),
+            reservations: Reservations::new(1, Duration::from_secs(60)),
+        };
+        let held = Served {
+            data: Data::Bytes(vec![7; 16]),
+            source: "test".into(),
+        };
+        let (id, _) = limits.reservations.hold("test", held.clone(), None);
+        let request = |n, op| Request {
+            id: n,
+            op,
+            bytes: 16,
+            format: OutputFormat::Raw,
+            deadline_ms: None,
+            token: None,
+        };
+        for r in [
+            request(1, Op::Reserve { hold_ms: None }),
+            request(2, Op::Fetch(id)),
+            request(3, Op::Fetch(id)),
+            request(4, Op::Cancel(id + 1)),
+        ] {
+            wire::write(&mut client, r.to_value()).unwrap();
+        }
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+        assert_eq!(
+            serve_conn(&mut server, &config, &limits, None, "test").unwrap(),
+            0
+        );
+
+        let mut next = || wire::read_response(&mut client, 1024).unwrap().unwrap();
+        let err = |r: Response| r.result.unwrap_err();
+        assert!(err(next()).contains("at most 1 reservations"));
+        assert_eq!(next().result, Ok(Reply::Served(held)));
+        assert!(err(next()).contains(&format!("no reservation {}", id)));
+        assert!(err(next()).contains("no reservation"));
+    }
+}
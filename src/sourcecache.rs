@@ -0,0 +1,264 @@
+//! `[source_cache]`: which of the sources probed on every request
+//! (/dev/hwrng, the CPU RNG, a /dev/random feeder) delivered on this boot,
+//! and how fast, so later one-shot requests skip the ones known to be
+//! missing instead of paying for the probes again. Off unless
+//! `[source_cache] enabled = true`; recording is a no-op until [`enable`]
+//! is called.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Read;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::atomicfile;
+use crate::config::{Config, CpuRngConfig, SourceCacheConfig};
+use crate::entropy::sysid;
+use crate::error::Error;
+use crate::output;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub available: bool,
+    /// Measured on the request that recorded the entry; 0 if it failed.
+    pub bytes_per_sec: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Cache {
+    version: String,
+    boot_id: String,
+    /// Fingerprint of `[cpu_rng]`, which decides what the CPU RNG may use.
+    cpu_rng: String,
+    created_unix: u64,
+    sources: BTreeMap<String, Entry>,
+}
+
+impl Cache {
+    fn new(boot_id: String, cpu_rng: String, now: u64) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").into(),
+            boot_id,
+            cpu_rng,
+            created_unix: now,
+            sources: BTreeMap::new(),
+        }
+    }
+
+    /// Whether this cache, loaded from disk, still describes `current`:
+    /// same build, same boot, same `[cpu_rng]`, and not older than `max_age_secs`.
+    fn still_valid(&self, current: &Cache, max_age_secs: u64) -> bool {
+        self.version == current.version
+            && self.boot_id == current.boot_id
+            && self.cpu_rng == current.cpu_rng
+            && current.created_unix.saturating_sub(self.created_unix) < max_age_secs
+    }
+}
+
+struct State {
+    file: PathBuf,
+    cache: Cache,
+}
+
+static STATE: Mutex<Option<State>> = Mutex::new(None);
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// SHA-256 of the `[cpu_rng]` settings as JSON.
+fn fingerprint(cpu_rng: &CpuRngConfig) -> String {
+    let json = serde_json::to_vec(cpu_rng).unwrap_or_default();
+    output::to_hex(&Sha256::digest(json))
+}
+
+/// Why a cache file with `uid` and `mode` must not be trusted: it steers
+/// which sources are tried, so only a private file of our own will do.
+fn untrusted(uid: u32, mode: u32, euid: u32) -> Option<&'static str> {
+    if uid != euid {
+        Some("not owned by the current user")
+    } else if mode & 0o077 != 0 {
+        Some("accessible to group or others")
+    } else {
+        None
+    }
+}
+
+/// Reads the cache, or `None` if it is missing, unreadable, a symlink,
+/// or fails [`untrusted`].
+fn load(path: &Path) -> Option<Cache> {
+    let mut f = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)
+        .ok()?;
+    let meta = f.metadata().ok()?;
+    let euid = unsafe { libc::geteuid() };
+    if let Some(why) = untrusted(meta.uid(), meta.mode(), euid) {
+        log::warn!("source cache {}: ignored, {}", path.display(), why);
+        return None;
+    }
+    let mut data = Vec::new();
+    f.read_to_end(&mut data).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+/// Atomically replaces `path`, creating its directory if needed.
+fn save(path: &Path, cache: &Cache) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut json =
+        serde_json::to_vec_pretty(cache).map_err(|e| Error::Io(std::io::Error::other(e)))?;
+    json.push(b'\n');
+    atomicfile::write(path, &json)
+}
+
+/// Loads the cache for this process, starting a fresh one if the file is
+/// missing or from another boot, build or `[cpu_rng]` setting.
+pub fn enable(config: &Config) {
+    let settings = &config.source_cache;
+    if !settings.is_enabled() {
+        return;
+    }
+    let boot_id = String::from_utf8_lossy(&sysid::read_boot_id())
+        .trim()
+        .to_string();
+    let current = Cache::new(boot_id, fingerprint(&config.cpu_rng), unix_now());
+    let cache = match load(&settings.file) {
+        Some(c) if c.still_valid(&current, settings.max_age_secs) => c,
+        _ => current,
+    };
+    *STATE.lock().unwrap_or_else(|e| e.into_inner()) = Some(State {
+        file: settings.file.clone(),
+        cache,
+    });
+}
+
+/// Deletes the cache file, so the next request probes every source.
+pub fn clear(settings: &SourceCacheConfig) -> Result<(), Error> {
+    match fs::remove_file(&settings.file) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+fn record(source: &str, entry: Entry) {
+    let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(s) = state.as_mut() {
+        s.cache.sources.insert(source.into(), entry);
+        if let Err(e) = save(&s.file, &s.cache) {
+            log::debug!("source cache {}: {}", s.file.display(), e);
+        }
+    }
+}
+
+/// Runs one attempt at `source` for `count` bytes. Fails at once if the
+/// source was unavailable earlier this boot; otherwise records whether it
+/// delivered and at what rate. Only [`Error::NoEntropy`] marks a source
+/// unavailable: timeouts and I/O errors are not remembered.
+pub fn attempt<T>(
+    source: &str,
+    count: usize,
+    f: impl FnOnce() -> Result<T, Error>,
+) -> Result<T, Error> {
+    let cached = match STATE.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        Some(s) => s.cache.sources.get(source).copied(),
+        None => return f(),
+    };
+    match cached {
+        Some(e) if !e.available => {
+            return Err(Error::NoEntropy(
+                "skipped: unavailable earlier this boot (see [source_cache])".into(),
+            ))
+        }
+        Some(_) => return f(),
+        None => {}
+    }
+
+    let start = Instant::now();
+    let result = f();
+    let entry = match result {
+        Ok(_) => Entry {
+            available: true,
+            bytes_per_sec: count as f64 / start.elapsed().as_secs_f64().max(1e-9),
+        },
+        Err(Error::NoEntropy(_)) => Entry {
+            available: false,
+            bytes_per_sec: 0.0,
+        },
+        Err(_) => return result,
+    };
+    record(source, entry);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_still_valid() {
+        let saved = Cache::new("boot-a".into(), "cpu".into(), 1000);
+        let now = |boot: &str, cpu: &str, t| Cache::new(boot.into(), cpu.into(), t);
+        assert!(saved.still_valid(&now("boot-a", "cpu", 1100), 3600));
+        assert!(!saved.still_valid(&now("boot-b", "cpu", 1100), 3600));
+        assert!(!saved.still_valid(&now("boot-a", "other", 1100), 3600));
+        assert!(!saved.still_valid(&now("boot-a", "cpu", 4600), 3600));
+
+        let old_build = Cache {
+            version: "0.0.0".into(),
+            ..saved.clone()
+        };
+        assert!(!old_build.still_valid(&now("boot-a", "cpu", 1100), 3600));
+    }
+
+    #[test]
+    fn test_fingerprint_tracks_settings() {
+        let base = CpuRngConfig::default();
+        assert_eq!(fingerprint(&base), fingerprint(&base.clone()));
+        assert_eq!(fingerprint(&base).len(), 64);
+        let changed = CpuRngConfig {
+            enable_rdseed: !base.enable_rdseed,
+            ..base.clone()
+        };
+        assert_ne!(fingerprint(&base), fingerprint(&changed));
+    }
+
+    #[test]
+    fn test_untrusted() {
+        assert_eq!(untrusted(1000, 0o100600, 1000), None);
+        assert!(untrusted(0, 0o100600, 1000).is_some());
+        assert!(untrusted(1000, 0o100644, 1000).is_some());
+        assert!(untrusted(1000, 0o100620, 1000).is_some());
+    }
+
+    #[test]
+    fn test_load_only_trusts_private_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join("mixrand_test_sourcecache.json");
+        let cache = Cache::new("boot".into(), "cpu".into(), 1);
+        save(&path, &cache).unwrap();
+        assert_eq!(load(&path), Some(cache));
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o666)).unwrap();
+        assert_eq!(load(&path), None);
+
+        let link = std::env::temp_dir().join("mixrand_test_sourcecache_link.json");
+        let _ = fs::remove_file(&link);
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        assert_eq!(load(&link), None);
+        let _ = fs::remove_file(&link);
+        let _ = fs::remove_file(&path);
+    }
+}
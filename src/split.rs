@@ -0,0 +1,203 @@
+//! `--split-size`: file output written as numbered parts (`out.000`,
+//! `out.001`, ...) of at most a given size, each digested on its own, for
+//! media with a file size limit.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::checksum::{Algorithm, Tee};
+
+/// Part numbers have three digits, so the parts sort in order.
+pub const MAX_PARTS: usize = 1000;
+
+/// A part size: bytes, or with a suffix as split(1) takes them, K/M/G
+/// (or KiB/MiB/GiB) for powers of 1024 and KB/MB/GB for powers of 1000.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SplitSize(pub u64);
+
+impl std::str::FromStr for SplitSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (number, suffix) = s.split_at(digits);
+        let multiplier: u64 = match suffix.to_ascii_lowercase().as_str() {
+            "" | "b" => 1,
+            "k" | "kib" => 1 << 10,
+            "m" | "mib" => 1 << 20,
+            "g" | "gib" => 1 << 30,
+            "kb" => 1000,
+            "mb" => 1000 * 1000,
+            "gb" => 1000 * 1000 * 1000,
+            _ => return Err(format!("'{}': unknown unit, e.g. 1MiB or 650MB", s)),
+        };
+        number
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(multiplier))
+            .filter(|&n| n > 0)
+            .map(SplitSize)
+            .ok_or_else(|| format!("'{}': expected a size above 0, e.g. 1MiB", s))
+    }
+}
+
+/// `path` with `.NNN` appended.
+pub fn part_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{:03}", index));
+    PathBuf::from(name)
+}
+
+/// A finished part and its lowercase hex digest.
+pub struct Part {
+    pub path: PathBuf,
+    pub digest: String,
+}
+
+struct Current {
+    path: PathBuf,
+    file: File,
+    out: Tee<BufWriter<File>>,
+    written: u64,
+}
+
+/// Starts a new part whenever the current one reaches the size, flushing,
+/// digesting and fsyncing each as it is finished.
+pub struct Writer {
+    path: PathBuf,
+    size: u64,
+    algorithm: Algorithm,
+    current: Option<Current>,
+    parts: Vec<Part>,
+    /// The part being written or last opened, to name in errors
+    part: PathBuf,
+}
+
+impl Writer {
+    pub fn new(path: &Path, size: SplitSize, algorithm: Algorithm) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            size: size.0,
+            algorithm,
+            current: None,
+            parts: Vec::new(),
+            part: part_path(path, 0),
+        }
+    }
+
+    pub fn part(&self) -> &Path {
+        &self.part
+    }
+
+    fn open(&mut self) -> io::Result<&mut Current> {
+        let index = self.parts.len();
+        if index >= MAX_PARTS {
+            return Err(io::Error::other(format!(
+                "more than {} parts; use a larger --split-size",
+                MAX_PARTS
+            )));
+        }
+        let path = part_path(&self.path, index);
+        self.part.clone_from(&path);
+        let file = File::create(&path)?;
+        let out = Tee::new(BufWriter::new(file.try_clone()?), Some(self.algorithm));
+        Ok(self.current.insert(Current {
+            path,
+            file,
+            out,
+            written: 0,
+        }))
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        let Some(mut current) = self.current.take() else {
+            return Ok(());
+        };
+        current.out.flush()?;
+        let digest = current.out.finish().unwrap_or_default();
+        current.file.sync_all()?;
+        self.parts.push(Part {
+            path: current.path,
+            digest,
+        });
+        Ok(())
+    }
+
+    /// Finishes the last part and returns them all, in order.
+    pub fn finish(&mut self) -> io::Result<Vec<Part>> {
+        if self.current.is_none() && self.parts.is_empty() {
+            self.open()?;
+        }
+        self.close()?;
+        Ok(std::mem::take(&mut self.parts))
+    }
+
+    /// Removes every part written so far, after a failure.
+    pub fn discard(&mut self) {
+        let current = self.current.take().map(|c| c.path);
+        for path in self.parts.drain(..).map(|p| p.path).chain(current) {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+impl Write for Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let size = self.size;
+        if self.current.as_ref().is_some_and(|c| c.written >= size) {
+            self.close()?;
+        }
+        let current = match self.current {
+            Some(ref mut current) => current,
+            None => self.open()?,
+        };
+        let room = (size - current.written).min(buf.len() as u64) as usize;
+        let n = current.out.write(&buf[..room])?;
+        current.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.current {
+            Some(ref mut current) => current.out.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_size() {
+        assert_eq!("4096".parse(), Ok(SplitSize(4096)));
+        assert_eq!("1MiB".parse(), Ok(SplitSize(1 << 20)));
+        assert_eq!("2k".parse(), Ok(SplitSize(2048)));
+        assert_eq!("650MB".parse(), Ok(SplitSize(650_000_000)));
+        assert!("0".parse::<SplitSize>().is_err());
+        assert!("1TiB".parse::<SplitSize>().is_err());
+    }
+
+    #[test]
+    fn test_writes_numbered_parts() {
+        let dir = std::env::temp_dir().join("mixrand_test_split");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out");
+        let mut writer = Writer::new(&path, SplitSize(4), Algorithm::Sha256);
+        writer.write_all(b"abcdefghij").unwrap();
+        let parts = writer.finish().unwrap();
+
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[2].path, dir.join("out.002"));
+        assert_eq!(fs::read(dir.join("out.001")).unwrap(), b"efgh");
+        assert_eq!(fs::read(dir.join("out.002")).unwrap(), b"ij");
+        // SHA-256 of "abcd"
+        assert!(parts[0].digest.starts_with("88d4266fd4e6338d"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
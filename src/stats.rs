@@ -1,3 +1,9 @@
+use std::ops::RangeInclusive;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::compress;
+
 /// Result of a single statistical test.
 pub struct TestResult {
     pub name: &'static str,
@@ -28,6 +34,9 @@ pub struct EntropyEstimates {
     pub chi_square: f64,
     pub mean: f64,
     pub serial_correlation: f64,
+    /// Deflate-compressed size over input size; about 1.0 when
+    /// incompressible.
+    pub compression_ratio: f64,
 }
 
 /// FIPS 140-2 Monobit Test.
@@ -188,8 +197,113 @@ pub fn fips_suite(data: &[u8; 2500]) -> FipsResult {
     }
 }
 
+/// FIPS 140-2 sample size: 20,000 bits.
+pub const FIPS_WINDOW_BYTES: usize = 2500;
+
+/// Splits a stream fed in chunks of any size into complete 2500-byte
+/// windows, so short or odd-sized chunks are still FIPS-tested.
+pub struct FipsWindows {
+    buf: Box<[u8; FIPS_WINDOW_BYTES]>,
+    len: usize,
+}
+
+impl Default for FipsWindows {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FipsWindows {
+    pub fn new() -> Self {
+        Self {
+            buf: Box::new([0u8; FIPS_WINDOW_BYTES]),
+            len: 0,
+        }
+    }
+
+    /// Appends `data`, calling `on_window` for each window it completes.
+    pub fn push<F: FnMut(&[u8; FIPS_WINDOW_BYTES])>(&mut self, mut data: &[u8], mut on_window: F) {
+        while !data.is_empty() {
+            let n = (FIPS_WINDOW_BYTES - self.len).min(data.len());
+            self.buf[self.len..self.len + n].copy_from_slice(&data[..n]);
+            self.len += n;
+            data = &data[n..];
+            if self.len == FIPS_WINDOW_BYTES {
+                on_window(&self.buf);
+                self.len = 0;
+            }
+        }
+    }
+
+    /// Bytes waiting for the rest of their window.
+    pub fn pending(&self) -> usize {
+        self.len
+    }
+}
+
+/// Saved as the pending bytes, so a resumed `check` completes the window.
+impl Serialize for FipsWindows {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(&self.buf[..self.len])
+    }
+}
+
+impl<'de> Deserialize<'de> for FipsWindows {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pending = Vec::<u8>::deserialize(deserializer)?;
+        if pending.len() >= FIPS_WINDOW_BYTES {
+            return Err(serde::de::Error::invalid_length(
+                pending.len(),
+                &"fewer bytes than a FIPS window",
+            ));
+        }
+        let mut windows = Self::new();
+        windows.push(&pending, |_| {});
+        Ok(windows)
+    }
+}
+
+/// Per-test pass counts over a number of FIPS windows.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct FipsTally {
+    pub windows: u64,
+    pub monobit: u64,
+    pub poker: u64,
+    pub runs: u64,
+    pub long_runs: u64,
+    pub all: u64,
+}
+
+impl FipsTally {
+    pub fn record(&mut self, r: &FipsResult) {
+        self.windows += 1;
+        self.monobit += r.monobit.passed as u64;
+        self.poker += r.poker.passed as u64;
+        self.runs += r.runs.passed as u64;
+        self.long_runs += r.long_runs.passed as u64;
+        self.all += r.all_passed() as u64;
+    }
+
+    /// Share of windows, in percent, that passed with `pass_count`.
+    pub fn pass_pct(&self, pass_count: u64) -> f64 {
+        if self.windows == 0 {
+            return 0.0;
+        }
+        100.0 * pass_count as f64 / self.windows as f64
+    }
+
+    pub fn merge(&mut self, other: &FipsTally) {
+        self.windows += other.windows;
+        self.monobit += other.monobit;
+        self.poker += other.poker;
+        self.runs += other.runs;
+        self.long_runs += other.long_runs;
+        self.all += other.all;
+    }
+}
+
 /// Compute byte frequency distribution.
-fn byte_frequencies(data: &[u8]) -> [u64; 256] {
+pub fn byte_frequencies(data: &[u8]) -> [u64; 256] {
     let mut freq = [0u64; 256];
     for &b in data {
         freq[b as usize] += 1;
@@ -199,13 +313,16 @@ fn byte_frequencies(data: &[u8]) -> [u64; 256] {
 
 /// Shannon entropy in bits per byte (max 8.0).
 pub fn shannon_entropy(data: &[u8]) -> f64 {
-    if data.is_empty() {
+    shannon_from_counts(&byte_frequencies(data), data.len() as u64)
+}
+
+fn shannon_from_counts(freq: &[u64; 256], n: u64) -> f64 {
+    if n == 0 {
         return 0.0;
     }
-    let freq = byte_frequencies(data);
-    let n = data.len() as f64;
+    let n = n as f64;
     let mut entropy = 0.0;
-    for &count in &freq {
+    for &count in freq {
         if count > 0 {
             let p = count as f64 / n;
             entropy -= p * p.log2();
@@ -216,22 +333,28 @@ pub fn shannon_entropy(data: &[u8]) -> f64 {
 
 /// Min-entropy: -log2(max(p(x))).
 pub fn min_entropy(data: &[u8]) -> f64 {
-    if data.is_empty() {
+    min_entropy_from_counts(&byte_frequencies(data), data.len() as u64)
+}
+
+fn min_entropy_from_counts(freq: &[u64; 256], n: u64) -> f64 {
+    if n == 0 {
         return 0.0;
     }
-    let freq = byte_frequencies(data);
-    let n = data.len() as f64;
+    let n = n as f64;
     let max_count = *freq.iter().max().unwrap() as f64;
     -(max_count / n).log2()
 }
 
 /// Chi-square statistic over byte frequencies (df=255).
 pub fn chi_square(data: &[u8]) -> f64 {
-    if data.is_empty() {
+    chi_square_from_counts(&byte_frequencies(data), data.len() as u64)
+}
+
+fn chi_square_from_counts(freq: &[u64; 256], n: u64) -> f64 {
+    if n == 0 {
         return 0.0;
     }
-    let freq = byte_frequencies(data);
-    let expected = data.len() as f64 / 256.0;
+    let expected = n as f64 / 256.0;
     freq.iter()
         .map(|&obs| {
             let diff = obs as f64 - expected;
@@ -250,30 +373,33 @@ pub fn mean_byte(data: &[u8]) -> f64 {
 
 /// Serial correlation coefficient (lag-1 autocorrelation, expected ~0.0).
 pub fn serial_correlation(data: &[u8]) -> f64 {
-    if data.len() < 2 {
-        return 0.0;
-    }
-    let n = data.len() as f64;
-    let mean = data.iter().map(|&b| b as f64).sum::<f64>() / n;
-
-    let mut numerator = 0.0;
-    for i in 0..data.len() - 1 {
-        numerator += (data[i] as f64 - mean) * (data[i + 1] as f64 - mean);
-    }
+    autocorrelation(data, 1)
+}
 
-    let denominator: f64 = data
-        .iter()
-        .map(|&b| {
-            let d = b as f64 - mean;
-            d * d
-        })
-        .sum();
+/// Lag-`k` autocorrelation coefficient (expected ~0.0). Periodic artifacts
+/// such as byte-aligned counters show up at their period rather than lag 1.
+pub fn autocorrelation(data: &[u8], lag: usize) -> f64 {
+    autocorrelation_spectrum(data, lag..=lag)
+        .first()
+        .copied()
+        .unwrap_or(0.0)
+}
 
-    if denominator.abs() < f64::EPSILON {
-        return 0.0;
-    }
+/// Autocorrelation coefficient at every lag in `lags`; lags that don't fit
+/// in `data` report 0.0.
+pub fn autocorrelation_spectrum(data: &[u8], lags: RangeInclusive<usize>) -> Vec<f64> {
+    let mut stream = EntropyStream::new((*lags.end()).min(data.len()));
+    stream.update(data);
+    stream.autocorrelation_spectrum(lags)
+}
 
-    numerator / denominator
+/// Lag with the largest |r| in a spectrum whose first entry is `first_lag`.
+pub fn max_abs_autocorrelation(spectrum: &[f64], first_lag: usize) -> Option<(usize, f64)> {
+    spectrum
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+        .map(|(i, &r)| (first_lag + i, r))
 }
 
 /// Standard normal CDF (Abramowitz & Stegun approximation).
@@ -307,14 +433,184 @@ pub fn chi_square_p_value(chi_sq: f64, df: f64) -> f64 {
     1.0 - normal_cdf(z)
 }
 
+/// Two-sided 95% normal quantile.
+pub const Z_95: f64 = 1.96;
+
+/// Wilson score interval for `successes` out of `trials`, as fractions.
+/// Unlike the normal approximation it stays within [0, 1] and is still
+/// meaningful for pass rates at or near 100%.
+pub fn wilson_interval(successes: u64, trials: u64, z: f64) -> (f64, f64) {
+    if trials == 0 {
+        return (0.0, 1.0);
+    }
+    let n = trials as f64;
+    let p = successes as f64 / n;
+    let z2 = z * z;
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let half = z / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+    ((center - half).max(0.0), (center + half).min(1.0))
+}
+
+/// Standard error of the mean of `n` values with the given sum and sum of
+/// squares; 0 for fewer than two values.
+pub fn standard_error(sum: f64, sum_sq: f64, n: u64) -> f64 {
+    if n < 2 {
+        return 0.0;
+    }
+    let n = n as f64;
+    let variance = (sum_sq - sum * sum / n) / (n - 1.0);
+    (variance.max(0.0) / n).sqrt()
+}
+
 /// Compute all entropy estimates for a byte slice.
 pub fn entropy_estimates(data: &[u8]) -> EntropyEstimates {
-    EntropyEstimates {
-        shannon: shannon_entropy(data),
-        min_entropy: min_entropy(data),
-        chi_square: chi_square(data),
-        mean: mean_byte(data),
-        serial_correlation: serial_correlation(data),
+    let mut stream = EntropyStream::new(1);
+    stream.update(data);
+    stream.estimates()
+}
+
+/// Entropy estimates and autocorrelation over a stream fed in chunks of any
+/// size, without buffering it: only byte counts and exact running sums are
+/// kept, plus the first and last `max_lag` bytes for the edge terms and the
+/// current [`compress::BLOCK`] for the compression ratio.
+pub struct EntropyStream {
+    freq: [u64; 256],
+    len: u64,
+    sum: u64,
+    sum_sq: u64,
+    /// Sum of x[i]·x[i+k] for k = 1..=max_lag.
+    lag_products: Vec<u64>,
+    head: Vec<u8>,
+    /// Ring buffer of the last `max_lag` bytes; the next byte goes at
+    /// `tail_pos`.
+    tail: Vec<u8>,
+    tail_pos: usize,
+    /// Input not yet compressed, and the compressed size of the rest.
+    block: Vec<u8>,
+    compressed: u64,
+}
+
+impl EntropyStream {
+    /// Tracks autocorrelation up to lag `max_lag` (at least 1, for the
+    /// serial correlation).
+    pub fn new(max_lag: usize) -> Self {
+        let max_lag = max_lag.max(1);
+        Self {
+            freq: [0; 256],
+            len: 0,
+            sum: 0,
+            sum_sq: 0,
+            lag_products: vec![0; max_lag],
+            head: Vec::with_capacity(max_lag),
+            tail: vec![0; max_lag],
+            tail_pos: 0,
+            block: Vec::new(),
+            compressed: 0,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for &b in data {
+            let x = u64::from(b);
+            self.freq[b as usize] += 1;
+            self.sum += x;
+            self.sum_sq += x * x;
+        }
+        let max_lag = self.tail.len();
+        let seen = self.len.min(max_lag as u64) as usize;
+        for (lag, product) in (1..=max_lag).zip(&mut self.lag_products) {
+            // Pairs whose earlier byte came in a previous chunk
+            for (i, &x) in data.iter().enumerate().take(lag) {
+                let back = lag - i;
+                if back <= seen {
+                    let prev = self.tail[(self.tail_pos + max_lag - back) % max_lag];
+                    *product += u64::from(x) * u64::from(prev);
+                }
+            }
+            if data.len() > lag {
+                *product += data[lag..]
+                    .iter()
+                    .zip(data)
+                    .map(|(&a, &b)| u64::from(a) * u64::from(b))
+                    .sum::<u64>();
+            }
+        }
+        let missing = max_lag - self.head.len();
+        self.head
+            .extend_from_slice(&data[..missing.min(data.len())]);
+        for &b in &data[data.len().saturating_sub(max_lag)..] {
+            self.tail[self.tail_pos] = b;
+            self.tail_pos = (self.tail_pos + 1) % max_lag;
+        }
+        let mut rest = data;
+        while !rest.is_empty() {
+            let take = (compress::BLOCK - self.block.len()).min(rest.len());
+            self.block.extend_from_slice(&rest[..take]);
+            rest = &rest[take..];
+            if self.block.len() == compress::BLOCK {
+                self.compressed += compress::deflate_size(&self.block) as u64;
+                self.block.clear();
+            }
+        }
+        self.len += data.len() as u64;
+    }
+
+    /// Bytes fed so far.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn estimates(&self) -> EntropyEstimates {
+        EntropyEstimates {
+            shannon: shannon_from_counts(&self.freq, self.len),
+            min_entropy: min_entropy_from_counts(&self.freq, self.len),
+            chi_square: chi_square_from_counts(&self.freq, self.len),
+            mean: match self.len {
+                0 => 0.0,
+                n => self.sum as f64 / n as f64,
+            },
+            serial_correlation: self.autocorrelation(1),
+            compression_ratio: match self.len {
+                0 => 0.0,
+                n => {
+                    let pending = compress::deflate_size(&self.block) as u64;
+                    (self.compressed + pending) as f64 / n as f64
+                }
+            },
+        }
+    }
+
+    /// Lag-`lag` autocorrelation coefficient; 0.0 for lag 0, lags that don't
+    /// fit in the input and lags above `max_lag`.
+    pub fn autocorrelation(&self, lag: usize) -> f64 {
+        let max_lag = self.tail.len();
+        if lag == 0 || lag > max_lag || lag as u64 >= self.len {
+            return 0.0;
+        }
+        // n·Σ(x - mean)², exact
+        let spread = u128::from(self.len) * u128::from(self.sum_sq) - u128::from(self.sum).pow(2);
+        if spread == 0 {
+            return 0.0;
+        }
+        let n = self.len as f64;
+        let mean = self.sum as f64 / n;
+        let first: u64 = self.head[..lag].iter().map(|&b| u64::from(b)).sum();
+        let last: u64 = (1..=lag)
+            .map(|k| u64::from(self.tail[(self.tail_pos + max_lag - k) % max_lag]))
+            .sum();
+        // Σ(x[i] - mean)(x[i+lag] - mean) over i < n - lag, expanded
+        let numerator = self.lag_products[lag - 1] as f64
+            - mean * ((self.sum - last) as f64 + (self.sum - first) as f64)
+            + (self.len - lag as u64) as f64 * mean * mean;
+        numerator / (spread as f64 / n)
+    }
+
+    pub fn autocorrelation_spectrum(&self, lags: RangeInclusive<usize>) -> Vec<f64> {
+        lags.map(|lag| self.autocorrelation(lag)).collect()
     }
 }
 
@@ -395,14 +691,53 @@ mod tests {
         assert_eq!(result.value, 1.0);
     }
 
+    // --- Autocorrelation ---
+
+    #[test]
+    fn test_autocorrelation_alternating() {
+        let data: Vec<u8> = (0..1000)
+            .map(|i| if i % 2 == 0 { 0 } else { 255 })
+            .collect();
+        assert!((serial_correlation(&data) + 1.0).abs() < 0.01);
+        assert!((autocorrelation(&data, 2) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_autocorrelation_finds_period() {
+        // Byte-aligned counter every 8 bytes over pseudo-random filler
+        let mut x = 0x9E3779B97F4A7C15u64;
+        let data: Vec<u8> = (0..8192)
+            .map(|i| {
+                if i % 8 == 0 {
+                    (i / 8) as u8
+                } else {
+                    x ^= x << 13;
+                    x ^= x >> 7;
+                    x ^= x << 17;
+                    x as u8
+                }
+            })
+            .collect();
+        let spectrum = autocorrelation_spectrum(&data, 1..=32);
+        assert_eq!(spectrum.len(), 32);
+        assert!(serial_correlation(&data).abs() < 0.05);
+        let (lag, r) = max_abs_autocorrelation(&spectrum, 1).unwrap();
+        assert_eq!(lag % 8, 0, "lag {} r {}", lag, r);
+        assert!(r > 0.1);
+    }
+
+    #[test]
+    fn test_autocorrelation_out_of_range_lag() {
+        assert_eq!(autocorrelation(&[1, 2, 3], 3), 0.0);
+        assert_eq!(autocorrelation(&[], 1), 0.0);
+        assert!(max_abs_autocorrelation(&[], 1).is_none());
+    }
+
     // --- Shannon Entropy ---
 
     #[test]
     fn test_shannon_uniform() {
-        let mut data = vec![0u8; 256 * 100];
-        for i in 0..data.len() {
-            data[i] = (i % 256) as u8;
-        }
+        let data: Vec<u8> = (0..256 * 100).map(|i| (i % 256) as u8).collect();
         let s = shannon_entropy(&data);
         assert!((s - 8.0).abs() < 0.01, "expected ~8.0, got {}", s);
     }
@@ -417,10 +752,7 @@ mod tests {
 
     #[test]
     fn test_min_entropy_uniform() {
-        let mut data = vec![0u8; 256 * 100];
-        for i in 0..data.len() {
-            data[i] = (i % 256) as u8;
-        }
+        let data: Vec<u8> = (0..256 * 100).map(|i| (i % 256) as u8).collect();
         let m = min_entropy(&data);
         assert!((m - 8.0).abs() < 0.01, "expected ~8.0, got {}", m);
     }
@@ -429,10 +761,7 @@ mod tests {
 
     #[test]
     fn test_mean_byte_uniform() {
-        let mut data = vec![0u8; 256];
-        for i in 0..256 {
-            data[i] = i as u8;
-        }
+        let data: Vec<u8> = (0..=255u8).collect();
         let m = mean_byte(&data);
         assert!((m - 127.5).abs() < 0.01, "expected ~127.5, got {}", m);
     }
@@ -448,16 +777,36 @@ mod tests {
 
     #[test]
     fn test_serial_correlation_alternating() {
-        let mut data = vec![0u8; 1000];
-        for i in 0..1000 {
-            data[i] = if i % 2 == 0 { 0 } else { 255 };
-        }
+        let data: Vec<u8> = (0..1000)
+            .map(|i| if i % 2 == 0 { 0 } else { 255 })
+            .collect();
         let s = serial_correlation(&data);
         assert!(s < -0.9, "expected strong negative correlation, got {}", s);
     }
 
     // --- Normal CDF ---
 
+    #[test]
+    fn test_wilson_interval() {
+        let (lo, hi) = wilson_interval(992, 1000, Z_95);
+        assert!((lo - 0.9843).abs() < 1e-3 && (hi - 0.9959).abs() < 1e-3);
+        let (lo, hi) = wilson_interval(100, 100, Z_95);
+        assert!(lo > 0.96 && lo < 1.0);
+        assert!(hi > 1.0 - 1e-9);
+        assert_eq!(wilson_interval(0, 0, Z_95), (0.0, 1.0));
+    }
+
+    #[test]
+    fn test_standard_error() {
+        // 2, 4, 4, 4, 5, 5, 7, 9: sample variance 32/7
+        let values = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        let sum: f64 = values.iter().sum();
+        let sum_sq: f64 = values.iter().map(|v| v * v).sum();
+        let expected = (32.0 / 7.0 / 8.0f64).sqrt();
+        assert!((standard_error(sum, sum_sq, 8) - expected).abs() < 1e-12);
+        assert_eq!(standard_error(3.0, 9.0, 1), 0.0);
+    }
+
     #[test]
     fn test_normal_cdf() {
         assert!((normal_cdf(0.0) - 0.5).abs() < 0.001);
@@ -467,6 +816,54 @@ mod tests {
 
     // --- Integration: ChaCha20Rng passes all FIPS ---
 
+    #[test]
+    fn test_entropy_stream_matches_slices() {
+        let data = crate::csprng::generate([9u8; 32], 10_007);
+        let whole = entropy_estimates(&data);
+        let mean = mean_byte(&data);
+        let centered: Vec<f64> = data.iter().map(|&b| b as f64 - mean).collect();
+        let denominator: f64 = centered.iter().map(|d| d * d).sum();
+        // Chunks shorter than the largest lag span several previous chunks
+        for chunk_len in [3, 333] {
+            let mut stream = EntropyStream::new(8);
+            for chunk in data.chunks(chunk_len) {
+                stream.update(chunk);
+            }
+            let streamed = stream.estimates();
+            for (a, b) in [
+                (whole.shannon, streamed.shannon),
+                (whole.min_entropy, streamed.min_entropy),
+                (whole.chi_square, streamed.chi_square),
+                (whole.mean, streamed.mean),
+                (whole.serial_correlation, streamed.serial_correlation),
+            ] {
+                assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+            }
+            // Against the direct definition
+            for lag in 1..=8 {
+                let numerator: f64 = centered
+                    .iter()
+                    .zip(&centered[lag..])
+                    .map(|(a, b)| a * b)
+                    .sum();
+                assert!((stream.autocorrelation(lag) - numerator / denominator).abs() < 1e-9);
+            }
+            assert_eq!(stream.autocorrelation(9), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_fips_windows() {
+        let data = crate::csprng::generate([5u8; 32], 2 * FIPS_WINDOW_BYTES + 100);
+        let mut windows = FipsWindows::new();
+        let mut tally = FipsTally::default();
+        for chunk in data.chunks(777) {
+            windows.push(chunk, |w| tally.record(&fips_suite(w)));
+        }
+        assert_eq!((tally.windows, windows.pending()), (2, 100));
+        assert_eq!(tally.all, 2);
+    }
+
     #[test]
     fn test_fips_suite_chacha20() {
         use rand_chacha::ChaCha20Rng;
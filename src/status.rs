@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::atomicfile;
+use crate::cadence::Cadence;
+use crate::error::Error;
+use crate::qualitycheck::{Report, Transition};
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Success/failure history of one entropy source.
+#[derive(Debug, Default, Serialize)]
+pub struct SourceHealth {
+    pub successes: u64,
+    pub failures: u64,
+    pub last_success_unix: Option<u64>,
+    pub last_error: Option<String>,
+    pub last_error_unix: Option<u64>,
+}
+
+/// Daemon state exported through `--status-file`.
+#[derive(Debug, Serialize)]
+pub struct DaemonStatus {
+    pub version: &'static str,
+    pub pid: u32,
+    pub started_unix: u64,
+    pub updated_unix: u64,
+    pub uptime_secs: u64,
+    pub threshold: u32,
+    pub entropy_avail: Option<u32>,
+    pub injections: u64,
+    pub injection_failures: u64,
+    /// Failed injections since the last successful one.
+    pub consecutive_failures: u32,
+    pub bytes_injected: u64,
+    pub bits_credited: u64,
+    pub sources: BTreeMap<&'static str, SourceHealth>,
+    pub last_error: Option<String>,
+    /// Background quality checks (`[quality_check]`) run and failed.
+    pub quality_checks: u64,
+    pub quality_check_failures: u64,
+    /// Alerts raised, and whether one is active.
+    pub quality_alerts: u64,
+    pub quality_degraded: bool,
+    pub last_quality_failure: Option<String>,
+    /// Poll interval asked for, the mean spacing the rounds on schedule
+    /// actually got, and deadlines skipped because a round overran them.
+    pub poll_interval_secs: f64,
+    pub poll_spacing_secs: Option<f64>,
+    pub poll_deadlines_skipped: u64,
+    #[serde(skip)]
+    started: Instant,
+}
+
+impl DaemonStatus {
+    pub fn new(threshold: u32) -> Self {
+        let now = unix_now();
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            pid: std::process::id(),
+            started_unix: now,
+            updated_unix: now,
+            uptime_secs: 0,
+            threshold,
+            entropy_avail: None,
+            injections: 0,
+            injection_failures: 0,
+            consecutive_failures: 0,
+            bytes_injected: 0,
+            bits_credited: 0,
+            sources: BTreeMap::new(),
+            last_error: None,
+            quality_checks: 0,
+            quality_check_failures: 0,
+            quality_alerts: 0,
+            quality_degraded: false,
+            last_quality_failure: None,
+            poll_interval_secs: 0.0,
+            poll_spacing_secs: None,
+            poll_deadlines_skipped: 0,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn source_ok(&mut self, source: &'static str) {
+        let health = self.sources.entry(source).or_default();
+        health.successes += 1;
+        health.last_success_unix = Some(unix_now());
+    }
+
+    pub fn source_failed(&mut self, source: &'static str, error: &Error) {
+        let message = format!("{}: {}", source, error);
+        let health = self.sources.entry(source).or_default();
+        health.failures += 1;
+        health.last_error = Some(error.to_string());
+        health.last_error_unix = Some(unix_now());
+        self.last_error = Some(message);
+    }
+
+    pub fn injected(&mut self, bytes: usize, credit_bits: u32) {
+        self.injections += 1;
+        self.consecutive_failures = 0;
+        self.bytes_injected += bytes as u64;
+        self.bits_credited += u64::from(credit_bits);
+    }
+
+    /// Records a failed injection; `target` prefixes the error message.
+    pub fn injection_failed(&mut self, target: &str, error: &Error) {
+        self.injection_failures += 1;
+        self.consecutive_failures += 1;
+        self.last_error = Some(format!("{}: {}", target, error));
+    }
+
+    pub fn quality_checked(&mut self, report: &Report) {
+        self.quality_checks += 1;
+        if let Some(ref why) = report.failure {
+            self.quality_check_failures += 1;
+            self.last_quality_failure = Some(why.clone());
+        }
+        match report.transition {
+            Some(Transition::Degraded) => {
+                self.quality_alerts += 1;
+                self.quality_degraded = true;
+            }
+            Some(Transition::Recovered) => self.quality_degraded = false,
+            None => {}
+        }
+    }
+
+    pub fn polled(&mut self, cadence: &Cadence) {
+        self.poll_interval_secs = cadence.interval().as_secs_f64();
+        self.poll_spacing_secs = cadence.mean_spacing().map(|d| d.as_secs_f64());
+        self.poll_deadlines_skipped = cadence.skipped();
+    }
+
+    /// Refreshes the timestamps and atomically replaces `path` with the
+    /// status as pretty-printed JSON (see [`atomicfile::write`]), so readers
+    /// never see a partial file.
+    pub fn write(&mut self, path: &Path) -> Result<(), Error> {
+        self.updated_unix = unix_now();
+        self.uptime_secs = self.started.elapsed().as_secs();
+
+        let mut json =
+            serde_json::to_vec_pretty(self).map_err(|e| Error::Io(std::io::Error::other(e)))?;
+        json.push(b'\n');
+
+        atomicfile::write(path, &json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_counters_and_errors() {
+        let mut status = DaemonStatus::new(256);
+        status.source_ok("fallback");
+        status.source_failed("fallback", &Error::NoEntropy("jitter stalled".into()));
+        status.injected(64, 256);
+        status.injected(64, 256);
+        status.injection_failed("ioctl", &Error::InvalidArgs("EPERM".into()));
+
+        let fallback = &status.sources["fallback"];
+        assert_eq!((fallback.successes, fallback.failures), (1, 1));
+        assert!(fallback
+            .last_error
+            .as_deref()
+            .unwrap()
+            .contains("jitter stalled"));
+        assert_eq!(status.injections, 2);
+        assert_eq!(status.bytes_injected, 128);
+        assert_eq!(status.bits_credited, 512);
+        assert_eq!(status.injection_failures, 1);
+        assert!(status.last_error.as_deref().unwrap().starts_with("ioctl:"));
+    }
+
+    #[test]
+    fn test_quality_checks() {
+        let mut status = DaemonStatus::new(256);
+        let failed = Report {
+            failure: Some("2 of 10 FIPS 140-2 windows failed".into()),
+            transition: Some(Transition::Degraded),
+        };
+        status.quality_checked(&failed);
+        assert!(status.quality_degraded);
+        status.quality_checked(&Report {
+            failure: None,
+            transition: Some(Transition::Recovered),
+        });
+        assert!(!status.quality_degraded);
+        assert_eq!(
+            (
+                status.quality_checks,
+                status.quality_check_failures,
+                status.quality_alerts
+            ),
+            (2, 1, 1)
+        );
+    }
+
+    #[test]
+    fn test_write_json() {
+        let path = std::env::temp_dir().join("mixrand_test_status.json");
+        let mut status = DaemonStatus::new(256);
+        status.entropy_avail = Some(128);
+        status.source_ok("fallback");
+        status.write(&path).unwrap();
+
+        let value: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(value["threshold"], 256);
+        assert_eq!(value["entropy_avail"], 128);
+        assert_eq!(value["sources"]["fallback"]["successes"], 1);
+        assert!(value.get("started").is_none());
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,376 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+use chacha20::cipher::StreamCipher;
+use chacha20::XChaCha20;
+
+use crate::budget;
+use crate::checksum::{self, Tee};
+use crate::cli::{StreamArgs, StreamDevice, StreamFormat};
+use crate::config::Config;
+use crate::csprng;
+use crate::entropy::{self, cpurng, hwrng, EntropyResult};
+use crate::error::Error;
+use crate::fips::{self, HmacDrbg};
+use crate::framing;
+use crate::mixer;
+use crate::pattern::Pattern;
+use crate::signals::{self, Signal};
+use crate::table;
+#[cfg(feature = "io-uring")]
+use crate::uring;
+
+/// Bytes generated and written per iteration.
+const CHUNK: usize = 64 * 1024;
+
+/// Fresh entropy drawn from the pipeline for each (re)key.
+const RESEED_ENTROPY: usize = 32;
+
+/// XChaCha20 keystream keyed from the entropy pipeline, rekeyed every
+/// `reseed_bytes` (at most the keystream one key can give). Each new key also depends on the previous one. In FIPS
+/// mode an HMAC_DRBG, reseeded at the same boundaries, replaces XChaCha20.
+pub struct KeyedStream {
+    config: Config,
+    key: [u8; 32],
+    cipher: XChaCha20,
+    drbg: Option<HmacDrbg>,
+    reseed_bytes: u64,
+    since_reseed: u64,
+    reseeds: u64,
+    /// Where the current key's entropy came from.
+    source: String,
+}
+
+impl KeyedStream {
+    pub fn new(config: &Config, reseed_bytes: u64) -> Result<Self, Error> {
+        Ok(Self::from_seed(config, reseed_bytes, Self::draw(config)?))
+    }
+
+    /// A stream keyed from `seed`, drawn by [`draw`](Self::draw).
+    pub fn from_seed(config: &Config, reseed_bytes: u64, seed: EntropyResult) -> Self {
+        let mut stream = Self {
+            config: config.clone(),
+            key: [0u8; 32],
+            cipher: csprng::xchacha20(&[0u8; 32], &[0u8; 24]),
+            drbg: None,
+            reseed_bytes: reseed_bytes.clamp(1, csprng::XCHACHA20_KEYSTREAM_BYTES),
+            since_reseed: 0,
+            reseeds: 0,
+            source: String::new(),
+        };
+        stream.rekey_from(seed);
+        stream.reseeds = 0;
+        stream
+    }
+
+    /// Times the stream has been rekeyed since [`new`](Self::new).
+    pub fn reseeds(&self) -> u64 {
+        self.reseeds
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Derives the next key and nonce from `fresh` and the current key.
+    fn rekey(&mut self, fresh: &[u8]) {
+        self.since_reseed = 0;
+        self.reseeds += 1;
+        if self.config.fips {
+            match self.drbg {
+                Some(ref mut drbg) => drbg.reseed(fresh, &[]),
+                None => self.drbg = Some(HmacDrbg::new(fresh, b"mixrand-stream")),
+            }
+            return;
+        }
+        let mut key = mixer::mix_entropy(&[("stream-key", fresh), ("stream-chain", &self.key)]);
+        let mut nonce = mixer::mix_entropy(&[("stream-nonce", fresh), ("stream-chain", &self.key)]);
+        self.cipher = csprng::xchacha20(&key, nonce[..24].try_into().unwrap());
+        self.key = key;
+        cpurng::zeroize_bytes(&mut key);
+        cpurng::zeroize_bytes(&mut nonce);
+    }
+
+    /// Runs the entropy pipeline for one (re)key. Separate from
+    /// [`rekey_from`](Self::rekey_from) so the slow part can happen while
+    /// the stream is still in use elsewhere.
+    pub fn draw(config: &Config) -> Result<EntropyResult, Error> {
+        let want = if config.fips {
+            fips::SEED_BYTES
+        } else {
+            RESEED_ENTROPY
+        };
+        entropy::generate(want, config)
+    }
+
+    pub fn rekey_from(&mut self, mut fresh: EntropyResult) {
+        log::debug!("stream rekeyed from {}", fresh.source);
+        self.rekey(&fresh.bytes);
+        cpurng::zeroize_vec(&mut fresh.bytes);
+        self.source = fresh.source;
+    }
+
+    fn reseed(&mut self) -> Result<(), Error> {
+        let fresh = Self::draw(&self.config)?;
+        self.rekey_from(fresh);
+        Ok(())
+    }
+
+    /// Fills `buf` with keystream, rekeying at each `reseed_bytes` boundary.
+    pub fn fill(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        let mut pos = 0;
+        while pos < buf.len() {
+            if self.since_reseed >= self.reseed_bytes {
+                self.reseed()?;
+            }
+            let left = (self.reseed_bytes - self.since_reseed).min((buf.len() - pos) as u64);
+            let end = pos + left as usize;
+            self.keystream(&mut buf[pos..end])?;
+            pos = end;
+        }
+        Ok(())
+    }
+
+    /// Fills `buf` from the current key, for callers that rekey from
+    /// another thread: fails instead of going past `reseed_bytes`.
+    pub fn fill_current(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        if self.since_reseed + buf.len() as u64 > self.reseed_bytes {
+            return Err(Error::NoEntropy(
+                "generator has used up its key and is waiting for a rekey".into(),
+            ));
+        }
+        self.keystream(buf)
+    }
+
+    /// Bytes produced since the last rekey.
+    pub fn since_rekey(&self) -> u64 {
+        self.since_reseed
+    }
+
+    fn keystream(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        match self.drbg {
+            Some(ref mut drbg) => {
+                fips::check_operational()?;
+                drbg.generate(buf)?;
+            }
+            None => {
+                buf.fill(0);
+                self.cipher.apply_keystream(buf);
+            }
+        }
+        self.since_reseed += buf.len() as u64;
+        Ok(())
+    }
+}
+
+impl Drop for KeyedStream {
+    fn drop(&mut self) {
+        cpurng::zeroize_bytes(&mut self.key);
+    }
+}
+
+/// Writes `chunk`, returning `false` once the reader has gone away.
+fn write_chunk(out: &mut dyn Write, chunk: &[u8]) -> io::Result<bool> {
+    match out.write_all(chunk) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Opens `--device`: through io_uring with `depth` reads of `chunk` bytes
+/// queued where built and supported, with plain blocking reads otherwise.
+/// A rate-capped /dev/hwrng is always read one paced chunk at a time.
+fn open_device(device: StreamDevice, chunk: usize, depth: usize) -> Result<Box<dyn Read>, Error> {
+    let path = device.path();
+    let file =
+        File::open(path).map_err(|e| Error::NoEntropy(format!("{} not available: {}", path, e)))?;
+    if device == StreamDevice::Hwrng && hwrng::rate_limited() {
+        log::info!(
+            "reading {} in paced chunks ([limits] hwrng_bytes_per_sec)",
+            path
+        );
+        return Ok(Box::new(hwrng::Paced::new(file)));
+    }
+    #[cfg(feature = "io-uring")]
+    match uring::BatchReader::new(file.try_clone()?, chunk, depth) {
+        Ok(reader) => {
+            log::info!("reading {} through io_uring, {} reads queued", path, depth);
+            return Ok(Box::new(reader));
+        }
+        Err(e) => log::warn!(
+            "io_uring unavailable ({}); reading {} synchronously",
+            e,
+            path
+        ),
+    }
+    #[cfg(not(feature = "io-uring"))]
+    let _ = (chunk, depth);
+    log::info!("reading {} synchronously", path);
+    Ok(Box::new(file))
+}
+
+fn stream_to(args: &StreamArgs, config: &Config, out: &mut dyn Write) -> Result<u64, Error> {
+    if args.device.is_some() && config.fips {
+        return Err(Error::InvalidArgs(
+            "--device bypasses the DRBG, which FIPS mode requires".into(),
+        ));
+    }
+    let mut keyed = if args.keyed {
+        Some(KeyedStream::new(config, args.reseed_mib << 20)?)
+    } else {
+        None
+    };
+    let chunk_size = match config.limits.max_request_bytes {
+        0 => CHUNK,
+        max => CHUNK.min(max),
+    };
+    let mut device = match args.device {
+        Some(d) => Some((d, open_device(d, chunk_size, args.queue_depth as usize)?)),
+        None => None,
+    };
+    let mut buf = vec![0u8; chunk_size];
+    let mut framed = Vec::new();
+    let mut seq = 0;
+    let mut written: u64 = 0;
+
+    while !signals::shutdown_requested() {
+        let mut n = match args.bytes {
+            Some(limit) => (limit - written).min(chunk_size as u64) as usize,
+            None => chunk_size,
+        };
+        if n == 0 {
+            break;
+        }
+        match (args.pattern, keyed.as_mut(), device.as_mut()) {
+            (Pattern::Random, _, Some((d, reader))) => {
+                if *d == StreamDevice::Hwrng && !budget::reserve_hwrng(n, &config.limits)? {
+                    return Err(Error::NoEntropy("hwrng daily budget exhausted".into()));
+                }
+                n = match reader.read(&mut buf[..n]) {
+                    Ok(0) => {
+                        return Err(Error::NoEntropy(format!("{} returned 0 bytes", d.path())))
+                    }
+                    Ok(got) => got,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e.into()),
+                };
+            }
+            (Pattern::Random, Some(k), None) => k.fill(&mut buf[..n])?,
+            (Pattern::Random, None, None) => {
+                let mut result = entropy::generate(n, config)?;
+                if written == 0 {
+                    log::info!("entropy source: {}", result.source);
+                }
+                buf[..n].copy_from_slice(&result.bytes);
+                cpurng::zeroize_vec(&mut result.bytes);
+            }
+            (pattern, _, _) => pattern.fill(written, &mut buf[..n]),
+        }
+        let open = match args.format {
+            StreamFormat::Raw => write_chunk(out, &buf[..n])?,
+            StreamFormat::Framed => {
+                framing::encode(&buf[..n], &mut seq, &mut framed);
+                let open = write_chunk(out, &framed);
+                cpurng::zeroize_vec(&mut framed);
+                framed.clear();
+                open?
+            }
+        };
+        if !open {
+            break;
+        }
+        written += n as u64;
+    }
+    cpurng::zeroize_vec(&mut buf);
+    if let Err(e) = out.flush() {
+        if e.kind() != io::ErrorKind::BrokenPipe {
+            return Err(e.into());
+        }
+    }
+
+    let units = args.units.unwrap_or(config.display.units);
+    match keyed {
+        Some(ref k) => log::info!(
+            "wrote {}, rekeyed {} times",
+            table::bytes(written, units),
+            k.reseeds()
+        ),
+        None => log::info!("wrote {}", table::bytes(written, units)),
+    }
+    Ok(written)
+}
+
+pub fn run(args: &StreamArgs, config: &Config) -> Result<(), Error> {
+    signals::install(&[Signal::Term, Signal::Int]);
+    let digest = match args.output_file {
+        Some(ref path) => {
+            let mut out = Tee::new(File::create(path)?, args.checksum);
+            stream_to(args, config, &mut out)?;
+            out.finish()
+        }
+        None => {
+            let mut out = Tee::new(io::stdout().lock(), args.checksum);
+            stream_to(args, config, &mut out)?;
+            out.finish()
+        }
+    };
+    if let (Some(algorithm), Some(digest)) = (args.checksum, digest) {
+        checksum::report(
+            algorithm,
+            &digest,
+            args.output_file.as_deref(),
+            args.checksum_sidecar,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(reseed_bytes: u64) -> KeyedStream {
+        let mut s = KeyedStream {
+            config: Config::default(),
+            key: [0u8; 32],
+            cipher: csprng::xchacha20(&[0u8; 32], &[0u8; 24]),
+            drbg: None,
+            reseed_bytes,
+            since_reseed: 0,
+            reseeds: 0,
+            source: String::new(),
+        };
+        s.rekey(&[1u8; 32]);
+        s
+    }
+
+    #[test]
+    fn test_rekey_chains_previous_key() {
+        let mut a = stream(1 << 20);
+        let mut b = stream(1 << 20);
+        assert_eq!(a.key, b.key);
+        b.key[0] ^= 1;
+        a.rekey(&[2u8; 32]);
+        b.rekey(&[2u8; 32]);
+        assert_ne!(a.key, b.key);
+    }
+
+    #[test]
+    fn test_fill_stops_at_reseed_boundary() {
+        let mut s = stream(100);
+        let mut buf = [0u8; 60];
+        s.fill(&mut buf).unwrap();
+        assert_eq!(s.since_reseed, 60);
+
+        // Same key, split and unsplit reads give the same keystream
+        let mut whole = stream(100);
+        let mut expected = [0u8; 100];
+        whole.fill(&mut expected).unwrap();
+        let mut rest = [0u8; 40];
+        s.fill(&mut rest).unwrap();
+        assert_eq!(buf[..], expected[..60]);
+        assert_eq!(rest[..], expected[60..]);
+        assert_eq!(s.since_reseed, 100);
+    }
+}
@@ -0,0 +1,291 @@
+//! Column-aligned text tables and human-readable numbers for terminal
+//! reports. Decimals follow the locale's separator (LC_NUMERIC), and
+//! symbols fall back to ASCII when the locale's charset isn't UTF-8.
+//! Machine-readable output (CSV, JSON) doesn't go through here.
+
+use std::ffi::CStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// Prefixes for byte sizes and rates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    /// Powers of 1000: KB, MB, GB
+    #[default]
+    Si,
+    /// Powers of 1024: KiB, MiB, GiB
+    Iec,
+}
+
+/// What the terminal can show, read once from the environment's locale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Locale {
+    pub decimal: char,
+    pub utf8: bool,
+}
+
+impl Locale {
+    const C: Locale = Locale {
+        decimal: '.',
+        utf8: false,
+    };
+
+    /// Queries the environment's locale, then restores the C locale the
+    /// rest of the process expects.
+    fn detect() -> Self {
+        unsafe {
+            if libc::setlocale(libc::LC_ALL, c"".as_ptr()).is_null() {
+                return Self::C;
+            }
+            let conv = libc::localeconv();
+            let decimal = if conv.is_null() || (*conv).decimal_point.is_null() {
+                '.'
+            } else {
+                CStr::from_ptr((*conv).decimal_point)
+                    .to_str()
+                    .ok()
+                    .and_then(|s| s.chars().next())
+                    .unwrap_or('.')
+            };
+            let codeset = CStr::from_ptr(libc::nl_langinfo(libc::CODESET))
+                .to_string_lossy()
+                .to_ascii_uppercase();
+            libc::setlocale(libc::LC_ALL, c"C".as_ptr());
+            Locale {
+                decimal,
+                utf8: codeset == "UTF-8" || codeset == "UTF8",
+            }
+        }
+    }
+}
+
+pub fn locale() -> Locale {
+    static LOCALE: OnceLock<Locale> = OnceLock::new();
+    *LOCALE.get_or_init(Locale::detect)
+}
+
+/// `→`, or `->` outside UTF-8 locales.
+pub fn arrow() -> &'static str {
+    if locale().utf8 {
+        "→"
+    } else {
+        "->"
+    }
+}
+
+/// `—`, or `-` outside UTF-8 locales.
+pub fn dash() -> &'static str {
+    if locale().utf8 {
+        "—"
+    } else {
+        "-"
+    }
+}
+
+/// `σ`, or `sd` outside UTF-8 locales.
+pub fn sigma() -> &'static str {
+    if locale().utf8 {
+        "σ"
+    } else {
+        "sd"
+    }
+}
+
+/// `±`, or `+/-` outside UTF-8 locales.
+pub fn plus_minus() -> &'static str {
+    if locale().utf8 {
+        "±"
+    } else {
+        "+/-"
+    }
+}
+
+/// Swaps the `.` in an already formatted number for `decimal`.
+fn with_separator(s: String, decimal: char) -> String {
+    if decimal == '.' {
+        s
+    } else {
+        s.replacen('.', &decimal.to_string(), 1)
+    }
+}
+
+/// `value` with `places` decimals and the locale's separator.
+pub fn decimal(value: f64, places: usize) -> String {
+    with_separator(format!("{:.*}", places, value), locale().decimal)
+}
+
+/// Like [`decimal`], always signed.
+pub fn signed(value: f64, places: usize) -> String {
+    with_separator(format!("{:+.*}", places, value), locale().decimal)
+}
+
+/// `value` as a percentage with `places` decimals.
+pub fn percent(value: f64, places: usize) -> String {
+    format!("{}%", decimal(value, places))
+}
+
+fn scaled(value: f64, units: Units, decimal: char) -> (String, &'static str) {
+    let (base, prefixes) = match units {
+        Units::Si => (1000.0, ["K", "M", "G", "T"]),
+        Units::Iec => (1024.0, ["Ki", "Mi", "Gi", "Ti"]),
+    };
+    if value < base {
+        return (format!("{:.0}", value), "");
+    }
+    let mut v = value / base;
+    let mut i = 0;
+    while v >= base && i + 1 < prefixes.len() {
+        v /= base;
+        i += 1;
+    }
+    (with_separator(format!("{:.2}", v), decimal), prefixes[i])
+}
+
+/// Duration in milliseconds with `places` decimals, e.g. `0.123 ms`.
+pub fn millis(d: Duration, places: usize) -> String {
+    format!("{} ms", decimal(d.as_secs_f64() * 1000.0, places))
+}
+
+/// Byte count, e.g. `1.50 MB` or `1.43 MiB`.
+pub fn bytes(n: u64, units: Units) -> String {
+    let (v, prefix) = scaled(n as f64, units, locale().decimal);
+    format!("{} {}B", v, prefix)
+}
+
+/// Byte rate, e.g. `12.30 MB/s` or `11.73 MiB/s`.
+pub fn rate(bytes_per_sec: f64, units: Units) -> String {
+    let (v, prefix) = scaled(bytes_per_sec, units, locale().decimal);
+    format!("{} {}B/s", v, prefix)
+}
+
+/// Columns a string takes in a terminal: combining marks take none, and
+/// East Asian wide and fullwidth characters take two.
+pub fn display_width(s: &str) -> usize {
+    s.chars()
+        .map(|c| match c as u32 {
+            0x0300..=0x036F | 0x200B..=0x200F | 0xFE00..=0xFE0F => 0,
+            0x1100..=0x115F
+            | 0x2E80..=0x303E
+            | 0x3041..=0xA4CF
+            | 0xAC00..=0xD7A3
+            | 0xF900..=0xFAFF
+            | 0xFE30..=0xFE4F
+            | 0xFF00..=0xFF60
+            | 0xFFE0..=0xFFE6
+            | 0x1F300..=0x1F64F
+            | 0x1F900..=0x1F9FF
+            | 0x20000..=0x3FFFD => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// A table whose columns are as wide as their widest cell.
+#[derive(Debug, Clone)]
+pub struct Table {
+    headers: Vec<String>,
+    align: Vec<Align>,
+    rows: Vec<Vec<String>>,
+    indent: usize,
+}
+
+impl Table {
+    pub fn new(columns: &[(&str, Align)]) -> Self {
+        Self {
+            headers: columns.iter().map(|(h, _)| h.to_string()).collect(),
+            align: columns.iter().map(|&(_, a)| a).collect(),
+            rows: Vec::new(),
+            indent: 0,
+        }
+    }
+
+    /// Spaces before every line.
+    pub fn indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Adds a row; missing cells are left blank, extra ones are dropped.
+    pub fn row(&mut self, mut cells: Vec<String>) {
+        cells.resize(self.headers.len(), String::new());
+        self.rows.push(cells);
+    }
+
+    /// Renders the header and rows, one per line, columns separated by two
+    /// spaces and without trailing whitespace.
+    pub fn render(&self) -> String {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| display_width(h)).collect();
+        for row in &self.rows {
+            for (w, cell) in widths.iter_mut().zip(row) {
+                *w = (*w).max(display_width(cell));
+            }
+        }
+        let mut out = String::new();
+        for row in std::iter::once(&self.headers).chain(&self.rows) {
+            let mut line = " ".repeat(self.indent);
+            for (i, cell) in row.iter().enumerate() {
+                if i > 0 {
+                    line.push_str("  ");
+                }
+                let pad = " ".repeat(widths[i] - display_width(cell));
+                match self.align[i] {
+                    Align::Left => {
+                        line.push_str(cell);
+                        line.push_str(&pad);
+                    }
+                    Align::Right => {
+                        line.push_str(&pad);
+                        line.push_str(cell);
+                    }
+                }
+            }
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_units() {
+        let (v, p) = scaled(1_500_000.0, Units::Si, '.');
+        assert_eq!((v.as_str(), p), ("1.50", "M"));
+        let (v, p) = scaled(1_500_000.0, Units::Iec, ',');
+        assert_eq!((v.as_str(), p), ("1,43", "Mi"));
+        assert_eq!(scaled(999.0, Units::Si, '.'), ("999".to_string(), ""));
+        assert_eq!(scaled(1024.0, Units::Iec, '.').1, "Ki");
+    }
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("min-ent"), 7);
+        assert_eq!(display_width("→ σ"), 3);
+        assert_eq!(display_width("熵源"), 4);
+        assert_eq!(display_width("e\u{301}"), 1);
+    }
+
+    #[test]
+    fn test_table_aligns_wide_cells() {
+        let mut t = Table::new(&[("Source", Align::Left), ("Rate", Align::Right)]).indent(2);
+        t.row(vec!["rdseed".into(), "1.00 MB/s".into()]);
+        t.row(vec!["熵源".into(), "5 B/s".into()]);
+        assert_eq!(
+            t.render(),
+            "  Source       Rate\n  rdseed  1.00 MB/s\n  熵源        5 B/s\n"
+        );
+    }
+}
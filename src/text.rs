@@ -0,0 +1,334 @@
+//! `-f text` output: printable ASCII or a custom alphabet (`--alphabet`),
+//! with optional grouping like a license key. Each character is drawn by
+//! rejection sampling: values that would make some symbols likelier than
+//! others are thrown away and more bytes requested from the sources, so
+//! any alphabet size stays unbiased.
+
+use crate::entropy::EntropyResult;
+use crate::error::Error;
+
+/// Largest alphabet: two bytes are drawn per character above 256 symbols.
+const MAX_SYMBOLS: usize = 1 << 16;
+
+/// Extra draws requested on top of the expected number, so a second
+/// request is rarely needed.
+const MARGIN: f64 = 1.1;
+
+/// Characters that would merge into their neighbours or vanish on screen:
+/// combining marks, zero-width characters and variation selectors.
+fn joins(c: char) -> bool {
+    matches!(c as u32,
+        0x0300..=0x036F
+        | 0x1AB0..=0x1AFF
+        | 0x1DC0..=0x1DFF
+        | 0x200B..=0x200F
+        | 0x20D0..=0x20FF
+        | 0x2060..=0x2064
+        | 0xFE00..=0xFE0F
+        | 0xFE20..=0xFE2F
+        | 0xFEFF
+        | 0xE0100..=0xE01EF)
+}
+
+/// The symbols characters are drawn from.
+#[derive(Debug, Clone)]
+pub struct Alphabet {
+    symbols: Vec<char>,
+}
+
+impl Alphabet {
+    /// The printable ASCII characters `!` through `~`.
+    pub fn printable() -> Self {
+        Self {
+            symbols: ('!'..='~').collect(),
+        }
+    }
+
+    /// An alphabet of the characters in `s`. Each must stand alone as one
+    /// grapheme, and none may repeat, since a repeat would be drawn twice
+    /// as often.
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let symbols: Vec<char> = s.chars().collect();
+        if let Some(c) = symbols
+            .iter()
+            .find(|c| c.is_control() || c.is_whitespace() || joins(**c))
+        {
+            return Err(Error::InvalidArgs(format!(
+                "--alphabet: U+{:04X} is not a standalone printable character",
+                *c as u32
+            )));
+        }
+        let mut sorted = symbols.clone();
+        sorted.sort_unstable();
+        if let Some(w) = sorted.windows(2).find(|w| w[0] == w[1]) {
+            return Err(Error::InvalidArgs(format!(
+                "--alphabet: '{}' appears more than once",
+                w[0]
+            )));
+        }
+        if !(2..=MAX_SYMBOLS).contains(&symbols.len()) {
+            return Err(Error::InvalidArgs(format!(
+                "--alphabet needs 2 to {} characters, got {}",
+                MAX_SYMBOLS,
+                symbols.len()
+            )));
+        }
+        Ok(Self { symbols })
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        self.symbols.contains(&c)
+    }
+
+    /// Entropy of one uniformly drawn character.
+    pub fn bits_per_char(&self) -> f64 {
+        (self.len() as f64).log2()
+    }
+
+    /// Bytes per draw.
+    fn width(&self) -> usize {
+        if self.len() <= 256 {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Draws below this are accepted: the largest multiple of the alphabet
+    /// size a draw can take.
+    fn limit(&self) -> u32 {
+        let range = 1u32 << (8 * self.width());
+        range - range % self.len() as u32
+    }
+
+    /// Bytes to request for `count` characters, with some margin.
+    fn request_bytes(&self, count: usize) -> usize {
+        let range = (1u32 << (8 * self.width())) as f64;
+        let draws = count as f64 * range / self.limit() as f64 * MARGIN;
+        (draws.ceil() as usize + 1) * self.width()
+    }
+
+    /// Appends a character to `out` for each accepted draw in `bytes`,
+    /// until it holds `count`. A trailing partial draw is discarded.
+    fn sample(&self, bytes: &[u8], count: usize, out: &mut Vec<char>) {
+        let limit = self.limit();
+        for draw in bytes.chunks_exact(self.width()) {
+            if out.len() >= count {
+                break;
+            }
+            let v = draw.iter().fold(0u32, |v, &b| v << 8 | b as u32);
+            if v < limit {
+                out.push(self.symbols[(v % self.len() as u32) as usize]);
+            }
+        }
+    }
+}
+
+/// `--group`: a separator after every `size` characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Grouping {
+    pub size: usize,
+    pub separator: char,
+}
+
+impl std::str::FromStr for Grouping {
+    type Err = String;
+
+    /// `4` or `4-`: the group size, then an optional separator (default `-`).
+    fn from_str(s: &str) -> Result<Self, String> {
+        let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (size, rest) = s.split_at(digits);
+        let size: usize = size
+            .parse()
+            .ok()
+            .filter(|&n| n > 0)
+            .ok_or_else(|| format!("'{}': expected a group size above 0, e.g. 4-", s))?;
+        let mut chars = rest.chars();
+        let separator = match (chars.next(), chars.next()) {
+            (None, _) => '-',
+            (Some(c), None) => c,
+            (Some(_), Some(_)) => return Err(format!("'{}': one separator character", s)),
+        };
+        Ok(Self { size, separator })
+    }
+}
+
+/// `-f text` with `--alphabet` or `--group`.
+#[derive(Debug, Clone)]
+pub struct Style {
+    pub alphabet: Alphabet,
+    pub group: Option<Grouping>,
+}
+
+impl Style {
+    pub fn new(alphabet: Alphabet, group: Option<Grouping>) -> Result<Self, Error> {
+        if let Some(g) = group.filter(|g| alphabet.contains(g.separator)) {
+            return Err(Error::InvalidArgs(format!(
+                "--group separator '{}' is also in the alphabet; choose another",
+                g.separator
+            )));
+        }
+        Ok(Self { alphabet, group })
+    }
+
+    /// Plain `-f text`: printable ASCII, ungrouped.
+    pub fn printable() -> Self {
+        Self {
+            alphabet: Alphabet::printable(),
+            group: None,
+        }
+    }
+
+    /// What `count` characters carry, e.g. `16 characters from 94 symbols:
+    /// 104.9 bits of entropy (6.55 per character)`.
+    pub fn describe(&self, count: usize) -> String {
+        let per_char = self.alphabet.bits_per_char();
+        format!(
+            "{} characters from {} symbols: {:.1} bits of entropy ({:.2} per character)",
+            count,
+            self.alphabet.len(),
+            count as f64 * per_char,
+            per_char
+        )
+    }
+
+    fn format(&self, chars: &[char]) -> String {
+        let mut text = String::new();
+        for (i, c) in chars.iter().enumerate() {
+            if let Some(g) = self.group.filter(|g| i > 0 && i % g.size == 0) {
+                text.push(g.separator);
+            }
+            text.push(*c);
+        }
+        text.push('\n');
+        text
+    }
+
+    /// Draws `count` characters with bytes from `collect`, calling it again
+    /// if rejections used up the first batch. Returns every byte drawn and
+    /// the formatted line.
+    pub fn generate(
+        &self,
+        count: usize,
+        mut collect: impl FnMut(usize) -> Result<EntropyResult, Error>,
+    ) -> Result<(EntropyResult, String), Error> {
+        let mut chars = Vec::with_capacity(count);
+        let mut drawn = EntropyResult {
+            bytes: Vec::new(),
+            source: String::new(),
+        };
+        while chars.len() < count {
+            let batch = collect(self.alphabet.request_bytes(count - chars.len()))?;
+            self.alphabet.sample(&batch.bytes, count, &mut chars);
+            drawn.bytes.extend_from_slice(&batch.bytes);
+            drawn.source = batch.source;
+        }
+        let text = self.format(&chars);
+        chars.fill('\0');
+        Ok((drawn, text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alphabet_parse() {
+        assert_eq!(Alphabet::parse("01").unwrap().len(), 2);
+        assert_eq!(Alphabet::parse("αβγδ").unwrap().len(), 4);
+        assert!(Alphabet::parse("a").is_err());
+        assert!(Alphabet::parse("abca").is_err());
+        assert!(Alphabet::parse("ab c").is_err());
+        // e + combining acute accent
+        assert!(Alphabet::parse("ae\u{301}").is_err());
+        assert_eq!(Alphabet::printable().len(), 94);
+    }
+
+    #[test]
+    fn test_sample_rejects_biased_draws() {
+        // 10 symbols: draws of 250 and above are rejected
+        let digits = Alphabet::parse("0123456789").unwrap();
+        assert_eq!(digits.limit(), 250);
+        let mut out = Vec::new();
+        digits.sample(&[7, 250, 255, 19, 249, 3], 3, &mut out);
+        assert_eq!(out, ['7', '9', '9']);
+
+        // Two bytes per draw above 256 symbols, big-endian
+        let wide = Alphabet::parse(&('\u{4E00}'..='\u{4F2B}').collect::<String>()).unwrap();
+        assert_eq!(wide.len(), 300);
+        assert_eq!(wide.limit(), 65400);
+        let mut out = Vec::new();
+        wide.sample(&[0xFF, 0x78, 0x01, 0x2D, 0x05], 5, &mut out);
+        assert_eq!(out, ['\u{4E01}']);
+    }
+
+    #[test]
+    fn test_printable_and_describe() {
+        // 94 symbols: bytes 188 and above are rejected, the rest taken mod 94
+        let style = Style::printable();
+        let mut out = Vec::new();
+        style
+            .alphabet
+            .sample(&[0, 93, 94, 187, 188, 255], usize::MAX, &mut out);
+        assert_eq!(out, ['!', '~', '!', '~']);
+        assert_eq!(
+            style.describe(16),
+            "16 characters from 94 symbols: 104.9 bits of entropy (6.55 per character)"
+        );
+    }
+
+    #[test]
+    fn test_grouping() {
+        assert_eq!(
+            "4-".parse::<Grouping>(),
+            Ok(Grouping {
+                size: 4,
+                separator: '-'
+            })
+        );
+        assert_eq!("5".parse::<Grouping>().unwrap().separator, '-');
+        assert_eq!("3 ".parse::<Grouping>().unwrap().separator, ' ');
+        assert!("0-".parse::<Grouping>().is_err());
+        assert!("-".parse::<Grouping>().is_err());
+        assert!("4--".parse::<Grouping>().is_err());
+
+        let letters = Alphabet::parse("ABCDEFGHIJ").unwrap();
+        let style = Style::new(letters, "4-".parse().ok()).unwrap();
+        let chars: Vec<char> = "ABCDEFGHIJ".chars().collect();
+        assert_eq!(style.format(&chars), "ABCD-EFGH-IJ\n");
+        // The separator would be ambiguous
+        assert!(Style::new(Alphabet::printable(), "4-".parse().ok()).is_err());
+        assert!(Style::new(Alphabet::printable(), "4 ".parse().ok()).is_ok());
+    }
+
+    #[test]
+    fn test_generate_requests_more_after_rejections() {
+        let digits = Alphabet::parse("0123456789").unwrap();
+        let style = Style::new(digits, None).unwrap();
+        let mut calls = 0;
+        let (drawn, text) = style
+            .generate(4, |n| {
+                calls += 1;
+                // Everything rejected the first time round
+                let byte = if calls == 1 { 255 } else { 42 };
+                Ok(EntropyResult {
+                    bytes: vec![byte; n],
+                    source: "test".into(),
+                })
+            })
+            .unwrap();
+        assert_eq!(calls, 2);
+        assert_eq!(text, "2222\n");
+        assert!(drawn.bytes.len() > 4);
+        assert_eq!(drawn.source, "test");
+    }
+}
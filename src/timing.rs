@@ -0,0 +1,137 @@
+//! `--timing`: where a one-shot request's time went, per source attempt
+//! and per pipeline stage. Recording is a no-op until [`enable`] is called.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+use crate::table::{self, Align, Table};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// BLAKE2b mixing, wherever a source or the pipeline does it
+    Mix,
+    /// ChaCha20 or HMAC_DRBG expansion
+    Csprng,
+    /// Encoding and writing the output
+    Format,
+}
+
+struct Attempt {
+    source: String,
+    elapsed: Duration,
+    /// Mixing and expansion done inside the attempt
+    staged: Duration,
+    error: Option<String>,
+}
+
+#[derive(Default)]
+struct Timings {
+    attempts: Vec<Attempt>,
+    mix: Duration,
+    csprng: Duration,
+    format: Duration,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static TIMINGS: Mutex<Option<Timings>> = Mutex::new(None);
+
+pub fn enable() {
+    *TIMINGS.lock().unwrap_or_else(|e| e.into_inner()) = Some(Timings::default());
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+fn with<T: Default>(f: impl FnOnce(&mut Timings) -> T) -> T {
+    TIMINGS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_mut()
+        .map(f)
+        .unwrap_or_default()
+}
+
+/// Runs `f`, adding its duration to `stage`.
+pub fn time<T>(stage: Stage, f: impl FnOnce() -> T) -> T {
+    if !enabled() {
+        return f();
+    }
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    with(|t| match stage {
+        Stage::Mix => t.mix += elapsed,
+        Stage::Csprng => t.csprng += elapsed,
+        Stage::Format => t.format += elapsed,
+    });
+    result
+}
+
+/// Runs one attempt at `source`, recording its time and outcome.
+pub fn attempt<T>(source: &str, f: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+    if !enabled() {
+        return f();
+    }
+    let stage_total = || with(|t| t.mix + t.csprng);
+    let before = stage_total();
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    let error = result.as_ref().err().map(|e| e.to_string());
+    let staged = stage_total().saturating_sub(before);
+    with(|t| {
+        t.attempts.push(Attempt {
+            source: source.to_string(),
+            elapsed,
+            staged,
+            error,
+        })
+    });
+    result
+}
+
+/// The attempts and the stage breakdown of a request that took `total`.
+/// Collection is the attempts' time less the mixing and expansion done
+/// inside them; `other` is whatever no stage accounts for.
+pub fn report(total: Duration) -> String {
+    let guard = TIMINGS.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(t) = guard.as_ref() else {
+        return String::new();
+    };
+
+    let mut sources = Table::new(&[
+        ("Source", Align::Left),
+        ("Time", Align::Right),
+        ("Result", Align::Left),
+    ]);
+    for a in &t.attempts {
+        sources.row(vec![
+            a.source.clone(),
+            table::millis(a.elapsed, 3),
+            a.error.clone().unwrap_or_else(|| "ok".into()),
+        ]);
+    }
+
+    let collect: Duration = t
+        .attempts
+        .iter()
+        .map(|a| a.elapsed.saturating_sub(a.staged))
+        .sum();
+    let accounted = collect + t.mix + t.csprng + t.format;
+    let mut stages = Table::new(&[("Stage", Align::Left), ("Time", Align::Right)]);
+    for (name, d) in [
+        ("collect", collect),
+        ("mix", t.mix),
+        ("csprng", t.csprng),
+        ("format", t.format),
+        ("other", total.saturating_sub(accounted)),
+        ("total", total),
+    ] {
+        stages.row(vec![name.into(), table::millis(d, 3)]);
+    }
+    format!("{}\n{}", sources.render(), stages.render())
+}
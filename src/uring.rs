@@ -0,0 +1,378 @@
+//! io_uring (Linux 5.6+) via the raw syscalls, for `stream --device`: a
+//! reader that keeps several chunk reads queued on one descriptor, so the
+//! kernel fills the next chunks while the current one is framed and
+//! written.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::entropy::cpurng;
+
+const OFF_SQ_RING: libc::off_t = 0;
+const OFF_CQ_RING: libc::off_t = 0x800_0000;
+const OFF_SQES: libc::off_t = 0x1000_0000;
+const OP_READ: u8 = 22;
+const ENTER_GETEVENTS: libc::c_uint = 1 << 0;
+/// Reads at offset -1 use the file position (5.6+), which devices need.
+const FEAT_RW_CUR_POS: u32 = 1 << 3;
+const CURRENT_POSITION: u64 = u64::MAX;
+
+#[repr(C)]
+#[derive(Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    rw_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+#[repr(C)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+/// One mmap'd part of the ring, unmapped on drop.
+struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(fd: RawFd, len: usize, offset: libc::off_t) -> io::Result<Self> {
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd,
+                offset,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            ptr: ptr.cast(),
+            len,
+        })
+    }
+
+    /// The u32 at `offset`, shared with the kernel.
+    fn atomic(&self, offset: u32) -> &AtomicU32 {
+        unsafe { &*(self.ptr.add(offset as usize) as *const AtomicU32) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr.cast(), self.len) };
+    }
+}
+
+struct Ring {
+    // Declared first so the rings are unmapped before the descriptor closes.
+    sq: Mapping,
+    cq: Mapping,
+    sqes: Mapping,
+    fd: OwnedFd,
+    params: Params,
+    sq_mask: u32,
+    cq_mask: u32,
+}
+
+impl Ring {
+    fn new(entries: u32) -> io::Result<Self> {
+        let mut params = Params::default();
+        let fd = unsafe { libc::syscall(libc::SYS_io_uring_setup, entries, &mut params) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as RawFd) };
+        if params.features & FEAT_RW_CUR_POS == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "kernel lacks io_uring reads at the file position",
+            ));
+        }
+        let raw = fd.as_raw_fd();
+        let sq = Mapping::new(
+            raw,
+            params.sq_off.array as usize + params.sq_entries as usize * mem::size_of::<u32>(),
+            OFF_SQ_RING,
+        )?;
+        let cq = Mapping::new(
+            raw,
+            params.cq_off.cqes as usize + params.cq_entries as usize * mem::size_of::<Cqe>(),
+            OFF_CQ_RING,
+        )?;
+        let sqes = Mapping::new(
+            raw,
+            params.sq_entries as usize * mem::size_of::<Sqe>(),
+            OFF_SQES,
+        )?;
+        let sq_mask = sq.atomic(params.sq_off.ring_mask).load(Ordering::Relaxed);
+        let cq_mask = cq.atomic(params.cq_off.ring_mask).load(Ordering::Relaxed);
+        Ok(Self {
+            sq,
+            cq,
+            sqes,
+            fd,
+            params,
+            sq_mask,
+            cq_mask,
+        })
+    }
+
+    /// Queues a read into `buf`, tagged `user_data`. The caller keeps no
+    /// more than `sq_entries` reads outstanding, so the queue never fills.
+    ///
+    /// # Safety
+    /// `buf` must stay allocated until the read's completion is popped.
+    unsafe fn push_read(&self, fd: RawFd, buf: &mut [u8], user_data: u64) {
+        let tail_ref = self.sq.atomic(self.params.sq_off.tail);
+        let tail = tail_ref.load(Ordering::Relaxed);
+        let index = tail & self.sq_mask;
+        let sqe = Sqe {
+            opcode: OP_READ,
+            fd,
+            off: CURRENT_POSITION,
+            addr: buf.as_mut_ptr() as u64,
+            len: buf.len() as u32,
+            user_data,
+            ..Sqe::default()
+        };
+        ptr::write((self.sqes.ptr as *mut Sqe).add(index as usize), sqe);
+        let array = self.sq.ptr.add(self.params.sq_off.array as usize) as *mut u32;
+        ptr::write(array.add(index as usize), index);
+        tail_ref.store(tail.wrapping_add(1), Ordering::Release);
+    }
+
+    /// Submits `submit` queued reads and waits for `wait` completions.
+    /// Fails with `Interrupted` (having submitted nothing) on a signal.
+    fn enter(&self, submit: u32, wait: u32) -> io::Result<()> {
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_enter,
+                self.fd.as_raw_fd(),
+                submit,
+                wait,
+                ENTER_GETEVENTS,
+                ptr::null::<libc::sigset_t>(),
+                0usize,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// The next completion's tag and result, if one is ready.
+    fn pop(&self) -> Option<(u64, i32)> {
+        let head_ref = self.cq.atomic(self.params.cq_off.head);
+        let head = head_ref.load(Ordering::Relaxed);
+        let tail = self
+            .cq
+            .atomic(self.params.cq_off.tail)
+            .load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        let cqe = unsafe {
+            let cqes = self.cq.ptr.add(self.params.cq_off.cqes as usize) as *const Cqe;
+            ptr::read(cqes.add((head & self.cq_mask) as usize))
+        };
+        head_ref.store(head.wrapping_add(1), Ordering::Release);
+        Some((cqe.user_data, cqe.res))
+    }
+}
+
+/// Reads a device through io_uring with `depth` chunk reads always queued.
+/// Completions are handed out in the order they finish; each buffer goes
+/// straight back on the queue once it has been copied out.
+pub struct BatchReader {
+    ring: Ring,
+    file: File,
+    bufs: Vec<Vec<u8>>,
+    /// Reads queued but not yet submitted.
+    unsubmitted: u32,
+    in_flight: usize,
+    /// The completed read being copied out: buffer, start, end.
+    current: Option<(usize, usize, usize)>,
+}
+
+impl BatchReader {
+    /// Sets up the ring and queues `depth` reads of `chunk` bytes from
+    /// `file`. Fails on kernels without io_uring or with it disabled.
+    pub fn new(file: File, chunk: usize, depth: usize) -> io::Result<Self> {
+        let ring = Ring::new(depth as u32)?;
+        let mut reader = Self {
+            ring,
+            file,
+            bufs: (0..depth).map(|_| vec![0u8; chunk]).collect(),
+            unsubmitted: 0,
+            in_flight: 0,
+            current: None,
+        };
+        for i in 0..depth {
+            reader.queue(i);
+        }
+        reader.submit()?;
+        Ok(reader)
+    }
+
+    fn queue(&mut self, i: usize) {
+        let fd = self.file.as_raw_fd();
+        // The buffers outlive every read: Drop waits for them all.
+        unsafe { self.ring.push_read(fd, &mut self.bufs[i], i as u64) };
+        self.unsubmitted += 1;
+        self.in_flight += 1;
+    }
+
+    fn submit(&mut self) -> io::Result<()> {
+        self.ring.enter(self.unsubmitted, 0)?;
+        self.unsubmitted = 0;
+        Ok(())
+    }
+
+    /// Waits for the next completed read.
+    fn complete(&mut self) -> io::Result<(usize, i32)> {
+        loop {
+            if let Some((tag, res)) = self.ring.pop() {
+                self.in_flight -= 1;
+                return Ok((tag as usize, res));
+            }
+            self.ring.enter(self.unsubmitted, 1)?;
+            self.unsubmitted = 0;
+        }
+    }
+}
+
+impl Read for BatchReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if out.is_empty() {
+            return Ok(0);
+        }
+        let (i, start, end) = match self.current {
+            Some(current) => current,
+            None => match self.complete()? {
+                (_, res) if res < 0 => return Err(io::Error::from_raw_os_error(-res)),
+                (_, 0) => return Ok(0),
+                (i, res) => (i, 0, res as usize),
+            },
+        };
+        let n = (end - start).min(out.len());
+        // No wipe: the buffer is queued straight back, the next read
+        // overwrites it and Drop wipes whatever is left.
+        out[..n].copy_from_slice(&self.bufs[i][start..start + n]);
+        if start + n < end {
+            self.current = Some((i, start + n, end));
+        } else {
+            self.current = None;
+            self.queue(i);
+            self.submit()?;
+        }
+        Ok(n)
+    }
+}
+
+impl Drop for BatchReader {
+    fn drop(&mut self) {
+        // The kernel writes into the buffers until each read completes.
+        while self.in_flight > 0 {
+            match self.complete() {
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => {
+                    // Leaked rather than freed under a pending read.
+                    mem::forget(mem::take(&mut self.bufs));
+                    return;
+                }
+            }
+        }
+        for buf in &mut self.bufs {
+            cpurng::zeroize_vec(buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_in_chunks() {
+        let file = File::open("/dev/zero").unwrap();
+        let mut reader = match BatchReader::new(file, 4096, 4) {
+            Ok(r) => r,
+            // io_uring disabled (e.g. a container's seccomp profile)
+            Err(_) => return,
+        };
+        let mut buf = [1u8; 10000];
+        assert_eq!(reader.read(&mut buf[..1000]).unwrap(), 1000);
+        assert_eq!(reader.read(&mut buf).unwrap(), 3096);
+        assert_eq!(reader.read(&mut buf).unwrap(), 4096);
+        assert!(buf[..4096].iter().all(|&b| b == 0));
+        assert_eq!(reader.in_flight, 4);
+    }
+}
@@ -0,0 +1,134 @@
+//! USDT probes (`--features usdt`) at the points worth tracing in
+//! production: a source delivering, a request being answered, a daemon
+//! injection and a failed health test. Each probe is a `nop` plus an ELF
+//! note in the `sys/sdt.h` format, under the provider `mixrand`, so it costs
+//! nothing until bpftrace, perf or SystemTap attaches. Without the feature,
+//! or off x86_64 and aarch64 Linux, the functions are empty. Strings are
+//! passed as a pointer and a length, e.g. `str(arg0, arg1)` in bpftrace.
+
+/// Operand prefix in the note's argument specs: `8@%rax` on x86_64
+/// (AT&T), `8@x0` on aarch64.
+#[cfg(all(feature = "usdt", target_arch = "x86_64"))]
+macro_rules! reg_prefix {
+    () => {
+        "%"
+    };
+}
+
+#[cfg(all(feature = "usdt", target_arch = "aarch64"))]
+macro_rules! reg_prefix {
+    () => {
+        ""
+    };
+}
+
+/// Emits the probe site and its `.note.stapsdt` entry; every argument is
+/// a u64.
+#[cfg(all(
+    feature = "usdt",
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+macro_rules! probe {
+    (@site $name:literal, $spec:expr, $($arg:expr),+) => {
+        unsafe {
+            std::arch::asm!(
+                "990: nop",
+                ".pushsection .note.stapsdt, \"?\", \"note\"",
+                ".balign 4",
+                ".4byte 992f-991f, 994f-993f, 3",
+                "991: .asciz \"stapsdt\"",
+                "992: .balign 4",
+                "993: .8byte 990b",
+                ".8byte _.stapsdt.base",
+                ".8byte 0",
+                ".asciz \"mixrand\"",
+                concat!(".asciz \"", $name, "\""),
+                concat!(".asciz \"", $spec, "\""),
+                "994: .balign 4",
+                ".popsection",
+                ".ifndef _.stapsdt.base",
+                ".pushsection .stapsdt.base, \"aG\", \"progbits\", .stapsdt.base, comdat",
+                ".weak _.stapsdt.base",
+                ".hidden _.stapsdt.base",
+                "_.stapsdt.base: .space 1",
+                ".size _.stapsdt.base, 1",
+                ".popsection",
+                ".endif",
+                $(in(reg) $arg,)+
+                options(readonly, nostack, preserves_flags),
+            )
+        }
+    };
+    ($name:literal, $a:expr, $b:expr) => {
+        probe!(@site $name,
+            concat!("8@", reg_prefix!(), "{0} 8@", reg_prefix!(), "{1}"),
+            $a, $b)
+    };
+    ($name:literal, $a:expr, $b:expr, $c:expr) => {
+        probe!(@site $name,
+            concat!("8@", reg_prefix!(), "{0} 8@", reg_prefix!(), "{1} 8@", reg_prefix!(), "{2}"),
+            $a, $b, $c)
+    };
+}
+
+#[cfg(not(all(
+    feature = "usdt",
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+macro_rules! probe {
+    ($name:literal, $($arg:expr),+) => {
+        $(let _ = $arg;)+
+    };
+}
+
+/// `source_selected(name, name_len, bytes)`: a source delivered `bytes`
+/// towards a request. Fires once per source when `[policy]` mixes several.
+#[inline(always)]
+#[allow(named_asm_labels)]
+pub fn source_selected(name: &str, bytes: usize) {
+    probe!(
+        "source_selected",
+        name.as_ptr() as u64,
+        name.len() as u64,
+        bytes as u64
+    );
+}
+
+/// `bytes_generated(bytes, source, source_len)`: a request was answered;
+/// `source` is the full label, e.g. `rdseed → HMAC_DRBG`.
+#[inline(always)]
+#[allow(named_asm_labels)]
+pub fn bytes_generated(bytes: usize, source: &str) {
+    probe!(
+        "bytes_generated",
+        bytes as u64,
+        source.as_ptr() as u64,
+        source.len() as u64
+    );
+}
+
+/// `injection(bytes, credit_bits)`: the daemon handed a batch to its
+/// inject target.
+#[inline(always)]
+#[allow(named_asm_labels)]
+pub fn injection(bytes: usize, credit_bits: u32) {
+    probe!("injection", bytes as u64, credit_bits as u64);
+}
+
+/// `health_failed(test, count, cutoff)`: a continuous health test tripped
+/// on `count` samples against `cutoff`. `test` is a [`HealthTest`]: 1 for
+/// the repetition count test, 2 for adaptive proportion.
+#[inline(always)]
+#[allow(named_asm_labels)]
+pub fn health_failed(test: HealthTest, count: u32, cutoff: u32) {
+    probe!("health_failed", test as u64, count as u64, cutoff as u64);
+}
+
+/// The SP 800-90B §4.4 test that tripped, as [`health_failed`] reports it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthTest {
+    RepetitionCount = 1,
+    AdaptiveProportion = 2,
+}
@@ -0,0 +1,126 @@
+//! `--verify`: re-reads an output file from disk, checks it against the
+//! digest taken while it was written, and runs quick statistics on the
+//! bytes it encodes, to catch silent disk or filesystem corruption.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use crate::checksum::{Algorithm, Tee};
+use crate::error::Error;
+use crate::stats::{self, FIPS_WINDOW_BYTES};
+
+/// Shortest output checked for a stuck value.
+const MIN_STUCK_CHECK_BYTES: usize = 16;
+
+/// Digest of `path` as stored: flushed, dropped from the page cache where
+/// the kernel allows, and read back.
+fn digest_from_disk(path: &Path, algorithm: Algorithm) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    file.sync_all()?;
+    unsafe {
+        libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED);
+    }
+    let mut tee = Tee::new(io::sink(), Some(algorithm));
+    io::copy(&mut file, &mut tee)?;
+    Ok(tee.finish().unwrap_or_default())
+}
+
+/// A summary of `bytes`, or why they don't look random.
+fn quick_stats(bytes: &[u8]) -> Result<String, String> {
+    if bytes.len() >= MIN_STUCK_CHECK_BYTES && bytes.iter().all(|&b| b == bytes[0]) {
+        return Err(format!("every byte is 0x{:02x}", bytes[0]));
+    }
+    let windows: Vec<&[u8; FIPS_WINDOW_BYTES]> = bytes
+        .chunks_exact(FIPS_WINDOW_BYTES)
+        .map(|w| w.try_into().unwrap())
+        .collect();
+    let failed = windows
+        .iter()
+        .filter(|w| !stats::fips_suite(w).all_passed())
+        .count();
+    if failed > 0 {
+        return Err(format!(
+            "{} of {} FIPS 140-2 windows failed",
+            failed,
+            windows.len()
+        ));
+    }
+    let fips = match windows.len() {
+        0 => "too short for FIPS 140-2".to_string(),
+        n => format!("{} FIPS 140-2 windows passed", n),
+    };
+    Ok(format!(
+        "{}, Shannon entropy {:.3} bits/byte",
+        fips,
+        stats::shannon_entropy(bytes)
+    ))
+}
+
+/// Checks `path` against `digest` (taken with `algorithm` while writing)
+/// and `bytes`, the output it encodes. Prints a summary to stderr.
+pub fn run(path: &Path, algorithm: Algorithm, digest: &str, bytes: &[u8]) -> Result<(), Error> {
+    let stored = digest_from_disk(path, algorithm).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("cannot re-read {}: {}", path.display(), e),
+        )
+    })?;
+    if stored != digest {
+        return Err(Error::Io(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} does not match what was written (digest {} on disk, {} written)",
+                path.display(),
+                stored,
+                digest
+            ),
+        )));
+    }
+    let summary = quick_stats(bytes).map_err(|why| {
+        Error::NoEntropy(format!(
+            "{}: output fails quick statistics: {}",
+            path.display(),
+            why
+        ))
+    })?;
+    eprintln!("verified {}: digest matches; {}", path.display(), summary);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quick_stats() {
+        let good = crate::csprng::generate([3u8; 32], 2 * FIPS_WINDOW_BYTES + 10);
+        assert!(quick_stats(&good)
+            .unwrap()
+            .starts_with("2 FIPS 140-2 windows passed"));
+        assert!(quick_stats(&good[..32]).unwrap().starts_with("too short"));
+        assert_eq!(quick_stats(&[0u8; 32]).unwrap_err(), "every byte is 0x00");
+        let mut stuck = good.clone();
+        stuck[..FIPS_WINDOW_BYTES].fill(0x55);
+        assert!(quick_stats(&stuck).unwrap_err().contains("1 of 2"));
+    }
+
+    #[test]
+    fn test_detects_change_on_disk() {
+        let path = std::env::temp_dir().join(format!("mixrand-verify-{}.bin", std::process::id()));
+        let bytes = crate::csprng::generate([4u8; 32], 64);
+        std::fs::write(&path, &bytes).unwrap();
+        let mut tee = Tee::new(io::sink(), Some(Algorithm::Sha256));
+        io::Write::write_all(&mut tee, &bytes).unwrap();
+        let digest = tee.finish().unwrap();
+        assert!(run(&path, Algorithm::Sha256, &digest, &bytes).is_ok());
+
+        let mut damaged = bytes.clone();
+        damaged[10] ^= 1;
+        std::fs::write(&path, &damaged).unwrap();
+        let err = run(&path, Algorithm::Sha256, &digest, &bytes).unwrap_err();
+        assert!(err.to_string().contains("does not match"), "{}", err);
+        let _ = std::fs::remove_file(&path);
+    }
+}
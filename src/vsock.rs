@@ -0,0 +1,203 @@
+//! `serve-vsock`: hands mixed entropy to VM guests over AF_VSOCK, using the
+//! framing in [`crate::entropy::vsock`].
+
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::cli::ServeVsockArgs;
+use crate::config::Config;
+use crate::entropy::{self, cpurng, plugin, vsock};
+use crate::error::Error;
+use crate::sandbox::{self, Policy};
+use crate::secmem;
+use crate::signals::{self, Signal};
+
+/// How often the accept loop checks for shutdown.
+const ACCEPT_POLL_MS: libc::c_int = 250;
+/// Guests idle for longer than this are disconnected.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Answers requests on one connection until the guest hangs up, returning
+/// the bytes served. A malformed request gets an error frame and ends the
+/// connection; an oversized one or a failed generation only fails that
+/// request.
+fn serve_conn<S: Read + Write>(
+    conn: &mut S,
+    config: &Config,
+    max_request_bytes: usize,
+) -> Result<u64, Error> {
+    let mut served = 0;
+    loop {
+        let count = match vsock::read_request(conn) {
+            Ok(Some(count)) => count,
+            Ok(None) => return Ok(served),
+            Err(Error::InvalidArgs(msg)) => {
+                let _ = vsock::write_response(conn, Err(&msg));
+                return Err(Error::InvalidArgs(msg));
+            }
+            Err(e) => return Err(e),
+        };
+        if count > max_request_bytes {
+            let msg = format!(
+                "request of {} bytes exceeds the server limit of {}",
+                count, max_request_bytes
+            );
+            vsock::write_response(conn, Err(&msg))?;
+            continue;
+        }
+        match entropy::generate(count, config) {
+            Ok(mut result) => {
+                let locked = secmem::protect(&result.bytes);
+                let sent = vsock::write_response(conn, Ok(&result.bytes));
+                cpurng::zeroize_vec(&mut result.bytes);
+                drop(locked);
+                sent?;
+                served += count as u64;
+                log::debug!("served {}B from {}", count, result.source);
+            }
+            Err(e) => vsock::write_response(conn, Err(&e.to_string()))?,
+        }
+    }
+}
+
+/// Decrements the connection count when a client thread ends.
+pub struct ClientSlot(pub Arc<AtomicUsize>);
+
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Installs the seccomp filter once the listener is bound; `serve-socket`
+/// shares it. An `[exec]` command would inherit the filter and fail, so it
+/// disables the sandbox.
+pub fn install_sandbox(no_sandbox: bool, config: &Config) {
+    if no_sandbox {
+        log::warn!("seccomp sandbox disabled");
+        return;
+    }
+    if config.exec.is_enabled() {
+        log::warn!("seccomp sandbox disabled: [exec] command would inherit the filter");
+        return;
+    }
+    if !plugin::load(&config.plugins).plugins.is_empty() {
+        log::warn!("seccomp sandbox disabled: plugins may need syscalls outside the allow-list");
+        return;
+    }
+    let mut policy = Policy::serve_vsock(config.serial.is_enabled());
+    if config.vsock.is_enabled() {
+        policy = policy.vsock_client();
+    }
+    match sandbox::install(&policy) {
+        Ok(()) => log::info!("seccomp sandbox installed"),
+        Err(e) => log::warn!("cannot install seccomp sandbox: {}", e),
+    }
+}
+
+pub fn run(args: &ServeVsockArgs, config: &Config) -> Result<(), Error> {
+    if args.max_clients == 0 {
+        return Err(Error::InvalidArgs(
+            "max-clients must be greater than 0".into(),
+        ));
+    }
+    let max_request_bytes = (args.max_request_bytes as usize).min(vsock::MAX_FRAME_BYTES);
+
+    signals::install(&[Signal::Term, Signal::Int]);
+
+    let listener = vsock::listen(libc::VMADDR_CID_ANY, args.port, 64).map_err(|e| {
+        Error::InvalidArgs(format!("cannot listen on vsock port {}: {}", args.port, e))
+    })?;
+    install_sandbox(args.no_sandbox, config);
+    log::info!(
+        "vsock server listening on port {} (guests={}, max clients={}, max request={}B)",
+        args.port,
+        args.cid.map_or("any".into(), |c| c.to_string()),
+        args.max_clients,
+        max_request_bytes
+    );
+
+    let config = Arc::new(config.clone());
+    let active = Arc::new(AtomicUsize::new(0));
+    while !signals::shutdown_requested() {
+        let mut pfd = libc::pollfd {
+            fd: listener.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut pfd, 1, ACCEPT_POLL_MS) } <= 0 {
+            continue;
+        }
+        let (mut conn, peer) = match vsock::accept(&listener) {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                log::warn!("accept failed: {}", e);
+                continue;
+            }
+        };
+        if let Some(cid) = args.cid.filter(|&c| c != peer) {
+            log::warn!("refused guest {}: only guest {} is served", peer, cid);
+            continue;
+        }
+        if active.load(Ordering::Relaxed) >= args.max_clients {
+            log::warn!(
+                "refused guest {}: {} clients already connected",
+                peer,
+                args.max_clients
+            );
+            continue;
+        }
+        if let Err(e) = vsock::set_timeouts(conn.as_raw_fd(), IDLE_TIMEOUT) {
+            log::warn!("guest {}: {}", peer, e);
+            continue;
+        }
+
+        active.fetch_add(1, Ordering::Relaxed);
+        let slot = ClientSlot(Arc::clone(&active));
+        let config = Arc::clone(&config);
+        let spawned = thread::Builder::new()
+            .name(format!("vsock-{}", peer))
+            .spawn(move || {
+                let _slot = slot;
+                log::info!("guest {} connected", peer);
+                match serve_conn(&mut conn, &config, max_request_bytes) {
+                    Ok(served) => log::info!("guest {} disconnected after {}B", peer, served),
+                    Err(e) => log::warn!("guest {}: {}", peer, e),
+                }
+            });
+        if let Err(e) = spawned {
+            log::error!("cannot serve guest {}: {}", peer, e);
+        }
+    }
+
+    log::info!("vsock server shut down");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn test_oversized_request_fails_alone() {
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        vsock::write_request(&mut client, 1024).unwrap();
+        client.write_all(b"GET / HTTP/1.0\r\n").unwrap();
+        let config = Config::default();
+        assert!(matches!(
+            serve_conn(&mut server, &config, 512),
+            Err(Error::InvalidArgs(_))
+        ));
+        for expected in ["server limit of 512", "not a mixrand vsock request"] {
+            match vsock::read_response(&mut client) {
+                Err(Error::NoEntropy(msg)) => assert!(msg.contains(expected), "{}", msg),
+                other => panic!("expected an error frame, got {:?}", other.map(|v| v.len())),
+            }
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! `mixrand watch`: a `vmstat`-style line per interval with the kernel's
+//! entropy accounting and, given the daemon's `--status-file`, what it
+//! injected since the previous line.
+
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::check;
+use crate::cli::WatchArgs;
+use crate::error::Error;
+use crate::signals::{self, Signal};
+
+const RANDOM_SYSCTL: &str = "/proc/sys/kernel/random";
+
+/// Reprint the header after this many rows, as `vmstat` does.
+const HEADER_EVERY: u64 = 20;
+
+/// The kernel's view of its pool. Fields missing on this kernel are `None`
+/// (5.18 kept the wakeup and reseed sysctls only as no-ops).
+#[derive(Debug, Clone, Copy, Default)]
+struct Kernel {
+    entropy_avail: Option<u32>,
+    poolsize: Option<u32>,
+    write_wakeup_threshold: Option<u32>,
+    urandom_min_reseed_secs: Option<u32>,
+}
+
+fn read_sysctl(name: &str) -> Option<u32> {
+    fs::read_to_string(Path::new(RANDOM_SYSCTL).join(name))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+impl Kernel {
+    fn read() -> Self {
+        Self {
+            entropy_avail: read_sysctl("entropy_avail"),
+            poolsize: read_sysctl("poolsize"),
+            write_wakeup_threshold: read_sysctl("write_wakeup_threshold"),
+            urandom_min_reseed_secs: read_sysctl("urandom_min_reseed_secs"),
+        }
+    }
+}
+
+/// The counters `watch` needs from the daemon's status file.
+#[derive(Debug, Clone, Deserialize)]
+struct Daemon {
+    pid: u32,
+    injections: u64,
+    injection_failures: u64,
+    bytes_injected: u64,
+    bits_credited: u64,
+}
+
+impl Daemon {
+    fn read(path: &Path) -> Option<Self> {
+        serde_json::from_slice(&fs::read(path).ok()?).ok()
+    }
+
+    /// Injections since `prev`, or `None` across a daemon restart, when the
+    /// counters started over.
+    fn since(&self, prev: &Daemon) -> Option<Daemon> {
+        if self.pid != prev.pid || self.injections < prev.injections {
+            return None;
+        }
+        Some(Daemon {
+            pid: self.pid,
+            injections: self.injections - prev.injections,
+            injection_failures: self
+                .injection_failures
+                .saturating_sub(prev.injection_failures),
+            bytes_injected: self.bytes_injected.saturating_sub(prev.bytes_injected),
+            bits_credited: self.bits_credited.saturating_sub(prev.bits_credited),
+        })
+    }
+}
+
+fn header() -> String {
+    format!(
+        "{:<8} {:>6} {:>6} {:>6} {:>6} {:>6} {:>5} {:>8} {:>8} {:>5}",
+        "time", "avail", "delta", "pool", "wakeup", "reseed", "inj", "bytes", "credit", "fail"
+    )
+}
+
+fn cell<T: ToString>(v: Option<T>) -> String {
+    v.map_or("-".into(), |v| v.to_string())
+}
+
+fn row(time: &str, kernel: &Kernel, prev: Option<&Kernel>, injected: Option<&Daemon>) -> String {
+    let delta = match (kernel.entropy_avail, prev.and_then(|p| p.entropy_avail)) {
+        (Some(now), Some(before)) => Some(format!("{:+}", now as i64 - before as i64)),
+        _ => None,
+    };
+    format!(
+        "{:<8} {:>6} {:>6} {:>6} {:>6} {:>6} {:>5} {:>8} {:>8} {:>5}",
+        time,
+        cell(kernel.entropy_avail),
+        cell(delta),
+        cell(kernel.poolsize),
+        cell(kernel.write_wakeup_threshold),
+        cell(kernel.urandom_min_reseed_secs),
+        cell(injected.map(|d| d.injections)),
+        cell(injected.map(|d| d.bytes_injected)),
+        cell(injected.map(|d| d.bits_credited)),
+        cell(injected.map(|d| d.injection_failures)),
+    )
+}
+
+/// Local wall-clock time as `HH:MM:SS`.
+fn clock() -> String {
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        format!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec)
+    }
+}
+
+pub fn run(args: &WatchArgs) -> Result<(), Error> {
+    let interval = check::parse_duration(&args.interval)?;
+    signals::install(&[Signal::Term, Signal::Int]);
+
+    let mut prev_kernel: Option<Kernel> = None;
+    let mut prev_daemon = args.status_file.as_deref().and_then(Daemon::read);
+    let mut rows = 0u64;
+
+    while !signals::shutdown_requested() {
+        if rows.is_multiple_of(HEADER_EVERY) {
+            println!("{}", header());
+        }
+        let kernel = Kernel::read();
+        let daemon = args.status_file.as_deref().and_then(Daemon::read);
+        let injected = match (&daemon, &prev_daemon) {
+            (Some(now), Some(before)) => now.since(before),
+            _ => None,
+        };
+        println!(
+            "{}",
+            row(&clock(), &kernel, prev_kernel.as_ref(), injected.as_ref())
+        );
+
+        rows += 1;
+        if args.count.is_some_and(|n| rows >= n) {
+            break;
+        }
+        prev_kernel = Some(kernel);
+        prev_daemon = daemon;
+        signals::interruptible_sleep(interval);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daemon_deltas() {
+        let before = Daemon {
+            pid: 7,
+            injections: 10,
+            injection_failures: 1,
+            bytes_injected: 640,
+            bits_credited: 2560,
+        };
+        let now = Daemon {
+            injections: 12,
+            bytes_injected: 768,
+            bits_credited: 3072,
+            ..before.clone()
+        };
+        let d = now.since(&before).unwrap();
+        assert_eq!(
+            (
+                d.injections,
+                d.injection_failures,
+                d.bytes_injected,
+                d.bits_credited
+            ),
+            (2, 0, 128, 512)
+        );
+
+        let restarted = Daemon {
+            pid: 8,
+            ..now.clone()
+        };
+        assert!(restarted.since(&now).is_none());
+    }
+
+    #[test]
+    fn test_row_columns_line_up() {
+        let kernel = Kernel {
+            entropy_avail: Some(256),
+            poolsize: Some(256),
+            ..Default::default()
+        };
+        let prev = Kernel {
+            entropy_avail: Some(300),
+            ..kernel
+        };
+        let r = row("12:00:00", &kernel, Some(&prev), None);
+        assert_eq!(r.len(), header().len());
+        assert!(r.contains("   -44"));
+    }
+}
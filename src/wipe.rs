@@ -0,0 +1,363 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Seek, SeekFrom};
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Digest};
+
+use crate::cli::WipeArgs;
+use crate::config::Config;
+use crate::entropy::cpurng;
+use crate::error::Error;
+use crate::pattern::Pattern;
+use crate::signals::{self, Signal};
+use crate::stream::KeyedStream;
+use crate::table::{self, Units};
+
+type Blake2b256 = Blake2b<U32>;
+
+/// O_DIRECT offset, length and buffer alignment.
+const DIRECT_ALIGN: usize = 4096;
+
+/// Seconds between progress lines.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Byte buffer aligned to [`DIRECT_ALIGN`], as O_DIRECT requires.
+struct AlignedBuf {
+    storage: Vec<u8>,
+    offset: usize,
+    len: usize,
+}
+
+impl AlignedBuf {
+    fn new(len: usize) -> Self {
+        let storage = vec![0u8; len + DIRECT_ALIGN];
+        let offset = storage.as_ptr().align_offset(DIRECT_ALIGN);
+        Self {
+            storage,
+            offset,
+            len,
+        }
+    }
+
+    fn as_mut(&mut self, len: usize) -> &mut [u8] {
+        &mut self.storage[self.offset..self.offset + len.min(self.len)]
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        cpurng::zeroize_vec(&mut self.storage);
+    }
+}
+
+/// True if `source` is `disk` itself or one of its partitions
+/// (`/dev/sda1`, `/dev/nvme0n1p2`).
+fn same_disk(source: &Path, disk: &Path) -> bool {
+    let (source, disk) = (source.to_string_lossy(), disk.to_string_lossy());
+    match source.strip_prefix(&*disk) {
+        Some(rest) => {
+            let rest = rest.strip_prefix('p').unwrap_or(rest);
+            rest.chars().all(|c| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+/// True if `path` (after resolving symlinks), or a partition of it, appears
+/// as a mount source.
+fn is_mounted(path: &Path) -> bool {
+    let Ok(target) = fs::canonicalize(path) else {
+        return false;
+    };
+    let Ok(mounts) = fs::read_to_string("/proc/mounts") else {
+        return false;
+    };
+    mounts
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .filter_map(|source| fs::canonicalize(source).ok())
+        .any(|source| same_disk(&source, &target))
+}
+
+/// Block indices to read back: `samples` distinct picks from `0..blocks`,
+/// drawn from `stream`.
+fn pick_samples(stream: &mut KeyedStream, blocks: u64, samples: usize) -> Result<Vec<u64>, Error> {
+    let mut picked = Vec::with_capacity(samples);
+    if blocks == 0 {
+        return Ok(picked);
+    }
+    let wanted = samples.min(blocks as usize);
+    let mut word = [0u8; 8];
+    while picked.len() < wanted {
+        stream.fill(&mut word)?;
+        let block = u64::from_le_bytes(word) % blocks;
+        if !picked.contains(&block) {
+            picked.push(block);
+        }
+    }
+    picked.sort_unstable();
+    Ok(picked)
+}
+
+fn digest(data: &[u8]) -> [u8; 32] {
+    Blake2b256::digest(data).into()
+}
+
+/// Opens the writer (O_DIRECT if requested) plus a plain descriptor for
+/// the unaligned tail and read-back.
+fn open_target(args: &WipeArgs) -> Result<(File, File), Error> {
+    let open = |direct: bool| {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(if direct { libc::O_DIRECT } else { 0 })
+            .open(&args.target)
+            .map_err(|e| {
+                Error::InvalidArgs(format!("cannot open {}: {}", args.target.display(), e))
+            })
+    };
+    Ok((open(args.direct)?, open(false)?))
+}
+
+/// Drops cached pages so the read-back hits the device.
+fn drop_cache(file: &File) {
+    unsafe {
+        libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED);
+    }
+}
+
+struct Progress {
+    pass: u32,
+    passes: u32,
+    total: u64,
+    start: Instant,
+    last: Instant,
+    reported: u64,
+    units: Units,
+}
+
+impl Progress {
+    fn report(&mut self, done: u64, force: bool) {
+        let due = self.last.elapsed() >= PROGRESS_INTERVAL;
+        if !(due || force) || done == self.reported {
+            return;
+        }
+        self.last = Instant::now();
+        self.reported = done;
+        let secs = self.start.elapsed().as_secs_f64().max(1e-9);
+        eprintln!(
+            "pass {}/{}: {} / {} ({}), {}",
+            self.pass,
+            self.passes,
+            table::bytes(done, self.units),
+            table::bytes(self.total, self.units),
+            table::percent(100.0 * done as f64 / self.total.max(1) as f64, 1),
+            table::rate(done as f64 / secs, self.units)
+        );
+    }
+}
+
+/// Writes one pass of keystream (or `--pattern`) over `size` bytes,
+/// recording digests of the blocks in `samples`.
+fn write_pass(
+    args: &WipeArgs,
+    files: &(File, File),
+    size: u64,
+    stream: &mut KeyedStream,
+    samples: &[u64],
+    progress: &mut Progress,
+) -> Result<BTreeMap<u64, [u8; 32]>, Error> {
+    let block = (args.block_kib << 10) as u64;
+    let mut buf = AlignedBuf::new(block as usize);
+    let mut digests = BTreeMap::new();
+    let mut offset = 0u64;
+
+    while offset < size {
+        if signals::shutdown_requested() {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::Interrupted,
+                format!("interrupted after {}", table::bytes(offset, progress.units)),
+            )));
+        }
+        let len = block.min(size - offset) as usize;
+        let chunk = buf.as_mut(len);
+        match args.pattern {
+            Pattern::Random => stream.fill(chunk)?,
+            pattern => pattern.fill(offset, chunk),
+        }
+        // O_DIRECT needs aligned lengths; the tail goes through the page cache
+        let aligned = if args.direct {
+            len - len % DIRECT_ALIGN
+        } else {
+            len
+        };
+        files.0.write_all_at(&chunk[..aligned], offset)?;
+        if aligned < len {
+            files
+                .1
+                .write_all_at(&chunk[aligned..], offset + aligned as u64)?;
+        }
+        let index = offset / block;
+        if samples.binary_search(&index).is_ok() {
+            digests.insert(index, digest(chunk));
+        }
+        offset += len as u64;
+        progress.report(offset, false);
+    }
+
+    files.0.sync_data()?;
+    files.1.sync_data()?;
+    progress.report(size, true);
+    Ok(digests)
+}
+
+/// Reads back the sampled blocks and compares them with their digests.
+fn verify(
+    args: &WipeArgs,
+    files: &(File, File),
+    size: u64,
+    digests: &BTreeMap<u64, [u8; 32]>,
+) -> Result<(), Error> {
+    let block = (args.block_kib << 10) as u64;
+    let mut buf = AlignedBuf::new(block as usize);
+    drop_cache(&files.1);
+
+    let mut mismatched = Vec::new();
+    for (&index, expected) in digests {
+        let offset = index * block;
+        let len = block.min(size - offset) as usize;
+        let chunk = buf.as_mut(len);
+        let aligned = if args.direct {
+            len - len % DIRECT_ALIGN
+        } else {
+            len
+        };
+        files.0.read_exact_at(&mut chunk[..aligned], offset)?;
+        if aligned < len {
+            files
+                .1
+                .read_exact_at(&mut chunk[aligned..], offset + aligned as u64)?;
+        }
+        if digest(chunk) != *expected {
+            mismatched.push(offset);
+        }
+    }
+
+    if mismatched.is_empty() {
+        log::info!("verified {} sampled blocks", digests.len());
+        Ok(())
+    } else {
+        Err(Error::Io(io::Error::other(format!(
+            "verification failed: {} of {} sampled blocks differ (first at offset {})",
+            mismatched.len(),
+            digests.len(),
+            mismatched[0]
+        ))))
+    }
+}
+
+fn validate(args: &WipeArgs) -> Result<(), Error> {
+    if args.block_kib == 0 || args.block_kib > 64 * 1024 {
+        return Err(Error::InvalidArgs(
+            "block-kib must be between 1 and 65536".into(),
+        ));
+    }
+    if args.direct && !(args.block_kib << 10).is_multiple_of(DIRECT_ALIGN) {
+        return Err(Error::InvalidArgs(format!(
+            "--direct needs a block size that is a multiple of {} bytes",
+            DIRECT_ALIGN
+        )));
+    }
+    if !args.force && is_mounted(&args.target) {
+        return Err(Error::InvalidArgs(format!(
+            "{} is mounted (use --force to wipe anyway)",
+            args.target.display()
+        )));
+    }
+    Ok(())
+}
+
+pub fn run(args: &WipeArgs, config: &Config) -> Result<(), Error> {
+    validate(args)?;
+    let files = open_target(args)?;
+    let size = (&files.1).seek(SeekFrom::End(0))?;
+    if size == 0 {
+        return Err(Error::InvalidArgs(format!(
+            "{} is empty",
+            args.target.display()
+        )));
+    }
+
+    let units = args.units.unwrap_or(config.display.units);
+    signals::install(&[Signal::Term, Signal::Int]);
+    let mut stream = KeyedStream::new(config, args.reseed_mib << 20)?;
+    let block = (args.block_kib << 10) as u64;
+    let samples = if args.verify {
+        pick_samples(&mut stream, size.div_ceil(block), args.verify_samples)?
+    } else {
+        Vec::new()
+    };
+
+    log::info!(
+        "wiping {} ({}) with {} pass(es) of {}{}",
+        args.target.display(),
+        table::bytes(size, units),
+        args.passes,
+        args.pattern.name(),
+        if args.direct { ", O_DIRECT" } else { "" }
+    );
+    let mut digests = BTreeMap::new();
+    for pass in 1..=args.passes {
+        let now = Instant::now();
+        let mut progress = Progress {
+            pass,
+            passes: args.passes,
+            total: size,
+            start: now,
+            last: now,
+            reported: 0,
+            units,
+        };
+        digests = write_pass(args, &files, size, &mut stream, &samples, &mut progress)?;
+    }
+
+    if args.verify {
+        verify(args, &files, size, &digests)?;
+    }
+    log::info!(
+        "wiped {} ({} rekeys)",
+        args.target.display(),
+        stream.reseeds()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aligned_buf() {
+        let mut buf = AlignedBuf::new(10_000);
+        assert_eq!(buf.as_mut(10_000).as_ptr() as usize % DIRECT_ALIGN, 0);
+        assert_eq!(buf.as_mut(20_000).len(), 10_000);
+        assert_eq!(buf.as_mut(512).len(), 512);
+    }
+
+    #[test]
+    fn test_same_disk() {
+        let sda = Path::new("/dev/sda");
+        assert!(same_disk(Path::new("/dev/sda"), sda));
+        assert!(same_disk(Path::new("/dev/sda1"), sda));
+        assert!(!same_disk(Path::new("/dev/sdb1"), sda));
+        assert!(!same_disk(Path::new("/dev/sdaa"), sda));
+        let nvme = Path::new("/dev/nvme0n1");
+        assert!(same_disk(Path::new("/dev/nvme0n1p2"), nvme));
+        assert!(!is_mounted(Path::new("/nonexistent/mixrand-wipe")));
+    }
+}
@@ -0,0 +1,489 @@
+//! Version 1 of the `serve-socket` protocol: a CBOR sequence (RFC 8742) of
+//! request and response maps in each direction, one response per request,
+//! in order.
+//!
+//! A request has `v` (protocol version, default 1), `id` (echoed back,
+//! default 0), `op` (default `get`), `bytes`, `format` (an output format
+//! name such as `hex`, default `raw`) and optionally `deadline_ms` and
+//! `token` (for a server with `[auth]` tokens). A response has `v`, `id`,
+//! `status` (`ok` or `error`) and `health` (`ok`, or `failed` once a FIPS
+//! self-test or health test has failed), then `data` and `source` on
+//! success or `error` on failure. `data` is a byte string for `raw` and
+//! `framed` and text otherwise, exactly as one-shot output prints it.
+//!
+//! `op` = `reserve` generates the bytes but holds them, for up to the
+//! optional `hold_ms`, and answers with `reservation` (its number) and
+//! `expires_ms` instead of `data`. `fetch` and `cancel` take that
+//! `reservation` in place of `bytes` and `format`; `fetch` answers with the
+//! held `data` and `source`, `cancel` with neither. Unknown keys are
+//! ignored, so later versions may add fields.
+
+use std::io::{Read, Write};
+
+use clap::ValueEnum;
+
+use crate::cbor::{self, Value};
+use crate::cli::OutputFormat;
+use crate::entropy::{cpurng, EntropyResult};
+use crate::error::Error;
+use crate::output;
+
+/// Protocol version spoken by this build.
+pub const VERSION: u64 = 1;
+
+/// Longest string accepted in a request.
+const MAX_REQUEST_STRING: usize = 256;
+
+/// What a request asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Generate `bytes` and send them.
+    Get,
+    /// Generate `bytes` and hold them, for up to `hold_ms`.
+    Reserve { hold_ms: Option<u64> },
+    /// Send a reservation's bytes.
+    Fetch(u64),
+    /// Wipe a reservation's bytes unsent.
+    Cancel(u64),
+}
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub id: u64,
+    pub op: Op,
+    /// Bytes to generate; 0 for `fetch` and `cancel`.
+    pub bytes: usize,
+    pub format: OutputFormat,
+    pub deadline_ms: Option<u64>,
+    pub token: Option<String>,
+}
+
+/// Generated bytes, encoded in the requested format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Data {
+    Bytes(Vec<u8>),
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Served {
+    pub data: Data,
+    pub source: String,
+}
+
+/// A successful response's payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    /// `data` and `source`, for `get` and `fetch`.
+    Served(Served),
+    /// `reservation` and `expires_ms`, for `reserve`.
+    Reserved { reservation: u64, expires_ms: u64 },
+    /// Neither, for `cancel`.
+    Cancelled,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub id: u64,
+    /// `false` once a FIPS self-test or health test has failed.
+    pub healthy: bool,
+    pub result: Result<Reply, String>,
+}
+
+fn format_name(format: &OutputFormat) -> String {
+    format
+        .to_possible_value()
+        .map_or_else(String::new, |v| v.get_name().to_string())
+}
+
+fn uint(value: &Value, key: &str) -> Result<Option<u64>, String> {
+    match value.get(key) {
+        None => Ok(None),
+        Some(Value::Uint(n)) => Ok(Some(*n)),
+        Some(_) => Err(format!("'{}' must be an unsigned integer", key)),
+    }
+}
+
+fn text<'a>(value: &'a Value, key: &str) -> Result<Option<&'a str>, String> {
+    match value.get(key) {
+        None => Ok(None),
+        Some(Value::Text(t)) => Ok(Some(t)),
+        Some(_) => Err(format!("'{}' must be a text string", key)),
+    }
+}
+
+/// Checks `v`, which both directions carry.
+fn check_version(value: &Value) -> Result<(), String> {
+    match uint(value, "v")?.unwrap_or(VERSION) {
+        VERSION => Ok(()),
+        v => Err(format!(
+            "protocol version {} is not supported (this end speaks {})",
+            v, VERSION
+        )),
+    }
+}
+
+impl Data {
+    /// `count` bytes, or characters for `-f text`, drawn from `collect`
+    /// and encoded in `format`. Returns the bytes drawn alongside.
+    pub fn draw(
+        count: usize,
+        format: &OutputFormat,
+        collect: impl FnMut(usize) -> Result<EntropyResult, Error>,
+    ) -> Result<(EntropyResult, Self), Error> {
+        let (mut drawn, out) = output::draw(count, format, collect)?;
+        if matches!(format, OutputFormat::Raw | OutputFormat::Framed) {
+            return Ok((drawn, Data::Bytes(out)));
+        }
+        match String::from_utf8(out) {
+            Ok(text) => Ok((drawn, Data::Text(text))),
+            Err(e) => {
+                cpurng::zeroize_vec(&mut e.into_bytes());
+                cpurng::zeroize_vec(&mut drawn.bytes);
+                Err(Error::InvalidArgs("formatted output is not UTF-8".into()))
+            }
+        }
+    }
+
+    pub fn zeroize(&mut self) {
+        match self {
+            Data::Bytes(b) => cpurng::zeroize_vec(b),
+            Data::Text(t) => cpurng::zeroize_vec(&mut std::mem::take(t).into_bytes()),
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Data::Bytes(b) => b,
+            Data::Text(t) => t.as_bytes(),
+        }
+    }
+}
+
+impl Request {
+    pub fn to_value(&self) -> Value {
+        let mut entries = vec![("v", Value::Uint(VERSION)), ("id", Value::Uint(self.id))];
+        match self.op {
+            Op::Get => {}
+            Op::Reserve { hold_ms } => {
+                entries.push(("op", Value::Text("reserve".into())));
+                if let Some(ms) = hold_ms {
+                    entries.push(("hold_ms", Value::Uint(ms)));
+                }
+            }
+            Op::Fetch(reservation) => {
+                entries.push(("op", Value::Text("fetch".into())));
+                entries.push(("reservation", Value::Uint(reservation)));
+            }
+            Op::Cancel(reservation) => {
+                entries.push(("op", Value::Text("cancel".into())));
+                entries.push(("reservation", Value::Uint(reservation)));
+            }
+        }
+        if matches!(self.op, Op::Get | Op::Reserve { .. }) {
+            entries.push(("bytes", Value::Uint(self.bytes as u64)));
+            entries.push(("format", Value::Text(format_name(&self.format))));
+        }
+        if let Some(ms) = self.deadline_ms {
+            entries.push(("deadline_ms", Value::Uint(ms)));
+        }
+        if let Some(token) = &self.token {
+            entries.push(("token", Value::Text(token.clone())));
+        }
+        Value::map(entries)
+    }
+
+    pub fn from_value(value: &Value) -> Result<Self, String> {
+        if !matches!(value, Value::Map(_)) {
+            return Err("request must be a map".into());
+        }
+        check_version(value)?;
+        let reservation = || {
+            uint(value, "reservation")?.ok_or_else(|| "request has no 'reservation'".to_string())
+        };
+        let op = match text(value, "op")? {
+            None | Some("get") => Op::Get,
+            Some("reserve") => Op::Reserve {
+                hold_ms: uint(value, "hold_ms")?,
+            },
+            Some("fetch") => Op::Fetch(reservation()?),
+            Some("cancel") => Op::Cancel(reservation()?),
+            Some(other) => return Err(format!("unknown op '{}'", other)),
+        };
+        let bytes = match op {
+            Op::Get | Op::Reserve { .. } => {
+                uint(value, "bytes")?.ok_or("request has no 'bytes'")?
+            }
+            Op::Fetch(_) | Op::Cancel(_) => 0,
+        };
+        let format = match text(value, "format")? {
+            None => OutputFormat::Raw,
+            Some(name) => OutputFormat::from_str(name, true)
+                .map_err(|_| format!("unknown format '{}'", name))?,
+        };
+        Ok(Self {
+            id: request_id(value),
+            op,
+            bytes: usize::try_from(bytes).unwrap_or(usize::MAX),
+            format,
+            deadline_ms: uint(value, "deadline_ms")?,
+            token: text(value, "token")?.map(str::to_string),
+        })
+    }
+}
+
+/// The `id` of a request, or 0 if it has none, for answering requests that
+/// could not be parsed.
+pub fn request_id(value: &Value) -> u64 {
+    uint(value, "id").ok().flatten().unwrap_or(0)
+}
+
+impl Response {
+    pub fn to_value(&self) -> Value {
+        let mut entries = vec![
+            ("v", Value::Uint(VERSION)),
+            ("id", Value::Uint(self.id)),
+            (
+                "status",
+                Value::Text(if self.result.is_ok() { "ok" } else { "error" }.into()),
+            ),
+            (
+                "health",
+                Value::Text(if self.healthy { "ok" } else { "failed" }.into()),
+            ),
+        ];
+        match &self.result {
+            Ok(Reply::Served(served)) => {
+                let data = match &served.data {
+                    Data::Bytes(b) => Value::Bytes(b.clone()),
+                    Data::Text(t) => Value::Text(t.clone()),
+                };
+                entries.push(("data", data));
+                entries.push(("source", Value::Text(served.source.clone())));
+            }
+            Ok(Reply::Reserved {
+                reservation,
+                expires_ms,
+            }) => {
+                entries.push(("reservation", Value::Uint(*reservation)));
+                entries.push(("expires_ms", Value::Uint(*expires_ms)));
+            }
+            Ok(Reply::Cancelled) => {}
+            Err(msg) => entries.push(("error", Value::Text(msg.clone()))),
+        }
+        Value::map(entries)
+    }
+
+    pub fn from_value(value: &Value) -> Result<Self, String> {
+        if !matches!(value, Value::Map(_)) {
+            return Err("response must be a map".into());
+        }
+        check_version(value)?;
+        let result = match text(value, "status")? {
+            Some("ok") => {
+                let data = match value.get("data") {
+                    Some(Value::Bytes(b)) => Some(Data::Bytes(b.clone())),
+                    Some(Value::Text(t)) => Some(Data::Text(t.clone())),
+                    Some(_) => return Err("'data' must be a byte or text string".into()),
+                    None => None,
+                };
+                match (data, uint(value, "reservation")?) {
+                    (Some(data), _) => {
+                        let source = text(value, "source")?.unwrap_or_default().to_string();
+                        Ok(Reply::Served(Served { data, source }))
+                    }
+                    (None, Some(reservation)) => Ok(Reply::Reserved {
+                        reservation,
+                        expires_ms: uint(value, "expires_ms")?.unwrap_or(0),
+                    }),
+                    (None, None) => Ok(Reply::Cancelled),
+                }
+            }
+            Some("error") => Err(text(value, "error")?
+                .unwrap_or("unspecified error")
+                .to_string()),
+            _ => return Err("response has no valid 'status'".into()),
+        };
+        Ok(Self {
+            id: uint(value, "id")?.unwrap_or(0),
+            healthy: text(value, "health")? != Some("failed"),
+            result,
+        })
+    }
+
+    /// Overwrites the data, once it has been written or printed.
+    pub fn zeroize(&mut self) {
+        if let Ok(Reply::Served(served)) = &mut self.result {
+            served.data.zeroize();
+        }
+    }
+}
+
+/// Encodes and writes `value`, zeroizing both afterwards.
+pub fn write<W: Write>(w: &mut W, mut value: Value) -> Result<(), Error> {
+    let mut buf = Vec::new();
+    cbor::encode(&value, &mut buf);
+    value.zeroize();
+    let written = w.write_all(&buf).and_then(|()| w.flush());
+    cpurng::zeroize_vec(&mut buf);
+    Ok(written?)
+}
+
+/// Reads the next request item, or `None` at the end of the stream.
+pub fn read_request<R: Read>(r: &mut R) -> Result<Option<Value>, Error> {
+    cbor::read(r, MAX_REQUEST_STRING)
+}
+
+/// Reads the next response, whose data may be up to `max_len` bytes once
+/// encoded.
+pub fn read_response<R: Read>(r: &mut R, max_len: usize) -> Result<Option<Response>, Error> {
+    let Some(mut value) = cbor::read(r, max_len)? else {
+        return Ok(None);
+    };
+    let response = Response::from_value(&value);
+    value.zeroize();
+    response.map(Some).map_err(Error::InvalidArgs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `bytes` in `format`, as the server would send them.
+    fn encode(bytes: &[u8], format: &OutputFormat) -> Data {
+        let collect = |_| {
+            Ok(EntropyResult {
+                bytes: bytes.to_vec(),
+                source: "test".into(),
+            })
+        };
+        Data::draw(bytes.len(), format, collect).unwrap().1
+    }
+
+    #[test]
+    fn test_request_round_trip() {
+        let request = Request {
+            id: 7,
+            op: Op::Get,
+            bytes: 32,
+            format: OutputFormat::HexUpper,
+            deadline_ms: Some(500),
+            token: Some("0123456789abcdef".into()),
+        };
+        let parsed = Request::from_value(&request.to_value()).unwrap();
+        assert_eq!(parsed.id, 7);
+        assert_eq!(parsed.op, Op::Get);
+        assert_eq!(parsed.bytes, 32);
+        assert!(matches!(parsed.format, OutputFormat::HexUpper));
+        assert_eq!(parsed.deadline_ms, Some(500));
+        assert_eq!(parsed.token.as_deref(), Some("0123456789abcdef"));
+
+        let minimal = Value::map([("bytes", Value::Uint(16)), ("extra", Value::Null)]);
+        let parsed = Request::from_value(&minimal).unwrap();
+        assert_eq!((parsed.id, parsed.bytes), (0, 16));
+        assert!(matches!(parsed.format, OutputFormat::Raw));
+
+        for op in [
+            Op::Reserve { hold_ms: Some(250) },
+            Op::Reserve { hold_ms: None },
+            Op::Fetch(9),
+            Op::Cancel(9),
+        ] {
+            let request = Request {
+                op,
+                ..request.clone()
+            };
+            let parsed = Request::from_value(&request.to_value()).unwrap();
+            assert_eq!(parsed.op, op);
+            let bytes = if matches!(op, Op::Reserve { .. }) {
+                32
+            } else {
+                0
+            };
+            assert_eq!(parsed.bytes, bytes);
+        }
+    }
+
+    #[test]
+    fn test_request_errors() {
+        for (value, expected) in [
+            (Value::Uint(1), "must be a map"),
+            (Value::map([("id", Value::Uint(1))]), "no 'bytes'"),
+            (
+                Value::map([("bytes", Value::Text("8".into()))]),
+                "unsigned integer",
+            ),
+            (
+                Value::map([
+                    ("bytes", Value::Uint(8)),
+                    ("format", Value::Text("pdf".into())),
+                ]),
+                "unknown format",
+            ),
+            (
+                Value::map([("v", Value::Uint(2)), ("bytes", Value::Uint(8))]),
+                "version 2",
+            ),
+            (
+                Value::map([("op", Value::Text("fetch".into()))]),
+                "no 'reservation'",
+            ),
+            (
+                Value::map([("op", Value::Text("renew".into()))]),
+                "unknown op",
+            ),
+        ] {
+            let err = Request::from_value(&value).unwrap_err();
+            assert!(err.contains(expected), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_response_round_trip() {
+        for response in [
+            Response {
+                id: 3,
+                healthy: true,
+                result: Ok(Reply::Served(Served {
+                    data: encode(&[0xde, 0xad], &OutputFormat::Hex),
+                    source: "rdseed".into(),
+                })),
+            },
+            Response {
+                id: 4,
+                healthy: true,
+                result: Ok(Reply::Served(Served {
+                    data: encode(&[0xde, 0xad], &OutputFormat::Raw),
+                    source: "hwrng".into(),
+                })),
+            },
+            Response {
+                id: 6,
+                healthy: true,
+                result: Ok(Reply::Reserved {
+                    reservation: 2,
+                    expires_ms: 30000,
+                }),
+            },
+            Response {
+                id: 7,
+                healthy: true,
+                result: Ok(Reply::Cancelled),
+            },
+            Response {
+                id: 5,
+                healthy: false,
+                result: Err("FIPS mode: output disabled".into()),
+            },
+        ] {
+            let mut buf = Vec::new();
+            write(&mut buf, response.to_value()).unwrap();
+            let parsed = read_response(&mut &buf[..], 1024).unwrap().unwrap();
+            assert_eq!(parsed, response);
+        }
+        assert_eq!(
+            encode(&[0xde, 0xad], &OutputFormat::Hex),
+            Data::Text("dead\n".into())
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! `[worker_pool]`: generators keyed from the entropy pipeline before the
+//! first request, so `serve-socket` and `serve-grpc` answer with one
+//! keystream fill instead of a pipeline run. Each request takes an idle
+//! generator, and a background thread rekeys them in turn on schedule,
+//! drawing the new entropy before it takes the generator's lock. Requests
+//! never rekey: a generator past [`RESEED_BYTES`] is only marked for the
+//! thread to rekey next.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::entropy::EntropyResult;
+use crate::error::Error;
+use crate::signals;
+use crate::stream::KeyedStream;
+
+/// Output after which a generator is rekeyed next rather than waiting for
+/// its turn, as `stream --keyed` rekeys by default.
+const RESEED_BYTES: u64 = 256 << 20;
+/// Output after which a generator refuses requests until it is rekeyed,
+/// should the rekey thread fall this far behind.
+const MAX_KEY_BYTES: u64 = 16 * RESEED_BYTES;
+/// Longest the rekey thread sleeps before re-checking shutdown.
+const IDLE_WAIT: Duration = Duration::from_millis(250);
+/// Back-off after the entropy pipeline fails during a rekey.
+const ERROR_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Point-in-time pool counters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WorkerPoolStats {
+    pub requests: u64,
+    pub reseeds: u64,
+    pub failed_reseeds: u64,
+}
+
+pub struct WorkerPool {
+    workers: Vec<Mutex<KeyedStream>>,
+    /// Generators past `rekey_bytes`, for the rekey thread to take first.
+    rekey_wanted: Vec<AtomicBool>,
+    rekey_bytes: u64,
+    /// Where the next request starts looking for an idle generator.
+    next: AtomicUsize,
+    interval: Duration,
+    requests: AtomicU64,
+    reseeds: AtomicU64,
+    failed_reseeds: AtomicU64,
+}
+
+impl WorkerPool {
+    fn new(workers: Vec<KeyedStream>, interval: Duration) -> Self {
+        Self {
+            rekey_wanted: workers.iter().map(|_| AtomicBool::new(false)).collect(),
+            workers: workers.into_iter().map(Mutex::new).collect(),
+            rekey_bytes: RESEED_BYTES,
+            next: AtomicUsize::new(0),
+            interval,
+            requests: AtomicU64::new(0),
+            reseeds: AtomicU64::new(0),
+            failed_reseeds: AtomicU64::new(0),
+        }
+    }
+
+    fn lock(&self, i: usize) -> MutexGuard<'_, KeyedStream> {
+        self.workers[i].lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Generates `count` bytes on the first idle generator, or waits for
+    /// one if all are busy. Never runs the pipeline: a generator due for a
+    /// rekey is marked for the rekey thread.
+    pub fn fill(&self, count: usize) -> Result<EntropyResult, Error> {
+        let n = self.workers.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let (i, mut worker) = (0..n)
+            .map(|k| (start + k) % n)
+            .find_map(|i| Some((i, self.workers[i].try_lock().ok()?)))
+            .unwrap_or_else(|| (start % n, self.lock(start % n)));
+        let mut bytes = vec![0u8; count];
+        worker.fill_current(&mut bytes)?;
+        if worker.since_rekey() >= self.rekey_bytes {
+            self.rekey_wanted[i].store(true, Ordering::Relaxed);
+        }
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        Ok(EntropyResult {
+            bytes,
+            source: format!("{} → worker pool", worker.source()),
+        })
+    }
+
+    pub fn stats(&self) -> WorkerPoolStats {
+        WorkerPoolStats {
+            requests: self.requests.load(Ordering::Relaxed),
+            reseeds: self.reseeds.load(Ordering::Relaxed),
+            failed_reseeds: self.failed_reseeds.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Logs the totals when a server shuts down.
+    pub fn log_totals(&self) {
+        let stats = self.stats();
+        log::info!(
+            "worker pool totals: requests={} rekeys={} failed rekeys={}",
+            stats.requests,
+            stats.reseeds,
+            stats.failed_reseeds
+        );
+    }
+
+    /// Rekeys each generator every `interval`, spread evenly so they
+    /// don't all draw from the pipeline at once, and any generator a
+    /// request marked as past [`RESEED_BYTES`] ahead of its turn.
+    fn reseed_loop(&self, config: &Config) {
+        let n = self.workers.len() as u32;
+        let start = Instant::now();
+        let mut due: Vec<Instant> = (1..=n).map(|i| start + self.interval * i / n).collect();
+
+        while !signals::shutdown_requested() {
+            let wanted = self
+                .rekey_wanted
+                .iter()
+                .position(|w| w.load(Ordering::Relaxed));
+            let (i, &next) = due
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, &d)| d)
+                .expect("pool has workers");
+            let now = Instant::now();
+            let i = match wanted {
+                Some(i) => i,
+                None if next > now => {
+                    thread::sleep((next - now).min(IDLE_WAIT));
+                    continue;
+                }
+                None => i,
+            };
+            match KeyedStream::draw(config) {
+                Ok(seed) => {
+                    self.lock(i).rekey_from(seed);
+                    self.rekey_wanted[i].store(false, Ordering::Relaxed);
+                    self.reseeds.fetch_add(1, Ordering::Relaxed);
+                    due[i] = Instant::now() + self.interval;
+                }
+                Err(e) => {
+                    log::warn!("worker pool rekey failed: {}", e);
+                    self.failed_reseeds.fetch_add(1, Ordering::Relaxed);
+                    thread::sleep(ERROR_BACKOFF);
+                }
+            }
+        }
+    }
+}
+
+/// Keys every generator and starts the rekey thread, or returns `None` if
+/// `[worker_pool]` is disabled. The thread exits once shutdown is
+/// requested.
+pub fn spawn(config: &Config) -> Result<Option<Arc<WorkerPool>>, Error> {
+    if !config.worker_pool.is_enabled() {
+        return Ok(None);
+    }
+    let started = Instant::now();
+    let workers = (0..config.worker_pool.size)
+        .map(|_| {
+            let seed = KeyedStream::draw(config)?;
+            Ok(KeyedStream::from_seed(config, MAX_KEY_BYTES, seed))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let interval = Duration::from_secs(config.worker_pool.reseed_secs);
+    let pool = Arc::new(WorkerPool::new(workers, interval));
+    let worker = Arc::clone(&pool);
+    let worker_config = config.clone();
+    thread::Builder::new()
+        .name("worker-pool".into())
+        .spawn(move || worker.reseed_loop(&worker_config))?;
+    log::info!(
+        "worker pool: {} generators keyed in {}ms, each rekeyed every {}s",
+        config.worker_pool.size,
+        started.elapsed().as_millis(),
+        config.worker_pool.reseed_secs
+    );
+    Ok(Some(pool))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(size: u8) -> WorkerPool {
+        pool_with_limit(size, MAX_KEY_BYTES)
+    }
+
+    fn pool_with_limit(size: u8, max_key_bytes: u64) -> WorkerPool {
+        let workers = (0..size)
+            .map(|i| {
+                let seed = EntropyResult {
+                    bytes: vec![i; 32],
+                    source: format!("test-{}", i),
+                };
+                KeyedStream::from_seed(&Config::default(), max_key_bytes, seed)
+            })
+            .collect();
+        WorkerPool::new(workers, Duration::from_secs(60))
+    }
+
+    #[test]
+    fn test_fill_skips_busy_workers() {
+        let p = pool(2);
+        let busy = p.lock(0);
+        let a = p.fill(16).unwrap();
+        let b = p.fill(16).unwrap();
+        assert_eq!(a.source, "test-1 → worker pool");
+        assert_eq!(b.source, "test-1 → worker pool");
+        assert_ne!(a.bytes, b.bytes);
+        drop(busy);
+
+        let c = p.fill(16).unwrap();
+        assert_eq!(c.source, "test-0 → worker pool");
+        assert_eq!(p.stats().requests, 3);
+    }
+
+    #[test]
+    fn test_fill_marks_spent_workers_instead_of_rekeying() {
+        let mut p = pool_with_limit(1, 64);
+        p.rekey_bytes = 32;
+        p.fill(24).unwrap();
+        assert!(!p.rekey_wanted[0].load(Ordering::Relaxed));
+        p.fill(24).unwrap();
+        assert!(p.rekey_wanted[0].load(Ordering::Relaxed));
+
+        // Past the hard limit requests fail rather than run the pipeline
+        assert!(p.fill(24).is_err());
+        assert_eq!(p.lock(0).reseeds(), 0);
+    }
+}